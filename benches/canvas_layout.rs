@@ -0,0 +1,69 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Compare `Layout::Linear` and `Layout::Tiled` canvases on block shaped access
+//! patterns, the way a triangle fill or a tiled raytracer walks the image.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use softrender::canvas::{Canvas, Color, Layout, TILE_SIZE};
+
+const WIDTH: usize = 1024;
+const HEIGHT: usize = 1024;
+
+/// Visit the canvas in 8x8 blocks, reading and writing each pixel.
+fn block_walk(canvas: &mut Canvas) {
+    for by in (0..canvas.height()).step_by(TILE_SIZE) {
+        for bx in (0..canvas.width()).step_by(TILE_SIZE) {
+            for y in by..(by + TILE_SIZE).min(canvas.height()) {
+                for x in bx..(bx + TILE_SIZE).min(canvas.width()) {
+                    let color = canvas.get(x, y);
+                    canvas.set(x, y, color + Color::new(0.001, 0.0, 0.0));
+                }
+            }
+        }
+    }
+}
+
+/// Visit a column strip of the canvas, the worst case for a linear layout.
+fn column_walk(canvas: &mut Canvas) {
+    for x in 0..canvas.width() {
+        for y in 0..canvas.height() {
+            let color = canvas.get(x, y);
+            canvas.set(x, y, color + Color::new(0.001, 0.0, 0.0));
+        }
+    }
+}
+
+fn bench_layouts(c: &mut Criterion) {
+    let mut group = c.benchmark_group("canvas_layout");
+    for &layout in &[Layout::Linear, Layout::Tiled] {
+        let name = format!("{:?}", layout);
+        let mut canvas = Canvas::with_layout(WIDTH, HEIGHT, layout);
+        group.bench_with_input(BenchmarkId::new("block_walk", &name), &layout, |b, _| {
+            b.iter(|| block_walk(black_box(&mut canvas)))
+        });
+        group.bench_with_input(BenchmarkId::new("column_walk", &name), &layout, |b, _| {
+            b.iter(|| column_walk(black_box(&mut canvas)))
+        });
+        group.bench_with_input(BenchmarkId::new("to_linear", &name), &layout, |b, _| {
+            b.iter(|| black_box(canvas.to_layout(Layout::Linear)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_layouts);
+criterion_main!(benches);
@@ -0,0 +1,156 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+//! Harness shared by the examples.
+//!
+//! Every example renders into a `Canvas` once per frame. With the `minifb` feature the
+//! frames are shown in a window until it is closed or Escape is pressed. Passing
+//! `--output <file>`, or building without `minifb`, renders `--frames <count>` frames
+//! offline at 60 frames per second and saves the last one as a BMP, PNG or PPM image
+//! depending on the file's extension.
+
+// Not every example uses every part of the harness
+#![allow(dead_code)]
+
+use softrender::canvas::{Canvas, BMP, PNG, PPM};
+use softrender::game_loop::{FrameTime, GameLoop};
+use softrender::input::InputState;
+use softrender::Result;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+///
+/// Command line options of an example and the loop driving it
+///
+pub struct Example {
+    title: String,
+    width: usize,
+    height: usize,
+    frames: Option<u64>,
+    default_frames: u64,
+    output: Option<PathBuf>,
+    args: Vec<String>,
+}
+
+impl Example {
+    /// Create new Example rendering `width` by `height` frames, reading the options from
+    /// the command line
+    pub fn new(title: &str, width: usize, height: usize) -> Example {
+        let mut example = Example {
+            title: title.to_string(),
+            width,
+            height,
+            frames: None,
+            default_frames: 1,
+            output: None,
+            args: Vec::new(),
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--output" | "-o" => example.output = args.next().map(PathBuf::from),
+                "--frames" | "-n" => example.frames = args.next().and_then(|count| count.parse().ok()),
+                _ => example.args.push(arg),
+            }
+        }
+        example
+    }
+    /// Set the number of frames rendered offline when `--frames` is not given
+    pub fn frames(mut self, count: u64) -> Self {
+        self.default_frames = count;
+        self
+    }
+    /// Command line arguments other than the harness options
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+    /// Render frames with `render` until the window closes or all offline frames are done
+    pub fn run<F: FnMut(&mut Canvas, &FrameTime, &InputState)>(self, render: F) -> Result<()> {
+        match self.output.clone() {
+            Some(path) => self.run_offline(&path, render),
+            None => self.run_window(render),
+        }
+    }
+
+    fn run_offline<F: FnMut(&mut Canvas, &FrameTime, &InputState)>(&self, path: &Path, mut render: F) -> Result<()> {
+        let mut canvas = Canvas::new(self.width, self.height);
+        let input = InputState::new();
+        let mut game_loop = GameLoop::new(60.0);
+        for _ in 0..self.frames.unwrap_or(self.default_frames).max(1) {
+            let time = game_loop.advance(Duration::from_secs_f32(1.0 / 60.0));
+            render(&mut canvas, &time, &input);
+        }
+        save(&canvas, path)?;
+        println!("{}: saved {}", self.title, path.display());
+        Ok(())
+    }
+
+    #[cfg(feature = "minifb")]
+    fn run_window<F: FnMut(&mut Canvas, &FrameTime, &InputState)>(&self, mut render: F) -> Result<()> {
+        use softrender::input::Key;
+        use softrender::presenter::{MinifbPresenter, Presenter, ResizeHandler, ResizePolicy};
+        use softrender::ui::{Ui, UiInput};
+
+        let title = format!("{} - ESC to exit", self.title);
+        let mut presenter = MinifbPresenter::new(&title, self.width, self.height)?;
+        let mut canvas = Canvas::new(self.width, self.height);
+        let mut resize = ResizeHandler::new(ResizePolicy::Reallocate, presenter.size());
+        let mut input = InputState::new();
+        let mut ui = Ui::new();
+        let mut game_loop = GameLoop::new(60.0);
+        game_loop.set_fps_limit(Some(60.0));
+        while presenter.is_open() && !input.is_key_down(Key::Escape) && !input.close_requested() {
+            input.begin_frame();
+            for event in presenter.poll_events() {
+                input.handle(&event);
+            }
+            resize.handle(&input, &mut canvas, None);
+            let time = game_loop.begin_frame();
+            ui.record_frame_time(time.delta);
+            render(&mut canvas, &time, &input);
+            ui.frame(&mut canvas, UiInput::from(&input)).fps_graph();
+            presenter.present(&canvas)?;
+        }
+        Ok(())
+    }
+
+    /// Without a window the frames are always rendered offline
+    #[cfg(not(feature = "minifb"))]
+    fn run_window<F: FnMut(&mut Canvas, &FrameTime, &InputState)>(&self, render: F) -> Result<()> {
+        let path = PathBuf::from(format!("{}.png", self.title.to_lowercase().replace(' ', "_")));
+        self.run_offline(&path, render)
+    }
+}
+
+/// Save `canvas` to `path` in the format named by its extension
+pub fn save(canvas: &Canvas, path: &Path) -> Result<()> {
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("");
+    let extension = extension.to_ascii_lowercase();
+    if !["bmp", "png", "ppm"].contains(&extension.as_str()) {
+        let message = format!("unknown image format {:?}, use .bmp, .png or .ppm", extension);
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+    }
+    let mut output = BufWriter::new(File::create(path)?);
+    match extension.as_str() {
+        "bmp" => canvas.to_bmp(&mut output)?,
+        "png" => canvas.to_png(&mut output)?,
+        _ => canvas.to_ppm(&mut output)?,
+    }
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+//! Cornell box path traced progressively, adding one sample per pixel every frame.
+//!
+//! The noise fades as samples accumulate, so offline renders default to more frames.
+
+mod common;
+
+use common::Example;
+use softrender::canvas::Color;
+use softrender::math::{Mat4f, Vec3f};
+use softrender::model::{cube, Material, Mesh, Scene, SphereLight, Triangle};
+use softrender::shader::Camera;
+use softrender::{Accumulator, PathTracer, SobolSampler};
+
+/// Quad with corners `a`, `b`, `c` and `d` in order around its edge
+fn quad(a: Vec3f, b: Vec3f, c: Vec3f, d: Vec3f) -> Mesh {
+    Mesh::new(vec![Triangle::new(a, b, c), Triangle::new(c, d, a)])
+}
+
+fn diffuse(surface: Color) -> Material {
+    let mut material = Material::new();
+    material.surface = surface;
+    material
+}
+
+/// Box two units across centered on the origin, open towards positive Z
+fn cornell_box() -> Scene {
+    let corner = |x: f32, y: f32, z: f32| Vec3f::new(x, y, z);
+    let white = Color::new(0.73, 0.73, 0.73);
+    Scene::builder()
+        // Floor, ceiling and back wall
+        .mesh(quad(corner(-1.0, -1.0, 1.0), corner(1.0, -1.0, 1.0), corner(1.0, -1.0, -1.0), corner(-1.0, -1.0, -1.0)))
+        .material(diffuse(white))
+        .mesh(quad(corner(-1.0, 1.0, -1.0), corner(1.0, 1.0, -1.0), corner(1.0, 1.0, 1.0), corner(-1.0, 1.0, 1.0)))
+        .material(diffuse(white))
+        .mesh(quad(corner(-1.0, -1.0, -1.0), corner(1.0, -1.0, -1.0), corner(1.0, 1.0, -1.0), corner(-1.0, 1.0, -1.0)))
+        .material(diffuse(white))
+        // Red left and green right wall
+        .mesh(quad(corner(-1.0, -1.0, 1.0), corner(-1.0, -1.0, -1.0), corner(-1.0, 1.0, -1.0), corner(-1.0, 1.0, 1.0)))
+        .material(diffuse(Color::new(0.65, 0.05, 0.05)))
+        .mesh(quad(corner(1.0, -1.0, -1.0), corner(1.0, -1.0, 1.0), corner(1.0, 1.0, 1.0), corner(1.0, 1.0, -1.0)))
+        .material(diffuse(Color::new(0.12, 0.45, 0.15)))
+        // Tall and short box sharing the white material
+        .mesh(cube(0.7))
+        .transform(Mat4f::translation(Vec3f::new(-0.4, -0.65, -0.4)))
+        .material(diffuse(white))
+        .mesh(cube(0.5))
+        .transform(Mat4f::translation(Vec3f::new(0.4, -0.75, 0.3)))
+        .material(diffuse(white))
+        .area_light(SphereLight::new(Vec3f::new(0.0, 0.8, 0.0), 0.15, 25.0))
+        .build()
+}
+
+fn main() -> softrender::Result<()> {
+    let scene = cornell_box();
+    let tracer = PathTracer::new(&scene);
+    let mut camera = Camera::new(Vec3f::new(0.0, 0.0, 3.6), -Vec3f::UNIT_Z, Vec3f::UNIT_Y);
+    camera.fov = 40.0;
+    let mut sampler = SobolSampler::new(0);
+    let mut accumulator = Accumulator::new(0, 0);
+    Example::new("Cornell Box", 400, 400).frames(64).run(|canvas, _, _| {
+        if (accumulator.width(), accumulator.height()) != (canvas.width(), canvas.height()) {
+            accumulator = Accumulator::new(canvas.width(), canvas.height());
+            camera.set_viewport(canvas.width(), canvas.height());
+        }
+        tracer.render_pass(&scene, &camera, &mut accumulator, &mut sampler);
+        *canvas = accumulator.resolve();
+    })
+}
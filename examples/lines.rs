@@ -0,0 +1,56 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+//! Line drawn by the rasterizer spinning around the center of the screen.
+//!
+//! Up and Down change the speed of the spin.
+
+mod common;
+
+use common::Example;
+use softrender::canvas::Color;
+use softrender::input::Key;
+use softrender::math::Vec2f;
+use softrender::Rasterizer;
+
+fn main() -> softrender::Result<()> {
+    let mut speed = 90.0;
+    let (mut previous, mut angle) = (0.0f32, 0.0f32);
+    Example::new("Lines", 640, 480).frames(30).run(|canvas, time, input| {
+        if input.is_key_down(Key::Up) {
+            speed += 180.0 * time.delta;
+        }
+        if input.is_key_down(Key::Down) {
+            speed -= 180.0 * time.delta;
+        }
+        for _ in 0..time.updates {
+            previous = angle;
+            angle += speed * time.fixed_delta;
+        }
+        let radians = (previous + (angle - previous) * time.alpha).to_radians();
+
+        Rasterizer::clear(canvas, Color::black());
+        let center = Vec2f::from_parts(canvas.width() as f32 / 2.0, canvas.height() as f32 / 2.0);
+        let radius = center.x.min(center.y) * 0.8;
+        for spoke in 0..12 {
+            let radians = radians + spoke as f32 * std::f32::consts::PI / 6.0;
+            let tip = center + Vec2f::from_parts(radians.cos(), radians.sin()) * radius;
+            let shade = 1.0 - spoke as f32 / 12.0;
+            Rasterizer::draw_line_f(canvas, center, tip, Color::new(shade, shade, 1.0));
+        }
+    })
+}
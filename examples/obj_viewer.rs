@@ -0,0 +1,124 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+//! Viewer for Wavefront OBJ meshes turning on a turntable.
+//!
+//! Pass the path of an `.obj` file to view it, without one a cube is shown. Only vertex
+//! positions, faces and smoothing groups are read; faces with more than three corners
+//! are split into a fan of triangles and smooth normals are computed for the mesh,
+//! within the smoothing groups if the file has any.
+//!
+//! ```text
+//! cargo run --example obj_viewer -- model.obj
+//! ```
+
+mod common;
+
+use common::Example;
+use softrender::canvas::{Aov, AovBuffers, Color};
+use softrender::math::{Sh9, Vec3f};
+use softrender::model::{cube, Material, Mesh, PointLight, Triangle};
+use softrender::shader::{CameraAnimation, Turntable};
+use softrender::{Error, Rasterizer, Result};
+use std::fs;
+
+/// Faces meeting at a sharper angle than this in degrees keep a hard edge
+const CREASE_ANGLE: f32 = 40.0;
+
+/// Parse the positions, faces and smoothing groups of an OBJ file
+fn parse_obj(source: &str) -> Result<Mesh> {
+    let mut positions = Vec::new();
+    let mut triangles = Vec::new();
+    // Smoothing group of each triangle, faces before any `s` line are not smoothed
+    let mut groups = Vec::new();
+    let mut group = 0;
+    let mut has_groups = false;
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: &str| Error::Decode(format!("line {}: {}", number + 1, message));
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some("v") => {
+                let mut coordinate = || -> Result<f32> {
+                    let part = parts.next().ok_or_else(|| error("vertex needs three coordinates"))?;
+                    part.parse().map_err(|_| error("bad vertex coordinate"))
+                };
+                positions.push(Vec3f::new(coordinate()?, coordinate()?, coordinate()?));
+            }
+            Some("f") => {
+                let mut corners = Vec::new();
+                for part in parts {
+                    // Corners are `v`, `v/vt`, `v//vn` or `v/vt/vn`, only `v` is used
+                    let index: i64 = part.split('/').next().unwrap_or("").parse().map_err(|_| error("bad face index"))?;
+                    // Negative indices count back from the last vertex read
+                    let index = if index < 0 { positions.len() as i64 + index } else { index - 1 };
+                    let position = positions.get(index as usize).ok_or_else(|| error("face index out of range"))?;
+                    corners.push(*position);
+                }
+                if corners.len() < 3 {
+                    return Err(error("face needs at least three corners"));
+                }
+                for corner in 1..corners.len() - 1 {
+                    triangles.push(Triangle::new(corners[0], corners[corner], corners[corner + 1]));
+                    groups.push(group);
+                }
+            }
+            Some("s") => {
+                group = match parts.next() {
+                    Some("off") => 0,
+                    Some(part) => part.parse().map_err(|_| error("bad smoothing group"))?,
+                    None => return Err(error("smoothing group needs a number or off")),
+                };
+                has_groups = true;
+            }
+            _ => {}
+        }
+    }
+    if triangles.is_empty() {
+        return Err(Error::Decode("no faces found".to_string()));
+    }
+    let mut mesh = Mesh::new(triangles);
+    if has_groups {
+        mesh.set_smoothing_groups(groups);
+    }
+    mesh.compute_normals_with_crease(CREASE_ANGLE.to_radians());
+    Ok(mesh)
+}
+
+fn main() -> Result<()> {
+    let example = Example::new("OBJ Viewer", 640, 480);
+    let mesh = match example.args().first() {
+        Some(path) => parse_obj(&fs::read_to_string(path)?)?,
+        None => cube(2.0),
+    };
+    let mut material = Material::new();
+    material.surface = Color::new(0.8, 0.8, 0.8);
+
+    let turntable = Turntable::around(&mesh.bounds());
+    let radius = turntable.distance;
+    let key = PointLight::new(turntable.center + Vec3f::new(0.6, 0.8, 0.5) * radius, 0.8 * radius * radius);
+    let ambient = Sh9::project_equirect(8, 4, |_, _| Vec3f::new(0.15, 0.15, 0.18));
+    example.run(|canvas, time, _| {
+        let mut camera = turntable.camera_at(time.elapsed / 12.0);
+        camera.set_viewport(canvas.width(), canvas.height());
+        let mut gbuffer = AovBuffers::new(canvas.width(), canvas.height(), &[Aov::Albedo, Aov::Normal, Aov::Depth]);
+        Rasterizer::draw_gbuffer(&mut gbuffer, &mesh, &material, &[], &camera);
+
+        Rasterizer::clear(canvas, Color::new(0.2, 0.2, 0.22));
+        Rasterizer::accumulate_ambient(canvas, &gbuffer, &ambient);
+        Rasterizer::accumulate_light(canvas, &gbuffer, &camera, &key);
+    })
+}
@@ -0,0 +1,110 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+//! Reflective spheres raytraced tile by tile across threads with `Raytracer::render_tiles`.
+//!
+//! The spheres are intersected analytically and lit by a single light with hard
+//! shadows, while the camera slowly circles them.
+
+mod common;
+
+use common::Example;
+use softrender::canvas::Color;
+use softrender::math::{Ray, Vec3f};
+use softrender::model::{Material, Sphere};
+use softrender::shader::Camera;
+use softrender::{Raytracer, RenderSettings};
+
+/// Number of times a ray is reflected before giving up
+const MAX_DEPTH: u32 = 4;
+
+fn sphere(center: Vec3f, radius: f32, surface: Color, reflectivity: f32) -> Sphere {
+    let mut material = Material::new();
+    material.surface = surface;
+    material.reflectivity = reflectivity;
+    Sphere { center, radius, material }
+}
+
+/// Distance along `ray` to the nearer side of `sphere` beyond `min`
+fn intersect(sphere: &Sphere, ray: &Ray, min: f32) -> Option<f32> {
+    let offset = ray.origin - sphere.center;
+    let a = ray.direction.dot(ray.direction);
+    let b = offset.dot(ray.direction);
+    let c = offset.dot(offset) - sphere.radius * sphere.radius;
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    [(-b - root) / a, (-b + root) / a].iter().copied().find(|&t| t > min)
+}
+
+/// Nearest sphere hit by `ray` and the distance to it
+fn nearest<'a>(spheres: &'a [Sphere], ray: &Ray) -> Option<(&'a Sphere, f32)> {
+    spheres
+        .iter()
+        .filter_map(|sphere| intersect(sphere, ray, 1e-3).map(|t| (sphere, t)))
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+}
+
+fn trace(spheres: &[Sphere], light: Vec3f, ray: &Ray, depth: u32) -> Color {
+    let (sphere, t) = match nearest(spheres, ray) {
+        Some(hit) => hit,
+        None => {
+            // Sky fading from white at the horizon to blue overhead
+            let up = ray.direction.normalize().y.max(0.0);
+            return Color::new(1.0 - 0.5 * up, 1.0 - 0.3 * up, 1.0);
+        }
+    };
+    let position = ray.at(t);
+    let normal = (position - sphere.center).normalize();
+    let to_light = light - position;
+    let shadow = Ray::new(position, to_light);
+    let lit = nearest(spheres, &shadow).is_none_or(|(_, t)| t > 1.0);
+    let diffuse = if lit { normal.dot(to_light.normalize()).max(0.0) } else { 0.0 };
+    let material = &sphere.material;
+    let mut color = material.surface * (0.1 + 0.9 * diffuse);
+    if material.reflectivity > 0.0 && depth < MAX_DEPTH {
+        let direction = ray.direction.normalize();
+        let reflected = direction - normal * (2.0 * direction.dot(normal));
+        let reflection = trace(spheres, light, &Ray::new(position, reflected), depth + 1);
+        color = color * (1.0 - material.reflectivity) + reflection * material.reflectivity;
+    }
+    color
+}
+
+fn main() -> softrender::Result<()> {
+    let spheres = vec![
+        sphere(Vec3f::new(0.0, -1000.0, 0.0), 1000.0, Color::new(0.8, 0.8, 0.7), 0.1),
+        sphere(Vec3f::new(0.0, 1.0, 0.0), 1.0, Color::new(0.9, 0.9, 0.9), 0.8),
+        sphere(Vec3f::new(-2.2, 0.7, 0.8), 0.7, Color::new(0.9, 0.2, 0.2), 0.2),
+        sphere(Vec3f::new(2.0, 0.5, 1.2), 0.5, Color::new(0.2, 0.4, 0.9), 0.3),
+        sphere(Vec3f::new(0.8, 0.35, 2.2), 0.35, Color::new(0.2, 0.8, 0.3), 0.0),
+    ];
+    let light = Vec3f::new(-4.0, 8.0, 6.0);
+    let settings = RenderSettings::default();
+    Example::new("Spheres", 640, 480).run(|canvas, time, _| {
+        let angle = (time.elapsed * 10.0).to_radians();
+        let position = Vec3f::new(7.0 * angle.sin(), 2.5, 7.0 * angle.cos());
+        let target = Vec3f::new(0.0, 0.8, 0.0);
+        let mut camera = Camera::new(position, (target - position).normalize(), Vec3f::UNIT_Y);
+        camera.set_viewport(canvas.width(), canvas.height());
+        let (width, height) = (canvas.width(), canvas.height());
+        let shade = |x, y| trace(&spheres, light, &camera.ray(x, y, width, height), 0);
+        Raytracer::render_tiles(canvas, &settings, shade, |_, _| {});
+    })
+}
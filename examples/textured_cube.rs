@@ -0,0 +1,75 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+//! Checkered cube drawn into a G-buffer by the rasterizer and lit by deferred lights.
+//!
+//! The cube has no texture coordinates, so the checker texture is projected onto it
+//! with triplanar mapping. The camera circles the cube once every twelve seconds.
+
+mod common;
+
+use common::Example;
+use softrender::canvas::{Aov, AovBuffers, Canvas, Color, Texture};
+use softrender::math::{Sh9, Vec3f};
+use softrender::model::{cube, Mapping, Material, PointLight, TextureSlot};
+use softrender::shader::{CameraAnimation, Turntable};
+use softrender::Rasterizer;
+
+/// `size` by `size` checker board of `squares` by `squares` squares
+fn checker(size: usize, squares: usize) -> Canvas {
+    let mut canvas = Canvas::new(size, size);
+    let square = size / squares;
+    for y in 0..size {
+        for x in 0..size {
+            let color = if (x / square + y / square).is_multiple_of(2) {
+                Color::new(0.9, 0.9, 0.9)
+            } else {
+                Color::new(0.8, 0.3, 0.1)
+            };
+            canvas.set(x, y, color);
+        }
+    }
+    canvas
+}
+
+fn main() -> softrender::Result<()> {
+    let mesh = cube(2.0);
+    let textures = vec![Texture::new(checker(64, 8))];
+    let mut material = Material::new();
+    let mut slot = TextureSlot::new(0);
+    slot.mapping = Mapping::Triplanar { scale: 0.5, sharpness: 8.0 };
+    material.surface_texture = Some(slot);
+
+    let mut key = PointLight::new(Vec3f::new(4.0, 5.0, 3.0), 40.0);
+    key.color = Color::new(1.0, 0.95, 0.85);
+    let mut fill = PointLight::new(Vec3f::new(-5.0, -1.0, -4.0), 15.0);
+    fill.color = Color::new(0.6, 0.7, 1.0);
+    let lights = [key, fill];
+    let ambient = Sh9::project_equirect(8, 4, |_, _| Vec3f::new(0.1, 0.1, 0.12));
+
+    let turntable = Turntable::around(&mesh.bounds());
+    Example::new("Textured Cube", 640, 480).run(|canvas, time, _| {
+        let mut camera = turntable.camera_at(time.elapsed / 12.0);
+        camera.set_viewport(canvas.width(), canvas.height());
+        let mut gbuffer = AovBuffers::new(canvas.width(), canvas.height(), &[Aov::Albedo, Aov::Normal, Aov::Depth]);
+        Rasterizer::draw_gbuffer(&mut gbuffer, &mesh, &material, &textures, &camera);
+
+        Rasterizer::clear(canvas, Color::new(0.05, 0.05, 0.08));
+        Rasterizer::accumulate_ambient(canvas, &gbuffer, &ambient);
+        Rasterizer::accumulate_lights(canvas, &gbuffer, &camera, &lights);
+    })
+}
@@ -0,0 +1,620 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+//! Keyframe animation of scene nodes with blending and state transitions.
+//!
+//! A `Clip` holds keyframed `Transform`s for some of the nodes of a scene or skeleton and
+//! is sampled into a `Pose`. An `Animator` plays clips through a tiny state machine,
+//! crossfading between them when a transition is triggered, and adds additive layers such
+//! as breathing or recoil on top.
+//!
+//! ```
+//! use softrender::animation::{Animator, Clip, Keyframe, Pose, Transform};
+//! use softrender::math::Vec3f;
+//!
+//! let mut idle = Clip::new("idle", 1.0, true);
+//! idle.add_track(0, vec![Keyframe::new(0.0, Transform::IDENTITY)]);
+//! let mut walk = Clip::new("walk", 1.0, true);
+//! walk.add_track(0, vec![
+//!     Keyframe::new(0.0, Transform::IDENTITY),
+//!     Keyframe::new(1.0, Transform::from_translation(Vec3f::new(0.0, 0.0, -2.0))),
+//! ]);
+//!
+//! let mut animator = Animator::new();
+//! let (idle, walk) = (animator.add_clip(idle), animator.add_clip(walk));
+//! let idle = animator.add_state("idle", idle);
+//! let walk = animator.add_state("walk", walk);
+//! animator.add_transition(idle, walk, "move", 0.25);
+//! animator.trigger("move");
+//! animator.update(0.5);
+//! assert_eq!(animator.state(), "walk");
+//! let pose = animator.pose(&Pose::new(1));
+//! ```
+
+use crate::math::{Mat4f, Vec3f, Vec4f};
+
+/// Unit quaternion rotating by nothing
+const NO_ROTATION: Vec4f = Vec4f::UNIT_W;
+
+/// Translation, rotation and scale of a node relative to its parent
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3f,
+    /// Unit quaternion as (x, y, z, w)
+    pub rotation: Vec4f,
+    pub scale: Vec3f,
+}
+
+impl Transform {
+    /// Transform leaving everything where it is
+    pub const IDENTITY: Transform = Transform {
+        translation: Vec3f::ZERO,
+        rotation: NO_ROTATION,
+        scale: Vec3f::ONE,
+    };
+
+    /// Create new Transform moving by `translation`
+    pub fn from_translation(translation: Vec3f) -> Self {
+        Self {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+    /// Create new Transform rotating `degrees` counter clockwise around `axis`
+    pub fn from_axis_angle(axis: Vec3f, degrees: f32) -> Self {
+        let half = degrees.to_radians() / 2.0;
+        Self {
+            rotation: (axis.normalize() * half.sin()).extend(half.cos()),
+            ..Self::IDENTITY
+        }
+    }
+    /// Rotate `vector` by the rotation of this Transform
+    pub fn rotate(&self, vector: Vec3f) -> Vec3f {
+        let axis = self.rotation.xyz();
+        let twice = Vec3f::cross(axis, vector) * 2.0;
+        vector + twice * self.rotation.w + Vec3f::cross(axis, twice)
+    }
+    /// Interpolate from this Transform at `t` of 0.0 to `other` at 1.0, turning the short
+    /// way round
+    pub fn lerp(&self, other: &Transform, t: f32) -> Transform {
+        Transform {
+            translation: self.translation + (other.translation - self.translation) * t,
+            rotation: nlerp(self.rotation, other.rotation, t),
+            scale: self.scale + (other.scale - self.scale) * t,
+        }
+    }
+    /// Difference from `reference` to this Transform, for use as an additive layer
+    pub fn relative_to(&self, reference: &Transform) -> Transform {
+        Transform {
+            translation: self.translation - reference.translation,
+            rotation: multiply(self.rotation, conjugate(reference.rotation)),
+            scale: self.scale / reference.scale,
+        }
+    }
+    /// Apply the difference `delta`, made with `relative_to`, scaled down by `weight`
+    pub fn add(&self, delta: &Transform, weight: f32) -> Transform {
+        Transform {
+            translation: self.translation + delta.translation * weight,
+            rotation: multiply(nlerp(NO_ROTATION, delta.rotation, weight), self.rotation).normalize(),
+            scale: self.scale * (Vec3f::ONE + (delta.scale - Vec3f::ONE) * weight),
+        }
+    }
+    /// Matrix scaling, then rotating, then translating row vectors
+    pub fn to_mat4(&self) -> Mat4f {
+        let x = self.rotate(Vec3f::UNIT_X) * self.scale.x;
+        let y = self.rotate(Vec3f::UNIT_Y) * self.scale.y;
+        let z = self.rotate(Vec3f::UNIT_Z) * self.scale.z;
+        let t = self.translation;
+        Mat4f::from_rows([
+            [x.x, x.y, x.z, 0.0],
+            [y.x, y.y, y.z, 0.0],
+            [z.x, z.y, z.z, 0.0],
+            [t.x, t.y, t.z, 1.0],
+        ])
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Rotation `b` followed by rotation `a`
+fn multiply(a: Vec4f, b: Vec4f) -> Vec4f {
+    Vec4f::from_parts(
+        a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+        a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+        a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+        a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+    )
+}
+
+fn conjugate(q: Vec4f) -> Vec4f {
+    Vec4f::from_parts(-q.x, -q.y, -q.z, q.w)
+}
+
+/// Normalized linear interpolation between rotations, through the shorter arc
+fn nlerp(a: Vec4f, b: Vec4f, t: f32) -> Vec4f {
+    let b = if a.dot(b) < 0.0 { -b } else { b };
+    (a * (1.0 - t) + b * t).normalize()
+}
+
+/// Transform of a node at a point in time
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Keyframe {
+    /// Seconds from the start of the clip
+    pub time: f32,
+    pub transform: Transform,
+}
+
+impl Keyframe {
+    /// Create new Keyframe
+    pub fn new(time: f32, transform: Transform) -> Self {
+        Self { time, transform }
+    }
+}
+
+/// Keyframes animating a single node
+#[derive(Clone, Debug, PartialEq)]
+pub struct Track {
+    node: usize,
+    keys: Vec<Keyframe>,
+}
+
+impl Track {
+    /// Create new Track animating node `node`, sorting the keyframes by time.
+    ///
+    /// # Panics
+    /// Panics if `keys` is empty.
+    pub fn new(node: usize, mut keys: Vec<Keyframe>) -> Self {
+        assert!(!keys.is_empty(), "track needs at least one keyframe");
+        keys.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+        Self { node, keys }
+    }
+    /// Index of the node animated
+    pub fn node(&self) -> usize {
+        self.node
+    }
+    /// Keyframes in time order
+    pub fn keys(&self) -> &[Keyframe] {
+        &self.keys
+    }
+    /// Transform at `time`, interpolating between keyframes and holding the first and last
+    /// outside them
+    pub fn sample(&self, time: f32) -> Transform {
+        let next = self.keys.iter().position(|key| key.time > time);
+        match next {
+            Some(0) => self.keys[0].transform,
+            Some(index) => {
+                let (a, b) = (&self.keys[index - 1], &self.keys[index]);
+                a.transform.lerp(&b.transform, (time - a.time) / (b.time - a.time))
+            }
+            None => self.keys[self.keys.len() - 1].transform,
+        }
+    }
+}
+
+/// Named set of tracks played together, such as a walk cycle
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clip {
+    pub name: String,
+    /// Length in seconds
+    pub duration: f32,
+    /// Start over after `duration` rather than holding the last frame
+    pub looping: bool,
+    tracks: Vec<Track>,
+}
+
+impl Clip {
+    /// Create new Clip without any tracks
+    pub fn new(name: &str, duration: f32, looping: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            duration,
+            looping,
+            tracks: Vec::new(),
+        }
+    }
+    /// Animate node `node` with `keys`
+    pub fn add_track(&mut self, node: usize, keys: Vec<Keyframe>) {
+        self.tracks.push(Track::new(node, keys));
+    }
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+    /// Time within the clip `time` seconds after it started playing
+    pub fn local_time(&self, time: f32) -> f32 {
+        if self.looping && self.duration > 0.0 {
+            time.rem_euclid(self.duration)
+        } else {
+            time.max(0.0).min(self.duration)
+        }
+    }
+    /// Check if a clip that does not loop has played to its end `time` seconds after it
+    /// started
+    pub fn is_finished(&self, time: f32) -> bool {
+        !self.looping && time >= self.duration
+    }
+    /// Write the transforms at `time` seconds into `pose`, leaving nodes without a track
+    /// or outside the pose alone
+    pub fn sample(&self, time: f32, pose: &mut Pose) {
+        let time = self.local_time(time);
+        for track in &self.tracks {
+            if let Some(transform) = pose.transforms.get_mut(track.node) {
+                *transform = track.sample(time);
+            }
+        }
+    }
+}
+
+/// Transform of every node of a scene or skeleton
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pose {
+    transforms: Vec<Transform>,
+}
+
+impl Pose {
+    /// Create new Pose with `nodes` nodes all at the identity
+    pub fn new(nodes: usize) -> Self {
+        Self {
+            transforms: vec![Transform::IDENTITY; nodes],
+        }
+    }
+    /// Create new Pose from the transform of each node
+    pub fn from_transforms(transforms: Vec<Transform>) -> Self {
+        Self { transforms }
+    }
+    pub fn transforms(&self) -> &[Transform] {
+        &self.transforms
+    }
+    /// Get the Transform of node `node`
+    pub fn get(&self, node: usize) -> Transform {
+        self.transforms[node]
+    }
+    /// Set the Transform of node `node`
+    pub fn set(&mut self, node: usize, transform: Transform) {
+        self.transforms[node] = transform;
+    }
+    /// Mix `weight` of `other` into this Pose
+    pub fn blend(&mut self, other: &Pose, weight: f32) {
+        for (transform, target) in self.transforms.iter_mut().zip(&other.transforms) {
+            *transform = transform.lerp(target, weight);
+        }
+    }
+    /// Add the difference between `additive` and `reference`, scaled by `weight`
+    pub fn add(&mut self, additive: &Pose, reference: &Pose, weight: f32) {
+        let deltas = additive.transforms.iter().zip(&reference.transforms);
+        for (transform, (additive, reference)) in self.transforms.iter_mut().zip(deltas) {
+            *transform = transform.add(&additive.relative_to(reference), weight);
+        }
+    }
+    /// Matrix of each node, for instance transforms or skinning
+    pub fn to_matrices(&self) -> Vec<Mat4f> {
+        self.transforms.iter().map(Transform::to_mat4).collect()
+    }
+}
+
+/// State of an `Animator`, playing a clip
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationState {
+    pub name: String,
+    /// Index of the clip played, from `Animator::add_clip`
+    pub clip: usize,
+    /// Playback speed, 1.0 for real time
+    pub speed: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Transition {
+    /// State the transition leaves, `None` for any state
+    from: Option<usize>,
+    to: usize,
+    trigger: String,
+    duration: f32,
+}
+
+/// Clip played on top of the states, relative to its first frame
+#[derive(Clone, Debug, PartialEq)]
+struct Layer {
+    clip: usize,
+    weight: f32,
+    time: f32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Playback {
+    state: usize,
+    time: f32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Crossfade {
+    from: Playback,
+    elapsed: f32,
+    duration: f32,
+}
+
+///
+/// Plays clips through a state machine with crossfaded transitions and additive layers.
+///
+/// The first state added starts playing. `trigger` follows a transition from the current
+/// state, fading the old state out over the transition's duration while the new one
+/// plays. Triggering during a fade starts a new fade from the current state.
+///
+#[derive(Clone, Debug, Default)]
+pub struct Animator {
+    clips: Vec<Clip>,
+    states: Vec<AnimationState>,
+    transitions: Vec<Transition>,
+    layers: Vec<Layer>,
+    current: Option<Playback>,
+    fade: Option<Crossfade>,
+}
+
+impl Animator {
+    /// Create new Animator without any clips or states
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add a clip, returning its index
+    pub fn add_clip(&mut self, clip: Clip) -> usize {
+        self.clips.push(clip);
+        self.clips.len() - 1
+    }
+    /// Add a state playing clip `clip` at normal speed, returning its index
+    pub fn add_state(&mut self, name: &str, clip: usize) -> usize {
+        self.states.push(AnimationState {
+            name: name.to_string(),
+            clip,
+            speed: 1.0,
+        });
+        let state = self.states.len() - 1;
+        if self.current.is_none() {
+            self.current = Some(Playback { state, time: 0.0 });
+        }
+        state
+    }
+    /// Get state `state` for changing its speed
+    pub fn state_mut(&mut self, state: usize) -> &mut AnimationState {
+        &mut self.states[state]
+    }
+    /// Go from state `from` to state `to` over `duration` seconds when `trigger` is fired
+    pub fn add_transition(&mut self, from: usize, to: usize, trigger: &str, duration: f32) {
+        self.transitions.push(Transition {
+            from: Some(from),
+            to,
+            trigger: trigger.to_string(),
+            duration,
+        });
+    }
+    /// Go from any other state to state `to` over `duration` seconds when `trigger` is fired
+    pub fn add_any_transition(&mut self, to: usize, trigger: &str, duration: f32) {
+        self.transitions.push(Transition {
+            from: None,
+            to,
+            trigger: trigger.to_string(),
+            duration,
+        });
+    }
+    /// Add clip `clip` as an additive layer applied at `weight`, returning its index
+    pub fn add_layer(&mut self, clip: usize, weight: f32) -> usize {
+        self.layers.push(Layer { clip, weight, time: 0.0 });
+        self.layers.len() - 1
+    }
+    /// Change how strongly layer `layer` is applied, 0.0 turns it off
+    pub fn set_layer_weight(&mut self, layer: usize, weight: f32) {
+        self.layers[layer].weight = weight;
+    }
+    /// Follow the transition for `trigger` from the current state, returns false if there is
+    /// none
+    pub fn trigger(&mut self, trigger: &str) -> bool {
+        let current = match self.current {
+            Some(current) => current.state,
+            None => return false,
+        };
+        let transition = self.transitions.iter().find(|transition| {
+            transition.trigger == trigger
+                && transition.from.map_or(transition.to != current, |from| from == current)
+        });
+        match transition.map(|transition| (transition.to, transition.duration)) {
+            Some((to, duration)) => {
+                self.play(to, duration);
+                true
+            }
+            None => false,
+        }
+    }
+    /// Switch to state `state` from the start, crossfading over `fade` seconds
+    pub fn play(&mut self, state: usize, fade: f32) {
+        let from = self.current.replace(Playback { state, time: 0.0 });
+        self.fade = match from {
+            Some(from) if fade > 0.0 => Some(Crossfade {
+                from,
+                elapsed: 0.0,
+                duration: fade,
+            }),
+            _ => None,
+        };
+    }
+    /// Name of the current state, empty before any state is added
+    pub fn state(&self) -> &str {
+        self.current.map_or("", |current| &self.states[current.state].name)
+    }
+    /// Check if a crossfade is in progress
+    pub fn is_fading(&self) -> bool {
+        self.fade.is_some()
+    }
+    /// Advance every playing clip by `delta` seconds
+    pub fn update(&mut self, delta: f32) {
+        if let Some(current) = &mut self.current {
+            current.time += delta * self.states[current.state].speed;
+        }
+        if let Some(fade) = &mut self.fade {
+            fade.from.time += delta * self.states[fade.from.state].speed;
+            fade.elapsed += delta;
+            if fade.elapsed >= fade.duration {
+                self.fade = None;
+            }
+        }
+        for layer in &mut self.layers {
+            layer.time += delta;
+        }
+    }
+    /// Current pose, starting from `rest` for nodes no clip animates
+    pub fn pose(&self, rest: &Pose) -> Pose {
+        let mut pose = rest.clone();
+        if let Some(current) = self.current {
+            self.sample(current, &mut pose);
+            if let Some(fade) = self.fade {
+                let mut previous = rest.clone();
+                self.sample(fade.from, &mut previous);
+                previous.blend(&pose, fade.elapsed / fade.duration);
+                pose = previous;
+            }
+        }
+        for layer in &self.layers {
+            if layer.weight <= 0.0 {
+                continue;
+            }
+            let clip = &self.clips[layer.clip];
+            let (mut additive, mut reference) = (rest.clone(), rest.clone());
+            clip.sample(layer.time, &mut additive);
+            clip.sample(0.0, &mut reference);
+            pose.add(&additive, &reference, layer.weight);
+        }
+        pose
+    }
+    fn sample(&self, playback: Playback, pose: &mut Pose) {
+        let clip = &self.clips[self.states[playback.state].clip];
+        clip.sample(playback.time, pose);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Animator, Clip, Keyframe, Pose, Track, Transform};
+    use crate::math::Vec3f;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn slide(name: &str, to: Vec3f) -> Clip {
+        let mut clip = Clip::new(name, 1.0, false);
+        clip.add_track(
+            0,
+            vec![
+                Keyframe::new(0.0, Transform::IDENTITY),
+                Keyframe::new(1.0, Transform::from_translation(to)),
+            ],
+        );
+        clip
+    }
+
+    #[test]
+    fn test_transform() {
+        let turn = Transform::from_axis_angle(Vec3f::UNIT_Y, 90.0);
+        assert!((turn.rotate(Vec3f::UNIT_X) - -Vec3f::UNIT_Z).magnitude() < 1e-6);
+        let mut transform = Transform::from_translation(Vec3f::new(1.0, 2.0, 3.0));
+        transform.rotation = turn.rotation;
+        transform.scale = Vec3f::new(2.0, 2.0, 2.0);
+        let moved = Vec3f::UNIT_X * transform.to_mat4();
+        assert!((moved - Vec3f::new(1.0, 2.0, 1.0)).magnitude() < 1e-5);
+        // Halfway through a quarter turn is an eighth turn
+        let half = Transform::IDENTITY.lerp(&turn, 0.5);
+        assert!((half.rotation - Transform::from_axis_angle(Vec3f::UNIT_Y, 45.0).rotation).magnitude() < 1e-6);
+        // Adding the difference back gives the original
+        let delta = transform.relative_to(&half);
+        let back = half.add(&delta, 1.0);
+        assert!((back.translation - transform.translation).magnitude() < 1e-5);
+        assert!((back.rotation - transform.rotation).magnitude() < 1e-5);
+        assert!((back.scale - transform.scale).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn test_track() {
+        let track = Track::new(
+            3,
+            vec![
+                Keyframe::new(2.0, Transform::from_translation(Vec3f::new(4.0, 0.0, 0.0))),
+                Keyframe::new(0.0, Transform::IDENTITY),
+            ],
+        );
+        assert_eq!(track.node(), 3);
+        assert_eq!(track.keys()[0].time, 0.0);
+        assert_approx_eq!(track.sample(0.5).translation.x, 1.0);
+        assert_eq!(track.sample(-1.0), Transform::IDENTITY);
+        assert_approx_eq!(track.sample(5.0).translation.x, 4.0);
+        let mut clip = Clip::new("loop", 2.0, true);
+        clip.tracks.push(track);
+        let mut pose = Pose::new(2);
+        clip.sample(2.5, &mut pose);
+        assert_eq!(pose, Pose::new(2));
+        let mut pose = Pose::new(4);
+        clip.sample(2.5, &mut pose);
+        assert_approx_eq!(pose.get(3).translation.x, 1.0);
+    }
+
+    #[test]
+    fn test_crossfade() {
+        let mut animator = Animator::new();
+        let left = animator.add_clip(slide("left", Vec3f::new(-1.0, 0.0, 0.0)));
+        let right = animator.add_clip(slide("right", Vec3f::new(1.0, 0.0, 0.0)));
+        let left = animator.add_state("left", left);
+        let right = animator.add_state("right", right);
+        animator.add_transition(left, right, "turn", 0.5);
+        assert_eq!(animator.state(), "left");
+        assert!(!animator.trigger("unknown"));
+        animator.update(1.0);
+        assert_approx_eq!(animator.pose(&Pose::new(1)).get(0).translation.x, -1.0);
+        assert!(animator.trigger("turn"));
+        // Transitions only leave the state they were added for
+        assert!(!animator.trigger("turn"));
+        animator.update(0.25);
+        assert!(animator.is_fading());
+        // Halfway between the end of left and a quarter into right
+        assert_approx_eq!(animator.pose(&Pose::new(1)).get(0).translation.x, -0.375);
+        animator.update(0.25);
+        assert!(!animator.is_fading());
+        assert_approx_eq!(animator.pose(&Pose::new(1)).get(0).translation.x, 0.5);
+        // Any state transitions work from everywhere else
+        animator.add_any_transition(left, "reset", 0.0);
+        assert!(animator.trigger("reset"));
+        assert!(!animator.is_fading());
+        assert_eq!(animator.pose(&Pose::new(1)).get(0), Transform::IDENTITY);
+    }
+
+    #[test]
+    fn test_additive_layer() {
+        let mut animator = Animator::new();
+        let rest = Pose::from_transforms(vec![Transform::from_translation(Vec3f::new(0.0, 5.0, 0.0))]);
+        assert_eq!(animator.pose(&rest), rest);
+        let mut bob = Clip::new("bob", 2.0, true);
+        bob.add_track(
+            0,
+            vec![
+                Keyframe::new(0.0, Transform::from_translation(Vec3f::new(0.0, 1.0, 0.0))),
+                Keyframe::new(1.0, Transform::from_translation(Vec3f::new(0.0, 2.0, 0.0))),
+            ],
+        );
+        let bob = animator.add_clip(bob);
+        let layer = animator.add_layer(bob, 0.5);
+        animator.update(1.0);
+        // Half of the clip's one unit rise over its first frame
+        assert_approx_eq!(animator.pose(&rest).get(0).translation.y, 5.5);
+        animator.set_layer_weight(layer, 0.0);
+        assert_eq!(animator.pose(&rest), rest);
+    }
+}
@@ -0,0 +1,350 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Canvas, Color, Format, PixelBuffer, Texture};
+use crate::math::{Vec2f, Vec3f};
+use crate::model::ALL_LINKS;
+
+/// Arbitrary Output Variable written alongside the beauty pass
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Aov {
+    /// Surface color before lighting
+    Albedo,
+    /// World space surface normal
+    Normal,
+    /// Distance from the camera, infinite where nothing was hit
+    Depth,
+    /// Surface texture coordinates
+    Uv,
+    /// Id of the object hit, 0 where nothing was hit
+    ObjectId,
+    /// Id of the material hit, 0 where nothing was hit
+    MaterialId,
+    /// Screen space motion in pixels since the previous frame, zero where nothing was hit
+    Velocity,
+    /// Light linking groups of the object hit, deciding which lights reach it, every group
+    /// where nothing was hit
+    LightLinks,
+}
+
+/// Everything a renderer knows about the surface seen through a pixel
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AovSample {
+    pub albedo: Color,
+    pub normal: Vec3f,
+    pub depth: f32,
+    pub uv: Vec2f,
+    pub object_id: u32,
+    pub material_id: u32,
+    pub velocity: Vec2f,
+    pub light_links: u32,
+}
+
+impl Default for AovSample {
+    fn default() -> Self {
+        Self {
+            albedo: Color::black(),
+            normal: Vec3f::from_parts(0.0, 0.0, 0.0),
+            depth: f32::INFINITY,
+            uv: Vec2f::from_parts(0.0, 0.0),
+            object_id: 0,
+            material_id: 0,
+            velocity: Vec2f::from_parts(0.0, 0.0),
+            light_links: ALL_LINKS,
+        }
+    }
+}
+
+///
+/// Set of optional AOV buffers matching the size of a `Canvas`.
+///
+/// Only the enabled outputs are allocated, writing a sample stores the fields of the
+/// enabled outputs and ignores the rest. Useful for denoising, compositing and debugging.
+///
+pub struct AovBuffers {
+    dimensions: (usize, usize),
+    albedo: Option<Vec<Color>>,
+    normal: Option<Vec<Vec3f>>,
+    depth: Option<Vec<f32>>,
+    uv: Option<Vec<Vec2f>>,
+    object_id: Option<Vec<u32>>,
+    material_id: Option<Vec<u32>>,
+    velocity: Option<Vec<Vec2f>>,
+    light_links: Option<Vec<u32>>,
+}
+
+impl AovBuffers {
+    /// Create new AovBuffers with the given outputs enabled
+    pub fn new(width: usize, height: usize, aovs: &[Aov]) -> Self {
+        let len = width * height;
+        let empty = AovSample::default();
+        let enabled = |aov: Aov| aovs.contains(&aov);
+        Self {
+            dimensions: (width, height),
+            albedo: if enabled(Aov::Albedo) { Some(vec![empty.albedo; len]) } else { None },
+            normal: if enabled(Aov::Normal) { Some(vec![empty.normal; len]) } else { None },
+            depth: if enabled(Aov::Depth) { Some(vec![empty.depth; len]) } else { None },
+            uv: if enabled(Aov::Uv) { Some(vec![empty.uv; len]) } else { None },
+            object_id: if enabled(Aov::ObjectId) { Some(vec![empty.object_id; len]) } else { None },
+            material_id: if enabled(Aov::MaterialId) { Some(vec![empty.material_id; len]) } else { None },
+            velocity: if enabled(Aov::Velocity) { Some(vec![empty.velocity; len]) } else { None },
+            light_links: if enabled(Aov::LightLinks) { Some(vec![empty.light_links; len]) } else { None },
+        }
+    }
+    /// Get Width of AovBuffers
+    pub fn width(&self) -> usize {
+        self.dimensions.0
+    }
+    /// Get Height of AovBuffers
+    pub fn height(&self) -> usize {
+        self.dimensions.1
+    }
+    /// Check if an output is being recorded
+    pub fn is_enabled(&self, aov: Aov) -> bool {
+        match aov {
+            Aov::Albedo => self.albedo.is_some(),
+            Aov::Normal => self.normal.is_some(),
+            Aov::Depth => self.depth.is_some(),
+            Aov::Uv => self.uv.is_some(),
+            Aov::ObjectId => self.object_id.is_some(),
+            Aov::MaterialId => self.material_id.is_some(),
+            Aov::Velocity => self.velocity.is_some(),
+            Aov::LightLinks => self.light_links.is_some(),
+        }
+    }
+    /// Reset every enabled output to its empty value
+    pub fn clear(&mut self) {
+        let empty = AovSample::default();
+        for y in 0..self.dimensions.1 {
+            for x in 0..self.dimensions.0 {
+                self.set(x, y, &empty);
+            }
+        }
+    }
+    /// Store the enabled outputs of `sample` at Pixel (x, y)
+    pub fn set(&mut self, x: usize, y: usize, sample: &AovSample) {
+        let index = self.index(x, y);
+        if let Some(buffer) = &mut self.albedo {
+            buffer[index] = sample.albedo;
+        }
+        if let Some(buffer) = &mut self.normal {
+            buffer[index] = sample.normal;
+        }
+        if let Some(buffer) = &mut self.depth {
+            buffer[index] = sample.depth;
+        }
+        if let Some(buffer) = &mut self.uv {
+            buffer[index] = sample.uv;
+        }
+        if let Some(buffer) = &mut self.object_id {
+            buffer[index] = sample.object_id;
+        }
+        if let Some(buffer) = &mut self.material_id {
+            buffer[index] = sample.material_id;
+        }
+        if let Some(buffer) = &mut self.velocity {
+            buffer[index] = sample.velocity;
+        }
+        if let Some(buffer) = &mut self.light_links {
+            buffer[index] = sample.light_links;
+        }
+    }
+    /// Get the recorded outputs at Pixel (x, y), disabled outputs hold their empty value
+    pub fn get(&self, x: usize, y: usize) -> AovSample {
+        let index = self.index(x, y);
+        let empty = AovSample::default();
+        AovSample {
+            albedo: self.albedo.as_ref().map_or(empty.albedo, |b| b[index]),
+            normal: self.normal.as_ref().map_or(empty.normal, |b| b[index]),
+            depth: self.depth.as_ref().map_or(empty.depth, |b| b[index]),
+            uv: self.uv.as_ref().map_or(empty.uv, |b| b[index]),
+            object_id: self.object_id.as_ref().map_or(empty.object_id, |b| b[index]),
+            material_id: self.material_id.as_ref().map_or(empty.material_id, |b| b[index]),
+            velocity: self.velocity.as_ref().map_or(empty.velocity, |b| b[index]),
+            light_links: self.light_links.as_ref().map_or(empty.light_links, |b| b[index]),
+        }
+    }
+    /// Render an output as a viewable `Canvas`, or `None` if it is not enabled.
+    ///
+    /// Normals are remapped from -1..1 to 0..1, depth is normalized between the nearest
+    /// and farthest finite values, ids are given distinct colors and velocities are shown
+    /// around grey with 16 pixels of motion reaching full red or green.
+    pub fn to_canvas(&self, aov: Aov) -> Option<Canvas> {
+        if !self.is_enabled(aov) {
+            return None;
+        }
+        let (near, far) = self.depth.as_ref().map_or((0.0, 1.0), |depth| {
+            depth
+                .iter()
+                .filter(|d| d.is_finite())
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(near, far), &d| {
+                    (near.min(d), far.max(d))
+                })
+        });
+        let mut canvas = Canvas::new(self.width(), self.height());
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let sample = self.get(x, y);
+                let color = match aov {
+                    Aov::Albedo => sample.albedo,
+                    Aov::Normal => {
+                        let n = sample.normal * 0.5 + 0.5;
+                        Color::new(n.x, n.y, n.z)
+                    }
+                    Aov::Depth if sample.depth.is_finite() && far > near => {
+                        let d = 1.0 - (sample.depth - near) / (far - near);
+                        Color::new(d, d, d)
+                    }
+                    Aov::Depth if sample.depth.is_finite() => Color::white(),
+                    Aov::Depth => Color::black(),
+                    Aov::Uv => Color::new(sample.uv.x, sample.uv.y, 0.0),
+                    Aov::ObjectId => id_color(sample.object_id),
+                    Aov::MaterialId => id_color(sample.material_id),
+                    Aov::LightLinks if sample.light_links == ALL_LINKS => Color::black(),
+                    Aov::LightLinks => id_color(sample.light_links),
+                    Aov::Velocity => Color::new(0.5 + sample.velocity.x / 32.0, 0.5 + sample.velocity.y / 32.0, 0.5),
+                };
+                canvas.set(x, y, color);
+            }
+        }
+        Some(canvas)
+    }
+    /// Copy the depth output into a `Format::Depth32F` Texture for sampling in a later
+    /// pass, or `None` if it is not enabled
+    pub fn depth_texture(&self) -> Option<Texture> {
+        let depth = self.depth.as_ref()?;
+        let mut canvas = Canvas::new(self.width(), self.height());
+        for (index, &d) in depth.iter().enumerate() {
+            canvas.set(index % self.width(), index / self.width(), Color::new(d, d, d));
+        }
+        Some(Texture::without_mips(canvas).with_format(Format::Depth32F))
+    }
+    fn index(&self, x: usize, y: usize) -> usize {
+        assert!(x < self.dimensions.0);
+        assert!(y < self.dimensions.1);
+        (y * self.dimensions.0) + x
+    }
+}
+
+impl PixelBuffer for AovBuffers {
+    type Pixel = AovSample;
+
+    fn width(&self) -> usize {
+        AovBuffers::width(self)
+    }
+    fn height(&self) -> usize {
+        AovBuffers::height(self)
+    }
+    fn get(&self, x: usize, y: usize) -> AovSample {
+        AovBuffers::get(self, x, y)
+    }
+    fn set(&mut self, x: usize, y: usize, value: AovSample) {
+        AovBuffers::set(self, x, y, &value)
+    }
+}
+
+/// Distinct color for an id, black for 0
+fn id_color(id: u32) -> Color {
+    if id == 0 {
+        return Color::black();
+    }
+    // Scramble the bits so neighbouring ids get very different colors
+    let hash = id.wrapping_mul(0x9E37_79B9);
+    let channel = |shift: u32| f32::from(((hash >> shift) & 0xFF) as u8) / 255.0;
+    Color::new(channel(24), channel(16), channel(8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Aov, AovBuffers, AovSample, Color};
+    use crate::canvas::{Format, Sampler};
+    use crate::math::{Vec2f, Vec3f};
+    use crate::rasterizer::Rasterizer;
+
+    fn sample() -> AovSample {
+        AovSample {
+            albedo: Color::new(0.5, 0.25, 1.0),
+            normal: Vec3f::from_parts(0.0, 1.0, 0.0),
+            depth: 4.0,
+            uv: Vec2f::from_parts(0.25, 0.75),
+            object_id: 7,
+            material_id: 3,
+            velocity: Vec2f::from_parts(-16.0, 8.0),
+            light_links: 2,
+        }
+    }
+
+    #[test]
+    fn test_enabled() {
+        let mut aovs = AovBuffers::new(4, 3, &[Aov::Depth, Aov::ObjectId]);
+        assert!(aovs.is_enabled(Aov::Depth));
+        assert!(aovs.is_enabled(Aov::ObjectId));
+        assert!(!aovs.is_enabled(Aov::Albedo));
+        aovs.set(3, 2, &sample());
+        let result = aovs.get(3, 2);
+        assert_eq!(result.depth, 4.0);
+        assert_eq!(result.object_id, 7);
+        assert_eq!(result.material_id, 0);
+        assert_eq!(result.albedo, Color::black());
+        assert_eq!(aovs.get(0, 0), AovSample::default());
+        aovs.clear();
+        assert_eq!(aovs.get(3, 2), AovSample::default());
+    }
+
+    #[test]
+    fn test_to_canvas() {
+        let mut aovs = AovBuffers::new(2, 1, &[Aov::Normal, Aov::Depth, Aov::ObjectId, Aov::Velocity]);
+        aovs.set(0, 0, &sample());
+        assert!(aovs.to_canvas(Aov::Albedo).is_none());
+        let normal = aovs.to_canvas(Aov::Normal).unwrap();
+        assert_eq!(normal.get(0, 0), Color::new(0.5, 1.0, 0.5));
+        let depth = aovs.to_canvas(Aov::Depth).unwrap();
+        assert_eq!(depth.get(0, 0), Color::white());
+        assert_eq!(depth.get(1, 0), Color::black());
+        let ids = aovs.to_canvas(Aov::ObjectId).unwrap();
+        assert_eq!(ids.get(1, 0), Color::black());
+        assert!(ids.get(0, 0) != Color::black());
+        let velocity = aovs.to_canvas(Aov::Velocity).unwrap();
+        assert_eq!(velocity.get(0, 0), Color::new(0.0, 0.75, 0.5));
+        assert_eq!(velocity.get(1, 0), Color::grey());
+    }
+
+    #[test]
+    fn test_depth_texture() {
+        let mut aovs = AovBuffers::new(2, 1, &[Aov::Depth]);
+        aovs.set(1, 0, &sample());
+        let depth = aovs.depth_texture().unwrap();
+        assert_eq!(depth.format(), Format::Depth32F);
+        let sampler = Sampler::default();
+        assert_eq!(depth.sample_depth(Vec2f::from_parts(0.75, 0.5), &sampler).unwrap(), 4.0);
+        assert!(AovBuffers::new(2, 1, &[Aov::Albedo]).depth_texture().is_none());
+        // Color textures refuse to be read as depth
+        let color = aovs.to_canvas(Aov::Depth).map(crate::canvas::Texture::new).unwrap();
+        assert!(color.sample_depth(Vec2f::from_parts(0.75, 0.5), &sampler).is_err());
+    }
+
+    #[test]
+    fn test_rasterizer() {
+        let mut aovs = AovBuffers::new(4, 4, &[Aov::Depth, Aov::ObjectId]);
+        Rasterizer::fill_rect(&mut aovs, 1, 1, 2, 2, sample());
+        Rasterizer::draw_line(&mut aovs, 0, 3, 3, 3, AovSample { object_id: 9, ..sample() });
+        assert_eq!(aovs.get(1, 1).object_id, 7);
+        assert_eq!(aovs.get(2, 2).depth, 4.0);
+        assert_eq!(aovs.get(3, 3).object_id, 9);
+        assert_eq!(aovs.get(0, 0), AovSample::default());
+        assert_eq!(aovs.get(3, 1), AovSample::default());
+    }
+}
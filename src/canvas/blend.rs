@@ -0,0 +1,177 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Canvas, Color, Rgba, RgbaCanvas};
+
+/// How the color of a source pixel relates to its alpha
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AlphaMode {
+    /// Color is independent of alpha
+    Straight,
+    /// Color has already been multiplied by alpha.
+    ///
+    /// Filtering or scaling straight alpha images mixes the color of transparent pixels
+    /// into the edges, which shows as dark fringes around antialiased sprites and text.
+    /// Premultiplied pixels do not have this problem.
+    Premultiplied,
+}
+
+/// Space colors are blended in
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlendSpace {
+    /// Pixels hold linear values and are blended as is
+    Linear,
+    /// Pixels hold sRGB encoded values, such as loaded 8 bit images, and are decoded
+    /// to linear before blending and encoded again afterwards
+    Srgb,
+}
+
+/// Options for compositing an `RgbaCanvas` onto a `Canvas`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BlendMode {
+    pub alpha: AlphaMode,
+    pub space: BlendSpace,
+}
+
+impl BlendMode {
+    /// Create new BlendMode
+    pub fn new(alpha: AlphaMode, space: BlendSpace) -> Self {
+        Self { alpha, space }
+    }
+    /// Blend `source` over `destination`, returning the new destination color
+    pub fn blend(self, source: Rgba, destination: Color) -> Color {
+        let alpha = source.alpha.clamp(0.0, 1.0);
+        let premultiplied = match self.alpha {
+            AlphaMode::Straight => source.color * alpha,
+            AlphaMode::Premultiplied => source.color,
+        };
+        match self.space {
+            BlendSpace::Linear => premultiplied + destination * (1.0 - alpha),
+            BlendSpace::Srgb => {
+                // Premultiplying happens in linear space, so undo it around the decode
+                let source = Rgba::new(premultiplied, alpha).unpremultiply();
+                let source = srgb_to_linear(source.color) * alpha;
+                linear_to_srgb(source + srgb_to_linear(destination) * (1.0 - alpha))
+            }
+        }
+    }
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        Self::new(AlphaMode::Straight, BlendSpace::Linear)
+    }
+}
+
+/// Decode an sRGB encoded color to linear
+pub fn srgb_to_linear(color: Color) -> Color {
+    let decode = |c: f32| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    Color::new(decode(color.r.value()), decode(color.g.value()), decode(color.b.value()))
+}
+
+/// Encode a linear color as sRGB
+pub fn linear_to_srgb(color: Color) -> Color {
+    let encode = |c: f32| {
+        if c <= 0.003_130_8 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    };
+    Color::new(encode(color.r.value()), encode(color.g.value()), encode(color.b.value()))
+}
+
+impl Canvas {
+    /// Composite `source` over this Canvas with its top left corner at (x, y).
+    ///
+    /// Parts of `source` outside the Canvas are skipped.
+    pub fn blit(&mut self, source: &RgbaCanvas, x: isize, y: isize, mode: BlendMode) {
+        for sy in 0..source.height() {
+            let dy = y + sy as isize;
+            if dy < 0 || dy >= self.height() as isize {
+                continue;
+            }
+            for sx in 0..source.width() {
+                let dx = x + sx as isize;
+                if dx < 0 || dx >= self.width() as isize {
+                    continue;
+                }
+                let (dx, dy) = (dx as usize, dy as usize);
+                let color = mode.blend(source.get(sx, sy), self.get(dx, dy));
+                self.set(dx, dy, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{linear_to_srgb, srgb_to_linear, AlphaMode, BlendMode, BlendSpace};
+    use crate::canvas::{Canvas, Color, Rgba, RgbaCanvas};
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_srgb_round_trip() {
+        assert_approx_eq!(srgb_to_linear(Color::grey()).r.value(), 0.214, 1e-3);
+        assert_approx_eq!(linear_to_srgb(Color::new(0.214, 0.0, 1.0)).r.value(), 0.5, 1e-3);
+        let color = Color::new(0.02, 0.3, 0.9);
+        assert_eq!(linear_to_srgb(srgb_to_linear(color)), color);
+    }
+
+    #[test]
+    fn test_alpha_modes() {
+        let straight = BlendMode::default();
+        let premultiplied = BlendMode::new(AlphaMode::Premultiplied, BlendSpace::Linear);
+        let source = Rgba::new(Color::white(), 0.25);
+        let blended = straight.blend(source, Color::black());
+        assert_eq!(blended, Color::new(0.25, 0.25, 0.25));
+        assert_eq!(premultiplied.blend(source.premultiply(), Color::black()), blended);
+        // Transparent premultiplied pixels leave the destination untouched
+        assert_eq!(premultiplied.blend(Rgba::transparent(), Color::grey()), Color::grey());
+    }
+
+    #[test]
+    fn test_srgb_blend() {
+        // Half covered white over black is half the light, which is brighter than 0.5 in sRGB
+        let mode = BlendMode::new(AlphaMode::Straight, BlendSpace::Srgb);
+        let blended = mode.blend(Rgba::new(Color::white(), 0.5), Color::black());
+        assert_approx_eq!(blended.r.value(), 0.735, 1e-3);
+        let mode = BlendMode::new(AlphaMode::Premultiplied, BlendSpace::Srgb);
+        let premultiplied = mode.blend(Rgba::new(Color::white(), 0.5).premultiply(), Color::black());
+        assert_eq!(premultiplied, blended);
+    }
+
+    #[test]
+    fn test_blit() {
+        let mut canvas = Canvas::new(4, 4);
+        let mut sprite = RgbaCanvas::new(2, 2);
+        sprite.fill(Rgba::new(Color::white(), 1.0));
+        sprite.set(0, 0, Rgba::transparent());
+        canvas.blit(&sprite, 3, -1, BlendMode::default());
+        assert_eq!(canvas.get(3, 0), Color::white());
+        assert_eq!(canvas.get(3, 1), Color::black());
+        canvas.blit(&sprite, -1, 2, BlendMode::default());
+        assert_eq!(canvas.get(0, 2), Color::white());
+        assert_eq!(canvas.get(0, 3), Color::white());
+        assert_eq!(canvas.get(1, 2), Color::black());
+    }
+}
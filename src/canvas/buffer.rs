@@ -0,0 +1,104 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Canvas, ClipRect, Color, HalfCanvas};
+use crate::{Error, Result};
+
+///
+/// Two dimensional grid of pixels the Rasterizer can draw into
+///
+pub trait PixelBuffer {
+    /// Value stored in each Pixel
+    type Pixel: Copy;
+    /// Get Width of Buffer
+    fn width(&self) -> usize;
+    /// Get Height of Buffer
+    fn height(&self) -> usize;
+    /// Get the Pixels drawing primitives may touch, the whole Buffer by default
+    fn clip_rect(&self) -> ClipRect {
+        ClipRect::full(self.width(), self.height())
+    }
+    /// Get value of Pixel at (x, y)
+    fn get(&self, x: usize, y: usize) -> Self::Pixel;
+    /// Set value of Pixel at (x, y)
+    fn set(&mut self, x: usize, y: usize, value: Self::Pixel);
+    /// Get value of Pixel at (x, y), or an error if it lies outside the Buffer
+    fn try_get(&self, x: usize, y: usize) -> Result<Self::Pixel> {
+        check_bounds(self, x, y)?;
+        Ok(self.get(x, y))
+    }
+    /// Set value of Pixel at (x, y), or return an error if it lies outside the Buffer
+    fn try_set(&mut self, x: usize, y: usize, value: Self::Pixel) -> Result<()> {
+        check_bounds(self, x, y)?;
+        self.set(x, y, value);
+        Ok(())
+    }
+    /// Set value of the Pixels from x0 up to but not including x1 on row y
+    fn fill_span(&mut self, y: usize, x0: usize, x1: usize, value: Self::Pixel) {
+        for x in x0..x1 {
+            self.set(x, y, value);
+        }
+    }
+}
+
+fn check_bounds<B: PixelBuffer + ?Sized>(buffer: &B, x: usize, y: usize) -> Result<()> {
+    let (width, height) = (buffer.width(), buffer.height());
+    if x < width && y < height {
+        Ok(())
+    } else {
+        Err(Error::OutOfBounds { x, y, width, height })
+    }
+}
+
+impl PixelBuffer for Canvas {
+    type Pixel = Color;
+
+    fn width(&self) -> usize {
+        Canvas::width(self)
+    }
+    fn height(&self) -> usize {
+        Canvas::height(self)
+    }
+    fn clip_rect(&self) -> ClipRect {
+        Canvas::clip_rect(self)
+    }
+    fn get(&self, x: usize, y: usize) -> Color {
+        Canvas::get(self, x, y)
+    }
+    fn set(&mut self, x: usize, y: usize, value: Color) {
+        Canvas::set(self, x, y, value)
+    }
+    fn fill_span(&mut self, y: usize, x0: usize, x1: usize, value: Color) {
+        Canvas::fill_span(self, y, x0, x1, value)
+    }
+}
+
+impl PixelBuffer for HalfCanvas {
+    type Pixel = Color;
+
+    fn width(&self) -> usize {
+        HalfCanvas::width(self)
+    }
+    fn height(&self) -> usize {
+        HalfCanvas::height(self)
+    }
+    fn get(&self, x: usize, y: usize) -> Color {
+        HalfCanvas::get(self, x, y)
+    }
+    fn set(&mut self, x: usize, y: usize, value: Color) {
+        HalfCanvas::set(self, x, y, value)
+    }
+}
@@ -14,21 +14,78 @@
 // limitations under the License.
 //
 
-use super::Color;
+use super::{ClipRect, Color};
+use crate::{Error, Result};
+
+/// Width and height in pixels of a tile in `Layout::Tiled`.
+pub const TILE_SIZE: usize = 8;
+
+/// Memory layout of the pixels in a `Canvas`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// Row major scanlines, one row after another
+    Linear,
+    /// 8x8 tiles stored row major, with the pixels of each tile in Morton (Z) order.
+    ///
+    /// Neighbouring pixels in both X and Y tend to share a cache line, which helps
+    /// triangle fill and raytracing which walk the image in small blocks.
+    Tiled,
+}
 
 /// Image Canvas
+#[derive(Clone)]
 pub struct Canvas {
     dimensions: (usize, usize),
+    layout: Layout,
     color_buffer: Vec<Color>,
+    clip_rect: Option<ClipRect>,
 }
 
 impl Canvas {
     /// Create new Canvas
     pub fn new(width: usize, height: usize) -> Self {
+        Self::with_layout(width, height, Layout::Linear)
+    }
+    /// Create new Canvas with the given pixel `Layout`
+    pub fn with_layout(width: usize, height: usize, layout: Layout) -> Self {
+        let len = match layout {
+            Layout::Linear => width * height,
+            Layout::Tiled => tiles(width) * tiles(height) * TILE_SIZE * TILE_SIZE,
+        };
         Self {
             dimensions: (width, height),
-            color_buffer: vec![Color::black(); width * height],
+            layout,
+            color_buffer: vec![Color::black(); len],
+            clip_rect: None,
+        }
+    }
+    /// Get pixel `Layout` of Canvas
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+    /// Copy this Canvas into a new Canvas with the given pixel `Layout`
+    pub fn to_layout(&self, layout: Layout) -> Canvas {
+        let mut canvas = Canvas::with_layout(self.width(), self.height(), layout);
+        for (x, y, color) in self.pixels() {
+            canvas.set(x, y, color);
         }
+        canvas.clip_rect = self.clip_rect;
+        canvas
+    }
+    /// Iterate over every pixel as `(x, y, color)` in storage order.
+    ///
+    /// This is the cache friendly way to visit a whole Canvas whatever its layout.
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize, Color)> + '_ {
+        let (width, height) = self.dimensions;
+        let layout = self.layout;
+        self.color_buffer
+            .iter()
+            .enumerate()
+            .map(move |(index, color)| {
+                let (x, y) = position(layout, width, index);
+                (x, y, *color)
+            })
+            .filter(move |&(x, y, _)| x < width && y < height)
     }
     /// Get Width of Canvas
     pub fn width(&self) -> usize {
@@ -38,23 +95,225 @@ impl Canvas {
     pub fn height(&self) -> usize {
         self.dimensions.1
     }
+    /// Restrict the Rasterizer's drawing primitives to `rect`, scissor style, or let them
+    /// draw anywhere again with `None`.
+    ///
+    /// Only drawing through the Rasterizer is clipped, `set` still reaches every Pixel.
+    pub fn set_clip_rect(&mut self, rect: Option<ClipRect>) {
+        self.clip_rect = rect.map(|rect| rect.intersect(&ClipRect::full(self.width(), self.height())));
+    }
+    /// Get the Pixels the Rasterizer may draw into
+    pub fn clip_rect(&self) -> ClipRect {
+        self.clip_rect.unwrap_or_else(|| ClipRect::full(self.width(), self.height()))
+    }
     /// Get Color of Pixel at (x, y)
     pub fn get(&self, x: usize, y: usize) -> Color {
         assert!(x < self.dimensions.0);
         assert!(y < self.dimensions.1);
-        self.color_buffer[(y * self.dimensions.0) + x]
+        self.color_buffer[self.index(x, y)]
     }
     /// Set `Color` of Pixel at (x, y) to color
     pub fn set(&mut self, x: usize, y: usize, color: Color) {
         assert!(x < self.dimensions.0);
         assert!(y < self.dimensions.1);
-        self.color_buffer[(y * self.dimensions.0) + x] = color;
+        let index = self.index(x, y);
+        self.color_buffer[index] = color;
+    }
+    /// Get Color of Pixel at (x, y), or an error if it lies outside the Canvas
+    pub fn try_get(&self, x: usize, y: usize) -> Result<Color> {
+        self.check_bounds(x, y)?;
+        Ok(self.color_buffer[self.index(x, y)])
+    }
+    /// Set `Color` of Pixel at (x, y) to color, or return an error if it lies outside the
+    /// Canvas
+    pub fn try_set(&mut self, x: usize, y: usize, color: Color) -> Result<()> {
+        self.check_bounds(x, y)?;
+        let index = self.index(x, y);
+        self.color_buffer[index] = color;
+        Ok(())
+    }
+    fn check_bounds(&self, x: usize, y: usize) -> Result<()> {
+        let (width, height) = self.dimensions;
+        if x < width && y < height {
+            Ok(())
+        } else {
+            Err(Error::OutOfBounds { x, y, width, height })
+        }
+    }
+    /// Set every Pixel to color
+    pub fn fill(&mut self, color: Color) {
+        for pixel in self.color_buffer.iter_mut() {
+            *pixel = color;
+        }
+    }
+    /// Set the Pixels from x0 up to but not including x1 on row y to color.
+    ///
+    /// Linear canvases fill the row with a single slice write, which makes this the fast
+    /// path for scanline fills.
+    pub fn fill_span(&mut self, y: usize, x0: usize, x1: usize, color: Color) {
+        assert!(y < self.dimensions.1);
+        assert!(x1 <= self.dimensions.0);
+        if x0 >= x1 {
+            return;
+        }
+        match self.layout {
+            Layout::Linear => {
+                let row = y * self.dimensions.0;
+                for pixel in &mut self.color_buffer[row + x0..row + x1] {
+                    *pixel = color;
+                }
+            }
+            Layout::Tiled => {
+                for x in x0..x1 {
+                    let index = self.index(x, y);
+                    self.color_buffer[index] = color;
+                }
+            }
+        }
+    }
+    /// Fill the region of similar color around (x, y) with color, like a paint bucket.
+    ///
+    /// Pixels join the region if they touch it horizontally or vertically and no channel
+    /// differs from the starting Pixel by more than `tolerance`. Starting outside the
+    /// Canvas fills nothing.
+    pub fn flood_fill(&mut self, x: usize, y: usize, color: Color, tolerance: f32) {
+        let (width, height) = self.dimensions;
+        if x >= width || y >= height {
+            return;
+        }
+        let target = self.get(x, y);
+        let matches = |c: Color| {
+            (c.r.value() - target.r.value()).abs() <= tolerance
+                && (c.g.value() - target.g.value()).abs() <= tolerance
+                && (c.b.value() - target.b.value()).abs() <= tolerance
+        };
+        // The fill color may itself match, so remember what was filled
+        let mut filled = vec![false; width * height];
+        let mut stack = vec![(x, y)];
+        while let Some((x, y)) = stack.pop() {
+            if filled[y * width + x] || !matches(self.get(x, y)) {
+                continue;
+            }
+            let mut x0 = x;
+            while x0 > 0 && !filled[y * width + x0 - 1] && matches(self.get(x0 - 1, y)) {
+                x0 -= 1;
+            }
+            let mut x1 = x + 1;
+            while x1 < width && !filled[y * width + x1] && matches(self.get(x1, y)) {
+                x1 += 1;
+            }
+            self.fill_span(y, x0, x1, color);
+            for flag in &mut filled[y * width + x0..y * width + x1] {
+                *flag = true;
+            }
+            // Seed one Pixel per run of candidates on the rows above and below
+            for row in [y.wrapping_sub(1), y + 1].iter().cloned().filter(|&row| row < height) {
+                let mut in_run = false;
+                for nx in x0..x1 {
+                    let candidate = !filled[row * width + nx] && matches(self.get(nx, row));
+                    if candidate && !in_run {
+                        stack.push((nx, row));
+                    }
+                    in_run = candidate;
+                }
+            }
+        }
+    }
+    /// Buffer index of Pixel at (x, y)
+    fn index(&self, x: usize, y: usize) -> usize {
+        match self.layout {
+            Layout::Linear => (y * self.dimensions.0) + x,
+            Layout::Tiled => {
+                let tile = (y / TILE_SIZE) * tiles(self.dimensions.0) + (x / TILE_SIZE);
+                tile * TILE_SIZE * TILE_SIZE + morton(x % TILE_SIZE, y % TILE_SIZE)
+            }
+        }
+    }
+}
+
+/// Number of tiles needed to cover `pixels`
+fn tiles(pixels: usize) -> usize {
+    pixels.div_ceil(TILE_SIZE)
+}
+
+/// Interleave the bits of x and y within a tile, x taking the low bit
+fn morton(x: usize, y: usize) -> usize {
+    let spread = |v: usize| {
+        let v = (v | (v << 2)) & 0b0011_0011;
+        (v | (v << 1)) & 0b0101_0101
+    };
+    spread(x) | (spread(y) << 1)
+}
+
+/// Inverse of `morton`
+fn unmorton(index: usize) -> (usize, usize) {
+    let compact = |v: usize| {
+        let v = v & 0b0101_0101;
+        let v = (v | (v >> 1)) & 0b0011_0011;
+        (v | (v >> 2)) & 0b0000_1111
+    };
+    (compact(index), compact(index >> 1))
+}
+
+/// Pixel position of buffer index in a Canvas of `width` pixels
+fn position(layout: Layout, width: usize, index: usize) -> (usize, usize) {
+    match layout {
+        Layout::Linear => (index % width, index / width),
+        Layout::Tiled => {
+            let tile = index / (TILE_SIZE * TILE_SIZE);
+            let (x, y) = unmorton(index % (TILE_SIZE * TILE_SIZE));
+            let tiles_x = tiles(width);
+            ((tile % tiles_x) * TILE_SIZE + x, (tile / tiles_x) * TILE_SIZE + y)
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Canvas, Color};
+    use super::{morton, unmorton, Canvas, Color, Layout};
+    use crate::canvas::PixelBuffer;
+    use crate::Error;
+
+    #[test]
+    fn test_fill_span() {
+        for &layout in &[Layout::Linear, Layout::Tiled] {
+            let mut canvas = Canvas::with_layout(10, 3, layout);
+            canvas.fill_span(1, 2, 9, Color::white());
+            canvas.fill_span(2, 4, 4, Color::white());
+            let lit: Vec<(usize, usize)> = canvas.pixels().filter(|p| p.2 == Color::white()).map(|p| (p.0, p.1)).collect();
+            assert_eq!(lit.len(), 7);
+            assert!(lit.iter().all(|&(x, y)| y == 1 && (2..9).contains(&x)));
+        }
+    }
+
+    #[test]
+    fn test_flood_fill() {
+        // A ring with a gap on the left, and a separate pocket inside it
+        let rows = ["#####.", "#...#.", "..#.#.", "#...#.", "#####."];
+        let mut canvas = Canvas::new(6, 5);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == '#' {
+                    canvas.set(x, y, Color::white());
+                }
+            }
+        }
+        canvas.set(5, 4, Color::new(0.01, 0.0, 0.0));
+        canvas.flood_fill(5, 0, Color::bright_red(), 0.02);
+        assert!((0..5).all(|y| canvas.get(5, y) == Color::bright_red()));
+        assert_eq!(canvas.get(1, 1), Color::black());
+        canvas.flood_fill(1, 1, Color::bright_blue(), 0.0);
+        assert_eq!(canvas.get(0, 2), Color::bright_blue());
+        assert_eq!(canvas.get(3, 3), Color::bright_blue());
+        assert_eq!(canvas.get(2, 2), Color::white());
+        // Filling with a color matching the region itself terminates
+        canvas.flood_fill(0, 0, Color::white(), 1.0);
+        assert!(canvas.pixels().all(|(_, _, c)| c == Color::white()));
+        // Seeds outside the canvas are ignored
+        canvas.flood_fill(6, 0, Color::black(), 1.0);
+        canvas.flood_fill(0, usize::MAX, Color::black(), 1.0);
+        assert!(canvas.pixels().all(|(_, _, c)| c == Color::white()));
+    }
 
     #[test]
     fn test_creation() {
@@ -68,4 +327,73 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_try_get_set() {
+        let mut c = Canvas::with_layout(10, 20, Layout::Tiled);
+        assert!(c.try_set(9, 19, Color::white()).is_ok());
+        assert_eq!(c.try_get(9, 19).unwrap(), Color::white());
+        match c.try_set(10, 0, Color::white()) {
+            Err(Error::OutOfBounds { x, y, width, height }) => assert_eq!((x, y, width, height), (10, 0, 10, 20)),
+            other => panic!("expected out of bounds, got {:?}", other),
+        }
+        assert!(c.try_get(0, 20).is_err());
+        // The padding of the last tile is not addressable
+        assert!(PixelBuffer::try_get(&c, 12, 0).is_err());
+    }
+
+    #[test]
+    fn test_morton() {
+        assert_eq!(morton(0, 0), 0);
+        assert_eq!(morton(1, 0), 1);
+        assert_eq!(morton(0, 1), 2);
+        assert_eq!(morton(1, 1), 3);
+        assert_eq!(morton(2, 0), 4);
+        assert_eq!(morton(7, 7), 63);
+        for i in 0..64 {
+            let (x, y) = unmorton(i);
+            assert_eq!(morton(x, y), i);
+        }
+    }
+
+    #[test]
+    fn test_tiled() {
+        let mut c = Canvas::with_layout(10, 13, Layout::Tiled);
+        assert_eq!(c.layout(), Layout::Tiled);
+        for y in 0..13 {
+            for x in 0..10 {
+                c.set(x, y, Color::new(x as f32 / 10.0, y as f32 / 13.0, 0.0));
+            }
+        }
+        for y in 0..13 {
+            for x in 0..10 {
+                assert_eq!(c.get(x, y), Color::new(x as f32 / 10.0, y as f32 / 13.0, 0.0));
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_layout() {
+        let mut c = Canvas::with_layout(9, 3, Layout::Tiled);
+        c.set(8, 2, Color::white());
+        c.set(3, 1, Color::bright_red());
+        let linear = c.to_layout(Layout::Linear);
+        assert_eq!(linear.layout(), Layout::Linear);
+        assert_eq!(linear.get(8, 2), Color::white());
+        assert_eq!(linear.get(3, 1), Color::bright_red());
+        assert_eq!(linear.get(0, 0), Color::black());
+        let tiled = linear.to_layout(Layout::Tiled);
+        assert_eq!(tiled.get(8, 2), Color::white());
+        assert_eq!(tiled.get(3, 1), Color::bright_red());
+    }
+
+    #[test]
+    fn test_pixels() {
+        let c = Canvas::with_layout(9, 3, Layout::Tiled);
+        let mut seen: Vec<(usize, usize)> = c.pixels().map(|(x, y, _)| (x, y)).collect();
+        assert_eq!(seen.len(), 27);
+        assert_eq!(seen[..4], [(0, 0), (1, 0), (0, 1), (1, 1)]);
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 27);
+    }
 }
\ No newline at end of file
@@ -17,11 +17,114 @@
 use std::{fmt, ops};
 
 ///
-/// Color Channel
+/// Color Channel holding an unclamped, high dynamic range value.
+///
+/// Arithmetic never clamps, so light can accumulate above 1.0 and be tone mapped later.
+/// Values are only clamped to 0.0 to 1.0 when converted to `u8`, `f32` or `LdrChannel`,
+/// or explicitly with `clamp` and the `saturating_` operations. Equality compares the
+/// unclamped values, within half a step of an 8 bit channel scaled to their magnitude.
 ///
 #[derive(Copy, Clone, Debug)]
 pub struct Channel(f32);
 
+impl Channel {
+    /// Create Channel from an unclamped value
+    pub const fn new(value: f32) -> Self {
+        Channel(value)
+    }
+    /// Get the unclamped value of this Channel, which may lie outside 0.0 to 1.0 for HDR
+    /// colors.
+    pub fn value(&self) -> f32 {
+        self.0
+    }
+    /// This Channel limited to 0.0 to 1.0, NaN becoming 0.0
+    pub fn clamp(self) -> Self {
+        Self(if self.0 >= 1.0 { 1.0 } else if self.0 > 0.0 { self.0 } else { 0.0 })
+    }
+    /// Sum of this and `rhs` limited to 0.0 to 1.0
+    pub fn saturating_add(self, rhs: Self) -> Self {
+        (self + rhs).clamp()
+    }
+    /// Difference of this and `rhs` limited to 0.0 to 1.0
+    pub fn saturating_sub(self, rhs: Self) -> Self {
+        (self - rhs).clamp()
+    }
+    /// Check if the value is neither infinite nor NaN
+    pub fn is_finite(&self) -> bool {
+        self.0.is_finite()
+    }
+}
+
+///
+/// Color Channel limited to 0.0 to 1.0, for low dynamic range output such as 8 bit
+/// images and UI colors.
+///
+/// Every operation clamps its result, so sums saturate at 1.0 rather than growing.
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct LdrChannel(f32);
+
+impl LdrChannel {
+    /// Create LdrChannel, clamping `value` to 0.0 to 1.0
+    pub fn new(value: f32) -> Self {
+        Self(Channel(value).clamp().0)
+    }
+    /// Get the value, always from 0.0 to 1.0
+    pub fn value(&self) -> f32 {
+        self.0
+    }
+}
+
+impl ops::Add<Self> for LdrChannel {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.0 + rhs.0)
+    }
+}
+
+impl ops::Sub<Self> for LdrChannel {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.0 - rhs.0)
+    }
+}
+
+impl ops::Mul<Self> for LdrChannel {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self(self.0 * rhs.0)
+    }
+}
+
+impl ops::Mul<f32> for LdrChannel {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self {
+        Self::new(self.0 * rhs)
+    }
+}
+
+impl From<Channel> for LdrChannel {
+    fn from(value: Channel) -> Self {
+        Self(value.clamp().0)
+    }
+}
+
+impl From<LdrChannel> for Channel {
+    fn from(value: LdrChannel) -> Self {
+        Channel(value.0)
+    }
+}
+
+impl From<LdrChannel> for u8 {
+    fn from(value: LdrChannel) -> Self {
+        u8::from(Channel::from(value))
+    }
+}
+
 impl ops::Add<Self> for Channel {
     type Output = Self;
 
@@ -80,7 +183,8 @@ impl ops::SubAssign<Self> for Channel {
 
 impl PartialEq<Self> for Channel {
     fn eq(&self, other: &Channel) -> bool {
-        (self.0.max(0.0).min(1.0) - other.0.max(0.0).min(1.0)).abs() < 0.004
+        let scale = self.0.abs().max(other.0.abs()).max(1.0);
+        self.0 == other.0 || (self.0 - other.0).abs() < 0.004 * scale
     }
 }
 
@@ -104,12 +208,13 @@ impl From<f32> for Channel {
 
 impl From<Channel> for u8 {
     fn from(value: Channel) -> Self {
-        (value.0.max(0.0).min(1.0) * 255.0) as u8
+        (value.clamp().0 * 255.0) as u8
     }
 }
 
+/// Clamps to 0.0 to 1.0, use `Channel::value` for the HDR value
 impl From<Channel> for f32 {
     fn from(value: Channel) -> Self {
-        value.0.max(0.0).min(1.0)
+        value.clamp().0
     }
 }
@@ -0,0 +1,357 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Color, PixelBuffer, Rgba, RgbaCanvas};
+use crate::math::Vec2f;
+use crate::Rasterizer;
+
+///
+/// Pixel value that can be partially covered by a `ClipMask`
+///
+pub trait Maskable: Copy {
+    /// Combine a new value drawn over `under` where the mask has `coverage`, from 0.0
+    /// (keep `under`) to 1.0 (take `self`)
+    fn mask(self, under: Self, coverage: f32) -> Self;
+}
+
+impl Maskable for Color {
+    fn mask(self, under: Self, coverage: f32) -> Self {
+        self * coverage + under * (1.0 - coverage)
+    }
+}
+
+impl Maskable for Rgba {
+    fn mask(self, under: Self, coverage: f32) -> Self {
+        Rgba::new(
+            self.color.mask(under.color, coverage),
+            self.alpha * coverage + under.alpha * (1.0 - coverage),
+        )
+    }
+}
+
+/// Ids cannot be mixed, so partially covered pixels take the new id from half coverage up
+impl Maskable for u32 {
+    fn mask(self, under: Self, coverage: f32) -> Self {
+        if coverage >= 0.5 {
+            self
+        } else {
+            under
+        }
+    }
+}
+
+/// Depths cannot be mixed, so partially covered pixels take the new depth from half coverage up
+impl Maskable for f32 {
+    fn mask(self, under: Self, coverage: f32) -> Self {
+        if coverage >= 0.5 {
+            self
+        } else {
+            under
+        }
+    }
+}
+
+///
+/// Rectangle of Pixels the Rasterizer may draw into, `x0..x1` by `y0..y1`.
+///
+/// Every buffer has one, covering the whole buffer unless narrowed with
+/// `Canvas::set_clip_rect`, and drawing primitives clip their shapes to it.
+///
+/// ```
+/// use softrender::canvas::{Canvas, ClipRect, Color};
+/// use softrender::Rasterizer;
+///
+/// let mut canvas = Canvas::new(64, 64);
+/// canvas.set_clip_rect(Some(ClipRect::new(8, 8, 16, 16)));
+/// Rasterizer::draw_line(&mut canvas, 0, 0, 63, 63, Color::white());
+/// assert_eq!(canvas.get(8, 8), Color::white());
+/// assert_eq!(canvas.get(24, 24), Color::black());
+/// ```
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ClipRect {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl ClipRect {
+    /// Create new ClipRect `width` by `height` Pixels with top left corner (x, y)
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x0: x,
+            y0: y,
+            x1: x + width,
+            y1: y + height,
+        }
+    }
+    /// Create new ClipRect covering a whole `width` by `height` buffer
+    pub fn full(width: usize, height: usize) -> Self {
+        Self::new(0, 0, width, height)
+    }
+    /// Check if no Pixels are inside
+    pub fn is_empty(&self) -> bool {
+        self.x0 >= self.x1 || self.y0 >= self.y1
+    }
+    /// Check if Pixel (x, y) is inside
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x0 && x < self.x1 && y >= self.y0 && y < self.y1
+    }
+    /// Pixels inside both rectangles
+    pub fn intersect(&self, other: &ClipRect) -> ClipRect {
+        let (x0, y0) = (self.x0.max(other.x0), self.y0.max(other.y0));
+        ClipRect {
+            x0,
+            y0,
+            x1: self.x1.min(other.x1).max(x0),
+            y1: self.y1.min(other.y1).max(y0),
+        }
+    }
+}
+
+///
+/// Coverage per Pixel restricting where drawing lands, from 0.0 (clipped) to 1.0 (drawn).
+///
+/// Masks are built by drawing into them like any other `PixelBuffer`, so every fill the
+/// Rasterizer offers can define a clip shape.
+///
+/// ```
+/// use softrender::canvas::{Canvas, ClipMask, Color};
+/// use softrender::Rasterizer;
+///
+/// let mut canvas = Canvas::new(64, 64);
+/// let mask = ClipMask::rounded_rect(64, 64, 8, 8, 48, 48, 6.0);
+/// Rasterizer::fill_rect(&mut mask.clip(&mut canvas), 0, 0, 64, 64, Color::white());
+/// assert_eq!(canvas.get(32, 32), Color::white());
+/// assert_eq!(canvas.get(8, 8), Color::black());
+/// ```
+///
+#[derive(Clone)]
+pub struct ClipMask {
+    dimensions: (usize, usize),
+    coverage: Vec<f32>,
+}
+
+impl ClipMask {
+    /// Create new ClipMask clipping everything away
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            dimensions: (width, height),
+            coverage: vec![0.0; width * height],
+        }
+    }
+    /// Create new ClipMask letting everything through
+    pub fn full(width: usize, height: usize) -> Self {
+        let mut mask = Self::new(width, height);
+        mask.invert();
+        mask
+    }
+    /// Create new ClipMask covering the inside of the polygon through `points`
+    pub fn from_polygon(width: usize, height: usize, points: &[Vec2f]) -> Self {
+        let mut mask = Self::new(width, height);
+        Rasterizer::fill_polygon(&mut mask, points, 1.0);
+        mask
+    }
+    /// Create new ClipMask covering a rectangle with corners rounded by `radius`
+    pub fn rounded_rect(width: usize, height: usize, x: isize, y: isize, w: usize, h: usize, radius: f32) -> Self {
+        let mut mask = Self::new(width, height);
+        let radius = radius.max(0.0).min(w.min(h) as f32 / 2.0);
+        let r = radius.ceil() as usize;
+        let step = r as isize;
+        Rasterizer::fill_rect(&mut mask, x + step, y, w.saturating_sub(2 * r), h, 1.0);
+        Rasterizer::fill_rect(&mut mask, x, y + step, w, h.saturating_sub(2 * r), 1.0);
+        let (left, top) = (x as f32 + radius, y as f32 + radius);
+        let (right, bottom) = ((x + w as isize) as f32 - radius, (y + h as isize) as f32 - radius);
+        for &(cx, cy) in &[(left, top), (right, top), (left, bottom), (right, bottom)] {
+            Rasterizer::fill_circle(&mut mask, Vec2f::from_parts(cx, cy), radius, 1.0);
+        }
+        mask
+    }
+    /// Create new ClipMask from the alpha channel of `canvas`
+    pub fn from_alpha(canvas: &RgbaCanvas) -> Self {
+        let mut mask = Self::new(canvas.width(), canvas.height());
+        for y in 0..canvas.height() {
+            for x in 0..canvas.width() {
+                mask.set(x, y, canvas.get(x, y).alpha);
+            }
+        }
+        mask
+    }
+    /// Get Width of ClipMask
+    pub fn width(&self) -> usize {
+        self.dimensions.0
+    }
+    /// Get Height of ClipMask
+    pub fn height(&self) -> usize {
+        self.dimensions.1
+    }
+    /// Get coverage of Pixel at (x, y)
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        self.coverage[self.index(x, y)]
+    }
+    /// Set coverage of Pixel at (x, y), clamped to 0.0 to 1.0
+    pub fn set(&mut self, x: usize, y: usize, coverage: f32) {
+        let index = self.index(x, y);
+        self.coverage[index] = coverage.clamp(0.0, 1.0);
+    }
+    /// Swap the drawn and clipped parts
+    pub fn invert(&mut self) {
+        for coverage in self.coverage.iter_mut() {
+            *coverage = 1.0 - *coverage;
+        }
+    }
+    /// Only keep what is covered by both masks
+    pub fn intersect(&mut self, other: &ClipMask) {
+        assert_eq!(self.dimensions, other.dimensions);
+        for (coverage, other) in self.coverage.iter_mut().zip(other.coverage.iter()) {
+            *coverage *= other;
+        }
+    }
+    /// Wrap `buffer` so drawing into it is restricted to this mask
+    pub fn clip<'a, B: PixelBuffer>(&'a self, buffer: &'a mut B) -> Clipped<'a, B>
+    where
+        B::Pixel: Maskable,
+    {
+        assert_eq!(self.width(), buffer.width());
+        assert_eq!(self.height(), buffer.height());
+        Clipped { mask: self, buffer }
+    }
+    fn index(&self, x: usize, y: usize) -> usize {
+        assert!(x < self.dimensions.0);
+        assert!(y < self.dimensions.1);
+        (y * self.dimensions.0) + x
+    }
+}
+
+impl PixelBuffer for ClipMask {
+    type Pixel = f32;
+
+    fn width(&self) -> usize {
+        ClipMask::width(self)
+    }
+    fn height(&self) -> usize {
+        ClipMask::height(self)
+    }
+    fn get(&self, x: usize, y: usize) -> f32 {
+        ClipMask::get(self, x, y)
+    }
+    fn set(&mut self, x: usize, y: usize, value: f32) {
+        ClipMask::set(self, x, y, value)
+    }
+}
+
+/// Buffer whose writes are restricted by a `ClipMask`
+pub struct Clipped<'a, B: PixelBuffer> {
+    mask: &'a ClipMask,
+    buffer: &'a mut B,
+}
+
+impl<'a, B: PixelBuffer> PixelBuffer for Clipped<'a, B>
+where
+    B::Pixel: Maskable,
+{
+    type Pixel = B::Pixel;
+
+    fn width(&self) -> usize {
+        self.buffer.width()
+    }
+    fn height(&self) -> usize {
+        self.buffer.height()
+    }
+    fn clip_rect(&self) -> ClipRect {
+        self.buffer.clip_rect()
+    }
+    fn get(&self, x: usize, y: usize) -> B::Pixel {
+        self.buffer.get(x, y)
+    }
+    fn set(&mut self, x: usize, y: usize, value: B::Pixel) {
+        let coverage = self.mask.get(x, y);
+        if coverage >= 1.0 {
+            self.buffer.set(x, y, value);
+        } else if coverage > 0.0 {
+            let under = self.buffer.get(x, y);
+            self.buffer.set(x, y, value.mask(under, coverage));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClipMask, ClipRect};
+    use crate::canvas::{Canvas, Color, IdBuffer, PixelBuffer, Rgba, RgbaCanvas};
+    use crate::math::Vec2f;
+    use crate::Rasterizer;
+
+    #[test]
+    fn test_polygon_mask() {
+        let triangle = [Vec2f::from_parts(0.0, 0.0), Vec2f::from_parts(8.0, 0.0), Vec2f::from_parts(0.0, 8.0)];
+        let mask = ClipMask::from_polygon(8, 8, &triangle);
+        let mut ids = IdBuffer::new(8, 8);
+        Rasterizer::clear(&mut mask.clip(&mut ids), 2);
+        assert_eq!(ids.pick(1, 1), Some(2));
+        assert_eq!(ids.pick(7, 7), None);
+        let mut inverted = mask.clone();
+        inverted.invert();
+        let mut canvas = Canvas::new(8, 8);
+        Rasterizer::clear(&mut inverted.clip(&mut canvas), Color::white());
+        assert_eq!(canvas.get(1, 1), Color::black());
+        assert_eq!(canvas.get(7, 7), Color::white());
+        inverted.intersect(&mask);
+        assert!((0..8).all(|y| (0..8).all(|x| inverted.get(x, y) == 0.0)));
+    }
+
+    #[test]
+    fn test_alpha_mask() {
+        let mut alpha = RgbaCanvas::new(2, 1);
+        alpha.set(0, 0, Rgba::new(Color::black(), 0.25));
+        alpha.set(1, 0, Rgba::new(Color::black(), 1.0));
+        let mask = ClipMask::from_alpha(&alpha);
+        let mut canvas = Canvas::new(2, 1);
+        Rasterizer::clear(&mut mask.clip(&mut canvas), Color::white());
+        assert_eq!(canvas.get(0, 0), Color::new(0.25, 0.25, 0.25));
+        assert_eq!(canvas.get(1, 0), Color::white());
+    }
+
+    #[test]
+    fn test_rounded_rect() {
+        let mask = ClipMask::rounded_rect(16, 16, 2, 2, 12, 12, 4.0);
+        assert_eq!(mask.get(2, 2), 0.0);
+        assert_eq!(mask.get(13, 13), 0.0);
+        assert_eq!(mask.get(3, 5), 1.0);
+        assert_eq!(mask.get(8, 2), 1.0);
+        assert_eq!(mask.get(8, 14), 0.0);
+        assert_eq!(ClipMask::full(2, 2).get(1, 1), 1.0);
+    }
+
+    #[test]
+    fn test_clip_rect() {
+        let rect = ClipRect::new(2, 3, 4, 5);
+        assert!(rect.contains(2, 3) && rect.contains(5, 7));
+        assert!(!rect.contains(6, 7) && !rect.contains(5, 8) && !rect.contains(1, 3));
+        assert_eq!(rect.intersect(&ClipRect::full(4, 4)), ClipRect { x0: 2, y0: 3, x1: 4, y1: 4 });
+        assert!(rect.intersect(&ClipRect::new(10, 0, 2, 2)).is_empty());
+        // Canvases keep their clip rectangle inside themselves
+        let mut canvas = Canvas::new(8, 8);
+        assert_eq!(canvas.clip_rect(), ClipRect::full(8, 8));
+        canvas.set_clip_rect(Some(ClipRect::new(4, 4, 10, 10)));
+        assert_eq!(canvas.clip_rect(), ClipRect::new(4, 4, 4, 4));
+        assert_eq!(ClipMask::full(8, 8).clip(&mut canvas).clip_rect(), ClipRect::new(4, 4, 4, 4));
+        assert_eq!(IdBuffer::new(8, 8).clip_rect(), ClipRect::full(8, 8));
+        canvas.set_clip_rect(None);
+        assert_eq!(canvas.clip_rect(), ClipRect::full(8, 8));
+    }
+}
@@ -14,11 +14,12 @@
 // limitations under the License.
 //
 
-use super::Channel;
-use std::{fmt, ops};
+use super::{Channel, LdrChannel};
+use std::{fmt, iter, ops};
 
 ///
-/// Color
+/// Linear high dynamic range Color, whose channels may exceed 1.0 and are only clamped
+/// on output or by `clamp` and `saturating_add`
 ///
 #[derive(Copy, Clone)]
 pub struct Color {
@@ -28,44 +29,87 @@ pub struct Color {
 }
 
 impl Color {
+    /// Color `Black` (0.0, 0.0, 0.0)
+    pub const BLACK: Color = Color::new(0.0, 0.0, 0.0);
+    /// Color `Grey` (0.5, 0.5, 0.5)
+    pub const GREY: Color = Color::new(0.5, 0.5, 0.5);
+    /// Color `White` (1.0, 1.0, 1.0)
+    pub const WHITE: Color = Color::new(1.0, 1.0, 1.0);
+    /// Color `Red` (1.0, 0.0, 0.0)
+    pub const RED: Color = Color::new(1.0, 0.0, 0.0);
+    /// Color `Green` (0.0, 1.0, 0.0)
+    pub const GREEN: Color = Color::new(0.0, 1.0, 0.0);
+    /// Color `Blue` (0.0, 0.0, 1.0)
+    pub const BLUE: Color = Color::new(0.0, 0.0, 1.0);
+    /// Color `Yellow` (1.0, 1.0, 0.0)
+    pub const YELLOW: Color = Color::new(1.0, 1.0, 0.0);
+    /// Color `Cyan` (0.0, 1.0, 1.0)
+    pub const CYAN: Color = Color::new(0.0, 1.0, 1.0);
+    /// Color `Magenta` (1.0, 0.0, 1.0)
+    pub const MAGENTA: Color = Color::new(1.0, 0.0, 1.0);
+
     /// Create a new Custom Color
-    pub fn new(r: f32, g: f32, b: f32) -> Self {
+    pub const fn new(r: f32, g: f32, b: f32) -> Self {
         Self {
-            r: Channel::from(r),
-            g: Channel::from(g),
-            b: Channel::from(b),
+            r: Channel::new(r),
+            g: Channel::new(g),
+            b: Channel::new(b),
         }
     }
     /// Create Color `Black` (0.0, 0.0, 0.0)
-    pub fn black() -> Self { Self::new(0.0, 0.0, 0.0) }
+    pub const fn black() -> Self { Self::new(0.0, 0.0, 0.0) }
     /// Create Color `Dark Red` (0.5, 0.0, 0.0)
-    pub fn dark_red() -> Self { Self::new(0.5, 0.0, 0.0) }
+    pub const fn dark_red() -> Self { Self::new(0.5, 0.0, 0.0) }
     /// Create Color `Dark Green` (0.0, 0.5, 0.0)
-    pub fn dark_green() -> Self { Self::new(0.0, 0.5, 0.0) }
+    pub const fn dark_green() -> Self { Self::new(0.0, 0.5, 0.0) }
     /// Create Color `Dark Blue` (0.0, 0.0, 0.5)
-    pub fn dark_blue() -> Self { Self::new(0.0, 0.0, 0.5) }
+    pub const fn dark_blue() -> Self { Self::new(0.0, 0.0, 0.5) }
     /// Create Color `Dark Yellow` (0.5, 0.5, 0.0)
-    pub fn dark_yellow() -> Self { Self::new(0.5, 0.5, 0.0) }
+    pub const fn dark_yellow() -> Self { Self::new(0.5, 0.5, 0.0) }
     /// Create Color `Dark Yellow` (0.0, 0.5, 0.5)
-    pub fn dark_cyan() -> Self { Self::new(0.0, 0.5, 0.5) }
+    pub const fn dark_cyan() -> Self { Self::new(0.0, 0.5, 0.5) }
     /// Create Color `Dark Yellow` (0.5, 0.0, 0.5)
-    pub fn dark_magenta() -> Self { Self::new(0.5, 0.0, 0.5) }
+    pub const fn dark_magenta() -> Self { Self::new(0.5, 0.0, 0.5) }
     /// Create Color `Grey` (0.5, 0.5, 0.5)
-    pub fn grey() -> Self { Self::new(0.5, 0.5, 0.5) }
+    pub const fn grey() -> Self { Self::new(0.5, 0.5, 0.5) }
     /// Create Color `Bright Red` (0.1, 0.0, 0.0)
-    pub fn bright_red() -> Self { Self::new(1.0, 0.0, 0.0) }
+    pub const fn bright_red() -> Self { Self::new(1.0, 0.0, 0.0) }
     /// Create Color `Bright Green` (0.0, 1.0, 0.0)
-    pub fn bright_green() -> Self { Self::new(0.0, 1.0, 0.0) }
+    pub const fn bright_green() -> Self { Self::new(0.0, 1.0, 0.0) }
     /// Create Color `Bright Blue` (0.0, 0.0, 1.0)
-    pub fn bright_blue() -> Self { Self::new(0.0, 0.0, 1.0) }
+    pub const fn bright_blue() -> Self { Self::new(0.0, 0.0, 1.0) }
     /// Create Color `Bright Yellow` (1.0, 1.0, 0.0)
-    pub fn bright_yellow() -> Self { Self::new(1.0, 1.0, 0.0) }
+    pub const fn bright_yellow() -> Self { Self::new(1.0, 1.0, 0.0) }
     /// Create Color `Bright Cyan` (0.0, 1.0, 1.0)
-    pub fn bright_cyan() -> Self { Self::new(0.0, 1.0, 1.0) }
+    pub const fn bright_cyan() -> Self { Self::new(0.0, 1.0, 1.0) }
     /// Create Color `Bright Magenta` (1.0, 0.0, 1.0)
-    pub fn bright_magenta() -> Self { Self::new(1.0, 0.0, 1.0) }
+    pub const fn bright_magenta() -> Self { Self::new(1.0, 0.0, 1.0) }
     /// Create Color `White` (1.0, 1.0, 1.0)
-    pub fn white() -> Self { Self::new(1.0, 1.0, 1.0) }
+    pub const fn white() -> Self { Self::new(1.0, 1.0, 1.0) }
+    /// Get the relative luminance of a linear Color using the Rec. 709 weights
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.r.value() + 0.7152 * self.g.value() + 0.0722 * self.b.value()
+    }
+    /// This Color with every channel limited to 0.0 to 1.0
+    pub fn clamp(&self) -> Self {
+        Self {
+            r: self.r.clamp(),
+            g: self.g.clamp(),
+            b: self.b.clamp(),
+        }
+    }
+    /// Sum of this and `rhs` with every channel limited to 0.0 to 1.0
+    pub fn saturating_add(&self, rhs: Self) -> Self {
+        Self {
+            r: self.r.saturating_add(rhs.r),
+            g: self.g.saturating_add(rhs.g),
+            b: self.b.saturating_add(rhs.b),
+        }
+    }
+    /// Check if no channel is infinite or NaN, such as from a division by zero in a shader
+    pub fn is_finite(&self) -> bool {
+        self.r.is_finite() && self.g.is_finite() && self.b.is_finite()
+    }
     pub fn to_rgba(&self) -> u32 {
         let r = (u8::from(self.r) as u32) << 24;
         let g = (u8::from(self.g) as u32) << 16;
@@ -83,6 +127,71 @@ impl Color {
 }
 
 
+///
+/// Low dynamic range Color, every channel kept within 0.0 to 1.0
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct LdrColor {
+    pub r: LdrChannel,
+    pub g: LdrChannel,
+    pub b: LdrChannel,
+}
+
+impl LdrColor {
+    /// Create new LdrColor, clamping each channel
+    pub fn new(r: f32, g: f32, b: f32) -> Self {
+        Self {
+            r: LdrChannel::new(r),
+            g: LdrChannel::new(g),
+            b: LdrChannel::new(b),
+        }
+    }
+}
+
+impl ops::Add<Self> for LdrColor {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            r: self.r + rhs.r,
+            g: self.g + rhs.g,
+            b: self.b + rhs.b,
+        }
+    }
+}
+
+impl ops::Mul<f32> for LdrColor {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self {
+        Self {
+            r: self.r * rhs,
+            g: self.g * rhs,
+            b: self.b * rhs,
+        }
+    }
+}
+
+impl From<Color> for LdrColor {
+    fn from(color: Color) -> Self {
+        Self {
+            r: color.r.into(),
+            g: color.g.into(),
+            b: color.b.into(),
+        }
+    }
+}
+
+impl From<LdrColor> for Color {
+    fn from(color: LdrColor) -> Self {
+        Self {
+            r: color.r.into(),
+            g: color.g.into(),
+            b: color.b.into(),
+        }
+    }
+}
+
 impl Default for Color {
     fn default() -> Self {
         Self::new(0.0, 0.0, 0.0)
@@ -175,6 +284,30 @@ impl ops::SubAssign<Self> for Color {
     }
 }
 
+impl iter::Sum for Color {
+    fn sum<I: Iterator<Item=Self>>(iter: I) -> Self {
+        iter.fold(Color::black(), |acc, c| acc + c)
+    }
+}
+
+impl<'a> iter::Sum<&'a Color> for Color {
+    fn sum<I: Iterator<Item=&'a Self>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+impl iter::Product for Color {
+    fn product<I: Iterator<Item=Self>>(iter: I) -> Self {
+        iter.fold(Color::white(), |acc, c| acc * c)
+    }
+}
+
+impl<'a> iter::Product<&'a Color> for Color {
+    fn product<I: Iterator<Item=&'a Self>>(iter: I) -> Self {
+        iter.copied().product()
+    }
+}
+
 impl fmt::Display for Color {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Color {{ r: {}, g: {}, b: {} }}", self.r, self.g, self.b)
@@ -184,9 +317,9 @@ impl fmt::Display for Color {
 impl fmt::Debug for Color {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Color {{ r: {} ({}), g: {} ({}), b: {} ({}) }}",
-               f32::from(self.r), u8::from(self.r),
-               f32::from(self.g), u8::from(self.g),
-               f32::from(self.b), u8::from(self.b),
+               self.r.value(), u8::from(self.r),
+               self.g.value(), u8::from(self.g),
+               self.b.value(), u8::from(self.b),
         )
     }
 }
@@ -194,13 +327,39 @@ impl fmt::Debug for Color {
 
 #[cfg(test)]
 mod tests {
-    use super::Color;
+    use super::{Color, LdrColor};
 
     #[test]
     fn test_color_equality() {
         assert_eq!(Color::new(0.0, 0.0, 0.0), Color::new(0.0, 0.0, 0.0));
         assert_eq!(Color::new(0.25, 0.50, 0.75), Color::new(0.25, 0.50, 0.75));
-        assert_eq!(Color::new(-5.0, 0.5, 5.0), Color::new(0.0, 0.5, 1.0));
+        // Differences outside 0.0 to 1.0 are not hidden
+        assert_ne!(Color::new(-5.0, 0.5, 5.0), Color::new(0.0, 0.5, 1.0));
+        assert_ne!(Color::new(1.0, 1.0, 1.0), Color::new(2.0, 1.0, 1.0));
+        assert_eq!(Color::new(1000.0, 0.0, 0.0), Color::new(1000.5, 0.0, 0.0));
+        assert_eq!(Color::new(-5.0, 0.5, 5.0).clamp(), Color::new(0.0, 0.5, 1.0));
+    }
+
+    #[test]
+    fn test_saturating() {
+        let c1 = Color::new(0.9, 0.6, 0.75);
+        let c2 = Color::new(0.7, 0.1, 0.25);
+        assert_eq!(c1.saturating_add(c2), Color::new(1.0, 0.7, 1.0));
+        assert_eq!(c2.r.saturating_sub(c1.r).value(), 0.0);
+        assert!(c1.is_finite());
+        assert!(!(c1 * f32::INFINITY).is_finite());
+        assert!(!Color::new(f32::NAN, 0.0, 0.0).is_finite());
+        assert_eq!(Color::new(f32::NAN, 2.0, 0.5).clamp(), Color::new(0.0, 1.0, 0.5));
+    }
+
+    #[test]
+    fn test_ldr_color() {
+        let ldr = LdrColor::from(Color::new(1.6, -0.5, 0.25));
+        assert_eq!(ldr, LdrColor::new(1.0, 0.0, 0.25));
+        assert_eq!(ldr + LdrColor::new(0.5, 0.5, 0.5), LdrColor::new(1.0, 0.5, 0.75));
+        assert_eq!((ldr * 8.0).b.value(), 1.0);
+        assert_eq!(Color::from(ldr), Color::new(1.0, 0.0, 0.25));
+        assert_eq!(u8::from(ldr.b), 63);
     }
 
     #[test]
@@ -229,4 +388,34 @@ mod tests {
         let c2 = Color::new(0.9, 1.0, 0.1);
         assert_eq!(c1 * c2, Color::new(0.9, 0.2, 0.04));
     }
+
+    #[test]
+    fn test_color_sum() {
+        let samples = vec![
+            Color::new(0.1, 0.2, 0.3),
+            Color::new(0.3, 0.2, 0.1),
+            Color::new(0.2, 0.2, 0.2),
+        ];
+        assert_eq!(samples.iter().sum::<Color>(), Color::new(0.6, 0.6, 0.6));
+        assert_eq!(samples.into_iter().sum::<Color>() * (1.0 / 3.0), Color::new(0.2, 0.2, 0.2));
+        assert_eq!(Vec::<Color>::new().into_iter().sum::<Color>(), Color::black());
+    }
+
+    #[test]
+    fn test_color_product() {
+        let samples = [Color::new(1.0, 0.5, 0.2), Color::new(0.5, 0.5, 0.5)];
+        assert_eq!(samples.iter().product::<Color>(), Color::new(0.5, 0.25, 0.1));
+        assert_eq!(Vec::<Color>::new().into_iter().product::<Color>(), Color::white());
+    }
+    #[test]
+    fn test_color_constants() {
+        const PALETTE: [Color; 3] = [Color::BLACK, Color::new(0.25, 0.5, 0.75), Color::bright_red()];
+        static GROUND: Color = Color::GREY;
+        assert_eq!(PALETTE[0], Color::black());
+        assert_eq!(PALETTE[1].g.value(), 0.5);
+        assert_eq!(PALETTE[2], Color::RED);
+        assert_eq!(GROUND, Color::grey());
+        assert_eq!(Color::WHITE, Color::white());
+        assert_eq!(Color::MAGENTA, Color::bright_magenta());
+    }
 }
\ No newline at end of file
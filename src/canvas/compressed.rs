@@ -0,0 +1,344 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Canvas, Color, Half};
+use std::cmp::Ordering;
+use std::mem::size_of;
+
+/// Side of the square blocks of `Compression::Block`
+const BLOCK_SIZE: usize = 4;
+/// Most colors in a `Compression::Palette` palette
+const PALETTE_SIZE: usize = 256;
+
+/// How a `CompressedImage` stores its texels
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// Up to 256 colors chosen by median cut and a byte per texel indexing them. Exact for
+    /// images with few colors, such as pixel art and flat shaded textures
+    Palette,
+    /// 4x4 blocks storing two half precision endpoint colors and a 2 bit index per texel
+    /// picking one of four colors between them, a byte per texel whatever the content.
+    /// Similar to BC1 with HDR endpoints, best for photographic textures
+    Block,
+}
+
+/// Two endpoints and sixteen 2 bit indices
+#[derive(Copy, Clone)]
+struct Block {
+    endpoints: [[Half; 3]; 2],
+    indices: u32,
+}
+
+#[derive(Clone)]
+enum Encoded {
+    Palette { palette: Vec<Color>, indices: Vec<u8> },
+    Block(Vec<Block>),
+}
+
+///
+/// Image stored with lossy `Compression`, decoded a texel or block at a time as it is
+/// read rather than kept decoded in memory.
+///
+/// ```
+/// use softrender::canvas::{Canvas, Color, CompressedImage, Compression};
+///
+/// let mut image = Canvas::new(64, 64);
+/// image.fill(Color::bright_cyan());
+/// let compressed = CompressedImage::new(&image, Compression::Palette);
+/// assert_eq!(compressed.get(10, 20), Color::bright_cyan());
+/// assert!(compressed.memory() * 8 < 64 * 64 * std::mem::size_of::<Color>());
+/// ```
+///
+#[derive(Clone)]
+pub struct CompressedImage {
+    dimensions: (usize, usize),
+    encoded: Encoded,
+}
+
+impl CompressedImage {
+    /// Compress `image` with `compression`
+    pub fn new(image: &Canvas, compression: Compression) -> CompressedImage {
+        let encoded = match compression {
+            Compression::Palette => palettize(image),
+            Compression::Block => Encoded::Block(encode_blocks(image)),
+        };
+        CompressedImage {
+            dimensions: (image.width(), image.height()),
+            encoded,
+        }
+    }
+    /// Get Width in texels
+    pub fn width(&self) -> usize {
+        self.dimensions.0
+    }
+    /// Get Height in texels
+    pub fn height(&self) -> usize {
+        self.dimensions.1
+    }
+    /// Compression in use
+    pub fn compression(&self) -> Compression {
+        match self.encoded {
+            Encoded::Palette { .. } => Compression::Palette,
+            Encoded::Block(_) => Compression::Block,
+        }
+    }
+    /// Decode texel (x, y)
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        assert!(x < self.dimensions.0 && y < self.dimensions.1);
+        match &self.encoded {
+            Encoded::Palette { palette, indices } => palette[usize::from(indices[y * self.dimensions.0 + x])],
+            Encoded::Block(blocks) => {
+                let block = &blocks[self.block_index(x / BLOCK_SIZE, y / BLOCK_SIZE)];
+                decode_texel(block, (y % BLOCK_SIZE) * BLOCK_SIZE + x % BLOCK_SIZE)
+            }
+        }
+    }
+    /// Decode the 4x4 texels with their top left corner at (4 `bx`, 4 `by`), row by row.
+    /// Texels past the right or bottom edge repeat the last column or row
+    pub fn decode_block(&self, bx: usize, by: usize) -> [Color; 16] {
+        if let Encoded::Block(blocks) = &self.encoded {
+            let block = &blocks[self.block_index(bx, by)];
+            let mut texels = [Color::black(); 16];
+            for (index, texel) in texels.iter_mut().enumerate() {
+                *texel = decode_texel(block, index);
+            }
+            return texels;
+        }
+        let mut texels = [Color::black(); 16];
+        for (index, texel) in texels.iter_mut().enumerate() {
+            let x = (bx * BLOCK_SIZE + index % BLOCK_SIZE).min(self.dimensions.0 - 1);
+            let y = (by * BLOCK_SIZE + index / BLOCK_SIZE).min(self.dimensions.1 - 1);
+            *texel = self.get(x, y);
+        }
+        texels
+    }
+    /// Decode every texel into a new `Canvas`
+    pub fn to_canvas(&self) -> Canvas {
+        let mut canvas = Canvas::new(self.width(), self.height());
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                canvas.set(x, y, self.get(x, y));
+            }
+        }
+        canvas
+    }
+    /// Bytes of heap memory used
+    pub fn memory(&self) -> usize {
+        match &self.encoded {
+            Encoded::Palette { palette, indices } => palette.len() * size_of::<Color>() + indices.len(),
+            Encoded::Block(blocks) => blocks.len() * size_of::<Block>(),
+        }
+    }
+    fn block_index(&self, bx: usize, by: usize) -> usize {
+        let blocks_across = self.dimensions.0.div_ceil(BLOCK_SIZE);
+        by * blocks_across + bx
+    }
+}
+
+fn channels(color: Color) -> [f32; 3] {
+    [color.r.value(), color.g.value(), color.b.value()]
+}
+
+/// Median cut quantization, splitting the box of colors with the widest channel range
+/// until there are enough boxes, then using the mean of each box
+fn palettize(image: &Canvas) -> Encoded {
+    let (width, height) = (image.width(), image.height());
+    let colors: Vec<[f32; 3]> = (0..width * height).map(|i| channels(image.get(i % width, i / width))).collect();
+    let widest = |members: &[usize]| {
+        let mut low = [f32::INFINITY; 3];
+        let mut high = [f32::NEG_INFINITY; 3];
+        for &member in members {
+            for (channel, &value) in colors[member].iter().enumerate() {
+                low[channel] = low[channel].min(value);
+                high[channel] = high[channel].max(value);
+            }
+        }
+        let ranges = (0..3).map(|channel| (channel, high[channel] - low[channel]));
+        ranges.fold((0, 0.0), |best, range| if range.1 > best.1 { range } else { best })
+    };
+    let all: Vec<usize> = (0..colors.len()).collect();
+    let mut boxes = vec![(widest(&all), all)];
+    while boxes.len() < PALETTE_SIZE {
+        let index = match boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, ((_, range), _))| *range > 0.0)
+            .max_by(|a, b| (a.1).0 .1.partial_cmp(&(b.1).0 .1).unwrap_or(Ordering::Equal))
+        {
+            Some((index, _)) => index,
+            None => break,
+        };
+        let ((channel, _), mut members) = boxes.swap_remove(index);
+        members.sort_by(|&a, &b| colors[a][channel].partial_cmp(&colors[b][channel]).unwrap_or(Ordering::Equal));
+        // Split where the value changes closest to the median so equal colors stay together
+        let differs = |at: usize| colors[members[at - 1]][channel] != colors[members[at]][channel];
+        let middle = members.len() / 2;
+        let split = (0..members.len())
+            .flat_map(|offset| vec![middle + offset, middle.wrapping_sub(offset)])
+            .find(|&at| at > 0 && at < members.len() && differs(at))
+            .unwrap_or(middle);
+        let upper = members.split_off(split);
+        boxes.push((widest(&members), members));
+        boxes.push((widest(&upper), upper));
+    }
+    let mut palette = Vec::with_capacity(boxes.len());
+    let mut indices = vec![0; colors.len()];
+    for (entry, (_, members)) in boxes.iter().enumerate() {
+        let mut sum = [0.0; 3];
+        for &member in members {
+            for (total, value) in sum.iter_mut().zip(&colors[member]) {
+                *total += value;
+            }
+            indices[member] = entry as u8;
+        }
+        let count = members.len().max(1) as f32;
+        palette.push(Color::new(sum[0] / count, sum[1] / count, sum[2] / count));
+    }
+    Encoded::Palette { palette, indices }
+}
+
+fn encode_blocks(image: &Canvas) -> Vec<Block> {
+    let (width, height) = (image.width(), image.height());
+    let mut blocks = Vec::new();
+    for by in (0..height).step_by(BLOCK_SIZE) {
+        for bx in (0..width).step_by(BLOCK_SIZE) {
+            let mut texels = [[0.0; 3]; 16];
+            for (index, texel) in texels.iter_mut().enumerate() {
+                let x = (bx + index % BLOCK_SIZE).min(width - 1);
+                let y = (by + index / BLOCK_SIZE).min(height - 1);
+                *texel = channels(image.get(x, y));
+            }
+            blocks.push(encode_block(&texels));
+        }
+    }
+    blocks
+}
+
+/// Pick endpoints at the corners of the bounding box of the block's colors along the
+/// diagonal that follows how the channels vary together, then index each texel
+fn encode_block(texels: &[[f32; 3]; 16]) -> Block {
+    let mut low = [f32::INFINITY; 3];
+    let mut high = [f32::NEG_INFINITY; 3];
+    let mut mean = [0.0; 3];
+    for texel in texels {
+        for channel in 0..3 {
+            low[channel] = low[channel].min(texel[channel]);
+            high[channel] = high[channel].max(texel[channel]);
+            mean[channel] += texel[channel] / 16.0;
+        }
+    }
+    let primary = (0..3).fold(0, |best, c| if high[c] - low[c] > high[best] - low[best] { c } else { best });
+    for channel in 0..3 {
+        let covariance: f32 = texels
+            .iter()
+            .map(|t| (t[primary] - mean[primary]) * (t[channel] - mean[channel]))
+            .sum();
+        if covariance < 0.0 {
+            std::mem::swap(&mut low[channel], &mut high[channel]);
+        }
+    }
+    let endpoints = [
+        [Half::from_f32(low[0]), Half::from_f32(low[1]), Half::from_f32(low[2])],
+        [Half::from_f32(high[0]), Half::from_f32(high[1]), Half::from_f32(high[2])],
+    ];
+    let (start, end) = (endpoint(&endpoints[0]), endpoint(&endpoints[1]));
+    let axis = [end[0] - start[0], end[1] - start[1], end[2] - start[2]];
+    let length = axis.iter().map(|a| a * a).sum::<f32>();
+    let mut indices = 0;
+    if length > 0.0 {
+        for (index, texel) in texels.iter().enumerate() {
+            let t = (0..3).map(|c| (texel[c] - start[c]) * axis[c]).sum::<f32>() / length;
+            let step = (t.clamp(0.0, 1.0) * 3.0).round() as u32;
+            indices |= step << (2 * index);
+        }
+    }
+    Block { endpoints, indices }
+}
+
+fn endpoint(halves: &[Half; 3]) -> [f32; 3] {
+    [halves[0].to_f32(), halves[1].to_f32(), halves[2].to_f32()]
+}
+
+fn decode_texel(block: &Block, index: usize) -> Color {
+    let (start, end) = (endpoint(&block.endpoints[0]), endpoint(&block.endpoints[1]));
+    let t = ((block.indices >> (2 * index)) & 3) as f32 / 3.0;
+    let mix = |c: usize| start[c] + (end[c] - start[c]) * t;
+    Color::new(mix(0), mix(1), mix(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CompressedImage, Compression};
+    use crate::canvas::{Canvas, Color};
+
+    fn gradient(width: usize, height: usize) -> Canvas {
+        let mut canvas = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let (u, v) = (x as f32 / width as f32, y as f32 / height as f32);
+                canvas.set(x, y, Color::new(u, 1.0 - u, v));
+            }
+        }
+        canvas
+    }
+
+    fn max_error(a: &Canvas, b: &CompressedImage) -> f32 {
+        a.pixels()
+            .map(|(x, y, c)| {
+                let d = c - b.get(x, y);
+                d.r.value().abs().max(d.g.value().abs()).max(d.b.value().abs())
+            })
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn test_palette() {
+        // Few colors are kept exactly
+        let mut image = Canvas::new(9, 7);
+        for (x, y, _) in Canvas::new(9, 7).pixels() {
+            image.set(x, y, Color::new((x % 3) as f32, (y % 5) as f32 * 0.25, 0.5));
+        }
+        let compressed = CompressedImage::new(&image, Compression::Palette);
+        assert_eq!(compressed.compression(), Compression::Palette);
+        assert_eq!(max_error(&image, &compressed), 0.0);
+        // Many colors are approximated by 256
+        let image = gradient(64, 64);
+        let compressed = CompressedImage::new(&image, Compression::Palette);
+        assert!(max_error(&image, &compressed) < 0.05);
+        assert_eq!(compressed.memory(), 256 * 12 + 64 * 64);
+    }
+
+    #[test]
+    fn test_block() {
+        let image = gradient(30, 18);
+        let compressed = CompressedImage::new(&image, Compression::Block);
+        assert_eq!((compressed.width(), compressed.height()), (30, 18));
+        assert!(max_error(&image, &compressed) < 0.1);
+        // A byte per texel, rounded up to whole blocks
+        assert_eq!(compressed.memory(), 8 * 5 * 16);
+        let block = compressed.decode_block(7, 4);
+        assert_eq!(block[0], compressed.get(28, 16));
+        assert_eq!(block[15], compressed.get(29, 17));
+        // Opposing channels are kept apart rather than blended to grey
+        let mut stripes = Canvas::new(4, 4);
+        for (x, y, _) in Canvas::new(4, 4).pixels() {
+            stripes.set(x, y, if x < 2 { Color::RED } else { Color::GREEN });
+        }
+        let compressed = CompressedImage::new(&stripes, Compression::Block);
+        assert_eq!(compressed.to_canvas().get(0, 0), Color::RED);
+        assert_eq!(compressed.to_canvas().get(3, 3), Color::GREEN);
+    }
+}
@@ -0,0 +1,192 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::PixelBuffer;
+
+/// Comparison deciding if a new depth passes against the one stored
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DepthFunc {
+    /// Never passes
+    Never,
+    /// Passes if closer than the stored depth
+    #[default]
+    Less,
+    /// Passes if closer than or as close as the stored depth
+    LEqual,
+    /// Passes if exactly at the stored depth
+    Equal,
+    /// Passes if as far as or farther than the stored depth
+    GEqual,
+    /// Passes if farther than the stored depth
+    Greater,
+    /// Passes if anywhere but at the stored depth
+    NotEqual,
+    /// Always passes, drawing in submission order
+    Always,
+}
+
+impl DepthFunc {
+    /// Check if `depth` passes against `stored`
+    pub fn test(self, depth: f32, stored: f32) -> bool {
+        match self {
+            DepthFunc::Never => false,
+            DepthFunc::Less => depth < stored,
+            DepthFunc::LEqual => depth <= stored,
+            DepthFunc::Equal => depth == stored,
+            DepthFunc::GEqual => depth >= stored,
+            DepthFunc::Greater => depth > stored,
+            DepthFunc::NotEqual => depth != stored,
+            DepthFunc::Always => true,
+        }
+    }
+}
+
+///
+/// Depth per Pixel for resolving which of overlapping surfaces is seen.
+///
+/// Smaller depths are closer. Depths start out infinitely far, so with the default
+/// `DepthFunc::Less` whatever is drawn first passes, and `Rasterizer::fill_triangle_depth`
+/// then only writes the Pixels of a triangle that pass `func`.
+///
+/// ```
+/// use softrender::canvas::{Canvas, Color, DepthBuffer};
+/// use softrender::math::Vec3f;
+/// use softrender::Rasterizer;
+///
+/// let mut canvas = Canvas::new(8, 8);
+/// let mut depth = DepthBuffer::new(8, 8);
+/// let (a, b, c) = (Vec3f::new(0.0, 0.0, 1.0), Vec3f::new(8.0, 0.0, 1.0), Vec3f::new(0.0, 8.0, 1.0));
+/// Rasterizer::fill_triangle_depth(&mut canvas, &mut depth, a, b, c, Color::white());
+/// // Farther away, so hidden where it overlaps
+/// let (a, b, c) = (Vec3f::new(0.0, 0.0, 2.0), Vec3f::new(8.0, 0.0, 2.0), Vec3f::new(8.0, 8.0, 2.0));
+/// Rasterizer::fill_triangle_depth(&mut canvas, &mut depth, a, b, c, Color::bright_red());
+/// assert_eq!(canvas.get(1, 0), Color::white());
+/// assert_eq!(canvas.get(7, 6), Color::bright_red());
+/// assert_eq!(depth.get(7, 6), 2.0);
+/// ```
+///
+#[derive(Clone)]
+pub struct DepthBuffer {
+    dimensions: (usize, usize),
+    depths: Vec<f32>,
+    /// Comparison new depths have to pass
+    pub func: DepthFunc,
+    /// Store the depth of Pixels that pass, turned off to test against what is there
+    /// without changing it, such as for transparent surfaces
+    pub write: bool,
+}
+
+impl DepthBuffer {
+    /// Depth of Pixels nothing was drawn to
+    pub const FAR: f32 = f32::INFINITY;
+
+    /// Create new DepthBuffer filled with `FAR`, testing with `DepthFunc::Less`
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            dimensions: (width, height),
+            depths: vec![Self::FAR; width * height],
+            func: DepthFunc::default(),
+            write: true,
+        }
+    }
+    /// Get Width of DepthBuffer
+    pub fn width(&self) -> usize {
+        self.dimensions.0
+    }
+    /// Get Height of DepthBuffer
+    pub fn height(&self) -> usize {
+        self.dimensions.1
+    }
+    /// Get depth of Pixel at (x, y)
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        assert!(x < self.dimensions.0);
+        assert!(y < self.dimensions.1);
+        self.depths[(y * self.dimensions.0) + x]
+    }
+    /// Set depth of Pixel at (x, y)
+    pub fn set(&mut self, x: usize, y: usize, depth: f32) {
+        assert!(x < self.dimensions.0);
+        assert!(y < self.dimensions.1);
+        self.depths[(y * self.dimensions.0) + x] = depth;
+    }
+    /// Reset every Pixel to `depth`, such as `FAR` or negative infinity for
+    /// `DepthFunc::Greater`
+    pub fn clear(&mut self, depth: f32) {
+        for stored in self.depths.iter_mut() {
+            *stored = depth;
+        }
+    }
+    /// Check if `depth` passes `func` at (x, y), storing it if it does and `write` is set
+    pub fn test(&mut self, x: usize, y: usize, depth: f32) -> bool {
+        let passed = self.func.test(depth, self.get(x, y));
+        if passed && self.write {
+            self.set(x, y, depth);
+        }
+        passed
+    }
+}
+
+impl PixelBuffer for DepthBuffer {
+    type Pixel = f32;
+
+    fn width(&self) -> usize {
+        DepthBuffer::width(self)
+    }
+    fn height(&self) -> usize {
+        DepthBuffer::height(self)
+    }
+    fn get(&self, x: usize, y: usize) -> f32 {
+        DepthBuffer::get(self, x, y)
+    }
+    fn set(&mut self, x: usize, y: usize, value: f32) {
+        DepthBuffer::set(self, x, y, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DepthBuffer, DepthFunc};
+
+    #[test]
+    fn test_depth_funcs() {
+        let passes = |func: DepthFunc| [1.0, 2.0, 3.0].map(|depth| func.test(depth, 2.0));
+        assert_eq!(passes(DepthFunc::Never), [false, false, false]);
+        assert_eq!(passes(DepthFunc::Less), [true, false, false]);
+        assert_eq!(passes(DepthFunc::LEqual), [true, true, false]);
+        assert_eq!(passes(DepthFunc::Equal), [false, true, false]);
+        assert_eq!(passes(DepthFunc::GEqual), [false, true, true]);
+        assert_eq!(passes(DepthFunc::Greater), [false, false, true]);
+        assert_eq!(passes(DepthFunc::NotEqual), [true, false, true]);
+        assert_eq!(passes(DepthFunc::Always), [true, true, true]);
+    }
+
+    #[test]
+    fn test_depth_test() {
+        let mut depth = DepthBuffer::new(2, 2);
+        assert!(depth.test(1, 1, 5.0));
+        assert!(!depth.test(1, 1, 6.0));
+        assert_eq!(depth.get(1, 1), 5.0);
+        depth.write = false;
+        assert!(depth.test(1, 1, 4.0));
+        assert_eq!(depth.get(1, 1), 5.0);
+        depth.func = DepthFunc::Greater;
+        depth.clear(f32::NEG_INFINITY);
+        depth.write = true;
+        assert!(depth.test(0, 0, -3.0));
+        assert!(!depth.test(0, 0, -4.0));
+        assert_eq!(depth.get(0, 0), -3.0);
+    }
+}
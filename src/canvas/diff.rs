@@ -0,0 +1,283 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+use super::{Canvas, Color, PixelBuffer};
+
+/// Side of the square windows SSIM is computed over
+const SSIM_WINDOW: usize = 8;
+
+/// How two images are normalized before they are compared
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DiffOptions {
+    /// Scale the second image so its mean luminance matches the first, hiding a global
+    /// exposure difference
+    pub match_exposure: bool,
+    /// Scale each channel of the second image so its mean matches the first, hiding a
+    /// white balance difference. Implies `match_exposure`.
+    pub match_white_balance: bool,
+    /// Error shown as white in the heat map, smaller errors ramp up from black through red
+    /// and yellow
+    pub heat_scale: f32,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            match_exposure: false,
+            match_white_balance: false,
+            heat_scale: 0.25,
+        }
+    }
+}
+
+///
+/// Difference between two images of the same size.
+///
+/// Errors are measured on the unclamped linear channels, with PSNR taking 1.0 as the peak
+/// value. SSIM is computed on luminance over 8 by 8 windows and averaged.
+///
+/// ```
+/// use softrender::canvas::{Canvas, Color, DiffOptions, ImageDiff};
+///
+/// let mut reference = Canvas::new(16, 16);
+/// reference.fill(Color::new(0.2, 0.4, 0.6));
+/// let mut brighter = Canvas::new(16, 16);
+/// brighter.fill(Color::new(0.4, 0.8, 1.2));
+/// assert!(ImageDiff::new(&reference, &brighter).mse > 0.0);
+/// let options = DiffOptions { match_exposure: true, ..DiffOptions::default() };
+/// assert!(ImageDiff::with_options(&reference, &brighter, &options).mse < 1e-6);
+/// ```
+///
+#[derive(Clone)]
+pub struct ImageDiff {
+    /// Largest per Pixel absolute error, averaged over the channels
+    pub max_error: f32,
+    /// Mean per Pixel absolute error
+    pub mean_error: f32,
+    /// Mean squared error over every channel
+    pub mse: f32,
+    /// Peak signal to noise ratio in decibels, infinite for identical images
+    pub psnr: f32,
+    /// Structural similarity, 1.0 for identical images
+    pub ssim: f32,
+    /// Per Pixel absolute error as a heat map
+    pub heat_map: Canvas,
+}
+
+impl ImageDiff {
+    /// Compare `a` against the reference `b` with the default options.
+    ///
+    /// # Panics
+    /// Panics if the images differ in size.
+    pub fn new<A, B>(a: &A, b: &B) -> Self
+    where
+        A: PixelBuffer<Pixel = Color>,
+        B: PixelBuffer<Pixel = Color>,
+    {
+        Self::with_options(a, b, &DiffOptions::default())
+    }
+    /// Compare `a` against `b`, normalizing `b` as described by `options`.
+    ///
+    /// # Panics
+    /// Panics if the images differ in size.
+    pub fn with_options<A, B>(a: &A, b: &B, options: &DiffOptions) -> Self
+    where
+        A: PixelBuffer<Pixel = Color>,
+        B: PixelBuffer<Pixel = Color>,
+    {
+        let (width, height) = (a.width(), a.height());
+        assert_eq!((width, height), (b.width(), b.height()), "compared images differ in size");
+        let gain = gain(a, b, options);
+        let normalized = |x, y| {
+            let color = b.get(x, y);
+            Color::new(color.r.value() * gain[0], color.g.value() * gain[1], color.b.value() * gain[2])
+        };
+        let mut heat_map = Canvas::new(width, height);
+        let (mut max_error, mut total_error, mut total_squared) = (0.0f32, 0.0f64, 0.0f64);
+        for y in 0..height {
+            for x in 0..width {
+                let (p, q) = (a.get(x, y), normalized(x, y));
+                let errors = [
+                    (p.r.value() - q.r.value()).abs(),
+                    (p.g.value() - q.g.value()).abs(),
+                    (p.b.value() - q.b.value()).abs(),
+                ];
+                let error = (errors[0] + errors[1] + errors[2]) / 3.0;
+                max_error = max_error.max(error);
+                total_error += f64::from(error);
+                total_squared += errors.iter().map(|e| f64::from(e * e)).sum::<f64>();
+                heat_map.set(x, y, heat(error / options.heat_scale));
+            }
+        }
+        let pixels = (width * height).max(1) as f64;
+        let mse = (total_squared / (pixels * 3.0)) as f32;
+        let psnr = if mse > 0.0 { -10.0 * mse.log10() } else { f32::INFINITY };
+        Self {
+            max_error,
+            mean_error: (total_error / pixels) as f32,
+            mse,
+            psnr,
+            ssim: ssim(width, height, |x, y| a.get(x, y).luminance(), |x, y| normalized(x, y).luminance()),
+            heat_map,
+        }
+    }
+}
+
+/// Per channel factors applied to `b` so its means match `a`
+fn gain<A, B>(a: &A, b: &B, options: &DiffOptions) -> [f32; 3]
+where
+    A: PixelBuffer<Pixel = Color>,
+    B: PixelBuffer<Pixel = Color>,
+{
+    if !options.match_exposure && !options.match_white_balance {
+        return [1.0; 3];
+    }
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    let ratio = |a: f32, b: f32| if b > 0.0 { a / b } else { 1.0 };
+    if options.match_white_balance {
+        [
+            ratio(mean_a.r.value(), mean_b.r.value()),
+            ratio(mean_a.g.value(), mean_b.g.value()),
+            ratio(mean_a.b.value(), mean_b.b.value()),
+        ]
+    } else {
+        [ratio(mean_a.luminance(), mean_b.luminance()); 3]
+    }
+}
+
+fn mean<B: PixelBuffer<Pixel = Color>>(buffer: &B) -> Color {
+    let mut sum = [0.0f64; 3];
+    for y in 0..buffer.height() {
+        for x in 0..buffer.width() {
+            let color = buffer.get(x, y);
+            sum[0] += f64::from(color.r.value());
+            sum[1] += f64::from(color.g.value());
+            sum[2] += f64::from(color.b.value());
+        }
+    }
+    let pixels = (buffer.width() * buffer.height()).max(1) as f64;
+    Color::new((sum[0] / pixels) as f32, (sum[1] / pixels) as f32, (sum[2] / pixels) as f32)
+}
+
+/// Mean SSIM of the luminance of two images over non overlapping windows
+fn ssim(width: usize, height: usize, a: impl Fn(usize, usize) -> f32, b: impl Fn(usize, usize) -> f32) -> f32 {
+    const C1: f32 = 0.01 * 0.01;
+    const C2: f32 = 0.03 * 0.03;
+    let (mut total, mut windows) = (0.0f32, 0);
+    for y0 in (0..height).step_by(SSIM_WINDOW) {
+        for x0 in (0..width).step_by(SSIM_WINDOW) {
+            let (x1, y1) = ((x0 + SSIM_WINDOW).min(width), (y0 + SSIM_WINDOW).min(height));
+            let count = ((x1 - x0) * (y1 - y0)) as f32;
+            let (mut sum_a, mut sum_b) = (0.0, 0.0);
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    sum_a += a(x, y);
+                    sum_b += b(x, y);
+                }
+            }
+            let (mean_a, mean_b) = (sum_a / count, sum_b / count);
+            let (mut var_a, mut var_b, mut covariance) = (0.0, 0.0, 0.0);
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let (da, db) = (a(x, y) - mean_a, b(x, y) - mean_b);
+                    var_a += da * da;
+                    var_b += db * db;
+                    covariance += da * db;
+                }
+            }
+            let (var_a, var_b, covariance) = (var_a / count, var_b / count, covariance / count);
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+    if windows > 0 {
+        total / windows as f32
+    } else {
+        1.0
+    }
+}
+
+/// Black to red to yellow to white ramp for `t` from 0.0 to 1.0
+fn heat(t: f32) -> Color {
+    let ramp = |offset: f32| (t * 3.0 - offset).clamp(0.0, 1.0);
+    Color::new(ramp(0.0), ramp(1.0), ramp(2.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DiffOptions, ImageDiff};
+    use crate::canvas::{Canvas, Color};
+    use assert_approx_eq::assert_approx_eq;
+
+    fn gradient(scale: Color) -> Canvas {
+        let mut canvas = Canvas::new(16, 16);
+        for y in 0..16 {
+            for x in 0..16 {
+                canvas.set(x, y, scale * ((x + y) as f32 / 32.0));
+            }
+        }
+        canvas
+    }
+
+    #[test]
+    fn test_identical() {
+        let image = gradient(Color::white());
+        let diff = ImageDiff::new(&image, &image);
+        assert_eq!(diff.mse, 0.0);
+        assert_eq!(diff.max_error, 0.0);
+        assert!(diff.psnr.is_infinite());
+        assert_approx_eq!(diff.ssim, 1.0);
+        assert!(diff.heat_map.pixels().all(|(_, _, c)| c == Color::black()));
+    }
+
+    #[test]
+    fn test_error() {
+        let a = gradient(Color::white());
+        let mut b = a.clone();
+        b.set(3, 4, a.get(3, 4) + Color::new(0.5, 0.5, 0.5));
+        let diff = ImageDiff::new(&a, &b);
+        assert_approx_eq!(diff.max_error, 0.5);
+        assert_approx_eq!(diff.mean_error, 0.5 / 256.0);
+        assert_approx_eq!(diff.mse, 0.25 / 256.0);
+        assert_approx_eq!(diff.psnr, 30.1, 0.1);
+        assert!(diff.ssim < 1.0);
+        assert_eq!(diff.heat_map.get(3, 4), Color::white());
+        assert_eq!(diff.heat_map.get(4, 4), Color::black());
+    }
+
+    #[test]
+    fn test_normalize() {
+        let a = gradient(Color::new(1.0, 0.8, 0.6));
+        let darker = gradient(Color::new(0.5, 0.4, 0.3));
+        let tinted = gradient(Color::new(0.9, 0.8, 0.9));
+        let exposure = DiffOptions {
+            match_exposure: true,
+            ..DiffOptions::default()
+        };
+        let white_balance = DiffOptions {
+            match_white_balance: true,
+            ..DiffOptions::default()
+        };
+        assert!(ImageDiff::new(&a, &darker).mse > 1e-3);
+        assert!(ImageDiff::with_options(&a, &darker, &exposure).mse < 1e-8);
+        assert!(ImageDiff::with_options(&a, &tinted, &exposure).mse > 1e-4);
+        let diff = ImageDiff::with_options(&a, &tinted, &white_balance);
+        assert!(diff.mse < 1e-8);
+        assert_approx_eq!(diff.ssim, 1.0, 1e-4);
+    }
+}
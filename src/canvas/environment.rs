@@ -0,0 +1,68 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Canvas, Color, Filter};
+use crate::math::{equirect_direction, equirect_uv, Sh9, Vec2f, Vec3f};
+
+impl Canvas {
+    /// Read this Canvas as an equirectangular environment map in the unit vector `direction`
+    pub fn sample_direction(&self, direction: Vec3f, filter: Filter) -> Color {
+        let uv = equirect_uv(direction);
+        self.sample(uv.x * self.width() as f32, uv.y * self.height() as f32, filter)
+    }
+    /// Direction through the center of Pixel (x, y) of this Canvas read as an
+    /// equirectangular environment map
+    pub fn texel_direction(&self, x: usize, y: usize) -> Vec3f {
+        equirect_direction(Vec2f::from_parts(
+            (x as f32 + 0.5) / self.width() as f32,
+            (y as f32 + 0.5) / self.height() as f32,
+        ))
+    }
+    /// Project this Canvas, read as an equirectangular environment map, onto spherical
+    /// harmonics for ambient lighting
+    pub fn project_sh9(&self) -> Sh9 {
+        Sh9::project_equirect(self.width(), self.height(), |x, y| {
+            let color = self.get(x, y);
+            Vec3f::from_parts(color.r.value(), color.g.value(), color.b.value())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::{Canvas, Color, Filter};
+    use crate::math::Vec3f;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_environment() {
+        // Red sky, blue ground
+        let mut environment = Canvas::new(16, 8);
+        for y in 0..8 {
+            for x in 0..16 {
+                environment.set(x, y, if y < 4 { Color::bright_red() } else { Color::bright_blue() });
+            }
+        }
+        let up = Vec3f::from_parts(0.0, 1.0, 0.0);
+        assert_eq!(environment.sample_direction(up, Filter::Nearest), Color::bright_red());
+        assert_eq!(environment.sample_direction(-up, Filter::Nearest), Color::bright_blue());
+        assert!(environment.texel_direction(3, 0).y > 0.9);
+        let sh = environment.project_sh9();
+        let irradiance = sh.irradiance(up);
+        assert!(irradiance.x > 2.5 && irradiance.z < 0.5, "{:?}", irradiance);
+        assert_approx_eq!(sh.irradiance(Vec3f::from_parts(0.0, 0.0, 1.0)).x, std::f32::consts::PI / 2.0, 0.05);
+    }
+}
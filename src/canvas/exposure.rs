@@ -0,0 +1,234 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Color, PixelBuffer};
+
+///
+/// Histogram of Pixel luminance in log2 space, so each bin covers the same number of
+/// exposure stops.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    bins: Vec<u32>,
+    min_log: f32,
+    max_log: f32,
+    count: u32,
+}
+
+impl Histogram {
+    /// Count the luminance of every Pixel of `buffer` into `bins` bins spanning
+    /// 2^`min_log` to 2^`max_log`.
+    ///
+    /// Black Pixels are skipped since they carry no exposure information, brighter or
+    /// darker Pixels are counted in the last or first bin.
+    pub fn new<B: PixelBuffer<Pixel = Color>>(buffer: &B, bins: usize, min_log: f32, max_log: f32) -> Self {
+        assert!(bins > 0);
+        assert!(max_log > min_log);
+        let mut histogram = Self {
+            bins: vec![0; bins],
+            min_log,
+            max_log,
+            count: 0,
+        };
+        for y in 0..buffer.height() {
+            for x in 0..buffer.width() {
+                let luminance = buffer.get(x, y).luminance();
+                if luminance > 0.0 {
+                    let bin = histogram.bin(luminance);
+                    histogram.bins[bin] += 1;
+                    histogram.count += 1;
+                }
+            }
+        }
+        histogram
+    }
+    /// Number of Pixels in each bin, darkest first
+    pub fn bins(&self) -> &[u32] {
+        &self.bins
+    }
+    /// Number of Pixels counted
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+    /// Luminance at the center of `bin`
+    pub fn bin_luminance(&self, bin: usize) -> f32 {
+        let width = (self.max_log - self.min_log) / self.bins.len() as f32;
+        (self.min_log + (bin as f32 + 0.5) * width).exp2()
+    }
+    /// Luminance below which `fraction` of the counted Pixels lie
+    pub fn percentile(&self, fraction: f32) -> f32 {
+        let target = (fraction.clamp(0.0, 1.0) * self.count as f32).ceil() as u32;
+        let mut seen = 0;
+        for (bin, &count) in self.bins.iter().enumerate() {
+            seen += count;
+            if seen >= target.max(1) {
+                return self.bin_luminance(bin);
+            }
+        }
+        self.bin_luminance(self.bins.len() - 1)
+    }
+    /// Geometric mean luminance of the Pixels between the `low` and `high` fractions,
+    /// ignoring the darkest and brightest outliers such as the sky or deep shadow.
+    ///
+    /// Returns `None` if no Pixels were counted.
+    pub fn average_luminance(&self, low: f32, high: f32) -> Option<f32> {
+        let low = low.clamp(0.0, 1.0) * self.count as f32;
+        let high = high.clamp(0.0, 1.0) * self.count as f32;
+        let (mut seen, mut sum, mut weight) = (0.0f32, 0.0f32, 0.0f32);
+        for (bin, &count) in self.bins.iter().enumerate() {
+            let (start, end) = (seen, seen + count as f32);
+            seen = end;
+            let used = end.min(high) - start.max(low);
+            if used > 0.0 {
+                sum += used * self.bin_luminance(bin).log2();
+                weight += used;
+            }
+        }
+        if weight > 0.0 {
+            Some((sum / weight).exp2())
+        } else {
+            None
+        }
+    }
+    fn bin(&self, luminance: f32) -> usize {
+        let t = (luminance.log2() - self.min_log) / (self.max_log - self.min_log);
+        ((t * self.bins.len() as f32) as isize).max(0).min(self.bins.len() as isize - 1) as usize
+    }
+}
+
+///
+/// Eye adaptation, moving the exposure towards one that maps the scene's average
+/// luminance to middle grey.
+///
+/// Multiply the HDR image by the exposure with `apply` before tonemapping.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AutoExposure {
+    /// Luminance the scene average is mapped to
+    pub key: f32,
+    /// Rate in stops per second at which the exposure follows the scene getting darker
+    pub speed_up: f32,
+    /// Rate in stops per second at which the exposure follows the scene getting brighter
+    pub speed_down: f32,
+    /// Smallest exposure ever used
+    pub min_exposure: f32,
+    /// Largest exposure ever used
+    pub max_exposure: f32,
+    /// Fraction of darkest Pixels ignored when averaging
+    pub low_percentile: f32,
+    /// Fraction of Pixels below the brightest ones ignored when averaging
+    pub high_percentile: f32,
+    exposure: f32,
+}
+
+impl AutoExposure {
+    /// Create new AutoExposure starting at an exposure of 1.0
+    pub fn new() -> Self {
+        Self {
+            key: 0.18,
+            speed_up: 3.0,
+            speed_down: 1.0,
+            min_exposure: 1.0 / 64.0,
+            max_exposure: 64.0,
+            low_percentile: 0.5,
+            high_percentile: 0.95,
+            exposure: 1.0,
+        }
+    }
+    /// Current exposure
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+    /// Jump straight to `exposure`, such as after a camera cut
+    pub fn reset(&mut self, exposure: f32) {
+        self.exposure = exposure.max(self.min_exposure).min(self.max_exposure);
+    }
+    /// Exposure that maps the average luminance of `histogram` to the key
+    pub fn target(&self, histogram: &Histogram) -> f32 {
+        match histogram.average_luminance(self.low_percentile, self.high_percentile) {
+            Some(average) => (self.key / average).max(self.min_exposure).min(self.max_exposure),
+            None => self.exposure,
+        }
+    }
+    /// Move the exposure towards the target for `histogram` over `delta` seconds and
+    /// return it
+    pub fn update(&mut self, histogram: &Histogram, delta: f32) -> f32 {
+        let (current, target) = (self.exposure.log2(), self.target(histogram).log2());
+        let speed = if target > current { self.speed_up } else { self.speed_down };
+        // Exponential approach in stops, so large jumps are smoothed the same way as small
+        let step = (target - current) * (1.0 - (-speed * delta).exp());
+        self.exposure = (current + step).exp2();
+        self.exposure
+    }
+    /// Multiply every Pixel of `buffer` by the current exposure
+    pub fn apply<B: PixelBuffer<Pixel = Color>>(&self, buffer: &mut B) {
+        for y in 0..buffer.height() {
+            for x in 0..buffer.width() {
+                let color = buffer.get(x, y) * self.exposure;
+                buffer.set(x, y, color);
+            }
+        }
+    }
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AutoExposure, Histogram};
+    use crate::canvas::{Canvas, Color, HalfCanvas};
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_histogram() {
+        let mut canvas = HalfCanvas::new(4, 1);
+        canvas.set(1, 0, Color::new(0.25, 0.25, 0.25));
+        canvas.set(2, 0, Color::new(4.0, 4.0, 4.0));
+        canvas.set(3, 0, Color::new(1000.0, 1000.0, 1000.0));
+        let histogram = Histogram::new(&canvas, 8, -4.0, 4.0);
+        assert_eq!(histogram.count(), 3);
+        assert_eq!(histogram.bins(), &[0, 0, 1, 0, 0, 0, 1, 1]);
+        assert_approx_eq!(histogram.bin_luminance(2), 0.3536, 1e-3);
+        assert_approx_eq!(histogram.percentile(0.5), 5.657, 1e-3);
+        // Dropping the brightest third leaves the geometric mean of 0.3536 and 5.657
+        assert_approx_eq!(histogram.average_luminance(0.0, 2.0 / 3.0).unwrap(), 1.414, 1e-2);
+        assert!(Histogram::new(&Canvas::new(2, 2), 4, -1.0, 1.0).average_luminance(0.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn test_auto_exposure() {
+        let mut canvas = Canvas::new(4, 4);
+        canvas.fill(Color::new(0.045, 0.045, 0.045));
+        let histogram = Histogram::new(&canvas, 256, -8.0, 8.0);
+        let mut exposure = AutoExposure::new();
+        exposure.low_percentile = 0.0;
+        assert_approx_eq!(exposure.target(&histogram), 4.0, 0.1);
+        // Adapts gradually, then settles on the target
+        let first = exposure.update(&histogram, 0.1);
+        assert!(first > 1.0 && first < 4.0);
+        for _ in 0..100 {
+            exposure.update(&histogram, 0.1);
+        }
+        assert_approx_eq!(exposure.exposure(), exposure.target(&histogram), 1e-3);
+        exposure.reset(2.0);
+        exposure.apply(&mut canvas);
+        assert_eq!(canvas.get(0, 0), Color::new(0.09, 0.09, 0.09));
+    }
+}
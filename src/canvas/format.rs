@@ -0,0 +1,98 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::{Error, Result};
+use std::fmt;
+
+///
+/// Layout and meaning of the pixels of a texture or render target.
+///
+/// Tagging buffers with a Format lets a later pass check it is reading what an earlier
+/// pass wrote, such as a depth buffer rather than a color image.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// Linear RGB with a 32 bit float per channel, as stored by `Canvas`
+    Rgb32F,
+    /// Linear RGB with a 16 bit float per channel, as stored by `HalfCanvas`
+    Rgb16F,
+    /// Linear RGB and alpha with a 32 bit float per channel, as stored by `RgbaCanvas`
+    Rgba32F,
+    /// View distance as a 32 bit float
+    Depth32F,
+    /// Object or material ids as 32 bit integers, as stored by `IdBuffer`
+    Id32,
+}
+
+impl Format {
+    /// Check if this Format holds colors
+    pub fn is_color(self) -> bool {
+        match self {
+            Format::Rgb32F | Format::Rgb16F | Format::Rgba32F => true,
+            Format::Depth32F | Format::Id32 => false,
+        }
+    }
+    /// Check if this Format holds depth
+    pub fn is_depth(self) -> bool {
+        self == Format::Depth32F
+    }
+    /// Bytes of storage per pixel
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            Format::Rgb32F => 12,
+            Format::Rgb16F => 6,
+            Format::Rgba32F => 16,
+            Format::Depth32F | Format::Id32 => 4,
+        }
+    }
+    /// Check that this Format is `expected`, or return `Error::Format`
+    pub fn expect(self, expected: Format) -> Result<()> {
+        if self == expected {
+            Ok(())
+        } else {
+            Err(Error::Format { expected, found: self })
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Format::Rgb32F => "rgb32f",
+            Format::Rgb16F => "rgb16f",
+            Format::Rgba32F => "rgba32f",
+            Format::Depth32F => "depth32f",
+            Format::Id32 => "id32",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Format;
+
+    #[test]
+    fn test_format() {
+        assert!(Format::Rgb16F.is_color());
+        assert!(!Format::Depth32F.is_color());
+        assert!(Format::Depth32F.is_depth());
+        assert_eq!(Format::Rgba32F.bytes_per_pixel(), 16);
+        assert!(Format::Id32.expect(Format::Id32).is_ok());
+        let error = Format::Rgb32F.expect(Format::Depth32F).unwrap_err();
+        assert_eq!(error.to_string(), "expected a depth32f buffer but found rgb32f");
+    }
+}
@@ -0,0 +1,164 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use std::fmt;
+
+///
+/// IEEE 754 half precision (binary16) float used for compact HDR storage.
+///
+/// Arithmetic is done by converting to `f32`; conversions round to nearest even.
+///
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Half(u16);
+
+impl Half {
+    /// Create a `Half` from its raw bit pattern.
+    pub fn from_bits(bits: u16) -> Self {
+        Half(bits)
+    }
+    /// Get the raw bit pattern of this `Half`.
+    pub fn to_bits(self) -> u16 {
+        self.0
+    }
+    /// Convert an `f32` to the nearest `Half`, saturating to infinity on overflow.
+    pub fn from_f32(value: f32) -> Self {
+        let bits = value.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exponent = ((bits >> 23) & 0xFF) as i32;
+        let mantissa = bits & 0x007F_FFFF;
+
+        // Infinity and NaN, keeping NaN quiet
+        if exponent == 0xFF {
+            let nan = if mantissa == 0 { 0 } else { 0x0200 | (mantissa >> 13) as u16 };
+            return Half(sign | 0x7C00 | nan);
+        }
+
+        let half_exponent = exponent - 127 + 15;
+        if half_exponent >= 0x1F {
+            // Too large, round to infinity
+            Half(sign | 0x7C00)
+        } else if half_exponent <= 0 {
+            // Subnormal or too small to represent
+            if half_exponent < -10 {
+                return Half(sign);
+            }
+            let mantissa = mantissa | 0x0080_0000;
+            let shift = (14 - half_exponent) as u32;
+            let half_mantissa = (mantissa >> shift) as u16;
+            let round_bit = 1 << (shift - 1);
+            if mantissa & round_bit != 0 && mantissa & (3 * round_bit - 1) != 0 {
+                Half(sign | (half_mantissa + 1))
+            } else {
+                Half(sign | half_mantissa)
+            }
+        } else {
+            let half = sign | ((half_exponent as u16) << 10) | (mantissa >> 13) as u16;
+            // Round to nearest even, a carry correctly bumps the exponent
+            if mantissa & 0x1000 != 0 && mantissa & 0x2FFF != 0 {
+                Half(half + 1)
+            } else {
+                Half(half)
+            }
+        }
+    }
+    /// Convert this `Half` to an `f32`, which is always exact.
+    pub fn to_f32(self) -> f32 {
+        let sign = u32::from(self.0 & 0x8000) << 16;
+        let exponent = u32::from((self.0 >> 10) & 0x1F);
+        let mantissa = u32::from(self.0 & 0x03FF);
+        match exponent {
+            0 => {
+                // Zero or subnormal, mantissa * 2^-24
+                let magnitude = mantissa as f32 * (1.0 / 16_777_216.0);
+                if sign == 0 { magnitude } else { -magnitude }
+            }
+            0x1F => f32::from_bits(sign | 0x7F80_0000 | (mantissa << 13)),
+            _ => f32::from_bits(sign | ((exponent + 112) << 23) | (mantissa << 13)),
+        }
+    }
+}
+
+impl From<f32> for Half {
+    fn from(value: f32) -> Self {
+        Half::from_f32(value)
+    }
+}
+
+impl From<Half> for f32 {
+    fn from(value: Half) -> Self {
+        value.to_f32()
+    }
+}
+
+impl fmt::Debug for Half {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Half({} / {:#06x})", self.to_f32(), self.0)
+    }
+}
+
+impl fmt::Display for Half {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_f32())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Half;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_from_f32() {
+        assert_eq!(Half::from(0.0).to_bits(), 0x0000);
+        assert_eq!(Half::from(-0.0).to_bits(), 0x8000);
+        assert_eq!(Half::from(1.0).to_bits(), 0x3C00);
+        assert_eq!(Half::from(-2.0).to_bits(), 0xC000);
+        assert_eq!(Half::from(0.5).to_bits(), 0x3800);
+        assert_eq!(Half::from(65504.0).to_bits(), 0x7BFF);
+        assert_eq!(Half::from(65520.0).to_bits(), 0x7C00);
+        assert_eq!(Half::from(f32::INFINITY).to_bits(), 0x7C00);
+        assert_eq!(Half::from(f32::NEG_INFINITY).to_bits(), 0xFC00);
+        assert_eq!(Half::from(5.960_464_5e-8).to_bits(), 0x0001);
+        assert_eq!(Half::from(1e-10).to_bits(), 0x0000);
+    }
+
+    #[test]
+    fn test_rounding() {
+        // 1 + 2^-11 is exactly half way between 1.0 and the next half, ties go to even
+        assert_eq!(Half::from(1.000_488_3).to_bits(), 0x3C00);
+        // 1 + 3 * 2^-11 is half way between two halves with an odd lower neighbour
+        assert_eq!(Half::from(1.001_464_8).to_bits(), 0x3C02);
+    }
+
+    #[test]
+    fn test_to_f32() {
+        assert_eq!(f32::from(Half::from_bits(0x3C00)), 1.0);
+        assert_eq!(f32::from(Half::from_bits(0xC000)), -2.0);
+        assert_eq!(f32::from(Half::from_bits(0x7BFF)), 65504.0);
+        assert_eq!(f32::from(Half::from_bits(0x0001)), 5.960_464_5e-8);
+        assert_eq!(f32::from(Half::from_bits(0x8001)), -5.960_464_5e-8);
+        assert_eq!(f32::from(Half::from_bits(0x7C00)), f32::INFINITY);
+        assert!(f32::from(Half::from_bits(0x7E00)).is_nan());
+        assert!(f32::from(Half::from(f32::NAN)).is_nan());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for &value in &[0.1f32, 1.0 / 3.0, 2.5, 100.25, 1234.5, -0.75, 6.1e-5] {
+            assert_approx_eq!(f32::from(Half::from(value)), value, value.abs() * 1e-3);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Canvas, Color, Half};
+
+/// Image Canvas storing unclamped HDR colors at half precision.
+///
+/// Uses half the memory of a `Canvas`, which matters for large supersampled render
+/// targets. Colors are converted to and from `f32` on access.
+#[derive(Clone)]
+pub struct HalfCanvas {
+    dimensions: (usize, usize),
+    color_buffer: Vec<[Half; 3]>,
+}
+
+impl HalfCanvas {
+    /// Create new HalfCanvas
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            dimensions: (width, height),
+            color_buffer: vec![[Half::default(); 3]; width * height],
+        }
+    }
+    /// Get Width of HalfCanvas
+    pub fn width(&self) -> usize {
+        self.dimensions.0
+    }
+    /// Get Height of HalfCanvas
+    pub fn height(&self) -> usize {
+        self.dimensions.1
+    }
+    /// Get Color of Pixel at (x, y)
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        assert!(x < self.dimensions.0);
+        assert!(y < self.dimensions.1);
+        let [r, g, b] = self.color_buffer[(y * self.dimensions.0) + x];
+        Color::new(r.into(), g.into(), b.into())
+    }
+    /// Set `Color` of Pixel at (x, y) to color
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        assert!(x < self.dimensions.0);
+        assert!(y < self.dimensions.1);
+        self.color_buffer[(y * self.dimensions.0) + x] = [
+            color.r.value().into(),
+            color.g.value().into(),
+            color.b.value().into(),
+        ];
+    }
+}
+
+impl From<&Canvas> for HalfCanvas {
+    fn from(canvas: &Canvas) -> Self {
+        let mut half = HalfCanvas::new(canvas.width(), canvas.height());
+        for y in 0..canvas.height() {
+            for x in 0..canvas.width() {
+                half.set(x, y, canvas.get(x, y));
+            }
+        }
+        half
+    }
+}
+
+impl From<&HalfCanvas> for Canvas {
+    fn from(half: &HalfCanvas) -> Self {
+        let mut canvas = Canvas::new(half.width(), half.height());
+        for y in 0..half.height() {
+            for x in 0..half.width() {
+                canvas.set(x, y, half.get(x, y));
+            }
+        }
+        canvas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Canvas, Color, HalfCanvas};
+
+    #[test]
+    fn test_creation() {
+        let c = HalfCanvas::new(10, 20);
+        assert_eq!(c.width(), 10);
+        assert_eq!(c.height(), 20);
+        assert_eq!(c.get(9, 19), Color::black());
+    }
+
+    #[test]
+    fn test_hdr_values() {
+        let mut c = HalfCanvas::new(2, 2);
+        c.set(1, 0, Color::new(16.0, 0.5, 1024.0));
+        let color = c.get(1, 0);
+        assert_eq!(color.r.value(), 16.0);
+        assert_eq!(color.g.value(), 0.5);
+        assert_eq!(color.b.value(), 1024.0);
+    }
+
+    #[test]
+    fn test_conversion() {
+        let mut canvas = Canvas::new(3, 2);
+        canvas.set(0, 0, Color::new(0.25, 0.5, 0.75));
+        canvas.set(2, 1, Color::new(4.0, 2.0, 1.0));
+        let half = HalfCanvas::from(&canvas);
+        let back = Canvas::from(&half);
+        assert_eq!(back.get(0, 0), Color::new(0.25, 0.5, 0.75));
+        assert_eq!(back.get(2, 1).r.value(), 4.0);
+        assert_eq!(back.get(1, 1), Color::black());
+    }
+}
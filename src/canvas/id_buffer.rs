@@ -0,0 +1,113 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::PixelBuffer;
+
+///
+/// Integer object id per Pixel, used for pixel exact picking.
+///
+/// Id 0 is reserved for the background, so objects should be numbered from 1.
+///
+pub struct IdBuffer {
+    dimensions: (usize, usize),
+    id_buffer: Vec<u32>,
+}
+
+impl IdBuffer {
+    /// Id of Pixels not covered by any object
+    pub const BACKGROUND: u32 = 0;
+
+    /// Create new IdBuffer filled with the background id
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            dimensions: (width, height),
+            id_buffer: vec![Self::BACKGROUND; width * height],
+        }
+    }
+    /// Get Width of IdBuffer
+    pub fn width(&self) -> usize {
+        self.dimensions.0
+    }
+    /// Get Height of IdBuffer
+    pub fn height(&self) -> usize {
+        self.dimensions.1
+    }
+    /// Get id of Pixel at (x, y)
+    pub fn get(&self, x: usize, y: usize) -> u32 {
+        assert!(x < self.dimensions.0);
+        assert!(y < self.dimensions.1);
+        self.id_buffer[(y * self.dimensions.0) + x]
+    }
+    /// Set id of Pixel at (x, y)
+    pub fn set(&mut self, x: usize, y: usize, id: u32) {
+        assert!(x < self.dimensions.0);
+        assert!(y < self.dimensions.1);
+        self.id_buffer[(y * self.dimensions.0) + x] = id;
+    }
+    /// Reset every Pixel to the background id
+    pub fn clear(&mut self) {
+        for id in self.id_buffer.iter_mut() {
+            *id = Self::BACKGROUND;
+        }
+    }
+    /// Get the id of the object under (x, y), such as the cursor position.
+    ///
+    /// Returns `None` for the background or for positions outside the buffer.
+    pub fn pick(&self, x: usize, y: usize) -> Option<u32> {
+        if x >= self.dimensions.0 || y >= self.dimensions.1 {
+            return None;
+        }
+        match self.get(x, y) {
+            Self::BACKGROUND => None,
+            id => Some(id),
+        }
+    }
+}
+
+impl PixelBuffer for IdBuffer {
+    type Pixel = u32;
+
+    fn width(&self) -> usize {
+        IdBuffer::width(self)
+    }
+    fn height(&self) -> usize {
+        IdBuffer::height(self)
+    }
+    fn get(&self, x: usize, y: usize) -> u32 {
+        IdBuffer::get(self, x, y)
+    }
+    fn set(&mut self, x: usize, y: usize, value: u32) {
+        IdBuffer::set(self, x, y, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IdBuffer;
+
+    #[test]
+    fn test_pick() {
+        let mut ids = IdBuffer::new(4, 4);
+        assert_eq!(ids.pick(1, 1), None);
+        ids.set(1, 1, 42);
+        assert_eq!(ids.get(1, 1), 42);
+        assert_eq!(ids.pick(1, 1), Some(42));
+        assert_eq!(ids.pick(1, 2), None);
+        assert_eq!(ids.pick(4, 1), None);
+        ids.clear();
+        assert_eq!(ids.pick(1, 1), None);
+    }
+}
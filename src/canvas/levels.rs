@@ -0,0 +1,114 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Canvas, Color};
+
+///
+/// Levels adjustment remapping an input range to an output range with a gamma curve
+/// in between, as found in image editors
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Levels {
+    /// Input value mapped to `output_black`
+    pub input_black: f32,
+    /// Input value mapped to `output_white`
+    pub input_white: f32,
+    /// Midtone curve, above 1.0 brightens and below 1.0 darkens
+    pub gamma: f32,
+    pub output_black: f32,
+    pub output_white: f32,
+}
+
+impl Levels {
+    /// Remap a single channel value
+    pub fn apply(&self, value: f32) -> f32 {
+        let range = self.input_white - self.input_black;
+        let t = if range > 0.0 { (value - self.input_black) / range } else { 0.0 };
+        let t = t.clamp(0.0, 1.0).powf(1.0 / self.gamma);
+        self.output_black + t * (self.output_white - self.output_black)
+    }
+}
+
+impl Default for Levels {
+    fn default() -> Self {
+        Self {
+            input_black: 0.0,
+            input_white: 1.0,
+            gamma: 1.0,
+            output_black: 0.0,
+            output_white: 1.0,
+        }
+    }
+}
+
+impl Canvas {
+    /// Shift every channel by `brightness` and scale it around middle grey by `contrast`.
+    ///
+    /// A brightness of 0.0 and contrast of 1.0 leave the Canvas unchanged.
+    pub fn brightness_contrast(&mut self, brightness: f32, contrast: f32) {
+        let adjust = |c: f32| (c - 0.5) * contrast + 0.5 + brightness;
+        self.map_channels(adjust);
+    }
+    /// Apply a `Levels` adjustment to every channel
+    pub fn levels(&mut self, levels: &Levels) {
+        self.map_channels(|c| levels.apply(c));
+    }
+    fn map_channels<F: Fn(f32) -> f32>(&mut self, f: F) {
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let color = self.get(x, y);
+                self.set(x, y, Color::new(f(color.r.value()), f(color.g.value()), f(color.b.value())));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Levels;
+    use crate::canvas::{Canvas, Color};
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_levels() {
+        let levels = Levels {
+            input_black: 0.2,
+            input_white: 0.6,
+            gamma: 2.0,
+            output_black: 0.1,
+            output_white: 0.9,
+        };
+        assert_approx_eq!(levels.apply(0.0), 0.1);
+        assert_approx_eq!(levels.apply(0.3), 0.5);
+        assert_approx_eq!(levels.apply(1.0), 0.9);
+        let mut canvas = Canvas::new(1, 1);
+        canvas.set(0, 0, Color::new(0.3, 0.1, 0.6));
+        canvas.levels(&levels);
+        assert_eq!(canvas.get(0, 0), Color::new(0.5, 0.1, 0.9));
+        canvas.levels(&Levels::default());
+        assert_eq!(canvas.get(0, 0), Color::new(0.5, 0.1, 0.9));
+    }
+
+    #[test]
+    fn test_brightness_contrast() {
+        let mut canvas = Canvas::new(1, 1);
+        canvas.set(0, 0, Color::new(0.25, 0.5, 0.75));
+        canvas.brightness_contrast(0.0, 1.0);
+        assert_eq!(canvas.get(0, 0), Color::new(0.25, 0.5, 0.75));
+        canvas.brightness_contrast(0.1, 2.0);
+        assert_eq!(canvas.get(0, 0), Color::new(0.1, 0.6, 1.1));
+    }
+}
@@ -14,16 +14,63 @@
 // limitations under the License.
 //
 
+mod aov;
+mod blend;
 mod bmp;
+mod buffer;
 mod canvas;
 mod channel;
+mod clip;
 mod color;
+mod compressed;
+mod depth_buffer;
+mod diff;
+mod environment;
+mod exposure;
+mod format;
+mod half;
+mod half_canvas;
+mod id_buffer;
+mod levels;
+mod palette;
+mod png;
 mod ppm;
 mod raw;
+mod resolve;
+mod rgba_canvas;
+mod sky;
+mod spectrum;
+mod streaming;
+mod texture;
+mod tonemap;
+mod transform;
 
+pub use self::aov::{Aov, AovBuffers, AovSample};
+pub use self::blend::{linear_to_srgb, srgb_to_linear, AlphaMode, BlendMode, BlendSpace};
 pub use self::bmp::BMP;
-pub use self::canvas::Canvas;
-pub use self::channel::Channel;
-pub use self::color::Color;
+pub use self::buffer::PixelBuffer;
+pub use self::canvas::{Canvas, Layout, TILE_SIZE};
+pub use self::channel::{Channel, LdrChannel};
+pub use self::clip::{ClipMask, ClipRect, Clipped, Maskable};
+pub use self::color::{Color, LdrColor};
+pub use self::compressed::{CompressedImage, Compression};
+pub use self::depth_buffer::{DepthBuffer, DepthFunc};
+pub use self::diff::{DiffOptions, ImageDiff};
+pub use self::exposure::{AutoExposure, Histogram};
+pub use self::format::Format;
+pub use self::half::Half;
+pub use self::half_canvas::HalfCanvas;
+pub use self::id_buffer::IdBuffer;
+pub use self::levels::Levels;
+pub use self::palette::CSS_COLORS;
+pub use self::png::PNG;
 pub use self::ppm::PPM;
 pub use self::raw::RAW;
+pub use self::resolve::{JitterPattern, ReconstructionFilter, SampleBuffer};
+pub use self::rgba_canvas::{Rgba, RgbaCanvas};
+pub use self::sky::SunSky;
+pub use self::spectrum::{cie_xyz, xyz_to_linear_srgb, Wavelengths, WAVELENGTH_COUNT, WAVELENGTH_MAX, WAVELENGTH_MIN};
+pub use self::streaming::{MipSource, StreamingTexture};
+pub use self::texture::{MipLevel, Sampler, Texture, Wrap};
+pub use self::tonemap::{tonemap, Tonemap, TonemapOutput};
+pub use self::transform::Filter;
@@ -0,0 +1,228 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+use super::{Color, Rgba};
+use crate::{Error, Result};
+use std::str::FromStr;
+
+/// CSS Color Module Level 4 named colors, which include the X11 names, as `0xRRGGBB`,
+/// sorted by name
+#[rustfmt::skip]
+#[allow(clippy::unreadable_literal)]
+pub const CSS_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xF0F8FF), ("antiquewhite", 0xFAEBD7), ("aqua", 0x00FFFF), ("aquamarine", 0x7FFFD4),
+    ("azure", 0xF0FFFF), ("beige", 0xF5F5DC), ("bisque", 0xFFE4C4), ("black", 0x000000),
+    ("blanchedalmond", 0xFFEBCD), ("blue", 0x0000FF), ("blueviolet", 0x8A2BE2), ("brown", 0xA52A2A),
+    ("burlywood", 0xDEB887), ("cadetblue", 0x5F9EA0), ("chartreuse", 0x7FFF00), ("chocolate", 0xD2691E),
+    ("coral", 0xFF7F50), ("cornflowerblue", 0x6495ED), ("cornsilk", 0xFFF8DC), ("crimson", 0xDC143C),
+    ("cyan", 0x00FFFF), ("darkblue", 0x00008B), ("darkcyan", 0x008B8B), ("darkgoldenrod", 0xB8860B),
+    ("darkgray", 0xA9A9A9), ("darkgreen", 0x006400), ("darkgrey", 0xA9A9A9), ("darkkhaki", 0xBDB76B),
+    ("darkmagenta", 0x8B008B), ("darkolivegreen", 0x556B2F), ("darkorange", 0xFF8C00),
+    ("darkorchid", 0x9932CC), ("darkred", 0x8B0000), ("darksalmon", 0xE9967A), ("darkseagreen", 0x8FBC8F),
+    ("darkslateblue", 0x483D8B), ("darkslategray", 0x2F4F4F), ("darkslategrey", 0x2F4F4F),
+    ("darkturquoise", 0x00CED1), ("darkviolet", 0x9400D3), ("deeppink", 0xFF1493), ("deepskyblue", 0x00BFFF),
+    ("dimgray", 0x696969), ("dimgrey", 0x696969), ("dodgerblue", 0x1E90FF), ("firebrick", 0xB22222),
+    ("floralwhite", 0xFFFAF0), ("forestgreen", 0x228B22), ("fuchsia", 0xFF00FF), ("gainsboro", 0xDCDCDC),
+    ("ghostwhite", 0xF8F8FF), ("gold", 0xFFD700), ("goldenrod", 0xDAA520), ("gray", 0x808080),
+    ("green", 0x008000), ("greenyellow", 0xADFF2F), ("grey", 0x808080), ("honeydew", 0xF0FFF0),
+    ("hotpink", 0xFF69B4), ("indianred", 0xCD5C5C), ("indigo", 0x4B0082), ("ivory", 0xFFFFF0),
+    ("khaki", 0xF0E68C), ("lavender", 0xE6E6FA), ("lavenderblush", 0xFFF0F5), ("lawngreen", 0x7CFC00),
+    ("lemonchiffon", 0xFFFACD), ("lightblue", 0xADD8E6), ("lightcoral", 0xF08080), ("lightcyan", 0xE0FFFF),
+    ("lightgoldenrodyellow", 0xFAFAD2), ("lightgray", 0xD3D3D3), ("lightgreen", 0x90EE90),
+    ("lightgrey", 0xD3D3D3), ("lightpink", 0xFFB6C1), ("lightsalmon", 0xFFA07A), ("lightseagreen", 0x20B2AA),
+    ("lightskyblue", 0x87CEFA), ("lightslategray", 0x778899), ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xB0C4DE), ("lightyellow", 0xFFFFE0), ("lime", 0x00FF00), ("limegreen", 0x32CD32),
+    ("linen", 0xFAF0E6), ("magenta", 0xFF00FF), ("maroon", 0x800000), ("mediumaquamarine", 0x66CDAA),
+    ("mediumblue", 0x0000CD), ("mediumorchid", 0xBA55D3), ("mediumpurple", 0x9370DB),
+    ("mediumseagreen", 0x3CB371), ("mediumslateblue", 0x7B68EE), ("mediumspringgreen", 0x00FA9A),
+    ("mediumturquoise", 0x48D1CC), ("mediumvioletred", 0xC71585), ("midnightblue", 0x191970),
+    ("mintcream", 0xF5FFFA), ("mistyrose", 0xFFE4E1), ("moccasin", 0xFFE4B5), ("navajowhite", 0xFFDEAD),
+    ("navy", 0x000080), ("oldlace", 0xFDF5E6), ("olive", 0x808000), ("olivedrab", 0x6B8E23),
+    ("orange", 0xFFA500), ("orangered", 0xFF4500), ("orchid", 0xDA70D6), ("palegoldenrod", 0xEEE8AA),
+    ("palegreen", 0x98FB98), ("paleturquoise", 0xAFEEEE), ("palevioletred", 0xDB7093),
+    ("papayawhip", 0xFFEFD5), ("peachpuff", 0xFFDAB9), ("peru", 0xCD853F), ("pink", 0xFFC0CB),
+    ("plum", 0xDDA0DD), ("powderblue", 0xB0E0E6), ("purple", 0x800080), ("rebeccapurple", 0x663399),
+    ("red", 0xFF0000), ("rosybrown", 0xBC8F8F), ("royalblue", 0x4169E1), ("saddlebrown", 0x8B4513),
+    ("salmon", 0xFA8072), ("sandybrown", 0xF4A460), ("seagreen", 0x2E8B57), ("seashell", 0xFFF5EE),
+    ("sienna", 0xA0522D), ("silver", 0xC0C0C0), ("skyblue", 0x87CEEB), ("slateblue", 0x6A5ACD),
+    ("slategray", 0x708090), ("slategrey", 0x708090), ("snow", 0xFFFAFA), ("springgreen", 0x00FF7F),
+    ("steelblue", 0x4682B4), ("tan", 0xD2B48C), ("teal", 0x008080), ("thistle", 0xD8BFD8),
+    ("tomato", 0xFF6347), ("turquoise", 0x40E0D0), ("violet", 0xEE82EE), ("wheat", 0xF5DEB3),
+    ("white", 0xFFFFFF), ("whitesmoke", 0xF5F5F5), ("yellow", 0xFFFF00), ("yellowgreen", 0x9ACD32),
+];
+
+impl Color {
+    /// Create Color from `0xRRGGBB`, each byte mapping to a channel like `From<u8>`.
+    ///
+    /// Design tools give sRGB encoded values, pass the result through `srgb_to_linear`
+    /// before lighting with it.
+    pub fn from_hex(hex: u32) -> Self {
+        Self::from_rgb8((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+    }
+    /// Create Color from 8 bit channels
+    pub fn from_rgb8(r: u8, g: u8, b: u8) -> Self {
+        Self {
+            r: r.into(),
+            g: g.into(),
+            b: b.into(),
+        }
+    }
+    /// Color as `0xRRGGBB`, clamping each channel
+    pub fn to_hex(&self) -> u32 {
+        let [r, g, b] = self.to_rgb8();
+        (u32::from(r) << 16) | (u32::from(g) << 8) | u32::from(b)
+    }
+    /// Color as 8 bit channels, clamping each channel
+    pub fn to_rgb8(&self) -> [u8; 3] {
+        [round8(self.r.value()), round8(self.g.value()), round8(self.b.value())]
+    }
+    /// Color as a CSS style `#rrggbb` string
+    pub fn to_hex_string(&self) -> String {
+        format!("#{:06x}", self.to_hex())
+    }
+    /// Look up a CSS or X11 color name such as `"cornflowerblue"`, ignoring case
+    pub fn named(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        CSS_COLORS
+            .binary_search_by(|(candidate, _)| (*candidate).cmp(name.as_str()))
+            .ok()
+            .map(|index| Color::from_hex(CSS_COLORS[index].1))
+    }
+}
+
+/// Parses `#rgb`, `#rrggbb` and CSS color names
+impl FromStr for Color {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let text = text.trim();
+        if text.starts_with('#') {
+            match parse_hex(text)? {
+                (hex, false) => Ok(Color::from_hex(hex)),
+                (_, true) => Err(Error::Decode(format!("color {:?} has an alpha channel", text))),
+            }
+        } else {
+            Color::named(text).ok_or_else(|| Error::Decode(format!("unknown color name {:?}", text)))
+        }
+    }
+}
+
+impl Rgba {
+    /// Create Rgba from 8 bit channels, `a` giving the alpha
+    pub fn from_rgba8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self::new(Color::from_rgb8(r, g, b), f32::from(a) / 255.0)
+    }
+    /// Create Rgba from `0xRRGGBBAA`
+    pub fn from_hex(hex: u32) -> Self {
+        Self::from_rgba8((hex >> 24) as u8, (hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+    }
+    /// Rgba as 8 bit channels, clamping each channel
+    pub fn to_rgba8(&self) -> [u8; 4] {
+        let [r, g, b] = self.color.to_rgb8();
+        [r, g, b, round8(self.alpha)]
+    }
+    /// Rgba as `0xRRGGBBAA`, clamping each channel
+    pub fn to_hex(&self) -> u32 {
+        u32::from_be_bytes(self.to_rgba8())
+    }
+    /// Rgba as a CSS style `#rrggbbaa` string
+    pub fn to_hex_string(&self) -> String {
+        format!("#{:08x}", self.to_hex())
+    }
+}
+
+/// Parses `#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa` and CSS color names, which are opaque
+impl FromStr for Rgba {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let text = text.trim();
+        if text.starts_with('#') {
+            match parse_hex(text)? {
+                (hex, true) => Ok(Rgba::from_hex(hex)),
+                (hex, false) => Ok(Rgba::from_hex((hex << 8) | 0xFF)),
+            }
+        } else {
+            Ok(Rgba::new(text.parse()?, 1.0))
+        }
+    }
+}
+
+/// Nearest 8 bit value of `value` clamped to 0.0 to 1.0
+fn round8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Value of a `#` hex color expanded to 8 bits per channel, and whether it has alpha
+fn parse_hex(text: &str) -> Result<(u32, bool)> {
+    let digits = &text[1..];
+    let invalid = || Error::Decode(format!("invalid hex color {:?}", text));
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let value = u32::from_str_radix(digits, 16).map_err(|_| invalid())?;
+    // Short forms repeat each digit, so #f80 is #ff8800
+    let expand = |value: u32, count: u32| (0..count).rev().fold(0, |hex, i| (hex << 8) | (((value >> (4 * i)) & 0xF) * 0x11));
+    match digits.len() {
+        3 => Ok((expand(value, 3), false)),
+        4 => Ok((expand(value, 4), true)),
+        6 => Ok((value, false)),
+        8 => Ok((value, true)),
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CSS_COLORS;
+    use crate::canvas::{Color, Rgba};
+
+    #[test]
+    fn test_hex() {
+        let color = Color::from_hex(0x6495ED);
+        assert_eq!(color.to_rgb8(), [0x64, 0x95, 0xED]);
+        assert_eq!(color.to_hex(), 0x6495ED);
+        assert_eq!(color.to_hex_string(), "#6495ed");
+        assert_eq!(Color::from_rgb8(255, 0, 128), Color::new(1.0, 0.0, 128.0 / 255.0));
+        assert_eq!(Color::new(2.0, -1.0, 0.5).to_hex(), 0xFF0080);
+        let rgba = Rgba::from_rgba8(255, 128, 0, 64);
+        assert_eq!(rgba.to_rgba8(), [255, 128, 0, 64]);
+        assert_eq!(Rgba::from_hex(0x11223344).to_hex_string(), "#11223344");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("#6495ED".parse::<Color>().unwrap(), Color::from_hex(0x6495ED));
+        assert_eq!("#f80".parse::<Color>().unwrap(), Color::from_hex(0xFF8800));
+        assert_eq!(" CornflowerBlue ".parse::<Color>().unwrap(), Color::from_hex(0x6495ED));
+        assert!("#12345".parse::<Color>().is_err());
+        assert!("#gg0000".parse::<Color>().is_err());
+        assert!("#ff000080".parse::<Color>().is_err());
+        assert!("blurple".parse::<Color>().is_err());
+        assert_eq!("#ff000080".parse::<Rgba>().unwrap().to_rgba8(), [255, 0, 0, 128]);
+        assert_eq!("#f008".parse::<Rgba>().unwrap().to_rgba8(), [255, 0, 0, 136]);
+        assert_eq!("red".parse::<Rgba>().unwrap().to_hex(), 0xFF0000FF);
+    }
+
+    #[test]
+    fn test_named() {
+        assert!(CSS_COLORS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(Color::named("rebeccapurple").unwrap().to_hex(), 0x663399);
+        assert_eq!(Color::named("WHITE").unwrap(), Color::white());
+        assert_eq!(Color::named("not a color"), None);
+    }
+}
@@ -0,0 +1,133 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+use super::Canvas;
+use std::io::{Result, Write};
+
+/// Largest payload of a stored deflate block
+const STORED_BLOCK: usize = 65535;
+
+///
+/// Uncompressed PNG output, readable by browsers and image viewers without any
+/// dependencies on a compression library.
+///
+pub trait PNG {
+    fn to_png<T: Write>(&self, output: &mut T) -> Result<()>;
+}
+
+impl PNG for Canvas {
+    fn to_png<T: Write>(&self, output: &mut T) -> Result<()> {
+        use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+
+        output.write_all(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A])?;
+
+        let mut header = Vec::with_capacity(13);
+        header.write_u32::<BigEndian>(self.width() as u32)?; // Width
+        header.write_u32::<BigEndian>(self.height() as u32)?; // Height
+        header.write_all(&[8, 2, 0, 0, 0])?; // 8 bit RGB, deflate, adaptive filters, no interlace
+        write_chunk(output, b"IHDR", &header)?;
+
+        // Every row starts with filter type 0, leaving the bytes as they are
+        let mut raw = Vec::with_capacity((self.width() * 3 + 1) * self.height());
+        for y in 0..self.height() {
+            raw.push(0);
+            for x in 0..self.width() {
+                let color = self.get(x, y);
+                raw.extend_from_slice(&[u8::from(color.r), u8::from(color.g), u8::from(color.b)]);
+            }
+        }
+
+        // Zlib stream of stored deflate blocks
+        let mut data = Vec::with_capacity(raw.len() + raw.len() / STORED_BLOCK * 5 + 11);
+        data.write_all(&[0x78, 0x01])?;
+        let mut blocks = raw.chunks(STORED_BLOCK).peekable();
+        if blocks.peek().is_none() {
+            data.write_all(&[1, 0, 0, 0xFF, 0xFF])?;
+        }
+        while let Some(block) = blocks.next() {
+            data.write_u8(u8::from(blocks.peek().is_none()))?; // Final block flag
+            data.write_u16::<LittleEndian>(block.len() as u16)?;
+            data.write_u16::<LittleEndian>(!(block.len() as u16))?;
+            data.write_all(block)?;
+        }
+        data.write_u32::<BigEndian>(adler32(&raw))?;
+        write_chunk(output, b"IDAT", &data)?;
+
+        write_chunk(output, b"IEND", &[])
+    }
+}
+
+fn write_chunk<T: Write>(output: &mut T, kind: &[u8; 4], data: &[u8]) -> Result<()> {
+    use byteorder::{BigEndian, WriteBytesExt};
+
+    output.write_u32::<BigEndian>(data.len() as u32)?;
+    output.write_all(kind)?;
+    output.write_all(data)?;
+    output.write_u32::<BigEndian>(!crc32(crc32(!0, kind), data))
+}
+
+/// Update a CRC-32 as used by PNG chunks, start with `!0` and invert the result
+fn crc32(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { 0xEDB8_8320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{adler32, crc32};
+    use crate::canvas::{Canvas, Color, PNG};
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(!crc32(!0, b"IEND"), 0xAE42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn test_to_png() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.set(1, 0, Color::new(1.0, 0.5, 0.0));
+        let mut png = Vec::new();
+        canvas.to_png(&mut png).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 2, 0, 0, 0, 1]);
+        assert_eq!(&png[37..41], b"IDAT");
+        // Zlib header, final stored block of 7 bytes, then the filtered row
+        assert_eq!(&png[41..48], &[0x78, 0x01, 1, 7, 0, 0xF8, 0xFF]);
+        assert_eq!(&png[48..55], &[0, 0, 0, 0, 255, 127, 0]);
+        assert_eq!(&png[png.len() - 12..], &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]);
+    }
+}
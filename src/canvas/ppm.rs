@@ -23,9 +23,9 @@ pub trait PPM {
 
 impl PPM for Canvas {
     fn to_ppm<T: Write>(&self, output: &mut T) -> Result<()> {
-        write!(output, "P3\n")?;
-        write!(output, "{} {}\n", self.width(), self.height())?;
-        write!(output, "255\n")?;
+        writeln!(output, "P3")?;
+        writeln!(output, "{} {}", self.width(), self.height())?;
+        writeln!(output, "255")?;
 
         let mut count = 0;
         for y in 0..self.height() {
@@ -36,7 +36,7 @@ impl PPM for Canvas {
                     write!(output, " ")?;
                     count += 1;
                 } else {
-                    write!(output, "\n")?;
+                    writeln!(output)?;
                     count = 0;
                 }
             }
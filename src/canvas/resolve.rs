@@ -0,0 +1,358 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+use super::{Canvas, Color};
+use crate::math::{halton, Vec2f};
+
+/// Standard 2x MSAA positions in sixteenths of a pixel from its center
+const STANDARD_2: [(i8, i8); 2] = [(4, 4), (-4, -4)];
+/// Standard 4x MSAA positions, a rotated grid
+const STANDARD_4: [(i8, i8); 4] = [(-2, -6), (6, -2), (-6, 2), (2, 6)];
+/// Standard 8x MSAA positions
+const STANDARD_8: [(i8, i8); 8] = [(1, -3), (-1, 3), (5, 1), (-3, -5), (-5, 5), (-7, -1), (3, 7), (7, -7)];
+
+/// Filter weighting samples by their distance from the center of the Pixel being resolved
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ReconstructionFilter {
+    /// Plain average of the samples inside the Pixel, sharp but prone to aliasing
+    #[default]
+    Box,
+    /// Linear falloff to one Pixel away, slightly soft
+    Tent,
+    /// Gaussian bell, `alpha` setting how quickly it falls off within a radius of 1.5 Pixels,
+    /// smooth with little aliasing
+    Gaussian { alpha: f32 },
+    /// Mitchell-Netravali cubic over a radius of 2 Pixels, sharp with mild ringing from its
+    /// negative lobes. `b` = `c` = 1/3 is the recommended balance
+    Mitchell { b: f32, c: f32 },
+}
+
+impl ReconstructionFilter {
+    /// Gaussian filter with the usual falloff
+    pub const GAUSSIAN: ReconstructionFilter = ReconstructionFilter::Gaussian { alpha: 2.0 };
+    /// Mitchell filter with the recommended parameters
+    pub const MITCHELL: ReconstructionFilter = ReconstructionFilter::Mitchell { b: 1.0 / 3.0, c: 1.0 / 3.0 };
+
+    /// Distance in Pixels beyond which samples get no weight
+    pub fn radius(&self) -> f32 {
+        match self {
+            ReconstructionFilter::Box => 0.5,
+            ReconstructionFilter::Tent => 1.0,
+            ReconstructionFilter::Gaussian { .. } => 1.5,
+            ReconstructionFilter::Mitchell { .. } => 2.0,
+        }
+    }
+    /// Weight of a sample `offset` Pixels from the center along one axis
+    pub fn weight_1d(&self, offset: f32) -> f32 {
+        let x = offset.abs();
+        match *self {
+            ReconstructionFilter::Box => {
+                if x <= 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            ReconstructionFilter::Tent => (1.0 - x).max(0.0),
+            ReconstructionFilter::Gaussian { alpha } => {
+                let radius = self.radius();
+                ((-alpha * x * x).exp() - (-alpha * radius * radius).exp()).max(0.0)
+            }
+            ReconstructionFilter::Mitchell { b, c } => {
+                let (x2, x3) = (x * x, x * x * x);
+                if x < 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x3 + (-18.0 + 12.0 * b + 6.0 * c) * x2 + (6.0 - 2.0 * b)) / 6.0
+                } else if x < 2.0 {
+                    ((-b - 6.0 * c) * x3 + (6.0 * b + 30.0 * c) * x2 + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+    /// Weight of a sample `offset` Pixels from the center
+    pub fn weight(&self, offset: Vec2f) -> f32 {
+        self.weight_1d(offset.x) * self.weight_1d(offset.y)
+    }
+}
+
+/// Where within each Pixel the samples of a `SampleBuffer` are taken
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum JitterPattern {
+    /// Evenly spaced rows and columns, the smallest square grid holding the samples
+    Grid,
+    /// The standard hardware MSAA positions for 1, 2, 4 and 8 samples, which avoid sharing
+    /// rows or columns, and the Halton sequence for other counts
+    Standard,
+    /// Points of the Halton sequence in bases 2 and 3
+    Halton,
+}
+
+impl JitterPattern {
+    /// Positions of `count` samples within a Pixel, from 0 to 1 on each axis
+    pub fn offsets(&self, count: usize) -> Vec<Vec2f> {
+        let table: Option<&[(i8, i8)]> = match (self, count) {
+            (JitterPattern::Standard, 1) => Some(&[(0, 0)]),
+            (JitterPattern::Standard, 2) => Some(&STANDARD_2),
+            (JitterPattern::Standard, 4) => Some(&STANDARD_4),
+            (JitterPattern::Standard, 8) => Some(&STANDARD_8),
+            _ => None,
+        };
+        if let Some(table) = table {
+            return table
+                .iter()
+                .map(|&(x, y)| Vec2f::from_parts(0.5 + f32::from(x) / 16.0, 0.5 + f32::from(y) / 16.0))
+                .collect();
+        }
+        match self {
+            JitterPattern::Grid => {
+                let side = (count as f32).sqrt().ceil() as usize;
+                (0..count)
+                    .map(|index| {
+                        let (x, y) = (index % side, index / side);
+                        Vec2f::from_parts((x as f32 + 0.5) / side as f32, (y as f32 + 0.5) / side as f32)
+                    })
+                    .collect()
+            }
+            JitterPattern::Standard | JitterPattern::Halton => (0..count as u32)
+                .map(|index| Vec2f::from_parts(halton(index + 1, 2), halton(index + 1, 3)))
+                .collect(),
+        }
+    }
+}
+
+///
+/// Several samples per Pixel at fixed sub-pixel positions, resolved to a `Canvas` with a
+/// choice of `ReconstructionFilter`.
+///
+/// Wider filters take samples from neighbouring Pixels into account as well, trading
+/// sharpness for less aliasing.
+///
+/// ```
+/// use softrender::canvas::{Color, JitterPattern, ReconstructionFilter, SampleBuffer};
+///
+/// let mut samples = SampleBuffer::new(64, 64, JitterPattern::Standard, 4);
+/// // Shade a disc, anti-aliased by its four samples per Pixel
+/// samples.render(|p| {
+///     let (dx, dy) = (p.x - 32.0, p.y - 32.0);
+///     if dx * dx + dy * dy < 400.0 { Color::white() } else { Color::black() }
+/// });
+/// let image = samples.resolve(ReconstructionFilter::MITCHELL);
+/// assert_eq!(image.get(32, 32), Color::white());
+/// ```
+///
+#[derive(Clone)]
+pub struct SampleBuffer {
+    dimensions: (usize, usize),
+    offsets: Vec<Vec2f>,
+    samples: Vec<Color>,
+}
+
+impl SampleBuffer {
+    /// Create new black SampleBuffer of `width` by `height` Pixels with `count` samples
+    /// each placed by `pattern`.
+    ///
+    /// # Panics
+    /// Panics if `count` is zero.
+    pub fn new(width: usize, height: usize, pattern: JitterPattern, count: usize) -> Self {
+        assert!(count > 0, "sample buffer needs at least one sample per pixel");
+        Self {
+            dimensions: (width, height),
+            offsets: pattern.offsets(count),
+            samples: vec![Color::black(); width * height * count],
+        }
+    }
+    /// Create new SampleBuffer from a `canvas` rendered at `factor` times the resolution in
+    /// each direction, taking each block of `factor` by `factor` Pixels as the samples of
+    /// one Pixel.
+    ///
+    /// # Panics
+    /// Panics if `factor` is zero.
+    pub fn from_supersampled(canvas: &Canvas, factor: usize) -> Self {
+        assert!(factor > 0, "supersampling factor must be at least one");
+        let (width, height) = (canvas.width() / factor, canvas.height() / factor);
+        let mut buffer = SampleBuffer::new(width, height, JitterPattern::Grid, factor * factor);
+        for y in 0..height {
+            for x in 0..width {
+                for index in 0..factor * factor {
+                    let color = canvas.get(x * factor + index % factor, y * factor + index / factor);
+                    buffer.set(x, y, index, color);
+                }
+            }
+        }
+        buffer
+    }
+    /// Get Width in Pixels
+    pub fn width(&self) -> usize {
+        self.dimensions.0
+    }
+    /// Get Height in Pixels
+    pub fn height(&self) -> usize {
+        self.dimensions.1
+    }
+    /// Number of samples per Pixel
+    pub fn count(&self) -> usize {
+        self.offsets.len()
+    }
+    /// Positions of the samples within each Pixel, from 0 to 1 on each axis
+    pub fn offsets(&self) -> &[Vec2f] {
+        &self.offsets
+    }
+    /// Position of sample `index` of Pixel (x, y) in Pixels
+    pub fn position(&self, x: usize, y: usize, index: usize) -> Vec2f {
+        Vec2f::from_parts(x as f32, y as f32) + self.offsets[index]
+    }
+    /// Get sample `index` of Pixel (x, y)
+    pub fn get(&self, x: usize, y: usize, index: usize) -> Color {
+        self.samples[self.index(x, y, index)]
+    }
+    /// Set sample `index` of Pixel (x, y)
+    pub fn set(&mut self, x: usize, y: usize, index: usize, color: Color) {
+        let index = self.index(x, y, index);
+        self.samples[index] = color;
+    }
+    /// Set every sample to what `shade` returns for its position in Pixels
+    pub fn render<F: FnMut(Vec2f) -> Color>(&mut self, mut shade: F) {
+        let (width, height) = self.dimensions;
+        for y in 0..height {
+            for x in 0..width {
+                for index in 0..self.count() {
+                    let color = shade(self.position(x, y, index));
+                    self.set(x, y, index, color);
+                }
+            }
+        }
+    }
+    /// Filter the samples down to one color per Pixel with `filter`.
+    ///
+    /// Weights are normalized per Pixel so flat areas keep their color up to the edges of
+    /// the image. Filters with negative lobes can push colors slightly past their range.
+    pub fn resolve(&self, filter: ReconstructionFilter) -> Canvas {
+        let (width, height) = self.dimensions;
+        let radius = filter.radius();
+        let mut canvas = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let center = Vec2f::from_parts(x as f32 + 0.5, y as f32 + 0.5);
+                let x0 = (center.x - radius).floor().max(0.0) as usize;
+                let y0 = (center.y - radius).floor().max(0.0) as usize;
+                let x1 = ((center.x + radius).floor() as usize).min(width - 1);
+                let y1 = ((center.y + radius).floor() as usize).min(height - 1);
+                let (mut sum, mut total) = (Color::black(), 0.0);
+                for sy in y0..=y1 {
+                    for sx in x0..=x1 {
+                        for index in 0..self.count() {
+                            let weight = filter.weight(self.position(sx, sy, index) - center);
+                            if weight != 0.0 {
+                                sum += self.get(sx, sy, index) * weight;
+                                total += weight;
+                            }
+                        }
+                    }
+                }
+                let color = if total.abs() > f32::EPSILON { sum * (1.0 / total) } else { self.average(x, y) };
+                canvas.set(x, y, color);
+            }
+        }
+        canvas
+    }
+    /// Unweighted mean of the samples of Pixel (x, y)
+    fn average(&self, x: usize, y: usize) -> Color {
+        let mut sum = Color::black();
+        for index in 0..self.count() {
+            sum += self.get(x, y, index);
+        }
+        sum * (1.0 / self.count() as f32)
+    }
+    fn index(&self, x: usize, y: usize, index: usize) -> usize {
+        assert!(x < self.dimensions.0 && y < self.dimensions.1 && index < self.count());
+        (y * self.dimensions.0 + x) * self.count() + index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JitterPattern, ReconstructionFilter, SampleBuffer};
+    use crate::canvas::{Canvas, Color};
+    use crate::math::Vec2f;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_filters() {
+        assert_eq!(ReconstructionFilter::Box.weight_1d(0.5), 1.0);
+        assert_eq!(ReconstructionFilter::Box.weight_1d(0.6), 0.0);
+        assert_approx_eq!(ReconstructionFilter::Tent.weight(Vec2f::from_parts(0.5, 0.5)), 0.25);
+        assert_eq!(ReconstructionFilter::GAUSSIAN.weight_1d(1.5), 0.0);
+        assert!(ReconstructionFilter::GAUSSIAN.weight_1d(0.5) > ReconstructionFilter::GAUSSIAN.weight_1d(1.0));
+        let mitchell = ReconstructionFilter::MITCHELL;
+        assert_approx_eq!(mitchell.weight_1d(0.0), 8.0 / 9.0);
+        assert_approx_eq!(mitchell.weight_1d(1.0), 1.0 / 18.0);
+        assert_approx_eq!(mitchell.weight_1d(2.0), 0.0);
+        // The negative lobe
+        assert!(mitchell.weight_1d(1.5) < 0.0);
+    }
+
+    #[test]
+    fn test_patterns() {
+        let grid = JitterPattern::Grid.offsets(4);
+        assert_eq!(grid[3], Vec2f::from_parts(0.75, 0.75));
+        let standard = JitterPattern::Standard.offsets(4);
+        assert_eq!(standard[0], Vec2f::from_parts(0.375, 0.125));
+        // No two samples share a row or column
+        for (index, a) in standard.iter().enumerate() {
+            assert!(standard[index + 1..].iter().all(|b| a.x != b.x && a.y != b.y));
+        }
+        for pattern in &[JitterPattern::Grid, JitterPattern::Standard, JitterPattern::Halton] {
+            for count in 1..17 {
+                let offsets = pattern.offsets(count);
+                assert_eq!(offsets.len(), count);
+                assert!(offsets.iter().all(|o| o.x > 0.0 && o.x < 1.0 && o.y > 0.0 && o.y < 1.0));
+            }
+        }
+        assert_eq!(JitterPattern::Standard.offsets(3), JitterPattern::Halton.offsets(3));
+    }
+
+    #[test]
+    fn test_resolve() {
+        // Supersampled vertical edge a third of the way into the second column
+        let mut canvas = Canvas::new(12, 6);
+        for y in 0..6 {
+            for x in 4..12 {
+                canvas.set(x, y, Color::white());
+            }
+        }
+        let samples = SampleBuffer::from_supersampled(&canvas, 3);
+        assert_eq!((samples.width(), samples.height(), samples.count()), (4, 2, 9));
+        let boxed = samples.resolve(ReconstructionFilter::Box);
+        assert_eq!(boxed.get(0, 0), Color::black());
+        assert_approx_eq!(boxed.get(1, 1).r.value(), 2.0 / 3.0);
+        assert_eq!(boxed.get(2, 0), Color::white());
+        // Wider filters spread the edge into the neighbouring Pixels
+        let gaussian = samples.resolve(ReconstructionFilter::GAUSSIAN);
+        assert!(gaussian.get(0, 0).r.value() > 0.0);
+        assert!(gaussian.get(2, 0).r.value() < 1.0);
+        // Flat areas keep their value with every filter, even at the image border
+        let mut flat = SampleBuffer::new(5, 5, JitterPattern::Halton, 6);
+        flat.render(|_| Color::grey());
+        for filter in &[ReconstructionFilter::Tent, ReconstructionFilter::GAUSSIAN, ReconstructionFilter::MITCHELL] {
+            let image = flat.resolve(*filter);
+            assert!(image.pixels().all(|(_, _, c)| (c.r.value() - 0.5).abs() < 1e-5));
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Canvas, Color, PixelBuffer};
+
+/// Color with an alpha (coverage) value
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Rgba {
+    pub color: Color,
+    pub alpha: f32,
+}
+
+impl Rgba {
+    /// Create new Rgba
+    pub const fn new(color: Color, alpha: f32) -> Self {
+        Self { color, alpha }
+    }
+    /// Create fully transparent Rgba
+    pub const fn transparent() -> Self {
+        Self::new(Color::black(), 0.0)
+    }
+    /// Multiply the color by alpha, converting a straight alpha value to premultiplied
+    pub fn premultiply(self) -> Self {
+        Self::new(self.color * self.alpha, self.alpha)
+    }
+    /// Divide the color by alpha, converting a premultiplied value to straight alpha
+    pub fn unpremultiply(self) -> Self {
+        if self.alpha > 0.0 {
+            Self::new(self.color * (1.0 / self.alpha), self.alpha)
+        } else {
+            Self::transparent()
+        }
+    }
+}
+
+///
+/// Color Canvas with an alpha channel, for sprites and overlays composited with
+/// `Canvas::blit`
+///
+#[derive(Clone)]
+pub struct RgbaCanvas {
+    dimensions: (usize, usize),
+    pixels: Vec<Rgba>,
+}
+
+impl RgbaCanvas {
+    /// Create new fully transparent RgbaCanvas
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            dimensions: (width, height),
+            pixels: vec![Rgba::transparent(); width * height],
+        }
+    }
+    /// Create new opaque RgbaCanvas from the pixels of `canvas`
+    pub fn from_canvas(canvas: &Canvas) -> Self {
+        let mut rgba = Self::new(canvas.width(), canvas.height());
+        for (x, y, color) in canvas.pixels() {
+            rgba.set(x, y, Rgba::new(color, 1.0));
+        }
+        rgba
+    }
+    /// Get Width of RgbaCanvas
+    pub fn width(&self) -> usize {
+        self.dimensions.0
+    }
+    /// Get Height of RgbaCanvas
+    pub fn height(&self) -> usize {
+        self.dimensions.1
+    }
+    /// Get value of Pixel at (x, y)
+    pub fn get(&self, x: usize, y: usize) -> Rgba {
+        self.pixels[self.index(x, y)]
+    }
+    /// Set value of Pixel at (x, y)
+    pub fn set(&mut self, x: usize, y: usize, value: Rgba) {
+        let index = self.index(x, y);
+        self.pixels[index] = value;
+    }
+    /// Set every Pixel to `value`
+    pub fn fill(&mut self, value: Rgba) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel = value;
+        }
+    }
+    /// Convert every Pixel from straight to premultiplied alpha
+    pub fn premultiply(&mut self) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel = pixel.premultiply();
+        }
+    }
+    fn index(&self, x: usize, y: usize) -> usize {
+        assert!(x < self.dimensions.0);
+        assert!(y < self.dimensions.1);
+        (y * self.dimensions.0) + x
+    }
+}
+
+impl PixelBuffer for RgbaCanvas {
+    type Pixel = Rgba;
+
+    fn width(&self) -> usize {
+        RgbaCanvas::width(self)
+    }
+    fn height(&self) -> usize {
+        RgbaCanvas::height(self)
+    }
+    fn get(&self, x: usize, y: usize) -> Rgba {
+        RgbaCanvas::get(self, x, y)
+    }
+    fn set(&mut self, x: usize, y: usize, value: Rgba) {
+        RgbaCanvas::set(self, x, y, value)
+    }
+}
@@ -0,0 +1,225 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+use super::{Canvas, Color};
+use crate::math::{equirect_direction, Sh9, Vec2f, Vec3f};
+use std::f32::consts::PI;
+
+/// Perez distribution coefficients A to E of one xyY component
+type Perez = [f32; 5];
+
+/// Change of the Perez coefficients of Y, x and y per unit of turbidity
+const PEREZ_SLOPE: [Perez; 3] = [
+    [0.1787, -0.3554, -0.0227, 0.1206, -0.0670],
+    [-0.0193, -0.0665, -0.0004, -0.0641, -0.0033],
+    [-0.0167, -0.0950, -0.0079, -0.0441, -0.0109],
+];
+/// Perez coefficients of Y, x and y at zero turbidity
+const PEREZ_OFFSET: [Perez; 3] = [
+    [-1.4630, 0.4275, 5.3251, -2.5771, 0.3703],
+    [-0.2592, 0.0008, 0.2125, -0.8989, 0.0452],
+    [-0.2608, 0.0092, 0.2102, -1.6537, 0.0529],
+];
+
+///
+/// Analytic daylight sky after Preetham, Shirley and Smits, "A Practical Analytic Model
+/// for Daylight" (1999).
+///
+/// Gives the radiance of a clear or hazy sky for a sun direction and atmospheric turbidity
+/// in cd/m², the units of `PointLight`, so it pairs with a daylight exposure such as
+/// `Camera::set_ev100(14.0)`. Use `to_equirect` as a raytracer environment and
+/// `project_sh9` for ambient light in the rasterizer.
+///
+/// ```
+/// use softrender::canvas::SunSky;
+/// use softrender::math::Vec3f;
+///
+/// let sky = SunSky::new(Vec3f::new(0.0, 0.5, -1.0), 3.0);
+/// let zenith = sky.radiance(Vec3f::UNIT_Y);
+/// let near_sun = sky.radiance(Vec3f::new(0.0, 0.6, -1.0).normalize());
+/// assert!(near_sun.luminance() > zenith.luminance());
+/// assert!(zenith.b.value() > zenith.r.value());
+/// ```
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SunSky {
+    sun_direction: Vec3f,
+    turbidity: f32,
+    /// Factor applied to the radiance, 1.0 for physical cd/m²
+    pub scale: f32,
+    /// Fraction of the horizon radiance reflected by the ground below it
+    pub ground_albedo: f32,
+    distribution: [Perez; 3],
+    zenith: [f32; 3],
+    normalization: [f32; 3],
+}
+
+impl SunSky {
+    /// Create new SunSky with the sun in `sun_direction` and `turbidity` from 2 for a very
+    /// clear sky to 10 for haze. Turbidity is limited to 1.7 to 10, where the model holds.
+    pub fn new(sun_direction: Vec3f, turbidity: f32) -> Self {
+        let sun_direction = sun_direction.normalize();
+        let t = turbidity.clamp(1.7, 10.0);
+        let mut distribution = [[0.0; 5]; 3];
+        for component in 0..3 {
+            for coefficient in 0..5 {
+                distribution[component][coefficient] =
+                    PEREZ_SLOPE[component][coefficient] * t + PEREZ_OFFSET[component][coefficient];
+            }
+        }
+        // The model is only fitted for the sun above the horizon
+        let theta = sun_direction.y.clamp(0.0, 1.0).acos().min(PI / 2.0 - 1e-3);
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta);
+        // Zenith luminance is fitted in kcd/m²
+        let luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0) * 1000.0;
+        let (t2, th2, th3) = (t * t, theta * theta, theta * theta * theta);
+        let x = t2 * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * theta)
+            + t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * theta + 0.00394)
+            + (0.11693 * th3 - 0.21196 * th2 + 0.06052 * theta + 0.25886);
+        let y = t2 * (0.00275 * th3 - 0.00610 * th2 + 0.00317 * theta)
+            + t * (-0.04214 * th3 + 0.08970 * th2 - 0.04153 * theta + 0.00516)
+            + (0.15346 * th3 - 0.26756 * th2 + 0.06670 * theta + 0.26688);
+        // Radiance is relative to the zenith, where the view is `theta` from the sun
+        let mut normalization = [0.0; 3];
+        for (component, coefficients) in distribution.iter().enumerate() {
+            normalization[component] = perez(coefficients, 1.0, theta);
+        }
+        Self {
+            sun_direction,
+            turbidity: t,
+            scale: 1.0,
+            ground_albedo: 0.3,
+            distribution,
+            zenith: [luminance, x, y],
+            normalization,
+        }
+    }
+    /// Unit direction towards the sun
+    pub fn sun_direction(&self) -> Vec3f {
+        self.sun_direction
+    }
+    /// Atmospheric turbidity the sky was created with
+    pub fn turbidity(&self) -> f32 {
+        self.turbidity
+    }
+    /// Radiance of the sky seen in the unit vector `direction`.
+    ///
+    /// Below the horizon the ground reflects `ground_albedo` of the horizon radiance.
+    pub fn radiance(&self, direction: Vec3f) -> Color {
+        if direction.y < 0.0 {
+            let horizon = Vec3f::new(direction.x, 0.0, direction.z);
+            let horizon = if horizon.magnitude() > 0.0 { horizon.normalize() } else { Vec3f::UNIT_X };
+            return self.sky(horizon) * self.ground_albedo;
+        }
+        self.sky(direction)
+    }
+    /// Render the sky into a `width` by `height` equirectangular environment map
+    pub fn to_equirect(&self, width: usize, height: usize) -> Canvas {
+        let mut canvas = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let direction = canvas.texel_direction(x, y);
+                canvas.set(x, y, self.radiance(direction));
+            }
+        }
+        canvas
+    }
+    /// Project the sky onto spherical harmonics for ambient lighting
+    pub fn project_sh9(&self) -> Sh9 {
+        let (width, height) = (64, 32);
+        Sh9::project_equirect(width, height, |x, y| {
+            let uv = Vec2f::from_parts((x as f32 + 0.5) / width as f32, (y as f32 + 0.5) / height as f32);
+            let color = self.radiance(equirect_direction(uv));
+            Vec3f::new(color.r.value(), color.g.value(), color.b.value())
+        })
+    }
+    fn sky(&self, direction: Vec3f) -> Color {
+        // Keep the view angle just above the horizon where the Perez function blows up
+        let cos_theta = direction.y.max(1e-3);
+        let gamma = direction.dot(self.sun_direction).clamp(-1.0, 1.0).acos();
+        let component = |index: usize| {
+            let relative = perez(&self.distribution[index], cos_theta, gamma) / self.normalization[index];
+            self.zenith[index] * relative
+        };
+        let (luminance, x, y) = (component(0), component(1), component(2));
+        if y <= 0.0 || luminance <= 0.0 {
+            return Color::black();
+        }
+        // xyY to XYZ to linear sRGB
+        let big_x = x / y * luminance;
+        let big_z = (1.0 - x - y) / y * luminance;
+        let r = 3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z;
+        let g = -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z;
+        let b = 0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z;
+        Color::new(r.max(0.0), g.max(0.0), b.max(0.0)) * self.scale
+    }
+}
+
+/// Perez sky distribution for a view `acos(cos_theta)` from the zenith and `gamma` from the
+/// sun
+fn perez(coefficients: &Perez, cos_theta: f32, gamma: f32) -> f32 {
+    let [a, b, c, d, e] = *coefficients;
+    let cos_gamma = gamma.cos();
+    (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SunSky;
+    use crate::math::Vec3f;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_zenith() {
+        // Straight up the model gives its fitted zenith values
+        let sky = SunSky::new(Vec3f::new(0.0, 1.0, -1.0), 2.0);
+        let zenith = sky.radiance(Vec3f::UNIT_Y);
+        let theta = std::f32::consts::FRAC_PI_4;
+        let chi = (4.0 / 9.0 - 2.0 / 120.0) * (std::f32::consts::PI - 2.0 * theta);
+        let expected = ((4.0453 * 2.0 - 4.9710) * chi.tan() - 0.2155 * 2.0 + 2.4192) * 1000.0;
+        assert_approx_eq!(zenith.luminance(), expected, expected * 1e-2);
+        // A clear sky is blue
+        assert!(zenith.b.value() > zenith.g.value() && zenith.g.value() > zenith.r.value());
+    }
+
+    #[test]
+    fn test_sun_and_ground() {
+        let sky = SunSky::new(Vec3f::new(1.0, 0.3, 0.0), 4.0);
+        assert_eq!(sky.sun_direction(), Vec3f::new(1.0, 0.3, 0.0).normalize());
+        let toward = sky.radiance(Vec3f::new(1.0, 0.35, 0.0).normalize()).luminance();
+        let away = sky.radiance(Vec3f::new(-1.0, 0.35, 0.0).normalize()).luminance();
+        assert!(toward > away * 2.0);
+        let horizon = sky.radiance(Vec3f::new(0.0, 0.0, 1.0));
+        let ground = sky.radiance(Vec3f::new(0.0, -1.0, 1.0).normalize());
+        assert_approx_eq!(ground.luminance(), horizon.luminance() * 0.3, 1.0);
+        // Hazier skies are brighter and greyer at the horizon
+        let hazy = SunSky::new(Vec3f::new(1.0, 0.3, 0.0), 9.0).radiance(Vec3f::new(0.0, 0.05, 1.0).normalize());
+        let clear = sky.radiance(Vec3f::new(0.0, 0.05, 1.0).normalize());
+        assert!(hazy.r.value() / hazy.b.value() > clear.r.value() / clear.b.value());
+    }
+
+    #[test]
+    fn test_environment() {
+        let sky = SunSky::new(Vec3f::new(0.0, 0.7, -1.0), 3.0);
+        let map = sky.to_equirect(32, 16);
+        assert_eq!(map.get(0, 0), sky.radiance(map.texel_direction(0, 0)));
+        let sh = sky.project_sh9();
+        let up = sh.irradiance(Vec3f::UNIT_Y);
+        let down = sh.irradiance(-Vec3f::UNIT_Y);
+        assert!(up.x > down.x && up.z > 0.0);
+    }
+}
@@ -0,0 +1,158 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Conversion between colors and light of single wavelengths, for spectral rendering.
+//!
+//! Colors become spectra by giving each channel a band of wavelengths, blue below 490nm,
+//! green up to 580nm and red above. This is coarse, but products of colors stay products
+//! of spectra, so a path can be traced in RGB and only turned into wavelengths where they
+//! go separate ways.
+
+use super::Color;
+
+/// Shortest wavelength rendered, in nanometers
+pub const WAVELENGTH_MIN: f32 = 380.0;
+/// Longest wavelength rendered, in nanometers
+pub const WAVELENGTH_MAX: f32 = 730.0;
+/// Number of wavelengths carried by each path
+pub const WAVELENGTH_COUNT: usize = 4;
+
+/// Integral of the CIE y matching function over the rendered wavelengths
+const Y_INTEGRAL: f32 = 106.917;
+/// Linear sRGB of a flat spectrum of 1, divided out so that it comes back white
+const WHITE: [f32; 3] = [1.2006, 0.9498, 0.9077];
+
+/// CIE 1931 color matching functions at `wavelength` nanometers, using the multi lobe
+/// Gaussian fit of Wyman, Sloan and Shirley
+pub fn cie_xyz(wavelength: f32) -> [f32; 3] {
+    let lobe = |mean: f32, below: f32, above: f32| {
+        let t = (wavelength - mean) / if wavelength < mean { below } else { above };
+        (-0.5 * t * t).exp()
+    };
+    [
+        1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7) - 0.065 * lobe(501.1, 20.4, 26.2),
+        0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1),
+        1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8),
+    ]
+}
+
+/// Linear sRGB color of CIE XYZ tristimulus values
+pub fn xyz_to_linear_srgb([x, y, z]: [f32; 3]) -> Color {
+    Color::new(
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    )
+}
+
+///
+/// Wavelengths carried by one path, in nanometers.
+///
+/// The first is the hero wavelength, picked at random, which decides where the path goes
+/// when light of different wavelengths would split. The others are spread evenly over the
+/// range after it.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Wavelengths {
+    pub lambda: [f32; WAVELENGTH_COUNT],
+}
+
+impl Wavelengths {
+    /// Pick the wavelengths from a uniform random number `u` in [0, 1)
+    pub fn sample(u: f32) -> Wavelengths {
+        let range = WAVELENGTH_MAX - WAVELENGTH_MIN;
+        let mut lambda = [0.0; WAVELENGTH_COUNT];
+        for (index, wavelength) in lambda.iter_mut().enumerate() {
+            let offset = (u + index as f32 / WAVELENGTH_COUNT as f32).fract();
+            *wavelength = WAVELENGTH_MIN + offset * range;
+        }
+        Wavelengths { lambda }
+    }
+    /// Wavelength deciding where the path goes
+    pub fn hero(&self) -> f32 {
+        self.lambda[0]
+    }
+    /// Linear color of light with `values` at each of the wavelengths.
+    ///
+    /// Averaged over many samples a flat spectrum of 1 comes out white.
+    pub fn to_color(&self, values: [f32; WAVELENGTH_COUNT]) -> Color {
+        // Every wavelength is picked with density 1 / range
+        let scale = (WAVELENGTH_MAX - WAVELENGTH_MIN) / (WAVELENGTH_COUNT as f32 * Y_INTEGRAL);
+        let mut xyz = [0.0; 3];
+        for (&wavelength, &value) in self.lambda.iter().zip(values.iter()) {
+            let matching = cie_xyz(wavelength);
+            for (total, weight) in xyz.iter_mut().zip(matching.iter()) {
+                *total += value * weight * scale;
+            }
+        }
+        let color = xyz_to_linear_srgb(xyz);
+        Color::new(color.r.value() / WHITE[0], color.g.value() / WHITE[1], color.b.value() / WHITE[2])
+    }
+}
+
+impl Color {
+    /// Value at `wavelength` nanometers of the spectrum standing in for this color
+    pub fn at_wavelength(&self, wavelength: f32) -> f32 {
+        if wavelength < 490.0 {
+            self.b.value()
+        } else if wavelength < 580.0 {
+            self.g.value()
+        } else {
+            self.r.value()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cie_xyz, Wavelengths, WAVELENGTH_COUNT};
+    use crate::canvas::Color;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_wavelengths() {
+        let wavelengths = Wavelengths::sample(0.9);
+        assert_approx_eq!(wavelengths.hero(), 695.0, 1e-3);
+        assert_approx_eq!(wavelengths.lambda[1], 432.5, 1e-3);
+        assert!(wavelengths.lambda.iter().all(|&wavelength| (380.0..730.0).contains(&wavelength)));
+        // Green peaks around 555nm
+        assert!(cie_xyz(555.0)[1] > 0.99);
+        assert!(cie_xyz(450.0)[2] > cie_xyz(650.0)[2]);
+    }
+
+    #[test]
+    fn test_flat_spectrum_is_white() {
+        let count = 1000;
+        let mut total = [0.0; 3];
+        for index in 0..count {
+            let color = Wavelengths::sample((index as f32 + 0.5) / count as f32).to_color([1.0; WAVELENGTH_COUNT]);
+            total[0] += color.r.value() / count as f32;
+            total[1] += color.g.value() / count as f32;
+            total[2] += color.b.value() / count as f32;
+        }
+        for channel in total.iter() {
+            assert_approx_eq!(channel, 1.0, 0.01);
+        }
+    }
+
+    #[test]
+    fn test_at_wavelength() {
+        let color = Color::new(0.2, 0.4, 0.6);
+        assert_eq!(color.at_wavelength(450.0), 0.6);
+        assert_eq!(color.at_wavelength(550.0), 0.4);
+        assert_eq!(color.at_wavelength(650.0), 0.2);
+    }
+}
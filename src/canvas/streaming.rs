@@ -0,0 +1,313 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Canvas, Color, Format, MipLevel, Sampler, Texture};
+use crate::math::Vec2f;
+use crate::{Error, Result};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+///
+/// Where a `StreamingTexture` loads its mip levels from, such as a file holding each level
+/// separately or a procedural generator.
+///
+pub trait MipSource {
+    /// Width and height of the full size level
+    fn size(&self) -> (usize, usize);
+    /// Load mip level `level`, which must be `width` by `height`
+    fn load(&self, level: usize, width: usize, height: usize) -> Result<Canvas>;
+}
+
+/// Serves the levels of an in memory Texture, decoding compressed levels
+impl MipSource for Texture {
+    fn size(&self) -> (usize, usize) {
+        (self.width(), self.height())
+    }
+    fn load(&self, level: usize, width: usize, height: usize) -> Result<Canvas> {
+        let source = self.levels().get(level).ok_or_else(|| Error::Decode(format!("no mip level {}", level)))?;
+        let mut canvas = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                canvas.set(x, y, source.get(x, y));
+            }
+        }
+        Ok(canvas)
+    }
+}
+
+///
+/// Texture keeping only its smaller mip levels in memory, loading larger ones from a
+/// `MipSource` once sampling asks for them.
+///
+/// Sampling never waits on loading: it falls back to the largest level in memory and
+/// records the level it wanted. Calling `update` between frames then loads the levels
+/// asked for since the last update.
+///
+/// ```
+/// use softrender::canvas::{Canvas, Color, Sampler, StreamingTexture, Texture};
+/// use softrender::math::Vec2f;
+///
+/// let source = Texture::new(Canvas::new(1024, 1024));
+/// let mut texture = StreamingTexture::new(Box::new(source), 64).unwrap();
+/// assert_eq!(texture.resident_level(), 4);
+/// // Seen up close, the first frame uses the 64x64 level ...
+/// texture.sample_lod(Vec2f::from_parts(0.5, 0.5), 0.0, &Sampler::default());
+/// assert_eq!(texture.requested_level(), Some(0));
+/// // ... and the full size level is there from the next one on
+/// texture.update().unwrap();
+/// assert_eq!(texture.resident_level(), 0);
+/// ```
+///
+pub struct StreamingTexture {
+    source: Box<dyn MipSource>,
+    dimensions: (usize, usize),
+    level_count: usize,
+    format: Format,
+    resident: Texture,
+    first: usize,
+    requested: AtomicUsize,
+}
+
+impl StreamingTexture {
+    /// Create new StreamingTexture loading from `source` the levels no larger than
+    /// `max_initial_size` texels on either side, and the smallest level in any case
+    pub fn new(source: Box<dyn MipSource>, max_initial_size: usize) -> Result<StreamingTexture> {
+        let dimensions = source.size();
+        let level_count = level_count(dimensions);
+        let first = (0..level_count)
+            .find(|&level| {
+                let (width, height) = level_size(dimensions, level);
+                width.max(height) <= max_initial_size
+            })
+            .unwrap_or(level_count - 1);
+        let mut levels = Vec::with_capacity(level_count - first);
+        for level in first..level_count {
+            levels.push(load_level(source.as_ref(), dimensions, level)?);
+        }
+        Ok(StreamingTexture {
+            source,
+            dimensions,
+            level_count,
+            format: Format::Rgb32F,
+            resident: Texture::from_levels(levels),
+            first,
+            requested: AtomicUsize::new(usize::MAX),
+        })
+    }
+    /// Tag this StreamingTexture as holding `format` data
+    pub fn with_format(mut self, format: Format) -> StreamingTexture {
+        self.format = format;
+        self.resident = self.resident.with_format(format);
+        self
+    }
+    /// Width of the full size level, whether loaded or not
+    pub fn width(&self) -> usize {
+        self.dimensions.0
+    }
+    /// Height of the full size level, whether loaded or not
+    pub fn height(&self) -> usize {
+        self.dimensions.1
+    }
+    /// Number of levels down to a single texel
+    pub fn level_count(&self) -> usize {
+        self.level_count
+    }
+    /// Largest level in memory, 0 when fully loaded
+    pub fn resident_level(&self) -> usize {
+        self.first
+    }
+    /// Largest level sampling asked for since the last `update`, if any
+    pub fn requested_level(&self) -> Option<usize> {
+        match self.requested.load(Ordering::Relaxed) {
+            usize::MAX => None,
+            level => Some(level),
+        }
+    }
+    /// Levels in memory as a Texture whose level 0 is `resident_level`
+    pub fn resident(&self) -> &Texture {
+        &self.resident
+    }
+    /// Bytes of heap memory used by the levels in memory
+    pub fn memory(&self) -> usize {
+        self.resident.levels().iter().map(MipLevel::memory).sum()
+    }
+    /// Ask for `level` to be loaded by the next `update`
+    pub fn request(&self, level: usize) {
+        self.requested.fetch_min(level, Ordering::Relaxed);
+    }
+    /// Read the color at `uv` from level of detail `lod`, or the largest level in memory
+    /// if that is smaller, asking for the level wanted to be loaded
+    pub fn sample_lod(&self, uv: Vec2f, lod: f32, sampler: &Sampler) -> Color {
+        self.request((lod + sampler.mip_bias).max(0.0) as usize);
+        self.resident.sample_lod(uv, lod - self.first as f32, sampler)
+    }
+    /// Read the color at `uv` for a pixel whose texture coordinates change by `dx` and
+    /// `dy` to the next pixel across and down, as `Texture::sample_grad`
+    pub fn sample_grad(&self, uv: Vec2f, dx: Vec2f, dy: Vec2f, sampler: &Sampler) -> Color {
+        let size = Vec2f::from_parts(self.width() as f32, self.height() as f32);
+        // Anisotropic filtering reads the level matching the short side of the footprint
+        let (x_length, y_length) = ((dx * size).magnitude(), (dy * size).magnitude());
+        let major = x_length.max(y_length);
+        let minor = x_length.min(y_length).max(major / sampler.anisotropy.max(1) as f32);
+        self.request((minor.max(1e-8).log2() + sampler.mip_bias).max(0.0) as usize);
+        // The resident levels are the same chain, so their sizes give the right lod
+        self.resident.sample_grad(uv, dx, dy, sampler)
+    }
+    /// Load the levels asked for since the last update, returning how many were loaded.
+    ///
+    /// Loading stops at the first level that fails, keeping those loaded before it.
+    pub fn update(&mut self) -> Result<usize> {
+        let wanted = self.requested.swap(usize::MAX, Ordering::Relaxed).min(self.level_count - 1);
+        if wanted >= self.first {
+            return Ok(0);
+        }
+        let mut loaded = Vec::with_capacity(self.first - wanted);
+        let mut result = Ok(());
+        for level in (wanted..self.first).rev() {
+            match load_level(self.source.as_ref(), self.dimensions, level) {
+                Ok(texels) => loaded.push(texels),
+                Err(error) => {
+                    result = Err(error);
+                    break;
+                }
+            }
+        }
+        let count = loaded.len();
+        if count > 0 {
+            loaded.reverse();
+            loaded.extend(self.resident.levels().iter().cloned());
+            self.resident = Texture::from_levels(loaded).with_format(self.format);
+            self.first -= count;
+        }
+        result.map(|()| count)
+    }
+    /// Drop the levels larger than `level` from memory, keeping at least the smallest
+    /// level, and return the bytes freed. They are loaded again when asked for.
+    pub fn evict(&mut self, level: usize) -> usize {
+        let level = level.min(self.level_count - 1);
+        if level <= self.first {
+            return 0;
+        }
+        let before = self.memory();
+        let kept = self.resident.levels()[level - self.first..].to_vec();
+        self.resident = Texture::from_levels(kept).with_format(self.format);
+        self.first = level;
+        before - self.memory()
+    }
+}
+
+/// Levels in a full chain for a texture of `dimensions`
+fn level_count(dimensions: (usize, usize)) -> usize {
+    let mut count = 1;
+    let (mut width, mut height) = dimensions;
+    while width > 1 || height > 1 {
+        width = (width / 2).max(1);
+        height = (height / 2).max(1);
+        count += 1;
+    }
+    count
+}
+
+fn level_size(dimensions: (usize, usize), level: usize) -> (usize, usize) {
+    ((dimensions.0 >> level).max(1), (dimensions.1 >> level).max(1))
+}
+
+fn load_level(source: &dyn MipSource, dimensions: (usize, usize), level: usize) -> Result<MipLevel> {
+    let (width, height) = level_size(dimensions, level);
+    let canvas = source.load(level, width, height)?;
+    if (canvas.width(), canvas.height()) != (width, height) {
+        let found = format!("{}x{}", canvas.width(), canvas.height());
+        return Err(Error::Decode(format!("mip level {} is {}, expected {}x{}", level, found, width, height)));
+    }
+    Ok(MipLevel::Raw(canvas))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MipSource, StreamingTexture};
+    use crate::canvas::{Canvas, Color, Filter, Sampler, Texture};
+    use crate::math::Vec2f;
+    use crate::{Error, Result};
+
+    /// Texel checkerboard, which every level but the first averages to grey
+    fn checker(size: usize) -> Texture {
+        let mut canvas = Canvas::new(size, size);
+        for y in 0..size {
+            for x in 0..size {
+                if (x + y) % 2 == 1 {
+                    canvas.set(x, y, Color::white());
+                }
+            }
+        }
+        Texture::new(canvas)
+    }
+
+    #[test]
+    fn test_streaming() {
+        let mut texture = StreamingTexture::new(Box::new(checker(64)), 16).unwrap();
+        assert_eq!(texture.level_count(), 7);
+        assert_eq!(texture.resident_level(), 2);
+        assert_eq!(texture.memory(), (16 * 16 + 8 * 8 + 4 * 4 + 2 * 2 + 1) * 12);
+        let nearest = Sampler {
+            filter: Filter::Nearest,
+            ..Sampler::default()
+        };
+        let uv = Vec2f::from_parts(0.5 / 64.0, 0.5 / 64.0);
+        assert_eq!(texture.sample_lod(uv, 3.0, &nearest), Color::grey());
+        assert_eq!(texture.requested_level(), Some(3));
+        assert_eq!(texture.update().unwrap(), 0);
+        // Closer than the resident levels allow
+        assert_eq!(texture.sample_lod(uv, 0.0, &nearest), Color::grey());
+        assert_eq!(texture.requested_level(), Some(0));
+        assert_eq!(texture.update().unwrap(), 2);
+        assert_eq!(texture.requested_level(), None);
+        assert_eq!(texture.resident_level(), 0);
+        assert_eq!(texture.sample_lod(uv, 0.0, &nearest), Color::black());
+        assert_eq!(texture.evict(1), 64 * 64 * 12);
+        assert_eq!(texture.resident_level(), 1);
+        assert_eq!(texture.evict(10), (32 * 32 + 16 * 16 + 8 * 8 + 4 * 4 + 2 * 2) * 12);
+        assert_eq!(texture.resident_level(), 6);
+        // Footprints of a texel ask for the full size level too
+        let texture = StreamingTexture::new(Box::new(checker(64)), 1).unwrap();
+        let texel = Vec2f::from_parts(1.0 / 64.0, 0.0);
+        texture.sample_grad(uv, texel, Vec2f::from_parts(0.0, 1.0 / 64.0), &nearest);
+        assert_eq!(texture.requested_level(), Some(0));
+    }
+
+    struct Broken;
+
+    impl MipSource for Broken {
+        fn size(&self) -> (usize, usize) {
+            (8, 4)
+        }
+        fn load(&self, level: usize, width: usize, height: usize) -> Result<Canvas> {
+            match level {
+                0 => Err(Error::Decode("unreadable".to_string())),
+                1 => Ok(Canvas::new(width + 1, height)),
+                _ => Ok(Canvas::new(width, height)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_failed_load() {
+        assert!(StreamingTexture::new(Box::new(Broken), 2).is_ok());
+        assert!(StreamingTexture::new(Box::new(Broken), 4).is_err());
+        let mut texture = StreamingTexture::new(Box::new(Broken), 2).unwrap();
+        texture.request(0);
+        assert!(texture.update().is_err());
+        assert_eq!(texture.resident_level(), 2);
+    }
+}
@@ -0,0 +1,436 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Canvas, Color, CompressedImage, Compression, Filter, Format, Half, HalfCanvas};
+use crate::math::Vec2f;
+use crate::Result;
+
+/// How texture coordinates outside 0 to 1 are brought back into the texture
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Wrap {
+    /// Tile the texture
+    Repeat,
+    /// Tile the texture, mirroring every other copy
+    MirroredRepeat,
+    /// Stretch the edge texels outwards
+    ClampToEdge,
+}
+
+///
+/// How a texture is read, set per texture use rather than globally
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sampler {
+    /// Filter within a mip level, `Filter::Bilinear` also blends between levels
+    pub filter: Filter,
+    pub wrap_u: Wrap,
+    pub wrap_v: Wrap,
+    /// Added to the level of detail, positive values pick blurrier levels
+    pub mip_bias: f32,
+    /// Most taps `Texture::sample_grad` spreads along a stretched pixel footprint, 1 turns
+    /// anisotropic filtering off
+    pub anisotropy: u32,
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Sampler {
+            filter: Filter::Bilinear,
+            wrap_u: Wrap::Repeat,
+            wrap_v: Wrap::Repeat,
+            mip_bias: 0.0,
+            anisotropy: 1,
+        }
+    }
+}
+
+/// One mip level of a `Texture`
+#[derive(Clone)]
+pub enum MipLevel {
+    /// Texels stored as is
+    Raw(Canvas),
+    /// Texels stored at half precision and widened as they are sampled
+    Half(HalfCanvas),
+    /// Texels stored compressed and decoded as they are sampled
+    Compressed(CompressedImage),
+}
+
+impl MipLevel {
+    /// Get Width in texels
+    pub fn width(&self) -> usize {
+        match self {
+            MipLevel::Raw(canvas) => canvas.width(),
+            MipLevel::Half(canvas) => canvas.width(),
+            MipLevel::Compressed(image) => image.width(),
+        }
+    }
+    /// Get Height in texels
+    pub fn height(&self) -> usize {
+        match self {
+            MipLevel::Raw(canvas) => canvas.height(),
+            MipLevel::Half(canvas) => canvas.height(),
+            MipLevel::Compressed(image) => image.height(),
+        }
+    }
+    /// Get texel (x, y)
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        match self {
+            MipLevel::Raw(canvas) => canvas.get(x, y),
+            MipLevel::Half(canvas) => canvas.get(x, y),
+            MipLevel::Compressed(image) => image.get(x, y),
+        }
+    }
+    /// Bytes of heap memory used
+    pub fn memory(&self) -> usize {
+        match self {
+            MipLevel::Raw(canvas) => canvas.width() * canvas.height() * std::mem::size_of::<Color>(),
+            MipLevel::Half(canvas) => canvas.width() * canvas.height() * std::mem::size_of::<[Half; 3]>(),
+            MipLevel::Compressed(image) => image.memory(),
+        }
+    }
+}
+
+///
+/// Image with a chain of successively halved mip levels for sampling at a distance
+///
+#[derive(Clone)]
+pub struct Texture {
+    levels: Vec<MipLevel>,
+    format: Format,
+}
+
+impl Texture {
+    /// Create new Texture from `image`, building mip levels down to a single texel by
+    /// averaging texel squares
+    pub fn new(image: Canvas) -> Texture {
+        let mut levels = vec![image];
+        loop {
+            let last = &levels[levels.len() - 1];
+            let (width, height) = (last.width(), last.height());
+            if width <= 1 && height <= 1 {
+                break;
+            }
+            let (next_width, next_height) = ((width / 2).max(1), (height / 2).max(1));
+            let mut next = Canvas::new(next_width, next_height);
+            for y in 0..next_height {
+                for x in 0..next_width {
+                    // Odd sizes fold their last row or column into the previous texel
+                    let (x0, y0) = ((2 * x).min(width - 1), (2 * y).min(height - 1));
+                    let (x1, y1) = ((2 * x + 1).min(width - 1), (2 * y + 1).min(height - 1));
+                    let sum = last.get(x0, y0) + last.get(x1, y0) + last.get(x0, y1) + last.get(x1, y1);
+                    next.set(x, y, sum * 0.25);
+                }
+            }
+            levels.push(next);
+        }
+        Texture {
+            levels: levels.into_iter().map(MipLevel::Raw).collect(),
+            format: Format::Rgb32F,
+        }
+    }
+    /// Create new Texture from ready made `levels`, largest first, each half the size of
+    /// the one before rounding down to at least 1.
+    ///
+    /// # Panics
+    /// Panics if `levels` is empty.
+    pub fn from_levels(levels: Vec<MipLevel>) -> Texture {
+        assert!(!levels.is_empty(), "texture needs at least one level");
+        Texture {
+            levels,
+            format: Format::Rgb32F,
+        }
+    }
+    /// Create new Texture of `image` alone, without mip levels
+    pub fn without_mips(image: Canvas) -> Texture {
+        Texture {
+            levels: vec![MipLevel::Raw(image)],
+            format: Format::Rgb32F,
+        }
+    }
+    /// Tag this Texture as holding `format` data, such as depth written into every
+    /// channel of its levels
+    pub fn with_format(mut self, format: Format) -> Texture {
+        self.format = format;
+        self
+    }
+    /// Format of the data held, `Format::Rgb32F` unless set with `with_format`
+    pub fn format(&self) -> Format {
+        self.format
+    }
+    /// Width of the full size level
+    pub fn width(&self) -> usize {
+        self.levels[0].width()
+    }
+    /// Height of the full size level
+    pub fn height(&self) -> usize {
+        self.levels[0].height()
+    }
+    /// Mip levels, largest first
+    pub fn levels(&self) -> &[MipLevel] {
+        &self.levels
+    }
+    /// Store every level with `compression`, cutting memory use at some cost in quality.
+    ///
+    /// Levels already compressed are decoded and compressed again.
+    pub fn compressed(self, compression: Compression) -> Texture {
+        let levels = self.levels.into_iter().map(|level| {
+            let image = match level {
+                MipLevel::Raw(canvas) => canvas,
+                MipLevel::Half(canvas) => Canvas::from(&canvas),
+                MipLevel::Compressed(image) => image.to_canvas(),
+            };
+            MipLevel::Compressed(CompressedImage::new(&image, compression))
+        });
+        Texture {
+            levels: levels.collect(),
+            format: self.format,
+        }
+    }
+    /// Store every level at half precision, halving the memory of raw levels while
+    /// keeping HDR values, and tag color textures as `Format::Rgb16F`.
+    ///
+    /// Compressed levels are decoded first.
+    pub fn half_precision(self) -> Texture {
+        let levels = self.levels.into_iter().map(|level| match level {
+            MipLevel::Raw(canvas) => MipLevel::Half(HalfCanvas::from(&canvas)),
+            MipLevel::Half(canvas) => MipLevel::Half(canvas),
+            MipLevel::Compressed(image) => MipLevel::Half(HalfCanvas::from(&image.to_canvas())),
+        });
+        let format = if self.format == Format::Rgb32F { Format::Rgb16F } else { self.format };
+        Texture {
+            levels: levels.collect(),
+            format,
+        }
+    }
+    /// Read the color at `uv` from the full size level, plus the sampler's bias
+    pub fn sample(&self, uv: Vec2f, sampler: &Sampler) -> Color {
+        self.sample_lod(uv, 0.0, sampler)
+    }
+    /// Read the color at `uv` from level of detail `lod`, where 0 is the full size level
+    /// and each step halves it
+    pub fn sample_lod(&self, uv: Vec2f, lod: f32, sampler: &Sampler) -> Color {
+        let last = (self.levels.len() - 1) as f32;
+        let lod = (lod + sampler.mip_bias).max(0.0).min(last);
+        match sampler.filter {
+            Filter::Nearest => self.sample_level(lod.round() as usize, uv, sampler),
+            Filter::Bilinear => {
+                let level = lod.floor();
+                let t = lod - level;
+                let fine = self.sample_level(level as usize, uv, sampler);
+                if t == 0.0 {
+                    return fine;
+                }
+                let coarse = self.sample_level(level as usize + 1, uv, sampler);
+                fine * (1.0 - t) + coarse * t
+            }
+        }
+    }
+    /// Read the depth at `uv`, or `Error::Format` if this is not a depth Texture.
+    ///
+    /// Depth is read from the nearest texel whatever the sampler's filter, as blending
+    /// across a silhouette gives a distance matching neither surface.
+    pub fn sample_depth(&self, uv: Vec2f, sampler: &Sampler) -> Result<f32> {
+        self.format.expect(Format::Depth32F)?;
+        let nearest = Sampler {
+            filter: Filter::Nearest,
+            ..*sampler
+        };
+        Ok(self.sample(uv, &nearest).r.value())
+    }
+    /// Read the color at `uv` for a pixel whose texture coordinates change by `dx` and `dy`
+    /// to the next pixel across and down.
+    ///
+    /// A footprint much longer in one direction than the other, as on a floor seen at a
+    /// grazing angle, is covered by up to `sampler.anisotropy` taps along its major axis,
+    /// each from the sharper level matching the minor axis, rather than one tap from the
+    /// level blurred to match the major axis.
+    pub fn sample_grad(&self, uv: Vec2f, dx: Vec2f, dy: Vec2f, sampler: &Sampler) -> Color {
+        let size = Vec2f::from_parts(self.width() as f32, self.height() as f32);
+        let (x_length, y_length) = ((dx * size).magnitude(), (dy * size).magnitude());
+        let (axis, major, minor) = if x_length >= y_length {
+            (dx, x_length, y_length)
+        } else {
+            (dy, y_length, x_length)
+        };
+        let ratio = (major / minor.max(1e-8)).ceil();
+        let taps = ratio.min(sampler.anisotropy.max(1) as f32) as usize;
+        if taps <= 1 {
+            return self.sample_lod(uv, major.max(1e-8).log2(), sampler);
+        }
+        let lod = (major / taps as f32).max(1e-8).log2();
+        let mut sum = Color::black();
+        for tap in 0..taps {
+            let t = (tap as f32 + 0.5) / taps as f32 - 0.5;
+            sum += self.sample_lod(uv + axis * t, lod, sampler);
+        }
+        sum * (1.0 / taps as f32)
+    }
+    /// Level of detail for a pixel whose texture coordinates change by `dx` and `dy` to
+    /// the next pixel across and down
+    pub fn lod(&self, dx: Vec2f, dy: Vec2f) -> f32 {
+        let size = Vec2f::from_parts(self.width() as f32, self.height() as f32);
+        let footprint = (dx * size).magnitude().max((dy * size).magnitude());
+        footprint.max(1e-8).log2()
+    }
+    fn sample_level(&self, level: usize, uv: Vec2f, sampler: &Sampler) -> Color {
+        let texels = &self.levels[level];
+        let (width, height) = (texels.width(), texels.height());
+        let texel = |x: isize, y: isize| texels.get(wrap(x, width, sampler.wrap_u), wrap(y, height, sampler.wrap_v));
+        let (x, y) = (uv.x * width as f32, uv.y * height as f32);
+        match sampler.filter {
+            Filter::Nearest => texel(x.floor() as isize, y.floor() as isize),
+            Filter::Bilinear => {
+                let (fx, fy) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (fx.floor(), fy.floor());
+                let (tx, ty) = (fx - x0, fy - y0);
+                let (x0, y0) = (x0 as isize, y0 as isize);
+                let top = texel(x0, y0) * (1.0 - tx) + texel(x0 + 1, y0) * tx;
+                let bottom = texel(x0, y0 + 1) * (1.0 - tx) + texel(x0 + 1, y0 + 1) * tx;
+                top * (1.0 - ty) + bottom * ty
+            }
+        }
+    }
+}
+
+/// Bring texel index `index` into `0..size`
+fn wrap(index: isize, size: usize, wrap: Wrap) -> usize {
+    let size = size as isize;
+    match wrap {
+        Wrap::Repeat => index.rem_euclid(size) as usize,
+        Wrap::MirroredRepeat => {
+            let period = index.rem_euclid(2 * size);
+            (if period < size { period } else { 2 * size - 1 - period }) as usize
+        }
+        Wrap::ClampToEdge => index.max(0).min(size - 1) as usize,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MipLevel, Sampler, Texture, Wrap};
+    use crate::canvas::{Canvas, Color, Compression, Filter, Format};
+    use crate::math::Vec2f;
+
+    /// 4x2 texture, black on the left half and white on the right
+    fn halves() -> Canvas {
+        let mut canvas = Canvas::new(4, 2);
+        for y in 0..2 {
+            canvas.set(2, y, Color::white());
+            canvas.set(3, y, Color::white());
+        }
+        canvas
+    }
+
+    #[test]
+    fn test_mips() {
+        let texture = Texture::new(halves());
+        let sizes: Vec<_> = texture.levels().iter().map(|l| (l.width(), l.height())).collect();
+        assert_eq!(sizes, vec![(4, 2), (2, 1), (1, 1)]);
+        assert_eq!(texture.levels()[2].get(0, 0), Color::grey());
+        let sampler = Sampler::default();
+        let uv = Vec2f::from_parts(0.1, 0.5);
+        assert_eq!(texture.sample_lod(uv, 5.0, &sampler), Color::grey());
+        let biased = Sampler { mip_bias: 2.0, ..sampler };
+        assert_eq!(texture.sample(uv, &biased), Color::grey());
+        assert_eq!(texture.lod(Vec2f::from_parts(0.5, 0.0), Vec2f::from_parts(0.0, 0.0)), 1.0);
+    }
+
+    #[test]
+    fn test_wrap() {
+        let texture = Texture::without_mips(halves());
+        let nearest = |wrap_u| Sampler {
+            filter: Filter::Nearest,
+            wrap_u,
+            wrap_v: Wrap::Repeat,
+            mip_bias: 0.0,
+            anisotropy: 1,
+        };
+        let uv = Vec2f::from_parts(1.1, 0.5);
+        assert_eq!(texture.sample(uv, &nearest(Wrap::Repeat)), Color::black());
+        assert_eq!(texture.sample(uv, &nearest(Wrap::MirroredRepeat)), Color::white());
+        assert_eq!(texture.sample(uv, &nearest(Wrap::ClampToEdge)), Color::white());
+        assert_eq!(texture.sample(Vec2f::from_parts(-0.9, 0.5), &nearest(Wrap::Repeat)), Color::black());
+        // Bilinear filtering across the repeat seam blends both edges
+        let seam = texture.sample(Vec2f::from_parts(0.0, 0.5), &Sampler::default());
+        assert_eq!(seam, Color::grey());
+    }
+
+    #[test]
+    fn test_compressed() {
+        let texture = Texture::new(halves()).compressed(Compression::Block);
+        assert!(texture.levels().iter().all(|level| matches!(level, MipLevel::Compressed(_))));
+        assert_eq!(texture.levels()[2].get(0, 0), Color::grey());
+        let uv = Vec2f::from_parts(0.625, 0.5);
+        assert_eq!(texture.sample(uv, &Sampler::default()), Color::white());
+        let memory: usize = texture.levels().iter().map(MipLevel::memory).sum();
+        assert!(memory < Texture::new(halves()).levels().iter().map(MipLevel::memory).sum());
+    }
+
+    #[test]
+    fn test_half_precision() {
+        let mut image = halves();
+        image.set(0, 1, Color::new(1024.0, 0.25, 16.0));
+        let texture = Texture::new(image).half_precision();
+        assert!(texture.levels().iter().all(|level| matches!(level, MipLevel::Half(_))));
+        assert_eq!(texture.format(), Format::Rgb16F);
+        assert_eq!(texture.levels()[0].get(0, 1), Color::new(1024.0, 0.25, 16.0));
+        assert_eq!(texture.levels()[0].get(3, 0), Color::white());
+        let memory: usize = texture.levels().iter().map(MipLevel::memory).sum();
+        assert_eq!(memory * 2, Texture::new(halves()).levels().iter().map(MipLevel::memory).sum());
+    }
+
+    #[test]
+    fn test_half_precision_mips() {
+        let full = Texture::new(halves());
+        let half = Texture::new(halves()).half_precision();
+        assert_eq!(half.levels().len(), 3);
+        assert_eq!(half.levels()[2].get(0, 0), Color::grey());
+        // Halves, quarters and greys are exact at half precision so every level samples the same
+        let sampler = Sampler::default();
+        for &lod in &[0.0, 0.5, 1.0, 1.5, 2.0] {
+            for &u in &[0.1, 0.4, 0.5, 0.9] {
+                let uv = Vec2f::from_parts(u, 0.5);
+                assert_eq!(half.sample_lod(uv, lod, &sampler), full.sample_lod(uv, lod, &sampler));
+            }
+        }
+    }
+
+    #[test]
+    fn test_anisotropic() {
+        // Texel wide vertical stripes seen at a grazing angle, a pixel covering one texel
+        // across and sixteen down
+        let mut stripes = Canvas::new(64, 64);
+        for y in 0..64 {
+            for x in (1..64).step_by(2) {
+                stripes.set(x, y, Color::white());
+            }
+        }
+        let texture = Texture::new(stripes);
+        let uv = Vec2f::from_parts(0.5 / 64.0, 0.5);
+        let (dx, dy) = (Vec2f::from_parts(1.0 / 64.0, 0.0), Vec2f::from_parts(0.0, 0.25));
+        let trilinear = Sampler::default();
+        assert_eq!(texture.sample_grad(uv, dx, dy, &trilinear), Color::grey());
+        // Taps along the stretched axis keep the stripes apart
+        let anisotropic = Sampler { anisotropy: 16, ..trilinear };
+        assert_eq!(texture.sample_grad(uv, dx, dy, &anisotropic), Color::black());
+        // Too few taps still need a level where the stripes have blurred together
+        let limited = Sampler { anisotropy: 4, ..trilinear };
+        assert_eq!(texture.sample_grad(uv, dx, dy, &limited), Color::grey());
+        // Square footprints sample as before
+        let square = texture.sample_grad(uv, dx, Vec2f::from_parts(0.0, 1.0 / 64.0), &anisotropic);
+        assert_eq!(square, texture.sample_lod(uv, 0.0, &trilinear));
+    }
+}
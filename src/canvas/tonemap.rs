@@ -0,0 +1,136 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Canvas, Color, PixelBuffer};
+
+/// Curve compressing HDR colors into the displayable 0 to 1 range
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Tonemap {
+    /// Cut every channel off at 1.0
+    Clamp,
+    /// `c / (1 + c)` per channel, never quite reaching white
+    Reinhard,
+    /// Reinhard reaching white at `white`, keeping more contrast below it
+    ReinhardExtended { white: f32 },
+    /// Narkowicz's fit of the ACES filmic curve, with a toe and a soft shoulder
+    #[default]
+    Aces,
+}
+
+impl Tonemap {
+    /// Map one HDR channel value into display range
+    pub fn apply(&self, value: f32) -> f32 {
+        let value = value.max(0.0);
+        let mapped = match *self {
+            Tonemap::Clamp => value,
+            Tonemap::Reinhard => value / (1.0 + value),
+            Tonemap::ReinhardExtended { white } => value * (1.0 + value / (white * white)) / (1.0 + value),
+            Tonemap::Aces => (value * (2.51 * value + 0.03)) / (value * (2.43 * value + 0.59) + 0.14),
+        };
+        mapped.min(1.0)
+    }
+}
+
+///
+/// Exposure and curve of one image made from an HDR render by `tonemap`.
+///
+/// ```
+/// use softrender::canvas::{tonemap, Canvas, Color, Tonemap, TonemapOutput};
+///
+/// let mut hdr = Canvas::new(4, 4);
+/// hdr.fill(Color::new(0.5, 0.5, 0.5));
+/// let outputs = tonemap(&hdr, &TonemapOutput::bracket(Tonemap::Clamp, &[-1.0, 0.0, 1.0]));
+/// assert_eq!(outputs[0].get(0, 0), Color::new(0.25, 0.25, 0.25));
+/// assert_eq!(outputs[2].get(0, 0), Color::new(1.0, 1.0, 1.0));
+/// ```
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TonemapOutput {
+    /// Exposure adjustment in stops, each doubling the light
+    pub stops: f32,
+    pub tonemap: Tonemap,
+}
+
+impl TonemapOutput {
+    /// Create new TonemapOutput exposed `stops` up from the render
+    pub fn new(stops: f32, tonemap: Tonemap) -> TonemapOutput {
+        TonemapOutput { stops, tonemap }
+    }
+    /// Outputs with the same `tonemap` at each exposure of `stops`, for exposure bracketing
+    pub fn bracket(tonemap: Tonemap, stops: &[f32]) -> Vec<TonemapOutput> {
+        stops.iter().map(|&stops| TonemapOutput::new(stops, tonemap)).collect()
+    }
+    /// Map an HDR color into display range
+    pub fn apply(&self, color: Color) -> Color {
+        let exposure = self.stops.exp2();
+        let channel = |value: f32| self.tonemap.apply(value * exposure);
+        Color::new(channel(color.r.value()), channel(color.g.value()), channel(color.b.value()))
+    }
+}
+
+/// Make one display range Canvas per entry of `outputs` from the HDR image `hdr`, reading
+/// it only once, so settings can be compared without rendering again
+pub fn tonemap<B: PixelBuffer<Pixel = Color>>(hdr: &B, outputs: &[TonemapOutput]) -> Vec<Canvas> {
+    crate::profile_scope!("tonemap");
+    let (width, height) = (hdr.width(), hdr.height());
+    let mut canvases: Vec<Canvas> = outputs.iter().map(|_| Canvas::new(width, height)).collect();
+    for y in 0..height {
+        for x in 0..width {
+            let color = hdr.get(x, y);
+            for (canvas, output) in canvases.iter_mut().zip(outputs) {
+                canvas.set(x, y, output.apply(color));
+            }
+        }
+    }
+    canvases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tonemap, Tonemap, TonemapOutput};
+    use crate::canvas::{Color, HalfCanvas};
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_curves() {
+        for curve in [Tonemap::Clamp, Tonemap::Reinhard, Tonemap::ReinhardExtended { white: 4.0 }, Tonemap::Aces] {
+            assert_eq!(curve.apply(0.0), 0.0);
+            assert_eq!(curve.apply(-1.0), 0.0);
+            assert!(curve.apply(0.5) < curve.apply(0.6));
+            assert!(curve.apply(1000.0) <= 1.0);
+        }
+        assert_eq!(Tonemap::Clamp.apply(2.0), 1.0);
+        assert_eq!(Tonemap::Reinhard.apply(1.0), 0.5);
+        assert_approx_eq!(Tonemap::ReinhardExtended { white: 4.0 }.apply(4.0), 1.0);
+        assert_approx_eq!(Tonemap::Aces.apply(0.18), 0.267, 1e-3);
+    }
+
+    #[test]
+    fn test_tonemap() {
+        let mut hdr = HalfCanvas::new(2, 1);
+        hdr.set(0, 0, Color::new(0.25, 0.5, 1.0));
+        hdr.set(1, 0, Color::new(8.0, 8.0, 8.0));
+        let mut outputs = TonemapOutput::bracket(Tonemap::Clamp, &[-2.0, 0.0, 2.0]);
+        outputs.push(TonemapOutput::new(0.0, Tonemap::Reinhard));
+        let images = tonemap(&hdr, &outputs);
+        assert_eq!(images.len(), 4);
+        assert_eq!(images[0].get(0, 0), Color::new(0.0625, 0.125, 0.25));
+        assert_eq!(images[1].get(0, 0), Color::new(0.25, 0.5, 1.0));
+        assert_eq!(images[2].get(0, 0), Color::new(1.0, 1.0, 1.0));
+        assert_eq!(images[3].get(1, 0), Color::new(8.0 / 9.0, 8.0 / 9.0, 8.0 / 9.0));
+        assert!(tonemap(&hdr, &[]).is_empty());
+    }
+}
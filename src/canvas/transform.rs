@@ -0,0 +1,165 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Canvas, Color};
+use crate::math::{Mat3f, Vec3f};
+
+/// How colors are read between Pixel centers
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Filter {
+    /// Color of the closest Pixel
+    Nearest,
+    /// Blend of the four closest Pixels
+    Bilinear,
+}
+
+impl Canvas {
+    /// Copy of this Canvas mirrored left to right
+    pub fn flipped_h(&self) -> Canvas {
+        let width = self.width();
+        self.remap(width, self.height(), |x, y| (width - 1 - x, y))
+    }
+    /// Copy of this Canvas mirrored top to bottom
+    pub fn flipped_v(&self) -> Canvas {
+        let height = self.height();
+        self.remap(self.width(), height, |x, y| (x, height - 1 - y))
+    }
+    /// Copy of this Canvas turned a quarter clockwise
+    pub fn rotated_90(&self) -> Canvas {
+        let height = self.height();
+        self.remap(height, self.width(), |x, y| (y, height - 1 - x))
+    }
+    /// Copy of this Canvas turned upside down
+    pub fn rotated_180(&self) -> Canvas {
+        let (width, height) = (self.width(), self.height());
+        self.remap(width, height, |x, y| (width - 1 - x, height - 1 - y))
+    }
+    /// Copy of this Canvas turned a quarter counter clockwise
+    pub fn rotated_270(&self) -> Canvas {
+        let width = self.width();
+        self.remap(self.height(), width, |x, y| (width - 1 - y, x))
+    }
+    /// Resample this Canvas under an affine `transform`, keeping its size.
+    ///
+    /// `transform` maps positions in this Canvas to positions in the result as
+    /// `transform * (x, y, 1)`. Parts of the result not covered by this Canvas are black.
+    /// A transform that cannot be inverted gives an all black Canvas.
+    pub fn warp_affine(&self, transform: Mat3f, filter: Filter) -> Canvas {
+        let mut canvas = Canvas::with_layout(self.width(), self.height(), self.layout());
+        let inverse = match transform.invert() {
+            Some(inverse) => inverse,
+            None => return canvas,
+        };
+        let (width, height) = (self.width() as f32, self.height() as f32);
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let source = inverse * Vec3f::from_parts(x as f32 + 0.5, y as f32 + 0.5, 1.0);
+                if source.x >= 0.0 && source.y >= 0.0 && source.x < width && source.y < height {
+                    canvas.set(x, y, self.sample(source.x, source.y, filter));
+                }
+            }
+        }
+        canvas
+    }
+    /// Read the color at position (x, y), where Pixel (0, 0) covers 0.0 to 1.0.
+    ///
+    /// Positions outside the Canvas read the closest edge Pixel.
+    pub fn sample(&self, x: f32, y: f32, filter: Filter) -> Color {
+        let clamp = |v: isize, size: usize| v.max(0).min(size as isize - 1) as usize;
+        match filter {
+            Filter::Nearest => self.get(clamp(x.floor() as isize, self.width()), clamp(y.floor() as isize, self.height())),
+            Filter::Bilinear => {
+                let (fx, fy) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (fx.floor(), fy.floor());
+                let (tx, ty) = (fx - x0, fy - y0);
+                let (x0, y0) = (x0 as isize, y0 as isize);
+                let (x1, y1) = (clamp(x0 + 1, self.width()), clamp(y0 + 1, self.height()));
+                let (x0, y0) = (clamp(x0, self.width()), clamp(y0, self.height()));
+                let top = self.get(x0, y0) * (1.0 - tx) + self.get(x1, y0) * tx;
+                let bottom = self.get(x0, y1) * (1.0 - tx) + self.get(x1, y1) * tx;
+                top * (1.0 - ty) + bottom * ty
+            }
+        }
+    }
+    /// New `width` by `height` Canvas taking each Pixel from `source(x, y)` in this one
+    fn remap<F: Fn(usize, usize) -> (usize, usize)>(&self, width: usize, height: usize, source: F) -> Canvas {
+        let mut canvas = Canvas::with_layout(width, height, self.layout());
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = source(x, y);
+                canvas.set(x, y, self.get(sx, sy));
+            }
+        }
+        canvas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Filter;
+    use crate::canvas::{Canvas, Color};
+    use crate::math::Mat3f;
+
+    /// 3x2 Canvas with a distinct color per Pixel
+    fn numbered() -> Canvas {
+        let mut canvas = Canvas::new(3, 2);
+        for y in 0..2 {
+            for x in 0..3 {
+                canvas.set(x, y, Color::new(x as f32 / 4.0, y as f32 / 4.0, 0.0));
+            }
+        }
+        canvas
+    }
+
+    #[test]
+    fn test_flip_rotate() {
+        let canvas = numbered();
+        let corner = canvas.get(0, 0);
+        assert_eq!(canvas.flipped_h().get(2, 0), corner);
+        assert_eq!(canvas.flipped_v().get(0, 1), corner);
+        assert_eq!(canvas.rotated_180().get(2, 1), corner);
+        let rotated = canvas.rotated_90();
+        assert_eq!((rotated.width(), rotated.height()), (2, 3));
+        assert_eq!(rotated.get(1, 0), corner);
+        assert_eq!(rotated.get(0, 2), canvas.get(2, 1));
+        let rotated = canvas.rotated_270();
+        assert_eq!(rotated.get(0, 2), corner);
+        assert_eq!(rotated.rotated_90().get(1, 1), canvas.get(1, 1));
+    }
+
+    #[test]
+    fn test_warp_affine() {
+        let canvas = numbered();
+        // Move one pixel right
+        let shift = Mat3f::from_rows([[1.0, 0.0, 1.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+        let warped = canvas.warp_affine(shift, Filter::Nearest);
+        assert_eq!(warped.get(0, 0), Color::black());
+        assert_eq!(warped.get(1, 1), canvas.get(0, 1));
+        assert_eq!(warped.get(2, 0), canvas.get(1, 0));
+        // Identity keeps every pixel, whatever the filter
+        let same = canvas.warp_affine(Mat3f::identity(), Filter::Bilinear);
+        assert!(canvas.pixels().all(|(x, y, c)| same.get(x, y) == c));
+        assert!(canvas.warp_affine(Mat3f::zero(), Filter::Nearest).pixels().all(|p| p.2 == Color::black()));
+    }
+
+    #[test]
+    fn test_sample() {
+        let canvas = numbered();
+        assert_eq!(canvas.sample(1.0, 0.5, Filter::Bilinear), Color::new(0.125, 0.0, 0.0));
+        assert_eq!(canvas.sample(1.0, 0.5, Filter::Nearest), canvas.get(1, 0));
+        assert_eq!(canvas.sample(-3.0, 9.0, Filter::Bilinear), canvas.get(0, 1));
+    }
+}
@@ -0,0 +1,100 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Error type shared across the crate.
+
+use crate::canvas::Format;
+use crate::render_graph::GraphError;
+use std::fmt;
+use std::io;
+
+/// Result of a fallible softrender operation
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Failure of a softrender operation
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file or stream failed
+    Io(io::Error),
+    /// Opening or updating a window failed
+    Window(String),
+    /// Pixel (x, y) lies outside a `width` by `height` buffer
+    OutOfBounds { x: usize, y: usize, width: usize, height: usize },
+    /// Data being loaded is malformed
+    Decode(String),
+    /// A buffer of Format `found` was used where one of Format `expected` was needed
+    Format { expected: Format, found: Format },
+    /// A `RenderGraph` could not be scheduled
+    Graph(GraphError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "i/o error: {}", error),
+            Error::Window(message) => write!(f, "window error: {}", message),
+            Error::OutOfBounds { x, y, width, height } => {
+                write!(f, "pixel ({}, {}) is outside the {}x{} buffer", x, y, width, height)
+            }
+            Error::Decode(message) => write!(f, "malformed data: {}", message),
+            Error::Format { expected, found } => write!(f, "expected a {} buffer but found {}", expected, found),
+            Error::Graph(error) => write!(f, "render graph error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            Error::Graph(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<GraphError> for Error {
+    fn from(error: GraphError) -> Self {
+        Error::Graph(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Error;
+    use crate::render_graph::GraphError;
+    use std::error::Error as _;
+    use std::io;
+
+    #[test]
+    fn test_display() {
+        let error = Error::OutOfBounds { x: 9, y: 2, width: 8, height: 4 };
+        assert_eq!(error.to_string(), "pixel (9, 2) is outside the 8x4 buffer");
+        assert!(error.source().is_none());
+        let error = Error::from(io::Error::new(io::ErrorKind::NotFound, "missing.bmp"));
+        assert_eq!(error.to_string(), "i/o error: missing.bmp");
+        assert!(error.source().is_some());
+        let error = Error::from(GraphError::Cycle(vec!["post".to_string()]));
+        assert_eq!(error.to_string(), "render graph error: render passes form a cycle: post");
+        assert!(error.source().is_some());
+    }
+}
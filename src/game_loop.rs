@@ -0,0 +1,178 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Frame timing with fixed timestep updates.
+//!
+//! Simulation runs in fixed steps so animation does not depend on the frame rate, while
+//! rendering interpolates between the last two simulation states using `FrameTime::alpha`.
+//!
+//! ```no_run
+//! use softrender::game_loop::GameLoop;
+//!
+//! let mut game_loop = GameLoop::new(60.0);
+//! game_loop.set_fps_limit(Some(144.0));
+//! let (mut previous, mut current) = (0.0f32, 0.0f32);
+//! loop {
+//!     let time = game_loop.begin_frame();
+//!     for _ in 0..time.updates {
+//!         previous = current;
+//!         current += 90.0 * time.fixed_delta;
+//!     }
+//!     let angle = previous + (current - previous) * time.alpha;
+//!     // render with angle...
+//! }
+//! ```
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Timing of a single frame
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FrameTime {
+    /// Seconds since the previous frame
+    pub delta: f32,
+    /// Seconds since the loop started
+    pub elapsed: f32,
+    /// Number of fixed updates to run this frame
+    pub updates: u32,
+    /// Seconds covered by each fixed update
+    pub fixed_delta: f32,
+    /// How far between the last two fixed updates this frame lies, from 0 to 1
+    pub alpha: f32,
+    /// Number of this frame, starting from 0
+    pub frame: u64,
+}
+
+///
+/// Game loop timer providing delta time, fixed timestep updates and FPS limiting
+///
+pub struct GameLoop {
+    fixed_delta: Duration,
+    fps_limit: Option<Duration>,
+    max_delta: Duration,
+    accumulator: Duration,
+    elapsed: Duration,
+    last_frame: Option<Instant>,
+    frame: u64,
+}
+
+impl GameLoop {
+    /// Create new GameLoop running `updates_per_second` fixed updates
+    pub fn new(updates_per_second: f32) -> Self {
+        Self {
+            fixed_delta: Duration::from_secs_f32(1.0 / updates_per_second),
+            fps_limit: None,
+            max_delta: Duration::from_millis(250),
+            accumulator: Duration::from_secs(0),
+            elapsed: Duration::from_secs(0),
+            last_frame: None,
+            frame: 0,
+        }
+    }
+    /// Limit frames per second by sleeping in `begin_frame`, `None` runs unlimited
+    pub fn set_fps_limit(&mut self, fps: Option<f32>) {
+        self.fps_limit = fps.map(|fps| Duration::from_secs_f32(1.0 / fps));
+    }
+    /// Set the longest frame time taken into account.
+    ///
+    /// Longer frames, such as after a breakpoint or window drag, are cut to this so the
+    /// simulation does not try to catch up with hundreds of updates.
+    pub fn set_max_delta(&mut self, max_delta: Duration) {
+        self.max_delta = max_delta;
+    }
+    /// Start a frame, sleeping first if it would exceed the FPS limit
+    pub fn begin_frame(&mut self) -> FrameTime {
+        let now = match (self.last_frame, self.fps_limit) {
+            (Some(last), Some(limit)) => {
+                let since = last.elapsed();
+                if since < limit {
+                    thread::sleep(limit - since);
+                }
+                Instant::now()
+            }
+            _ => Instant::now(),
+        };
+        let delta = self.last_frame.map_or(Duration::from_secs(0), |last| now - last);
+        self.last_frame = Some(now);
+        self.advance(delta)
+    }
+    /// Start a frame `delta` after the previous one without looking at the clock.
+    ///
+    /// Useful for offline rendering of animations and for tests.
+    pub fn advance(&mut self, delta: Duration) -> FrameTime {
+        let delta = delta.min(self.max_delta);
+        self.elapsed += delta;
+        self.accumulator += delta;
+        let mut updates = 0;
+        while self.accumulator >= self.fixed_delta {
+            self.accumulator -= self.fixed_delta;
+            updates += 1;
+        }
+        let frame = self.frame;
+        self.frame += 1;
+        FrameTime {
+            delta: delta.as_secs_f32(),
+            elapsed: self.elapsed.as_secs_f32(),
+            updates,
+            fixed_delta: self.fixed_delta.as_secs_f32(),
+            alpha: self.accumulator.as_secs_f32() / self.fixed_delta.as_secs_f32(),
+            frame,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GameLoop;
+    use assert_approx_eq::assert_approx_eq;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_fixed_updates() {
+        let mut game_loop = GameLoop::new(100.0);
+        let time = game_loop.advance(Duration::from_millis(25));
+        assert_eq!(time.frame, 0);
+        assert_eq!(time.updates, 2);
+        assert_approx_eq!(time.fixed_delta, 0.01);
+        assert_approx_eq!(time.alpha, 0.5, 1e-3);
+        let time = game_loop.advance(Duration::from_millis(6));
+        assert_eq!(time.frame, 1);
+        assert_eq!(time.updates, 1);
+        assert_approx_eq!(time.alpha, 0.1, 1e-3);
+        assert_approx_eq!(time.elapsed, 0.031, 1e-4);
+    }
+
+    #[test]
+    fn test_max_delta() {
+        let mut game_loop = GameLoop::new(8.0);
+        game_loop.set_max_delta(Duration::from_millis(500));
+        let time = game_loop.advance(Duration::from_secs(10));
+        assert_eq!(time.updates, 4);
+        assert_approx_eq!(time.delta, 0.5);
+    }
+
+    #[test]
+    fn test_fps_limit() {
+        let mut game_loop = GameLoop::new(60.0);
+        game_loop.set_fps_limit(Some(50.0));
+        let start = Instant::now();
+        let first = game_loop.begin_frame();
+        assert_eq!(first.delta, 0.0);
+        game_loop.begin_frame();
+        game_loop.begin_frame();
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
@@ -0,0 +1,297 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Backend agnostic input events and state.
+//!
+//! Presenters translate their window system's input into `Event`s, which are folded
+//! into an `InputState` that camera controllers and the debug UI query each frame.
+
+use crate::ui::UiInput;
+use std::collections::HashSet;
+
+/// Keyboard key
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    Num0,
+    Num1,
+    Num2,
+    Num3,
+    Num4,
+    Num5,
+    Num6,
+    Num7,
+    Num8,
+    Num9,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    Escape,
+    Space,
+    Enter,
+    Tab,
+    Backspace,
+    Left,
+    Right,
+    Up,
+    Down,
+    LeftShift,
+    RightShift,
+    LeftCtrl,
+    RightCtrl,
+    LeftAlt,
+    RightAlt,
+}
+
+/// Mouse button
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+/// Input event reported by a presenter
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Event {
+    KeyDown(Key),
+    KeyUp(Key),
+    /// Mouse moved to (x, y) in window pixels
+    MouseMove { x: f32, y: f32 },
+    MouseDown(MouseButton),
+    MouseUp(MouseButton),
+    /// Wheel scrolled, positive `y` is away from the user
+    MouseWheel { x: f32, y: f32 },
+    /// Window client area changed size
+    Resize { width: usize, height: usize },
+    /// User asked to close the window
+    Close,
+}
+
+///
+/// Current input state built from `Event`s.
+///
+/// Call `begin_frame` before handling each frame's events so that the pressed and
+/// released sets, wheel movement and resize only cover that frame.
+///
+#[derive(Clone, Debug, Default)]
+pub struct InputState {
+    keys_down: HashSet<Key>,
+    keys_pressed: HashSet<Key>,
+    keys_released: HashSet<Key>,
+    buttons_down: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+    mouse_position: (f32, f32),
+    wheel: (f32, f32),
+    resized: Option<(usize, usize)>,
+    close_requested: bool,
+}
+
+impl InputState {
+    /// Create new InputState with nothing held
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Forget the per frame changes of the last frame
+    pub fn begin_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.wheel = (0.0, 0.0);
+        self.resized = None;
+    }
+    /// Fold an event into the state
+    pub fn handle(&mut self, event: &Event) {
+        match *event {
+            Event::KeyDown(key) => {
+                if self.keys_down.insert(key) {
+                    self.keys_pressed.insert(key);
+                }
+            }
+            Event::KeyUp(key) => {
+                if self.keys_down.remove(&key) {
+                    self.keys_released.insert(key);
+                }
+            }
+            Event::MouseMove { x, y } => self.mouse_position = (x, y),
+            Event::MouseDown(button) => {
+                if self.buttons_down.insert(button) {
+                    self.buttons_pressed.insert(button);
+                }
+            }
+            Event::MouseUp(button) => {
+                if self.buttons_down.remove(&button) {
+                    self.buttons_released.insert(button);
+                }
+            }
+            Event::MouseWheel { x, y } => {
+                self.wheel.0 += x;
+                self.wheel.1 += y;
+            }
+            Event::Resize { width, height } => self.resized = Some((width, height)),
+            Event::Close => self.close_requested = true,
+        }
+    }
+    /// Check if `key` is held
+    pub fn is_key_down(&self, key: Key) -> bool {
+        self.keys_down.contains(&key)
+    }
+    /// Check if `key` went down this frame
+    pub fn was_key_pressed(&self, key: Key) -> bool {
+        self.keys_pressed.contains(&key)
+    }
+    /// Check if `key` came up this frame
+    pub fn was_key_released(&self, key: Key) -> bool {
+        self.keys_released.contains(&key)
+    }
+    /// Check if `button` is held
+    pub fn is_mouse_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+    /// Check if `button` went down this frame
+    pub fn was_mouse_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+    /// Check if `button` came up this frame
+    pub fn was_mouse_released(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+    /// Mouse position in window pixels
+    pub fn mouse_position(&self) -> (f32, f32) {
+        self.mouse_position
+    }
+    /// Wheel movement this frame
+    pub fn wheel_delta(&self) -> (f32, f32) {
+        self.wheel
+    }
+    /// New window size if the window was resized this frame
+    pub fn resized(&self) -> Option<(usize, usize)> {
+        self.resized
+    }
+    /// Check if the user asked to close the window
+    pub fn close_requested(&self) -> bool {
+        self.close_requested
+    }
+}
+
+impl From<&InputState> for UiInput {
+    fn from(input: &InputState) -> Self {
+        let (mouse_x, mouse_y) = input.mouse_position();
+        UiInput {
+            mouse_x,
+            mouse_y,
+            mouse_down: input.is_mouse_down(MouseButton::Left),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, InputState, Key, MouseButton};
+    use crate::ui::UiInput;
+
+    #[test]
+    fn test_keys() {
+        let mut input = InputState::new();
+        input.handle(&Event::KeyDown(Key::W));
+        assert!(input.is_key_down(Key::W));
+        assert!(input.was_key_pressed(Key::W));
+        input.begin_frame();
+        // Key repeat does not count as a new press
+        input.handle(&Event::KeyDown(Key::W));
+        assert!(input.is_key_down(Key::W));
+        assert!(!input.was_key_pressed(Key::W));
+        input.handle(&Event::KeyUp(Key::W));
+        assert!(!input.is_key_down(Key::W));
+        assert!(input.was_key_released(Key::W));
+        input.begin_frame();
+        assert!(!input.was_key_released(Key::W));
+    }
+
+    #[test]
+    fn test_mouse() {
+        let mut input = InputState::new();
+        input.handle(&Event::MouseMove { x: 10.0, y: 20.0 });
+        input.handle(&Event::MouseDown(MouseButton::Left));
+        input.handle(&Event::MouseWheel { x: 0.0, y: 1.0 });
+        input.handle(&Event::MouseWheel { x: 0.0, y: 2.0 });
+        assert_eq!(input.mouse_position(), (10.0, 20.0));
+        assert!(input.was_mouse_pressed(MouseButton::Left));
+        assert!(!input.is_mouse_down(MouseButton::Right));
+        assert_eq!(input.wheel_delta(), (0.0, 3.0));
+        assert_eq!(
+            UiInput::from(&input),
+            UiInput {
+                mouse_x: 10.0,
+                mouse_y: 20.0,
+                mouse_down: true
+            }
+        );
+        input.begin_frame();
+        assert_eq!(input.wheel_delta(), (0.0, 0.0));
+        input.handle(&Event::MouseUp(MouseButton::Left));
+        assert!(input.was_mouse_released(MouseButton::Left));
+    }
+
+    #[test]
+    fn test_window() {
+        let mut input = InputState::new();
+        assert_eq!(input.resized(), None);
+        input.handle(&Event::Resize { width: 800, height: 600 });
+        assert_eq!(input.resized(), Some((800, 600)));
+        input.begin_frame();
+        assert_eq!(input.resized(), None);
+        assert!(!input.close_requested());
+        input.handle(&Event::Close);
+        assert!(input.close_requested());
+    }
+}
@@ -0,0 +1,387 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Small job system shared by the renderers.
+//!
+//! Worker threads are started once and kept alive between frames, so jobs queued with
+//! `spawn` only pay for queueing rather than spawning threads. Each worker owns a queue
+//! and steals from the back of the other queues when its own runs dry.
+//!
+//! Parallel loops with `for_each` and `map` hand out their indices in chunks to the same
+//! workers, with the calling thread taking chunks too, and wait only for their own chunks.
+//! The loop body may borrow from the caller as the call does not return before every
+//! chunk has finished.
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+type Body<'a> = dyn Fn(usize) + Sync + 'a;
+
+static GLOBAL: Mutex<Option<Arc<ThreadPool>>> = Mutex::new(None);
+
+/// Set the number of worker threads used by the global pool.
+///
+/// The current pool, if any, finishes its queued jobs and shuts down once the last
+/// renderer holding it lets go. A count of 0 uses the number of available cores.
+pub fn set_thread_count(threads: usize) {
+    let pool = Arc::new(ThreadPool::new(threads));
+    *GLOBAL.lock().unwrap() = Some(pool);
+}
+
+/// Get the global pool, starting it with one thread per core on first use.
+pub fn global() -> Arc<ThreadPool> {
+    GLOBAL
+        .lock()
+        .unwrap()
+        .get_or_insert_with(|| Arc::new(ThreadPool::new(0)))
+        .clone()
+}
+
+/// Pool of persistent worker threads with work stealing queues
+pub struct ThreadPool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+struct Shared {
+    /// One queue per worker; owners pop from the front, thieves from the back.
+    queues: Vec<Mutex<VecDeque<Job>>>,
+    /// Jobs queued but not yet claimed by a worker.
+    queued: Mutex<usize>,
+    available: Condvar,
+    /// Jobs queued or running.
+    pending: Mutex<usize>,
+    finished: Condvar,
+    next_queue: AtomicUsize,
+    panicked: AtomicBool,
+    shutdown: AtomicBool,
+}
+
+impl ThreadPool {
+    /// Create a pool with `threads` workers, or one per core if `threads` is 0.
+    pub fn new(threads: usize) -> Self {
+        let threads = if threads == 0 {
+            thread::available_parallelism().map_or(1, |n| n.get())
+        } else {
+            threads
+        };
+        let shared = Arc::new(Shared {
+            queues: (0..threads).map(|_| Mutex::new(VecDeque::new())).collect(),
+            queued: Mutex::new(0),
+            available: Condvar::new(),
+            pending: Mutex::new(0),
+            finished: Condvar::new(),
+            next_queue: AtomicUsize::new(0),
+            panicked: AtomicBool::new(false),
+            shutdown: AtomicBool::new(false),
+        });
+        let workers = (0..threads)
+            .map(|index| {
+                let shared = shared.clone();
+                thread::Builder::new()
+                    .name(format!("softrender-worker-{}", index))
+                    .spawn(move || shared.work(index))
+                    .expect("failed to spawn worker thread")
+            })
+            .collect();
+        Self { shared, workers }
+    }
+    /// Number of worker threads in this pool
+    pub fn thread_count(&self) -> usize {
+        self.workers.len()
+    }
+    /// Queue a job to run on one of the workers.
+    pub fn spawn<F: FnOnce() + Send + 'static>(&self, job: F) {
+        *self.shared.pending.lock().unwrap() += 1;
+        let queue = self.shared.next_queue.fetch_add(1, Ordering::Relaxed) % self.shared.queues.len();
+        self.shared.queues[queue].lock().unwrap().push_back(Box::new(job));
+        *self.shared.queued.lock().unwrap() += 1;
+        self.shared.available.notify_one();
+    }
+    /// Block until every queued job has finished.
+    ///
+    /// # Panics
+    /// Panics if any job panicked since the last call to `wait`.
+    pub fn wait(&self) {
+        let mut pending = self.shared.pending.lock().unwrap();
+        while *pending > 0 {
+            pending = self.shared.finished.wait(pending).unwrap();
+        }
+        drop(pending);
+        if self.shared.panicked.swap(false, Ordering::SeqCst) {
+            panic!("a job panicked on a worker thread");
+        }
+    }
+    /// Call `job` for every index in `0..count` on the workers and the calling thread and
+    /// wait for them.
+    ///
+    /// Indices are handed out in contiguous chunks, a few per thread, each thread taking
+    /// the next chunk when it is done so the cost of each index may vary. Each call only
+    /// waits for its own chunks, and as the calling thread works through chunks until none
+    /// are left, calls may nest or run from several threads at once without deadlocking.
+    ///
+    /// # Panics
+    /// Panics if `job` panicked for any index.
+    pub fn for_each<F: Fn(usize) + Sync>(&self, count: usize, job: F) {
+        let chunk_size = count.div_ceil(self.thread_count() * 4).max(1);
+        let chunks = count.div_ceil(chunk_size);
+        let job: &Body<'_> = &job;
+        // SAFETY: workers only call `job` for chunks they claimed, and this call catches
+        // panics of `job` and does not return before every claimed chunk has finished.
+        // Helpers still queued afterwards find no chunk left and never touch `job`.
+        #[allow(unsafe_code)]
+        let job = unsafe { std::mem::transmute::<&Body<'_>, &'static Body<'static>>(job) };
+        let task = Arc::new(Loop {
+            job,
+            count,
+            chunk_size,
+            next: AtomicUsize::new(0),
+            remaining: Mutex::new(chunks),
+            finished: Condvar::new(),
+            panic: Mutex::new(None),
+        });
+        for _ in 0..self.thread_count().min(chunks.saturating_sub(1)) {
+            let task = task.clone();
+            self.spawn(move || task.run());
+        }
+        task.run();
+        task.wait();
+        let panic = task.panic.lock().unwrap().take();
+        if let Some(payload) = panic {
+            panic::resume_unwind(payload);
+        }
+    }
+    /// Call `job` for every index in `0..count` like `for_each` and collect what it returns
+    /// in index order.
+    ///
+    /// # Panics
+    /// Panics if `job` panicked for any index.
+    pub fn map<T: Send, F: Fn(usize) -> T + Sync>(&self, count: usize, job: F) -> Vec<T> {
+        let results: Vec<Mutex<Option<T>>> = (0..count).map(|_| Mutex::new(None)).collect();
+        self.for_each(count, |index| *results[index].lock().unwrap() = Some(job(index)));
+        results.into_iter().map(|result| result.into_inner().unwrap().expect("job did not run")).collect()
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        {
+            let _queued = self.shared.queued.lock().unwrap();
+            self.shared.shutdown.store(true, Ordering::SeqCst);
+        }
+        self.shared.available.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Indices of one `for_each` call, shared between the threads working through them
+struct Loop {
+    job: &'static Body<'static>,
+    count: usize,
+    chunk_size: usize,
+    /// Start of the next chunk to hand out
+    next: AtomicUsize,
+    /// Chunks not finished yet
+    remaining: Mutex<usize>,
+    finished: Condvar,
+    /// What the first panicking chunk panicked with
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
+}
+
+impl Loop {
+    /// Claim and run chunks until none are left.
+    fn run(&self) {
+        loop {
+            let start = self.next.fetch_add(self.chunk_size, Ordering::Relaxed);
+            if start >= self.count {
+                return;
+            }
+            let indices = start..(start + self.chunk_size).min(self.count);
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| indices.for_each(self.job))) {
+                self.panic.lock().unwrap().get_or_insert(payload);
+            }
+            let mut remaining = self.remaining.lock().unwrap();
+            *remaining -= 1;
+            if *remaining == 0 {
+                self.finished.notify_all();
+            }
+        }
+    }
+    /// Block until every chunk has finished.
+    fn wait(&self) {
+        let mut remaining = self.remaining.lock().unwrap();
+        while *remaining > 0 {
+            remaining = self.finished.wait(remaining).unwrap();
+        }
+    }
+}
+
+impl Shared {
+    /// Worker loop: claim a job, find it, run it.
+    fn work(&self, index: usize) {
+        loop {
+            {
+                let mut queued = self.queued.lock().unwrap();
+                while *queued == 0 {
+                    if self.shutdown.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    queued = self.available.wait(queued).unwrap();
+                }
+                *queued -= 1;
+            }
+            // A claimed job was pushed before the count went up, so one is waiting somewhere.
+            let job = loop {
+                if let Some(job) = self.take(index) {
+                    break job;
+                }
+                thread::yield_now();
+            };
+            if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                self.panicked.store(true, Ordering::SeqCst);
+            }
+            let mut pending = self.pending.lock().unwrap();
+            *pending -= 1;
+            if *pending == 0 {
+                self.finished.notify_all();
+            }
+        }
+    }
+    /// Pop from our own queue, otherwise steal from the others.
+    fn take(&self, index: usize) -> Option<Job> {
+        if let Some(job) = self.queues[index].lock().unwrap().pop_front() {
+            return Some(job);
+        }
+        let count = self.queues.len();
+        (1..count).find_map(|offset| self.queues[(index + offset) % count].lock().unwrap().pop_back())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ThreadPool;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_for_each() {
+        let pool = ThreadPool::new(4);
+        assert_eq!(pool.thread_count(), 4);
+        let sum = Arc::new(AtomicUsize::new(0));
+        let total = sum.clone();
+        pool.for_each(1000, move |i| {
+            total.fetch_add(i, Ordering::SeqCst);
+        });
+        assert_eq!(sum.load(Ordering::SeqCst), 999 * 1000 / 2);
+    }
+
+    #[test]
+    fn test_map() {
+        let pool = ThreadPool::new(3);
+        let squares: Vec<usize> = (0..100).map(|i| i * i).collect();
+        assert_eq!(pool.map(100, |i| squares[i]), squares);
+        assert!(pool.map(0, |i| i).is_empty());
+    }
+
+    #[test]
+    fn test_nested() {
+        // Loops inside loops and loops on several threads at once each wait for their own
+        let pool = Arc::new(ThreadPool::new(2));
+        let rows = pool.map(8, |y| pool.map(8, |x| x + y * 8).iter().sum::<usize>());
+        assert_eq!(rows.iter().sum::<usize>(), 63 * 64 / 2);
+        let callers: Vec<_> = (0..4)
+            .map(|_| {
+                let pool = pool.clone();
+                std::thread::spawn(move || pool.map(1000, |i| i).len())
+            })
+            .collect();
+        assert!(callers.into_iter().all(|caller| caller.join().unwrap() == 1000));
+    }
+
+    #[test]
+    fn test_workers() {
+        // Loops run on the persistent workers and the caller, never on threads of their own
+        let pool = ThreadPool::new(3);
+        let caller = std::thread::current().id();
+        let names = pool.map(64, |_| {
+            let current = std::thread::current();
+            if current.id() == caller {
+                None
+            } else {
+                current.name().map(str::to_owned)
+            }
+        });
+        assert!(names.iter().flatten().all(|name| name.starts_with("softrender-worker-")));
+    }
+
+    #[test]
+    #[should_panic(expected = "index failed")]
+    fn test_for_each_panic() {
+        let pool = ThreadPool::new(2);
+        pool.for_each(100, |i| assert!(i != 42, "index failed"));
+    }
+
+    #[test]
+    fn test_reuse() {
+        let pool = ThreadPool::new(2);
+        let count = Arc::new(AtomicUsize::new(0));
+        for _ in 0..10 {
+            for _ in 0..16 {
+                let count = count.clone();
+                pool.spawn(move || {
+                    count.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+            pool.wait();
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 160);
+    }
+
+    #[test]
+    fn test_concurrent() {
+        // Every job blocks its worker until all jobs have started, which only completes
+        // if every worker is picking up jobs.
+        let pool = ThreadPool::new(3);
+        let started = Arc::new(AtomicUsize::new(0));
+        for _ in 0..3 {
+            let started = started.clone();
+            pool.spawn(move || {
+                started.fetch_add(1, Ordering::SeqCst);
+                while started.load(Ordering::SeqCst) < 3 {
+                    std::thread::yield_now();
+                }
+            });
+        }
+        pool.wait();
+        assert_eq!(started.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_panic() {
+        let pool = ThreadPool::new(1);
+        pool.spawn(|| panic!("job failed"));
+        pool.wait();
+    }
+}
@@ -14,14 +14,34 @@
 // limitations under the License.
 //
 
-#![forbid(unsafe_code)]
+#![deny(unsafe_code)]
 #![warn(clippy::pedantic)]
 
-//pub mod canvas;
+pub mod animation;
+pub mod canvas;
+pub mod error;
+pub mod game_loop;
+pub mod input;
+pub mod jobs;
 pub mod math;
-//pub mod model;
-//mod rasterizer;
-//mod raytracer;
+pub mod model;
+pub mod post;
+pub mod presenter;
+pub mod profile;
+pub mod render_graph;
+pub mod shader;
+pub mod ui;
+mod rasterizer;
+mod raytracer;
 
-//pub use rasterizer::Rasterizer;
-//pub use raytracer::Raytracer;
+pub use rasterizer::{
+    Arena, ArenaStats, ClipVertex, ClippedPolygon, Command, CommandBuffer, DebugDraw, Gradient, LightBounds, LightGrid,
+    Paint, PlanarReflector, Rasterizer, ReflectionSettings, MAX_CLIPPED_VERTICES,
+};
+pub use raytracer::{
+    power_heuristic, Accumulator, Bvh, BvhNode, BvhOptions, BvhStats, Checkpointer, EnvironmentSample,
+    EnvironmentSampler, HaltonSampler, Hit, InstanceHit, Integrator, LightSampling, Lightmap, LightmapBaker, PathTracer,
+    PixelSampler, RandomSampler, Raytracer, RenderSettings, SobolSampler, SplitMethod, Tile, TileOrder, Tlas,
+};
+pub use error::{Error, Result};
+pub use jobs::set_thread_count;
@@ -0,0 +1,153 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Mat4f, Ray, Vec3f};
+
+///
+/// Axis aligned bounding box from `min` to `max`
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3f,
+    pub max: Vec3f,
+}
+
+impl Aabb {
+    /// Create new Aabb containing nothing, which grows to fit the first point added
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Vec3f::from_parts(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Vec3f::from_parts(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+    /// Smallest Aabb containing every point of `points`
+    pub fn from_points<I: IntoIterator<Item = Vec3f>>(points: I) -> Aabb {
+        let mut aabb = Aabb::empty();
+        for point in points {
+            aabb.grow(point);
+        }
+        aabb
+    }
+    /// Check if nothing was added to this Aabb
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+    /// Grow to contain `point`
+    pub fn grow(&mut self, point: Vec3f) {
+        self.min = Vec3f::from_parts(self.min.x.min(point.x), self.min.y.min(point.y), self.min.z.min(point.z));
+        self.max = Vec3f::from_parts(self.max.x.max(point.x), self.max.y.max(point.y), self.max.z.max(point.z));
+    }
+    /// Smallest Aabb containing both this and `other`
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        let mut aabb = *self;
+        if !other.is_empty() {
+            aabb.grow(other.min);
+            aabb.grow(other.max);
+        }
+        aabb
+    }
+    /// Size along each axis, zero for an empty Aabb
+    pub fn size(&self) -> Vec3f {
+        if self.is_empty() {
+            Vec3f::default()
+        } else {
+            self.max - self.min
+        }
+    }
+    /// Middle of the box
+    pub fn center(&self) -> Vec3f {
+        (self.min + self.max) * 0.5
+    }
+    /// Area of the six sides, zero for an empty Aabb
+    pub fn surface_area(&self) -> f32 {
+        let size = self.size();
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+    /// Bounds of this box after `transform`, which applies like `Vec3f * Mat4f`
+    pub fn transform(&self, transform: Mat4f) -> Aabb {
+        if self.is_empty() {
+            return *self;
+        }
+        Aabb::from_points((0..8).map(|corner| {
+            let pick = |bit: usize, min: f32, max: f32| if corner & bit == 0 { min } else { max };
+            let point = Vec3f::from_parts(
+                pick(1, self.min.x, self.max.x),
+                pick(2, self.min.y, self.max.y),
+                pick(4, self.min.z, self.max.z),
+            );
+            point * transform
+        }))
+    }
+    /// Distance along `ray` to where it enters this box, in multiples of its direction,
+    /// if it does so before `t_max`. A ray starting inside enters at 0.
+    pub fn intersect(&self, ray: &Ray, t_max: f32) -> Option<f32> {
+        let (mut near, mut far) = (0.0f32, t_max);
+        for axis in 0..3 {
+            let inverse = 1.0 / ray.direction[axis];
+            let mut t0 = (self.min[axis] - ray.origin[axis]) * inverse;
+            let mut t1 = (self.max[axis] - ray.origin[axis]) * inverse;
+            if inverse < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // NaN from a ray lying in a slab plane leaves the range alone
+            near = if t0 > near { t0 } else { near };
+            far = if t1 < far { t1 } else { far };
+            if near > far {
+                return None;
+            }
+        }
+        Some(near)
+    }
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Aabb::empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Aabb;
+    use crate::math::{Mat4f, Ray, Vec3f};
+
+    #[test]
+    fn test_grow() {
+        let mut aabb = Aabb::empty();
+        assert!(aabb.is_empty());
+        assert_eq!(aabb.surface_area(), 0.0);
+        aabb.grow(Vec3f::from_parts(1.0, 2.0, 3.0));
+        aabb.grow(Vec3f::from_parts(-1.0, 0.0, 4.0));
+        assert_eq!(aabb.size(), Vec3f::from_parts(2.0, 2.0, 1.0));
+        assert_eq!(aabb.center(), Vec3f::from_parts(0.0, 1.0, 3.5));
+        assert_eq!(aabb.surface_area(), 16.0);
+        assert_eq!(aabb.union(&Aabb::empty()), aabb);
+        let moved = aabb.transform(Mat4f::translation(Vec3f::from_parts(1.0, 0.0, 0.0)));
+        assert_eq!(moved.min, Vec3f::from_parts(0.0, 0.0, 3.0));
+    }
+
+    #[test]
+    fn test_intersect() {
+        let aabb = Aabb::from_points(vec![Vec3f::from_parts(-1.0, -1.0, -1.0), Vec3f::from_parts(1.0, 1.0, 1.0)]);
+        let ray = Ray::new(Vec3f::from_parts(0.0, 0.0, 5.0), Vec3f::from_parts(0.0, 0.0, -1.0));
+        assert_eq!(aabb.intersect(&ray, 100.0), Some(4.0));
+        assert_eq!(aabb.intersect(&ray, 3.0), None);
+        let inside = Ray::new(Vec3f::from_parts(0.0, 0.0, 0.0), Vec3f::from_parts(1.0, 0.0, 0.0));
+        assert_eq!(aabb.intersect(&inside, 100.0), Some(0.0));
+        let miss = Ray::new(Vec3f::from_parts(2.0, 0.0, 5.0), Vec3f::from_parts(0.0, 0.0, -1.0));
+        assert_eq!(aabb.intersect(&miss, 100.0), None);
+    }
+}
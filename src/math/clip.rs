@@ -0,0 +1,120 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Vec3f, Vec4f};
+
+/// Most user clip planes a `ClipPlanes` holds
+pub const MAX_CLIP_PLANES: usize = 8;
+
+///
+/// User clip planes cutting away part of what is rendered, for cutaway and section views.
+///
+/// Each plane `(a, b, c, d)` keeps the points with `a * x + b * y + c * z + d >= 0` and
+/// clips the rest. Holds up to `MAX_CLIP_PLANES` planes without allocating, so it can live
+/// in `Copy` types such as `Camera`.
+///
+/// ```
+/// use softrender::math::{ClipPlanes, Vec3f};
+///
+/// let mut planes = ClipPlanes::new();
+/// planes.push(ClipPlanes::plane(Vec3f::ZERO, Vec3f::UNIT_X));
+/// assert!(planes.is_clipped(Vec3f::new(-1.0, 0.0, 0.0)));
+/// assert!(!planes.is_clipped(Vec3f::new(1.0, 0.0, 0.0)));
+/// ```
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ClipPlanes {
+    planes: [Vec4f; MAX_CLIP_PLANES],
+    count: usize,
+}
+
+impl ClipPlanes {
+    /// Create new ClipPlanes clipping nothing
+    pub fn new() -> ClipPlanes {
+        ClipPlanes::default()
+    }
+    /// Plane through `point` keeping the side `normal` points to
+    pub fn plane(point: Vec3f, normal: Vec3f) -> Vec4f {
+        let normal = normal.normalize();
+        normal.extend(-normal.dot(point))
+    }
+    /// Add `plane`, returning false if `MAX_CLIP_PLANES` are already in use
+    pub fn push(&mut self, plane: Vec4f) -> bool {
+        if self.count == MAX_CLIP_PLANES {
+            return false;
+        }
+        self.planes[self.count] = plane;
+        self.count += 1;
+        true
+    }
+    /// Remove every plane
+    pub fn clear(&mut self) {
+        self.count = 0;
+    }
+    /// Planes in use
+    pub fn planes(&self) -> &[Vec4f] {
+        &self.planes[..self.count]
+    }
+    /// Number of planes in use
+    pub fn len(&self) -> usize {
+        self.count
+    }
+    /// Check if no planes are in use
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+    /// Check if `point` lies on the clipped side of any plane
+    pub fn is_clipped(&self, point: Vec3f) -> bool {
+        let point = point.extend(1.0);
+        self.planes().iter().any(|plane| plane.dot(point) < 0.0)
+    }
+    /// Check if every one of `points` lies on the clipped side of the same plane, so
+    /// nothing between them can be seen
+    pub fn clips_all(&self, points: &[Vec3f]) -> bool {
+        let inside = |plane: &Vec4f, point: &Vec3f| plane.dot(point.extend(1.0)) >= 0.0;
+        self.planes().iter().any(|plane| !points.iter().any(|point| inside(plane, point)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClipPlanes, MAX_CLIP_PLANES};
+    use crate::math::{Vec3f, Vec4f};
+
+    #[test]
+    fn test_clip_planes() {
+        let mut planes = ClipPlanes::new();
+        assert!(planes.is_empty());
+        assert!(!planes.is_clipped(Vec3f::new(-1.0, -1.0, 0.0)));
+        planes.push(ClipPlanes::plane(Vec3f::new(0.0, 1.0, 0.0), Vec3f::new(0.0, 2.0, 0.0)));
+        assert_eq!(planes.planes(), &[Vec4f::from_parts(0.0, 1.0, 0.0, -1.0)]);
+        planes.push(ClipPlanes::plane(Vec3f::ZERO, Vec3f::UNIT_X));
+        assert!(planes.is_clipped(Vec3f::new(1.0, 0.5, 0.0)));
+        assert!(planes.is_clipped(Vec3f::new(-1.0, 1.5, 0.0)));
+        assert!(!planes.is_clipped(Vec3f::new(1.0, 1.5, 0.0)));
+        // Points on either side of a plane may have something visible between them
+        let below = [Vec3f::new(1.0, 0.0, 0.0), Vec3f::new(2.0, 0.5, 0.0)];
+        assert!(planes.clips_all(&below));
+        assert!(!planes.clips_all(&[below[0], Vec3f::new(2.0, 2.0, 0.0)]));
+        assert!(!planes.clips_all(&[Vec3f::new(-1.0, 2.0, 0.0), Vec3f::new(1.0, 0.0, 0.0)]));
+        while planes.len() < MAX_CLIP_PLANES {
+            assert!(planes.push(Vec4f::from_parts(0.0, 0.0, 1.0, 10.0)));
+        }
+        assert!(!planes.push(Vec4f::from_parts(0.0, 0.0, 1.0, 10.0)));
+        planes.clear();
+        assert!(!planes.is_clipped(Vec3f::new(-1.0, -1.0, 0.0)));
+    }
+}
@@ -29,6 +29,8 @@ pub struct Mat2f {
 }
 
 impl Mat2f {
+    /// 2x2 Identity Matrix
+    pub const IDENTITY: Mat2f = Mat2f::identity();
     ///
     /// Create Matrix from Rows
     /// ```
@@ -46,7 +48,7 @@ impl Mat2f {
     /// 0 ( a, b )    | a, b | 0
     /// 1 ( c, d )  = | c, d | 1
     ///
-    pub fn from_rows(rows: [[f32; 2]; 2]) -> Mat2f {
+    pub const fn from_rows(rows: [[f32; 2]; 2]) -> Mat2f {
         Self {
             c0r0: rows[0][0],
             c1r0: rows[0][1],
@@ -72,7 +74,7 @@ impl Mat2f {
     /// 0 ( a, c )    | a, b | 0
     /// 1 ( b, d )  = | c, d | 1
     ///
-    pub fn from_cols(cols: [[f32; 2]; 2]) -> Mat2f {
+    pub const fn from_cols(cols: [[f32; 2]; 2]) -> Mat2f {
         Self {
             c0r0: cols[0][0],
             c0r1: cols[0][1],
@@ -94,7 +96,7 @@ impl Mat2f {
     /// 0 | 0.0, 0.0 |
     /// 1 | 0.0, 0.0 |
     ///
-    pub fn zero() -> Self {
+    pub const fn zero() -> Self {
         Self {
             c0r0: 1.0,
             c0r1: 0.0,
@@ -116,7 +118,7 @@ impl Mat2f {
     /// 0 | 1.0, 0.0 |
     /// 1 | 0.0, 1.0 |
     ///
-    pub fn identity() -> Self {
+    pub const fn identity() -> Self {
         Self {
             c0r0: 1.0,
             c0r1: 0.0,
@@ -223,6 +225,46 @@ impl Mat2f {
             c0r1:  self.c0r1 / det, c1r1: -self.c0r0 / det,
         }
     }
+    ///
+    /// Get a row of this Matrix as a vector.
+    ///
+    /// ```
+    /// use softrender::math::{Mat2f, Vec2f};
+    ///
+    /// let r = Mat2f::identity().row(0);
+    /// ```
+    ///
+    pub fn row(&self, index: usize) -> Vec2f {
+        Vec2f::from_parts(self[(index, 0)], self[(index, 1)])
+    }
+    ///
+    /// Get a column of this Matrix as a vector.
+    ///
+    /// ```
+    /// use softrender::math::{Mat2f, Vec2f};
+    ///
+    /// let c = Mat2f::identity().col(0);
+    /// ```
+    ///
+    pub fn col(&self, index: usize) -> Vec2f {
+        Vec2f::from_parts(self[(0, index)], self[(1, index)])
+    }
+    ///
+    /// Replace a row of this Matrix.
+    ///
+    pub fn set_row(&mut self, index: usize, row: Vec2f) {
+        for (col, value) in row.to_array().iter().enumerate() {
+            self[(index, col)] = *value;
+        }
+    }
+    ///
+    /// Replace a column of this Matrix.
+    ///
+    pub fn set_col(&mut self, index: usize, col: Vec2f) {
+        for (row, value) in col.to_array().iter().enumerate() {
+            self[(row, index)] = *value;
+        }
+    }
 }
 
 impl Default for Mat2f {
@@ -262,6 +304,26 @@ impl ops::AddAssign<Self> for Mat2f {
     }
 }
 
+impl ops::Div<f32> for Mat2f {
+    type Output = Self;
+
+    /// Divide every element by a scalar.
+    fn div(self, rhs: f32) -> Self {
+        Self {
+            c0r0: self.c0r0 / rhs,
+            c0r1: self.c0r1 / rhs,
+            c1r0: self.c1r0 / rhs,
+            c1r1: self.c1r1 / rhs,
+        }
+    }
+}
+
+impl ops::DivAssign<f32> for Mat2f {
+    fn div_assign(&mut self, rhs: f32) {
+        *self = *self / rhs;
+    }
+}
+
 impl ops::Mul<Self> for Mat2f {
     type Output = Self;
 
@@ -298,6 +360,49 @@ impl ops::Mul<Vec2f> for Mat2f {
     }
 }
 
+impl ops::Mul<f32> for Mat2f {
+    type Output = Self;
+
+    /// Multiply every element by a scalar.
+    fn mul(self, rhs: f32) -> Self {
+        Self {
+            c0r0: self.c0r0 * rhs,
+            c0r1: self.c0r1 * rhs,
+            c1r0: self.c1r0 * rhs,
+            c1r1: self.c1r1 * rhs,
+        }
+    }
+}
+
+impl ops::MulAssign<f32> for Mat2f {
+    fn mul_assign(&mut self, rhs: f32) {
+        *self = *self * rhs;
+    }
+}
+
+impl ops::Mul<Mat2f> for f32 {
+    type Output = Mat2f;
+
+    /// Multiply every element by a scalar.
+    fn mul(self, rhs: Mat2f) -> Mat2f {
+        rhs * self
+    }
+}
+
+impl ops::Neg for Mat2f {
+    type Output = Self;
+
+    /// Negate every element.
+    fn neg(self) -> Self {
+        Self {
+            c0r0: -self.c0r0,
+            c0r1: -self.c0r1,
+            c1r0: -self.c1r0,
+            c1r1: -self.c1r1,
+        }
+    }
+}
+
 impl ops::Sub<Self> for Mat2f {
     type Output = Self;
 
@@ -320,6 +425,34 @@ impl ops::SubAssign<Self> for Mat2f {
     }
 }
 
+impl ops::Index<(usize, usize)> for Mat2f {
+    type Output = f32;
+
+    /// Get the element at (row, column).
+    fn index(&self, index: (usize, usize)) -> &f32 {
+        match index {
+            (0, 0) => &self.c0r0,
+            (0, 1) => &self.c1r0,
+            (1, 0) => &self.c0r1,
+            (1, 1) => &self.c1r1,
+            (row, col) => panic!("Mat2f index ({}, {}) out of range", row, col),
+        }
+    }
+}
+
+impl ops::IndexMut<(usize, usize)> for Mat2f {
+    /// Get a mutable reference to the element at (row, column).
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut f32 {
+        match index {
+            (0, 0) => &mut self.c0r0,
+            (0, 1) => &mut self.c1r0,
+            (1, 0) => &mut self.c0r1,
+            (1, 1) => &mut self.c1r1,
+            (row, col) => panic!("Mat2f index ({}, {}) out of range", row, col),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{Mat2f, Vec2f};
@@ -614,4 +747,99 @@ mod tests {
         );
         assert_eq!(a, c);
     }
+
+    #[test]
+    fn test_index() {
+        let mut m = Mat2f::from_rows(
+            [
+                [1.0, 2.0],
+                [3.0, 4.0],
+            ]
+        );
+        assert_eq!(m[(0, 0)], 1.0);
+        assert_eq!(m[(0, 1)], 2.0);
+        assert_eq!(m[(1, 0)], 3.0);
+        m[(1, 0)] = 42.0;
+        assert_eq!(m.to_rows()[1][0], 42.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_out_of_range() {
+        let _ = Mat2f::identity()[(2, 0)];
+    }
+
+    #[test]
+    fn test_row_col() {
+        let mut m = Mat2f::from_rows(
+            [
+                [1.0, 2.0],
+                [3.0, 4.0],
+            ]
+        );
+        assert_eq!(m.row(1), Vec2f::from_parts(3.0, 4.0));
+        assert_eq!(m.col(1), Vec2f::from_parts(2.0, 4.0));
+        m.set_row(0, Vec2f::from_parts(-1.0, -2.0));
+        assert_eq!(m.to_rows()[0], [-1.0, -2.0]);
+        m.set_col(1, Vec2f::from_parts(-1.0, -2.0));
+        assert_eq!(m.to_cols()[1], [-1.0, -2.0]);
+    }
+
+    #[test]
+    fn test_scalar_mul() {
+        let a = Mat2f::from_rows(
+            [
+                [1.0, 2.0],
+                [3.0, 4.0],
+            ]
+        );
+        let b = Mat2f::from_rows(
+            [
+                [2.0, 4.0],
+                [6.0, 8.0],
+            ]
+        );
+        assert_eq!(a * 2.0, b);
+        assert_eq!(2.0 * a, b);
+        let mut c = a;
+        c *= 2.0;
+        assert_eq!(c, b);
+    }
+
+    #[test]
+    fn test_scalar_div() {
+        let a = Mat2f::from_rows(
+            [
+                [2.0, 4.0],
+                [6.0, 8.0],
+            ]
+        );
+        let b = Mat2f::from_rows(
+            [
+                [1.0, 2.0],
+                [3.0, 4.0],
+            ]
+        );
+        assert_eq!(a / 2.0, b);
+        let mut c = a;
+        c /= 2.0;
+        assert_eq!(c, b);
+    }
+
+    #[test]
+    fn test_neg() {
+        let a = Mat2f::from_rows(
+            [
+                [1.0, 2.0],
+                [3.0, 4.0],
+            ]
+        );
+        let b = Mat2f::from_rows(
+            [
+                [-1.0, -2.0],
+                [-3.0, -4.0],
+            ]
+        );
+        assert_eq!(-a, b);
+    }
 }
@@ -13,7 +13,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 //
-use super::Vec3f;
+use super::{Mat4f, Vec3f};
 use std::{fmt, ops};
 
 ///
@@ -33,6 +33,10 @@ pub struct Mat3f {
 }
 
 impl Mat3f {
+    /// 3x3 Zero Matrix
+    pub const ZERO: Mat3f = Mat3f::zero();
+    /// 3x3 Identity Matrix
+    pub const IDENTITY: Mat3f = Mat3f::identity();
     ///
     /// Create 3x3 Matrix from an array of column arrays.
     ///
@@ -53,7 +57,7 @@ impl Mat3f {
     /// 1 ( b, e, h )    1 | d, e, f |
     /// 2 ( c, f, i ) -> 2 | g, h, i |
     ///
-    pub fn from_cols(cols: [[f32; 3]; 3]) -> Mat3f {
+    pub const fn from_cols(cols: [[f32; 3]; 3]) -> Mat3f {
         Mat3f {
             c0r0: cols[0][0],
             c0r1: cols[1][0],
@@ -87,7 +91,7 @@ impl Mat3f {
     /// 1 ( d, e, f )    | d, e, f | 1
     /// 2 ( g, h, i ) -> | g, h, i | 2
     ///
-    pub fn from_rows(rows: [[f32; 3]; 3]) -> Mat3f {
+    pub const fn from_rows(rows: [[f32; 3]; 3]) -> Mat3f {
         Mat3f {
             c0r0: rows[0][0],
             c0r1: rows[0][1],
@@ -137,7 +141,7 @@ impl Mat3f {
     /// 1 | d, e, f |    1 ( b, e, h )
     /// 2 | g, h, i | -> 2 ( c, f, i )
     ///
-    pub fn cols(&self) -> [[f32; 3]; 3] {
+    pub fn to_cols(&self) -> [[f32; 3]; 3] {
         [
             [self.c0r0, self.c1r0, self.c2r0],
             [self.c0r1, self.c1r1, self.c2r1],
@@ -159,7 +163,7 @@ impl Mat3f {
     /// 1 | 0.0, 0.0, 0.0 |
     /// 2 | 0.0, 0.0, 0.0 |
     ///
-    pub fn zero() -> Mat3f {
+    pub const fn zero() -> Mat3f {
         Mat3f {
             c0r0: 0.0,
             c0r1: 0.0,
@@ -187,7 +191,7 @@ impl Mat3f {
     /// 1 | 0.0, 1.0, 0.0 |
     /// 2 | 0.0, 0.0, 1.0 |
     ///
-    pub fn identity() -> Mat3f {
+    pub const fn identity() -> Mat3f {
         Mat3f {
             c0r0: 1.0,
             c0r1: 0.0,
@@ -262,11 +266,93 @@ impl Mat3f {
                     c1r2: -(-self.c1r0 * self.c0r2 + self.c0r0 * self.c1r2) / det,
                     c2r0: (-self.c2r0 * self.c1r1 + self.c1r0 * self.c2r1) / det,
                     c2r1: -(-self.c2r0 * self.c0r1 + self.c0r0 * self.c2r1) / det,
-                    c2r2: (-self.c1r0 * self.c0r2 + self.c0r0 * self.c1r1) / det,
+                    c2r2: (self.c0r0 * self.c1r1 - self.c0r1 * self.c1r0) / det,
                 }
             )
         }
     }
+    ///
+    /// Get a row of this Matrix as a vector.
+    ///
+    /// ```
+    /// use softrender::math::{Mat3f, Vec3f};
+    ///
+    /// let r = Mat3f::identity().row(0);
+    /// ```
+    ///
+    pub fn row(&self, index: usize) -> Vec3f {
+        Vec3f::from_parts(self[(index, 0)], self[(index, 1)], self[(index, 2)])
+    }
+    ///
+    /// Get a column of this Matrix as a vector.
+    ///
+    /// ```
+    /// use softrender::math::{Mat3f, Vec3f};
+    ///
+    /// let c = Mat3f::identity().col(0);
+    /// ```
+    ///
+    pub fn col(&self, index: usize) -> Vec3f {
+        Vec3f::from_parts(self[(0, index)], self[(1, index)], self[(2, index)])
+    }
+    ///
+    /// Replace a row of this Matrix.
+    ///
+    pub fn set_row(&mut self, index: usize, row: Vec3f) {
+        for (col, value) in row.to_array().iter().enumerate() {
+            self[(index, col)] = *value;
+        }
+    }
+    ///
+    /// Replace a column of this Matrix.
+    ///
+    pub fn set_col(&mut self, index: usize, col: Vec3f) {
+        for (row, value) in col.to_array().iter().enumerate() {
+            self[(row, index)] = *value;
+        }
+    }
+    ///
+    /// Create a 4x4 Matrix with this Matrix as its upper 3x3 and no translation.
+    ///
+    /// ```
+    /// use softrender::math::Mat3f;
+    ///
+    /// let m = Mat3f::identity().to_mat4();
+    /// ```
+    ///
+    pub fn to_mat4(&self) -> Mat4f {
+        Mat4f {
+            c0r0: self.c0r0,
+            c0r1: self.c0r1,
+            c0r2: self.c0r2,
+            c0r3: 0.0,
+            c1r0: self.c1r0,
+            c1r1: self.c1r1,
+            c1r2: self.c1r2,
+            c1r3: 0.0,
+            c2r0: self.c2r0,
+            c2r1: self.c2r1,
+            c2r2: self.c2r2,
+            c2r3: 0.0,
+            c3r0: 0.0,
+            c3r1: 0.0,
+            c3r2: 0.0,
+            c3r3: 1.0,
+        }
+    }
+    ///
+    /// Calculate the inverse-transpose of this Matrix, used to transform normals so they
+    /// stay perpendicular to their surfaces under non-uniform scale and shear.
+    ///
+    /// ```
+    /// use softrender::math::Mat3f;
+    ///
+    /// let n = Mat3f::identity().normal_matrix().unwrap();
+    /// ```
+    ///
+    pub fn normal_matrix(&self) -> Option<Mat3f> {
+        self.invert().map(|inverse| inverse.transpose())
+    }
 }
 
 impl Default for Mat3f {
@@ -313,6 +399,31 @@ impl ops::AddAssign<Self> for Mat3f {
     }
 }
 
+impl ops::Div<f32> for Mat3f {
+    type Output = Self;
+
+    /// Divide every element by a scalar.
+    fn div(self, rhs: f32) -> Self {
+        Self {
+            c0r0: self.c0r0 / rhs,
+            c0r1: self.c0r1 / rhs,
+            c0r2: self.c0r2 / rhs,
+            c1r0: self.c1r0 / rhs,
+            c1r1: self.c1r1 / rhs,
+            c1r2: self.c1r2 / rhs,
+            c2r0: self.c2r0 / rhs,
+            c2r1: self.c2r1 / rhs,
+            c2r2: self.c2r2 / rhs,
+        }
+    }
+}
+
+impl ops::DivAssign<f32> for Mat3f {
+    fn div_assign(&mut self, rhs: f32) {
+        *self = *self / rhs;
+    }
+}
+
 impl ops::Mul<Self> for Mat3f {
     type Output = Self;
 
@@ -357,6 +468,59 @@ impl ops::Mul<Vec3f> for Mat3f {
     }
 }
 
+impl ops::Mul<f32> for Mat3f {
+    type Output = Self;
+
+    /// Multiply every element by a scalar.
+    fn mul(self, rhs: f32) -> Self {
+        Self {
+            c0r0: self.c0r0 * rhs,
+            c0r1: self.c0r1 * rhs,
+            c0r2: self.c0r2 * rhs,
+            c1r0: self.c1r0 * rhs,
+            c1r1: self.c1r1 * rhs,
+            c1r2: self.c1r2 * rhs,
+            c2r0: self.c2r0 * rhs,
+            c2r1: self.c2r1 * rhs,
+            c2r2: self.c2r2 * rhs,
+        }
+    }
+}
+
+impl ops::MulAssign<f32> for Mat3f {
+    fn mul_assign(&mut self, rhs: f32) {
+        *self = *self * rhs;
+    }
+}
+
+impl ops::Mul<Mat3f> for f32 {
+    type Output = Mat3f;
+
+    /// Multiply every element by a scalar.
+    fn mul(self, rhs: Mat3f) -> Mat3f {
+        rhs * self
+    }
+}
+
+impl ops::Neg for Mat3f {
+    type Output = Self;
+
+    /// Negate every element.
+    fn neg(self) -> Self {
+        Self {
+            c0r0: -self.c0r0,
+            c0r1: -self.c0r1,
+            c0r2: -self.c0r2,
+            c1r0: -self.c1r0,
+            c1r1: -self.c1r1,
+            c1r2: -self.c1r2,
+            c2r0: -self.c2r0,
+            c2r1: -self.c2r1,
+            c2r2: -self.c2r2,
+        }
+    }
+}
+
 impl ops::Sub<Self> for Mat3f {
     type Output = Self;
 
@@ -386,6 +550,44 @@ impl ops::SubAssign<Self> for Mat3f {
 }
 
 
+impl ops::Index<(usize, usize)> for Mat3f {
+    type Output = f32;
+
+    /// Get the element at (row, column).
+    fn index(&self, index: (usize, usize)) -> &f32 {
+        match index {
+            (0, 0) => &self.c0r0,
+            (0, 1) => &self.c0r1,
+            (0, 2) => &self.c0r2,
+            (1, 0) => &self.c1r0,
+            (1, 1) => &self.c1r1,
+            (1, 2) => &self.c1r2,
+            (2, 0) => &self.c2r0,
+            (2, 1) => &self.c2r1,
+            (2, 2) => &self.c2r2,
+            (row, col) => panic!("Mat3f index ({}, {}) out of range", row, col),
+        }
+    }
+}
+
+impl ops::IndexMut<(usize, usize)> for Mat3f {
+    /// Get a mutable reference to the element at (row, column).
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut f32 {
+        match index {
+            (0, 0) => &mut self.c0r0,
+            (0, 1) => &mut self.c0r1,
+            (0, 2) => &mut self.c0r2,
+            (1, 0) => &mut self.c1r0,
+            (1, 1) => &mut self.c1r1,
+            (1, 2) => &mut self.c1r2,
+            (2, 0) => &mut self.c2r0,
+            (2, 1) => &mut self.c2r1,
+            (2, 2) => &mut self.c2r2,
+            (row, col) => panic!("Mat3f index ({}, {}) out of range", row, col),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{Mat3f, Vec3f};
@@ -448,12 +650,41 @@ mod tests {
         );
         let b = Mat3f::from_rows(
             [
-                [-0.375, 0.5, 0.125],
-                [0.5, -1.0, 0.5],
-                [0.125, 0.5, -0.375],
+                [-3.0 / 12.0, 4.0 / 12.0, 1.0 / 12.0],
+                [3.0 / 12.0, -8.0 / 12.0, 7.0 / 12.0],
+                [3.0 / 12.0, 4.0 / 12.0, -5.0 / 12.0],
             ]
         );
-        assert_eq!(a.invert().unwrap(), b)
+        let inverse = a.invert().unwrap();
+        for (row, expected) in inverse.to_rows().iter().zip(b.to_rows().iter()) {
+            for (value, expected) in row.iter().zip(expected.iter()) {
+                assert_approx_eq!(value, expected);
+            }
+        }
+        assert!(Mat3f::zero().invert().is_none());
+    }
+
+    #[test]
+    fn test_normal_matrix() {
+        // Rotation about z and x followed by a non-uniform scale
+        let (s, c) = 30f32.to_radians().sin_cos();
+        let about_z = Mat3f::from_rows([[c, -s, 0.0], [s, c, 0.0], [0.0, 0.0, 1.0]]);
+        let about_x = Mat3f::from_rows([[1.0, 0.0, 0.0], [0.0, c, -s], [0.0, s, c]]);
+        let scale = Mat3f::from_rows([[2.0, 0.0, 0.0], [0.0, 0.5, 0.0], [0.0, 0.0, 3.0]]);
+        let m = scale * about_x * about_z;
+        let n = m.normal_matrix().unwrap();
+        let identity = (n * n.invert().unwrap()).to_rows();
+        for (row, expected) in identity.iter().zip(Mat3f::identity().to_rows().iter()) {
+            for (value, expected) in row.iter().zip(expected.iter()) {
+                assert_approx_eq!(value, expected, 1e-5);
+            }
+        }
+        // Normals stay perpendicular to the transformed tangents of their surface
+        let tangents = [Vec3f::from_parts(1.0, 2.0, -1.0), Vec3f::from_parts(0.5, -1.0, 2.0)];
+        let normal = Vec3f::cross(tangents[0], tangents[1]);
+        for &tangent in &tangents {
+            assert_approx_eq!((m * tangent).normalize().dot((n * normal).normalize()), 0.0, 1e-5);
+        }
     }
 
     #[test]
@@ -644,4 +875,107 @@ mod tests {
         );
         assert_eq!(a, c);
     }
+
+    #[test]
+    fn test_index() {
+        let mut m = Mat3f::from_rows(
+            [
+                [1.0, 2.0, 3.0],
+                [4.0, 5.0, 6.0],
+                [7.0, 8.0, 9.0],
+            ]
+        );
+        assert_eq!(m[(0, 0)], 1.0);
+        assert_eq!(m[(0, 2)], 3.0);
+        assert_eq!(m[(2, 0)], 7.0);
+        m[(1, 0)] = 42.0;
+        assert_eq!(m.to_rows()[1][0], 42.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_out_of_range() {
+        let _ = Mat3f::identity()[(3, 0)];
+    }
+
+    #[test]
+    fn test_row_col() {
+        let mut m = Mat3f::from_rows(
+            [
+                [1.0, 2.0, 3.0],
+                [4.0, 5.0, 6.0],
+                [7.0, 8.0, 9.0],
+            ]
+        );
+        assert_eq!(m.row(1), Vec3f::from_parts(4.0, 5.0, 6.0));
+        assert_eq!(m.col(1), Vec3f::from_parts(2.0, 5.0, 8.0));
+        m.set_row(0, Vec3f::from_parts(-1.0, -2.0, -3.0));
+        assert_eq!(m.to_rows()[0], [-1.0, -2.0, -3.0]);
+        m.set_col(2, Vec3f::from_parts(-1.0, -2.0, -3.0));
+        assert_eq!(m.to_cols()[2], [-1.0, -2.0, -3.0]);
+    }
+
+    #[test]
+    fn test_scalar_mul() {
+        let a = Mat3f::from_rows(
+            [
+                [1.0, 2.0, 3.0],
+                [4.0, 5.0, 6.0],
+                [7.0, 8.0, 9.0],
+            ]
+        );
+        let b = Mat3f::from_rows(
+            [
+                [2.0, 4.0, 6.0],
+                [8.0, 10.0, 12.0],
+                [14.0, 16.0, 18.0],
+            ]
+        );
+        assert_eq!(a * 2.0, b);
+        assert_eq!(2.0 * a, b);
+        let mut c = a;
+        c *= 2.0;
+        assert_eq!(c, b);
+    }
+
+    #[test]
+    fn test_scalar_div() {
+        let a = Mat3f::from_rows(
+            [
+                [2.0, 4.0, 6.0],
+                [8.0, 10.0, 12.0],
+                [14.0, 16.0, 18.0],
+            ]
+        );
+        let b = Mat3f::from_rows(
+            [
+                [1.0, 2.0, 3.0],
+                [4.0, 5.0, 6.0],
+                [7.0, 8.0, 9.0],
+            ]
+        );
+        assert_eq!(a / 2.0, b);
+        let mut c = a;
+        c /= 2.0;
+        assert_eq!(c, b);
+    }
+
+    #[test]
+    fn test_neg() {
+        let a = Mat3f::from_rows(
+            [
+                [1.0, 2.0, 3.0],
+                [4.0, 5.0, 6.0],
+                [7.0, 8.0, 9.0],
+            ]
+        );
+        let b = Mat3f::from_rows(
+            [
+                [-1.0, -2.0, -3.0],
+                [-4.0, -5.0, -6.0],
+                [-7.0, -8.0, -9.0],
+            ]
+        );
+        assert_eq!(-a, b);
+    }
 }
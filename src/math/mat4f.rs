@@ -14,7 +14,7 @@
 // limitations under the License.
 //
 use std::{fmt, ops};
-use super::{Vec3f, Vec4f};
+use super::{Mat3f, Vec3f, Vec4f};
 
 ///
 /// 4x4 Matrix
@@ -40,6 +40,10 @@ pub struct Mat4f {
 }
 
 impl Mat4f {
+    /// 4x4 Zero Matrix
+    pub const ZERO: Mat4f = Mat4f::zero();
+    /// 4x4 Identity Matrix
+    pub const IDENTITY: Mat4f = Mat4f::identity();
     ///
     /// Create 4x4 Matrix from an array of column arrays.
     ///
@@ -62,7 +66,7 @@ impl Mat4f {
     /// ( c, g, k, o )    2 | i, j, k, l |
     /// ( d, h, l, p )  = 3 | m, n, o, p |
     ///
-    pub fn from_cols(data: [[f32; 4]; 4]) -> Mat4f {
+    pub const fn from_cols(data: [[f32; 4]; 4]) -> Mat4f {
         Mat4f {
             c0r0: data[0][0],
             c0r1: data[1][0],
@@ -105,7 +109,7 @@ impl Mat4f {
     /// ( i, j, k, l )    2 | i, j, k, l |
     /// ( m, n, o, p )  = 3 | m, n, o, p |
     ///
-    pub fn from_rows(data: [[f32; 4]; 4]) -> Mat4f {
+    pub const fn from_rows(data: [[f32; 4]; 4]) -> Mat4f {
         Mat4f {
             c0r0: data[0][0],
             c0r1: data[0][1],
@@ -139,7 +143,7 @@ impl Mat4f {
     /// 2 | 0.0, 0.0, 0.0, 0.0 |
     /// 3 | 0.0, 0.0, 0.0, 0.0 |
     ///
-    pub fn zero() -> Mat4f {
+    pub const fn zero() -> Mat4f {
         Mat4f {
             c0r0: 0.0,
             c0r1: 0.0,
@@ -174,7 +178,7 @@ impl Mat4f {
     /// 2 | 0.0, 0.0, 1.0, 0.0 |
     /// 3 | 0.0, 0.0, 0.0, 1.0 |
     ///
-    pub fn identity() -> Mat4f {
+    pub const fn identity() -> Mat4f {
         Mat4f {
             c0r0: 1.0,
             c0r1: 0.0,
@@ -195,6 +199,23 @@ impl Mat4f {
         }
     }
     ///
+    /// Create a translation by `offset`, for points transformed as `point * m`.
+    ///
+    /// ```
+    /// use softrender::math::{Mat4f, Vec3f};
+    ///
+    /// let m = Mat4f::translation(Vec3f::from_parts(1.0, 2.0, 3.0));
+    /// assert_eq!(Vec3f::from_parts(1.0, 1.0, 1.0) * m, Vec3f::from_parts(2.0, 3.0, 4.0));
+    /// ```
+    ///
+    pub fn translation(offset: Vec3f) -> Mat4f {
+        let mut m = Mat4f::identity();
+        m.c3r0 = offset.x;
+        m.c3r1 = offset.y;
+        m.c3r2 = offset.z;
+        m
+    }
+    ///
     /// Calculate the transpose of this matrix.
     ///
     /// ```
@@ -260,13 +281,13 @@ impl Mat4f {
     /// ```
     /// use softrender::math::Mat4f;
     ///
-    /// let m = Mat4f::identity().determinant();
+    /// let m = Mat4f::identity().invert();
     /// ```
     ///
     pub fn invert(&self) -> Option<Self> {
         let x00 = self.c0r0;
         let x01 = self.c0r1;
-        let x02 = self.c0r3;
+        let x02 = self.c0r2;
         let x03 = self.c0r3;
         let x04 = self.c1r0;
         let x05 = self.c1r1;
@@ -319,29 +340,294 @@ impl Mat4f {
             )
         }
     }
-    pub fn perspective(fov_deg: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
-        let fov_rad = 1.0 / (fov_deg * 0.5 / 180.0 * std::f32::consts::PI).tan();
-        Mat4f {
-            c0r0: aspect_ratio * fov_rad,
-            c0r1: 0.0,
-            c0r2: 0.0,
+    ///
+    /// Calculate the inversion of an affine transform.
+    ///
+    /// Only valid when the last column is `(0, 0, 0, 1)`, as it is for model and view
+    /// matrices. The upper 3x3 is inverted on its own and the translation row is
+    /// transformed by it, which is considerably cheaper than [`Mat4f::invert`].
+    ///
+    /// ```
+    /// use softrender::math::{Mat4f, Vec3f};
+    ///
+    /// let view = Mat4f::look_at_rh(
+    ///     Vec3f::from_parts(0.0, 0.0, 5.0),
+    ///     Vec3f::from_parts(0.0, 0.0, 0.0),
+    ///     Vec3f::from_parts(0.0, 1.0, 0.0),
+    /// );
+    /// let camera = view.invert_affine().unwrap();
+    /// ```
+    ///
+    pub fn invert_affine(&self) -> Option<Self> {
+        let c00 = self.c1r1 * self.c2r2 - self.c1r2 * self.c2r1;
+        let c01 = self.c1r2 * self.c2r0 - self.c1r0 * self.c2r2;
+        let c02 = self.c1r0 * self.c2r1 - self.c1r1 * self.c2r0;
+        let det = self.c0r0 * c00 + self.c0r1 * c01 + self.c0r2 * c02;
+        if det == 0.0 {
+            None
+        } else {
+            let inv_det = 1.0 / det;
+            let r00 = c00 * inv_det;
+            let r01 = (self.c0r2 * self.c2r1 - self.c0r1 * self.c2r2) * inv_det;
+            let r02 = (self.c0r1 * self.c1r2 - self.c0r2 * self.c1r1) * inv_det;
+            let r10 = c01 * inv_det;
+            let r11 = (self.c0r0 * self.c2r2 - self.c0r2 * self.c2r0) * inv_det;
+            let r12 = (self.c0r2 * self.c1r0 - self.c0r0 * self.c1r2) * inv_det;
+            let r20 = c02 * inv_det;
+            let r21 = (self.c0r1 * self.c2r0 - self.c0r0 * self.c2r1) * inv_det;
+            let r22 = (self.c0r0 * self.c1r1 - self.c0r1 * self.c1r0) * inv_det;
+            Some(Self::affine_with_inverse_rotation(self, r00, r01, r02, r10, r11, r12, r20, r21, r22))
+        }
+    }
+    ///
+    /// Calculate the inversion of a rigid transform.
+    ///
+    /// Only valid when the upper 3x3 is a pure rotation (orthonormal, no scale or shear)
+    /// and the last column is `(0, 0, 0, 1)`. The rotation is transposed and the
+    /// translation negated, avoiding any division.
+    ///
+    /// ```
+    /// use softrender::math::{Mat4f, Vec3f};
+    ///
+    /// let view = Mat4f::look_at_rh(
+    ///     Vec3f::from_parts(0.0, 0.0, 5.0),
+    ///     Vec3f::from_parts(0.0, 0.0, 0.0),
+    ///     Vec3f::from_parts(0.0, 1.0, 0.0),
+    /// );
+    /// let camera = view.invert_rigid();
+    /// ```
+    ///
+    pub fn invert_rigid(&self) -> Self {
+        Self::affine_with_inverse_rotation(
+            self,
+            self.c0r0, self.c1r0, self.c2r0,
+            self.c0r1, self.c1r1, self.c2r1,
+            self.c0r2, self.c1r2, self.c2r2,
+        )
+    }
+    ///
+    /// Assemble the inverse of an affine transform from its inverted upper 3x3.
+    ///
+    #[allow(clippy::too_many_arguments)]
+    fn affine_with_inverse_rotation(
+        &self,
+        r00: f32, r01: f32, r02: f32,
+        r10: f32, r11: f32, r12: f32,
+        r20: f32, r21: f32, r22: f32,
+    ) -> Self {
+        let (tx, ty, tz) = (self.c3r0, self.c3r1, self.c3r2);
+        Self {
+            c0r0: r00,
+            c0r1: r01,
+            c0r2: r02,
             c0r3: 0.0,
-            c1r0: 0.0,
-            c1r1: fov_rad,
-            c1r2: 0.0,
+            c1r0: r10,
+            c1r1: r11,
+            c1r2: r12,
             c1r3: 0.0,
-            c2r0: 0.0,
-            c2r1: 0.0,
-            c2r2: far / (far - near),
-            c2r3: 1.0,
-            c3r0: 0.0,
-            c3r1: 0.0,
-            c3r2: (-far * near) / (far - near),
-            c3r3: 0.0,
+            c2r0: r20,
+            c2r1: r21,
+            c2r2: r22,
+            c2r3: 0.0,
+            c3r0: -(tx * r00 + ty * r10 + tz * r20),
+            c3r1: -(tx * r01 + ty * r11 + tz * r21),
+            c3r2: -(tx * r02 + ty * r12 + tz * r22),
+            c3r3: 1.0,
         }
     }
+    ///
+    /// Create a left handed perspective projection with a zero to one depth range.
+    ///
+    /// Kept for compatibility, this is equivalent to [`Mat4f::perspective_lh`].
+    ///
+    pub fn perspective(fov_deg: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+        Self::perspective_lh(fov_deg, aspect_ratio, near, far)
+    }
+    ///
+    /// Create a right handed perspective projection with an OpenGL style depth range
+    /// of -1 to 1.
+    ///
+    /// The camera looks down -Z, `fov_deg` is the vertical field of view in degrees and
+    /// points are transformed as row vectors (`Vec4f * Mat4f`).
+    ///
+    /// ```
+    /// use softrender::math::Mat4f;
+    ///
+    /// let m = Mat4f::perspective_rh_gl(90.0, 16.0 / 9.0, 0.1, 100.0);
+    /// ```
+    ///
+    pub fn perspective_rh_gl(fov_deg: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+        let focal = 1.0 / (fov_deg.to_radians() * 0.5).tan();
+        let range = 1.0 / (near - far);
+        Mat4f::from_rows([
+            [focal / aspect_ratio, 0.0, 0.0, 0.0],
+            [0.0, focal, 0.0, 0.0],
+            [0.0, 0.0, (far + near) * range, -1.0],
+            [0.0, 0.0, 2.0 * far * near * range, 0.0],
+        ])
+    }
+    ///
+    /// Create a right handed perspective projection with a zero to one depth range
+    /// (Vulkan, Direct3D and Metal style).
+    ///
+    /// The camera looks down -Z, `fov_deg` is the vertical field of view in degrees and
+    /// points are transformed as row vectors (`Vec4f * Mat4f`).
+    ///
+    /// ```
+    /// use softrender::math::Mat4f;
+    ///
+    /// let m = Mat4f::perspective_rh_zo(90.0, 16.0 / 9.0, 0.1, 100.0);
+    /// ```
+    ///
+    pub fn perspective_rh_zo(fov_deg: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+        let focal = 1.0 / (fov_deg.to_radians() * 0.5).tan();
+        let range = 1.0 / (near - far);
+        Mat4f::from_rows([
+            [focal / aspect_ratio, 0.0, 0.0, 0.0],
+            [0.0, focal, 0.0, 0.0],
+            [0.0, 0.0, far * range, -1.0],
+            [0.0, 0.0, far * near * range, 0.0],
+        ])
+    }
+    ///
+    /// Create a left handed perspective projection with a zero to one depth range
+    /// (classic Direct3D style).
+    ///
+    /// The camera looks down +Z, `fov_deg` is the vertical field of view in degrees and
+    /// points are transformed as row vectors (`Vec4f * Mat4f`).
+    ///
+    /// ```
+    /// use softrender::math::Mat4f;
+    ///
+    /// let m = Mat4f::perspective_lh(90.0, 16.0 / 9.0, 0.1, 100.0);
+    /// ```
+    ///
+    pub fn perspective_lh(fov_deg: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+        let focal = 1.0 / (fov_deg.to_radians() * 0.5).tan();
+        let range = 1.0 / (far - near);
+        Mat4f::from_rows([
+            [focal / aspect_ratio, 0.0, 0.0, 0.0],
+            [0.0, focal, 0.0, 0.0],
+            [0.0, 0.0, far * range, 1.0],
+            [0.0, 0.0, -far * near * range, 0.0],
+        ])
+    }
+    ///
+    /// Create a right handed orthographic projection with an OpenGL style depth range
+    /// of -1 to 1.
+    ///
+    /// The camera looks down -Z, `height` is the height of the view in world units and
+    /// points are transformed as row vectors (`Vec4f * Mat4f`).
+    ///
+    /// ```
+    /// use softrender::math::Mat4f;
+    ///
+    /// let m = Mat4f::orthographic_rh_gl(10.0, 16.0 / 9.0, 0.1, 100.0);
+    /// ```
+    ///
+    pub fn orthographic_rh_gl(height: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+        let scale = 2.0 / height;
+        let range = 1.0 / (near - far);
+        Mat4f::from_rows([
+            [scale / aspect_ratio, 0.0, 0.0, 0.0],
+            [0.0, scale, 0.0, 0.0],
+            [0.0, 0.0, 2.0 * range, 0.0],
+            [0.0, 0.0, (far + near) * range, 1.0],
+        ])
+    }
+    ///
+    /// Create a right handed view matrix looking from `eye` towards `target`.
+    ///
+    /// Kept for compatibility, this is equivalent to [`Mat4f::look_at_rh`].
+    ///
     pub fn look_at(eye: Vec3f, target: Vec3f, up: Vec3f) -> Self {
-        let zaxis = (eye - target).normalize(); // The "forward" vector.
+        Self::look_at_rh(eye, target, up)
+    }
+    ///
+    /// Create a right handed view matrix looking from `eye` towards `target`.
+    ///
+    /// In view space the camera sits at the origin looking down -Z with +Y up and +X
+    /// to the right. Pair it with [`Mat4f::perspective_rh_gl`] or
+    /// [`Mat4f::perspective_rh_zo`].
+    ///
+    /// ```
+    /// use softrender::math::{Mat4f, Vec3f};
+    ///
+    /// let m = Mat4f::look_at_rh(
+    ///     Vec3f::from_parts(0.0, 0.0, 5.0),
+    ///     Vec3f::from_parts(0.0, 0.0, 0.0),
+    ///     Vec3f::from_parts(0.0, 1.0, 0.0),
+    /// );
+    /// ```
+    ///
+    pub fn look_at_rh(eye: Vec3f, target: Vec3f, up: Vec3f) -> Self {
+        Self::look_to_rh(eye, target - eye, up)
+    }
+    ///
+    /// Create a left handed view matrix looking from `eye` towards `target`.
+    ///
+    /// In view space the camera sits at the origin looking down +Z with +Y up and +X
+    /// to the right. Pair it with [`Mat4f::perspective_lh`].
+    ///
+    /// ```
+    /// use softrender::math::{Mat4f, Vec3f};
+    ///
+    /// let m = Mat4f::look_at_lh(
+    ///     Vec3f::from_parts(0.0, 0.0, -5.0),
+    ///     Vec3f::from_parts(0.0, 0.0, 0.0),
+    ///     Vec3f::from_parts(0.0, 1.0, 0.0),
+    /// );
+    /// ```
+    ///
+    pub fn look_at_lh(eye: Vec3f, target: Vec3f, up: Vec3f) -> Self {
+        Self::look_to_lh(eye, target - eye, up)
+    }
+    ///
+    /// Create a right handed view matrix looking from `eye` along `direction`.
+    ///
+    /// Kept symmetrical with [`Mat4f::look_at`], this is equivalent to
+    /// [`Mat4f::look_to_rh`].
+    ///
+    pub fn look_to(eye: Vec3f, direction: Vec3f, up: Vec3f) -> Self {
+        Self::look_to_rh(eye, direction, up)
+    }
+    ///
+    /// Create a right handed view matrix looking from `eye` along `direction`.
+    ///
+    /// ```
+    /// use softrender::math::{Mat4f, Vec3f};
+    ///
+    /// let m = Mat4f::look_to_rh(
+    ///     Vec3f::from_parts(0.0, 0.0, 5.0),
+    ///     Vec3f::from_parts(0.0, 0.0, -1.0),
+    ///     Vec3f::from_parts(0.0, 1.0, 0.0),
+    /// );
+    /// ```
+    ///
+    pub fn look_to_rh(eye: Vec3f, direction: Vec3f, up: Vec3f) -> Self {
+        // The camera looks down -Z, so the view space Z axis points backwards.
+        Self::view_from_basis(eye, -direction.normalize(), up)
+    }
+    ///
+    /// Create a left handed view matrix looking from `eye` along `direction`.
+    ///
+    /// ```
+    /// use softrender::math::{Mat4f, Vec3f};
+    ///
+    /// let m = Mat4f::look_to_lh(
+    ///     Vec3f::from_parts(0.0, 0.0, -5.0),
+    ///     Vec3f::from_parts(0.0, 0.0, 1.0),
+    ///     Vec3f::from_parts(0.0, 1.0, 0.0),
+    /// );
+    /// ```
+    ///
+    pub fn look_to_lh(eye: Vec3f, direction: Vec3f, up: Vec3f) -> Self {
+        Self::view_from_basis(eye, direction.normalize(), up)
+    }
+    ///
+    /// Build a view matrix from the view space Z axis and an approximate up vector.
+    ///
+    fn view_from_basis(eye: Vec3f, zaxis: Vec3f, up: Vec3f) -> Self {
         let xaxis = Vec3f::cross(up, zaxis).normalize(); // The "right" vector.
         let yaxis = Vec3f::cross(zaxis, xaxis); // The "up" vector.
 
@@ -350,9 +636,121 @@ impl Mat4f {
             [xaxis.x, yaxis.x, zaxis.x, 0.0],
             [xaxis.y, yaxis.y, zaxis.y, 0.0],
             [xaxis.z, yaxis.z, zaxis.z, 0.0],
-            [-xaxis.dot(eye), -yaxis.dot(eye), -zaxis.dot(eye), 1.0]
+            [-xaxis.dot(eye), -yaxis.dot(eye), -zaxis.dot(eye), 1.0],
         ])
     }
+    ///
+    /// Create an array of rows from a 4x4 Matrix.
+    ///
+    /// ```
+    /// use softrender::math::Mat4f;
+    ///
+    /// let m = Mat4f::identity().to_rows();
+    /// ```
+    ///
+    pub fn to_rows(&self) -> [[f32; 4]; 4] {
+        [
+            [self.c0r0, self.c0r1, self.c0r2, self.c0r3],
+            [self.c1r0, self.c1r1, self.c1r2, self.c1r3],
+            [self.c2r0, self.c2r1, self.c2r2, self.c2r3],
+            [self.c3r0, self.c3r1, self.c3r2, self.c3r3],
+        ]
+    }
+    ///
+    /// Create an array of columns from a 4x4 Matrix.
+    ///
+    /// ```
+    /// use softrender::math::Mat4f;
+    ///
+    /// let m = Mat4f::identity().to_cols();
+    /// ```
+    ///
+    pub fn to_cols(&self) -> [[f32; 4]; 4] {
+        [
+            [self.c0r0, self.c1r0, self.c2r0, self.c3r0],
+            [self.c0r1, self.c1r1, self.c2r1, self.c3r1],
+            [self.c0r2, self.c1r2, self.c2r2, self.c3r2],
+            [self.c0r3, self.c1r3, self.c2r3, self.c3r3],
+        ]
+    }
+    ///
+    /// Get a row of this Matrix as a vector.
+    ///
+    /// ```
+    /// use softrender::math::{Mat4f, Vec4f};
+    ///
+    /// let r = Mat4f::identity().row(0);
+    /// ```
+    ///
+    pub fn row(&self, index: usize) -> Vec4f {
+        Vec4f::from_parts(self[(index, 0)], self[(index, 1)], self[(index, 2)], self[(index, 3)])
+    }
+    ///
+    /// Get a column of this Matrix as a vector.
+    ///
+    /// ```
+    /// use softrender::math::{Mat4f, Vec4f};
+    ///
+    /// let c = Mat4f::identity().col(0);
+    /// ```
+    ///
+    pub fn col(&self, index: usize) -> Vec4f {
+        Vec4f::from_parts(self[(0, index)], self[(1, index)], self[(2, index)], self[(3, index)])
+    }
+    ///
+    /// Replace a row of this Matrix.
+    ///
+    pub fn set_row(&mut self, index: usize, row: Vec4f) {
+        for (col, value) in row.to_array().iter().enumerate() {
+            self[(index, col)] = *value;
+        }
+    }
+    ///
+    /// Replace a column of this Matrix.
+    ///
+    pub fn set_col(&mut self, index: usize, col: Vec4f) {
+        for (row, value) in col.to_array().iter().enumerate() {
+            self[(row, index)] = *value;
+        }
+    }
+    ///
+    /// Extract the upper 3x3 of this Matrix, dropping translation and projection.
+    ///
+    /// ```
+    /// use softrender::math::Mat4f;
+    ///
+    /// let m = Mat4f::identity().to_mat3();
+    /// ```
+    ///
+    pub fn to_mat3(&self) -> Mat3f {
+        Mat3f {
+            c0r0: self.c0r0,
+            c0r1: self.c0r1,
+            c0r2: self.c0r2,
+            c1r0: self.c1r0,
+            c1r1: self.c1r1,
+            c1r2: self.c1r2,
+            c2r0: self.c2r0,
+            c2r1: self.c2r1,
+            c2r2: self.c2r2,
+        }
+    }
+    ///
+    /// Calculate the matrix used to transform normals by this Matrix.
+    ///
+    /// This is the inverse-transpose of the upper 3x3, which keeps normals perpendicular
+    /// to their surfaces under non-uniform scale and shear. Returns `None` if the upper
+    /// 3x3 is singular.
+    ///
+    /// ```
+    /// use softrender::math::Mat4f;
+    ///
+    /// let n = Mat4f::identity().normal_matrix().unwrap();
+    /// ```
+    ///
+    pub fn normal_matrix(&self) -> Option<Mat3f> {
+        self.to_mat3().normal_matrix()
+    }
 }
 
 impl fmt::Debug for Mat4f {
@@ -402,6 +800,38 @@ impl ops::AddAssign<Self> for Mat4f {
     }
 }
 
+impl ops::Div<f32> for Mat4f {
+    type Output = Self;
+
+    /// Divide every element by a scalar.
+    fn div(self, rhs: f32) -> Self {
+        Self {
+            c0r0: self.c0r0 / rhs,
+            c0r1: self.c0r1 / rhs,
+            c0r2: self.c0r2 / rhs,
+            c0r3: self.c0r3 / rhs,
+            c1r0: self.c1r0 / rhs,
+            c1r1: self.c1r1 / rhs,
+            c1r2: self.c1r2 / rhs,
+            c1r3: self.c1r3 / rhs,
+            c2r0: self.c2r0 / rhs,
+            c2r1: self.c2r1 / rhs,
+            c2r2: self.c2r2 / rhs,
+            c2r3: self.c2r3 / rhs,
+            c3r0: self.c3r0 / rhs,
+            c3r1: self.c3r1 / rhs,
+            c3r2: self.c3r2 / rhs,
+            c3r3: self.c3r3 / rhs,
+        }
+    }
+}
+
+impl ops::DivAssign<f32> for Mat4f {
+    fn div_assign(&mut self, rhs: f32) {
+        *self = *self / rhs;
+    }
+}
+
 impl ops::Mul<Self> for Mat4f {
     type Output = Self;
 
@@ -457,6 +887,73 @@ impl ops::Mul<Vec4f> for Mat4f {
 }
 
 
+impl ops::Mul<f32> for Mat4f {
+    type Output = Self;
+
+    /// Multiply every element by a scalar.
+    fn mul(self, rhs: f32) -> Self {
+        Self {
+            c0r0: self.c0r0 * rhs,
+            c0r1: self.c0r1 * rhs,
+            c0r2: self.c0r2 * rhs,
+            c0r3: self.c0r3 * rhs,
+            c1r0: self.c1r0 * rhs,
+            c1r1: self.c1r1 * rhs,
+            c1r2: self.c1r2 * rhs,
+            c1r3: self.c1r3 * rhs,
+            c2r0: self.c2r0 * rhs,
+            c2r1: self.c2r1 * rhs,
+            c2r2: self.c2r2 * rhs,
+            c2r3: self.c2r3 * rhs,
+            c3r0: self.c3r0 * rhs,
+            c3r1: self.c3r1 * rhs,
+            c3r2: self.c3r2 * rhs,
+            c3r3: self.c3r3 * rhs,
+        }
+    }
+}
+
+impl ops::MulAssign<f32> for Mat4f {
+    fn mul_assign(&mut self, rhs: f32) {
+        *self = *self * rhs;
+    }
+}
+
+impl ops::Mul<Mat4f> for f32 {
+    type Output = Mat4f;
+
+    /// Multiply every element by a scalar.
+    fn mul(self, rhs: Mat4f) -> Mat4f {
+        rhs * self
+    }
+}
+
+impl ops::Neg for Mat4f {
+    type Output = Self;
+
+    /// Negate every element.
+    fn neg(self) -> Self {
+        Self {
+            c0r0: -self.c0r0,
+            c0r1: -self.c0r1,
+            c0r2: -self.c0r2,
+            c0r3: -self.c0r3,
+            c1r0: -self.c1r0,
+            c1r1: -self.c1r1,
+            c1r2: -self.c1r2,
+            c1r3: -self.c1r3,
+            c2r0: -self.c2r0,
+            c2r1: -self.c2r1,
+            c2r2: -self.c2r2,
+            c2r3: -self.c2r3,
+            c3r0: -self.c3r0,
+            c3r1: -self.c3r1,
+            c3r2: -self.c3r2,
+            c3r3: -self.c3r3,
+        }
+    }
+}
+
 impl ops::Sub<Self> for Mat4f {
     type Output = Self;
 
@@ -495,11 +992,75 @@ impl ops::SubAssign<Self> for Mat4f {
     }
 }
 
+impl ops::Index<(usize, usize)> for Mat4f {
+    type Output = f32;
+
+    /// Get the element at (row, column).
+    fn index(&self, index: (usize, usize)) -> &f32 {
+        match index {
+            (0, 0) => &self.c0r0,
+            (0, 1) => &self.c0r1,
+            (0, 2) => &self.c0r2,
+            (0, 3) => &self.c0r3,
+            (1, 0) => &self.c1r0,
+            (1, 1) => &self.c1r1,
+            (1, 2) => &self.c1r2,
+            (1, 3) => &self.c1r3,
+            (2, 0) => &self.c2r0,
+            (2, 1) => &self.c2r1,
+            (2, 2) => &self.c2r2,
+            (2, 3) => &self.c2r3,
+            (3, 0) => &self.c3r0,
+            (3, 1) => &self.c3r1,
+            (3, 2) => &self.c3r2,
+            (3, 3) => &self.c3r3,
+            (row, col) => panic!("Mat4f index ({}, {}) out of range", row, col),
+        }
+    }
+}
+
+impl ops::IndexMut<(usize, usize)> for Mat4f {
+    /// Get a mutable reference to the element at (row, column).
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut f32 {
+        match index {
+            (0, 0) => &mut self.c0r0,
+            (0, 1) => &mut self.c0r1,
+            (0, 2) => &mut self.c0r2,
+            (0, 3) => &mut self.c0r3,
+            (1, 0) => &mut self.c1r0,
+            (1, 1) => &mut self.c1r1,
+            (1, 2) => &mut self.c1r2,
+            (1, 3) => &mut self.c1r3,
+            (2, 0) => &mut self.c2r0,
+            (2, 1) => &mut self.c2r1,
+            (2, 2) => &mut self.c2r2,
+            (2, 3) => &mut self.c2r3,
+            (3, 0) => &mut self.c3r0,
+            (3, 1) => &mut self.c3r1,
+            (3, 2) => &mut self.c3r2,
+            (3, 3) => &mut self.c3r3,
+            (row, col) => panic!("Mat4f index ({}, {}) out of range", row, col),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Mat4f, Vec3f, Vec4f};
+    use super::{Mat3f, Mat4f, Vec3f, Vec4f};
     use assert_approx_eq::assert_approx_eq;
 
+    #[test]
+    fn test_constants() {
+        const TRANSFORMS: [Mat4f; 2] = [Mat4f::IDENTITY, Mat4f::ZERO];
+        static AXES: [Vec3f; 3] = [Vec3f::UNIT_X, Vec3f::UNIT_Y, Vec3f::UNIT_Z];
+        assert_eq!(TRANSFORMS[0], Mat4f::identity());
+        assert_eq!(TRANSFORMS[1], Mat4f::zero());
+        assert_eq!(Mat3f::IDENTITY, Mat3f::identity());
+        assert_eq!(AXES[2], Vec3f::new(0.0, 0.0, 1.0));
+        assert_eq!(Vec3f::UNIT_Y * TRANSFORMS[0], Vec3f::UNIT_Y);
+        assert_eq!(Vec3f::ZERO + Vec3f::ONE, Vec3f::from_parts(1.0, 1.0, 1.0));
+    }
+
     #[test]
     fn test_from_rows() {
         let m = Mat4f::from_rows(
@@ -589,38 +1150,168 @@ mod tests {
                 [00.1, 00.0, 00.5, -0.4],
             ]
         );
-        assert_eq!(a.invert().unwrap(), b);
+        assert_mat4f_approx_eq(a.invert().unwrap(), b);
+    }
+
+    #[test]
+    fn test_invert_affine() {
+        let a = Mat4f::from_rows(
+            [
+                [2.0, 0.0, 0.0, 0.0],
+                [0.0, 0.0, 3.0, 0.0],
+                [0.0, -4.0, 0.0, 0.0],
+                [5.0, 6.0, 7.0, 1.0],
+            ]
+        );
+        let b = a.invert_affine().unwrap();
+        assert_mat4f_approx_eq(b, a.invert().unwrap());
+        assert_mat4f_approx_eq(a * b, Mat4f::identity());
+        assert!(Mat4f::zero().invert_affine().is_none());
+    }
+
+    #[test]
+    fn test_invert_rigid() {
+        let eye = Vec3f::from_parts(1.0, 2.0, 3.0);
+        let target = Vec3f::from_parts(4.0, -1.0, 0.5);
+        let up = Vec3f::from_parts(0.0, 1.0, 0.0);
+        let a = Mat4f::look_at_rh(eye, target, up);
+        let b = a.invert_rigid();
+        assert_mat4f_approx_eq(b, a.invert().unwrap());
+        assert_mat4f_approx_eq(b, a.invert_affine().unwrap());
+        assert_vec3f_approx_eq(Vec3f::default() * b, eye);
+    }
+
+    fn assert_vec3f_approx_eq(a: Vec3f, b: Vec3f) {
+        assert_approx_eq!(a.x, b.x);
+        assert_approx_eq!(a.y, b.y);
+        assert_approx_eq!(a.z, b.z);
     }
 
     #[test]
     fn test_lookat() {
-        let eye = Vec3f::from_parts(0.0, 0.0, 0.0);
-        let target = Vec3f::from_parts(1.0, 1.0, 1.0);
-        let up = Vec3f::from_parts(0.0, 0.0, 1.0);
-        let a = Mat4f::look_at(eye, target, up);
+        let eye = Vec3f::from_parts(1.0, 2.0, 3.0);
+        let target = Vec3f::from_parts(4.0, -1.0, 0.5);
+        let up = Vec3f::from_parts(0.0, 1.0, 0.0);
+        assert_mat4f_approx_eq(Mat4f::look_at(eye, target, up), Mat4f::look_at_rh(eye, target, up));
+    }
+
+    #[test]
+    fn test_lookat_rh() {
+        let eye = Vec3f::from_parts(0.0, 0.0, 5.0);
+        let up = Vec3f::from_parts(0.0, 1.0, 0.0);
+        let a = Mat4f::look_at_rh(eye, Vec3f::default(), up);
         let b = Mat4f::from_rows([
-            [-6.0, 01.0, 01.0, 06.0],
-            [-8.0, 05.0, 08.0, 06.0],
-            [-1.0, 00.0, 08.0, 02.0],
-            [-7.0, 01.0, -1.0, 01.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, -5.0, 1.0],
         ]);
-        assert_eq!(a, b)
+        assert_mat4f_approx_eq(a, b);
+        assert_vec3f_approx_eq(Vec3f::default() * a, Vec3f::from_parts(0.0, 0.0, -5.0));
+        assert_vec3f_approx_eq(Vec3f::from_parts(1.0, 1.0, 0.0) * a, Vec3f::from_parts(1.0, 1.0, -5.0));
+    }
+
+    #[test]
+    fn test_lookat_lh() {
+        let eye = Vec3f::from_parts(0.0, 0.0, 5.0);
+        let up = Vec3f::from_parts(0.0, 1.0, 0.0);
+        let a = Mat4f::look_at_lh(eye, Vec3f::default(), up);
+        let b = Mat4f::from_rows([
+            [-1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, -1.0, 0.0],
+            [0.0, 0.0, 5.0, 1.0],
+        ]);
+        assert_mat4f_approx_eq(a, b);
+        assert_vec3f_approx_eq(Vec3f::default() * a, Vec3f::from_parts(0.0, 0.0, 5.0));
+        assert_vec3f_approx_eq(Vec3f::from_parts(1.0, 1.0, 0.0) * a, Vec3f::from_parts(-1.0, 1.0, 5.0));
+    }
+
+    #[test]
+    fn test_lookto() {
+        let eye = Vec3f::from_parts(1.0, 2.0, 3.0);
+        let target = Vec3f::from_parts(4.0, -1.0, 0.5);
+        let up = Vec3f::from_parts(0.0, 1.0, 0.0);
+        assert_mat4f_approx_eq(Mat4f::look_to(eye, target - eye, up), Mat4f::look_at_rh(eye, target, up));
+        assert_mat4f_approx_eq(Mat4f::look_to_rh(eye, target - eye, up), Mat4f::look_at_rh(eye, target, up));
+        assert_mat4f_approx_eq(Mat4f::look_to_lh(eye, target - eye, up), Mat4f::look_at_lh(eye, target, up));
+        // The eye always ends up at the view space origin.
+        assert_vec3f_approx_eq(eye * Mat4f::look_to_rh(eye, target - eye, up), Vec3f::default());
+        assert_vec3f_approx_eq(eye * Mat4f::look_to_lh(eye, target - eye, up), Vec3f::default());
+    }
+
+    fn assert_mat4f_approx_eq(a: Mat4f, b: Mat4f) {
+        let a = [
+            a.c0r0, a.c0r1, a.c0r2, a.c0r3, a.c1r0, a.c1r1, a.c1r2, a.c1r3,
+            a.c2r0, a.c2r1, a.c2r2, a.c2r3, a.c3r0, a.c3r1, a.c3r2, a.c3r3,
+        ];
+        let b = [
+            b.c0r0, b.c0r1, b.c0r2, b.c0r3, b.c1r0, b.c1r1, b.c1r2, b.c1r3,
+            b.c2r0, b.c2r1, b.c2r2, b.c2r3, b.c3r0, b.c3r1, b.c3r2, b.c3r3,
+        ];
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert_approx_eq!(a, b);
+        }
     }
 
     #[test]
     fn test_perspective() {
-        let fov = 90.0;
-        let aspect_ratio = 90.0;
-        let near = 0.0001;
-        let far = 1.0000;
-        let a = Mat4f::perspective(fov, aspect_ratio, near, far);
+        assert_mat4f_approx_eq(
+            Mat4f::perspective(90.0, 2.0, 1.0, 3.0),
+            Mat4f::perspective_lh(90.0, 2.0, 1.0, 3.0),
+        );
+    }
+
+    #[test]
+    fn test_perspective_rh_gl() {
+        let a = Mat4f::perspective_rh_gl(90.0, 2.0, 1.0, 3.0);
         let b = Mat4f::from_rows([
-            [01.810660, 00.000000, 00.000000, 00.000000],
-            [00.000000, 02.414213, 00.000000, 00.000000],
-            [00.000000, 00.000000, -1.002002, -1.000000],
-            [00.000000, 00.000000, -0.200200, 00.000000],
+            [0.5, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, -2.0, -1.0],
+            [0.0, 0.0, -3.0, 0.0],
         ]);
-        assert_eq!(a, b)
+        assert_mat4f_approx_eq(a, b);
+        assert_approx_eq!((Vec4f::from_parts(0.0, 0.0, -1.0, 1.0) * a).z, -1.0);
+        assert_approx_eq!((Vec4f::from_parts(0.0, 0.0, -3.0, 1.0) * a).z, 1.0);
+    }
+
+    #[test]
+    fn test_perspective_rh_zo() {
+        let a = Mat4f::perspective_rh_zo(90.0, 2.0, 1.0, 3.0);
+        let b = Mat4f::from_rows([
+            [0.5, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, -1.5, -1.0],
+            [0.0, 0.0, -1.5, 0.0],
+        ]);
+        assert_mat4f_approx_eq(a, b);
+        assert_approx_eq!((Vec4f::from_parts(0.0, 0.0, -1.0, 1.0) * a).z, 0.0);
+        assert_approx_eq!((Vec4f::from_parts(0.0, 0.0, -3.0, 1.0) * a).z, 1.0);
+    }
+
+    #[test]
+    fn test_perspective_lh() {
+        let a = Mat4f::perspective_lh(90.0, 2.0, 1.0, 3.0);
+        let b = Mat4f::from_rows([
+            [0.5, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.5, 1.0],
+            [0.0, 0.0, -1.5, 0.0],
+        ]);
+        assert_mat4f_approx_eq(a, b);
+        assert_approx_eq!((Vec4f::from_parts(0.0, 0.0, 1.0, 1.0) * a).z, 0.0);
+        assert_approx_eq!((Vec4f::from_parts(0.0, 0.0, 3.0, 1.0) * a).z, 1.0);
+    }
+
+    #[test]
+    fn test_perspective_aspect_ratio() {
+        // A point on the edge of a 90 degree frustum must land on the edge of NDC space
+        // for non-square viewports as well.
+        let a = Mat4f::perspective_rh_gl(90.0, 16.0 / 9.0, 0.1, 100.0);
+        let p = Vec4f::from_parts(16.0 / 9.0, 1.0, -1.0, 1.0) * a;
+        assert_approx_eq!(p.x, 1.0);
+        assert_approx_eq!(p.y, 1.0);
     }
 
     #[test]
@@ -841,4 +1532,159 @@ mod tests {
         );
         assert_eq!(a, c);
     }
+
+    #[test]
+    fn test_index() {
+        let mut m = Mat4f::from_rows(
+            [
+                [1.0, 2.0, 3.0, 4.0],
+                [5.0, 6.0, 7.0, 8.0],
+                [9.0, 10.0, 11.0, 12.0],
+                [13.0, 14.0, 15.0, 16.0],
+            ]
+        );
+        assert_eq!(m[(0, 0)], 1.0);
+        assert_eq!(m[(0, 3)], 4.0);
+        assert_eq!(m[(3, 0)], 13.0);
+        m[(1, 0)] = 42.0;
+        assert_eq!(m.to_rows()[1][0], 42.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_out_of_range() {
+        let _ = Mat4f::identity()[(4, 0)];
+    }
+
+    #[test]
+    fn test_row_col() {
+        let mut m = Mat4f::from_rows(
+            [
+                [1.0, 2.0, 3.0, 4.0],
+                [5.0, 6.0, 7.0, 8.0],
+                [9.0, 10.0, 11.0, 12.0],
+                [13.0, 14.0, 15.0, 16.0],
+            ]
+        );
+        assert_eq!(m.row(1), Vec4f::from_parts(5.0, 6.0, 7.0, 8.0));
+        assert_eq!(m.col(1), Vec4f::from_parts(2.0, 6.0, 10.0, 14.0));
+        m.set_row(0, Vec4f::from_parts(-1.0, -2.0, -3.0, -4.0));
+        assert_eq!(m.to_rows()[0], [-1.0, -2.0, -3.0, -4.0]);
+        m.set_col(3, Vec4f::from_parts(-1.0, -2.0, -3.0, -4.0));
+        assert_eq!(m.to_cols()[3], [-1.0, -2.0, -3.0, -4.0]);
+    }
+
+    #[test]
+    fn test_to_mat3() {
+        let a = Mat4f::from_rows(
+            [
+                [01.0, 02.0, 03.0, 04.0],
+                [05.0, 06.0, 07.0, 08.0],
+                [09.0, 10.0, 11.0, 12.0],
+                [13.0, 14.0, 15.0, 16.0],
+            ]
+        );
+        let b = Mat3f::from_rows(
+            [
+                [01.0, 02.0, 03.0],
+                [05.0, 06.0, 07.0],
+                [09.0, 10.0, 11.0],
+            ]
+        );
+        assert_eq!(a.to_mat3(), b);
+        assert_eq!(b.to_mat4().to_mat3(), b);
+    }
+
+    #[test]
+    fn test_normal_matrix() {
+        // Non-uniform scale with a translation, the translation must not leak in.
+        let a = Mat4f::from_rows(
+            [
+                [2.0, 0.0, 0.0, 0.0],
+                [0.0, 4.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [5.0, 6.0, 7.0, 1.0],
+            ]
+        );
+        let n = a.normal_matrix().unwrap();
+        let b = Mat3f::from_rows(
+            [
+                [0.5, 0.0, 0.0],
+                [0.0, 0.25, 0.0],
+                [0.0, 0.0, 1.0],
+            ]
+        );
+        assert_eq!(n, b);
+        assert!(Mat4f::zero().normal_matrix().is_none());
+    }
+
+    #[test]
+    fn test_scalar_mul() {
+        let a = Mat4f::from_rows(
+            [
+                [1.0, 2.0, 3.0, 4.0],
+                [5.0, 6.0, 7.0, 8.0],
+                [9.0, 10.0, 11.0, 12.0],
+                [13.0, 14.0, 15.0, 16.0],
+            ]
+        );
+        let b = Mat4f::from_rows(
+            [
+                [2.0, 4.0, 6.0, 8.0],
+                [10.0, 12.0, 14.0, 16.0],
+                [18.0, 20.0, 22.0, 24.0],
+                [26.0, 28.0, 30.0, 32.0],
+            ]
+        );
+        assert_eq!(a * 2.0, b);
+        assert_eq!(2.0 * a, b);
+        let mut c = a;
+        c *= 2.0;
+        assert_eq!(c, b);
+    }
+
+    #[test]
+    fn test_scalar_div() {
+        let a = Mat4f::from_rows(
+            [
+                [2.0, 4.0, 6.0, 8.0],
+                [10.0, 12.0, 14.0, 16.0],
+                [18.0, 20.0, 22.0, 24.0],
+                [26.0, 28.0, 30.0, 32.0],
+            ]
+        );
+        let b = Mat4f::from_rows(
+            [
+                [1.0, 2.0, 3.0, 4.0],
+                [5.0, 6.0, 7.0, 8.0],
+                [9.0, 10.0, 11.0, 12.0],
+                [13.0, 14.0, 15.0, 16.0],
+            ]
+        );
+        assert_eq!(a / 2.0, b);
+        let mut c = a;
+        c /= 2.0;
+        assert_eq!(c, b);
+    }
+
+    #[test]
+    fn test_neg() {
+        let a = Mat4f::from_rows(
+            [
+                [1.0, 2.0, 3.0, 4.0],
+                [5.0, 6.0, 7.0, 8.0],
+                [9.0, 10.0, 11.0, 12.0],
+                [13.0, 14.0, 15.0, 16.0],
+            ]
+        );
+        let b = Mat4f::from_rows(
+            [
+                [-1.0, -2.0, -3.0, -4.0],
+                [-5.0, -6.0, -7.0, -8.0],
+                [-9.0, -10.0, -11.0, -12.0],
+                [-13.0, -14.0, -15.0, -16.0],
+            ]
+        );
+        assert_eq!(-a, b);
+    }
 }
@@ -16,16 +16,36 @@
 
 //! Column Major math Library
 
+mod aabb;
+mod clip;
 mod mat2f;
 mod mat3f;
 mod mat4f;
+mod projection;
+mod ray;
+mod sampling;
+mod sequence;
+mod sh;
 mod vec2f;
 mod vec3f;
 mod vec4f;
 
+pub use self::aabb::Aabb;
+pub use self::clip::{ClipPlanes, MAX_CLIP_PLANES};
 pub use self::mat2f::Mat2f;
 pub use self::mat3f::Mat3f;
 pub use self::mat4f::Mat4f;
+pub use self::projection::{project, unproject};
+pub use self::ray::Ray;
+pub use self::sampling::{
+    concentric_disk, cosine_hemisphere, cosine_hemisphere_pdf, ggx_distribution, ggx_half_vector, spherical_direction,
+    uniform_cone, uniform_cone_pdf, uniform_hemisphere, uniform_hemisphere_pdf, uniform_sphere, uniform_sphere_pdf,
+    OrthonormalBasis,
+};
+pub use self::sequence::{halton, owen_scramble, scrambled_halton, scrambled_sobol, sobol};
+pub use self::sh::{equirect_direction, equirect_uv, Sh9};
 pub use self::vec2f::Vec2f;
 pub use self::vec3f::Vec3f;
 pub use self::vec4f::Vec4f;
+
+pub(crate) use self::sequence::hash;
@@ -0,0 +1,102 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Mat4f, Vec3f, Vec4f};
+
+///
+/// Project a world space point into screen space.
+///
+/// `viewport` is `(x, y, width, height)` in pixels. Screen space has its origin in the
+/// top left corner with Y growing downwards, matching `Canvas` rows. The returned `z`
+/// is the normalized device depth produced by the projection in `mvp`, so it is in
+/// `-1..1` for OpenGL style projections and `0..1` for zero to one projections.
+///
+/// ```
+/// use softrender::math::{project, Mat4f, Vec3f, Vec4f};
+///
+/// let mvp = Mat4f::perspective_rh_gl(90.0, 1.0, 0.1, 100.0);
+/// let viewport = Vec4f::from_parts(0.0, 0.0, 640.0, 480.0);
+/// let screen = project(Vec3f::from_parts(0.0, 0.0, -1.0), mvp, viewport);
+/// assert_eq!((screen.x, screen.y), (320.0, 240.0));
+/// ```
+///
+pub fn project(world: Vec3f, mvp: Mat4f, viewport: Vec4f) -> Vec3f {
+    let ndc = world.extend(1.0) * mvp;
+    Vec3f::from_parts(
+        viewport.x + (ndc.x + 1.0) * 0.5 * viewport.z,
+        viewport.y + (1.0 - ndc.y) * 0.5 * viewport.w,
+        ndc.z,
+    )
+}
+
+///
+/// Unproject a screen space point back into world space.
+///
+/// This is the inverse of [`project`]; `inv_mvp` is the inverse of the matrix passed to
+/// it and `screen.z` is the normalized device depth of the point.
+///
+/// ```
+/// use softrender::math::{unproject, Mat4f, Vec3f, Vec4f};
+///
+/// let inv_mvp = Mat4f::perspective_rh_gl(90.0, 1.0, 0.1, 100.0).invert().unwrap();
+/// let viewport = Vec4f::from_parts(0.0, 0.0, 640.0, 480.0);
+/// let near = unproject(Vec3f::from_parts(320.0, 240.0, -1.0), inv_mvp, viewport);
+/// ```
+///
+pub fn unproject(screen: Vec3f, inv_mvp: Mat4f, viewport: Vec4f) -> Vec3f {
+    let ndc = Vec4f::from_parts(
+        (screen.x - viewport.x) / viewport.z * 2.0 - 1.0,
+        1.0 - (screen.y - viewport.y) / viewport.w * 2.0,
+        screen.z,
+        1.0,
+    );
+    (ndc * inv_mvp).truncate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{project, unproject, Mat4f, Vec3f, Vec4f};
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_project() {
+        let mvp = Mat4f::perspective_rh_gl(90.0, 2.0, 1.0, 3.0);
+        let viewport = Vec4f::from_parts(10.0, 20.0, 200.0, 100.0);
+        let center = project(Vec3f::from_parts(0.0, 0.0, -1.0), mvp, viewport);
+        assert_approx_eq!(center.x, 110.0);
+        assert_approx_eq!(center.y, 70.0);
+        assert_approx_eq!(center.z, -1.0);
+        let corner = project(Vec3f::from_parts(2.0, 1.0, -1.0), mvp, viewport);
+        assert_approx_eq!(corner.x, 210.0);
+        assert_approx_eq!(corner.y, 20.0);
+    }
+
+    #[test]
+    fn test_unproject() {
+        let mvp = Mat4f::look_at_rh(
+            Vec3f::from_parts(1.0, 2.0, 3.0),
+            Vec3f::from_parts(0.0, 0.0, 0.0),
+            Vec3f::from_parts(0.0, 1.0, 0.0),
+        ) * Mat4f::perspective_rh_zo(60.0, 4.0 / 3.0, 0.1, 100.0);
+        let viewport = Vec4f::from_parts(0.0, 0.0, 640.0, 480.0);
+        let world = Vec3f::from_parts(0.25, -0.5, 0.75);
+        let screen = project(world, mvp, viewport);
+        let result = unproject(screen, mvp.invert().unwrap(), viewport);
+        assert_approx_eq!(result.x, world.x, 1e-3);
+        assert_approx_eq!(result.y, world.y, 1e-3);
+        assert_approx_eq!(result.z, world.z, 1e-3);
+    }
+}
@@ -0,0 +1,75 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::Vec3f;
+use std::fmt;
+
+///
+/// Half line starting at `origin` heading along `direction`
+///
+#[derive(Copy, Clone, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3f,
+    pub direction: Vec3f,
+    /// Moment within the shutter the ray samples, from 0.0 at the previous frame to 1.0 at
+    /// the current one, deciding where moving geometry is hit
+    pub time: f32,
+}
+
+impl Ray {
+    ///
+    /// Create a new `Ray` at the end of the shutter, seeing the scene as it is now
+    ///
+    pub fn new(origin: Vec3f, direction: Vec3f) -> Self {
+        Self {
+            origin,
+            direction,
+            time: 1.0,
+        }
+    }
+    ///
+    /// This `Ray` sampling the moment `time` within the shutter
+    ///
+    pub fn with_time(self, time: f32) -> Self {
+        Self { time, ..self }
+    }
+    ///
+    /// Point at distance `t` along the `Ray`, measured in multiples of `direction`
+    ///
+    pub fn at(&self, t: f32) -> Vec3f {
+        self.origin + self.direction * t
+    }
+}
+
+impl fmt::Debug for Ray {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Ray({:?} -> {:?})", self.origin, self.direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Ray, Vec3f};
+
+    #[test]
+    fn test_at() {
+        let ray = Ray::new(Vec3f::from_parts(1.0, 2.0, 3.0), Vec3f::from_parts(0.0, 0.0, -2.0));
+        assert_eq!(ray.at(0.0), Vec3f::from_parts(1.0, 2.0, 3.0));
+        assert_eq!(ray.at(1.5), Vec3f::from_parts(1.0, 2.0, 0.0));
+        assert_eq!(ray.time, 1.0);
+        assert_eq!(ray.with_time(0.25).time, 0.25);
+    }
+}
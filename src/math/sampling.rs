@@ -0,0 +1,223 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Turning uniform random numbers in [0, 1) into points and directions.
+//!
+//! Directions are made around the Z axis and brought to a surface with an
+//! `OrthonormalBasis`, so one set of helpers serves every normal.
+
+use super::{Vec2f, Vec3f};
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+///
+/// Three perpendicular unit vectors, with `normal` along the Z axis of the local frame
+///
+/// ```
+/// use softrender::math::{OrthonormalBasis, Vec3f};
+///
+/// let basis = OrthonormalBasis::from_normal(Vec3f::UNIT_Y);
+/// assert_eq!(basis.to_world(Vec3f::UNIT_Z), Vec3f::UNIT_Y);
+/// assert_eq!(basis.to_local(Vec3f::UNIT_Y), Vec3f::UNIT_Z);
+/// ```
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OrthonormalBasis {
+    pub tangent: Vec3f,
+    pub bitangent: Vec3f,
+    pub normal: Vec3f,
+}
+
+impl OrthonormalBasis {
+    /// Create new OrthonormalBasis around the unit `normal`, with arbitrary but stable
+    /// tangents
+    pub fn from_normal(normal: Vec3f) -> OrthonormalBasis {
+        let helper = if normal.x.abs() > 0.9 { Vec3f::UNIT_Y } else { Vec3f::UNIT_X };
+        let tangent = Vec3f::cross(helper, normal).normalize();
+        OrthonormalBasis {
+            tangent,
+            bitangent: Vec3f::cross(normal, tangent),
+            normal,
+        }
+    }
+    /// World direction of the local direction `local`
+    pub fn to_world(&self, local: Vec3f) -> Vec3f {
+        self.tangent * local.x + self.bitangent * local.y + self.normal * local.z
+    }
+    /// Local direction of the world direction `world`
+    pub fn to_local(&self, world: Vec3f) -> Vec3f {
+        Vec3f::new(world.dot(self.tangent), world.dot(self.bitangent), world.dot(self.normal))
+    }
+}
+
+/// Local unit direction at angle `acos(cos_theta)` from the Z axis, turned `phi` radians
+/// around it from the X axis
+pub fn spherical_direction(cos_theta: f32, phi: f32) -> Vec3f {
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    Vec3f::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+/// Point of the unit disk, spread evenly over its area.
+///
+/// Maps squares around the center to rings, keeping neighbouring numbers close, which
+/// suits stratified and low discrepancy samples better than polar mapping.
+pub fn concentric_disk(u1: f32, u2: f32) -> Vec2f {
+    let (x, y) = (2.0 * u1 - 1.0, 2.0 * u2 - 1.0);
+    if x == 0.0 && y == 0.0 {
+        return Vec2f::from_parts(0.0, 0.0);
+    }
+    let (radius, theta) = if x.abs() > y.abs() {
+        (x, FRAC_PI_4 * (y / x))
+    } else {
+        (y, FRAC_PI_2 - FRAC_PI_4 * (x / y))
+    };
+    Vec2f::from_parts(radius * theta.cos(), radius * theta.sin())
+}
+
+/// Local direction on the hemisphere around the Z axis, every direction equally likely
+pub fn uniform_hemisphere(u1: f32, u2: f32) -> Vec3f {
+    spherical_direction(u1, 2.0 * PI * u2)
+}
+
+/// Density of `uniform_hemisphere` per steradian
+pub fn uniform_hemisphere_pdf() -> f32 {
+    1.0 / (2.0 * PI)
+}
+
+/// Direction on the unit sphere, every direction equally likely
+pub fn uniform_sphere(u1: f32, u2: f32) -> Vec3f {
+    spherical_direction(1.0 - 2.0 * u1, 2.0 * PI * u2)
+}
+
+/// Density of `uniform_sphere` per steradian
+pub fn uniform_sphere_pdf() -> f32 {
+    1.0 / (4.0 * PI)
+}
+
+/// Local direction within `acos(cos_max)` of the Z axis, every direction equally likely
+pub fn uniform_cone(cos_max: f32, u1: f32, u2: f32) -> Vec3f {
+    spherical_direction(1.0 - u1 * (1.0 - cos_max), 2.0 * PI * u2)
+}
+
+/// Density of `uniform_cone` per steradian
+pub fn uniform_cone_pdf(cos_max: f32) -> f32 {
+    1.0 / (2.0 * PI * (1.0 - cos_max))
+}
+
+/// Local direction on the hemisphere around the Z axis, with density proportional to the
+/// cosine of its angle from the axis, the distribution of light leaving a Lambertian surface
+pub fn cosine_hemisphere(u1: f32, u2: f32) -> Vec3f {
+    let disk = concentric_disk(u1, u2);
+    let z = (1.0 - disk.x * disk.x - disk.y * disk.y).max(0.0).sqrt();
+    Vec3f::new(disk.x, disk.y, z)
+}
+
+/// Density of `cosine_hemisphere` per steradian for a direction with `cos_theta` to the axis
+pub fn cosine_hemisphere_pdf(cos_theta: f32) -> f32 {
+    cos_theta.max(0.0) / PI
+}
+
+/// GGX distribution of microfacet normals with roughness `alpha`, for a normal with
+/// `cos_theta` to the surface normal, per steradian of microfacet normal
+pub fn ggx_distribution(alpha: f32, cos_theta: f32) -> f32 {
+    if cos_theta <= 0.0 {
+        return 0.0;
+    }
+    let alpha_squared = alpha * alpha;
+    let denominator = cos_theta * cos_theta * (alpha_squared - 1.0) + 1.0;
+    alpha_squared / (PI * denominator * denominator)
+}
+
+/// Local microfacet normal picked from the GGX distribution with roughness `alpha`, with
+/// density `ggx_distribution(alpha, z) * z` per steradian, where `z` is its Z component
+pub fn ggx_half_vector(alpha: f32, u1: f32, u2: f32) -> Vec3f {
+    let tan_squared = alpha * alpha * u1 / (1.0 - u1).max(1e-7);
+    spherical_direction(1.0 / (1.0 + tan_squared).sqrt(), 2.0 * PI * u2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        concentric_disk, cosine_hemisphere, ggx_distribution, ggx_half_vector, uniform_cone, OrthonormalBasis,
+    };
+    use crate::math::Vec3f;
+    use assert_approx_eq::assert_approx_eq;
+    use std::f32::consts::PI;
+
+    /// Grid of `n` by `n` stratified numbers
+    fn grid(n: usize) -> impl Iterator<Item = (f32, f32)> {
+        (0..n * n).map(move |index| (((index % n) as f32 + 0.5) / n as f32, ((index / n) as f32 + 0.5) / n as f32))
+    }
+
+    #[test]
+    fn test_basis() {
+        let normal = Vec3f::new(1.0, 2.0, -0.5).normalize();
+        let basis = OrthonormalBasis::from_normal(normal);
+        assert_approx_eq!(basis.tangent.magnitude(), 1.0);
+        assert_approx_eq!(basis.bitangent.magnitude(), 1.0);
+        assert_approx_eq!(basis.tangent.dot(normal), 0.0);
+        assert_approx_eq!(basis.bitangent.dot(normal), 0.0);
+        assert_approx_eq!(basis.tangent.dot(basis.bitangent), 0.0);
+        let local = basis.to_local(Vec3f::new(0.3, -0.2, 0.9));
+        let world = basis.to_world(local);
+        assert_approx_eq!(world.x, 0.3);
+        assert_approx_eq!(world.y, -0.2);
+        assert_approx_eq!(world.z, 0.9);
+    }
+
+    #[test]
+    fn test_concentric_disk() {
+        assert_eq!(concentric_disk(0.5, 0.5).x, 0.0);
+        let edge = concentric_disk(1.0, 0.5);
+        assert_approx_eq!(edge.x, 1.0);
+        assert_approx_eq!(edge.y, 0.0);
+        // Even spread puts a quarter of the points within half the radius
+        let inside = grid(64).filter(|&(u1, u2)| {
+            let point = concentric_disk(u1, u2);
+            point.x * point.x + point.y * point.y < 0.25
+        });
+        assert_approx_eq!(inside.count() as f32 / 4096.0, 0.25, 0.01);
+    }
+
+    #[test]
+    fn test_cosine_hemisphere() {
+        // Mean cosine of cosine weighted directions is 2/3
+        let total: f32 = grid(64).map(|(u1, u2)| cosine_hemisphere(u1, u2).z).sum();
+        assert_approx_eq!(total / 4096.0, 2.0 / 3.0, 2e-3);
+        assert!(grid(16).all(|(u1, u2)| (cosine_hemisphere(u1, u2).magnitude() - 1.0).abs() < 1e-5));
+    }
+
+    #[test]
+    fn test_cone() {
+        assert!(grid(16).all(|(u1, u2)| uniform_cone(0.9, u1, u2).z >= 0.9 - 1e-6));
+    }
+
+    #[test]
+    fn test_ggx() {
+        // Projected microfacet area covers the surface exactly
+        let alpha = 0.3;
+        let count = 200_000;
+        let mut total = 0.0;
+        for index in 0..count {
+            let cos_theta = (index as f32 + 0.5) / count as f32;
+            total += ggx_distribution(alpha, cos_theta) * cos_theta * 2.0 * PI / count as f32;
+        }
+        assert_approx_eq!(total, 1.0, 1e-2);
+        // Rougher surfaces tilt their microfacets further
+        let tilt = |alpha| grid(32).map(|(u1, u2)| ggx_half_vector(alpha, u1, u2).z).sum::<f32>();
+        assert!(tilt(0.1) > tilt(0.5));
+        assert_approx_eq!(ggx_half_vector(0.5, 0.0, 0.3).z, 1.0);
+    }
+}
@@ -0,0 +1,208 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+///
+/// Radical inverse of `index` in `base`, the `index`th point of the Halton sequence for
+/// that base, in 0 to 1.
+///
+/// Successive points fill the interval evenly, and pairs with different prime bases fill
+/// the unit square, which makes them good sub-pixel sample positions.
+///
+/// ```
+/// use softrender::math::halton;
+///
+/// assert_eq!(halton(1, 2), 0.5);
+/// assert_eq!(halton(2, 3), 2.0 / 3.0);
+/// ```
+///
+pub fn halton(mut index: u32, base: u32) -> f32 {
+    let (mut result, mut fraction) = (0.0, 1.0);
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+/// Largest f32 below 1
+const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
+
+///
+/// Halton point `index` in `base`, like `halton` but with every digit shifted by an amount
+/// picked from `seed`.
+///
+/// The points fill the interval just as evenly, but different seeds give unrelated sets,
+/// which breaks up the patterns neighbouring Pixels would otherwise share.
+///
+/// ```
+/// use softrender::math::scrambled_halton;
+///
+/// let mut points: Vec<f32> = (0..4).map(|index| scrambled_halton(index, 2, 7)).collect();
+/// points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+/// // One point in each quarter
+/// assert!(points.iter().enumerate().all(|(i, &p)| (p * 4.0) as usize == i));
+/// ```
+///
+pub fn scrambled_halton(mut index: u32, base: u32, seed: u32) -> f32 {
+    let (mut result, mut fraction) = (0.0, 1.0);
+    let mut digit = 0;
+    // Digits past the last of `index` are shifted too, down to the precision of a f32
+    while fraction > 1e-7 {
+        fraction /= base as f32;
+        let shift = hash(seed ^ hash(digit)) % base;
+        result += fraction * ((index % base + shift) % base) as f32;
+        index /= base;
+        digit += 1;
+    }
+    result.min(ONE_MINUS_EPSILON)
+}
+
+///
+/// Bits of dimension `dimension`, 0 or 1, of point `index` of the Sobol sequence.
+///
+/// Only the first two dimensions are provided, which together fill the unit square
+/// evenly for every power of two points. Samplers needing more pad them with
+/// independently scrambled copies.
+///
+fn sobol_bits(index: u32, dimension: u32) -> u32 {
+    match dimension {
+        0 => index.reverse_bits(),
+        1 => {
+            let (mut bits, mut direction) = (0, 1 << 31);
+            for bit in 0..32 {
+                if index >> bit & 1 == 1 {
+                    bits ^= direction;
+                }
+                direction ^= direction >> 1;
+            }
+            bits
+        }
+        _ => panic!("Sobol dimension {} out of range", dimension),
+    }
+}
+
+///
+/// Dimension `dimension`, 0 or 1, of point `index` of the Sobol sequence, in 0 to 1.
+///
+/// ```
+/// use softrender::math::sobol;
+///
+/// assert_eq!(sobol(1, 0), 0.5);
+/// assert_eq!(sobol(2, 1), 0.75);
+/// ```
+///
+pub fn sobol(index: u32, dimension: u32) -> f32 {
+    to_unit(sobol_bits(index, dimension))
+}
+
+///
+/// Sobol point like `sobol`, with Owen scrambling by `seed`.
+///
+/// Scrambling keeps the points as evenly spread as the plain sequence while making
+/// different seeds independent, and removes the structured artifacts of the plain one.
+///
+pub fn scrambled_sobol(index: u32, dimension: u32, seed: u32) -> f32 {
+    to_unit(owen_scramble(sobol_bits(index, dimension), seed))
+}
+
+///
+/// Owen scramble of the binary fraction `bits`, randomly flipping each bit by `seed` and
+/// the bits above it.
+///
+/// This permutes the fraction while keeping its dyadic intervals together, so it can also
+/// shuffle sample indices without losing the stratification of the first `2^k` of them.
+///
+pub fn owen_scramble(bits: u32, seed: u32) -> u32 {
+    // Laine and Karras permutation, which works from the low bits up
+    let mut x = bits.reverse_bits().wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x.reverse_bits()
+}
+
+/// Binary fraction `bits` as a number in [0, 1)
+fn to_unit(bits: u32) -> f32 {
+    // Top 24 bits, which a f32 holds exactly
+    (bits >> 8) as f32 / 16_777_216.0
+}
+
+/// Integer hash spreading nearby seeds over the whole range
+pub(crate) fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^ (x >> 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{owen_scramble, scrambled_halton, scrambled_sobol, sobol};
+
+    /// Check that `points` put exactly one point in each of `points.len()` equal intervals
+    fn stratified(points: &[f32]) -> bool {
+        let mut cells = vec![false; points.len()];
+        for &point in points {
+            let cell = (point * points.len() as f32) as usize;
+            if cell >= cells.len() || cells[cell] {
+                return false;
+            }
+            cells[cell] = true;
+        }
+        true
+    }
+
+    #[test]
+    fn test_sobol() {
+        let first: Vec<(f32, f32)> = (0..4).map(|index| (sobol(index, 0), sobol(index, 1))).collect();
+        assert_eq!(first, vec![(0.0, 0.0), (0.5, 0.5), (0.25, 0.75), (0.75, 0.25)]);
+        for seed in 0..8 {
+            for dimension in 0..2 {
+                let points: Vec<f32> = (0..64).map(|index| scrambled_sobol(index, dimension, seed)).collect();
+                assert!(stratified(&points));
+            }
+        }
+        // Both dimensions together put one of 16 points in each 4 by 4 cell
+        let mut cells = [false; 16];
+        for index in 0..16 {
+            let (x, y) = (scrambled_sobol(index, 0, 3), scrambled_sobol(index, 1, 5));
+            cells[(y * 4.0) as usize * 4 + (x * 4.0) as usize] = true;
+        }
+        assert!(cells.iter().all(|&cell| cell));
+    }
+
+    #[test]
+    fn test_owen_scramble() {
+        // Shuffling the first 2^k indices keeps them the first 2^k indices
+        let mut shuffled: Vec<u32> = (0..16).map(|index| owen_scramble(index, 99) % 16).collect();
+        shuffled.sort_unstable();
+        assert_eq!(shuffled, (0..16).collect::<Vec<u32>>());
+        assert_ne!(owen_scramble(5, 1), owen_scramble(5, 2));
+    }
+
+    #[test]
+    fn test_scrambled_halton() {
+        for seed in 0..8 {
+            let points: Vec<f32> = (0..27).map(|index| scrambled_halton(index, 3, seed)).collect();
+            assert!(stratified(&points));
+        }
+        assert_ne!(scrambled_halton(1, 2, 0), scrambled_halton(1, 2, 1));
+    }
+}
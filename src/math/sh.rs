@@ -0,0 +1,193 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Spherical harmonics and equirectangular mapping for environment lighting.
+
+use super::{Vec2f, Vec3f};
+use std::f32::consts::PI;
+use std::ops;
+
+/// Direction through position (u, v) of an equirectangular map, both from 0 to 1.
+///
+/// `v` runs from +Y at the top to -Y at the bottom, `u` runs around the horizon starting
+/// and ending at -Z and passing +X at a quarter.
+pub fn equirect_direction(uv: Vec2f) -> Vec3f {
+    let phi = 2.0 * PI * uv.x;
+    let theta = PI * uv.y;
+    Vec3f::from_parts(theta.sin() * phi.sin(), theta.cos(), -theta.sin() * phi.cos())
+}
+
+/// Position in an equirectangular map of the unit vector `direction`, the inverse of
+/// `equirect_direction`
+pub fn equirect_uv(direction: Vec3f) -> Vec2f {
+    let phi = direction.x.atan2(-direction.z);
+    let u = phi / (2.0 * PI);
+    Vec2f::from_parts(if u < 0.0 { u + 1.0 } else { u }, direction.y.clamp(-1.0, 1.0).acos() / PI)
+}
+
+///
+/// First nine real spherical harmonics coefficients (bands 0 to 2) of a function over
+/// the sphere, with RGB values stored as `Vec3f`s.
+///
+/// Nine coefficients capture the diffuse lighting of an environment with an average
+/// error of a few percent, so `irradiance` is a cheap replacement for integrating the
+/// whole environment per normal.
+///
+/// ```
+/// use softrender::math::{Sh9, Vec3f};
+///
+/// // Uniform grey sky
+/// let sh = Sh9::project_equirect(64, 32, |_, _| Vec3f::from_parts(0.5, 0.5, 0.5));
+/// let irradiance = sh.irradiance(Vec3f::from_parts(0.0, 1.0, 0.0));
+/// assert!((irradiance.x - 0.5 * std::f32::consts::PI).abs() < 1e-2);
+/// ```
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Sh9 {
+    pub coefficients: [Vec3f; 9],
+}
+
+impl Sh9 {
+    /// Create new Sh9 of a function that is zero everywhere
+    pub fn zero() -> Sh9 {
+        Sh9::default()
+    }
+    /// Values of the nine basis functions in the unit vector `direction`
+    pub fn basis(direction: Vec3f) -> [f32; 9] {
+        let (x, y, z) = (direction.x, direction.y, direction.z);
+        [
+            0.282_095,
+            0.488_603 * y,
+            0.488_603 * z,
+            0.488_603 * x,
+            1.092_548 * x * y,
+            1.092_548 * y * z,
+            0.315_392 * (3.0 * z * z - 1.0),
+            1.092_548 * x * z,
+            0.546_274 * (x * x - y * y),
+        ]
+    }
+    /// Add `value` arriving from the unit vector `direction`, covering `weight` steradians
+    pub fn add_sample(&mut self, direction: Vec3f, value: Vec3f, weight: f32) {
+        for (coefficient, basis) in self.coefficients.iter_mut().zip(Sh9::basis(direction).iter()) {
+            *coefficient += value * (basis * weight);
+        }
+    }
+    /// Project an equirectangular environment map of `width` by `height` texels, reading
+    /// texel (x, y) with `texel`.
+    ///
+    /// Each texel is weighted by the solid angle it covers, which shrinks towards the poles.
+    pub fn project_equirect<F: Fn(usize, usize) -> Vec3f>(width: usize, height: usize, texel: F) -> Sh9 {
+        let mut sh = Sh9::zero();
+        let texel_area = (2.0 * PI / width as f32) * (PI / height as f32);
+        for y in 0..height {
+            let v = (y as f32 + 0.5) / height as f32;
+            let weight = texel_area * (PI * v).sin();
+            for x in 0..width {
+                let u = (x as f32 + 0.5) / width as f32;
+                let direction = equirect_direction(Vec2f::from_parts(u, v));
+                sh.add_sample(direction, texel(x, y), weight);
+            }
+        }
+        sh
+    }
+    /// Value of the projected function in the unit vector `direction`
+    pub fn evaluate(&self, direction: Vec3f) -> Vec3f {
+        self.weighted(direction, [1.0; 3])
+    }
+    /// Light arriving at a surface facing the unit vector `normal` when this is the
+    /// radiance of the environment, following Ramamoorthi and Hanrahan's "An Efficient
+    /// Representation for Irradiance Environment Maps".
+    ///
+    /// A Lambertian surface reflects `albedo * irradiance / PI`.
+    pub fn irradiance(&self, normal: Vec3f) -> Vec3f {
+        // Clamped cosine lobe convolved with each band
+        self.weighted(normal, [PI, 2.0 * PI / 3.0, PI / 4.0])
+    }
+    /// Sum of the coefficients times the basis in `direction`, scaled per band by `bands`
+    fn weighted(&self, direction: Vec3f, bands: [f32; 3]) -> Vec3f {
+        let basis = Sh9::basis(direction);
+        let band = |index: usize| match index {
+            0 => bands[0],
+            1..=3 => bands[1],
+            _ => bands[2],
+        };
+        let mut sum = Vec3f::from_parts(0.0, 0.0, 0.0);
+        for (index, coefficient) in self.coefficients.iter().enumerate() {
+            sum += *coefficient * (basis[index] * band(index));
+        }
+        sum
+    }
+}
+
+impl ops::Add<Self> for Sh9 {
+    type Output = Self;
+
+    fn add(mut self, rhs: Self) -> Self {
+        for (coefficient, other) in self.coefficients.iter_mut().zip(rhs.coefficients.iter()) {
+            *coefficient += *other;
+        }
+        self
+    }
+}
+
+impl ops::Mul<f32> for Sh9 {
+    type Output = Self;
+
+    fn mul(mut self, rhs: f32) -> Self {
+        for coefficient in &mut self.coefficients {
+            *coefficient *= rhs;
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{equirect_direction, equirect_uv, Sh9};
+    use crate::math::{Vec2f, Vec3f};
+    use assert_approx_eq::assert_approx_eq;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_equirect() {
+        let up = equirect_direction(Vec2f::from_parts(0.3, 0.0));
+        assert_approx_eq!(up.y, 1.0);
+        let east = equirect_direction(Vec2f::from_parts(0.25, 0.5));
+        assert_approx_eq!(east.x, 1.0);
+        let uv = equirect_uv(equirect_direction(Vec2f::from_parts(0.7, 0.4)));
+        assert_approx_eq!(uv.x, 0.7, 1e-5);
+        assert_approx_eq!(uv.y, 0.4, 1e-5);
+    }
+
+    #[test]
+    fn test_irradiance() {
+        // Bright sky over a black ground
+        let sh = Sh9::project_equirect(128, 64, |_, y| {
+            if y < 32 { Vec3f::from_parts(1.0, 1.0, 1.0) } else { Vec3f::from_parts(0.0, 0.0, 0.0) }
+        });
+        let up = sh.irradiance(Vec3f::from_parts(0.0, 1.0, 0.0));
+        let side = sh.irradiance(Vec3f::from_parts(1.0, 0.0, 0.0));
+        let down = sh.irradiance(Vec3f::from_parts(0.0, -1.0, 0.0));
+        // Exact values are PI, PI / 2 and 0
+        assert_approx_eq!(up.x, PI, 0.1 * PI);
+        assert_approx_eq!(side.x, PI / 2.0, 1e-2);
+        assert_approx_eq!(down.x, 0.0, 0.1 * PI);
+        assert!(sh.evaluate(Vec3f::from_parts(0.0, 1.0, 0.0)).x > sh.evaluate(Vec3f::from_parts(0.0, -1.0, 0.0)).x);
+        let doubled = sh + sh * 0.5;
+        assert_approx_eq!(doubled.irradiance(Vec3f::from_parts(1.0, 0.0, 0.0)).x, 1.5 * side.x, 1e-4);
+    }
+}
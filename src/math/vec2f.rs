@@ -14,7 +14,8 @@
 // limitations under the License.
 //
 
-use std::{fmt, ops};
+use std::{fmt, iter, ops};
+use super::Vec3f;
 
 ///
 /// 2 Dimensional Vector
@@ -26,16 +27,24 @@ pub struct Vec2f {
 }
 
 impl Vec2f {
+    /// Vector with every component 0.0
+    pub const ZERO: Vec2f = Vec2f::from_parts(0.0, 0.0);
+    /// Vector with every component 1.0
+    pub const ONE: Vec2f = Vec2f::from_parts(1.0, 1.0);
+    /// Unit vector along the X axis
+    pub const UNIT_X: Vec2f = Vec2f::from_parts(1.0, 0.0);
+    /// Unit vector along the Y axis
+    pub const UNIT_Y: Vec2f = Vec2f::from_parts(0.0, 1.0);
     ///
     /// Create a new `Vec2f` from parts.
     ///
-    pub fn from_parts(x: f32, y: f32) -> Self {
+    pub const fn from_parts(x: f32, y: f32) -> Self {
         Self { x, y }
     }
     ///
     /// Create a new `Vec2f` from array.
     ///
-    pub fn from_array(data: [f32; 2]) -> Self {
+    pub const fn from_array(data: [f32; 2]) -> Self {
         Self { x: data[0], y: data[1] }
     }
     ///
@@ -76,6 +85,12 @@ impl Vec2f {
     pub fn magnitude(&self) -> f32 {
         ((self.x * self.x) + (self.y * self.y)).sqrt()
     }
+    ///
+    /// Extend into a `Vec3f` with the given `z` component
+    ///
+    pub fn extend(&self, z: f32) -> Vec3f {
+        Vec3f::from_parts(self.x, self.y, z)
+    }
 }
 
 impl Default for Vec2f {
@@ -90,6 +105,24 @@ impl fmt::Debug for Vec2f {
     }
 }
 
+impl ops::Add<f32> for Vec2f {
+    type Output = Self;
+
+    fn add(self, rhs: f32) -> Self {
+        Self {
+            x: self.x + rhs,
+            y: self.y + rhs,
+        }
+    }
+}
+
+impl ops::AddAssign<f32> for Vec2f {
+    fn add_assign(&mut self, rhs: f32) {
+        self.x += rhs;
+        self.y += rhs;
+    }
+}
+
 impl ops::Add<Self> for Vec2f {
     type Output = Self;
 
@@ -180,6 +213,14 @@ impl ops::MulAssign<Self> for Vec2f {
     }
 }
 
+impl ops::Mul<Vec2f> for f32 {
+    type Output = Vec2f;
+
+    fn mul(self, rhs: Vec2f) -> Vec2f {
+        rhs * self
+    }
+}
+
 impl ops::Neg for Vec2f {
     type Output = Self;
 
@@ -191,6 +232,42 @@ impl ops::Neg for Vec2f {
     }
 }
 
+impl ops::Rem<f32> for Vec2f {
+    type Output = Self;
+
+    fn rem(self, rhs: f32) -> Self {
+        Self {
+            x: self.x % rhs,
+            y: self.y % rhs,
+        }
+    }
+}
+
+impl ops::RemAssign<f32> for Vec2f {
+    fn rem_assign(&mut self, rhs: f32) {
+        self.x %= rhs;
+        self.y %= rhs;
+    }
+}
+
+impl ops::Rem<Self> for Vec2f {
+    type Output = Self;
+
+    fn rem(self, rhs: Self) -> Self {
+        Self {
+            x: self.x % rhs.x,
+            y: self.y % rhs.y,
+        }
+    }
+}
+
+impl ops::RemAssign<Self> for Vec2f {
+    fn rem_assign(&mut self, rhs: Self) {
+        self.x %= rhs.x;
+        self.y %= rhs.y;
+    }
+}
+
 impl ops::Sub<f32> for Vec2f {
     type Output = Self;
 
@@ -239,10 +316,56 @@ impl From<[f32; 2]> for Vec2f {
     }
 }
 
+impl ops::Index<usize> for Vec2f {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &f32 {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            _ => panic!("Vec2f index {} out of range", index),
+        }
+    }
+}
+
+impl ops::IndexMut<usize> for Vec2f {
+    fn index_mut(&mut self, index: usize) -> &mut f32 {
+        match index {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            _ => panic!("Vec2f index {} out of range", index),
+        }
+    }
+}
+
+impl iter::Sum for Vec2f {
+    fn sum<I: Iterator<Item=Self>>(iter: I) -> Self {
+        iter.fold(Vec2f::from_parts(0.0, 0.0), |acc, v| acc + v)
+    }
+}
+
+impl<'a> iter::Sum<&'a Vec2f> for Vec2f {
+    fn sum<I: Iterator<Item=&'a Self>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+impl iter::Product for Vec2f {
+    fn product<I: Iterator<Item=Self>>(iter: I) -> Self {
+        iter.fold(Vec2f::from_parts(1.0, 1.0), |acc, v| acc * v)
+    }
+}
+
+impl<'a> iter::Product<&'a Vec2f> for Vec2f {
+    fn product<I: Iterator<Item=&'a Self>>(iter: I) -> Self {
+        iter.copied().product()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use assert_approx_eq::assert_approx_eq;
-    use super::Vec2f;
+    use super::{Vec2f, Vec3f};
 
     #[test]
     fn test_from_parts() {
@@ -332,4 +455,65 @@ mod tests {
         let b = Vec2f::from_parts(2.0, 3.0);
         assert_approx_eq!(a.dot(b), 8.0)
     }
+
+    #[test]
+    fn test_scalar_addition() {
+        let a = Vec2f::from_parts(1.0, -2.0);
+        assert_eq!(a + 1.5, Vec2f::from_parts(2.5, -0.5));
+        let mut b = a;
+        b += 1.5;
+        assert_eq!(b, Vec2f::from_parts(2.5, -0.5));
+    }
+
+    #[test]
+    fn test_scalar_left_multiplication() {
+        let a = Vec2f::from_parts(1.0, -2.0);
+        assert_eq!(3.5 * a, a * 3.5);
+    }
+
+    #[test]
+    fn test_remainder() {
+        let a = Vec2f::from_parts(5.0, 7.0);
+        assert_eq!(a % 3.0, Vec2f::from_parts(2.0, 1.0));
+        assert_eq!(a % Vec2f::from_parts(2.0, 4.0), Vec2f::from_parts(1.0, 3.0));
+        let mut b = a;
+        b %= 3.0;
+        assert_eq!(b, Vec2f::from_parts(2.0, 1.0));
+    }
+
+    #[test]
+    fn test_sum() {
+        let samples = vec![Vec2f::from_parts(1.0, -2.0), Vec2f::from_parts(2.0, -4.0), Vec2f::from_parts(3.0, -6.0)];
+        assert_eq!(samples.iter().sum::<Vec2f>(), Vec2f::from_parts(6.0, -12.0));
+        assert_eq!(samples.into_iter().sum::<Vec2f>() / 3.0, Vec2f::from_parts(2.0, -4.0));
+        assert_eq!(Vec::<Vec2f>::new().into_iter().sum::<Vec2f>(), Vec2f::from_parts(0.0, 0.0));
+    }
+
+    #[test]
+    fn test_product() {
+        let samples = [Vec2f::from_parts(1.0, -2.0), Vec2f::from_parts(2.0, 2.0)];
+        assert_eq!(samples.iter().product::<Vec2f>(), Vec2f::from_parts(2.0, -4.0));
+        assert_eq!(Vec::<Vec2f>::new().into_iter().product::<Vec2f>(), Vec2f::from_parts(1.0, 1.0));
+    }
+
+    #[test]
+    fn test_swizzle() {
+        let a = Vec2f::from_parts(1.0, 2.0);
+        assert_eq!(a.extend(3.0), Vec3f::from_parts(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn test_index() {
+        let mut a = Vec2f::from_parts(1.0, 2.0);
+        assert_eq!(a[0], 1.0);
+        assert_eq!(a[1], 2.0);
+        a[1] = 9.0;
+        assert_eq!(a.y, 9.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_out_of_range() {
+        let _ = Vec2f::from_parts(1.0, 2.0)[2];
+    }
 }
@@ -20,7 +20,7 @@ pub struct Vec2u {
 }
 
 impl Vec2u {
-    pub fn new(x: usize, y: usize) -> Vec2u {
+    pub const fn new(x: usize, y: usize) -> Vec2u {
         Vec2u { x, y }
     }
 }
\ No newline at end of file
@@ -14,8 +14,8 @@
 // limitations under the License.
 //
 
-use std::{fmt, ops};
-use super::{Mat4f, Vec4f};
+use std::{fmt, iter, ops};
+use super::{Mat4f, Vec2f, Vec4f};
 
 ///
 /// 3 Dimensional Vector
@@ -28,16 +28,32 @@ pub struct Vec3f {
 }
 
 impl Vec3f {
+    /// Vector with every component 0.0
+    pub const ZERO: Vec3f = Vec3f::from_parts(0.0, 0.0, 0.0);
+    /// Vector with every component 1.0
+    pub const ONE: Vec3f = Vec3f::from_parts(1.0, 1.0, 1.0);
+    /// Unit vector along the X axis
+    pub const UNIT_X: Vec3f = Vec3f::from_parts(1.0, 0.0, 0.0);
+    /// Unit vector along the Y axis
+    pub const UNIT_Y: Vec3f = Vec3f::from_parts(0.0, 1.0, 0.0);
+    /// Unit vector along the Z axis
+    pub const UNIT_Z: Vec3f = Vec3f::from_parts(0.0, 0.0, 1.0);
+    ///
+    /// Create a new `Vec3f`, same as `from_parts`
+    ///
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
     ///
     /// Create a new `Vec3f`
     ///
-    pub fn from_parts(x: f32, y: f32, z: f32) -> Self {
+    pub const fn from_parts(x: f32, y: f32, z: f32) -> Self {
         Self { x, y, z }
     }
     ///
     /// Create a new `Vec3f`
     ///
-    pub fn from_array(data: [f32; 3]) -> Self {
+    pub const fn from_array(data: [f32; 3]) -> Self {
         Self { x: data[0], y: data[1], z: data[2] }
     }
     ///
@@ -86,6 +102,36 @@ impl Vec3f {
     pub fn magnitude(&self) -> f32 {
         ((self.x * self.x) + (self.y * self.y) + (self.z * self.z)).sqrt()
     }
+    ///
+    /// Get the `xy` components as a `Vec2f`
+    ///
+    pub fn xy(&self) -> Vec2f {
+        Vec2f::from_parts(self.x, self.y)
+    }
+    ///
+    /// Get the `xz` components as a `Vec2f`
+    ///
+    pub fn xz(&self) -> Vec2f {
+        Vec2f::from_parts(self.x, self.z)
+    }
+    ///
+    /// Get the `yz` components as a `Vec2f`
+    ///
+    pub fn yz(&self) -> Vec2f {
+        Vec2f::from_parts(self.y, self.z)
+    }
+    ///
+    /// Drop the `z` component
+    ///
+    pub fn truncate(&self) -> Vec2f {
+        Vec2f::from_parts(self.x, self.y)
+    }
+    ///
+    /// Extend into a `Vec4f` with the given `w` component
+    ///
+    pub fn extend(&self, w: f32) -> Vec4f {
+        Vec4f::from_parts(self.x, self.y, self.z, w)
+    }
 }
 
 impl Default for Vec3f {
@@ -106,6 +152,26 @@ impl fmt::Debug for Vec3f {
     }
 }
 
+impl ops::Add<f32> for Vec3f {
+    type Output = Self;
+
+    fn add(self, rhs: f32) -> Self {
+        Self {
+            x: self.x + rhs,
+            y: self.y + rhs,
+            z: self.z + rhs,
+        }
+    }
+}
+
+impl ops::AddAssign<f32> for Vec3f {
+    fn add_assign(&mut self, rhs: f32) {
+        self.x += rhs;
+        self.y += rhs;
+        self.z += rhs;
+    }
+}
+
 impl ops::Add<Self> for Vec3f {
     type Output = Self;
 
@@ -226,6 +292,14 @@ impl ops::Mul<Mat4f> for Vec3f {
     }
 }
 
+impl ops::Mul<Vec3f> for f32 {
+    type Output = Vec3f;
+
+    fn mul(self, rhs: Vec3f) -> Vec3f {
+        rhs * self
+    }
+}
+
 impl ops::Neg for Vec3f {
     type Output = Self;
 
@@ -238,6 +312,46 @@ impl ops::Neg for Vec3f {
     }
 }
 
+impl ops::Rem<f32> for Vec3f {
+    type Output = Self;
+
+    fn rem(self, rhs: f32) -> Self {
+        Self {
+            x: self.x % rhs,
+            y: self.y % rhs,
+            z: self.z % rhs,
+        }
+    }
+}
+
+impl ops::RemAssign<f32> for Vec3f {
+    fn rem_assign(&mut self, rhs: f32) {
+        self.x %= rhs;
+        self.y %= rhs;
+        self.z %= rhs;
+    }
+}
+
+impl ops::Rem<Self> for Vec3f {
+    type Output = Self;
+
+    fn rem(self, rhs: Self) -> Self {
+        Self {
+            x: self.x % rhs.x,
+            y: self.y % rhs.y,
+            z: self.z % rhs.z,
+        }
+    }
+}
+
+impl ops::RemAssign<Self> for Vec3f {
+    fn rem_assign(&mut self, rhs: Self) {
+        self.x %= rhs.x;
+        self.y %= rhs.y;
+        self.z %= rhs.z;
+    }
+}
+
 impl ops::Sub<f32> for Vec3f {
     type Output = Self;
 
@@ -291,10 +405,58 @@ impl From<Vec3f> for [f32; 3] {
 }
 
 
+impl ops::Index<usize> for Vec3f {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &f32 {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3f index {} out of range", index),
+        }
+    }
+}
+
+impl ops::IndexMut<usize> for Vec3f {
+    fn index_mut(&mut self, index: usize) -> &mut f32 {
+        match index {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("Vec3f index {} out of range", index),
+        }
+    }
+}
+
+impl iter::Sum for Vec3f {
+    fn sum<I: Iterator<Item=Self>>(iter: I) -> Self {
+        iter.fold(Vec3f::from_parts(0.0, 0.0, 0.0), |acc, v| acc + v)
+    }
+}
+
+impl<'a> iter::Sum<&'a Vec3f> for Vec3f {
+    fn sum<I: Iterator<Item=&'a Self>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+impl iter::Product for Vec3f {
+    fn product<I: Iterator<Item=Self>>(iter: I) -> Self {
+        iter.fold(Vec3f::from_parts(1.0, 1.0, 1.0), |acc, v| acc * v)
+    }
+}
+
+impl<'a> iter::Product<&'a Vec3f> for Vec3f {
+    fn product<I: Iterator<Item=&'a Self>>(iter: I) -> Self {
+        iter.copied().product()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use assert_approx_eq::assert_approx_eq;
-    use super::Vec3f;
+    use super::{Vec2f, Vec3f, Vec4f};
 
     #[test]
     fn test_from_parts() {
@@ -395,4 +557,70 @@ mod tests {
         assert_eq!(Vec3f::cross(a, b), Vec3f::from_parts(-1.0, 2.0, -1.0));
         assert_eq!(Vec3f::cross(b, a), Vec3f::from_parts(1.0, -2.0, 1.0));
     }
+
+    #[test]
+    fn test_scalar_addition() {
+        let a = Vec3f::from_parts(1.0, -2.0, 3.0);
+        assert_eq!(a + 1.5, Vec3f::from_parts(2.5, -0.5, 4.5));
+        let mut b = a;
+        b += 1.5;
+        assert_eq!(b, Vec3f::from_parts(2.5, -0.5, 4.5));
+    }
+
+    #[test]
+    fn test_scalar_left_multiplication() {
+        let a = Vec3f::from_parts(1.0, -2.0, 3.0);
+        assert_eq!(3.5 * a, a * 3.5);
+    }
+
+    #[test]
+    fn test_remainder() {
+        let a = Vec3f::from_parts(5.0, 7.0, -5.0);
+        assert_eq!(a % 3.0, Vec3f::from_parts(2.0, 1.0, -2.0));
+        assert_eq!(a % Vec3f::from_parts(2.0, 4.0, 3.0), Vec3f::from_parts(1.0, 3.0, -2.0));
+        let mut b = a;
+        b %= 3.0;
+        assert_eq!(b, Vec3f::from_parts(2.0, 1.0, -2.0));
+    }
+
+    #[test]
+    fn test_sum() {
+        let samples = vec![Vec3f::from_parts(1.0, -2.0, 3.0), Vec3f::from_parts(2.0, -4.0, 6.0), Vec3f::from_parts(3.0, -6.0, 9.0)];
+        assert_eq!(samples.iter().sum::<Vec3f>(), Vec3f::from_parts(6.0, -12.0, 18.0));
+        assert_eq!(samples.into_iter().sum::<Vec3f>() / 3.0, Vec3f::from_parts(2.0, -4.0, 6.0));
+        assert_eq!(Vec::<Vec3f>::new().into_iter().sum::<Vec3f>(), Vec3f::from_parts(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_product() {
+        let samples = [Vec3f::from_parts(1.0, -2.0, 3.0), Vec3f::from_parts(2.0, 2.0, 2.0)];
+        assert_eq!(samples.iter().product::<Vec3f>(), Vec3f::from_parts(2.0, -4.0, 6.0));
+        assert_eq!(Vec::<Vec3f>::new().into_iter().product::<Vec3f>(), Vec3f::from_parts(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_swizzle() {
+        let a = Vec3f::from_parts(1.0, 2.0, 3.0);
+        assert_eq!(a.xy(), Vec2f::from_parts(1.0, 2.0));
+        assert_eq!(a.xz(), Vec2f::from_parts(1.0, 3.0));
+        assert_eq!(a.yz(), Vec2f::from_parts(2.0, 3.0));
+        assert_eq!(a.truncate(), Vec2f::from_parts(1.0, 2.0));
+        assert_eq!(a.extend(4.0), Vec4f::from_parts(1.0, 2.0, 3.0, 4.0));
+    }
+
+    #[test]
+    fn test_index() {
+        let mut a = Vec3f::from_parts(1.0, 2.0, 3.0);
+        assert_eq!(a[0], 1.0);
+        assert_eq!(a[1], 2.0);
+        assert_eq!(a[2], 3.0);
+        a[2] = 9.0;
+        assert_eq!(a.z, 9.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_out_of_range() {
+        let _ = Vec3f::from_parts(1.0, 2.0, 3.0)[3];
+    }
 }
@@ -14,8 +14,8 @@
 // limitations under the License.
 //
 
-use std::{fmt, ops};
-use super::{Mat4f, Vec3f};
+use std::{fmt, iter, ops};
+use super::{Mat4f, Vec2f, Vec3f};
 
 ///
 /// 4 Dimensional Vector
@@ -29,16 +29,28 @@ pub struct Vec4f {
 }
 
 impl Vec4f {
+    /// Vector with every component 0.0
+    pub const ZERO: Vec4f = Vec4f::from_parts(0.0, 0.0, 0.0, 0.0);
+    /// Vector with every component 1.0
+    pub const ONE: Vec4f = Vec4f::from_parts(1.0, 1.0, 1.0, 1.0);
+    /// Unit vector along the X axis
+    pub const UNIT_X: Vec4f = Vec4f::from_parts(1.0, 0.0, 0.0, 0.0);
+    /// Unit vector along the Y axis
+    pub const UNIT_Y: Vec4f = Vec4f::from_parts(0.0, 1.0, 0.0, 0.0);
+    /// Unit vector along the Z axis
+    pub const UNIT_Z: Vec4f = Vec4f::from_parts(0.0, 0.0, 1.0, 0.0);
+    /// Unit vector along the W axis
+    pub const UNIT_W: Vec4f = Vec4f::from_parts(0.0, 0.0, 0.0, 1.0);
     ///
     /// Create a new `Vec4f` from parts
     ///
-    pub fn from_parts(x: f32, y: f32, z: f32, w: f32) -> Self {
+    pub const fn from_parts(x: f32, y: f32, z: f32, w: f32) -> Self {
         Self { x, y, z, w }
     }
     ///
     /// Create a new `Vec4f` from an array
     ///
-    pub fn from_array(data: [f32; 4]) -> Self {
+    pub const fn from_array(data: [f32; 4]) -> Self {
         Self { x: data[0], y: data[1], z: data[2], w: data[3] }
     }
     ///
@@ -77,6 +89,47 @@ impl Vec4f {
     pub fn magnitude(&self) -> f32 {
         ((self.x * self.x) + (self.y * self.y) + (self.z * self.z) + (self.w * self.w)).sqrt()
     }
+    ///
+    /// Get the `xy` components as a `Vec2f`
+    ///
+    pub fn xy(&self) -> Vec2f {
+        Vec2f::from_parts(self.x, self.y)
+    }
+    ///
+    /// Get the `xz` components as a `Vec2f`
+    ///
+    pub fn xz(&self) -> Vec2f {
+        Vec2f::from_parts(self.x, self.z)
+    }
+    ///
+    /// Get the `yz` components as a `Vec2f`
+    ///
+    pub fn yz(&self) -> Vec2f {
+        Vec2f::from_parts(self.y, self.z)
+    }
+    ///
+    /// Get the `xyz` components as a `Vec3f`
+    ///
+    pub fn xyz(&self) -> Vec3f {
+        Vec3f::from_parts(self.x, self.y, self.z)
+    }
+    ///
+    /// Drop the `w` component without a perspective divide
+    ///
+    pub fn truncate(&self) -> Vec3f {
+        Vec3f::from_parts(self.x, self.y, self.z)
+    }
+    ///
+    /// Transform by `m` into clip space, keeping `w` rather than dividing by it
+    ///
+    pub fn transform_homogeneous(&self, m: Mat4f) -> Self {
+        Self {
+            x: self.x * m.c0r0 + self.y * m.c1r0 + self.z * m.c2r0 + self.w * m.c3r0,
+            y: self.x * m.c0r1 + self.y * m.c1r1 + self.z * m.c2r1 + self.w * m.c3r1,
+            z: self.x * m.c0r2 + self.y * m.c1r2 + self.z * m.c2r2 + self.w * m.c3r2,
+            w: self.x * m.c0r3 + self.y * m.c1r3 + self.z * m.c2r3 + self.w * m.c3r3,
+        }
+    }
 }
 
 impl Default for Vec4f {
@@ -107,6 +160,28 @@ impl PartialEq<Self> for Vec4f {
     }
 }
 
+impl ops::Add<f32> for Vec4f {
+    type Output = Self;
+
+    fn add(self, rhs: f32) -> Self {
+        Self {
+            x: self.x + rhs,
+            y: self.y + rhs,
+            z: self.z + rhs,
+            w: self.w + rhs,
+        }
+    }
+}
+
+impl ops::AddAssign<f32> for Vec4f {
+    fn add_assign(&mut self, rhs: f32) {
+        self.x += rhs;
+        self.y += rhs;
+        self.z += rhs;
+        self.w += rhs;
+    }
+}
+
 impl ops::Add<Self> for Vec4f {
     type Output = Self;
 
@@ -238,6 +313,14 @@ impl ops::Mul<Mat4f> for Vec4f {
     }
 }
 
+impl ops::Mul<Vec4f> for f32 {
+    type Output = Vec4f;
+
+    fn mul(self, rhs: Vec4f) -> Vec4f {
+        rhs * self
+    }
+}
+
 impl ops::Neg for Vec4f {
     type Output = Self;
 
@@ -251,6 +334,50 @@ impl ops::Neg for Vec4f {
     }
 }
 
+impl ops::Rem<f32> for Vec4f {
+    type Output = Self;
+
+    fn rem(self, rhs: f32) -> Self {
+        Self {
+            x: self.x % rhs,
+            y: self.y % rhs,
+            z: self.z % rhs,
+            w: self.w % rhs,
+        }
+    }
+}
+
+impl ops::RemAssign<f32> for Vec4f {
+    fn rem_assign(&mut self, rhs: f32) {
+        self.x %= rhs;
+        self.y %= rhs;
+        self.z %= rhs;
+        self.w %= rhs;
+    }
+}
+
+impl ops::Rem<Self> for Vec4f {
+    type Output = Self;
+
+    fn rem(self, rhs: Self) -> Self {
+        Self {
+            x: self.x % rhs.x,
+            y: self.y % rhs.y,
+            z: self.z % rhs.z,
+            w: self.w % rhs.w,
+        }
+    }
+}
+
+impl ops::RemAssign<Self> for Vec4f {
+    fn rem_assign(&mut self, rhs: Self) {
+        self.x %= rhs.x;
+        self.y %= rhs.y;
+        self.z %= rhs.z;
+        self.w %= rhs.w;
+    }
+}
+
 impl ops::Sub<f32> for Vec4f {
     type Output = Self;
 
@@ -307,10 +434,60 @@ impl From<[f32; 4]> for Vec4f {
     }
 }
 
+impl ops::Index<usize> for Vec4f {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &f32 {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            3 => &self.w,
+            _ => panic!("Vec4f index {} out of range", index),
+        }
+    }
+}
+
+impl ops::IndexMut<usize> for Vec4f {
+    fn index_mut(&mut self, index: usize) -> &mut f32 {
+        match index {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            3 => &mut self.w,
+            _ => panic!("Vec4f index {} out of range", index),
+        }
+    }
+}
+
+impl iter::Sum for Vec4f {
+    fn sum<I: Iterator<Item=Self>>(iter: I) -> Self {
+        iter.fold(Vec4f::from_parts(0.0, 0.0, 0.0, 0.0), |acc, v| acc + v)
+    }
+}
+
+impl<'a> iter::Sum<&'a Vec4f> for Vec4f {
+    fn sum<I: Iterator<Item=&'a Self>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+impl iter::Product for Vec4f {
+    fn product<I: Iterator<Item=Self>>(iter: I) -> Self {
+        iter.fold(Vec4f::from_parts(1.0, 1.0, 1.0, 1.0), |acc, v| acc * v)
+    }
+}
+
+impl<'a> iter::Product<&'a Vec4f> for Vec4f {
+    fn product<I: Iterator<Item=&'a Self>>(iter: I) -> Self {
+        iter.copied().product()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use assert_approx_eq::assert_approx_eq;
-    use super::Vec4f;
+    use super::{Mat4f, Vec2f, Vec3f, Vec4f};
 
     #[test]
     fn test_vec4f_addition() {
@@ -383,4 +560,83 @@ mod tests {
         let b = Vec4f::from_parts(2.0, 3.0, 4.0, 0.0);
         assert_approx_eq!(a.dot(b), 20.0)
     }
+
+    #[test]
+    fn test_scalar_addition() {
+        let a = Vec4f::from_parts(1.0, -2.0, 3.0, -4.0);
+        assert_eq!(a + 1.5, Vec4f::from_parts(2.5, -0.5, 4.5, -2.5));
+        let mut b = a;
+        b += 1.5;
+        assert_eq!(b, Vec4f::from_parts(2.5, -0.5, 4.5, -2.5));
+    }
+
+    #[test]
+    fn test_scalar_left_multiplication() {
+        let a = Vec4f::from_parts(1.0, -2.0, 3.0, -4.0);
+        assert_eq!(3.5 * a, a * 3.5);
+    }
+
+    #[test]
+    fn test_remainder() {
+        let a = Vec4f::from_parts(5.0, 7.0, -5.0, 9.0);
+        assert_eq!(a % 3.0, Vec4f::from_parts(2.0, 1.0, -2.0, 0.0));
+        assert_eq!(a % Vec4f::from_parts(2.0, 4.0, 3.0, 5.0), Vec4f::from_parts(1.0, 3.0, -2.0, 4.0));
+        let mut b = a;
+        b %= 3.0;
+        assert_eq!(b, Vec4f::from_parts(2.0, 1.0, -2.0, 0.0));
+    }
+
+    #[test]
+    fn test_sum() {
+        let samples = vec![Vec4f::from_parts(1.0, -2.0, 3.0, -4.0), Vec4f::from_parts(2.0, -4.0, 6.0, -8.0), Vec4f::from_parts(3.0, -6.0, 9.0, -12.0)];
+        assert_eq!(samples.iter().sum::<Vec4f>(), Vec4f::from_parts(6.0, -12.0, 18.0, -24.0));
+        assert_eq!(samples.into_iter().sum::<Vec4f>() / 3.0, Vec4f::from_parts(2.0, -4.0, 6.0, -8.0));
+        assert_eq!(Vec::<Vec4f>::new().into_iter().sum::<Vec4f>(), Vec4f::from_parts(0.0, 0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_product() {
+        let samples = [Vec4f::from_parts(1.0, -2.0, 3.0, -4.0), Vec4f::from_parts(2.0, 2.0, 2.0, 2.0)];
+        assert_eq!(samples.iter().product::<Vec4f>(), Vec4f::from_parts(2.0, -4.0, 6.0, -8.0));
+        assert_eq!(Vec::<Vec4f>::new().into_iter().product::<Vec4f>(), Vec4f::from_parts(1.0, 1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_swizzle() {
+        let a = Vec4f::from_parts(1.0, 2.0, 3.0, 4.0);
+        assert_eq!(a.xy(), Vec2f::from_parts(1.0, 2.0));
+        assert_eq!(a.xz(), Vec2f::from_parts(1.0, 3.0));
+        assert_eq!(a.yz(), Vec2f::from_parts(2.0, 3.0));
+        assert_eq!(a.xyz(), Vec3f::from_parts(1.0, 2.0, 3.0));
+        assert_eq!(a.truncate(), Vec3f::from_parts(1.0, 2.0, 3.0));
+        assert_eq!(a.truncate().extend(4.0), a);
+    }
+
+    #[test]
+    fn test_transform_homogeneous() {
+        let m = Mat4f::perspective_rh_gl(90.0, 1.0, 1.0, 3.0);
+        let clip = Vec4f::from_parts(1.0, 0.5, -2.0, 1.0).transform_homogeneous(m);
+        assert_eq!(clip.w, 2.0);
+        assert_eq!(clip.x, 1.0);
+        assert_eq!(clip.y, 0.5);
+        let ndc = Vec4f::from_parts(1.0, 0.5, -2.0, 1.0) * m;
+        assert_eq!(ndc.x, clip.x / clip.w);
+    }
+
+    #[test]
+    fn test_index() {
+        let mut a = Vec4f::from_parts(1.0, 2.0, 3.0, 4.0);
+        assert_eq!(a[0], 1.0);
+        assert_eq!(a[1], 2.0);
+        assert_eq!(a[2], 3.0);
+        assert_eq!(a[3], 4.0);
+        a[3] = 9.0;
+        assert_eq!(a.w, 9.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_out_of_range() {
+        let _ = Vec4f::from_parts(1.0, 2.0, 3.0, 4.0)[4];
+    }
 }
@@ -0,0 +1,343 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Boolean operations on closed triangle meshes.
+//!
+//! Follows the BSP tree approach of Evan Wallace's csg.js: each mesh is built into a BSP
+//! tree, the trees clip away each other's polygons, and what remains is merged. Meshes
+//! need to be closed and wound counter clockwise seen from outside, like `cube`.
+
+use super::{Mesh, Triangle};
+use crate::math::Vec3f;
+
+/// Distance within which points count as lying on a plane
+const EPSILON: f32 = 1e-5;
+
+#[derive(Copy, Clone, Debug)]
+struct Plane {
+    normal: Vec3f,
+    w: f32,
+}
+
+impl Plane {
+    fn from_points(a: Vec3f, b: Vec3f, c: Vec3f) -> Option<Plane> {
+        let normal = Vec3f::cross(b - a, c - a);
+        if normal.magnitude() < EPSILON * EPSILON {
+            return None;
+        }
+        let normal = normal.normalize();
+        Some(Plane { normal, w: normal.dot(a) })
+    }
+    fn flip(&mut self) {
+        self.normal = -self.normal;
+        self.w = -self.w;
+    }
+}
+
+/// Convex polygon with its supporting plane
+#[derive(Clone, Debug)]
+struct Polygon {
+    vertices: Vec<Vec3f>,
+    plane: Plane,
+}
+
+impl Polygon {
+    fn flip(&mut self) {
+        self.vertices.reverse();
+        self.plane.flip();
+    }
+}
+
+const COPLANAR: u8 = 0;
+const FRONT: u8 = 1;
+const BACK: u8 = 2;
+const SPANNING: u8 = 3;
+
+/// Sort `polygon` by `plane`, splitting it if it crosses
+fn split(
+    plane: &Plane,
+    polygon: &Polygon,
+    coplanar_front: &mut Vec<Polygon>,
+    coplanar_back: &mut Vec<Polygon>,
+    front: &mut Vec<Polygon>,
+    back: &mut Vec<Polygon>,
+) {
+    let types: Vec<u8> = polygon
+        .vertices
+        .iter()
+        .map(|vertex| {
+            let distance = plane.normal.dot(*vertex) - plane.w;
+            if distance < -EPSILON {
+                BACK
+            } else if distance > EPSILON {
+                FRONT
+            } else {
+                COPLANAR
+            }
+        })
+        .collect();
+    match types.iter().fold(COPLANAR, |all, kind| all | kind) {
+        COPLANAR => {
+            if plane.normal.dot(polygon.plane.normal) > 0.0 {
+                coplanar_front.push(polygon.clone());
+            } else {
+                coplanar_back.push(polygon.clone());
+            }
+        }
+        FRONT => front.push(polygon.clone()),
+        BACK => back.push(polygon.clone()),
+        _ => {
+            let (mut f, mut b) = (Vec::new(), Vec::new());
+            let count = polygon.vertices.len();
+            for i in 0..count {
+                let j = (i + 1) % count;
+                let (ti, tj) = (types[i], types[j]);
+                let (vi, vj) = (polygon.vertices[i], polygon.vertices[j]);
+                if ti != BACK {
+                    f.push(vi);
+                }
+                if ti != FRONT {
+                    b.push(vi);
+                }
+                if (ti | tj) == SPANNING {
+                    let t = (plane.w - plane.normal.dot(vi)) / plane.normal.dot(vj - vi);
+                    let v = vi + (vj - vi) * t;
+                    f.push(v);
+                    b.push(v);
+                }
+            }
+            if f.len() >= 3 {
+                front.push(Polygon { vertices: f, plane: polygon.plane });
+            }
+            if b.len() >= 3 {
+                back.push(Polygon { vertices: b, plane: polygon.plane });
+            }
+        }
+    }
+}
+
+/// BSP tree node, polygons lie in `plane`
+#[derive(Clone, Default)]
+struct Node {
+    plane: Option<Plane>,
+    front: Option<Box<Node>>,
+    back: Option<Box<Node>>,
+    polygons: Vec<Polygon>,
+}
+
+impl Node {
+    fn new(polygons: Vec<Polygon>) -> Node {
+        let mut node = Node::default();
+        node.build(polygons);
+        node
+    }
+    /// Turn solid space into empty space and back
+    fn invert(&mut self) {
+        for polygon in &mut self.polygons {
+            polygon.flip();
+        }
+        if let Some(plane) = &mut self.plane {
+            plane.flip();
+        }
+        if let Some(front) = &mut self.front {
+            front.invert();
+        }
+        if let Some(back) = &mut self.back {
+            back.invert();
+        }
+        std::mem::swap(&mut self.front, &mut self.back);
+    }
+    /// Parts of `polygons` outside the solid this tree describes
+    fn clip_polygons(&self, polygons: Vec<Polygon>) -> Vec<Polygon> {
+        let plane = match &self.plane {
+            Some(plane) => plane,
+            None => return polygons,
+        };
+        let (mut front, mut back) = (Vec::new(), Vec::new());
+        for polygon in &polygons {
+            let (mut coplanar_front, mut coplanar_back) = (Vec::new(), Vec::new());
+            split(plane, polygon, &mut coplanar_front, &mut coplanar_back, &mut front, &mut back);
+            front.append(&mut coplanar_front);
+            back.append(&mut coplanar_back);
+        }
+        let mut front = match &self.front {
+            Some(node) => node.clip_polygons(front),
+            None => front,
+        };
+        let back = match &self.back {
+            Some(node) => node.clip_polygons(back),
+            None => Vec::new(),
+        };
+        front.extend(back);
+        front
+    }
+    /// Remove the parts of this tree's polygons inside `other`
+    fn clip_to(&mut self, other: &Node) {
+        self.polygons = other.clip_polygons(std::mem::take(&mut self.polygons));
+        if let Some(front) = &mut self.front {
+            front.clip_to(other);
+        }
+        if let Some(back) = &mut self.back {
+            back.clip_to(other);
+        }
+    }
+    fn all_polygons(&self) -> Vec<Polygon> {
+        let mut polygons = self.polygons.clone();
+        if let Some(front) = &self.front {
+            polygons.extend(front.all_polygons());
+        }
+        if let Some(back) = &self.back {
+            polygons.extend(back.all_polygons());
+        }
+        polygons
+    }
+    fn build(&mut self, polygons: Vec<Polygon>) {
+        if polygons.is_empty() {
+            return;
+        }
+        let plane = *self.plane.get_or_insert(polygons[0].plane);
+        let (mut front, mut back) = (Vec::new(), Vec::new());
+        let (mut coplanar_front, mut coplanar_back) = (Vec::new(), Vec::new());
+        for polygon in &polygons {
+            split(&plane, polygon, &mut coplanar_front, &mut coplanar_back, &mut front, &mut back);
+        }
+        self.polygons.append(&mut coplanar_front);
+        self.polygons.append(&mut coplanar_back);
+        if !front.is_empty() {
+            self.front.get_or_insert_with(Box::default).build(front);
+        }
+        if !back.is_empty() {
+            self.back.get_or_insert_with(Box::default).build(back);
+        }
+    }
+}
+
+fn to_polygons(mesh: &Mesh) -> Vec<Polygon> {
+    mesh.triangles()
+        .iter()
+        .filter_map(|t| {
+            Plane::from_points(t.a, t.b, t.c).map(|plane| Polygon {
+                vertices: vec![t.a, t.b, t.c],
+                plane,
+            })
+        })
+        .collect()
+}
+
+fn to_mesh(polygons: Vec<Polygon>) -> Mesh {
+    let mut triangles = Vec::new();
+    for polygon in polygons {
+        for i in 1..polygon.vertices.len() - 1 {
+            triangles.push(Triangle::new(polygon.vertices[0], polygon.vertices[i], polygon.vertices[i + 1]));
+        }
+    }
+    Mesh::new(triangles)
+}
+
+impl Mesh {
+    /// Space inside either this mesh or `other`.
+    ///
+    /// Texture coordinates and normals are not carried over.
+    pub fn union(&self, other: &Mesh) -> Mesh {
+        let (mut a, mut b) = (Node::new(to_polygons(self)), Node::new(to_polygons(other)));
+        a.clip_to(&b);
+        b.clip_to(&a);
+        b.invert();
+        b.clip_to(&a);
+        b.invert();
+        a.build(b.all_polygons());
+        to_mesh(a.all_polygons())
+    }
+    /// Space inside this mesh but not `other`.
+    ///
+    /// Texture coordinates and normals are not carried over.
+    pub fn subtract(&self, other: &Mesh) -> Mesh {
+        let (mut a, mut b) = (Node::new(to_polygons(self)), Node::new(to_polygons(other)));
+        a.invert();
+        a.clip_to(&b);
+        b.clip_to(&a);
+        b.invert();
+        b.clip_to(&a);
+        b.invert();
+        a.build(b.all_polygons());
+        a.invert();
+        to_mesh(a.all_polygons())
+    }
+    /// Space inside both this mesh and `other`.
+    ///
+    /// Texture coordinates and normals are not carried over.
+    pub fn intersect(&self, other: &Mesh) -> Mesh {
+        let (mut a, mut b) = (Node::new(to_polygons(self)), Node::new(to_polygons(other)));
+        a.invert();
+        b.clip_to(&a);
+        b.invert();
+        a.clip_to(&b);
+        b.clip_to(&a);
+        a.build(b.all_polygons());
+        a.invert();
+        to_mesh(a.all_polygons())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::Vec3f;
+    use crate::model::{cube, Mesh, Triangle};
+    use assert_approx_eq::assert_approx_eq;
+
+    fn shifted(mesh: &Mesh, offset: Vec3f) -> Mesh {
+        let triangles = mesh.triangles().iter();
+        Mesh::new(triangles.map(|t| Triangle::new(t.a + offset, t.b + offset, t.c + offset)).collect())
+    }
+
+    /// Volume enclosed by a closed mesh, from the divergence theorem
+    fn volume(mesh: &Mesh) -> f32 {
+        let signed = |t: &Triangle| t.a.dot(Vec3f::cross(t.b, t.c)) / 6.0;
+        mesh.triangles().iter().map(signed).sum()
+    }
+
+    #[test]
+    fn test_booleans() {
+        let a = cube(2.0);
+        let b = shifted(&cube(2.0), Vec3f::from_parts(1.0, 0.0, 0.0));
+        assert_approx_eq!(volume(&a), 8.0, 1e-4);
+        assert_approx_eq!(volume(&a.union(&b)), 12.0, 1e-3);
+        assert_approx_eq!(volume(&a.subtract(&b)), 4.0, 1e-3);
+        assert_approx_eq!(volume(&a.intersect(&b)), 4.0, 1e-3);
+        // Disjoint meshes
+        let far = shifted(&cube(2.0), Vec3f::from_parts(5.0, 0.0, 0.0));
+        assert_approx_eq!(volume(&a.union(&far)), 16.0, 1e-3);
+        assert_approx_eq!(volume(&a.subtract(&far)), 8.0, 1e-3);
+        assert!(a.intersect(&far).triangles().is_empty());
+    }
+
+    #[test]
+    fn test_hole() {
+        // A bar through the middle of a cube leaves a tunnel
+        let bar = Mesh::new(
+            cube(1.0)
+                .triangles()
+                .iter()
+                .map(|t| {
+                    let stretch = |v: Vec3f| Vec3f::from_parts(v.x, v.y, v.z * 4.0);
+                    Triangle::new(stretch(t.a), stretch(t.b), stretch(t.c))
+                })
+                .collect(),
+        );
+        let tunnel = cube(2.0).subtract(&bar);
+        assert_approx_eq!(volume(&tunnel), 8.0 - 2.0, 1e-3);
+    }
+}
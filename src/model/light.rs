@@ -0,0 +1,246 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::canvas::Color;
+use crate::math::{uniform_cone, uniform_cone_pdf, OrthonormalBasis, Ray, Vec3f};
+use std::f32::consts::PI;
+
+/// Lumens per watt of light at 555nm, converting radiometric to photometric units
+pub const LUMINOUS_EFFICACY: f32 = 683.0;
+
+/// Light linking groups of a light shining on every object
+pub const ALL_LINKS: u32 = u32::MAX;
+
+///
+/// Light shining equally in every direction from a point.
+///
+/// Scene distances are in meters and `intensity` is the luminous intensity in candela, so
+/// `irradiance` gives illuminance in lux. Renders come out in cd/m² and are brought to
+/// display range with `Camera::expose`, the same way for the Rasterizer and Raytracer.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PointLight {
+    pub position: Vec3f,
+    pub color: Color,
+    /// Luminous intensity in candela
+    pub intensity: f32,
+    /// Distance at which the light fades out completely, `None` for unlimited reach
+    pub range: Option<f32>,
+    /// Light linking groups the light shines on as bits, it only lights objects in at
+    /// least one of them, see `Instance::light_links`
+    pub links: u32,
+}
+
+impl PointLight {
+    /// Create new white PointLight of unlimited range
+    pub fn new(position: Vec3f, intensity: f32) -> PointLight {
+        PointLight {
+            position,
+            color: Color::white(),
+            intensity,
+            range: None,
+            links: ALL_LINKS,
+        }
+    }
+    /// Create new white PointLight of `candela` luminous intensity
+    pub fn from_candela(position: Vec3f, candela: f32) -> PointLight {
+        PointLight::new(position, candela)
+    }
+    /// Create new white PointLight emitting a luminous flux of `lumens`, as printed on
+    /// light bulb packaging
+    pub fn from_lumens(position: Vec3f, lumens: f32) -> PointLight {
+        PointLight::new(position, lumens / (4.0 * PI))
+    }
+    /// Create new white PointLight of `watts_per_steradian` radiant intensity
+    pub fn from_radiant_intensity(position: Vec3f, watts_per_steradian: f32) -> PointLight {
+        PointLight::new(position, watts_per_steradian * LUMINOUS_EFFICACY)
+    }
+    /// Luminous flux in lumens
+    pub fn lumens(&self) -> f32 {
+        self.intensity * 4.0 * PI
+    }
+    /// Radiant intensity in watts per steradian
+    pub fn radiant_intensity(&self) -> f32 {
+        self.intensity / LUMINOUS_EFFICACY
+    }
+    /// Check if the light shines on objects in the light linking groups `links`
+    pub fn links_to(&self, links: u32) -> bool {
+        self.links & links != 0
+    }
+    /// Fraction of the intensity reaching `distance` from the light.
+    ///
+    /// Falls off with the inverse square of the distance, windowed to reach zero at the
+    /// range so lights only touch the pixels inside their bounding volume.
+    pub fn attenuation(&self, distance: f32) -> f32 {
+        let falloff = 1.0 / (distance * distance).max(1e-4);
+        match self.range {
+            Some(range) => {
+                let ratio = distance / range;
+                let window = (1.0 - ratio * ratio * ratio * ratio).max(0.0);
+                falloff * window * window
+            }
+            None => falloff,
+        }
+    }
+    /// Light arriving at `point` with normal `normal`, before the surface color
+    pub fn irradiance(&self, point: Vec3f, normal: Vec3f) -> Color {
+        let to_light = self.position - point;
+        let distance = to_light.magnitude();
+        if distance == 0.0 {
+            return Color::black();
+        }
+        let cosine = normal.dot(to_light * (1.0 / distance)).max(0.0);
+        self.color * (self.intensity * cosine * self.attenuation(distance))
+    }
+}
+
+///
+/// Sphere glowing evenly over its surface.
+///
+/// Unlike a `PointLight` it has a size, so rays can hit it and its shadows are soft. It is
+/// only seen by the path tracer. `intensity` is the luminance of the surface in cd/m².
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SphereLight {
+    pub position: Vec3f,
+    pub radius: f32,
+    pub color: Color,
+    /// Luminance of the surface in cd/m²
+    pub intensity: f32,
+    /// Light linking groups the light shines on as bits, it only lights objects in at
+    /// least one of them, see `Instance::light_links`
+    pub links: u32,
+}
+
+impl SphereLight {
+    /// Create new white SphereLight
+    pub fn new(position: Vec3f, radius: f32, intensity: f32) -> SphereLight {
+        SphereLight {
+            position,
+            radius,
+            color: Color::white(),
+            intensity,
+            links: ALL_LINKS,
+        }
+    }
+    /// Check if the light shines on objects in the light linking groups `links`
+    pub fn links_to(&self, links: u32) -> bool {
+        self.links & links != 0
+    }
+    /// Light leaving every point of the surface
+    pub fn radiance(&self) -> Color {
+        self.color * self.intensity
+    }
+    /// Distance along `ray` to where it enters the sphere, in multiples of its direction
+    pub fn intersect(&self, ray: &Ray) -> Option<f32> {
+        let offset = ray.origin - self.position;
+        let a = ray.direction.dot(ray.direction);
+        let b = offset.dot(ray.direction);
+        let c = offset.dot(offset) - self.radius * self.radius;
+        let discriminant = b * b - a * c;
+        if a == 0.0 || discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        [(-b - root) / a, (-b + root) / a].iter().copied().find(|&t| t > 0.0)
+    }
+    /// Probability density of `sample` picking a direction from `point` towards the
+    /// light, per steradian. Directions inside the cone the light covers are equally
+    /// likely, 0 when `point` is inside the light.
+    pub fn pdf(&self, point: Vec3f) -> f32 {
+        match self.cone(point) {
+            Some(cos_max) => uniform_cone_pdf(cos_max),
+            None => 0.0,
+        }
+    }
+    /// Pick a unit direction from `point` towards the light from two uniform random
+    /// numbers in [0, 1), returning it with the distance to the light's surface
+    pub fn sample(&self, point: Vec3f, u1: f32, u2: f32) -> Option<(Vec3f, f32)> {
+        let cos_max = self.cone(point)?;
+        let to_center = self.position - point;
+        let distance = to_center.magnitude();
+        let axis = to_center * (1.0 / distance);
+        let local = uniform_cone(cos_max, u1, u2);
+        let direction = OrthonormalBasis::from_normal(axis).to_world(local);
+        let (cos_theta, sin_squared) = (local.z, local.x * local.x + local.y * local.y);
+        // Nearest point on the sphere along the direction
+        let along = distance * cos_theta;
+        let across = (self.radius * self.radius - distance * distance * sin_squared).max(0.0);
+        Some((direction, along - across.sqrt()))
+    }
+    /// Cosine of the half angle of the cone the light covers seen from `point`, `None`
+    /// inside the light
+    fn cone(&self, point: Vec3f) -> Option<f32> {
+        let distance_squared = (self.position - point).dot(self.position - point);
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return None;
+        }
+        Some((1.0 - radius_squared / distance_squared).sqrt())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PointLight, SphereLight, LUMINOUS_EFFICACY};
+    use crate::canvas::Color;
+    use crate::math::{Ray, Vec3f};
+    use assert_approx_eq::assert_approx_eq;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_irradiance() {
+        let mut light = PointLight::new(Vec3f::from_parts(0.0, 2.0, 0.0), 4.0);
+        let up = Vec3f::from_parts(0.0, 1.0, 0.0);
+        let origin = Vec3f::from_parts(0.0, 0.0, 0.0);
+        assert_eq!(light.irradiance(origin, up), Color::white());
+        assert_eq!(light.irradiance(origin, -up), Color::black());
+        light.range = Some(4.0);
+        assert_approx_eq!(light.attenuation(2.0), 0.25 * 0.9375 * 0.9375);
+        assert_eq!(light.attenuation(4.0), 0.0);
+        assert_eq!(light.attenuation(5.0), 0.0);
+    }
+
+    #[test]
+    fn test_units() {
+        let origin = Vec3f::ZERO;
+        let bulb = PointLight::from_lumens(origin, 800.0);
+        assert_approx_eq!(bulb.intensity, 800.0 / (4.0 * PI));
+        assert_approx_eq!(bulb.lumens(), 800.0, 1e-3);
+        assert_eq!(PointLight::from_candela(origin, 100.0).intensity, 100.0);
+        let radiant = PointLight::from_radiant_intensity(origin, 2.0);
+        assert_approx_eq!(radiant.intensity, 2.0 * LUMINOUS_EFFICACY);
+        assert_approx_eq!(radiant.radiant_intensity(), 2.0);
+        // 100 candela lights a surface 2 meters away facing it with 25 lux
+        let light = PointLight::from_candela(Vec3f::new(0.0, 2.0, 0.0), 100.0);
+        assert_approx_eq!(light.irradiance(origin, Vec3f::UNIT_Y).g.value(), 25.0);
+    }
+
+    #[test]
+    fn test_sphere_light() {
+        let light = SphereLight::new(Vec3f::new(0.0, 4.0, 0.0), 2.0, 10.0);
+        let up = Ray::new(Vec3f::ZERO, Vec3f::UNIT_Y);
+        assert_approx_eq!(light.intersect(&up).unwrap(), 2.0);
+        assert!(light.intersect(&Ray::new(Vec3f::ZERO, Vec3f::UNIT_X)).is_none());
+        // Seen from 4 away a sphere of radius 2 covers a cone of 30 degrees
+        assert_approx_eq!(light.pdf(Vec3f::ZERO), 1.0 / (2.0 * PI * (1.0 - 0.75f32.sqrt())));
+        assert_eq!(light.pdf(Vec3f::new(0.0, 3.0, 0.0)), 0.0);
+        for &(u1, u2) in &[(0.0, 0.0), (0.5, 0.25), (0.99, 0.7)] {
+            let (direction, distance) = light.sample(Vec3f::ZERO, u1, u2).unwrap();
+            assert_approx_eq!(light.intersect(&Ray::new(Vec3f::ZERO, direction)).unwrap(), distance, 1e-3);
+        }
+    }
+}
@@ -14,22 +14,402 @@
 // limitations under the License.
 //
 
-use crate::image::Color;
+use crate::canvas::{Color, Sampler, Texture};
+use crate::math::{Vec2f, Vec3f};
 
-pub struct Material<T: Color> {
-    pub surface: T,
-    pub emission: T,
+/// Offset, rotation and scale applied to texture coordinates before sampling, as in
+/// glTF's `KHR_texture_transform`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UvTransform {
+    pub offset: Vec2f,
+    /// Counter clockwise rotation in radians
+    pub rotation: f32,
+    pub scale: Vec2f,
+}
+
+impl UvTransform {
+    /// Transformed `uv`, scaled first, then rotated, then offset
+    pub fn apply(&self, uv: Vec2f) -> Vec2f {
+        let scaled = uv * self.scale;
+        let (sin, cos) = self.rotation.sin_cos();
+        // glTF rotates with V pointing down, so positive angles turn counter clockwise on screen
+        let rotated = Vec2f::from_parts(cos * scaled.x + sin * scaled.y, cos * scaled.y - sin * scaled.x);
+        rotated + self.offset
+    }
+}
+
+impl Default for UvTransform {
+    fn default() -> Self {
+        UvTransform {
+            offset: Vec2f::from_parts(0.0, 0.0),
+            rotation: 0.0,
+            scale: Vec2f::from_parts(1.0, 1.0),
+        }
+    }
+}
+
+/// Where on a texture each point of a surface is found
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Mapping {
+    /// Use the surface's texture coordinates
+    Uv,
+    /// Project the texture along the X, Y and Z axes and blend the three by how much the
+    /// surface faces each, for meshes without texture coordinates
+    Triplanar {
+        /// Texture repeats per world unit
+        scale: f32,
+        /// Exponent on the blend weights, higher values shorten the blend between axes
+        sharpness: f32,
+    },
+}
+
+/// How the raytracer treats rays hitting the back of a triangle, the side its winding
+/// faces away from
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BackfaceMode {
+    /// Turn the normal towards the ray and shade both sides alike, for thin two sided
+    /// surfaces such as leaves
+    #[default]
+    Flip,
+    /// Let every ray pass through the back, for single sided surfaces such as open meshes
+    /// whose inside should not block the view or the light
+    Skip,
+    /// Keep the normal facing out, so the back blocks light but reflects none and looks
+    /// like the dark inside of a closed surface. Transparent materials are flipped as
+    /// with `Flip`, so glass still refracts out of the material.
+    Interior,
+}
+
+/// Texture used by a material, with its own coordinate transform and sampler
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextureSlot {
+    /// Index of the texture in the scene's texture list
+    pub texture: usize,
+    pub mapping: Mapping,
+    pub transform: UvTransform,
+    pub sampler: Sampler,
+}
+
+impl TextureSlot {
+    /// Create new TextureSlot using texture `texture` untransformed with default sampling
+    pub fn new(texture: usize) -> TextureSlot {
+        TextureSlot {
+            texture,
+            mapping: Mapping::Uv,
+            transform: UvTransform::default(),
+            sampler: Sampler::default(),
+        }
+    }
+    /// Read the texture at `uv` from `textures`, white if the texture is missing.
+    ///
+    /// Always uses `uv`, whatever the mapping.
+    pub fn sample(&self, textures: &[Texture], uv: Vec2f) -> Color {
+        match textures.get(self.texture) {
+            Some(texture) => texture.sample(self.transform.apply(uv), &self.sampler),
+            None => Color::white(),
+        }
+    }
+    /// Read the texture for the surface point at world `position` with unit `normal` and
+    /// texture coordinates `uv`, following the mapping
+    pub fn sample_point(&self, textures: &[Texture], uv: Vec2f, position: Vec3f, normal: Vec3f) -> Color {
+        let (scale, sharpness) = match self.mapping {
+            Mapping::Uv => return self.sample(textures, uv),
+            Mapping::Triplanar { scale, sharpness } => (scale, sharpness),
+        };
+        let p = position * scale;
+        let weight = |n: f32| n.abs().powf(sharpness);
+        let (wx, wy, wz) = (weight(normal.x), weight(normal.y), weight(normal.z));
+        let total = wx + wy + wz;
+        if total <= 0.0 {
+            return self.sample(textures, uv);
+        }
+        // World up is texture up on the side projections
+        let x = self.sample(textures, Vec2f::from_parts(p.z, -p.y));
+        let y = self.sample(textures, Vec2f::from_parts(p.x, p.z));
+        let z = self.sample(textures, Vec2f::from_parts(p.x, -p.y));
+        (x * wx + y * wy + z * wz) * (1.0 / total)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Material {
+    pub surface: Color,
+    /// Texture multiplied with `surface`
+    pub surface_texture: Option<TextureSlot>,
+    pub emission: Color,
+    /// Fraction of light passing into the surface as through clear glass, used by the path
+    /// tracer
     pub transparency: f32,
+    /// Index of refraction of the inside of transparent surfaces, at 587.6nm if dispersive
+    pub ior: f32,
+    /// Abbe number of transparent surfaces splitting light by wavelength, lower numbers
+    /// split it further, `None` refracts every wavelength alike
+    pub dispersion: Option<f32>,
     pub reflectivity: f32,
+    /// Spread of the reflection from 0 for a mirror to 1 for a broad highlight, used by
+    /// the path tracer
+    pub roughness: f32,
+    /// Coverage from 0 to 1, multiplied with the luminance of `alpha_texture`
+    pub alpha: f32,
+    pub alpha_texture: Option<TextureSlot>,
+    /// Discard the surface where its alpha falls below this, `None` keeps it everywhere
+    pub alpha_cutoff: Option<f32>,
+    /// Tangent space normal map, with red along increasing u, green along increasing v and
+    /// blue out of the surface, each mapped from 0..1 to -1..1
+    pub normal_texture: Option<TextureSlot>,
+    /// Height map bending the shading normal as if the surface was raised along it by the
+    /// luminance of the texture times `bump_scale` world units
+    pub bump_texture: Option<TextureSlot>,
+    pub bump_scale: f32,
+    /// How the raytracer treats hits on the back of triangles
+    pub backface: BackfaceMode,
 }
 
-impl<T: Color> Material<T> {
-    pub fn new() -> Material<T> {
+impl Material {
+    pub fn new() -> Material {
         Material {
-            surface: T::white(),
-            emission: T::white(),
+            surface: Color::white(),
+            surface_texture: None,
+            emission: Color::white(),
             transparency: 0.0,
+            ior: 1.5,
+            dispersion: None,
             reflectivity: 0.0,
+            roughness: 0.0,
+            alpha: 1.0,
+            alpha_texture: None,
+            alpha_cutoff: None,
+            normal_texture: None,
+            bump_texture: None,
+            bump_scale: 1.0,
+            backface: BackfaceMode::default(),
+        }
+    }
+    /// Surface color at texture coordinates `uv`, looking textures up in `textures`
+    pub fn surface_at(&self, textures: &[Texture], uv: Vec2f) -> Color {
+        match &self.surface_texture {
+            Some(slot) => self.surface * slot.sample(textures, uv),
+            None => self.surface,
+        }
+    }
+    /// Surface color at the point at world `position` with unit `normal` and texture
+    /// coordinates `uv`, following the texture's mapping
+    pub fn surface_at_point(&self, textures: &[Texture], uv: Vec2f, position: Vec3f, normal: Vec3f) -> Color {
+        match &self.surface_texture {
+            Some(slot) => self.surface * slot.sample_point(textures, uv, position, normal),
+            None => self.surface,
         }
     }
-}
\ No newline at end of file
+    /// Unit normal to shade the point with unit geometric `normal` and texture coordinates
+    /// `uv` with, bent by `bump_texture` and then `normal_texture`.
+    ///
+    /// `dpdu` and `dpdv` are the change of position with u and v at the point, as given by
+    /// `Mesh::uv_derivatives`. Both maps are read at `uv` whatever their mapping, and
+    /// raise the surface towards the side `normal` faces.
+    pub fn shading_normal(&self, textures: &[Texture], uv: Vec2f, normal: Vec3f, dpdu: Vec3f, dpdv: Vec3f) -> Vec3f {
+        let mut shading = normal;
+        if let Some(slot) = &self.bump_texture {
+            // Slopes from the texels next to `uv`
+            let texel = textures.get(slot.texture).map_or((1e-3, 1e-3), |texture| {
+                (1.0 / texture.width().max(1) as f32, 1.0 / texture.height().max(1) as f32)
+            });
+            let height = |uv: Vec2f| slot.sample(textures, uv).luminance() * self.bump_scale;
+            let here = height(uv);
+            let dhdu = (height(uv + Vec2f::from_parts(texel.0, 0.0)) - here) / texel.0;
+            let dhdv = (height(uv + Vec2f::from_parts(0.0, texel.1)) - here) / texel.1;
+            let bumped = Vec3f::cross(dpdu + normal * dhdu, dpdv + normal * dhdv);
+            if bumped.magnitude() > 0.0 {
+                shading = if bumped.dot(normal) < 0.0 { -bumped } else { bumped }.normalize();
+            }
+        }
+        if let Some(slot) = &self.normal_texture {
+            let texel = slot.sample(textures, uv);
+            let [x, y, z] = [texel.r.value(), texel.g.value(), texel.b.value()].map(|value| value * 2.0 - 1.0);
+            let tangent = dpdu - shading * dpdu.dot(shading);
+            if tangent.magnitude() > 0.0 {
+                let tangent = tangent.normalize();
+                let bitangent = Vec3f::cross(shading, tangent);
+                let bitangent = if bitangent.dot(dpdv) < 0.0 { -bitangent } else { bitangent };
+                let mapped = tangent * x + bitangent * y + shading * z;
+                if mapped.magnitude() > 0.0 {
+                    shading = mapped.normalize();
+                }
+            }
+        }
+        shading
+    }
+    /// Index of refraction for light of `wavelength` nanometers, fitting Cauchy's equation
+    /// to `ior` and the Abbe number in `dispersion`
+    pub fn ior_at(&self, wavelength: f32) -> f32 {
+        let abbe = match self.dispersion {
+            Some(abbe) if abbe > 0.0 => abbe,
+            _ => return self.ior,
+        };
+        // Fraunhofer F, d and C lines
+        let (f, d, c) = (486.1f32, 587.6f32, 656.3f32);
+        let b = (self.ior - 1.0) / (abbe * (1.0 / (f * f) - 1.0 / (c * c)));
+        self.ior + b * (1.0 / (wavelength * wavelength) - 1.0 / (d * d))
+    }
+    /// Coverage at texture coordinates `uv`, looking textures up in `textures`
+    pub fn alpha_at(&self, textures: &[Texture], uv: Vec2f) -> f32 {
+        match &self.alpha_texture {
+            Some(slot) => self.alpha * slot.sample(textures, uv).luminance(),
+            None => self.alpha,
+        }
+    }
+    /// Check if the surface is cut away at texture coordinates `uv` by `alpha_cutoff`
+    pub fn is_cut_out(&self, textures: &[Texture], uv: Vec2f) -> bool {
+        self.alpha_cutoff.is_some_and(|cutoff| self.alpha_at(textures, uv) < cutoff)
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Mapping, Material, TextureSlot, UvTransform};
+    use crate::canvas::{Canvas, Color, Filter, Texture, Wrap};
+    use crate::math::{Vec2f, Vec3f};
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_uv_transform() {
+        let transform = UvTransform {
+            offset: Vec2f::from_parts(0.5, 0.0),
+            rotation: std::f32::consts::FRAC_PI_2,
+            scale: Vec2f::from_parts(2.0, 2.0),
+        };
+        let uv = transform.apply(Vec2f::from_parts(0.25, 0.0));
+        assert_approx_eq!(uv.x, 0.5);
+        assert_approx_eq!(uv.y, -0.5);
+        assert_eq!(UvTransform::default().apply(Vec2f::from_parts(0.3, 0.7)), Vec2f::from_parts(0.3, 0.7));
+    }
+
+    #[test]
+    fn test_surface_at() {
+        let mut image = Canvas::new(2, 1);
+        image.set(1, 0, Color::white());
+        let textures = [Texture::new(image)];
+        let mut material = Material::new();
+        material.surface = Color::bright_red();
+        assert_eq!(material.surface_at(&textures, Vec2f::from_parts(0.1, 0.5)), Color::bright_red());
+        let mut slot = TextureSlot::new(0);
+        slot.sampler.filter = Filter::Nearest;
+        slot.sampler.wrap_u = Wrap::ClampToEdge;
+        slot.transform.offset = Vec2f::from_parts(0.5, 0.0);
+        material.surface_texture = Some(slot);
+        // Shifted by half, the left texel now shows the white right one
+        assert_eq!(material.surface_at(&textures, Vec2f::from_parts(0.1, 0.5)), Color::bright_red());
+        assert_eq!(material.surface_at(&textures, Vec2f::from_parts(0.9, 0.5)), Color::bright_red());
+        slot.transform.offset = Vec2f::from_parts(0.0, 0.0);
+        material.surface_texture = Some(slot);
+        assert_eq!(material.surface_at(&textures, Vec2f::from_parts(0.1, 0.5)), Color::black());
+        // A missing texture leaves the surface color
+        material.surface_texture = Some(TextureSlot::new(3));
+        assert_eq!(material.surface_at(&textures, Vec2f::from_parts(0.1, 0.5)), Color::bright_red());
+    }
+
+    #[test]
+    fn test_alpha_cutoff() {
+        let mut image = Canvas::new(2, 1);
+        image.set(1, 0, Color::white());
+        let textures = [Texture::new(image)];
+        let mut material = Material::new();
+        let (left, right) = (Vec2f::from_parts(0.25, 0.5), Vec2f::from_parts(0.75, 0.5));
+        assert!(!material.is_cut_out(&textures, left));
+        let mut slot = TextureSlot::new(0);
+        slot.sampler.filter = Filter::Nearest;
+        material.alpha_texture = Some(slot);
+        material.alpha = 0.8;
+        assert_approx_eq!(material.alpha_at(&textures, right), 0.8);
+        // Without a cutoff the texture only sets coverage
+        assert!(!material.is_cut_out(&textures, left));
+        material.alpha_cutoff = Some(0.5);
+        assert!(material.is_cut_out(&textures, left));
+        assert!(!material.is_cut_out(&textures, right));
+    }
+
+    #[test]
+    fn test_triplanar() {
+        // Black left half, white right half
+        let mut image = Canvas::new(2, 2);
+        image.set(1, 0, Color::white());
+        image.set(1, 1, Color::white());
+        let textures = [Texture::new(image)];
+        let mut slot = TextureSlot::new(0);
+        slot.sampler.filter = Filter::Nearest;
+        slot.mapping = Mapping::Triplanar { scale: 1.0, sharpness: 4.0 };
+        let mut material = Material::new();
+        material.surface_texture = Some(slot);
+        let uv = Vec2f::from_parts(0.0, 0.0);
+        let point = Vec3f::from_parts(0.75, 0.0, 0.25);
+        // Facing up the texture runs along X, facing along X it runs along Z
+        let up = material.surface_at_point(&textures, uv, point, Vec3f::from_parts(0.0, 1.0, 0.0));
+        assert_eq!(up, Color::white());
+        let side = material.surface_at_point(&textures, uv, point, Vec3f::from_parts(1.0, 0.0, 0.0));
+        assert_eq!(side, Color::black());
+        // Halfway between blends both
+        let diagonal = Vec3f::from_parts(1.0, 1.0, 0.0).normalize();
+        let blend = material.surface_at_point(&textures, uv, point, diagonal);
+        assert_approx_eq!(blend.r.value(), 0.5);
+        // Plain UV sampling ignores the mapping
+        assert_eq!(material.surface_at(&textures, Vec2f::from_parts(0.75, 0.0)), Color::white());
+    }
+
+    #[test]
+    fn test_shading_normal() {
+        let (normal, dpdu, dpdv) = (Vec3f::UNIT_Z, Vec3f::UNIT_X * 2.0, Vec3f::UNIT_Y * 2.0);
+        let uv = Vec2f::from_parts(0.25, 0.5);
+        let mut material = Material::new();
+        assert_eq!(material.shading_normal(&[], uv, normal, dpdu, dpdv), normal);
+        // A flat normal map keeps the normal, one leaning along u tilts it along the tangent
+        let solid = |color: Color| {
+            let mut image = Canvas::new(1, 1);
+            image.fill(color);
+            Texture::new(image)
+        };
+        let textures = [solid(Color::new(0.5, 0.5, 1.0)), solid(Color::new(1.0, 0.5, 1.0))];
+        material.normal_texture = Some(TextureSlot::new(0));
+        let shading = material.shading_normal(&textures, uv, normal, dpdu, dpdv);
+        assert_approx_eq!(shading.z, 1.0);
+        material.normal_texture = Some(TextureSlot::new(1));
+        let shading = material.shading_normal(&textures, uv, normal, dpdu, dpdv);
+        assert_approx_eq!(shading.x, 0.5f32.sqrt());
+        assert_approx_eq!(shading.z, 0.5f32.sqrt());
+        // Seen from behind the map bends the flipped normal the same way along u
+        let shading = material.shading_normal(&textures, uv, -normal, dpdu, dpdv);
+        assert_approx_eq!(shading.x, 0.5f32.sqrt());
+        assert_approx_eq!(shading.z, -(0.5f32.sqrt()));
+        // Height rising along u tilts the normal back against u
+        let mut ramp = Canvas::new(4, 1);
+        for x in 0..4 {
+            ramp.set(x, 0, Color::white() * (x as f32 / 4.0));
+        }
+        let mut slot = TextureSlot::new(0);
+        slot.sampler.filter = Filter::Nearest;
+        let textures = [Texture::new(ramp)];
+        let mut material = Material::new();
+        material.bump_texture = Some(slot);
+        material.bump_scale = 2.0;
+        let shading = material.shading_normal(&textures, Vec2f::from_parts(0.4, 0.5), normal, dpdu, dpdv);
+        assert_approx_eq!(shading.magnitude(), 1.0);
+        assert!(shading.x < 0.0 && shading.z > 0.0);
+        assert_approx_eq!(shading.y, 0.0);
+    }
+
+    #[test]
+    fn test_ior_at() {
+        let mut material = Material::new();
+        assert_eq!(material.ior_at(400.0), 1.5);
+        // Dense flint glass
+        material.ior = 1.62;
+        material.dispersion = Some(36.0);
+        assert_approx_eq!(material.ior_at(587.6), 1.62);
+        assert_approx_eq!(material.ior_at(486.1) - material.ior_at(656.3), 0.62 / 36.0);
+        assert!(material.ior_at(400.0) > material.ior_at(700.0));
+    }
+}
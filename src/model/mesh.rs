@@ -14,14 +14,338 @@
 // limitations under the License.
 //
 use super::Triangle;
+use crate::canvas::{Sampler, Texture};
+use crate::math::{OrthonormalBasis, Vec2f, Vec3f};
+use std::collections::HashMap;
 
 #[derive(Clone)]
 pub struct Mesh {
-    triangles: Vec<Triangle>
+    triangles: Vec<Triangle>,
+    uvs: Option<Vec<[Vec2f; 3]>>,
+    normals: Option<Vec<[Vec3f; 3]>>,
+    smoothing_groups: Option<Vec<u32>>,
+    previous: Option<Vec<Triangle>>,
 }
 
 impl Mesh {
     pub fn new(triangles: Vec<Triangle>) -> Mesh {
-        Mesh { triangles }
+        Mesh {
+            triangles,
+            uvs: None,
+            normals: None,
+            smoothing_groups: None,
+            previous: None,
+        }
     }
-}
\ No newline at end of file
+    /// Create new Mesh with texture coordinates for the vertices of each triangle
+    ///
+    /// # Panics
+    /// Panics if `uvs` does not have an entry for every triangle.
+    pub fn with_uvs(triangles: Vec<Triangle>, uvs: Vec<[Vec2f; 3]>) -> Mesh {
+        assert_eq!(triangles.len(), uvs.len(), "mesh needs texture coordinates for every triangle");
+        Mesh {
+            triangles,
+            uvs: Some(uvs),
+            normals: None,
+            smoothing_groups: None,
+            previous: None,
+        }
+    }
+    pub fn triangles(&self) -> &[Triangle] {
+        &self.triangles
+    }
+    /// Triangles as they were in the previous frame, if the mesh deforms
+    pub fn previous_triangles(&self) -> Option<&[Triangle]> {
+        self.previous.as_deref()
+    }
+    /// Set where each triangle was in the previous frame, such as before skinning or
+    /// morphing moved it, for deformation motion blur and motion vectors.
+    ///
+    /// # Panics
+    /// Panics if `triangles` does not have an entry for every triangle.
+    pub fn set_previous_triangles(&mut self, triangles: Vec<Triangle>) {
+        assert_eq!(self.triangles.len(), triangles.len(), "mesh needs previous positions for every triangle");
+        self.previous = Some(triangles);
+    }
+    /// Triangle `triangle` at `time` within the shutter, moving in a straight line from its
+    /// previous position at 0.0 to its current one at 1.0
+    pub fn triangle_at(&self, triangle: usize, time: f32) -> Triangle {
+        let current = self.triangles[triangle];
+        match &self.previous {
+            Some(previous) if time < 1.0 => {
+                let previous = previous[triangle];
+                let lerp = |from: Vec3f, to: Vec3f| from + (to - from) * time;
+                Triangle::new(
+                    lerp(previous.a, current.a),
+                    lerp(previous.b, current.b),
+                    lerp(previous.c, current.c),
+                )
+            }
+            _ => current,
+        }
+    }
+    /// Texture coordinates of the vertices of each triangle, if the mesh has any
+    pub fn uvs(&self) -> Option<&[[Vec2f; 3]]> {
+        self.uvs.as_deref()
+    }
+    /// Texture coordinates of the point with barycentric weights `u` for `b` and `v` for
+    /// `c` on triangle `triangle`, (0, 0) if the mesh has none
+    pub fn uv(&self, triangle: usize, u: f32, v: f32) -> Vec2f {
+        match &self.uvs {
+            Some(uvs) => {
+                let [a, b, c] = uvs[triangle];
+                a * (1.0 - u - v) + b * u + c * v
+            }
+            None => Vec2f::from_parts(0.0, 0.0),
+        }
+    }
+    /// Change of position with texture coordinates u and v across triangle `triangle`,
+    /// the unnormalized tangent and bitangent that normal and bump maps are applied along.
+    ///
+    /// Triangles without texture coordinates, or whose coordinates do not span an area,
+    /// get an arbitrary unit basis around their face normal.
+    pub fn uv_derivatives(&self, triangle: usize) -> (Vec3f, Vec3f) {
+        let t = &self.triangles[triangle];
+        if let Some(uvs) = &self.uvs {
+            let [a, b, c] = uvs[triangle];
+            let (duv1, duv2) = (b - a, c - a);
+            let determinant = duv1.x * duv2.y - duv2.x * duv1.y;
+            if determinant != 0.0 {
+                let (e1, e2) = (t.b - t.a, t.c - t.a);
+                let dpdu = (e1 * duv2.y - e2 * duv1.y) * (1.0 / determinant);
+                let dpdv = (e2 * duv1.x - e1 * duv2.x) * (1.0 / determinant);
+                return (dpdu, dpdv);
+            }
+        }
+        let basis = OrthonormalBasis::from_normal(face_normal(t).normalize());
+        (basis.tangent, basis.bitangent)
+    }
+    /// Vertex normals of each triangle, if they were computed
+    pub fn normals(&self) -> Option<&[[Vec3f; 3]]> {
+        self.normals.as_deref()
+    }
+    /// Normal at the point with barycentric weights `u` for `b` and `v` for `c` on
+    /// triangle `triangle`, the face normal if the mesh has no vertex normals
+    pub fn normal(&self, triangle: usize, u: f32, v: f32) -> Vec3f {
+        match &self.normals {
+            Some(normals) => {
+                let [a, b, c] = normals[triangle];
+                (a * (1.0 - u - v) + b * u + c * v).normalize()
+            }
+            None => face_normal(&self.triangles[triangle]).normalize(),
+        }
+    }
+    /// Set the vertex normals of each triangle, such as analytic normals of a generated
+    /// surface.
+    ///
+    /// # Panics
+    /// Panics if `normals` does not have an entry for every triangle.
+    pub fn set_normals(&mut self, normals: Vec<[Vec3f; 3]>) {
+        assert_eq!(self.triangles.len(), normals.len(), "mesh needs normals for every triangle");
+        self.normals = Some(normals);
+    }
+    /// Smoothing group of each triangle, if the mesh has them
+    pub fn smoothing_groups(&self) -> Option<&[u32]> {
+        self.smoothing_groups.as_deref()
+    }
+    /// Set the smoothing group of each triangle, as given by `s` lines in OBJ files.
+    ///
+    /// Normals are only averaged between triangles in the same group, triangles in group 0
+    /// are shaded flat.
+    ///
+    /// # Panics
+    /// Panics if `groups` does not have an entry for every triangle.
+    pub fn set_smoothing_groups(&mut self, groups: Vec<u32>) {
+        assert_eq!(self.triangles.len(), groups.len(), "mesh needs a smoothing group for every triangle");
+        self.smoothing_groups = Some(groups);
+    }
+    /// Compute smooth vertex normals, averaging the faces around each vertex weighted by
+    /// their angle at the vertex, so how a face is split into triangles does not matter.
+    ///
+    /// Vertices of different triangles at exactly the same position count as one.
+    pub fn compute_normals(&mut self) {
+        self.compute_normals_with_crease(std::f32::consts::PI);
+    }
+    /// Compute vertex normals like `compute_normals`, but split them along edges where the
+    /// faces meet at more than `crease_angle` radians, so a cube stays sharp while a sphere
+    /// shades smoothly.
+    ///
+    /// Smoothing groups, if set, also split normals between groups.
+    pub fn compute_normals_with_crease(&mut self, crease_angle: f32) {
+        let faces: Vec<Vec3f> = self
+            .triangles
+            .iter()
+            .map(|triangle| {
+                let normal = face_normal(triangle);
+                if normal.magnitude() > 0.0 { normal.normalize() } else { normal }
+            })
+            .collect();
+        // Faces around each vertex with their angle at it
+        let mut corners: HashMap<[u32; 3], Vec<(usize, f32)>> = HashMap::new();
+        for (index, triangle) in self.triangles.iter().enumerate() {
+            for &(vertex, next, previous) in &[
+                (triangle.a, triangle.b, triangle.c),
+                (triangle.b, triangle.c, triangle.a),
+                (triangle.c, triangle.a, triangle.b),
+            ] {
+                let cosine = (next - vertex).normalize().dot((previous - vertex).normalize());
+                corners.entry(key(vertex)).or_default().push((index, cosine.clamp(-1.0, 1.0).acos()));
+            }
+        }
+        let threshold = crease_angle.min(std::f32::consts::PI).cos() - 1e-6;
+        let groups = self.smoothing_groups.as_deref();
+        let smooths_with = |index: usize, other: usize| {
+            let grouped = groups.is_none_or(|groups| groups[index] != 0 && groups[index] == groups[other]);
+            other == index || (grouped && faces[index].dot(faces[other]) >= threshold)
+        };
+        let normal = |index: usize, vertex: Vec3f| {
+            let mut sum = Vec3f::default();
+            for &(other, angle) in &corners[&key(vertex)] {
+                if smooths_with(index, other) {
+                    sum += faces[other] * angle;
+                }
+            }
+            if sum.magnitude() > 0.0 { sum.normalize() } else { faces[index] }
+        };
+        let normals = self
+            .triangles
+            .iter()
+            .enumerate()
+            .map(|(index, t)| [normal(index, t.a), normal(index, t.b), normal(index, t.c)])
+            .collect();
+        self.normals = Some(normals);
+    }
+    /// Move every vertex along its smooth normal by `height(position, normal, uv)`, then
+    /// recompute the normals.
+    ///
+    /// Vertices shared by several triangles move together so the surface stays closed;
+    /// where they have different texture coordinates, those of the first triangle using
+    /// the vertex are passed to `height`.
+    pub fn displace<F: Fn(Vec3f, Vec3f, Vec2f) -> f32>(&mut self, height: F) {
+        self.compute_normals();
+        let normals = self.normals.take().unwrap_or_default();
+        let mut moved: HashMap<[u32; 3], Vec3f> = HashMap::new();
+        for (index, triangle) in self.triangles.iter().enumerate() {
+            let uvs = self.uvs.as_ref().map_or([Vec2f::default(); 3], |uvs| uvs[index]);
+            let vertices = [triangle.a, triangle.b, triangle.c];
+            for corner in 0..3 {
+                let (vertex, normal) = (vertices[corner], normals[index][corner]);
+                moved
+                    .entry(key(vertex))
+                    .or_insert_with(|| vertex + normal * height(vertex, normal, uvs[corner]));
+            }
+        }
+        for triangle in &mut self.triangles {
+            *triangle = Triangle::new(moved[&key(triangle.a)], moved[&key(triangle.b)], moved[&key(triangle.c)]);
+        }
+        self.compute_normals();
+    }
+    /// Displace vertices along their normals by the luminance of `heightmap` at their
+    /// texture coordinates times `scale`, see `displace`.
+    pub fn displace_texture(&mut self, heightmap: &Texture, sampler: &Sampler, scale: f32) {
+        self.displace(|_, _, uv| heightmap.sample(uv, sampler).luminance() * scale);
+    }
+}
+
+/// Normal of `triangle` with a length of twice its area
+fn face_normal(triangle: &Triangle) -> Vec3f {
+    Vec3f::cross(triangle.b - triangle.a, triangle.c - triangle.a)
+}
+
+/// Exact position of a vertex, for finding vertices shared between triangles
+fn key(vertex: Vec3f) -> [u32; 3] {
+    // Treat -0.0 and 0.0 as the same position
+    let bits = |v: f32| (v + 0.0).to_bits();
+    [bits(vertex.x), bits(vertex.y), bits(vertex.z)]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::{Canvas, Color, Filter, Sampler, Texture};
+    use crate::math::{Vec2f, Vec3f};
+    use crate::model::{cube, Mesh, Triangle};
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_compute_normals() {
+        let mut mesh = cube(2.0);
+        assert!(mesh.normals().is_none());
+        assert_eq!(mesh.normal(0, 0.2, 0.2), Vec3f::from_parts(0.0, 0.0, -1.0));
+        mesh.compute_normals();
+        // Corners point away from the center
+        let corner = mesh.normals().unwrap()[0][0];
+        assert_approx_eq!(corner.magnitude(), 1.0, 1e-5);
+        assert!(corner.x < 0.0 && corner.y < 0.0 && corner.z < 0.0);
+    }
+
+    #[test]
+    fn test_crease() {
+        let mut mesh = cube(2.0);
+        // Faces of a cube meet at 90 degrees, so a smaller crease keeps them flat
+        mesh.compute_normals_with_crease(80f32.to_radians());
+        assert_eq!(mesh.normals().unwrap()[0][0], Vec3f::from_parts(0.0, 0.0, -1.0));
+        mesh.compute_normals_with_crease(100f32.to_radians());
+        assert!(mesh.normals().unwrap()[0][0].x < 0.0);
+        // Smoothing groups split the smooth normals too
+        let mut groups = vec![1; 12];
+        groups[0] = 2;
+        groups[1] = 2;
+        mesh.set_smoothing_groups(groups);
+        mesh.compute_normals();
+        assert_eq!(mesh.normals().unwrap()[0][0], Vec3f::from_parts(0.0, 0.0, -1.0));
+        assert!(mesh.normals().unwrap()[2][0].x > 0.0 && mesh.normals().unwrap()[2][0].z == 0.0);
+        mesh.set_smoothing_groups(vec![0; 12]);
+        mesh.compute_normals();
+        assert_eq!(mesh.normals().unwrap()[3][1], Vec3f::from_parts(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_uv_derivatives() {
+        // U runs along X over 2 units, V along Z over 4
+        let p = |x: f32, z: f32| Vec3f::from_parts(x, 0.0, z);
+        let t = |u: f32, v: f32| Vec2f::from_parts(u, v);
+        let mesh = Mesh::with_uvs(
+            vec![Triangle::new(p(0.0, 0.0), p(0.0, 4.0), p(2.0, 0.0))],
+            vec![[t(0.0, 0.0), t(0.0, 1.0), t(1.0, 0.0)]],
+        );
+        let (dpdu, dpdv) = mesh.uv_derivatives(0);
+        assert_eq!(dpdu, Vec3f::from_parts(2.0, 0.0, 0.0));
+        assert_eq!(dpdv, Vec3f::from_parts(0.0, 0.0, 4.0));
+        // Without texture coordinates any basis around the normal will do
+        let (dpdu, dpdv) = cube(2.0).uv_derivatives(0);
+        assert_approx_eq!(dpdu.dot(Vec3f::UNIT_Z), 0.0);
+        assert_approx_eq!(dpdv.dot(Vec3f::UNIT_Z), 0.0);
+        assert_approx_eq!(Vec3f::cross(dpdu, dpdv).magnitude(), 1.0);
+    }
+
+    #[test]
+    fn test_displace() {
+        let mut mesh = cube(2.0);
+        mesh.displace(|_, _, _| 1.0);
+        // Every corner moved out along its diagonal and stays shared
+        for triangle in mesh.triangles() {
+            for vertex in &[triangle.a, triangle.b, triangle.c] {
+                assert_approx_eq!(vertex.magnitude(), 3f32.sqrt() + 1.0, 1e-5);
+            }
+        }
+        assert!(mesh.normals().is_some());
+    }
+
+    #[test]
+    fn test_displace_texture() {
+        // Flat quad raised where the heightmap is white
+        let p = |x: f32, z: f32| Vec3f::from_parts(x, 0.0, z);
+        let t = |u: f32, v: f32| Vec2f::from_parts(u, v);
+        let mut mesh = Mesh::with_uvs(
+            vec![Triangle::new(p(0.0, 0.0), p(0.0, 1.0), p(1.0, 0.0))],
+            vec![[t(0.25, 0.5), t(0.25, 0.5), t(0.75, 0.5)]],
+        );
+        let mut image = Canvas::new(2, 1);
+        image.set(1, 0, Color::white());
+        let sampler = Sampler { filter: Filter::Nearest, ..Sampler::default() };
+        mesh.displace_texture(&Texture::new(image), &sampler, 2.0);
+        let triangle = mesh.triangles()[0];
+        assert_eq!(triangle.a.y, 0.0);
+        assert_approx_eq!(triangle.c.y, 2.0);
+    }
+}
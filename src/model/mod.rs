@@ -13,16 +13,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 //
+mod csg;
+mod light;
 mod material;
 mod mesh;
+mod resources;
+mod scene;
 mod shapes;
 mod sphere;
+mod stats;
 mod triangle;
+mod water;
 
 use super::math::Vec3f;
-pub use self::material::Material;
+pub use self::light::{PointLight, SphereLight, ALL_LINKS, LUMINOUS_EFFICACY};
+pub use self::material::{BackfaceMode, Mapping, Material, TextureSlot, UvTransform};
 pub use self::mesh::Mesh;
-pub use self::shapes::cube;
+pub use self::resources::{ResourceManager, ResourceStats};
+pub use self::scene::{Instance, RayKind, Scene, SceneBuilder, Visibility};
+pub use self::shapes::{cube, sphere, SphereTessellation};
 pub use self::sphere::Sphere;
-pub use self::triangle::Triangle;
+pub use self::stats::{MeshStats, SceneStats};
+pub use self::triangle::{Triangle, TriangleHit, TriangleIntersector};
+pub use self::water::{GerstnerWave, Water};
 
@@ -0,0 +1,221 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::Scene;
+use crate::canvas::StreamingTexture;
+use crate::Result;
+
+/// Memory use and streaming activity reported by `ResourceManager::update`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResourceStats {
+    /// Bytes the manager tries to stay under
+    pub budget: usize,
+    /// Bytes of scene meshes and textures, which are always in memory
+    pub fixed: usize,
+    /// Bytes of streaming texture levels in memory
+    pub streamed: usize,
+    /// Streaming textures sampled since the previous update
+    pub textures_used: usize,
+    /// Mip levels loaded this update
+    pub levels_loaded: usize,
+    /// Mip levels evicted this update
+    pub levels_evicted: usize,
+    /// Bytes freed by evictions this update
+    pub bytes_evicted: usize,
+}
+
+impl ResourceStats {
+    /// Total bytes in memory
+    pub fn total(&self) -> usize {
+        self.fixed + self.streamed
+    }
+    /// Check if memory use is still over budget after evicting everything not in use
+    pub fn is_over_budget(&self) -> bool {
+        self.total() > self.budget
+    }
+}
+
+struct Entry {
+    texture: StreamingTexture,
+    last_used: u64,
+}
+
+///
+/// Owner of the streamed assets of a scene, keeping their memory together with that of
+/// the scene within a budget.
+///
+/// Each `update` streams in the levels sampling asked for, then while over budget drops
+/// the largest resident level of the least recently used texture. Textures sampled since
+/// the previous update are never evicted, so a budget too small for a single frame's
+/// working set shows up as `ResourceStats::is_over_budget` rather than thrashing.
+///
+/// ```
+/// use softrender::canvas::{Canvas, StreamingTexture, Texture};
+/// use softrender::model::{ResourceManager, Scene};
+///
+/// let mut resources = ResourceManager::new(64 * 1024 * 1024);
+/// let source = Texture::new(Canvas::new(256, 256));
+/// let id = resources.add_texture(StreamingTexture::new(Box::new(source), 32).unwrap());
+/// // Render, sampling through resources.texture(id), then between frames:
+/// let stats = resources.update(&Scene::new()).unwrap();
+/// assert!(!stats.is_over_budget());
+/// ```
+///
+pub struct ResourceManager {
+    budget: usize,
+    frame: u64,
+    textures: Vec<Entry>,
+    stats: ResourceStats,
+}
+
+impl ResourceManager {
+    /// Create new ResourceManager keeping memory under `budget` bytes
+    pub fn new(budget: usize) -> ResourceManager {
+        ResourceManager {
+            budget,
+            frame: 0,
+            textures: Vec::new(),
+            stats: ResourceStats {
+                budget,
+                ..ResourceStats::default()
+            },
+        }
+    }
+    /// Get the budget in bytes
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+    /// Set the budget in bytes, applied from the next `update`
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+    }
+    /// Add a streaming texture, returning its id
+    pub fn add_texture(&mut self, texture: StreamingTexture) -> usize {
+        self.textures.push(Entry {
+            texture,
+            last_used: self.frame,
+        });
+        self.textures.len() - 1
+    }
+    /// Get streaming texture `id` for sampling
+    pub fn texture(&self, id: usize) -> &StreamingTexture {
+        &self.textures[id].texture
+    }
+    /// Number of streaming textures
+    pub fn texture_count(&self) -> usize {
+        self.textures.len()
+    }
+    /// Figures from the last `update`
+    pub fn stats(&self) -> ResourceStats {
+        self.stats
+    }
+    /// Stream in the levels asked for since the last update and evict least recently used
+    /// levels until memory use, including that of `scene`, fits the budget.
+    ///
+    /// Every texture is updated even if one fails to load, the first error is returned.
+    pub fn update(&mut self, scene: &Scene) -> Result<ResourceStats> {
+        self.frame += 1;
+        let mut stats = ResourceStats {
+            budget: self.budget,
+            fixed: scene.stats().memory,
+            ..ResourceStats::default()
+        };
+        let mut result = Ok(());
+        for entry in &mut self.textures {
+            if entry.texture.requested_level().is_some() {
+                entry.last_used = self.frame;
+                stats.textures_used += 1;
+            }
+            let before = entry.texture.resident_level();
+            let loaded = entry.texture.update();
+            stats.levels_loaded += before - entry.texture.resident_level();
+            if let (Err(error), Ok(())) = (loaded, &result) {
+                result = Err(error);
+            }
+        }
+        stats.streamed = self.streamed();
+        while stats.total() > self.budget {
+            let frame = self.frame;
+            let victim = self
+                .textures
+                .iter_mut()
+                .filter(|entry| entry.last_used < frame)
+                .filter(|entry| entry.texture.resident_level() + 1 < entry.texture.level_count())
+                .min_by_key(|entry| entry.last_used);
+            let entry = match victim {
+                Some(entry) => entry,
+                None => break,
+            };
+            let level = entry.texture.resident_level() + 1;
+            let freed = entry.texture.evict(level);
+            stats.levels_evicted += 1;
+            stats.bytes_evicted += freed;
+            stats.streamed -= freed;
+        }
+        self.stats = stats;
+        result.map(|()| stats)
+    }
+    fn streamed(&self) -> usize {
+        self.textures.iter().map(|entry| entry.texture.memory()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResourceManager;
+    use crate::canvas::{Canvas, Sampler, StreamingTexture, Texture};
+    use crate::math::Vec2f;
+    use crate::model::{cube, Scene};
+
+    fn streaming(size: usize) -> StreamingTexture {
+        StreamingTexture::new(Box::new(Texture::new(Canvas::new(size, size))), 1).unwrap()
+    }
+
+    #[test]
+    fn test_budget() {
+        // Room for one fully loaded 32x32 texture and most of another
+        let budget = 32 * 32 * 12 * 2;
+        let mut resources = ResourceManager::new(budget);
+        let a = resources.add_texture(streaming(32));
+        let b = resources.add_texture(streaming(32));
+        let uv = Vec2f::from_parts(0.5, 0.5);
+        let sampler = Sampler::default();
+        resources.texture(a).sample_lod(uv, 0.0, &sampler);
+        let stats = resources.update(&Scene::new()).unwrap();
+        assert_eq!((stats.textures_used, stats.levels_loaded, stats.levels_evicted), (1, 5, 0));
+        assert_eq!(resources.texture(a).resident_level(), 0);
+        assert!(!stats.is_over_budget());
+        // Loading b pushes a, now the least recently used, out
+        resources.texture(b).sample_lod(uv, 0.0, &sampler);
+        let stats = resources.update(&Scene::new()).unwrap();
+        assert_eq!(stats.levels_evicted, 1);
+        assert_eq!(stats.bytes_evicted, 32 * 32 * 12);
+        assert_eq!(resources.texture(a).resident_level(), 1);
+        assert_eq!(resources.texture(b).resident_level(), 0);
+        assert_eq!(stats.total(), stats.streamed);
+        assert_eq!(resources.stats(), stats);
+        // Scene memory counts against the budget, textures in use are kept regardless
+        let mut scene = Scene::new();
+        scene.add_mesh(cube(1.0));
+        resources.set_budget(0);
+        resources.texture(b).sample_lod(uv, 0.0, &sampler);
+        let stats = resources.update(&scene).unwrap();
+        assert!(stats.fixed > 0);
+        assert!(stats.is_over_budget());
+        assert_eq!(resources.texture(a).resident_level(), 5);
+        assert_eq!(resources.texture(b).resident_level(), 0);
+    }
+}
@@ -0,0 +1,346 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Material, Mesh, PointLight, SphereLight};
+use crate::canvas::Texture;
+use crate::math::{ClipPlanes, Mat4f};
+
+/// Kind of ray traced through a `Scene`, deciding which instances it sees
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RayKind {
+    /// Ray leaving the camera
+    Camera,
+    /// Ray testing if a light reaches a point
+    Shadow,
+    /// Ray leaving a surface, for reflections, refractions and indirect light
+    Reflection,
+}
+
+///
+/// Kinds of rays an instance shows up to.
+///
+/// Clearing flags allows tricks such as a shadow only proxy, casting the shadow of an
+/// object that is not there, or a light blocker the camera does not see.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Visibility {
+    /// Seen by camera rays
+    pub camera: bool,
+    /// Blocks light, casting shadows
+    pub shadow: bool,
+    /// Seen in reflections and refractions and bounces light onto other surfaces
+    pub reflection: bool,
+}
+
+impl Visibility {
+    /// Visible to every kind of ray
+    pub const ALL: Visibility = Visibility {
+        camera: true,
+        shadow: true,
+        reflection: true,
+    };
+    /// Only casting shadows
+    pub const SHADOW_ONLY: Visibility = Visibility {
+        camera: false,
+        shadow: true,
+        reflection: false,
+    };
+    /// Check if rays of kind `kind` see the instance
+    pub fn visible_to(&self, kind: RayKind) -> bool {
+        match kind {
+            RayKind::Camera => self.camera,
+            RayKind::Shadow => self.shadow,
+            RayKind::Reflection => self.reflection,
+        }
+    }
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Visibility::ALL
+    }
+}
+
+/// Placement of a mesh in a `Scene`, several instances may share one mesh
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Instance {
+    /// Index of the mesh in `Scene::meshes`
+    pub mesh: usize,
+    /// Index of the material in `Scene::materials`
+    pub material: usize,
+    /// Model to world transform, applied like `Vec3f * Mat4f`
+    pub transform: Mat4f,
+    /// Light linking groups the instance belongs to as bits, 1 by default.
+    ///
+    /// Lights only shine on instances sharing a group with their `links`, which start out
+    /// covering every group. To light only some instances with a light, put them in a
+    /// group of their own as well and limit the light to it; to keep a light off some
+    /// instances, take a group away from the light and move the instances to just that
+    /// group.
+    pub light_links: u32,
+    /// Kinds of rays the instance shows up to, every kind by default
+    pub visibility: Visibility,
+    /// Model to world transform in the previous frame for transformation motion blur,
+    /// `None` if the instance holds still
+    pub previous_transform: Option<Mat4f>,
+}
+
+impl Instance {
+    /// Model to world transform at `time` within the shutter, blending the matrices from
+    /// `previous_transform` at 0.0 to `transform` at 1.0.
+    ///
+    /// Blending matrices is exact for translation and scaling, fast rotations shrink
+    /// towards the middle of the shutter.
+    pub fn transform_at(&self, time: f32) -> Mat4f {
+        match self.previous_transform {
+            Some(previous) if time < 1.0 => previous + (self.transform - previous) * time,
+            _ => self.transform,
+        }
+    }
+}
+
+///
+/// Meshes, materials, textures and lights making up what is rendered
+///
+#[derive(Clone, Default)]
+pub struct Scene {
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+    pub textures: Vec<Texture>,
+    pub instances: Vec<Instance>,
+    pub lights: Vec<PointLight>,
+    /// Lights with a size, only seen by the path tracer
+    pub area_lights: Vec<SphereLight>,
+    /// User clip planes cutting the scene open for the Raytracer, which ignores every
+    /// surface on their clipped side. The Rasterizer takes its planes from
+    /// `Camera::clip_planes`.
+    pub clip_planes: ClipPlanes,
+}
+
+impl Scene {
+    /// Create new empty Scene
+    pub fn new() -> Scene {
+        Scene::default()
+    }
+    /// Start building a Scene with a `SceneBuilder`
+    pub fn builder() -> SceneBuilder {
+        SceneBuilder::default()
+    }
+    /// Add `mesh`, returning its index for instances
+    pub fn add_mesh(&mut self, mesh: Mesh) -> usize {
+        self.meshes.push(mesh);
+        self.meshes.len() - 1
+    }
+    /// Add `material`, returning its index for instances
+    pub fn add_material(&mut self, material: Material) -> usize {
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+    /// Add `texture`, returning its index for texture slots
+    pub fn add_texture(&mut self, texture: Texture) -> usize {
+        self.textures.push(texture);
+        self.textures.len() - 1
+    }
+    /// Place mesh `mesh` with material `material` at `transform`, returning the index of the
+    /// instance
+    pub fn add_instance(&mut self, mesh: usize, material: usize, transform: Mat4f) -> usize {
+        self.instances.push(Instance {
+            mesh,
+            material,
+            transform,
+            light_links: 1,
+            visibility: Visibility::ALL,
+            previous_transform: None,
+        });
+        self.instances.len() - 1
+    }
+    /// Add `light`
+    pub fn add_light(&mut self, light: PointLight) {
+        self.lights.push(light);
+    }
+    /// Add `light` to the area lights
+    pub fn add_area_light(&mut self, light: SphereLight) {
+        self.area_lights.push(light);
+    }
+}
+
+/// Instance a `SceneBuilder` is still configuring
+#[derive(Copy, Clone, Debug)]
+struct PendingInstance {
+    mesh: usize,
+    material: Option<usize>,
+    transform: Mat4f,
+    visibility: Visibility,
+}
+
+///
+/// Builds a `Scene` a few lines at a time, for tests, examples and small demos.
+///
+/// Every `mesh` or `instance` call starts a new instance, which the following `transform`,
+/// `material` and `visibility` calls configure. Instances start at the origin, visible to
+/// every ray, and share a default `Material::new()` unless given one of their own.
+///
+/// ```
+/// use softrender::math::{Mat4f, Vec3f};
+/// use softrender::model::{cube, Material, PointLight, Scene};
+///
+/// let scene = Scene::builder()
+///     .mesh(cube(1.0))
+///     .transform(Mat4f::translation(Vec3f::new(-2.0, 0.0, 0.0)))
+///     .instance(0)
+///     .transform(Mat4f::translation(Vec3f::new(2.0, 0.0, 0.0)))
+///     .material(Material::new())
+///     .light(PointLight::new(Vec3f::new(0.0, 4.0, 0.0), 20.0))
+///     .build();
+/// assert_eq!(scene.meshes.len(), 1);
+/// assert_eq!(scene.instances.len(), 2);
+/// assert_eq!(scene.materials.len(), 2);
+/// ```
+///
+#[derive(Clone, Default)]
+pub struct SceneBuilder {
+    scene: Scene,
+    pending: Option<PendingInstance>,
+    default_material: Option<usize>,
+}
+
+impl SceneBuilder {
+    /// Add `mesh` and start an instance of it
+    pub fn mesh(mut self, mesh: Mesh) -> Self {
+        let mesh = self.scene.add_mesh(mesh);
+        self.instance(mesh)
+    }
+    /// Start another instance of the mesh at index `mesh`, counting meshes in the order
+    /// they were added.
+    ///
+    /// # Panics
+    /// Panics if no mesh was added at `mesh`.
+    pub fn instance(mut self, mesh: usize) -> Self {
+        assert!(mesh < self.scene.meshes.len(), "no mesh at index {}", mesh);
+        self.finish_instance();
+        self.pending = Some(PendingInstance {
+            mesh,
+            material: None,
+            transform: Mat4f::identity(),
+            visibility: Visibility::ALL,
+        });
+        self
+    }
+    /// Place the current instance at `transform`
+    ///
+    /// # Panics
+    /// Panics if no instance was started.
+    pub fn transform(mut self, transform: Mat4f) -> Self {
+        self.current().transform = transform;
+        self
+    }
+    /// Add `material` and give it to the current instance
+    ///
+    /// # Panics
+    /// Panics if no instance was started.
+    pub fn material(mut self, material: Material) -> Self {
+        let material = self.scene.add_material(material);
+        self.current().material = Some(material);
+        self
+    }
+    /// Show the current instance only to the rays `visibility` allows
+    ///
+    /// # Panics
+    /// Panics if no instance was started.
+    pub fn visibility(mut self, visibility: Visibility) -> Self {
+        self.current().visibility = visibility;
+        self
+    }
+    /// Add `texture`, which materials refer to by the order textures were added
+    pub fn texture(mut self, texture: Texture) -> Self {
+        self.scene.add_texture(texture);
+        self
+    }
+    /// Add the point light `light`
+    pub fn light(mut self, light: PointLight) -> Self {
+        self.scene.add_light(light);
+        self
+    }
+    /// Add the area light `light`
+    pub fn area_light(mut self, light: SphereLight) -> Self {
+        self.scene.add_area_light(light);
+        self
+    }
+    /// Finish the Scene
+    pub fn build(mut self) -> Scene {
+        self.finish_instance();
+        self.scene
+    }
+    fn current(&mut self) -> &mut PendingInstance {
+        self.pending.as_mut().expect("no instance started, add a mesh first")
+    }
+    /// Add the pending instance to the scene, with the default material if it has none
+    fn finish_instance(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            let material = match (pending.material, self.default_material) {
+                (Some(material), _) | (None, Some(material)) => material,
+                (None, None) => {
+                    let material = self.scene.add_material(Material::new());
+                    self.default_material = Some(material);
+                    material
+                }
+            };
+            let instance = self.scene.add_instance(pending.mesh, material, pending.transform);
+            self.scene.instances[instance].visibility = pending.visibility;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scene, Visibility};
+    use crate::math::{Mat4f, Vec3f};
+    use crate::model::{cube, Material, PointLight, SphereLight};
+
+    #[test]
+    fn test_builder() {
+        let mut glass = Material::new();
+        glass.transparency = 1.0;
+        let shift = Mat4f::translation(Vec3f::new(0.0, 1.0, 0.0));
+        let scene = Scene::builder()
+            .mesh(cube(1.0))
+            .mesh(cube(2.0))
+            .transform(shift)
+            .material(glass)
+            .instance(0)
+            .visibility(Visibility::SHADOW_ONLY)
+            .light(PointLight::new(Vec3f::new(0.0, 4.0, 0.0), 20.0))
+            .area_light(SphereLight::new(Vec3f::new(0.0, 4.0, 4.0), 0.5, 100.0))
+            .build();
+        assert_eq!(scene.meshes.len(), 2);
+        assert_eq!((scene.lights.len(), scene.area_lights.len()), (1, 1));
+        let instances: Vec<(usize, usize)> = scene.instances.iter().map(|i| (i.mesh, i.material)).collect();
+        // Instances without a material of their own share the default one
+        assert_eq!(instances, vec![(0, 0), (1, 1), (0, 0)]);
+        assert_eq!(scene.materials[0], Material::new());
+        assert_eq!(scene.materials[1].transparency, 1.0);
+        assert_eq!(scene.instances[0].transform, Mat4f::identity());
+        assert_eq!(scene.instances[1].transform, shift);
+        assert_eq!(scene.instances[2].visibility, Visibility::SHADOW_ONLY);
+        assert_eq!(scene.instances[1].visibility, Visibility::ALL);
+    }
+
+    #[test]
+    #[should_panic(expected = "add a mesh first")]
+    fn test_builder_without_instance() {
+        Scene::builder().material(Material::new());
+    }
+}
@@ -16,18 +16,39 @@
 use super::Mesh;
 use super::Triangle;
 use super::Vec3f;
+use crate::math::Vec2f;
+use std::f32::consts::PI;
+
+/// How `sphere` divides the surface of a sphere into triangles
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SphereTessellation {
+    /// Grid of `segments` slices around the vertical axis by `rings` stacks from pole to
+    /// pole, with texture coordinates following longitude and latitude. Triangles crowd
+    /// together towards the poles.
+    Uv { segments: usize, rings: usize },
+    /// Icosahedron with each triangle split into four `subdivisions` times, giving
+    /// triangles of nearly even size over the whole sphere. Has 20 * 4^`subdivisions`
+    /// triangles.
+    Icosphere { subdivisions: usize },
+}
+
+impl Default for SphereTessellation {
+    fn default() -> Self {
+        SphereTessellation::Uv { segments: 32, rings: 16 }
+    }
+}
 
 pub fn cube(size: f32) -> Mesh {
     let rad = size / 2.0;
     // Front
-    let swb = Vec3f::new(-rad, -rad, -rad);
-    let swt = Vec3f::new(-rad, rad, -rad);
-    let seb = Vec3f::new(rad, -rad, -rad);
-    let set = Vec3f::new(rad, rad, -rad);
-    let nwb = Vec3f::new(-rad, -rad, rad);
-    let nwt = Vec3f::new(-rad, rad, rad);
-    let neb = Vec3f::new(rad, -rad, rad);
-    let net = Vec3f::new(rad, rad, rad);
+    let swb = Vec3f::from_parts(-rad, -rad, -rad);
+    let swt = Vec3f::from_parts(-rad, rad, -rad);
+    let seb = Vec3f::from_parts(rad, -rad, -rad);
+    let set = Vec3f::from_parts(rad, rad, -rad);
+    let nwb = Vec3f::from_parts(-rad, -rad, rad);
+    let nwt = Vec3f::from_parts(-rad, rad, rad);
+    let neb = Vec3f::from_parts(rad, -rad, rad);
+    let net = Vec3f::from_parts(rad, rad, rad);
     Mesh::new(
         vec!(
             // South
@@ -51,3 +72,208 @@ pub fn cube(size: f32) -> Mesh {
         )
     )
 }
+
+///
+/// Sphere of `radius` centered on the origin with smooth normals and texture coordinates.
+///
+/// Texture coordinates map longitude to `u` and latitude to `v`, with `v` 0 at the top
+/// pole. Triangles crossing the seam at `u` = 0 run past 1 rather than wrapping, and the
+/// pole vertex of each triangle touching a pole takes the `u` of the middle of its
+/// triangle, so textures neither smear across the seam nor twist at the poles.
+///
+/// ```
+/// use softrender::model::{sphere, SphereTessellation};
+///
+/// let uv = sphere(1.0, SphereTessellation::Uv { segments: 8, rings: 4 });
+/// assert_eq!(uv.triangles().len(), 8 * 2 + 8 * 2 * 2);
+/// let ico = sphere(1.0, SphereTessellation::Icosphere { subdivisions: 2 });
+/// assert_eq!(ico.triangles().len(), 320);
+/// ```
+///
+pub fn sphere(radius: f32, tessellation: SphereTessellation) -> Mesh {
+    let directions = match tessellation {
+        SphereTessellation::Uv { segments, rings } => uv_sphere(segments.max(3), rings.max(2)),
+        SphereTessellation::Icosphere { subdivisions } => icosphere(subdivisions),
+    };
+    let mut triangles = Vec::with_capacity(directions.len());
+    let mut uvs = Vec::with_capacity(directions.len());
+    let mut normals = Vec::with_capacity(directions.len());
+    for [a, b, c] in directions {
+        triangles.push(Triangle::new(a * radius, b * radius, c * radius));
+        uvs.push(sphere_uvs([a, b, c]));
+        normals.push([a, b, c]);
+    }
+    let mut mesh = Mesh::with_uvs(triangles, uvs);
+    mesh.set_normals(normals);
+    mesh
+}
+
+/// Point of the unit sphere at longitude `u` and latitude `v`, both from 0 to 1
+fn sphere_point(u: f32, v: f32) -> Vec3f {
+    let (theta, phi) = (v * PI, u * 2.0 * PI);
+    Vec3f::new(theta.sin() * phi.cos(), theta.cos(), -theta.sin() * phi.sin())
+}
+
+/// Triangles of a unit UV sphere, wound counter-clockwise seen from outside
+fn uv_sphere(segments: usize, rings: usize) -> Vec<[Vec3f; 3]> {
+    let point = |segment: usize, ring: usize| {
+        sphere_point(segment as f32 / segments as f32, ring as f32 / rings as f32)
+    };
+    let mut triangles = Vec::with_capacity(segments * (rings - 1) * 2);
+    for segment in 0..segments {
+        // The rings next to the poles are fans of single triangles
+        triangles.push([Vec3f::UNIT_Y, point(segment, 1), point(segment + 1, 1)]);
+        for ring in 1..rings - 1 {
+            let (top_left, top_right) = (point(segment, ring), point(segment + 1, ring));
+            let (bottom_left, bottom_right) = (point(segment, ring + 1), point(segment + 1, ring + 1));
+            triangles.push([top_left, bottom_left, top_right]);
+            triangles.push([top_right, bottom_left, bottom_right]);
+        }
+        triangles.push([point(segment, rings - 1), -Vec3f::UNIT_Y, point(segment + 1, rings - 1)]);
+    }
+    triangles
+}
+
+/// Triangles of a unit icosahedron split `subdivisions` times, wound counter-clockwise
+/// seen from outside
+fn icosphere(subdivisions: usize) -> Vec<[Vec3f; 3]> {
+    let t = (1.0 + 5f32.sqrt()) / 2.0;
+    let vertices = [
+        Vec3f::new(-1.0, t, 0.0),
+        Vec3f::new(1.0, t, 0.0),
+        Vec3f::new(-1.0, -t, 0.0),
+        Vec3f::new(1.0, -t, 0.0),
+        Vec3f::new(0.0, -1.0, t),
+        Vec3f::new(0.0, 1.0, t),
+        Vec3f::new(0.0, -1.0, -t),
+        Vec3f::new(0.0, 1.0, -t),
+        Vec3f::new(t, 0.0, -1.0),
+        Vec3f::new(t, 0.0, 1.0),
+        Vec3f::new(-t, 0.0, -1.0),
+        Vec3f::new(-t, 0.0, 1.0),
+    ];
+    let faces = [
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+    let mut triangles: Vec<[Vec3f; 3]> = faces
+        .iter()
+        .map(|face| [vertices[face[0]].normalize(), vertices[face[1]].normalize(), vertices[face[2]].normalize()])
+        .collect();
+    for _ in 0..subdivisions {
+        let mut split = Vec::with_capacity(triangles.len() * 4);
+        for [a, b, c] in triangles {
+            // Both triangles sharing an edge compute the same midpoint, keeping it closed
+            let (ab, bc, ca) = ((a + b).normalize(), (b + c).normalize(), (c + a).normalize());
+            split.push([a, ab, ca]);
+            split.push([ab, b, bc]);
+            split.push([ca, bc, c]);
+            split.push([ab, bc, ca]);
+        }
+        triangles = split;
+    }
+    triangles
+}
+
+/// Texture coordinates of a triangle of the unit sphere, inverting `sphere_point`
+fn sphere_uvs(corners: [Vec3f; 3]) -> [Vec2f; 3] {
+    let is_pole = |p: Vec3f| p.x.abs() < 1e-6 && p.z.abs() < 1e-6;
+    let mut uvs = [Vec2f::from_parts(0.0, 0.0); 3];
+    for (uv, p) in uvs.iter_mut().zip(&corners) {
+        let u = (-p.z).atan2(p.x) / (2.0 * PI);
+        *uv = Vec2f::from_parts(if u < 0.0 { u + 1.0 } else { u }, p.y.clamp(-1.0, 1.0).acos() / PI);
+    }
+    let seam = |uvs: &[Vec2f; 3]| {
+        let around = (0..3).filter(|&i| !is_pole(corners[i])).map(|i| uvs[i].x);
+        let (min, max) = around.fold((1.0f32, 0.0f32), |(min, max), u| (min.min(u), max.max(u)));
+        max - min > 0.5
+    };
+    if seam(&uvs) {
+        for uv in uvs.iter_mut().filter(|uv| uv.x < 0.5) {
+            uv.x += 1.0;
+        }
+    }
+    for pole in (0..3).filter(|&i| is_pole(corners[i])) {
+        let others: Vec<f32> = (0..3).filter(|&i| !is_pole(corners[i])).map(|i| uvs[i].x).collect();
+        let u = others.iter().sum::<f32>() / others.len().max(1) as f32;
+        uvs[pole] = Vec2f::from_parts(u, if corners[pole].y > 0.0 { 0.0 } else { 1.0 });
+    }
+    uvs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sphere, SphereTessellation};
+    use crate::model::Mesh;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn check_sphere(mesh: &Mesh, radius: f32) {
+        let uvs = mesh.uvs().unwrap();
+        let normals = mesh.normals().unwrap();
+        for (index, triangle) in mesh.triangles().iter().enumerate() {
+            let corners = [triangle.a, triangle.b, triangle.c];
+            for (corner, normal) in corners.iter().zip(&normals[index]) {
+                assert_approx_eq!(corner.magnitude(), radius, 1e-4);
+                assert_approx_eq!((*corner * (1.0 / radius) - *normal).magnitude(), 0.0, 1e-5);
+            }
+            // Wound to face outwards
+            assert!(triangle.normal().dot(triangle.centroid()) > 0.0, "triangle {} faces inwards", index);
+            // No triangle stretches back across the seam
+            let u: Vec<f32> = uvs[index].iter().map(|uv| uv.x).collect();
+            let span = u.iter().cloned().fold(0.0f32, f32::max) - u.iter().cloned().fold(2.0f32, f32::min);
+            assert!(span < 0.5, "triangle {} spans {} in u", index, span);
+            // Corners moved past the seam run on beyond 1
+            for uv in &uvs[index] {
+                assert!(uv.x >= 0.0 && uv.x < 1.5 && uv.y >= 0.0 && uv.y <= 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_uv_sphere() {
+        let mesh = sphere(2.0, SphereTessellation::Uv { segments: 8, rings: 4 });
+        assert_eq!(mesh.triangles().len(), 8 * 2 + 8 * 2 * 2);
+        check_sphere(&mesh, 2.0);
+        // Each pole vertex sits in the middle of its triangle's span of u
+        let first = mesh.uvs().unwrap()[0];
+        assert_approx_eq!(first[0].x, 0.5 / 8.0, 1e-5);
+        assert_approx_eq!(first[0].y, 0.0);
+        assert_approx_eq!(first[1].x, 0.0, 1e-5);
+        assert_approx_eq!(first[2].x, 1.0 / 8.0, 1e-5);
+        // The last segment ends at u = 1 rather than wrapping to 0
+        let last = mesh.uvs().unwrap()[mesh.triangles().len() - 1];
+        assert_approx_eq!(last[2].x, 1.0, 1e-5);
+    }
+
+    #[test]
+    fn test_icosphere() {
+        assert_eq!(sphere(1.0, SphereTessellation::Icosphere { subdivisions: 0 }).triangles().len(), 20);
+        let mesh = sphere(0.5, SphereTessellation::Icosphere { subdivisions: 2 });
+        assert_eq!(mesh.triangles().len(), 320);
+        check_sphere(&mesh, 0.5);
+        // Subdividing puts a vertex on each pole, whose triangles fan around it in u
+        let poles = mesh.uvs().unwrap().iter().flatten().filter(|uv| uv.y == 0.0 || uv.y == 1.0).count();
+        assert!(poles > 0);
+        // Closed surface: the area of the triangles approaches that of the sphere
+        let area: f32 = mesh.triangles().iter().map(|triangle| triangle.area()).sum();
+        assert!(area < std::f32::consts::PI && area > 0.95 * std::f32::consts::PI);
+    }
+}
@@ -13,7 +13,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 //
-use ::math::Vec3f;
+use crate::math::Vec3f;
 use super::Material;
 
 pub struct Sphere {
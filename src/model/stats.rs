@@ -0,0 +1,163 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Mesh, Scene, Triangle};
+use crate::canvas::{MipLevel, Texture};
+use crate::math::{Aabb, Vec3f};
+use std::collections::HashMap;
+use std::mem::size_of_val;
+
+/// Size and shape figures of a `Mesh`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MeshStats {
+    pub triangles: usize,
+    /// Distinct vertex positions
+    pub vertices: usize,
+    pub surface_area: f32,
+    /// Enclosed volume, `None` unless every edge is shared by exactly two triangles
+    pub volume: Option<f32>,
+    pub bounds: Aabb,
+    /// Estimated bytes of heap memory used
+    pub memory: usize,
+}
+
+/// Totals over every instance and resource of a `Scene`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SceneStats {
+    pub meshes: usize,
+    pub instances: usize,
+    pub materials: usize,
+    pub textures: usize,
+    pub lights: usize,
+    /// Triangles of the meshes, counting each mesh once
+    pub unique_triangles: usize,
+    /// Triangles drawn, counting each instance
+    pub instanced_triangles: usize,
+    /// World space bounds of every instance
+    pub bounds: Aabb,
+    /// Estimated bytes of heap memory used by meshes and textures
+    pub memory: usize,
+}
+
+impl Mesh {
+    /// Count and measure this Mesh
+    pub fn stats(&self) -> MeshStats {
+        let triangles = self.triangles();
+        let key = |v: Vec3f| [(v.x + 0.0).to_bits(), (v.y + 0.0).to_bits(), (v.z + 0.0).to_bits()];
+        let mut vertices = HashMap::new();
+        let mut edges: HashMap<([u32; 3], [u32; 3]), usize> = HashMap::new();
+        for triangle in triangles {
+            let corners = [key(triangle.a), key(triangle.b), key(triangle.c)];
+            for index in 0..3 {
+                let count = vertices.len();
+                vertices.entry(corners[index]).or_insert(count);
+                let (a, b) = (corners[index], corners[(index + 1) % 3]);
+                *edges.entry(if a < b { (a, b) } else { (b, a) }).or_default() += 1;
+            }
+        }
+        let closed = !triangles.is_empty() && edges.values().all(|&count| count == 2);
+        // Divergence theorem, sums signed tetrahedra to the origin
+        let volume = |t: &Triangle| t.a.dot(Vec3f::cross(t.b, t.c)) / 6.0;
+        let mut memory = size_of_val(triangles);
+        memory += self.uvs().map_or(0, size_of_val);
+        memory += self.normals().map_or(0, size_of_val);
+        memory += self.smoothing_groups().map_or(0, size_of_val);
+        MeshStats {
+            triangles: triangles.len(),
+            vertices: vertices.len(),
+            surface_area: triangles.iter().map(Triangle::area).sum(),
+            volume: if closed { Some(triangles.iter().map(volume).sum::<f32>().abs()) } else { None },
+            bounds: self.bounds(),
+            memory,
+        }
+    }
+    /// Smallest box containing every vertex
+    pub fn bounds(&self) -> Aabb {
+        Aabb::from_points(self.triangles().iter().flat_map(|t| vec![t.a, t.b, t.c]))
+    }
+}
+
+impl Texture {
+    /// Estimated bytes of heap memory used by every level
+    pub fn memory(&self) -> usize {
+        self.levels().iter().map(MipLevel::memory).sum()
+    }
+}
+
+impl Scene {
+    /// Count and measure this Scene
+    pub fn stats(&self) -> SceneStats {
+        let mesh_triangles = |index: usize| self.meshes.get(index).map_or(0, |mesh| mesh.triangles().len());
+        let mut bounds = Aabb::empty();
+        for instance in &self.instances {
+            if let Some(mesh) = self.meshes.get(instance.mesh) {
+                bounds = bounds.union(&mesh.bounds().transform(instance.transform));
+            }
+        }
+        let meshes: usize = self.meshes.iter().map(|mesh| mesh.stats().memory).sum();
+        let textures: usize = self.textures.iter().map(Texture::memory).sum();
+        SceneStats {
+            meshes: self.meshes.len(),
+            instances: self.instances.len(),
+            materials: self.materials.len(),
+            textures: self.textures.len(),
+            lights: self.lights.len(),
+            unique_triangles: (0..self.meshes.len()).map(mesh_triangles).sum(),
+            instanced_triangles: self.instances.iter().map(|instance| mesh_triangles(instance.mesh)).sum(),
+            bounds,
+            memory: meshes + textures,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::{Canvas, Color, Texture};
+    use crate::math::{Mat4f, Vec3f};
+    use crate::model::{cube, Material, Mesh, Scene};
+    use assert_approx_eq::assert_approx_eq;
+    use std::mem::size_of;
+
+    #[test]
+    fn test_mesh_stats() {
+        let stats = cube(2.0).stats();
+        assert_eq!(stats.triangles, 12);
+        assert_eq!(stats.vertices, 8);
+        assert_approx_eq!(stats.surface_area, 24.0);
+        assert_approx_eq!(stats.volume.unwrap(), 8.0);
+        assert_eq!(stats.bounds.min, Vec3f::from_parts(-1.0, -1.0, -1.0));
+        assert!(stats.memory >= 12 * 36);
+        // An open mesh has no volume
+        let open = Mesh::new(cube(2.0).triangles()[1..].to_vec());
+        assert_eq!(open.stats().volume, None);
+    }
+
+    #[test]
+    fn test_scene_stats() {
+        let mut scene = Scene::new();
+        let mesh = scene.add_mesh(cube(2.0));
+        let material = scene.add_material(Material::new());
+        scene.add_texture(Texture::new(Canvas::new(4, 4)));
+        scene.add_instance(mesh, material, Mat4f::identity());
+        scene.add_instance(mesh, material, Mat4f::translation(Vec3f::from_parts(10.0, 0.0, 0.0)));
+        let stats = scene.stats();
+        assert_eq!((stats.meshes, stats.instances, stats.materials, stats.textures), (1, 2, 1, 1));
+        assert_eq!(stats.unique_triangles, 12);
+        assert_eq!(stats.instanced_triangles, 24);
+        assert_eq!(stats.bounds.max, Vec3f::from_parts(11.0, 1.0, 1.0));
+        assert_eq!(stats.memory, cube(2.0).stats().memory + (16 + 4 + 1) * size_of::<Color>());
+    }
+}
@@ -13,8 +13,35 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 //
-use super::super::math::Vec3f;
+use super::super::math::{Ray, Vec3f};
 
+/// Ray/Triangle intersection algorithm
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum TriangleIntersector {
+    /// Möller–Trumbore, fast but can miss rays passing exactly through shared edges
+    #[default]
+    MollerTrumbore,
+    /// Woop, Benthin and Wald's watertight test, rays never leak between adjacent triangles
+    Watertight,
+}
+
+/// Ray/Triangle hit
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TriangleHit {
+    /// Distance along the ray in multiples of its direction
+    pub t: f32,
+    /// Barycentric weight of vertex `b`
+    pub u: f32,
+    /// Barycentric weight of vertex `c`
+    pub v: f32,
+}
+
+///
+/// Triangle of three positions, wound counter-clockwise seen from its front.
+///
+/// Per vertex normals and texture coordinates are kept alongside the triangles in the
+/// `Mesh`, so the Triangle stays small for the BVH and intersection loops.
+///
 #[derive(Copy, Clone, PartialEq)]
 pub struct Triangle {
     pub a: Vec3f,
@@ -26,4 +53,201 @@ impl Triangle {
     pub fn new(a: Vec3f, b: Vec3f, c: Vec3f) -> Triangle {
         Triangle { a, b, c }
     }
+    /// Unit normal of the front face, zero for a degenerate triangle
+    pub fn normal(&self) -> Vec3f {
+        Vec3f::cross(self.b - self.a, self.c - self.a).normalize()
+    }
+    /// Surface area
+    pub fn area(&self) -> f32 {
+        Vec3f::cross(self.b - self.a, self.c - self.a).magnitude() / 2.0
+    }
+    /// Average of the three corners, the triangle's center of mass
+    pub fn centroid(&self) -> Vec3f {
+        (self.a + self.b + self.c) * (1.0 / 3.0)
+    }
+    /// Check if `ray` travels towards the back of this triangle, the side its winding
+    /// faces away from
+    pub fn is_backface(&self, ray: &Ray) -> bool {
+        Vec3f::cross(self.b - self.a, self.c - self.a).dot(ray.direction) > 0.0
+    }
+    /// Intersect `ray` with this triangle using the chosen algorithm.
+    ///
+    /// Both sides of the triangle are hit, only hits in front of the ray origin count.
+    pub fn intersect_with(&self, ray: &Ray, intersector: TriangleIntersector) -> Option<TriangleHit> {
+        match intersector {
+            TriangleIntersector::MollerTrumbore => self.intersect(ray),
+            TriangleIntersector::Watertight => self.intersect_watertight(ray),
+        }
+    }
+    /// Intersect `ray` with this triangle using Möller–Trumbore.
+    pub fn intersect(&self, ray: &Ray) -> Option<TriangleHit> {
+        let edge1 = self.b - self.a;
+        let edge2 = self.c - self.a;
+        let p = Vec3f::cross(ray.direction, edge2);
+        let det = edge1.dot(p);
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let s = ray.origin - self.a;
+        let u = s.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = Vec3f::cross(s, edge1);
+        let v = ray.direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(q) * inv_det;
+        if t > 0.0 {
+            Some(TriangleHit { t, u, v })
+        } else {
+            None
+        }
+    }
+    /// Intersect `ray` with this triangle using the watertight test from
+    /// "Watertight Ray/Triangle Intersection" (Woop, Benthin and Wald 2013).
+    ///
+    /// The triangle is sheared into a space where the ray runs along +Z, so the edge tests
+    /// become 2D and a point on a shared edge is never rejected by both triangles.
+    pub fn intersect_watertight(&self, ray: &Ray) -> Option<TriangleHit> {
+        let dir = ray.direction;
+        // Permute so the largest direction component becomes Z, keeping winding
+        let kz = if dir.x.abs() > dir.y.abs() {
+            if dir.x.abs() > dir.z.abs() { 0 } else { 2 }
+        } else if dir.y.abs() > dir.z.abs() {
+            1
+        } else {
+            2
+        };
+        let mut kx = (kz + 1) % 3;
+        let mut ky = (kx + 1) % 3;
+        if dir[kz] < 0.0 {
+            std::mem::swap(&mut kx, &mut ky);
+        }
+        let sx = dir[kx] / dir[kz];
+        let sy = dir[ky] / dir[kz];
+        let sz = 1.0 / dir[kz];
+
+        let a = self.a - ray.origin;
+        let b = self.b - ray.origin;
+        let c = self.c - ray.origin;
+        let ax = a[kx] - sx * a[kz];
+        let ay = a[ky] - sy * a[kz];
+        let bx = b[kx] - sx * b[kz];
+        let by = b[ky] - sy * b[kz];
+        let cx = c[kx] - sx * c[kz];
+        let cy = c[ky] - sy * c[kz];
+
+        let mut u = cx * by - cy * bx;
+        let mut v = ax * cy - ay * cx;
+        let mut w = bx * ay - by * ax;
+        // Fall back to double precision when an edge passes exactly through the ray
+        if u == 0.0 || v == 0.0 || w == 0.0 {
+            let (ax, ay, bx, by, cx, cy) = (
+                f64::from(ax),
+                f64::from(ay),
+                f64::from(bx),
+                f64::from(by),
+                f64::from(cx),
+                f64::from(cy),
+            );
+            u = (cx * by - cy * bx) as f32;
+            v = (ax * cy - ay * cx) as f32;
+            w = (bx * ay - by * ax) as f32;
+        }
+        if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+            return None;
+        }
+        let det = u + v + w;
+        if det == 0.0 {
+            return None;
+        }
+        let t_scaled = sz * (u * a[kz] + v * b[kz] + w * c[kz]);
+        let t = t_scaled / det;
+        if t > 0.0 {
+            Some(TriangleHit { t, u: v / det, v: w / det })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Ray, Triangle, TriangleIntersector, Vec3f};
+    use assert_approx_eq::assert_approx_eq;
+
+    fn triangle() -> Triangle {
+        Triangle::new(
+            Vec3f::from_parts(-1.0, -1.0, 0.0),
+            Vec3f::from_parts(1.0, -1.0, 0.0),
+            Vec3f::from_parts(-1.0, 1.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn test_normal_area_centroid() {
+        let tri = triangle();
+        assert_eq!(tri.normal(), Vec3f::from_parts(0.0, 0.0, 1.0));
+        assert_approx_eq!(tri.area(), 2.0);
+        let centroid = tri.centroid();
+        assert_approx_eq!(centroid.x, -1.0 / 3.0);
+        assert_approx_eq!(centroid.y, -1.0 / 3.0);
+        // Reversing the winding turns the triangle over
+        let flipped = Triangle::new(tri.a, tri.c, tri.b);
+        assert_eq!(flipped.normal(), Vec3f::from_parts(0.0, 0.0, -1.0));
+        let degenerate = Triangle::new(tri.a, tri.b, tri.b);
+        assert_eq!((degenerate.normal(), degenerate.area()), (Vec3f::from_parts(0.0, 0.0, 0.0), 0.0));
+    }
+
+    #[test]
+    fn test_intersect() {
+        let tri = triangle();
+        for &intersector in &[TriangleIntersector::MollerTrumbore, TriangleIntersector::Watertight] {
+            let ray = Ray::new(Vec3f::from_parts(-0.5, -0.5, 2.0), Vec3f::from_parts(0.0, 0.0, -1.0));
+            let hit = tri.intersect_with(&ray, intersector).unwrap();
+            assert_approx_eq!(hit.t, 2.0);
+            assert_approx_eq!(hit.u, 0.25);
+            assert_approx_eq!(hit.v, 0.25);
+            assert!(!tri.is_backface(&ray));
+            // From behind
+            let ray = Ray::new(Vec3f::from_parts(-0.5, 0.0, -3.0), Vec3f::from_parts(0.0, 0.0, 2.0));
+            assert!(tri.is_backface(&ray));
+            let hit = tri.intersect_with(&ray, intersector).unwrap();
+            assert_approx_eq!(hit.t, 1.5);
+            assert_approx_eq!(hit.u, 0.25);
+            assert_approx_eq!(hit.v, 0.5);
+            // Miss outside, pointing away and parallel
+            let ray = Ray::new(Vec3f::from_parts(0.5, 0.5, 2.0), Vec3f::from_parts(0.0, 0.0, -1.0));
+            assert!(tri.intersect_with(&ray, intersector).is_none());
+            let ray = Ray::new(Vec3f::from_parts(-0.5, -0.5, 2.0), Vec3f::from_parts(0.0, 0.0, 1.0));
+            assert!(tri.intersect_with(&ray, intersector).is_none());
+            let ray = Ray::new(Vec3f::from_parts(-0.5, -0.5, 2.0), Vec3f::from_parts(1.0, 0.0, 0.0));
+            assert!(tri.intersect_with(&ray, intersector).is_none());
+        }
+    }
+
+    #[test]
+    fn test_watertight_shared_edge() {
+        // Two triangles sharing the diagonal of a quad, rays along the diagonal must hit one
+        let first = triangle();
+        let second = Triangle::new(
+            Vec3f::from_parts(1.0, -1.0, 0.0),
+            Vec3f::from_parts(1.0, 1.0, 0.0),
+            Vec3f::from_parts(-1.0, 1.0, 0.0),
+        );
+        for i in 1..100 {
+            let s = -1.0 + i as f32 * 0.02;
+            let origin = Vec3f::from_parts(s + 0.3, -s + 0.7, 5.0);
+            let direction = Vec3f::from_parts(-0.3, -0.7, -5.0);
+            let ray = Ray::new(origin, direction);
+            assert!(
+                first.intersect_watertight(&ray).is_some() || second.intersect_watertight(&ray).is_some(),
+                "ray {:?} leaked through the shared edge",
+                ray
+            );
+        }
+    }
 }
@@ -0,0 +1,232 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+use super::{Mesh, Triangle};
+use crate::canvas::Color;
+use crate::math::{Vec2f, Vec3f};
+use crate::rasterizer::PlanarReflector;
+use std::f32::consts::PI;
+
+/// Acceleration due to gravity in m/s², setting how fast waves of each length travel
+const GRAVITY: f32 = 9.81;
+
+/// Reflectance of water seen head on
+const WATER_F0: f32 = 0.02;
+
+/// Single Gerstner wave travelling across a `Water` surface
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GerstnerWave {
+    /// Unit direction of travel in the XZ plane
+    pub direction: Vec2f,
+    /// Distance between crests
+    pub wavelength: f32,
+    /// Height of the crests above the rest level
+    pub amplitude: f32,
+    /// How far water gathers towards the crests, 0 for a sine wave up to 1 for sharp crests
+    pub steepness: f32,
+}
+
+impl GerstnerWave {
+    /// Create new GerstnerWave travelling along `direction`
+    pub fn new(direction: Vec2f, wavelength: f32, amplitude: f32, steepness: f32) -> Self {
+        Self {
+            direction: direction.normalize(),
+            wavelength,
+            amplitude,
+            steepness,
+        }
+    }
+    /// Wave number and phase of this wave at (x, z) at `time` seconds
+    fn phase(&self, x: f32, z: f32, time: f32) -> (f32, f32) {
+        let k = 2.0 * PI / self.wavelength;
+        // Deep water waves travel at sqrt(g / k)
+        let speed = (GRAVITY / k).sqrt();
+        (k, k * (self.direction.x * x + self.direction.y * z - speed * time))
+    }
+}
+
+///
+/// Animated water surface built from Gerstner waves.
+///
+/// `mesh` gives the displaced surface for the rasterizer, `reflector` the mirror to render
+/// the reflection with, and `Rasterizer::composite_water` shades it over the scene below,
+/// absorbing the light coming up through the water by how far it travelled.
+///
+/// ```
+/// use softrender::model::Water;
+///
+/// let water = Water::new(20.0, 32);
+/// let mesh = water.mesh(1.5);
+/// assert_eq!(mesh.triangles().len(), 32 * 32 * 2);
+/// ```
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Water {
+    /// Height of the surface at rest
+    pub level: f32,
+    /// Width and depth of the square surface, centered on the origin
+    pub size: f32,
+    /// Number of quads along each side of the mesh
+    pub resolution: usize,
+    pub waves: Vec<GerstnerWave>,
+    /// Fraction of each channel absorbed per unit of distance travelled through the water
+    pub absorption: Color,
+    /// Color of light scattered back out of deep water
+    pub scatter: Color,
+    /// How far reflections are shifted by the slope of the waves, in world units
+    pub distortion: f32,
+}
+
+impl Water {
+    /// Create new Water `size` across split into `resolution` quads a side, with a gentle
+    /// swell and a smaller cross wave
+    pub fn new(size: f32, resolution: usize) -> Self {
+        Self {
+            level: 0.0,
+            size,
+            resolution,
+            waves: vec![
+                GerstnerWave::new(Vec2f::from_parts(1.0, 0.3), 8.0, 0.15, 0.5),
+                GerstnerWave::new(Vec2f::from_parts(-0.4, 1.0), 3.0, 0.05, 0.3),
+            ],
+            absorption: Color::new(0.45, 0.09, 0.06),
+            scatter: Color::new(0.0, 0.06, 0.08),
+            distortion: 0.2,
+        }
+    }
+    /// Where the water at rest above (x, z) is at `time` seconds
+    pub fn position(&self, x: f32, z: f32, time: f32) -> Vec3f {
+        let mut position = Vec3f::from_parts(x, self.level, z);
+        for wave in &self.waves {
+            let (_, phase) = wave.phase(x, z, time);
+            let horizontal = wave.steepness * wave.amplitude * phase.cos();
+            position.x += wave.direction.x * horizontal;
+            position.y += wave.amplitude * phase.sin();
+            position.z += wave.direction.y * horizontal;
+        }
+        position
+    }
+    /// Unit normal of the water at rest above (x, z) at `time` seconds
+    pub fn normal(&self, x: f32, z: f32, time: f32) -> Vec3f {
+        let mut normal = Vec3f::UNIT_Y;
+        for wave in &self.waves {
+            let (k, phase) = wave.phase(x, z, time);
+            let slope = k * wave.amplitude;
+            normal.x -= wave.direction.x * slope * phase.cos();
+            normal.y -= wave.steepness * slope * phase.sin();
+            normal.z -= wave.direction.y * slope * phase.cos();
+        }
+        normal.normalize()
+    }
+    /// Surface at `time` seconds as a grid of triangles with texture coordinates running
+    /// from 0 to 1 across it and the wave normals at each vertex
+    pub fn mesh(&self, time: f32) -> Mesh {
+        let count = self.resolution.max(1);
+        let half = self.size / 2.0;
+        let vertex = |i: usize, j: usize| {
+            let (u, v) = (i as f32 / count as f32, j as f32 / count as f32);
+            let (x, z) = (u * self.size - half, v * self.size - half);
+            (self.position(x, z, time), Vec2f::from_parts(u, v), self.normal(x, z, time))
+        };
+        let mut triangles = Vec::with_capacity(count * count * 2);
+        let mut uvs = Vec::with_capacity(count * count * 2);
+        let mut normals = Vec::with_capacity(count * count * 2);
+        for j in 0..count {
+            for i in 0..count {
+                let corners = [vertex(i, j), vertex(i, j + 1), vertex(i + 1, j + 1), vertex(i + 1, j)];
+                // Counter clockwise seen from above
+                for [a, b, c] in &[[0, 1, 2], [2, 3, 0]] {
+                    let (a, b, c) = (corners[*a], corners[*b], corners[*c]);
+                    triangles.push(Triangle::new(a.0, b.0, c.0));
+                    uvs.push([a.1, b.1, c.1]);
+                    normals.push([a.2, b.2, c.2]);
+                }
+            }
+        }
+        let mut mesh = Mesh::with_uvs(triangles, uvs);
+        mesh.set_normals(normals);
+        mesh
+    }
+    /// Mirror at the rest level to render reflections with
+    pub fn reflector(&self) -> PlanarReflector {
+        PlanarReflector::new(Vec3f::from_parts(0.0, self.level, 0.0), Vec3f::UNIT_Y)
+    }
+    /// Fraction of each channel left after travelling `distance` through the water
+    pub fn transmittance(&self, distance: f32) -> Color {
+        let channel = |absorption: f32| (-absorption * distance).exp();
+        Color::new(
+            channel(self.absorption.r.value()),
+            channel(self.absorption.g.value()),
+            channel(self.absorption.b.value()),
+        )
+    }
+    /// Fraction of light reflected off the surface seen at an angle with cosine
+    /// `cos_theta` to the normal
+    pub fn fresnel(&self, cos_theta: f32) -> f32 {
+        WATER_F0 + (1.0 - WATER_F0) * (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GerstnerWave, Water};
+    use crate::canvas::Color;
+    use crate::math::{Vec2f, Vec3f};
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_waves() {
+        let mut water = Water::new(10.0, 4);
+        water.waves = vec![GerstnerWave::new(Vec2f::from_parts(2.0, 0.0), 4.0, 0.5, 0.0)];
+        // A sine wave only moves the surface up and down
+        let crest = water.position(1.0, 3.0, 0.0);
+        assert_approx_eq!(crest.x, 1.0);
+        assert_approx_eq!(crest.y, 0.5);
+        assert!((water.normal(1.0, 3.0, 0.0) - Vec3f::UNIT_Y).magnitude() < 1e-5);
+        assert!(water.normal(0.0, 0.0, 0.0).x < 0.0);
+        // Steep waves gather water towards the crests
+        water.waves[0].steepness = 1.0;
+        assert_approx_eq!(water.position(0.0, 0.0, 0.0).x, 0.5);
+        // A calm surface is flat
+        water.waves.clear();
+        water.level = 2.0;
+        assert_eq!(water.position(1.0, 1.0, 5.0), Vec3f::from_parts(1.0, 2.0, 1.0));
+        assert_eq!(water.normal(1.0, 1.0, 5.0), Vec3f::UNIT_Y);
+    }
+
+    #[test]
+    fn test_mesh() {
+        let water = Water::new(4.0, 2);
+        let mesh = water.mesh(0.0);
+        assert_eq!(mesh.triangles().len(), 8);
+        assert_eq!(mesh.normals().unwrap()[0][0], water.normal(-2.0, -2.0, 0.0));
+        assert_eq!(mesh.uvs().unwrap()[7][2], Vec2f::from_parts(0.5, 0.5));
+        // Triangles face up
+        let t = mesh.triangles()[0];
+        assert!(t.normal().y > 0.0);
+    }
+
+    #[test]
+    fn test_absorption() {
+        let water = Water::new(1.0, 1);
+        assert_eq!(water.transmittance(0.0), Color::WHITE);
+        let deep = water.transmittance(5.0);
+        assert!(deep.r.value() < deep.g.value() && deep.g.value() < deep.b.value());
+        assert_approx_eq!(water.fresnel(1.0), 0.02);
+        assert_approx_eq!(water.fresnel(0.0), 1.0);
+    }
+}
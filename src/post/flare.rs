@@ -0,0 +1,214 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+use super::PostEffect;
+use crate::canvas::{Canvas, Color, Filter};
+use crate::math::Vec2f;
+use std::f32::consts::PI;
+
+/// Tints cycled through by successive ghosts, like the coatings of lens elements
+const GHOST_TINTS: [(f32, f32, f32); 3] = [(1.0, 0.7, 0.4), (0.5, 1.0, 0.6), (0.5, 0.6, 1.0)];
+
+///
+/// Lens flare and glare from the brightest parts of an HDR frame.
+///
+/// Pixels brighter than `threshold` cast ghosts mirrored through the image center and
+/// star shaped streaks, both worked out at a reduced resolution and added on top of the
+/// frame. Apply it before tonemapping, and keep one per camera to match each lens.
+///
+/// ```
+/// use softrender::canvas::{Canvas, Color};
+/// use softrender::post::{LensFlare, PostChain};
+///
+/// let mut canvas = Canvas::new(64, 64);
+/// canvas.set(16, 16, Color::new(500.0, 500.0, 400.0));
+/// let mut chain = PostChain::new();
+/// chain.push(LensFlare::new(1.0));
+/// chain.apply(&mut canvas, 0.0);
+/// // A ghost appears across the center from the light
+/// assert!(canvas.get(47, 47).luminance() > 0.0);
+/// ```
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LensFlare {
+    /// Luminance above which Pixels cast a flare
+    pub threshold: f32,
+    /// Number of ghosts along the line through the image center
+    pub ghosts: usize,
+    /// Distance between ghosts as a fraction of the distance to the center
+    pub ghost_spacing: f32,
+    /// Brightness of the ghosts relative to their source
+    pub ghost_intensity: f32,
+    /// Number of aperture blades, giving twice as many streak arms for an even count, zero
+    /// for no streaks
+    pub blades: usize,
+    /// Length of each streak arm as a fraction of the image width
+    pub streak_length: f32,
+    /// Brightness of the streaks relative to their source
+    pub streak_intensity: f32,
+    /// Angle of the first streak arm in degrees
+    pub streak_rotation: f32,
+    /// Factor the frame is shrunk by before looking for bright Pixels
+    pub downsample: usize,
+}
+
+impl LensFlare {
+    /// Create new LensFlare from Pixels brighter than `threshold`, with four ghosts and a
+    /// six pointed star
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            ghosts: 4,
+            ghost_spacing: 0.4,
+            ghost_intensity: 0.02,
+            blades: 6,
+            streak_length: 0.15,
+            streak_intensity: 0.05,
+            streak_rotation: 15.0,
+            downsample: 4,
+        }
+    }
+    /// Directions of the streak arms in the downsampled frame
+    fn streak_directions(&self) -> Vec<Vec2f> {
+        // Each blade edge diffracts light both ways, so even blade counts overlap in pairs
+        let arms = if self.blades.is_multiple_of(2) { self.blades } else { self.blades * 2 };
+        (0..arms)
+            .map(|arm| {
+                let angle = self.streak_rotation.to_radians() + 2.0 * PI * arm as f32 / arms as f32;
+                Vec2f::from_parts(angle.cos(), angle.sin())
+            })
+            .collect()
+    }
+}
+
+impl PostEffect for LensFlare {
+    fn apply(&self, source: &Canvas, target: &mut Canvas, _time: f32) {
+        let scale = self.downsample.max(1);
+        let bright = bright_pass(source, scale, self.threshold);
+        let (width, height) = (bright.width() as f32, bright.height() as f32);
+        let center = Vec2f::from_parts(width / 2.0, height / 2.0);
+        let max_distance = center.magnitude();
+        let streaks = if self.blades > 0 { self.streak_directions() } else { Vec::new() };
+        let steps = (self.streak_length * width).ceil().max(1.0) as usize;
+        let mut flare = Canvas::new(bright.width(), bright.height());
+        for y in 0..bright.height() {
+            for x in 0..bright.width() {
+                let position = Vec2f::from_parts(x as f32 + 0.5, y as f32 + 0.5);
+                let mut sum = Color::black();
+                // Ghosts of the sources lying along the line from here through the center
+                let step = (center - position) * (2.0 * self.ghost_spacing);
+                for ghost in 1..=self.ghosts {
+                    let sample = position + step * ghost as f32;
+                    if sample.x < 0.0 || sample.y < 0.0 || sample.x >= width || sample.y >= height {
+                        continue;
+                    }
+                    // Ghosts fade towards the edges where the lens vignettes them
+                    let falloff = (1.0 - (sample - center).magnitude() / max_distance).max(0.0);
+                    let (r, g, b) = GHOST_TINTS[(ghost - 1) % GHOST_TINTS.len()];
+                    let tint = Color::new(r, g, b) * (self.ghost_intensity * falloff);
+                    sum += bright.sample(sample.x, sample.y, Filter::Bilinear) * tint;
+                }
+                for &direction in &streaks {
+                    for distance in 1..=steps {
+                        let decay = 1.0 - distance as f32 / (steps + 1) as f32;
+                        let sample = position - direction * distance as f32;
+                        if sample.x < 0.0 || sample.y < 0.0 || sample.x >= width || sample.y >= height {
+                            break;
+                        }
+                        let weight = self.streak_intensity * decay * decay / steps as f32;
+                        sum += bright.sample(sample.x, sample.y, Filter::Nearest) * weight;
+                    }
+                }
+                flare.set(x, y, sum);
+            }
+        }
+        for y in 0..source.height() {
+            for x in 0..source.width() {
+                let (fx, fy) = ((x as f32 + 0.5) / scale as f32, (y as f32 + 0.5) / scale as f32);
+                let glow = flare.sample(fx, fy, Filter::Bilinear);
+                target.set(x, y, source.get(x, y) + glow);
+            }
+        }
+    }
+}
+
+/// `source` shrunk by `scale` keeping only the light above `threshold` luminance
+fn bright_pass(source: &Canvas, scale: usize, threshold: f32) -> Canvas {
+    let width = source.width().div_ceil(scale);
+    let height = source.height().div_ceil(scale);
+    let mut bright = Canvas::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let (x0, y0) = (x * scale, y * scale);
+            let (x1, y1) = ((x0 + scale).min(source.width()), (y0 + scale).min(source.height()));
+            let mut sum = Color::black();
+            for sy in y0..y1 {
+                for sx in x0..x1 {
+                    let color = source.get(sx, sy);
+                    let luminance = color.luminance();
+                    // Keep the part above the threshold with the source's hue
+                    if luminance > threshold {
+                        sum += color * ((luminance - threshold) / luminance);
+                    }
+                }
+            }
+            bright.set(x, y, sum * (1.0 / ((x1 - x0) * (y1 - y0)) as f32));
+        }
+    }
+    bright
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LensFlare;
+    use crate::canvas::{Canvas, Color};
+    use crate::post::PostEffect;
+
+    #[test]
+    fn test_below_threshold() {
+        let mut source = Canvas::new(32, 32);
+        source.fill(Color::new(0.5, 0.5, 0.5));
+        let mut target = Canvas::new(32, 32);
+        LensFlare::new(1.0).apply(&source, &mut target, 0.0);
+        assert!(target.pixels().all(|(_, _, c)| c == Color::new(0.5, 0.5, 0.5)));
+    }
+
+    #[test]
+    fn test_ghosts_and_streaks() {
+        let mut source = Canvas::new(64, 64);
+        source.set(16, 32, Color::new(1000.0, 1000.0, 1000.0));
+        let mut flare = LensFlare::new(1.0);
+        flare.blades = 0;
+        let mut ghosts = Canvas::new(64, 64);
+        flare.apply(&source, &mut ghosts, 0.0);
+        // Mirrored through the center on the same row, nothing off the line
+        assert!(ghosts.get(48, 32).luminance() > 0.0);
+        assert_eq!(ghosts.get(32, 8), Color::black());
+
+        flare.ghosts = 0;
+        flare.blades = 4;
+        flare.streak_rotation = 0.0;
+        let mut streaks = Canvas::new(64, 64);
+        flare.apply(&source, &mut streaks, 0.0);
+        // Four arms along the axes fading with distance
+        assert!(streaks.get(20, 32).luminance() > streaks.get(24, 32).luminance());
+        assert!(streaks.get(24, 32).luminance() > 0.0);
+        assert!(streaks.get(16, 28).luminance() > 0.0);
+        assert_eq!(streaks.get(24, 40), Color::black());
+        assert_eq!(streaks.get(16, 32).r.value(), streaks.get(16, 32).b.value());
+    }
+}
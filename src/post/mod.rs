@@ -0,0 +1,153 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Post processing effects applied to finished frames.
+//!
+//! Effects are chained in a `PostChain`, each one reading the output of the previous.
+//!
+//! ```
+//! use softrender::canvas::Canvas;
+//! use softrender::post::{ChromaticAberration, FilmGrain, PostChain, Scanlines};
+//!
+//! let mut canvas = Canvas::new(64, 48);
+//! let mut chain = PostChain::new();
+//! chain.push(ChromaticAberration::new(0.01));
+//! chain.push(FilmGrain::new(0.05));
+//! chain.push(Scanlines::new(0.3, 2));
+//! chain.apply(&mut canvas, 0.0);
+//! ```
+
+mod flare;
+mod stylize;
+mod temporal;
+
+use crate::canvas::Canvas;
+use std::mem;
+
+pub use self::flare::LensFlare;
+pub use self::stylize::{BarrelDistortion, ChromaticAberration, FilmGrain, Scanlines};
+pub use self::temporal::TemporalAccumulator;
+
+///
+/// Image operation in a `PostChain`
+///
+pub trait PostEffect {
+    /// Write the processed `source` to `target`, which has the same size.
+    ///
+    /// `time` is in seconds and lets effects such as grain animate.
+    fn apply(&self, source: &Canvas, target: &mut Canvas, time: f32);
+}
+
+///
+/// Ordered list of post effects
+///
+pub struct PostChain {
+    effects: Vec<Box<dyn PostEffect>>,
+    scratch: Option<Canvas>,
+}
+
+impl PostChain {
+    /// Create an empty PostChain
+    pub fn new() -> Self {
+        Self {
+            effects: Vec::new(),
+            scratch: None,
+        }
+    }
+    /// Add an effect to the end of the chain
+    pub fn push<E: PostEffect + 'static>(&mut self, effect: E) {
+        self.effects.push(Box::new(effect));
+    }
+    /// Number of effects in the chain
+    pub fn len(&self) -> usize {
+        self.effects.len()
+    }
+    /// Check if the chain has no effects
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+    /// Remove every effect
+    pub fn clear(&mut self) {
+        self.effects.clear();
+    }
+    /// Run every effect over `canvas` in order, leaving the result in `canvas`
+    pub fn apply(&mut self, canvas: &mut Canvas, time: f32) {
+        if self.effects.is_empty() {
+            return;
+        }
+        let size = (canvas.width(), canvas.height());
+        let mut scratch = match self.scratch.take() {
+            Some(scratch) if (scratch.width(), scratch.height()) == size => scratch,
+            _ => Canvas::with_layout(size.0, size.1, canvas.layout()),
+        };
+        for effect in &self.effects {
+            effect.apply(canvas, &mut scratch, time);
+            mem::swap(canvas, &mut scratch);
+        }
+        self.scratch = Some(scratch);
+    }
+}
+
+impl Default for PostChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PostChain, PostEffect};
+    use crate::canvas::{Canvas, Color};
+
+    struct Scale(f32);
+
+    impl PostEffect for Scale {
+        fn apply(&self, source: &Canvas, target: &mut Canvas, _time: f32) {
+            for (x, y, color) in source.pixels() {
+                target.set(x, y, color * self.0);
+            }
+        }
+    }
+
+    struct Shift;
+
+    impl PostEffect for Shift {
+        fn apply(&self, source: &Canvas, target: &mut Canvas, _time: f32) {
+            for (x, y, color) in source.pixels() {
+                target.set(x, y, color + Color::new(0.25, 0.25, 0.25));
+            }
+        }
+    }
+
+    #[test]
+    fn test_chain_order() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.fill(Color::new(0.5, 0.5, 0.5));
+        let mut chain = PostChain::new();
+        chain.apply(&mut canvas, 0.0);
+        assert_eq!(canvas.get(0, 0), Color::new(0.5, 0.5, 0.5));
+        chain.push(Scale(0.5));
+        chain.push(Shift);
+        chain.push(Scale(2.0));
+        assert_eq!(chain.len(), 3);
+        chain.apply(&mut canvas, 0.0);
+        assert_eq!(canvas.get(1, 1), Color::new(1.0, 1.0, 1.0));
+        // Reuses its scratch buffer, and copes with a new size
+        let mut canvas = Canvas::new(3, 1);
+        chain.apply(&mut canvas, 0.0);
+        assert_eq!(canvas.get(2, 0), Color::new(0.5, 0.5, 0.5));
+    }
+}
@@ -0,0 +1,219 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::PostEffect;
+use crate::canvas::{Canvas, Color, Filter};
+use crate::math::Vec2f;
+use std::f32::consts::PI;
+
+/// Red and blue channels pulled apart towards the edges, like a cheap lens
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ChromaticAberration {
+    /// Fraction of the distance from the center the red and blue channels are scaled by
+    pub strength: f32,
+}
+
+impl ChromaticAberration {
+    /// Create new ChromaticAberration
+    pub fn new(strength: f32) -> Self {
+        Self { strength }
+    }
+}
+
+impl PostEffect for ChromaticAberration {
+    fn apply(&self, source: &Canvas, target: &mut Canvas, _time: f32) {
+        let center = Vec2f::from_parts(source.width() as f32 / 2.0, source.height() as f32 / 2.0);
+        for y in 0..source.height() {
+            for x in 0..source.width() {
+                let offset = Vec2f::from_parts(x as f32 + 0.5, y as f32 + 0.5) - center;
+                let red = center + offset * (1.0 + self.strength);
+                let blue = center + offset * (1.0 - self.strength);
+                let color = Color {
+                    r: source.sample(red.x, red.y, Filter::Bilinear).r,
+                    g: source.get(x, y).g,
+                    b: source.sample(blue.x, blue.y, Filter::Bilinear).b,
+                };
+                target.set(x, y, color);
+            }
+        }
+    }
+}
+
+/// Animated noise added to every Pixel
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FilmGrain {
+    /// Largest brightness change
+    pub intensity: f32,
+    /// Number of new grain patterns per second
+    pub rate: f32,
+}
+
+impl FilmGrain {
+    /// Create new FilmGrain changing 24 times per second
+    pub fn new(intensity: f32) -> Self {
+        Self { intensity, rate: 24.0 }
+    }
+}
+
+impl PostEffect for FilmGrain {
+    fn apply(&self, source: &Canvas, target: &mut Canvas, time: f32) {
+        let frame = (time * self.rate).max(0.0) as u32;
+        for y in 0..source.height() {
+            for x in 0..source.width() {
+                let noise = hash(x as u32, y as u32, frame) * 2.0 - 1.0;
+                let grain = noise * self.intensity;
+                target.set(x, y, source.get(x, y) + Color::new(grain, grain, grain));
+            }
+        }
+    }
+}
+
+/// Darkened horizontal lines like a CRT display
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Scanlines {
+    /// How much the darkest part of each line is darkened, from 0.0 to 1.0
+    pub intensity: f32,
+    /// Pixels from one line to the next
+    pub spacing: usize,
+}
+
+impl Scanlines {
+    /// Create new Scanlines
+    pub fn new(intensity: f32, spacing: usize) -> Self {
+        Self { intensity, spacing }
+    }
+}
+
+impl PostEffect for Scanlines {
+    fn apply(&self, source: &Canvas, target: &mut Canvas, _time: f32) {
+        let spacing = self.spacing.max(1) as f32;
+        for y in 0..source.height() {
+            // Brightest at the top row of each line, darkest halfway down
+            let wave = 0.5 + 0.5 * (2.0 * PI * y as f32 / spacing).cos();
+            let factor = 1.0 - self.intensity * (1.0 - wave);
+            for x in 0..source.width() {
+                target.set(x, y, source.get(x, y) * factor);
+            }
+        }
+    }
+}
+
+/// Bulging image like a curved CRT screen, with black outside the bent picture
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BarrelDistortion {
+    /// Amount of bulge, 0.0 leaves the image flat
+    pub strength: f32,
+}
+
+impl BarrelDistortion {
+    /// Create new BarrelDistortion
+    pub fn new(strength: f32) -> Self {
+        Self { strength }
+    }
+}
+
+impl PostEffect for BarrelDistortion {
+    fn apply(&self, source: &Canvas, target: &mut Canvas, _time: f32) {
+        let (width, height) = (source.width() as f32, source.height() as f32);
+        for y in 0..source.height() {
+            for x in 0..source.width() {
+                // Position from -1 to 1 across the image
+                let u = (x as f32 + 0.5) / width * 2.0 - 1.0;
+                let v = (y as f32 + 0.5) / height * 2.0 - 1.0;
+                let scale = 1.0 + self.strength * (u * u + v * v);
+                let (su, sv) = (u * scale, v * scale);
+                let color = if su.abs() <= 1.0 && sv.abs() <= 1.0 {
+                    source.sample((su + 1.0) / 2.0 * width, (sv + 1.0) / 2.0 * height, Filter::Bilinear)
+                } else {
+                    Color::black()
+                };
+                target.set(x, y, color);
+            }
+        }
+    }
+}
+
+/// Pseudo random value from 0.0 to 1.0 for a Pixel and frame
+fn hash(x: u32, y: u32, frame: u32) -> f32 {
+    let mut h = x.wrapping_mul(0x8DA6_B343) ^ y.wrapping_mul(0xD816_3841) ^ frame.wrapping_mul(0xCB1A_B31F);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0x5BD1_E995);
+    h ^= h >> 15;
+    (h & 0x00FF_FFFF) as f32 / 0x0100_0000 as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BarrelDistortion, ChromaticAberration, FilmGrain, Scanlines};
+    use crate::canvas::{Canvas, Color};
+    use crate::post::PostEffect;
+
+    fn run<E: PostEffect>(effect: &E, source: &Canvas, time: f32) -> Canvas {
+        let mut target = Canvas::new(source.width(), source.height());
+        effect.apply(source, &mut target, time);
+        target
+    }
+
+    #[test]
+    fn test_chromatic_aberration() {
+        // A white column right of center gets a red fringe inside and a blue one outside
+        let mut source = Canvas::new(16, 1);
+        source.set(12, 0, Color::white());
+        let target = run(&ChromaticAberration::new(0.25), &source, 0.0);
+        assert_eq!(target.get(12, 0).g, Color::white().g);
+        assert!(target.get(11, 0).r.value() > 0.0 && target.get(11, 0).b.value() == 0.0);
+        assert!(target.get(13, 0).b.value() > 0.0 && target.get(13, 0).r.value() == 0.0);
+        // The center does not move
+        let flat = run(&ChromaticAberration::new(0.0), &source, 0.0);
+        assert!(flat.pixels().all(|(x, y, c)| c == source.get(x, y)));
+    }
+
+    #[test]
+    fn test_film_grain() {
+        let mut source = Canvas::new(8, 8);
+        source.fill(Color::grey());
+        let grain = FilmGrain::new(0.1);
+        let first = run(&grain, &source, 0.0);
+        assert!(first.pixels().all(|(_, _, c)| (c.r.value() - 0.5).abs() <= 0.1));
+        assert!(first.pixels().any(|(_, _, c)| c != Color::grey()));
+        // Same frame gives the same grain, a later frame moves it
+        let same = run(&grain, &source, 0.01);
+        assert!(first.pixels().all(|(x, y, c)| same.get(x, y) == c));
+        let later = run(&grain, &source, 1.0);
+        assert!(first.pixels().any(|(x, y, c)| later.get(x, y) != c));
+    }
+
+    #[test]
+    fn test_scanlines() {
+        let mut source = Canvas::new(1, 4);
+        source.fill(Color::white());
+        let target = run(&Scanlines::new(0.5, 2), &source, 0.0);
+        assert_eq!(target.get(0, 0), Color::white());
+        assert_eq!(target.get(0, 1), Color::grey());
+        assert_eq!(target.get(0, 2), Color::white());
+    }
+
+    #[test]
+    fn test_barrel_distortion() {
+        let mut source = Canvas::new(9, 9);
+        source.fill(Color::white());
+        let target = run(&BarrelDistortion::new(0.5), &source, 0.0);
+        assert_eq!(target.get(4, 4), Color::white());
+        assert_eq!(target.get(0, 0), Color::black());
+        let flat = run(&BarrelDistortion::new(0.0), &source, 0.0);
+        assert!(flat.pixels().all(|(_, _, c)| c == Color::white()));
+    }
+}
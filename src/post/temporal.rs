@@ -0,0 +1,232 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::canvas::{AovBuffers, Canvas, Color, Filter};
+use crate::math::{halton, Vec2f};
+use crate::shader::Camera;
+
+///
+/// Temporal accumulation of frames, blending each new frame into a history reprojected
+/// to follow the camera.
+///
+/// Jittering the camera by `jitter` every frame turns the accumulation into temporal
+/// anti-aliasing, while for a still camera it averages away raytracing noise.
+///
+pub struct TemporalAccumulator {
+    /// Weight of the current frame in the result, lower is smoother but ghosts more
+    pub blend: f32,
+    /// Clamp the history to the colors around each pixel of the current frame, which
+    /// stops disoccluded or changed pixels from ghosting
+    pub neighborhood_clamp: bool,
+    history: Option<Canvas>,
+    frames: u32,
+}
+
+impl TemporalAccumulator {
+    /// Create new TemporalAccumulator
+    pub fn new() -> Self {
+        Self {
+            blend: 0.1,
+            neighborhood_clamp: true,
+            history: None,
+            frames: 0,
+        }
+    }
+    /// Forget the history, such as after a camera cut
+    pub fn reset(&mut self) {
+        self.history = None;
+        self.frames = 0;
+    }
+    /// Accumulated result of the frames so far, `None` before the first frame
+    pub fn history(&self) -> Option<&Canvas> {
+        self.history.as_ref()
+    }
+    /// Sub-pixel offset, from -0.5 to 0.5, to shift the camera by for `frame`
+    pub fn jitter(frame: u32) -> Vec2f {
+        Vec2f::from_parts(halton(frame + 1, 2) - 0.5, halton(frame + 1, 3) - 0.5)
+    }
+    /// Blend `current` into the history and return the result.
+    ///
+    /// `reproject` gives the position in the previous frame of the surface seen through
+    /// Pixel (x, y), or `None` if it was not visible.
+    pub fn accumulate_with<F: Fn(usize, usize) -> Option<Vec2f>>(&mut self, current: &Canvas, reproject: F) -> &Canvas {
+        let (width, height) = (current.width(), current.height());
+        let history = match self.history.take() {
+            Some(history) if (history.width(), history.height()) == (width, height) => history,
+            _ => {
+                self.frames = 1;
+                self.history = Some(current.clone());
+                return self.history.as_ref().unwrap();
+            }
+        };
+        self.frames += 1;
+        // Average evenly until there are enough frames for the blend factor to take over
+        let blend = self.blend.max(1.0 / self.frames as f32);
+        let mut result = Canvas::with_layout(width, height, current.layout());
+        for y in 0..height {
+            for x in 0..width {
+                let color = current.get(x, y);
+                let previous = reproject(x, y)
+                    .filter(|p| p.x >= 0.0 && p.y >= 0.0 && p.x < width as f32 && p.y < height as f32)
+                    .map(|p| history.sample(p.x, p.y, Filter::Bilinear));
+                let blended = match previous {
+                    Some(mut previous) => {
+                        if self.neighborhood_clamp {
+                            previous = clamp_to_neighborhood(current, x, y, previous);
+                        }
+                        previous * (1.0 - blend) + color * blend
+                    }
+                    None => color,
+                };
+                result.set(x, y, blended);
+            }
+        }
+        self.history = Some(result);
+        self.history.as_ref().unwrap()
+    }
+    /// Blend `current` into the history for a scene that did not move.
+    pub fn accumulate(&mut self, current: &Canvas) -> &Canvas {
+        self.accumulate_with(current, |x, y| Some(Vec2f::from_parts(x as f32 + 0.5, y as f32 + 0.5)))
+    }
+    /// Blend `current` into the history, reprojecting static geometry by the camera
+    /// moving from `previous` to `camera`.
+    ///
+    /// Uses the depth AOV of `aovs` to find where each pixel's surface is, pixels
+    /// without depth are treated as infinitely far away.
+    pub fn accumulate_camera(&mut self, current: &Canvas, aovs: &AovBuffers, camera: &Camera, previous: &Camera) -> &Canvas {
+        let (width, height) = (current.width(), current.height());
+        self.accumulate_with(current, |x, y| {
+            let direction = camera.ray_direction(x, y, width, height);
+            let depth = aovs.get(x, y).depth;
+            let point = if depth.is_finite() {
+                (camera.position + direction * depth).extend(1.0)
+            } else {
+                direction.extend(0.0)
+            };
+            previous.project(point, width, height)
+        })
+    }
+    /// Blend `current` into the history, reprojecting by the velocity AOV of `aovs`,
+    /// such as written by `Rasterizer::draw_velocity`, so moving objects are followed too
+    pub fn accumulate_velocity(&mut self, current: &Canvas, aovs: &AovBuffers) -> &Canvas {
+        self.accumulate_with(current, |x, y| {
+            let velocity = aovs.get(x, y).velocity;
+            Some(Vec2f::from_parts(x as f32 + 0.5 - velocity.x, y as f32 + 0.5 - velocity.y))
+        })
+    }
+}
+
+impl Default for TemporalAccumulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Clamp `color` to the range of colors in the 3x3 block around Pixel (x, y)
+fn clamp_to_neighborhood(canvas: &Canvas, x: usize, y: usize, color: Color) -> Color {
+    let (mut low, mut high) = ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]);
+    for ny in y.saturating_sub(1)..(y + 2).min(canvas.height()) {
+        for nx in x.saturating_sub(1)..(x + 2).min(canvas.width()) {
+            let c = canvas.get(nx, ny);
+            for (i, v) in [c.r.value(), c.g.value(), c.b.value()].iter().enumerate() {
+                low[i] = low[i].min(*v);
+                high[i] = high[i].max(*v);
+            }
+        }
+    }
+    let clamp = |v: f32, i: usize| v.max(low[i]).min(high[i]);
+    Color::new(clamp(color.r.value(), 0), clamp(color.g.value(), 1), clamp(color.b.value(), 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TemporalAccumulator;
+    use crate::canvas::{Aov, AovBuffers, AovSample, Canvas, Color};
+    use crate::math::{Vec2f, Vec3f};
+    use crate::shader::Camera;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_static_accumulation() {
+        // Alternating noisy frames converge on their average
+        let mut taa = TemporalAccumulator::new();
+        taa.blend = 0.0;
+        taa.neighborhood_clamp = false;
+        let mut bright = Canvas::new(2, 2);
+        bright.fill(Color::new(0.8, 0.8, 0.8));
+        let dark = Canvas::new(2, 2);
+        for _ in 0..50 {
+            taa.accumulate(&bright);
+            taa.accumulate(&dark);
+        }
+        assert_approx_eq!(taa.history().unwrap().get(1, 1).r.value(), 0.4);
+        taa.reset();
+        assert!(taa.history().is_none());
+        assert_eq!(taa.accumulate(&dark).get(0, 0), Color::black());
+    }
+
+    #[test]
+    fn test_reprojection() {
+        let mut taa = TemporalAccumulator::new();
+        taa.blend = 0.5;
+        let mut first = Canvas::new(4, 1);
+        first.set(1, 0, Color::white());
+        taa.accumulate(&first);
+        // The white pixel moved one to the right, without reprojection it would be smeared
+        let mut second = Canvas::new(4, 1);
+        second.set(2, 0, Color::white());
+        let result = taa.accumulate_with(&second, |x, y| Some(Vec2f::from_parts(x as f32 - 0.5, y as f32 + 0.5)));
+        assert_eq!(result.get(2, 0), Color::white());
+        assert_eq!(result.get(1, 0), Color::black());
+        // Neighborhood clamping stops a vanished pixel ghosting
+        let result = taa.accumulate(&Canvas::new(4, 1));
+        assert_eq!(result.get(2, 0), Color::black());
+    }
+
+    #[test]
+    fn test_camera_reprojection() {
+        let up = Vec3f::from_parts(0.0, 1.0, 0.0);
+        let front = Vec3f::from_parts(0.0, 0.0, -1.0);
+        let previous = Camera::new(Vec3f::from_parts(0.0, 0.0, 0.0), front, up);
+        let camera = Camera::new(Vec3f::from_parts(0.5, 0.0, 0.0), front, up);
+        let mut aovs = AovBuffers::new(8, 6, &[Aov::Depth]);
+        let sample = AovSample { depth: 2.0, ..AovSample::default() };
+        aovs.set(4, 3, &sample);
+        let mut taa = TemporalAccumulator::new();
+        taa.neighborhood_clamp = false;
+        let mut frame = Canvas::new(8, 6);
+        frame.fill(Color::grey());
+        taa.accumulate(&frame);
+        taa.accumulate_camera(&frame, &aovs, &camera, &previous);
+        // A nearby surface shifts against the camera movement, the sky stays put
+        let direction = camera.ray_direction(4, 3, 8, 6);
+        let moved = previous.project((camera.position + direction * 2.0).extend(1.0), 8, 6).unwrap();
+        assert!(moved.x > 5.0);
+        let sky = previous.project(camera.ray_direction(1, 1, 8, 6).extend(0.0), 8, 6).unwrap();
+        assert_approx_eq!(sky.x, 1.5, 1e-3);
+        assert_eq!(taa.history().unwrap().get(4, 3), Color::grey());
+    }
+
+    #[test]
+    fn test_jitter() {
+        let jitter = TemporalAccumulator::jitter(0);
+        assert_eq!((jitter.x, jitter.y), (0.0, 1.0 / 3.0 - 0.5));
+        for frame in 0..16 {
+            let jitter = TemporalAccumulator::jitter(frame);
+            assert!(jitter.x.abs() <= 0.5 && jitter.y.abs() <= 0.5);
+        }
+    }
+}
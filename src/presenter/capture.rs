@@ -0,0 +1,159 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::canvas::{Canvas, BMP, PNG, PPM};
+use crate::input::{Event, Key};
+use crate::shader::{Camera, CameraAnimation};
+use std::fs::File;
+use crate::Result;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// File format screenshots are written in
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Bmp,
+    Png,
+    Ppm,
+}
+
+impl ImageFormat {
+    /// File extension for this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Bmp => "bmp",
+            ImageFormat::Png => "png",
+            ImageFormat::Ppm => "ppm",
+        }
+    }
+}
+
+/// Write `canvas` into `directory` as `screenshot-<unix time in ms>.<ext>`
+pub fn save_screenshot(canvas: &Canvas, directory: &Path, format: ImageFormat) -> Result<PathBuf> {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |t| t.as_millis());
+    let path = directory.join(format!("screenshot-{}.{}", millis, format.extension()));
+    write_image(canvas, &path, format)?;
+    Ok(path)
+}
+
+/// Render `frames` frames of `animation` with `render` and write them into `directory` as
+/// `frame-<number>.<ext>`, numbered from 0 and zero padded so they sort in order.
+///
+/// Returns the paths written, ready for an encoder such as
+/// `ffmpeg -i frame-%04d.bmp`.
+pub fn render_sequence<A, F>(
+    animation: &A,
+    frames: usize,
+    directory: &Path,
+    format: ImageFormat,
+    mut render: F,
+) -> Result<Vec<PathBuf>>
+where
+    A: CameraAnimation + ?Sized,
+    F: FnMut(&Camera) -> Canvas,
+{
+    let digits = frames.saturating_sub(1).to_string().len().max(4);
+    let mut paths = Vec::with_capacity(frames);
+    for (index, camera) in animation.frames(frames).iter().enumerate() {
+        let canvas = render(camera);
+        let path = directory.join(format!("frame-{:0width$}.{}", index, format.extension(), width = digits));
+        write_image(&canvas, &path, format)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+fn write_image(canvas: &Canvas, path: &Path, format: ImageFormat) -> Result<()> {
+    let mut output = BufWriter::new(File::create(path)?);
+    match format {
+        ImageFormat::Bmp => canvas.to_bmp(&mut output)?,
+        ImageFormat::Png => canvas.to_png(&mut output)?,
+        ImageFormat::Ppm => canvas.to_ppm(&mut output)?,
+    }
+    Ok(())
+}
+
+///
+/// Key that makes a presenter save the current frame
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScreenshotHotkey {
+    pub key: Key,
+    pub directory: PathBuf,
+    pub format: ImageFormat,
+}
+
+impl ScreenshotHotkey {
+    /// Save `frame` if `events` contain a press of the hotkey.
+    ///
+    /// Failures are logged rather than returned so a full disk never stops the session.
+    pub(crate) fn handle(&self, events: &[Event], frame: Option<Canvas>) -> Option<PathBuf> {
+        if !events.contains(&Event::KeyDown(self.key)) {
+            return None;
+        }
+        let frame = frame?;
+        match save_screenshot(&frame, &self.directory, self.format) {
+            Ok(path) => {
+                tracing::info!("saved screenshot to {}", path.display());
+                Some(path)
+            }
+            Err(error) => {
+                tracing::warn!("failed to save screenshot: {}", error);
+                None
+            }
+        }
+    }
+}
+
+impl Default for ScreenshotHotkey {
+    /// F12 saves a BMP into the working directory
+    fn default() -> Self {
+        Self {
+            key: Key::F12,
+            directory: PathBuf::from("."),
+            format: ImageFormat::Bmp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_sequence, ImageFormat};
+    use crate::canvas::{Canvas, Color};
+    use crate::math::{Aabb, Vec3f};
+    use crate::shader::Turntable;
+
+    #[test]
+    fn test_render_sequence() {
+        let directory = std::env::temp_dir().join(format!("softrender-sequence-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let turntable = Turntable::around(&Aabb::from_points(vec![Vec3f::ZERO, Vec3f::ONE]));
+        let mut positions = Vec::new();
+        let paths = render_sequence(&turntable, 3, &directory, ImageFormat::Ppm, |camera| {
+            positions.push(camera.position);
+            let mut canvas = Canvas::new(2, 2);
+            canvas.fill(Color::white());
+            canvas
+        })
+        .unwrap();
+        assert_eq!(positions.len(), 3);
+        let names: Vec<_> = paths.iter().map(|p| p.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(names, vec!["frame-0000.ppm", "frame-0001.ppm", "frame-0002.ppm"]);
+        assert!(std::fs::read_to_string(&paths[2]).unwrap().starts_with("P3"));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Presenter, ScreenshotHotkey};
+use crate::canvas::Canvas;
+use crate::input::Event;
+use crate::Result;
+
+///
+/// Presenter without a window, for tests, servers and offline rendering.
+///
+/// Events are supplied by the application with `push_event`.
+///
+pub struct HeadlessPresenter {
+    size: (usize, usize),
+    events: Vec<Event>,
+    frames: usize,
+    open: bool,
+    last_frame: Option<Canvas>,
+    hotkey: Option<ScreenshotHotkey>,
+}
+
+impl HeadlessPresenter {
+    /// Create new HeadlessPresenter pretending to have a window of the given size
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            size: (width, height),
+            events: Vec::new(),
+            frames: 0,
+            open: true,
+            last_frame: None,
+            hotkey: None,
+        }
+    }
+    /// Queue an event for the next `poll_events`
+    pub fn push_event(&mut self, event: Event) {
+        match event {
+            Event::Resize { width, height } => self.size = (width, height),
+            Event::Close => self.open = false,
+            _ => (),
+        }
+        self.events.push(event);
+    }
+    /// Number of frames presented
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+}
+
+impl Presenter for HeadlessPresenter {
+    fn present(&mut self, canvas: &Canvas) -> Result<()> {
+        self.frames += 1;
+        self.last_frame = Some(canvas.clone());
+        Ok(())
+    }
+    fn poll_events(&mut self) -> Vec<Event> {
+        let events = std::mem::take(&mut self.events);
+        if let Some(hotkey) = &self.hotkey {
+            hotkey.handle(&events, self.capture());
+        }
+        events
+    }
+    fn is_open(&self) -> bool {
+        self.open
+    }
+    fn size(&self) -> (usize, usize) {
+        self.size
+    }
+    fn capture(&self) -> Option<Canvas> {
+        self.last_frame.clone()
+    }
+    fn set_screenshot_hotkey(&mut self, hotkey: Option<ScreenshotHotkey>) {
+        self.hotkey = hotkey;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HeadlessPresenter, Presenter};
+    use crate::canvas::{Canvas, Color};
+    use crate::input::{Event, InputState, Key};
+    use crate::presenter::{ImageFormat, ScreenshotHotkey};
+
+    #[test]
+    fn test_events() {
+        let mut presenter = HeadlessPresenter::new(64, 48);
+        let mut input = InputState::new();
+        presenter.push_event(Event::KeyDown(Key::Space));
+        presenter.push_event(Event::Resize { width: 80, height: 60 });
+        input.begin_frame();
+        for event in presenter.poll_events() {
+            input.handle(&event);
+        }
+        assert!(input.was_key_pressed(Key::Space));
+        assert_eq!(input.resized(), Some((80, 60)));
+        assert_eq!(presenter.size(), (80, 60));
+        assert!(presenter.poll_events().is_empty());
+        presenter.present(&Canvas::new(80, 60)).unwrap();
+        assert_eq!(presenter.frames(), 1);
+        presenter.push_event(Event::Close);
+        assert!(!presenter.is_open());
+    }
+
+    #[test]
+    fn test_capture() {
+        let mut presenter = HeadlessPresenter::new(4, 4);
+        assert!(presenter.capture().is_none());
+        let mut canvas = Canvas::new(4, 4);
+        canvas.set(1, 2, Color::white());
+        presenter.present(&canvas).unwrap();
+        assert_eq!(presenter.capture().unwrap().get(1, 2), Color::white());
+    }
+
+    #[test]
+    fn test_screenshot_hotkey() {
+        let directory = std::env::temp_dir().join(format!("softrender-capture-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let mut presenter = HeadlessPresenter::new(4, 4);
+        presenter.set_screenshot_hotkey(Some(ScreenshotHotkey {
+            directory: directory.clone(),
+            format: ImageFormat::Ppm,
+            ..ScreenshotHotkey::default()
+        }));
+        presenter.present(&Canvas::new(4, 4)).unwrap();
+        presenter.push_event(Event::KeyDown(Key::F11));
+        presenter.poll_events();
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 0);
+        presenter.push_event(Event::KeyDown(Key::F12));
+        presenter.poll_events();
+        let saved: Vec<_> = std::fs::read_dir(&directory).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].extension().unwrap(), "ppm");
+        assert!(std::fs::read_to_string(&saved[0]).unwrap().starts_with("P3"));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,252 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Presenter, ScreenshotHotkey};
+use crate::canvas::{Canvas, Color};
+use crate::input::{Event, Key, MouseButton};
+use minifb::{MouseMode, Window, WindowOptions};
+use crate::{Error, Result};
+
+const BUTTONS: [(MouseButton, minifb::MouseButton); 3] = [
+    (MouseButton::Left, minifb::MouseButton::Left),
+    (MouseButton::Right, minifb::MouseButton::Right),
+    (MouseButton::Middle, minifb::MouseButton::Middle),
+];
+
+///
+/// Presenter showing frames in a minifb window
+///
+/// minifb only exposes polled state, so events are synthesized by comparing it with the
+/// state seen on the previous poll. minifb expects buffers of the size the window was
+/// opened with and scales them to the window itself, so frames of any other size are
+/// resampled to that size first.
+///
+pub struct MinifbPresenter {
+    window: Window,
+    buffer: Vec<u32>,
+    scaled: Vec<u32>,
+    buffer_size: (usize, usize),
+    keys_down: Vec<Key>,
+    buttons_down: [bool; 3],
+    mouse_position: (f32, f32),
+    size: (usize, usize),
+    frame_size: (usize, usize),
+    hotkey: Option<ScreenshotHotkey>,
+}
+
+impl MinifbPresenter {
+    /// Open a resizable window
+    pub fn new(title: &str, width: usize, height: usize) -> Result<Self> {
+        let options = WindowOptions {
+            resize: true,
+            ..WindowOptions::default()
+        };
+        let window = Window::new(title, width, height, options).map_err(|e| Error::Window(e.to_string()))?;
+        Ok(Self {
+            window,
+            buffer: Vec::new(),
+            scaled: Vec::new(),
+            buffer_size: (width, height),
+            keys_down: Vec::new(),
+            buttons_down: [false; 3],
+            mouse_position: (0.0, 0.0),
+            size: (width, height),
+            frame_size: (0, 0),
+            hotkey: Some(ScreenshotHotkey::default()),
+        })
+    }
+}
+
+impl Presenter for MinifbPresenter {
+    fn present(&mut self, canvas: &Canvas) -> Result<()> {
+        self.frame_size = (canvas.width(), canvas.height());
+        self.buffer.resize(canvas.width() * canvas.height(), 0);
+        for y in 0..canvas.height() {
+            for x in 0..canvas.width() {
+                self.buffer[y * canvas.width() + x] = canvas.get(x, y).to_argb();
+            }
+        }
+        let (width, height) = self.buffer_size;
+        let buffer = if self.frame_size == self.buffer_size {
+            &self.buffer
+        } else {
+            self.scaled.resize(width * height, 0);
+            for y in 0..height {
+                let source_y = y * canvas.height() / height;
+                for x in 0..width {
+                    let source_x = x * canvas.width() / width;
+                    self.scaled[y * width + x] = self.buffer[source_y * canvas.width() + source_x];
+                }
+            }
+            &self.scaled
+        };
+        self.window
+            .update_with_buffer(buffer)
+            .map_err(|e| Error::Window(e.to_string()))
+    }
+    fn poll_events(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        if !self.window.is_open() {
+            events.push(Event::Close);
+            return events;
+        }
+        let keys: Vec<Key> = self
+            .window
+            .get_keys()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(map_key)
+            .collect();
+        for key in &keys {
+            if !self.keys_down.contains(key) {
+                events.push(Event::KeyDown(*key));
+            }
+        }
+        for key in &self.keys_down {
+            if !keys.contains(key) {
+                events.push(Event::KeyUp(*key));
+            }
+        }
+        self.keys_down = keys;
+        if let Some((x, y)) = self.window.get_mouse_pos(MouseMode::Pass) {
+            if (x, y) != self.mouse_position {
+                self.mouse_position = (x, y);
+                events.push(Event::MouseMove { x, y });
+            }
+        }
+        for (index, &(button, minifb_button)) in BUTTONS.iter().enumerate() {
+            let down = self.window.get_mouse_down(minifb_button);
+            if down != self.buttons_down[index] {
+                self.buttons_down[index] = down;
+                events.push(if down { Event::MouseDown(button) } else { Event::MouseUp(button) });
+            }
+        }
+        if let Some((x, y)) = self.window.get_scroll_wheel() {
+            events.push(Event::MouseWheel { x, y });
+        }
+        let size = self.window.get_size();
+        if size != self.size {
+            self.size = size;
+            events.push(Event::Resize {
+                width: size.0,
+                height: size.1,
+            });
+        }
+        if let Some(hotkey) = &self.hotkey {
+            hotkey.handle(&events, self.capture());
+        }
+        events
+    }
+    fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+    fn size(&self) -> (usize, usize) {
+        self.size
+    }
+    fn capture(&self) -> Option<Canvas> {
+        let (width, height) = self.frame_size;
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let mut canvas = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let argb = self.buffer[y * width + x];
+                let channel = |shift: u32| ((argb >> shift) & 0xFF) as u8;
+                let color = Color {
+                    r: channel(16).into(),
+                    g: channel(8).into(),
+                    b: channel(0).into(),
+                };
+                canvas.set(x, y, color);
+            }
+        }
+        Some(canvas)
+    }
+    fn set_screenshot_hotkey(&mut self, hotkey: Option<ScreenshotHotkey>) {
+        self.hotkey = hotkey;
+    }
+}
+
+fn map_key(key: minifb::Key) -> Option<Key> {
+    use minifb::Key as K;
+    Some(match key {
+        K::A => Key::A,
+        K::B => Key::B,
+        K::C => Key::C,
+        K::D => Key::D,
+        K::E => Key::E,
+        K::F => Key::F,
+        K::G => Key::G,
+        K::H => Key::H,
+        K::I => Key::I,
+        K::J => Key::J,
+        K::K => Key::K,
+        K::L => Key::L,
+        K::M => Key::M,
+        K::N => Key::N,
+        K::O => Key::O,
+        K::P => Key::P,
+        K::Q => Key::Q,
+        K::R => Key::R,
+        K::S => Key::S,
+        K::T => Key::T,
+        K::U => Key::U,
+        K::V => Key::V,
+        K::W => Key::W,
+        K::X => Key::X,
+        K::Y => Key::Y,
+        K::Z => Key::Z,
+        K::Key0 => Key::Num0,
+        K::Key1 => Key::Num1,
+        K::Key2 => Key::Num2,
+        K::Key3 => Key::Num3,
+        K::Key4 => Key::Num4,
+        K::Key5 => Key::Num5,
+        K::Key6 => Key::Num6,
+        K::Key7 => Key::Num7,
+        K::Key8 => Key::Num8,
+        K::Key9 => Key::Num9,
+        K::F1 => Key::F1,
+        K::F2 => Key::F2,
+        K::F3 => Key::F3,
+        K::F4 => Key::F4,
+        K::F5 => Key::F5,
+        K::F6 => Key::F6,
+        K::F7 => Key::F7,
+        K::F8 => Key::F8,
+        K::F9 => Key::F9,
+        K::F10 => Key::F10,
+        K::F11 => Key::F11,
+        K::F12 => Key::F12,
+        K::Escape => Key::Escape,
+        K::Space => Key::Space,
+        K::Enter => Key::Enter,
+        K::Tab => Key::Tab,
+        K::Backspace => Key::Backspace,
+        K::Left => Key::Left,
+        K::Right => Key::Right,
+        K::Up => Key::Up,
+        K::Down => Key::Down,
+        K::LeftShift => Key::LeftShift,
+        K::RightShift => Key::RightShift,
+        K::LeftCtrl => Key::LeftCtrl,
+        K::RightCtrl => Key::RightCtrl,
+        K::LeftAlt => Key::LeftAlt,
+        K::RightAlt => Key::RightAlt,
+        _ => return None,
+    })
+}
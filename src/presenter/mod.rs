@@ -0,0 +1,56 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Presentation of finished frames to a window, and the window's input.
+
+mod capture;
+mod headless;
+#[cfg(feature = "minifb")]
+mod minifb;
+#[cfg(feature = "preview")]
+mod preview;
+mod resize;
+
+use crate::canvas::Canvas;
+use crate::input::Event;
+use crate::Result;
+
+pub use self::capture::{render_sequence, save_screenshot, ImageFormat, ScreenshotHotkey};
+pub use self::headless::HeadlessPresenter;
+#[cfg(feature = "minifb")]
+pub use self::minifb::MinifbPresenter;
+#[cfg(feature = "preview")]
+pub use self::preview::PreviewServer;
+pub use self::resize::{ResizeHandler, ResizePolicy, Resized, Viewport};
+
+///
+/// Window system backend showing frames and reporting input
+///
+pub trait Presenter {
+    /// Show `canvas` in the window
+    fn present(&mut self, canvas: &Canvas) -> Result<()>;
+    /// Input events that happened since the last call
+    fn poll_events(&mut self) -> Vec<Event>;
+    /// Check if the window is still open
+    fn is_open(&self) -> bool;
+    /// Size of the window client area in pixels
+    fn size(&self) -> (usize, usize);
+    /// Copy of the last presented frame, `None` before the first `present`
+    fn capture(&self) -> Option<Canvas>;
+    /// Set the key that saves the last presented frame while polling events, `None`
+    /// disables it
+    fn set_screenshot_hotkey(&mut self, hotkey: Option<ScreenshotHotkey>);
+}
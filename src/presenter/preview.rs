@@ -0,0 +1,251 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+//! Tiny HTTP server showing the latest frame in a browser, for watching long headless
+//! renders on a remote machine.
+//!
+//! Serves an auto refreshing page at `/`, and the frame itself at `/frame.png` and
+//! `/frame.ppm`. Requests are answered one at a time on a background thread, which is
+//! plenty for a handful of people watching a render.
+//!
+//! ```no_run
+//! use softrender::canvas::Canvas;
+//! use softrender::presenter::PreviewServer;
+//!
+//! let server = PreviewServer::bind("0.0.0.0:8080").unwrap();
+//! println!("watch at http://{}/", server.local_addr());
+//! let canvas = Canvas::new(640, 480);
+//! // after each pass of a progressive render...
+//! server.update(&canvas);
+//! ```
+
+use crate::canvas::{Canvas, PNG, PPM};
+use crate::Result;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// State shared between the render thread and the server thread
+struct Shared {
+    frame: Mutex<Option<Canvas>>,
+    generation: AtomicU64,
+    refresh: Mutex<Duration>,
+    stop: AtomicBool,
+}
+
+///
+/// HTTP server publishing the latest frame handed to `update`.
+///
+/// The server stops when dropped.
+///
+pub struct PreviewServer {
+    shared: Arc<Shared>,
+    address: SocketAddr,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PreviewServer {
+    /// Start serving on `address`, use port 0 to pick a free port
+    pub fn bind<A: ToSocketAddrs>(address: A) -> Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let shared = Arc::new(Shared {
+            frame: Mutex::new(None),
+            generation: AtomicU64::new(0),
+            refresh: Mutex::new(Duration::from_secs(1)),
+            stop: AtomicBool::new(false),
+        });
+        let server = Arc::clone(&shared);
+        let thread = thread::Builder::new()
+            .name("softrender-preview".to_string())
+            .spawn(move || serve(&listener, &server))?;
+        Ok(Self {
+            shared,
+            address,
+            thread: Some(thread),
+        })
+    }
+    /// Address the server listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+    /// Publish a copy of `canvas` as the current frame
+    pub fn update(&self, canvas: &Canvas) {
+        *lock(&self.shared.frame) = Some(canvas.clone());
+        self.shared.generation.fetch_add(1, Ordering::Relaxed);
+    }
+    /// Number of frames published so far
+    pub fn generation(&self) -> u64 {
+        self.shared.generation.load(Ordering::Relaxed)
+    }
+    /// Set how often the page reloads the frame, one second by default
+    pub fn set_refresh(&self, interval: Duration) {
+        *lock(&self.shared.refresh) = interval;
+    }
+}
+
+impl Drop for PreviewServer {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+        // Wake the blocking accept so the thread sees the stop flag
+        let _ = TcpStream::connect(self.address);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Lock `mutex`, carrying on with the data of a thread that panicked while holding it
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+fn serve(listener: &TcpListener, shared: &Shared) {
+    for stream in listener.incoming() {
+        if shared.stop.load(Ordering::Relaxed) {
+            break;
+        }
+        let result = stream.and_then(|stream| {
+            stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+            stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+            respond(stream, shared)
+        });
+        if let Err(error) = result {
+            tracing::debug!("preview request failed: {}", error);
+        }
+    }
+}
+
+fn respond(stream: TcpStream, shared: &Shared) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // Skip the headers, nothing in them changes the response
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let mut stream = reader.into_inner();
+    let mut parts = request.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let path = target.split('?').next().unwrap_or("");
+    if method != "GET" {
+        return send(&mut stream, "405 Method Not Allowed", "text/plain", b"method not allowed\n");
+    }
+    match path {
+        "/" | "/index.html" => {
+            let page = page(*lock(&shared.refresh), shared.generation.load(Ordering::Relaxed));
+            send(&mut stream, "200 OK", "text/html; charset=utf-8", page.as_bytes())
+        }
+        "/frame.png" | "/frame.ppm" => {
+            // Encode a copy so the render thread is not held up by slow clients
+            let frame = lock(&shared.frame).clone();
+            let frame = match frame {
+                Some(frame) => frame,
+                None => return send(&mut stream, "503 Service Unavailable", "text/plain", b"no frame yet\n"),
+            };
+            let mut body = Vec::new();
+            if path == "/frame.png" {
+                frame.to_png(&mut body)?;
+                send(&mut stream, "200 OK", "image/png", &body)
+            } else {
+                frame.to_ppm(&mut body)?;
+                send(&mut stream, "200 OK", "image/x-portable-pixmap", &body)
+            }
+        }
+        _ => send(&mut stream, "404 Not Found", "text/plain", b"not found\n"),
+    }
+}
+
+fn send(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+/// Page reloading the frame every `refresh`, keeping the old image up until the new one
+/// has loaded so the preview does not flicker
+fn page(refresh: Duration, generation: u64) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head><title>softrender preview</title></head>
+<body style="margin:0;background:#202020;color:#c0c0c0;font-family:monospace">
+<img id="frame" src="/frame.png?{generation}" style="display:block;margin:auto;image-rendering:pixelated">
+<script>
+setInterval(function () {{
+    var next = new Image();
+    next.onload = function () {{ document.getElementById("frame").src = next.src; }};
+    next.src = "/frame.png?" + Date.now();
+}}, {interval});
+</script>
+</body>
+</html>
+"#,
+        generation = generation,
+        interval = refresh.as_millis().max(1),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PreviewServer;
+    use crate::canvas::{Canvas, Color};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    fn get(server: &PreviewServer, path: &str) -> Vec<u8> {
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        response
+    }
+
+    fn body(response: &[u8]) -> &[u8] {
+        let end = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        &response[end + 4..]
+    }
+
+    #[test]
+    fn test_preview() {
+        let server = PreviewServer::bind("127.0.0.1:0").unwrap();
+        assert!(get(&server, "/frame.png").starts_with(b"HTTP/1.1 503"));
+        let mut canvas = Canvas::new(3, 2);
+        canvas.set(2, 1, Color::white());
+        server.update(&canvas);
+        assert_eq!(server.generation(), 1);
+        let page = get(&server, "/");
+        assert!(page.starts_with(b"HTTP/1.1 200 OK"));
+        assert!(String::from_utf8_lossy(body(&page)).contains("<img id=\"frame\" src=\"/frame.png?1\""));
+        let png = get(&server, "/frame.png?123");
+        assert!(png.starts_with(b"HTTP/1.1 200 OK"));
+        assert!(body(&png).starts_with(b"\x89PNG"));
+        let ppm = get(&server, "/frame.ppm");
+        assert!(body(&ppm).starts_with(b"P3\n3 2\n"));
+        assert!(get(&server, "/missing").starts_with(b"HTTP/1.1 404"));
+    }
+}
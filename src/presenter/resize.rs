@@ -0,0 +1,251 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::canvas::{Canvas, Color};
+use crate::input::InputState;
+use crate::shader::Camera;
+
+/// What happens to the frame buffer when the window changes size
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ResizePolicy {
+    /// Reallocate the canvas to the window size and update the camera aspect ratio
+    Reallocate,
+    /// Keep the canvas size and show it centered with black bars, keeping its aspect ratio
+    Letterbox,
+    /// Keep the canvas size and stretch it over the whole window
+    Stretch,
+}
+
+/// Area of the window a frame is shown in, in window pixels
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Viewport {
+    /// Largest area of a `window` sized window showing a `frame` sized frame undistorted
+    pub fn letterbox(frame: (usize, usize), window: (usize, usize)) -> Viewport {
+        let (frame_width, frame_height) = frame;
+        let (window_width, window_height) = window;
+        if frame_width == 0 || frame_height == 0 {
+            return Viewport::full(window);
+        }
+        // Compare aspect ratios with integers to avoid rounding a matching size
+        let (width, height) = if window_width * frame_height > window_height * frame_width {
+            (window_height * frame_width / frame_height, window_height)
+        } else {
+            (window_width, window_width * frame_height / frame_width)
+        };
+        Viewport {
+            x: (window_width - width) / 2,
+            y: (window_height - height) / 2,
+            width,
+            height,
+        }
+    }
+    /// Whole of a `window` sized window
+    pub fn full(window: (usize, usize)) -> Viewport {
+        Viewport {
+            x: 0,
+            y: 0,
+            width: window.0,
+            height: window.1,
+        }
+    }
+}
+
+/// Notification that the window was resized and how the frame was adapted
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Resized {
+    /// New window size
+    pub window: (usize, usize),
+    /// Canvas size after applying the policy
+    pub canvas: (usize, usize),
+    /// Area of the window the canvas is shown in
+    pub viewport: Viewport,
+}
+
+///
+/// Applies a `ResizePolicy` to the canvas and camera when the window is resized.
+///
+/// ```
+/// use softrender::canvas::Canvas;
+/// use softrender::input::{Event, InputState};
+/// use softrender::presenter::{ResizeHandler, ResizePolicy};
+///
+/// let mut canvas = Canvas::new(640, 480);
+/// let mut handler = ResizeHandler::new(ResizePolicy::Reallocate, (640, 480));
+/// let mut input = InputState::new();
+/// input.handle(&Event::Resize { width: 800, height: 600 });
+/// let resized = handler.handle(&input, &mut canvas, None).unwrap();
+/// assert_eq!(resized.canvas, (800, 600));
+/// assert_eq!(canvas.width(), 800);
+/// ```
+///
+pub struct ResizeHandler {
+    policy: ResizePolicy,
+    window: (usize, usize),
+    viewport: Viewport,
+}
+
+impl ResizeHandler {
+    /// Create new ResizeHandler for a window of `window` size showing a canvas of the same size
+    pub fn new(policy: ResizePolicy, window: (usize, usize)) -> ResizeHandler {
+        ResizeHandler {
+            policy,
+            window,
+            viewport: Viewport::full(window),
+        }
+    }
+    /// Get the policy in use
+    pub fn policy(&self) -> ResizePolicy {
+        self.policy
+    }
+    /// Current window size
+    pub fn window(&self) -> (usize, usize) {
+        self.window
+    }
+    /// Area of the window the canvas is shown in
+    pub fn viewport(&self) -> Viewport {
+        self.viewport
+    }
+    /// Apply a resize reported this frame by `input`, returning what changed
+    pub fn handle(&mut self, input: &InputState, canvas: &mut Canvas, camera: Option<&mut Camera>) -> Option<Resized> {
+        input.resized().map(|window| self.resize(window, canvas, camera))
+    }
+    /// Apply a resize of the window to `window`.
+    ///
+    /// A zero sized window, as reported by some platforms while minimized, leaves the
+    /// canvas and camera alone.
+    pub fn resize(&mut self, window: (usize, usize), canvas: &mut Canvas, camera: Option<&mut Camera>) -> Resized {
+        self.window = window;
+        let minimized = window.0 == 0 || window.1 == 0;
+        if self.policy == ResizePolicy::Reallocate && !minimized && (canvas.width(), canvas.height()) != window {
+            *canvas = Canvas::with_layout(window.0, window.1, canvas.layout());
+        }
+        if let (ResizePolicy::Reallocate, Some(camera)) = (self.policy, camera) {
+            camera.set_viewport(canvas.width(), canvas.height());
+        }
+        let frame = (canvas.width(), canvas.height());
+        self.viewport = match self.policy {
+            ResizePolicy::Letterbox => Viewport::letterbox(frame, window),
+            ResizePolicy::Reallocate | ResizePolicy::Stretch => Viewport::full(window),
+        };
+        Resized {
+            window,
+            canvas: frame,
+            viewport: self.viewport,
+        }
+    }
+    /// Frame to present in place of `canvas`, or `None` to present `canvas` as is.
+    ///
+    /// With `ResizePolicy::Letterbox` this is a window sized frame with `canvas` scaled
+    /// into the viewport and black bars around it.
+    pub fn compose(&self, canvas: &Canvas) -> Option<Canvas> {
+        let (width, height) = self.window;
+        let letterboxed = self.policy == ResizePolicy::Letterbox && (canvas.width(), canvas.height()) != self.window;
+        if !letterboxed || width == 0 || height == 0 {
+            return None;
+        }
+        let mut frame = Canvas::new(width, height);
+        frame.fill(Color::black());
+        let viewport = self.viewport;
+        for y in 0..viewport.height {
+            let source_y = y * canvas.height() / viewport.height;
+            for x in 0..viewport.width {
+                let source_x = x * canvas.width() / viewport.width;
+                frame.set(viewport.x + x, viewport.y + y, canvas.get(source_x, source_y));
+            }
+        }
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ResizeHandler, ResizePolicy, Viewport};
+    use crate::canvas::{Canvas, Color, Layout};
+    use crate::math::Vec3f;
+    use crate::shader::Camera;
+
+    fn camera() -> Camera {
+        Camera::new(
+            Vec3f::from_parts(0.0, 0.0, 0.0),
+            Vec3f::from_parts(0.0, 0.0, -1.0),
+            Vec3f::from_parts(0.0, 1.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn test_letterbox_viewport() {
+        let pillarbox = Viewport::letterbox((640, 480), (1000, 480));
+        assert_eq!(pillarbox, Viewport { x: 180, y: 0, width: 640, height: 480 });
+        let letterbox = Viewport::letterbox((640, 480), (320, 480));
+        assert_eq!(letterbox, Viewport { x: 0, y: 120, width: 320, height: 240 });
+        assert_eq!(Viewport::letterbox((4, 3), (8, 6)), Viewport::full((8, 6)));
+    }
+
+    #[test]
+    fn test_reallocate() {
+        let mut canvas = Canvas::with_layout(64, 48, Layout::Tiled);
+        let mut camera = camera();
+        let mut handler = ResizeHandler::new(ResizePolicy::Reallocate, (64, 48));
+        let resized = handler.resize((128, 32), &mut canvas, Some(&mut camera));
+        assert_eq!(resized.canvas, (128, 32));
+        assert_eq!(resized.viewport, Viewport::full((128, 32)));
+        assert_eq!((canvas.width(), canvas.height()), (128, 32));
+        assert_eq!(canvas.layout(), Layout::Tiled);
+        assert_eq!(camera.aspect_ratio, 4.0);
+        assert!(handler.compose(&canvas).is_none());
+        // Minimizing keeps the last canvas
+        handler.resize((0, 0), &mut canvas, Some(&mut camera));
+        assert_eq!((canvas.width(), canvas.height()), (128, 32));
+        assert_eq!(camera.aspect_ratio, 4.0);
+    }
+
+    #[test]
+    fn test_letterbox_compose() {
+        let mut canvas = Canvas::new(4, 2);
+        canvas.fill(Color::white());
+        let mut camera = camera();
+        let aspect_ratio = camera.aspect_ratio;
+        let mut handler = ResizeHandler::new(ResizePolicy::Letterbox, (4, 2));
+        assert!(handler.compose(&canvas).is_none());
+        let resized = handler.resize((8, 8), &mut canvas, Some(&mut camera));
+        assert_eq!(resized.canvas, (4, 2));
+        assert_eq!(resized.viewport, Viewport { x: 0, y: 2, width: 8, height: 4 });
+        assert_eq!(camera.aspect_ratio, aspect_ratio);
+        let frame = handler.compose(&canvas).unwrap();
+        assert_eq!((frame.width(), frame.height()), (8, 8));
+        assert_eq!(frame.get(3, 1), Color::black());
+        assert_eq!(frame.get(3, 2), Color::white());
+        assert_eq!(frame.get(7, 5), Color::white());
+        assert_eq!(frame.get(7, 6), Color::black());
+    }
+
+    #[test]
+    fn test_stretch() {
+        let mut canvas = Canvas::new(4, 2);
+        let mut handler = ResizeHandler::new(ResizePolicy::Stretch, (4, 2));
+        let resized = handler.resize((8, 8), &mut canvas, None);
+        assert_eq!(resized.canvas, (4, 2));
+        assert_eq!(resized.viewport, Viewport::full((8, 8)));
+        assert!(handler.compose(&canvas).is_none());
+    }
+}
@@ -0,0 +1,344 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Lightweight instrumentation of render stages.
+//!
+//! `profile_scope!` times the rest of the enclosing block when profiling is enabled, and
+//! costs a single flag check when it is not. Recorded spans are collected from every
+//! thread and can be summed per stage or exported for `chrome://tracing`.
+//!
+//! Alongside the timings each thread counts the work the rasterizer did in
+//! `PipelineStats`, which are always collected since they cost one update per draw.
+//!
+//! ```
+//! use softrender::profile;
+//! use softrender::profile_scope;
+//!
+//! profile::set_enabled(true);
+//! {
+//!     profile_scope!("frame");
+//!     profile_scope!("clip");
+//! }
+//! let spans = profile::take_spans();
+//! let json = profile::chrome_trace(&spans);
+//! assert!(json.starts_with("{\"traceEvents\":["));
+//! ```
+
+use crate::Result;
+use std::cell::Cell;
+use std::io::{self, Write};
+use std::ops;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static RECORDER: Mutex<Recorder> = Mutex::new(Recorder {
+    epoch: None,
+    spans: Vec::new(),
+});
+static NEXT_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static THREAD: usize = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+    static STATS: Cell<PipelineStats> = Cell::new(PipelineStats::default());
+}
+
+struct Recorder {
+    epoch: Option<Instant>,
+    spans: Vec<Span>,
+}
+
+/// Time `name` took, from where this macro is used to the end of the enclosing block
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_scope = $crate::profile::Scope::new($name);
+    };
+}
+
+/// Timing of one run of a profiled stage
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub name: &'static str,
+    /// Small number identifying the thread the stage ran on
+    pub thread: usize,
+    /// When the stage started, since profiling was first enabled
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+/// Total time spent in one stage
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StageTiming {
+    pub name: &'static str,
+    pub total: Duration,
+    pub calls: usize,
+}
+
+/// Work done drawing triangles
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PipelineStats {
+    /// Triangles passed to a draw call
+    pub triangles_submitted: u64,
+    /// Triangles that reached the pixel loop
+    pub triangles_drawn: u64,
+    /// Triangles wholly outside the view
+    pub frustum_culled: u64,
+    /// Triangles too small to cover the center of any pixel
+    pub small_culled: u64,
+    /// Fragments that passed the depth test and were written
+    pub pixels_shaded: u64,
+    /// Fragments hidden behind what was already drawn
+    pub depth_rejected: u64,
+    /// Draws executed from command buffers
+    pub draw_calls: u64,
+    /// Rasterizer calls those draws were merged into
+    pub batches: u64,
+    /// Camera and material changes executed from command buffers
+    pub state_changes: u64,
+}
+
+impl PipelineStats {
+    /// Average number of draws merged into each batch, 1.0 if nothing was merged or no
+    /// command buffers were executed
+    pub fn draws_per_batch(&self) -> f32 {
+        if self.batches == 0 {
+            1.0
+        } else {
+            self.draw_calls as f32 / self.batches as f32
+        }
+    }
+}
+
+impl ops::AddAssign<Self> for PipelineStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.triangles_submitted += rhs.triangles_submitted;
+        self.triangles_drawn += rhs.triangles_drawn;
+        self.frustum_culled += rhs.frustum_culled;
+        self.small_culled += rhs.small_culled;
+        self.pixels_shaded += rhs.pixels_shaded;
+        self.depth_rejected += rhs.depth_rejected;
+        self.draw_calls += rhs.draw_calls;
+        self.batches += rhs.batches;
+        self.state_changes += rhs.state_changes;
+    }
+}
+
+/// Add the work of a draw call to the counts of this thread
+pub fn record_stats(stats: &PipelineStats) {
+    STATS.with(|counts| {
+        let mut total = counts.get();
+        total += *stats;
+        counts.set(total);
+    });
+}
+
+/// Work counted on this thread since the last `take_stats`
+pub fn stats() -> PipelineStats {
+    STATS.with(Cell::get)
+}
+
+/// Take the work counted on this thread, such as at the end of each frame
+pub fn take_stats() -> PipelineStats {
+    STATS.with(|counts| counts.replace(PipelineStats::default()))
+}
+
+/// Start or stop recording spans
+pub fn set_enabled(enabled: bool) {
+    if enabled {
+        RECORDER.lock().unwrap().epoch.get_or_insert_with(Instant::now);
+    }
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Check if spans are being recorded
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Take every span recorded so far, such as at the end of each frame
+pub fn take_spans() -> Vec<Span> {
+    std::mem::take(&mut RECORDER.lock().unwrap().spans)
+}
+
+/// Sum `spans` per stage, the most expensive stage first
+pub fn totals(spans: &[Span]) -> Vec<StageTiming> {
+    let mut totals: Vec<StageTiming> = Vec::new();
+    for span in spans {
+        match totals.iter_mut().find(|stage| stage.name == span.name) {
+            Some(stage) => {
+                stage.total += span.duration;
+                stage.calls += 1;
+            }
+            None => totals.push(StageTiming {
+                name: span.name,
+                total: span.duration,
+                calls: 1,
+            }),
+        }
+    }
+    totals.sort_by_key(|stage| std::cmp::Reverse(stage.total));
+    totals
+}
+
+/// Format `spans` as Trace Event JSON, which `chrome://tracing` and Perfetto open
+pub fn chrome_trace(spans: &[Span]) -> String {
+    let mut json = Vec::new();
+    write_chrome_trace(&mut json, spans).expect("writing to a Vec does not fail");
+    String::from_utf8(json).expect("trace is valid UTF-8")
+}
+
+/// Write `spans` as Trace Event JSON to `writer`
+pub fn write_chrome_trace<W: Write>(writer: &mut W, spans: &[Span]) -> Result<()> {
+    write!(writer, "{{\"traceEvents\":[")?;
+    for (index, span) in spans.iter().enumerate() {
+        if index > 0 {
+            write!(writer, ",")?;
+        }
+        write!(writer, "{{\"name\":\"")?;
+        write_escaped(writer, span.name)?;
+        write!(
+            writer,
+            "\",\"cat\":\"softrender\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":{}}}",
+            span.start.as_micros(),
+            span.duration.as_micros(),
+            span.thread
+        )?;
+    }
+    write!(writer, "],\"displayTimeUnit\":\"ms\"}}")?;
+    Ok(())
+}
+
+/// Write `text` escaped for use inside a JSON string
+fn write_escaped<W: Write>(writer: &mut W, text: &str) -> io::Result<()> {
+    for c in text.chars() {
+        match c {
+            '"' => write!(writer, "\\\"")?,
+            '\\' => write!(writer, "\\\\")?,
+            c if (c as u32) < 0x20 => write!(writer, "\\u{:04x}", c as u32)?,
+            c => write!(writer, "{}", c)?,
+        }
+    }
+    Ok(())
+}
+
+/// Records a span from its creation until it is dropped, see `profile_scope!`
+pub struct Scope {
+    name: &'static str,
+    start: Option<Instant>,
+}
+
+impl Scope {
+    /// Start timing `name` if profiling is enabled
+    pub fn new(name: &'static str) -> Scope {
+        Scope {
+            name,
+            start: if is_enabled() { Some(Instant::now()) } else { None },
+        }
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        if let Some(start) = self.start {
+            let duration = start.elapsed();
+            let thread = THREAD.with(|thread| *thread);
+            let mut recorder = RECORDER.lock().unwrap();
+            let epoch = *recorder.epoch.get_or_insert(start);
+            recorder.spans.push(Span {
+                name: self.name,
+                thread,
+                start: start.saturating_duration_since(epoch),
+                duration,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chrome_trace, totals, PipelineStats, Span};
+    use std::thread;
+    use std::time::Duration;
+
+    fn span(name: &'static str, start: u64, duration: u64) -> Span {
+        Span {
+            name,
+            thread: 0,
+            start: Duration::from_micros(start),
+            duration: Duration::from_micros(duration),
+        }
+    }
+
+    #[test]
+    fn test_scopes() {
+        super::set_enabled(true);
+        {
+            profile_scope!("profile test outer");
+            thread::sleep(Duration::from_millis(2));
+            profile_scope!("profile test inner");
+        }
+        super::set_enabled(false);
+        {
+            profile_scope!("profile test disabled");
+        }
+        // Other tests may record spans of their own meanwhile
+        let spans: Vec<Span> = super::take_spans().into_iter().filter(|s| s.name.starts_with("profile test")).collect();
+        assert_eq!(spans.len(), 2);
+        // The inner scope ends first
+        assert_eq!(spans[0].name, "profile test inner");
+        assert!(spans[1].duration >= Duration::from_millis(2));
+        assert!(spans[0].start >= spans[1].start);
+    }
+
+    #[test]
+    fn test_stats() {
+        // Counts are per thread, so other tests drawing meanwhile do not show up
+        thread::spawn(|| {
+            assert_eq!(super::take_stats(), PipelineStats::default());
+            let draw = PipelineStats {
+                triangles_submitted: 3,
+                pixels_shaded: 40,
+                ..PipelineStats::default()
+            };
+            super::record_stats(&draw);
+            super::record_stats(&draw);
+            assert_eq!(super::stats().pixels_shaded, 80);
+            assert_eq!(super::take_stats().triangles_submitted, 6);
+            assert_eq!(super::stats(), PipelineStats::default());
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_totals() {
+        let spans = [span("clip", 0, 10), span("shade", 10, 50), span("clip", 60, 15)];
+        let stages = totals(&spans);
+        assert_eq!(stages.len(), 2);
+        assert_eq!((stages[0].name, stages[0].calls), ("shade", 1));
+        assert_eq!((stages[1].total, stages[1].calls), (Duration::from_micros(25), 2));
+    }
+
+    #[test]
+    fn test_chrome_trace() {
+        assert_eq!(chrome_trace(&[]), "{\"traceEvents\":[],\"displayTimeUnit\":\"ms\"}");
+        let json = chrome_trace(&[span("clip", 5, 7), span("a \"b\"", 12, 1)]);
+        assert!(json.contains("{\"name\":\"clip\",\"cat\":\"softrender\",\"ph\":\"X\",\"ts\":5,\"dur\":7,\"pid\":1,\"tid\":0}"));
+        assert!(json.contains("\"name\":\"a \\\"b\\\"\""));
+    }
+}
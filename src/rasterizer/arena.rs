@@ -0,0 +1,215 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::Rasterizer;
+use std::cell::RefCell;
+
+/// Elements reserved up front by the rasterizer scratch arena of each thread
+const SCRATCH_CAPACITY: usize = 1024;
+
+thread_local! {
+    static SCRATCH: RefCell<Arena<f32>> = RefCell::new(Arena::with_capacity(SCRATCH_CAPACITY));
+}
+
+/// Memory use of an `Arena`, in elements
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArenaStats {
+    /// Elements the arena holds without growing
+    pub capacity: usize,
+    /// Elements currently allocated
+    pub used: usize,
+    /// Most elements allocated at once since creation or `reset_high_water`
+    pub high_water: usize,
+    /// Times the arena had to grow, each a heap allocation
+    pub grows: usize,
+}
+
+///
+/// Bump allocator for short lived scratch data.
+///
+/// Allocations are carved off the end of one buffer and freed together by releasing back
+/// to a `mark`, so reusing an arena every frame costs no heap allocations once it has
+/// grown to the frame's high water.
+///
+/// ```
+/// use softrender::Arena;
+///
+/// let mut arena = Arena::with_capacity(16);
+/// let mark = arena.mark();
+/// arena.push(3.0f32);
+/// arena.push(1.0);
+/// arena.since(mark).sort_by(|a, b| a.partial_cmp(b).unwrap());
+/// assert_eq!(arena.since(mark), &[1.0, 3.0]);
+/// arena.release(mark);
+/// assert_eq!(arena.stats().high_water, 2);
+/// ```
+///
+#[derive(Clone, Debug, Default)]
+pub struct Arena<T> {
+    storage: Vec<T>,
+    used: usize,
+    high_water: usize,
+    grows: usize,
+}
+
+impl<T: Copy + Default> Arena<T> {
+    /// Create new empty Arena
+    pub fn new() -> Arena<T> {
+        Arena::with_capacity(0)
+    }
+    /// Create new Arena holding `capacity` elements before it grows
+    pub fn with_capacity(capacity: usize) -> Arena<T> {
+        Arena {
+            storage: vec![T::default(); capacity],
+            used: 0,
+            high_water: 0,
+            grows: 0,
+        }
+    }
+    /// Grow to hold at least `capacity` elements without further allocations
+    pub fn reserve(&mut self, capacity: usize) {
+        if capacity > self.storage.len() {
+            self.storage.resize(capacity, T::default());
+        }
+    }
+    /// Position to `release` back to, freeing everything allocated after it
+    pub fn mark(&self) -> usize {
+        self.used
+    }
+    /// Free everything allocated since `mark` was taken
+    pub fn release(&mut self, mark: usize) {
+        self.used = self.used.min(mark);
+    }
+    /// Free everything, keeping the memory for reuse
+    pub fn reset(&mut self) {
+        self.used = 0;
+    }
+    /// Allocate `len` elements set to their default
+    pub fn alloc(&mut self, len: usize) -> &mut [T] {
+        let start = self.used;
+        self.grow_to(start + len);
+        let slice = &mut self.storage[start..start + len];
+        for value in slice.iter_mut() {
+            *value = T::default();
+        }
+        slice
+    }
+    /// Allocate one element holding `value`, right after the previous allocation
+    pub fn push(&mut self, value: T) {
+        let index = self.used;
+        self.grow_to(index + 1);
+        self.storage[index] = value;
+    }
+    /// Everything allocated since `mark` was taken, as one slice
+    pub fn since(&mut self, mark: usize) -> &mut [T] {
+        &mut self.storage[mark.min(self.used)..self.used]
+    }
+    /// Memory use of this Arena
+    pub fn stats(&self) -> ArenaStats {
+        ArenaStats {
+            capacity: self.storage.len(),
+            used: self.used,
+            high_water: self.high_water,
+            grows: self.grows,
+        }
+    }
+    /// Start measuring the high water anew, such as at the start of a frame
+    pub fn reset_high_water(&mut self) {
+        self.high_water = self.used;
+    }
+    fn grow_to(&mut self, used: usize) {
+        if used > self.storage.len() {
+            // Doubling keeps the number of allocations logarithmic in the high water
+            self.storage.resize(used.max(2 * self.storage.len()), T::default());
+            self.grows += 1;
+        }
+        self.used = used;
+        self.high_water = self.high_water.max(used);
+    }
+}
+
+/// Run `f` with the rasterizer scratch arena of this thread
+pub(super) fn with_scratch<R, F: FnOnce(&mut Arena<f32>) -> R>(f: F) -> R {
+    SCRATCH.with(|scratch| f(&mut scratch.borrow_mut()))
+}
+
+impl Rasterizer {
+    /// Reserve `capacity` elements in the scratch arena the rasterizer uses on this thread
+    /// for per primitive temporaries such as polygon edge crossings
+    pub fn set_scratch_capacity(capacity: usize) {
+        with_scratch(|scratch| scratch.reserve(capacity));
+    }
+    /// Memory use of the scratch arena of this thread
+    pub fn scratch_stats() -> ArenaStats {
+        with_scratch(|scratch| scratch.stats())
+    }
+    /// Start measuring the scratch high water of this thread anew, such as each frame
+    pub fn reset_scratch_stats() {
+        with_scratch(Arena::reset_high_water);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Arena;
+    use crate::canvas::{Canvas, Color};
+    use crate::math::Vec2f;
+    use crate::Rasterizer;
+
+    #[test]
+    fn test_bump() {
+        let mut arena: Arena<u32> = Arena::with_capacity(4);
+        let outer = arena.mark();
+        arena.alloc(2).copy_from_slice(&[7, 8]);
+        let inner = arena.mark();
+        arena.push(9);
+        assert_eq!(arena.since(outer), &[7, 8, 9]);
+        arena.release(inner);
+        assert_eq!(arena.since(outer), &[7, 8]);
+        // Growing past the capacity allocates once and keeps earlier allocations
+        assert_eq!(arena.alloc(6), &[0; 6]);
+        assert_eq!(&arena.since(outer)[..2], &[7, 8]);
+        let stats = arena.stats();
+        assert_eq!((stats.used, stats.high_water, stats.grows), (8, 8, 1));
+        assert!(stats.capacity >= 8);
+        arena.reset();
+        arena.reset_high_water();
+        arena.alloc(3);
+        assert_eq!(arena.stats().high_water, 3);
+        assert_eq!(arena.stats().grows, 1);
+    }
+
+    #[test]
+    fn test_rasterizer_scratch() {
+        Rasterizer::set_scratch_capacity(4096);
+        Rasterizer::reset_scratch_stats();
+        let mut canvas = Canvas::new(16, 16);
+        let star: Vec<Vec2f> = (0..10)
+            .map(|i| {
+                let angle = i as f32 * std::f32::consts::PI / 5.0;
+                let radius = if i % 2 == 0 { 7.0 } else { 3.0 };
+                Vec2f::from_parts(8.0 + radius * angle.cos(), 8.0 + radius * angle.sin())
+            })
+            .collect();
+        Rasterizer::fill_polygon(&mut canvas, &star, Color::white());
+        assert_eq!(canvas.get(8, 8), Color::white());
+        let stats = Rasterizer::scratch_stats();
+        assert_eq!(stats.used, 0);
+        assert!(stats.high_water >= 2);
+        assert!(stats.capacity >= 4096);
+        assert_eq!(stats.grows, 0);
+    }
+}
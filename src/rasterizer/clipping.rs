@@ -0,0 +1,236 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::velocity::scan_triangle;
+use super::Rasterizer;
+use crate::math::{Vec2f, Vec4f};
+
+/// Most vertices a triangle has after clipping, one more per plane of the view volume
+pub const MAX_CLIPPED_VERTICES: usize = 9;
+
+/// Corner of a clipped triangle
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ClipVertex {
+    /// Position in clip space
+    pub position: Vec4f,
+    /// Barycentric weights of the corners of the triangle that was clipped at this point,
+    /// to interpolate its vertex attributes with
+    pub weights: [f32; 3],
+}
+
+///
+/// Convex polygon left of a triangle after clipping it to the view volume.
+///
+/// The polygon keeps the winding of the triangle and is drawn as a fan of triangles
+/// around its first vertex.
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ClippedPolygon {
+    vertices: [ClipVertex; MAX_CLIPPED_VERTICES],
+    count: usize,
+}
+
+impl ClippedPolygon {
+    /// Vertices in order around the polygon
+    pub fn vertices(&self) -> &[ClipVertex] {
+        &self.vertices[..self.count]
+    }
+    /// Check if nothing of the triangle was left
+    pub fn is_empty(&self) -> bool {
+        self.count < 3
+    }
+    /// Triangles of the fan covering the polygon
+    pub fn triangles(&self) -> impl Iterator<Item = [ClipVertex; 3]> + '_ {
+        let vertices = self.vertices();
+        (2..vertices.len()).map(move |index| [vertices[0], vertices[index - 1], vertices[index]])
+    }
+    /// Positions of the vertices in a `width` by `height` buffer, in pixels
+    pub(super) fn screen(&self, width: usize, height: usize) -> impl Iterator<Item = Vec2f> + '_ {
+        self.vertices().iter().map(move |vertex| to_screen(vertex.position, width, height))
+    }
+    fn push(&mut self, vertex: ClipVertex) {
+        self.vertices[self.count] = vertex;
+        self.count += 1;
+    }
+}
+
+/// Position of `clip` in a `width` by `height` buffer, in pixels
+fn to_screen(clip: Vec4f, width: usize, height: usize) -> Vec2f {
+    Vec2f::from_parts(
+        (clip.x / clip.w + 1.0) / 2.0 * width as f32,
+        (1.0 - clip.y / clip.w) / 2.0 * height as f32,
+    )
+}
+
+/// Signed distances of a clip space point inside each plane of the view volume,
+/// `-w <= x, y, z <= w`, negative outside. The near plane comes first so nothing behind
+/// the camera reaches the others.
+const PLANES: [fn(Vec4f) -> f32; 6] = [
+    |p| p.w + p.z,
+    |p| p.w - p.z,
+    |p| p.w + p.x,
+    |p| p.w - p.x,
+    |p| p.w + p.y,
+    |p| p.w - p.y,
+];
+
+impl Rasterizer {
+    /// Clip the triangle with corners `clip` in clip space to the view volume, the part of
+    /// clip space between the near and far planes and inside the edges of the view.
+    ///
+    /// Sutherland-Hodgman clipping against one plane after another, interpolating in clip
+    /// space before the perspective divide, so triangles reaching behind the camera are cut
+    /// at the near plane instead of wrapping around through infinity. Triangles wholly
+    /// inside come back unchanged.
+    ///
+    /// ```
+    /// use softrender::math::Vec4f;
+    /// use softrender::Rasterizer;
+    ///
+    /// // One corner behind the camera, which has a w of 1 at the near plane
+    /// let polygon = Rasterizer::clip_triangle([
+    ///     Vec4f::from_parts(0.0, 0.0, 0.0, 2.0),
+    ///     Vec4f::from_parts(1.0, 0.0, 0.0, 2.0),
+    ///     Vec4f::from_parts(0.0, 0.0, -4.0, -2.0),
+    /// ]);
+    /// assert_eq!(polygon.vertices().len(), 4);
+    /// assert!(polygon.vertices().iter().all(|vertex| vertex.position.w > 0.0));
+    /// ```
+    pub fn clip_triangle(clip: [Vec4f; 3]) -> ClippedPolygon {
+        let mut polygon = ClippedPolygon::default();
+        let corners = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        for (position, weights) in clip.iter().zip(corners.iter()) {
+            polygon.push(ClipVertex {
+                position: *position,
+                weights: *weights,
+            });
+        }
+        for distance in PLANES.iter() {
+            if polygon.vertices().iter().all(|vertex| distance(vertex.position) >= 0.0) {
+                continue;
+            }
+            let input = polygon;
+            polygon = ClippedPolygon::default();
+            let vertices = input.vertices();
+            for (index, &current) in vertices.iter().enumerate() {
+                let previous = vertices[(index + vertices.len() - 1) % vertices.len()];
+                let (d0, d1) = (distance(previous.position), distance(current.position));
+                if (d0 >= 0.0) != (d1 >= 0.0) {
+                    let t = d0 / (d0 - d1);
+                    let lerp = |a: f32, b: f32| a + (b - a) * t;
+                    polygon.push(ClipVertex {
+                        position: previous.position + (current.position - previous.position) * t,
+                        weights: [
+                            lerp(previous.weights[0], current.weights[0]),
+                            lerp(previous.weights[1], current.weights[1]),
+                            lerp(previous.weights[2], current.weights[2]),
+                        ],
+                    });
+                }
+                if d1 >= 0.0 {
+                    polygon.push(current);
+                }
+            }
+            if polygon.is_empty() {
+                return ClippedPolygon::default();
+            }
+        }
+        polygon
+    }
+}
+
+/// Call `fragment` with the position, Pixel center and perspective correct barycentric
+/// weights of the clipped triangle for every Pixel of a `width` by `height` buffer covered
+/// by `polygon`
+pub(super) fn scan_polygon<F: FnMut(usize, usize, Vec2f, [f32; 3])>(
+    width: usize,
+    height: usize,
+    polygon: &ClippedPolygon,
+    mut fragment: F,
+) {
+    for [a, b, c] in polygon.triangles() {
+        let screen = [a, b, c].map(|vertex| to_screen(vertex.position, width, height));
+        let w = [a.position.w, b.position.w, c.position.w];
+        scan_triangle(width, height, screen, w, |x, y, point, [pa, pb, pc]| {
+            let weight = |corner: usize| a.weights[corner] * pa + b.weights[corner] * pb + c.weights[corner] * pc;
+            fragment(x, y, point, [weight(0), weight(1), weight(2)]);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scan_polygon, Rasterizer};
+    use crate::math::{Vec2f, Vec4f};
+    use assert_approx_eq::assert_approx_eq;
+
+    fn clip(x: f32, y: f32, z: f32, w: f32) -> Vec4f {
+        Vec4f::from_parts(x, y, z, w)
+    }
+
+    #[test]
+    fn test_clip_inside() {
+        let triangle = [clip(-0.5, -0.5, 0.0, 1.0), clip(0.5, -0.5, 0.0, 1.0), clip(0.0, 0.5, 0.5, 1.0)];
+        let polygon = Rasterizer::clip_triangle(triangle);
+        let positions: Vec<Vec4f> = polygon.vertices().iter().map(|vertex| vertex.position).collect();
+        assert_eq!(positions, triangle.to_vec());
+        assert_eq!(polygon.vertices()[1].weights, [0.0, 1.0, 0.0]);
+        assert_eq!(polygon.triangles().count(), 1);
+        // Wholly outside one plane leaves nothing
+        let beyond = [clip(2.0, 0.0, 0.0, 1.0), clip(3.0, 0.0, 0.0, 1.0), clip(2.0, 1.0, 0.0, 1.0)];
+        assert!(Rasterizer::clip_triangle(beyond).is_empty());
+        let behind = [clip(0.0, 0.0, 1.0, -1.0), clip(0.5, 0.0, 1.0, -1.0), clip(0.0, 0.5, 1.0, -1.0)];
+        assert!(Rasterizer::clip_triangle(behind).is_empty());
+    }
+
+    #[test]
+    fn test_clip_near() {
+        // Corner `c` lies behind the near plane, which cuts both edges reaching it
+        let triangle = [clip(0.0, 0.0, 0.0, 2.0), clip(1.0, 0.0, 0.0, 2.0), clip(0.0, 0.0, -4.0, -2.0)];
+        let polygon = Rasterizer::clip_triangle(triangle);
+        let vertices = polygon.vertices();
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(polygon.triangles().count(), 2);
+        for vertex in vertices {
+            assert!(vertex.position.z >= -vertex.position.w - 1e-5);
+            // Weights still rebuild the position from the corners
+            let [wa, wb, wc] = vertex.weights;
+            let rebuilt = triangle[0] * wa + triangle[1] * wb + triangle[2] * wc;
+            assert_approx_eq!((rebuilt - vertex.position).magnitude(), 0.0, 1e-5);
+        }
+        let cut: Vec<[f32; 3]> = vertices.iter().map(|vertex| vertex.weights).filter(|w| w[2] > 0.0).collect();
+        assert_eq!(cut.len(), 2);
+        assert_approx_eq!(cut[0][2], 0.25);
+        assert_approx_eq!(cut[1][2], 0.25);
+    }
+
+    #[test]
+    fn test_scan_polygon() {
+        // Big triangle crossing behind the camera and every edge of the view
+        let triangle = [clip(-3.0, -0.5, 0.0, 1.0), clip(3.0, -0.5, 0.0, 1.0), clip(0.0, 1.0, -3.0, -1.0)];
+        let polygon = Rasterizer::clip_triangle(triangle);
+        assert!(!polygon.is_empty());
+        let mut covered = 0;
+        scan_polygon(8, 8, &polygon, |x, y, point, weights| {
+            assert!(x < 8 && y < 8);
+            assert_eq!(point, Vec2f::from_parts(x as f32 + 0.5, y as f32 + 0.5));
+            assert_approx_eq!(weights[0] + weights[1] + weights[2], 1.0, 1e-5);
+            assert!(weights.iter().all(|&weight| weight >= -1e-5));
+            covered += 1;
+        });
+        assert!(covered > 0);
+    }
+}
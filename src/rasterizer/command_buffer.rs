@@ -0,0 +1,315 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::Rasterizer;
+use crate::canvas::{AovBuffers, Texture};
+use crate::model::{Material, Mesh};
+use crate::profile::{self, PipelineStats};
+use crate::shader::Camera;
+
+/// Draw call or state change recorded into a `CommandBuffer`
+#[derive(Clone)]
+pub enum Command<'a> {
+    /// Reset every AOV of the target to its empty value
+    Clear,
+    /// Camera the following draws are seen through, boxed to keep draws small
+    SetCamera(Box<Camera>),
+    /// Material the following draws are drawn with
+    SetMaterial(&'a Material),
+    /// Rasterize a mesh with the current camera and material
+    Draw(&'a Mesh),
+}
+
+///
+/// Draw calls, state changes and clears recorded to be executed later.
+///
+/// Recording only stores references, so scene traversal can run apart from rasterizing,
+/// with several threads each recording their own buffer to `append` together. Before
+/// executing, `sort_by_state` can group the draws between clears and camera changes
+/// by material, cutting down state changes without changing the depth tested result.
+///
+/// Executing merges runs of draws of small meshes sharing the same state into batches
+/// drawn by one rasterizer call, and counts draws, batches and state changes in the
+/// `PipelineStats` of the thread.
+///
+/// ```
+/// use softrender::canvas::{Aov, AovBuffers};
+/// use softrender::math::Vec3f;
+/// use softrender::model::{cube, Material};
+/// use softrender::shader::Camera;
+/// use softrender::CommandBuffer;
+///
+/// let (mesh, material) = (cube(1.0), Material::new());
+/// let mut commands = CommandBuffer::new();
+/// commands.clear();
+/// commands.set_camera(Camera::new(Vec3f::new(0.0, 0.0, 3.0), -Vec3f::UNIT_Z, Vec3f::UNIT_Y));
+/// commands.set_material(&material);
+/// commands.draw(&mesh);
+/// let mut gbuffer = AovBuffers::new(8, 6, &[Aov::Albedo, Aov::Depth]);
+/// commands.execute(&mut gbuffer, &[]);
+/// assert!(gbuffer.get(4, 3).depth.is_finite());
+/// ```
+///
+#[derive(Clone)]
+pub struct CommandBuffer<'a> {
+    /// Meshes of at most this many triangles are merged with neighbouring draws sharing
+    /// their state, 0 draws every mesh on its own
+    pub small_draw: usize,
+    commands: Vec<Command<'a>>,
+}
+
+impl<'a> CommandBuffer<'a> {
+    /// Create new empty CommandBuffer
+    pub fn new() -> Self {
+        Self {
+            small_draw: 256,
+            commands: Vec::new(),
+        }
+    }
+    /// Number of recorded commands
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+    /// Check if no commands are recorded
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+    /// Recorded commands in execution order
+    pub fn commands(&self) -> &[Command<'a>] {
+        &self.commands
+    }
+    /// Remove every recorded command
+    pub fn reset(&mut self) {
+        self.commands.clear();
+    }
+    /// Record a clear of the target
+    pub fn clear(&mut self) {
+        self.commands.push(Command::Clear);
+    }
+    /// Record a change of camera
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.commands.push(Command::SetCamera(Box::new(camera)));
+    }
+    /// Record a change of material
+    pub fn set_material(&mut self, material: &'a Material) {
+        self.commands.push(Command::SetMaterial(material));
+    }
+    /// Record a draw of `mesh`
+    pub fn draw(&mut self, mesh: &'a Mesh) {
+        self.commands.push(Command::Draw(mesh));
+    }
+    /// Move the commands of `other` to the end of this buffer, continuing with the state
+    /// this buffer leaves behind until `other` changes it
+    pub fn append(&mut self, other: &mut CommandBuffer<'a>) {
+        self.commands.append(&mut other.commands);
+    }
+    /// Number of camera and material changes, the per draw overhead sorting reduces
+    pub fn state_changes(&self) -> usize {
+        let changes = self.commands.iter();
+        changes.filter(|command| matches!(command, Command::SetCamera(_) | Command::SetMaterial(_))).count()
+    }
+    /// Reorder the draws between clears and camera changes so draws sharing a material
+    /// follow each other, keeping their order otherwise.
+    ///
+    /// Alpha tested materials go last, after the opaque surfaces that may hide them, and
+    /// materials sharing a surface texture go together so its texels stay in cache. Draws
+    /// recorded before any material was set are dropped, as `execute` would skip them.
+    pub fn sort_by_state(&mut self) {
+        let mut sorted = Vec::with_capacity(self.commands.len());
+        let mut material = None;
+        let mut draws = Vec::new();
+        for command in std::mem::take(&mut self.commands) {
+            match command {
+                Command::SetMaterial(next) => material = Some(next),
+                Command::Draw(mesh) => draws.extend(material.map(|material| (material, mesh))),
+                barrier => {
+                    flush_draws(&mut sorted, &mut draws);
+                    sorted.push(barrier);
+                }
+            }
+        }
+        flush_draws(&mut sorted, &mut draws);
+        self.commands = sorted;
+    }
+    /// Run the recorded commands against the G-buffer `gbuffer`, drawing with
+    /// `Rasterizer::draw_gbuffer`. Draws before a camera and material are set are skipped.
+    pub fn execute(&self, gbuffer: &mut AovBuffers, textures: &[Texture]) {
+        crate::profile_scope!("command_buffer");
+        let mut stats = PipelineStats::default();
+        let mut camera = None;
+        let mut material = None;
+        let mut batch: Vec<&Mesh> = Vec::new();
+        let mut commands = self.commands.iter().peekable();
+        while let Some(command) = commands.next() {
+            match command {
+                Command::Clear => gbuffer.clear(),
+                Command::SetCamera(next) => {
+                    camera = Some(next.as_ref());
+                    stats.state_changes += 1;
+                }
+                Command::SetMaterial(next) => {
+                    material = Some(*next);
+                    stats.state_changes += 1;
+                }
+                Command::Draw(mesh) => {
+                    if let (Some(camera), Some(material)) = (camera, material) {
+                        batch.push(mesh);
+                        stats.draw_calls += 1;
+                        let small = |mesh: &Mesh| mesh.triangles().len() <= self.small_draw;
+                        let merges = match commands.peek() {
+                            Some(Command::Draw(next)) => small(mesh) && small(next),
+                            _ => false,
+                        };
+                        if !merges {
+                            Rasterizer::draw_gbuffer_batch(gbuffer, &batch, material, textures, camera);
+                            stats.batches += 1;
+                            batch.clear();
+                        }
+                    }
+                }
+            }
+        }
+        profile::record_stats(&stats);
+    }
+}
+
+impl<'a> Default for CommandBuffer<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Append `draws` to `commands` sorted by state, setting each material once
+fn flush_draws<'a>(commands: &mut Vec<Command<'a>>, draws: &mut Vec<(&'a Material, &'a Mesh)>) {
+    draws.sort_by_key(|&(material, _)| {
+        let texture = material.surface_texture.as_ref().map(|slot| slot.texture);
+        (material.alpha_cutoff.is_some(), texture, material as *const Material as usize)
+    });
+    let mut current: Option<&Material> = None;
+    for (material, mesh) in draws.drain(..) {
+        if !current.is_some_and(|current| std::ptr::eq(current, material)) {
+            commands.push(Command::SetMaterial(material));
+            current = Some(material);
+        }
+        commands.push(Command::Draw(mesh));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, CommandBuffer};
+    use crate::canvas::{Aov, AovBuffers, Color};
+    use crate::math::Vec3f;
+    use crate::model::{cube, Material, Mesh, Triangle};
+    use crate::shader::Camera;
+
+    fn camera() -> Camera {
+        let mut camera = Camera::new(Vec3f::new(0.0, 0.0, 8.0), -Vec3f::UNIT_Z, Vec3f::UNIT_Y);
+        camera.set_viewport(32, 32);
+        camera
+    }
+
+    fn moved(mesh: &Mesh, x: f32, z: f32) -> Mesh {
+        let offset = Vec3f::new(x, 0.0, z);
+        let triangles = mesh.triangles().iter();
+        Mesh::new(triangles.map(|t| Triangle::new(t.a + offset, t.b + offset, t.c + offset)).collect())
+    }
+
+    #[test]
+    fn test_sort_by_state() {
+        let mut red = Material::new();
+        red.surface = Color::new(1.0, 0.0, 0.0);
+        let mut blue = Material::new();
+        blue.surface = Color::new(0.0, 0.0, 1.0);
+        let cube = cube(1.0);
+        let meshes = [moved(&cube, -2.0, 0.0), moved(&cube, 0.0, 1.0), moved(&cube, 2.0, 0.0), moved(&cube, 0.0, 0.0)];
+        let mut commands = CommandBuffer::new();
+        commands.clear();
+        commands.set_camera(camera());
+        for (index, mesh) in meshes.iter().enumerate() {
+            commands.set_material(if index % 2 == 0 { &red } else { &blue });
+            commands.draw(mesh);
+        }
+        // Recorded on another thread and added at the end
+        let mut more = CommandBuffer::new();
+        more.draw(&cube);
+        commands.append(&mut more);
+        assert!(more.is_empty());
+        assert_eq!(commands.state_changes(), 5);
+        let mut unsorted = AovBuffers::new(32, 32, &[Aov::Albedo, Aov::Depth]);
+        commands.execute(&mut unsorted, &[]);
+        commands.sort_by_state();
+        assert_eq!(commands.len(), 9);
+        assert_eq!(commands.state_changes(), 3);
+        assert!(matches!(commands.commands()[0], Command::Clear));
+        let mut sorted = AovBuffers::new(32, 32, &[Aov::Albedo, Aov::Depth]);
+        crate::profile::take_stats();
+        commands.execute(&mut sorted, &[]);
+        // Each material's run of small cubes is drawn in one batch
+        let stats = crate::profile::take_stats();
+        assert_eq!((stats.draw_calls, stats.batches, stats.state_changes), (5, 2, 3));
+        assert_eq!(stats.draws_per_batch(), 2.5);
+        for (x, y) in (0..32).flat_map(|y| (0..32).map(move |x| (x, y))) {
+            assert_eq!(sorted.get(x, y), unsorted.get(x, y));
+        }
+        // The nearer blue cube hides the red one behind it whatever the order
+        assert_eq!(sorted.get(16, 16).albedo, Color::new(0.0, 0.0, 1.0));
+        commands.reset();
+        assert!(commands.is_empty());
+    }
+
+    #[test]
+    fn test_missing_state() {
+        let (mesh, material) = (cube(1.0), Material::new());
+        let mut commands = CommandBuffer::new();
+        commands.draw(&mesh);
+        commands.set_material(&material);
+        commands.draw(&mesh);
+        let mut gbuffer = AovBuffers::new(32, 32, &[Aov::Depth]);
+        commands.execute(&mut gbuffer, &[]);
+        assert!(!gbuffer.get(16, 16).depth.is_finite());
+        commands.sort_by_state();
+        assert_eq!(commands.len(), 2);
+        commands.set_camera(camera());
+        commands.draw(&mesh);
+        commands.execute(&mut gbuffer, &[]);
+        assert!(gbuffer.get(16, 16).depth.is_finite());
+    }
+
+    #[test]
+    fn test_batching() {
+        let (mesh, mut cutout, opaque) = (cube(1.0), Material::new(), Material::new());
+        cutout.alpha_cutoff = Some(0.5);
+        let mut commands = CommandBuffer::new();
+        commands.set_camera(camera());
+        for material in [&cutout, &opaque, &cutout, &opaque] {
+            commands.set_material(material);
+            commands.draw(&mesh);
+        }
+        commands.sort_by_state();
+        // Alpha tested surfaces are drawn after the opaque ones
+        assert!(matches!(commands.commands()[1], Command::SetMaterial(material) if std::ptr::eq(material, &opaque)));
+        assert!(matches!(commands.commands()[4], Command::SetMaterial(material) if std::ptr::eq(material, &cutout)));
+        let mut gbuffer = AovBuffers::new(32, 32, &[Aov::Depth]);
+        crate::profile::take_stats();
+        commands.execute(&mut gbuffer, &[]);
+        assert_eq!(crate::profile::take_stats().batches, 2);
+        // Meshes over the small draw size are drawn on their own
+        commands.small_draw = 8;
+        commands.execute(&mut gbuffer, &[]);
+        assert_eq!(crate::profile::take_stats().batches, 4);
+    }
+}
@@ -0,0 +1,203 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::Rasterizer;
+use crate::canvas::{Color, PixelBuffer};
+use crate::math::{Mat4f, Vec3f, Vec4f};
+use crate::model::Mesh;
+
+///
+/// Batch of world space debugging gizmos drawn as lines.
+///
+/// Queue up normals, boxes, grids and so on while building a frame, then `render` them
+/// all through the line rasterizer on top of the finished image.
+///
+#[derive(Clone, Default)]
+pub struct DebugDraw {
+    lines: Vec<(Vec3f, Vec3f, Color)>,
+}
+
+impl DebugDraw {
+    /// Create an empty DebugDraw batch
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Number of queued lines
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+    /// Check if no lines are queued
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+    /// Remove all queued lines
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+    /// Queue a line from `from` to `to`
+    pub fn line(&mut self, from: Vec3f, to: Vec3f, color: Color) {
+        self.lines.push((from, to, color));
+    }
+    /// Queue a normal line of `length` starting at `position`
+    pub fn normal(&mut self, position: Vec3f, normal: Vec3f, length: f32, color: Color) {
+        self.line(position, position + normal.normalize() * length, color);
+    }
+    /// Queue the face normal of every triangle of `mesh` at each of its vertices
+    pub fn mesh_normals(&mut self, mesh: &Mesh, length: f32, color: Color) {
+        for triangle in mesh.triangles() {
+            for &vertex in &[triangle.a, triangle.b, triangle.c] {
+                self.normal(vertex, triangle.normal(), length, color);
+            }
+        }
+    }
+    /// Queue the twelve edges of an axis aligned bounding box
+    pub fn aabb(&mut self, min: Vec3f, max: Vec3f, color: Color) {
+        let corner = |i: usize| {
+            Vec3f::from_parts(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        };
+        self.box_edges(corner, color);
+    }
+    /// Queue the twelve edges of the view frustum described by `inv_view_proj`.
+    ///
+    /// Assumes an OpenGL style projection with depth in -1..1.
+    pub fn frustum(&mut self, inv_view_proj: Mat4f, color: Color) {
+        let corner = |i: usize| {
+            let ndc = Vec4f::from_parts(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { -1.0 } else { 1.0 },
+                1.0,
+            );
+            (ndc * inv_view_proj).truncate()
+        };
+        self.box_edges(corner, color);
+    }
+    /// Queue a grid on the XZ plane centered on the origin, `extent` units out each way
+    pub fn grid(&mut self, extent: f32, spacing: f32, color: Color) {
+        let steps = (extent / spacing).floor() as i32;
+        for i in -steps..=steps {
+            let offset = i as f32 * spacing;
+            self.line(
+                Vec3f::from_parts(offset, 0.0, -extent),
+                Vec3f::from_parts(offset, 0.0, extent),
+                color,
+            );
+            self.line(
+                Vec3f::from_parts(-extent, 0.0, offset),
+                Vec3f::from_parts(extent, 0.0, offset),
+                color,
+            );
+        }
+    }
+    /// Queue an axis tripod at `origin`, X in red, Y in green and Z in blue
+    pub fn axes(&mut self, origin: Vec3f, length: f32) {
+        self.line(origin, origin + Vec3f::from_parts(length, 0.0, 0.0), Color::bright_red());
+        self.line(origin, origin + Vec3f::from_parts(0.0, length, 0.0), Color::bright_green());
+        self.line(origin, origin + Vec3f::from_parts(0.0, 0.0, length), Color::bright_blue());
+    }
+    /// Queue a star shaped light icon of `size` at `position`
+    pub fn light(&mut self, position: Vec3f, size: f32, color: Color) {
+        let r = size * 0.5;
+        let d = r / 3f32.sqrt();
+        for &offset in &[
+            Vec3f::from_parts(r, 0.0, 0.0),
+            Vec3f::from_parts(0.0, r, 0.0),
+            Vec3f::from_parts(0.0, 0.0, r),
+            Vec3f::from_parts(d, d, d),
+            Vec3f::from_parts(-d, d, d),
+            Vec3f::from_parts(d, -d, d),
+            Vec3f::from_parts(d, d, -d),
+        ] {
+            self.line(position - offset, position + offset, color);
+        }
+    }
+    /// Draw every queued line into `buffer` through the view projection `view_proj`.
+    ///
+    /// Lines are cut where they pass behind the camera and clipped to the buffer.
+    pub fn render<B: PixelBuffer<Pixel = Color>>(&self, buffer: &mut B, view_proj: Mat4f) {
+        for &(from, to, color) in &self.lines {
+            let a = from.extend(1.0).transform_homogeneous(view_proj);
+            let b = to.extend(1.0).transform_homogeneous(view_proj);
+            Rasterizer::draw_line_clip(buffer, a, b, color);
+        }
+    }
+    /// Queue the edges of a box given a function returning corner `i`, where bit 0, 1 and
+    /// 2 of `i` select the X, Y and Z side.
+    fn box_edges<F: Fn(usize) -> Vec3f>(&mut self, corner: F, color: Color) {
+        for i in 0..8 {
+            for &axis in &[1, 2, 4] {
+                if i & axis == 0 {
+                    self.line(corner(i), corner(i | axis), color);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DebugDraw;
+    use crate::canvas::{Canvas, Color};
+    use crate::math::{Mat4f, Vec3f};
+    use crate::model::cube;
+
+    fn view_proj() -> Mat4f {
+        Mat4f::look_at_rh(
+            Vec3f::from_parts(0.0, 0.0, 5.0),
+            Vec3f::from_parts(0.0, 0.0, 0.0),
+            Vec3f::from_parts(0.0, 1.0, 0.0),
+        ) * Mat4f::perspective_rh_gl(90.0, 1.0, 0.1, 100.0)
+    }
+
+    #[test]
+    fn test_batching() {
+        let mut debug = DebugDraw::new();
+        assert!(debug.is_empty());
+        debug.aabb(Vec3f::from_parts(-1.0, -1.0, -1.0), Vec3f::from_parts(1.0, 1.0, 1.0), Color::white());
+        assert_eq!(debug.len(), 12);
+        debug.axes(Vec3f::from_parts(0.0, 0.0, 0.0), 1.0);
+        assert_eq!(debug.len(), 15);
+        debug.grid(2.0, 1.0, Color::white());
+        assert_eq!(debug.len(), 25);
+        debug.mesh_normals(&cube(1.0), 0.5, Color::white());
+        assert_eq!(debug.len(), 25 + 36);
+        debug.frustum(view_proj().invert().unwrap(), Color::white());
+        debug.light(Vec3f::from_parts(0.0, 2.0, 0.0), 1.0, Color::white());
+        assert_eq!(debug.len(), 25 + 36 + 12 + 7);
+        debug.clear();
+        assert!(debug.is_empty());
+    }
+
+    #[test]
+    fn test_render() {
+        let mut debug = DebugDraw::new();
+        debug.axes(Vec3f::from_parts(0.0, 0.0, 0.0), 2.0);
+        // Passes behind the camera, must be cut rather than wrap around
+        debug.line(Vec3f::from_parts(0.0, -1.0, 0.0), Vec3f::from_parts(0.0, -1.0, 10.0), Color::white());
+        let mut canvas = Canvas::new(64, 64);
+        debug.render(&mut canvas, view_proj());
+        // X axis runs right from the center, Y axis runs up
+        assert_eq!(canvas.get(40, 32), Color::bright_red());
+        assert_eq!(canvas.get(32, 24), Color::bright_green());
+        assert_eq!(canvas.get(20, 32), Color::black());
+        assert_eq!(canvas.get(32, 36), Color::black());
+        assert_eq!(canvas.get(32, 63), Color::white());
+    }
+}
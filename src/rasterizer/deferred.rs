@@ -0,0 +1,224 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::Rasterizer;
+use crate::canvas::{Aov, AovBuffers, Canvas, Color};
+use crate::math::{Sh9, Vec2f, Vec3f};
+use crate::model::PointLight;
+use crate::shader::Camera;
+
+/// Pixels a light pass touches, `x0..x1` by `y0..y1`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LightBounds {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl LightBounds {
+    /// Number of Pixels covered
+    pub fn area(&self) -> usize {
+        (self.x1 - self.x0) * (self.y1 - self.y0)
+    }
+}
+
+impl Rasterizer {
+    /// Screen area of a `width` by `height` image lit by `light`.
+    ///
+    /// Lights with a range only reach the pixels covered by their bounding box, lights
+    /// without one or surrounding the camera cover the whole screen.
+    pub fn light_bounds(light: &PointLight, camera: &Camera, width: usize, height: usize) -> LightBounds {
+        let full = LightBounds { x0: 0, y0: 0, x1: width, y1: height };
+        let range = match light.range {
+            Some(range) if (light.position - camera.position).magnitude() > range * 3f32.sqrt() => range,
+            _ => return full,
+        };
+        let mut min = Vec2f::from_parts(f32::INFINITY, f32::INFINITY);
+        let mut max = Vec2f::from_parts(f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for corner in 0..8 {
+            let sign = |bit: usize| if corner & bit == 0 { -range } else { range };
+            let point = light.position + Vec3f::from_parts(sign(1), sign(2), sign(4));
+            match camera.project(point.extend(1.0), width, height) {
+                Some(p) => {
+                    min = Vec2f::from_parts(min.x.min(p.x), min.y.min(p.y));
+                    max = Vec2f::from_parts(max.x.max(p.x), max.y.max(p.y));
+                }
+                // Part of the box is behind the camera, its projection is unbounded
+                None => return full,
+            }
+        }
+        let clamp = |v: f32, limit: usize| v.max(0.0).min(limit as f32) as usize;
+        LightBounds {
+            x0: clamp(min.x.floor(), width),
+            y0: clamp(min.y.floor(), height),
+            x1: clamp(max.x.ceil(), width),
+            y1: clamp(max.y.ceil(), height),
+        }
+    }
+
+    /// Add the light `light` reflects off the G-buffer `gbuffer` into `target`.
+    ///
+    /// The G-buffer needs the albedo, normal and depth AOVs. Each light is a separate
+    /// additive pass over the pixels inside its bounds, so any number of lights can be
+    /// accumulated one after another in any order. With the light links AOV enabled, pixels
+    /// of objects the light is not linked to are left alone.
+    pub fn accumulate_light(target: &mut Canvas, gbuffer: &AovBuffers, camera: &Camera, light: &PointLight) {
+        assert!(gbuffer.is_enabled(Aov::Albedo) && gbuffer.is_enabled(Aov::Normal) && gbuffer.is_enabled(Aov::Depth));
+        assert_eq!((target.width(), target.height()), (gbuffer.width(), gbuffer.height()));
+        let (width, height) = (target.width(), target.height());
+        let bounds = Rasterizer::light_bounds(light, camera, width, height);
+        for y in bounds.y0..bounds.y1 {
+            for x in bounds.x0..bounds.x1 {
+                let sample = gbuffer.get(x, y);
+                if !sample.depth.is_finite() || !light.links_to(sample.light_links) {
+                    continue;
+                }
+                let position = camera.position + camera.ray_direction(x, y, width, height) * sample.depth;
+                let light = sample.albedo * light.irradiance(position, sample.normal);
+                target.set(x, y, target.get(x, y) + light);
+            }
+        }
+    }
+
+    /// Add the ambient light of an environment projected to `environment` reflecting off
+    /// the G-buffer `gbuffer` into `target`.
+    ///
+    /// The G-buffer needs the albedo, normal and depth AOVs. A uniform environment of one
+    /// color lights a white surface to that color whichever way it faces.
+    pub fn accumulate_ambient(target: &mut Canvas, gbuffer: &AovBuffers, environment: &Sh9) {
+        assert!(gbuffer.is_enabled(Aov::Albedo) && gbuffer.is_enabled(Aov::Normal) && gbuffer.is_enabled(Aov::Depth));
+        assert_eq!((target.width(), target.height()), (gbuffer.width(), gbuffer.height()));
+        for y in 0..target.height() {
+            for x in 0..target.width() {
+                let sample = gbuffer.get(x, y);
+                if !sample.depth.is_finite() {
+                    continue;
+                }
+                let irradiance = environment.irradiance(sample.normal) * std::f32::consts::FRAC_1_PI;
+                let light = sample.albedo * Color::new(irradiance.x, irradiance.y, irradiance.z);
+                target.set(x, y, target.get(x, y) + light);
+            }
+        }
+    }
+
+    /// Accumulate every light in `lights` with `accumulate_light`
+    pub fn accumulate_lights(target: &mut Canvas, gbuffer: &AovBuffers, camera: &Camera, lights: &[PointLight]) {
+        crate::profile_scope!("deferred_lights");
+        for light in lights {
+            Rasterizer::accumulate_light(target, gbuffer, camera, light);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::{Aov, AovBuffers, AovSample, Canvas, Color};
+    use crate::math::{Sh9, Vec3f};
+    use crate::model::PointLight;
+    use crate::shader::Camera;
+    use crate::Rasterizer;
+
+    /// Camera looking down at a white floor 4 units below filling the view
+    fn floor() -> (Camera, AovBuffers) {
+        let mut camera = Camera::new(
+            Vec3f::from_parts(0.0, 4.0, 0.0),
+            Vec3f::from_parts(0.0, -1.0, 0.0),
+            Vec3f::from_parts(0.0, 0.0, -1.0),
+        );
+        camera.set_viewport(32, 32);
+        let mut gbuffer = AovBuffers::new(32, 32, &[Aov::Albedo, Aov::Normal, Aov::Depth]);
+        for y in 0..32 {
+            for x in 0..32 {
+                let direction = camera.ray_direction(x, y, 32, 32);
+                let sample = AovSample {
+                    albedo: Color::white(),
+                    normal: Vec3f::from_parts(0.0, 1.0, 0.0),
+                    depth: 4.0 / -direction.y,
+                    ..AovSample::default()
+                };
+                gbuffer.set(x, y, &sample);
+            }
+        }
+        (camera, gbuffer)
+    }
+
+    #[test]
+    fn test_light_bounds() {
+        let (camera, _) = floor();
+        let mut light = PointLight::new(Vec3f::from_parts(0.0, 0.0, 0.0), 1.0);
+        assert_eq!(Rasterizer::light_bounds(&light, &camera, 32, 32).area(), 32 * 32);
+        light.range = Some(0.5);
+        let bounds = Rasterizer::light_bounds(&light, &camera, 32, 32);
+        assert!(bounds.area() < 32 * 32 / 4);
+        assert!(bounds.x0 < 16 && bounds.x1 > 16 && bounds.y0 < 16 && bounds.y1 > 16);
+        light.position = Vec3f::from_parts(0.0, 4.0, 0.0);
+        assert_eq!(Rasterizer::light_bounds(&light, &camera, 32, 32).area(), 32 * 32);
+    }
+
+    #[test]
+    fn test_accumulate_lights() {
+        let (camera, gbuffer) = floor();
+        let mut left = PointLight::new(Vec3f::from_parts(-1.0, 1.0, 0.0), 1.0);
+        left.range = Some(1.5);
+        let mut right = left;
+        right.position = Vec3f::from_parts(1.0, 1.0, 0.0);
+        let mut target = Canvas::new(32, 32);
+        Rasterizer::accumulate_lights(&mut target, &gbuffer, &camera, &[left, right]);
+        // Directly under each light, and nothing where neither reaches
+        let (a, b) = (target.get(12, 16), target.get(19, 16));
+        assert!(a.r.value() > 0.0 && (a.r.value() - b.r.value()).abs() < 0.05);
+        assert_eq!(target.get(0, 0), Color::black());
+        // Passes add up, in either order
+        let mut reversed = Canvas::new(32, 32);
+        Rasterizer::accumulate_lights(&mut reversed, &gbuffer, &camera, &[right, left]);
+        assert!(target.pixels().all(|(x, y, c)| reversed.get(x, y) == c));
+        let mut single = Canvas::new(32, 32);
+        Rasterizer::accumulate_light(&mut single, &gbuffer, &camera, &left);
+        assert!(target.get(12, 16).r.value() >= single.get(12, 16).r.value());
+    }
+
+    #[test]
+    fn test_light_links() {
+        let (camera, gbuffer) = floor();
+        let mut light = PointLight::new(Vec3f::from_parts(0.0, 1.0, 0.0), 1.0);
+        light.links = 2;
+        let mut target = Canvas::new(32, 32);
+        // Without the AOV every pixel is lit by every light
+        Rasterizer::accumulate_light(&mut target, &gbuffer, &camera, &light);
+        assert!(target.get(16, 16).r.value() > 0.0);
+        let mut linked = AovBuffers::new(32, 32, &[Aov::Albedo, Aov::Normal, Aov::Depth, Aov::LightLinks]);
+        for (x, y) in (0..32).flat_map(|y| (0..32).map(move |x| (x, y))) {
+            let mut sample = gbuffer.get(x, y);
+            sample.light_links = if x < 16 { 1 } else { 2 };
+            linked.set(x, y, &sample);
+        }
+        let mut target = Canvas::new(32, 32);
+        Rasterizer::accumulate_light(&mut target, &linked, &camera, &light);
+        assert_eq!(target.get(15, 16), Color::black());
+        assert!(target.get(16, 16).r.value() > 0.0);
+    }
+
+    #[test]
+    fn test_accumulate_ambient() {
+        let (_, gbuffer) = floor();
+        let grey = Vec3f::from_parts(0.5, 0.5, 0.5);
+        let environment = Sh9::project_equirect(64, 32, |_, _| grey);
+        let mut target = Canvas::new(32, 32);
+        Rasterizer::accumulate_ambient(&mut target, &gbuffer, &environment);
+        assert!((target.get(5, 20).r.value() - 0.5).abs() < 0.01);
+    }
+}
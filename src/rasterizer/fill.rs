@@ -0,0 +1,377 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::arena::with_scratch;
+use super::{Paint, Rasterizer};
+use crate::canvas::{Color, DepthBuffer, PixelBuffer};
+use crate::math::{Vec2f, Vec3f};
+
+impl Rasterizer {
+    /// Fill the `width` by `height` rectangle with top left corner (x, y) with `paint`,
+    /// clipped to the buffer.
+    pub fn paint_rect<B: PixelBuffer<Pixel = Color>>(buffer: &mut B, x: isize, y: isize, width: usize, height: usize, paint: &Paint) {
+        let rect = buffer.clip_rect();
+        let (x0, x1) = span(x as f32, (x + width as isize) as f32, rect.x0, rect.x1);
+        let (y0, y1) = span(y as f32, (y + height as isize) as f32, rect.y0, rect.y1);
+        for py in y0..y1 {
+            paint_span(buffer, py, x0, x1, paint);
+        }
+    }
+
+    /// Fill the circle around `center` with every pixel whose center lies within `radius`
+    pub fn fill_circle<B: PixelBuffer>(buffer: &mut B, center: Vec2f, radius: f32, value: B::Pixel) {
+        scan_circle(buffer, center, radius, |buffer, y, x0, x1| buffer.fill_span(y, x0, x1, value));
+    }
+
+    /// Fill the circle around `center` with `paint`
+    pub fn paint_circle<B: PixelBuffer<Pixel = Color>>(buffer: &mut B, center: Vec2f, radius: f32, paint: &Paint) {
+        scan_circle(buffer, center, radius, |buffer, y, x0, x1| paint_span(buffer, y, x0, x1, paint));
+    }
+
+    /// Fill the polygon through `points` using the even-odd rule, so self intersecting
+    /// polygons get holes where they overlap
+    pub fn fill_polygon<B: PixelBuffer>(buffer: &mut B, points: &[Vec2f], value: B::Pixel) {
+        scan_polygon(buffer, points, |buffer, y, x0, x1| buffer.fill_span(y, x0, x1, value));
+    }
+
+    /// Fill the polygon through `points` with `paint`
+    pub fn paint_polygon<B: PixelBuffer<Pixel = Color>>(buffer: &mut B, points: &[Vec2f], paint: &Paint) {
+        scan_polygon(buffer, points, |buffer, y, x0, x1| paint_span(buffer, y, x0, x1, paint));
+    }
+
+    /// Fill the triangle `a`, `b`, `c` in either winding.
+    ///
+    /// Pixel centers exactly on an edge follow the top-left rule, so triangles sharing an
+    /// edge, such as the two halves of a `model::cube` face, cover every Pixel once.
+    ///
+    /// ```
+    /// use softrender::canvas::{Canvas, Color};
+    /// use softrender::math::{Vec3f, Vec4f};
+    /// use softrender::model::cube;
+    /// use softrender::shader::Camera;
+    /// use softrender::Rasterizer;
+    ///
+    /// let mut camera = Camera::new(
+    ///     Vec3f::from_parts(0.0, 0.0, 5.0),
+    ///     Vec3f::from_parts(0.0, 0.0, -1.0),
+    ///     Vec3f::from_parts(0.0, 1.0, 0.0),
+    /// );
+    /// camera.set_viewport(64, 64);
+    /// let mut canvas = Canvas::new(64, 64);
+    /// for triangle in cube(1.0).triangles() {
+    ///     let project = |p: Vec3f| camera.project(Vec4f::from_parts(p.x, p.y, p.z, 1.0), 64, 64).unwrap();
+    ///     let (a, b, c) = (project(triangle.a), project(triangle.b), project(triangle.c));
+    ///     Rasterizer::fill_triangle(&mut canvas, a, b, c, Color::white());
+    /// }
+    /// assert_eq!(canvas.get(32, 32), Color::white());
+    /// assert_eq!(canvas.get(0, 0), Color::black());
+    /// ```
+    pub fn fill_triangle<B: PixelBuffer>(buffer: &mut B, a: Vec2f, b: Vec2f, c: Vec2f, value: B::Pixel) {
+        scan_triangle(buffer, [a, b, c], |buffer, x, y, _| buffer.set(x, y, value));
+    }
+
+    /// Fill the triangle `a`, `b`, `c` with the value `shader` returns for each Pixel
+    /// given its position and barycentric weights for `a`, `b` and `c`.
+    ///
+    /// Weights are linear in screen space, so attributes of projected triangles should be
+    /// divided by `w` beforehand for perspective correct results.
+    pub fn shade_triangle<B: PixelBuffer, F: FnMut(usize, usize, [f32; 3]) -> B::Pixel>(
+        buffer: &mut B,
+        a: Vec2f,
+        b: Vec2f,
+        c: Vec2f,
+        mut shader: F,
+    ) {
+        scan_triangle(buffer, [a, b, c], |buffer, x, y, weights| {
+            let value = shader(x, y, weights);
+            buffer.set(x, y, value);
+        });
+    }
+
+    /// Fill the Pixels of the triangle `a`, `b`, `c` that pass the depth test of `depth`,
+    /// with x and y in pixels and z the depth.
+    ///
+    /// Depth is interpolated linearly across the screen, as normalized device z is, so
+    /// pass that rather than view distance for projected triangles.
+    pub fn fill_triangle_depth<B: PixelBuffer>(
+        buffer: &mut B,
+        depth: &mut DepthBuffer,
+        a: Vec3f,
+        b: Vec3f,
+        c: Vec3f,
+        value: B::Pixel,
+    ) {
+        let screen = [a, b, c].map(|vertex| Vec2f::from_parts(vertex.x, vertex.y));
+        scan_triangle(buffer, screen, |buffer, x, y, [wa, wb, wc]| {
+            if x < depth.width() && y < depth.height() && depth.test(x, y, a.z * wa + b.z * wb + c.z * wc) {
+                buffer.set(x, y, value);
+            }
+        });
+    }
+
+    /// Draw the outline of the triangle `a`, `b`, `c`
+    pub fn draw_triangle<B: PixelBuffer>(buffer: &mut B, a: Vec2f, b: Vec2f, c: Vec2f, value: B::Pixel) {
+        Rasterizer::draw_line_f(buffer, a, b, value);
+        Rasterizer::draw_line_f(buffer, b, c, value);
+        Rasterizer::draw_line_f(buffer, c, a, value);
+    }
+}
+
+/// Center of Pixel (x, y)
+fn center(x: usize, y: usize) -> Vec2f {
+    Vec2f::from_parts(x as f32 + 0.5, y as f32 + 0.5)
+}
+
+/// Fill the Pixels from x0 up to x1 on row y with `paint`, taking the fast path for
+/// solid colors
+fn paint_span<B: PixelBuffer<Pixel = Color>>(buffer: &mut B, y: usize, x0: usize, x1: usize, paint: &Paint) {
+    match paint {
+        Paint::Solid(color) => buffer.fill_span(y, x0, x1, *color),
+        _ => {
+            for x in x0..x1 {
+                buffer.set(x, y, paint.color_at(center(x, y)));
+            }
+        }
+    }
+}
+
+/// Clamp the half open span of pixel centers between `from` and `to` to `min..max`
+fn span(from: f32, to: f32, min: usize, max: usize) -> (usize, usize) {
+    let first = (from - 0.5).ceil().max(min as f32).min(max as f32) as usize;
+    let last = (to - 0.5).ceil().max(min as f32).min(max as f32) as usize;
+    (first, last.max(first))
+}
+
+fn scan_circle<B: PixelBuffer, F: FnMut(&mut B, usize, usize, usize)>(buffer: &mut B, center: Vec2f, radius: f32, mut plot: F) {
+    if radius <= 0.0 {
+        return;
+    }
+    let rect = buffer.clip_rect();
+    let (y0, y1) = span(center.y - radius, center.y + radius, rect.y0, rect.y1);
+    for y in y0..y1 {
+        let dy = y as f32 + 0.5 - center.y;
+        let half = (radius * radius - dy * dy).max(0.0).sqrt();
+        let (x0, x1) = span(center.x - half, center.x + half, rect.x0, rect.x1);
+        plot(buffer, y, x0, x1);
+    }
+}
+
+fn scan_triangle<B, F>(buffer: &mut B, [a, b, c]: [Vec2f; 3], mut plot: F)
+where
+    B: PixelBuffer,
+    F: FnMut(&mut B, usize, usize, [f32; 3]),
+{
+    let edge = |p: Vec2f, q: Vec2f, r: Vec2f| (q.x - p.x) * (r.y - p.y) - (q.y - p.y) * (r.x - p.x);
+    let area = edge(a, b, c);
+    if area == 0.0 || !area.is_finite() {
+        return;
+    }
+    // Wind clockwise on screen, y down, so every edge function is positive inside
+    let flipped = area < 0.0;
+    let (b, c, area) = if flipped { (c, b, -area) } else { (b, c, area) };
+    // Top edges run right and left edges run up, points exactly on them are inside
+    let top_left = |p: Vec2f, q: Vec2f| (p.y == q.y && q.x > p.x) || q.y < p.y;
+    let inside = |weight: f32, p: Vec2f, q: Vec2f| weight > 0.0 || (weight == 0.0 && top_left(p, q));
+    let rect = buffer.clip_rect();
+    let (x0, x1) = span(a.x.min(b.x).min(c.x), a.x.max(b.x).max(c.x), rect.x0, rect.x1);
+    let (y0, y1) = span(a.y.min(b.y).min(c.y), a.y.max(b.y).max(c.y), rect.y0, rect.y1);
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let point = center(x, y);
+            let (wa, wb, wc) = (edge(b, c, point), edge(c, a, point), edge(a, b, point));
+            if inside(wa, b, c) && inside(wb, c, a) && inside(wc, a, b) {
+                let (wb, wc) = if flipped { (wc, wb) } else { (wb, wc) };
+                plot(buffer, x, y, [wa / area, wb / area, wc / area]);
+            }
+        }
+    }
+}
+
+fn scan_polygon<B: PixelBuffer, F: FnMut(&mut B, usize, usize, usize)>(buffer: &mut B, points: &[Vec2f], mut plot: F) {
+    if points.len() < 3 {
+        return;
+    }
+    let top = points.iter().map(|p| p.y).fold(f32::INFINITY, f32::min);
+    let bottom = points.iter().map(|p| p.y).fold(f32::NEG_INFINITY, f32::max);
+    let rect = buffer.clip_rect();
+    let (y0, y1) = span(top, bottom, rect.y0, rect.y1);
+    with_scratch(|scratch| {
+        for y in y0..y1 {
+            let scan = y as f32 + 0.5;
+            let mark = scratch.mark();
+            for (index, &a) in points.iter().enumerate() {
+                let b = points[(index + 1) % points.len()];
+                // Half open in y so vertices shared by two edges are only counted once
+                if (a.y <= scan) != (b.y <= scan) {
+                    scratch.push(a.x + (scan - a.y) / (b.y - a.y) * (b.x - a.x));
+                }
+            }
+            let crossings = scratch.since(mark);
+            crossings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            for pair in crossings.chunks(2) {
+                if let [from, to] = *pair {
+                    let (x0, x1) = span(from, to, rect.x0, rect.x1);
+                    plot(buffer, y, x0, x1);
+                }
+            }
+            scratch.release(mark);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Gradient, Paint, Rasterizer};
+    use crate::canvas::{Canvas, Color, DepthBuffer, DepthFunc, IdBuffer};
+    use crate::math::{Vec2f, Vec3f};
+
+    fn lit(canvas: &Canvas) -> usize {
+        canvas.pixels().filter(|p| p.2 != Color::black()).count()
+    }
+
+    #[test]
+    fn test_fill_polygon() {
+        let mut ids = IdBuffer::new(8, 8);
+        let square = [
+            Vec2f::from_parts(2.0, 2.0),
+            Vec2f::from_parts(6.0, 2.0),
+            Vec2f::from_parts(6.0, 6.0),
+            Vec2f::from_parts(2.0, 6.0),
+        ];
+        Rasterizer::fill_polygon(&mut ids, &square, 3);
+        for y in 0..8 {
+            for x in 0..8 {
+                let inside = (2..6).contains(&x) && (2..6).contains(&y);
+                assert_eq!(ids.pick(x, y) == Some(3), inside);
+            }
+        }
+        // Adjacent triangles sharing an edge cover each pixel exactly once
+        let mut canvas = Canvas::new(8, 8);
+        let (a, b, c, d) = (square[0], square[1], square[2], square[3]);
+        Rasterizer::fill_polygon(&mut canvas, &[a, b, c], Color::white());
+        Rasterizer::fill_polygon(&mut canvas, &[a, c, d], Color::white());
+        assert_eq!(lit(&canvas), 16);
+    }
+
+    #[test]
+    fn test_fill_triangle() {
+        let p = |x: f32, y: f32| Vec2f::from_parts(x, y);
+        // Either winding fills the same Pixels
+        let mut ids = IdBuffer::new(8, 8);
+        Rasterizer::fill_triangle(&mut ids, p(0.0, 0.0), p(8.0, 0.0), p(0.0, 8.0), 1);
+        Rasterizer::fill_triangle(&mut ids, p(0.0, 0.0), p(0.0, 8.0), p(8.0, 0.0), 2);
+        assert_eq!(ids.pick(0, 0), Some(2));
+        assert_eq!(ids.pick(3, 3), Some(2));
+        assert_eq!(ids.pick(4, 4), None);
+        // Pixel centers on shared edges belong to exactly one of the triangles
+        let mut canvas = Canvas::new(8, 8);
+        let (a, b, c, d) = (p(0.5, 0.5), p(6.5, 0.5), p(6.5, 6.5), p(0.5, 6.5));
+        let mut covered = [[0; 8]; 8];
+        for triangle in &[[a, b, c], [a, c, d], [a, b, d], [b, c, d]] {
+            let mut ids = IdBuffer::new(8, 8);
+            Rasterizer::fill_triangle(&mut ids, triangle[0], triangle[1], triangle[2], 1);
+            for (y, row) in covered.iter_mut().enumerate() {
+                for (x, count) in row.iter_mut().enumerate() {
+                    *count += ids.pick(x, y).is_some() as usize;
+                }
+            }
+        }
+        // Each split of the square covers its 6x6 Pixel centers once, top and left edges only
+        for (y, row) in covered.iter().enumerate() {
+            for (x, &count) in row.iter().enumerate() {
+                assert_eq!(count, if x < 6 && y < 6 { 2 } else { 0 }, "({}, {})", x, y);
+            }
+        }
+        Rasterizer::draw_triangle(&mut canvas, a, b, c, Color::white());
+        assert_eq!(canvas.get(0, 0), Color::white());
+        assert_eq!(canvas.get(6, 6), Color::white());
+        assert_eq!(canvas.get(0, 6), Color::black());
+    }
+
+    #[test]
+    fn test_fill_triangle_depth() {
+        let v = |x: f32, y: f32, z: f32| Vec3f::new(x, y, z);
+        // A near triangle crossing a far one at x = 4 shows the near half either way round
+        let near = [v(0.0, 0.0, 0.0), v(8.0, 0.0, 1.0), v(0.0, 8.0, 0.0)];
+        let far = [v(0.0, 0.0, 0.5), v(8.0, 0.0, 0.5), v(8.0, 8.0, 0.5)];
+        let render = |first: [Vec3f; 3], second: [Vec3f; 3], func: DepthFunc| {
+            let mut ids = IdBuffer::new(8, 8);
+            let mut depth = DepthBuffer::new(8, 8);
+            depth.func = func;
+            Rasterizer::fill_triangle_depth(&mut ids, &mut depth, first[0], first[1], first[2], 1);
+            Rasterizer::fill_triangle_depth(&mut ids, &mut depth, second[0], second[1], second[2], 2);
+            ids
+        };
+        let (near_first, far_first) = (render(near, far, DepthFunc::Less), render(far, near, DepthFunc::Less));
+        assert_eq!((near_first.pick(2, 0), near_first.pick(6, 0)), (Some(1), Some(2)));
+        assert_eq!((far_first.pick(2, 0), far_first.pick(6, 0)), (Some(2), Some(1)));
+        // Always draws in submission order
+        assert_eq!(render(near, far, DepthFunc::Always).pick(2, 0), Some(2));
+        assert_eq!(render(far, near, DepthFunc::Always).pick(2, 0), Some(2));
+    }
+
+    #[test]
+    fn test_shade_triangle() {
+        let p = |x: f32, y: f32| Vec2f::from_parts(x, y);
+        let mut canvas = Canvas::new(8, 8);
+        let (a, b, c) = (p(0.0, 0.0), p(0.0, 8.0), p(8.0, 0.0));
+        Rasterizer::shade_triangle(&mut canvas, a, b, c, |x, y, [wa, wb, wc]| {
+            // Weights interpolate the vertices back to the Pixel center
+            let point = a * wa + b * wb + c * wc;
+            assert!((point.x - (x as f32 + 0.5)).abs() < 1e-4);
+            assert!((point.y - (y as f32 + 0.5)).abs() < 1e-4);
+            Color::new(wa, wb, wc)
+        });
+        assert_eq!(canvas.get(0, 6), Color::new(0.125, 0.8125, 0.0625));
+        // The long edge is neither top nor left, so centers on it are left out
+        assert_eq!(canvas.get(0, 7), Color::black());
+        assert_eq!(lit(&canvas), 28);
+    }
+
+    #[test]
+    fn test_fill_circle() {
+        let mut canvas = Canvas::new(16, 16);
+        Rasterizer::fill_circle(&mut canvas, Vec2f::from_parts(8.0, 8.0), 4.0, Color::white());
+        assert_eq!(canvas.get(8, 8), Color::white());
+        assert_eq!(canvas.get(11, 8), Color::white());
+        assert_eq!(canvas.get(12, 8), Color::black());
+        assert_eq!(canvas.get(11, 11), Color::black());
+        // Partly off screen
+        Rasterizer::fill_circle(&mut canvas, Vec2f::from_parts(0.0, 0.0), 2.0, Color::white());
+        assert_eq!(canvas.get(0, 0), Color::white());
+    }
+
+    #[test]
+    fn test_paint_fills() {
+        let paint = Paint::Linear {
+            start: Vec2f::from_parts(0.0, 0.0),
+            end: Vec2f::from_parts(8.0, 0.0),
+            gradient: Gradient::new(Color::black(), Color::white()),
+        };
+        let mut canvas = Canvas::new(8, 8);
+        Rasterizer::paint_rect(&mut canvas, 0, 0, 8, 8, &paint);
+        assert_eq!(canvas.get(3, 5), Color::new(0.4375, 0.4375, 0.4375));
+        assert_eq!(canvas.get(7, 0), Color::new(0.9375, 0.9375, 0.9375));
+        let mut canvas = Canvas::new(8, 8);
+        let triangle = [Vec2f::from_parts(0.0, 0.0), Vec2f::from_parts(8.0, 0.0), Vec2f::from_parts(0.0, 8.0)];
+        Rasterizer::paint_polygon(&mut canvas, &triangle, &paint);
+        assert_eq!(canvas.get(6, 0), Color::new(0.8125, 0.8125, 0.8125));
+        assert_eq!(canvas.get(7, 7), Color::black());
+        let mut canvas = Canvas::new(8, 8);
+        Rasterizer::paint_circle(&mut canvas, Vec2f::from_parts(4.0, 4.0), 3.0, &Color::white().into());
+        assert_eq!(canvas.get(4, 4), Color::white());
+    }
+}
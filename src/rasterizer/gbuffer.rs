@@ -0,0 +1,232 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::clipping::scan_polygon;
+use super::velocity::cull_polygon;
+use super::Rasterizer;
+use crate::canvas::{AovBuffers, Texture};
+use crate::math::Vec3f;
+use crate::model::{Material, Mesh};
+use crate::profile::{self, PipelineStats};
+use crate::shader::Camera;
+
+impl Rasterizer {
+    /// Rasterize `mesh` with `material` into the G-buffer `gbuffer` for deferred shading.
+    ///
+    /// Writes the albedo textured with each texture's mapping, face normal bent by the
+    /// material's bump and normal maps, distance from the camera and texture
+    /// coordinates of the closest surface, depth testing against the depth AOV. Fragments
+    /// cut out by the material's `alpha_cutoff` are discarded, leaving whatever was behind
+    /// them, as are those behind the camera's clip plane or user clip planes. Triangles are
+    /// clipped to the view, and those wholly clipped, off screen or between pixel centers
+    /// are culled.
+    pub fn draw_gbuffer(gbuffer: &mut AovBuffers, mesh: &Mesh, material: &Material, textures: &[Texture], camera: &Camera) {
+        Rasterizer::draw_gbuffer_batch(gbuffer, &[mesh], material, textures, camera);
+    }
+
+    /// Rasterize every mesh of `meshes` with `material` into the G-buffer `gbuffer` like
+    /// `draw_gbuffer`, setting up the camera transform once for all of them
+    pub fn draw_gbuffer_batch(
+        gbuffer: &mut AovBuffers,
+        meshes: &[&Mesh],
+        material: &Material,
+        textures: &[Texture],
+        camera: &Camera,
+    ) {
+        crate::profile_scope!("gbuffer");
+        let (width, height) = (gbuffer.width(), gbuffer.height());
+        let view_projection = camera.view_projection();
+        let project = |vertex: Vec3f| {
+            let clip = vertex.extend(1.0).transform_homogeneous(view_projection);
+            (clip, vertex - camera.position)
+        };
+        let mut stats = PipelineStats::default();
+        let triangles = meshes.iter().flat_map(|&mesh| mesh.triangles().iter().enumerate().map(move |t| (mesh, t)));
+        for (mesh, (index, triangle)) in triangles {
+            if camera.clip_planes.clips_all(&[triangle.a, triangle.b, triangle.c]) {
+                stats.triangles_submitted += 1;
+                stats.frustum_culled += 1;
+                continue;
+            }
+            let [(ca, oa), (cb, ob), (cc, oc)] = [project(triangle.a), project(triangle.b), project(triangle.c)];
+            let polygon = Rasterizer::clip_triangle([ca, cb, cc]);
+            if cull_polygon(width, height, &polygon, &mut stats) {
+                continue;
+            }
+            let normal = triangle.normal();
+            let (dpdu, dpdv) = mesh.uv_derivatives(index);
+            scan_polygon(width, height, &polygon, |x, y, _, [pa, u, v]| {
+                let depth = (oa * pa + ob * u + oc * v).magnitude();
+                let mut sample = gbuffer.get(x, y);
+                if depth >= sample.depth {
+                    stats.depth_rejected += 1;
+                    return;
+                }
+                let position = camera.position + oa * pa + ob * u + oc * v;
+                if camera.is_clipped(position) {
+                    return;
+                }
+                let uv = mesh.uv(index, u, v);
+                if material.is_cut_out(textures, uv) {
+                    return;
+                }
+                sample.albedo = material.surface_at_point(textures, uv, position, normal);
+                sample.normal = material.shading_normal(textures, uv, normal, dpdu, dpdv);
+                sample.depth = depth;
+                sample.uv = uv;
+                gbuffer.set(x, y, &sample);
+                stats.pixels_shaded += 1;
+            });
+        }
+        profile::record_stats(&stats);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::{Aov, AovBuffers, Canvas, Color, Filter, Texture};
+    use crate::math::{ClipPlanes, Mat4f, Ray, Vec2f, Vec3f};
+    use crate::model::{Material, Mesh, RayKind, Scene, TextureSlot, Triangle, TriangleIntersector};
+    use crate::raytracer::{Hit, Tlas};
+    use crate::shader::Camera;
+    use crate::Rasterizer;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_cutout() {
+        let mut camera = Camera::new(
+            Vec3f::from_parts(0.0, 0.0, 5.0),
+            Vec3f::from_parts(0.0, 0.0, -1.0),
+            Vec3f::from_parts(0.0, 1.0, 0.0),
+        );
+        camera.set_viewport(32, 32);
+        // Quad facing the camera, textured with an opaque left and a cut out right half
+        let p = |x: f32, y: f32| Vec3f::from_parts(x, y, 0.0);
+        let t = |u: f32, v: f32| Vec2f::from_parts(u, v);
+        let quad = Mesh::with_uvs(
+            vec![
+                Triangle::new(p(-2.0, -2.0), p(2.0, -2.0), p(-2.0, 2.0)),
+                Triangle::new(p(2.0, 2.0), p(-2.0, 2.0), p(2.0, -2.0)),
+            ],
+            vec![[t(0.0, 1.0), t(1.0, 1.0), t(0.0, 0.0)], [t(1.0, 0.0), t(0.0, 0.0), t(1.0, 1.0)]],
+        );
+        let mut mask = Canvas::new(2, 1);
+        mask.set(0, 0, Color::white());
+        let textures = [Texture::new(mask)];
+        let mut slot = TextureSlot::new(0);
+        slot.sampler.filter = Filter::Nearest;
+        let mut material = Material::new();
+        material.surface = Color::bright_green();
+        material.alpha_texture = Some(slot);
+        material.alpha_cutoff = Some(0.5);
+        let mut gbuffer = AovBuffers::new(32, 32, &[Aov::Albedo, Aov::Normal, Aov::Depth, Aov::Uv]);
+        Rasterizer::draw_gbuffer(&mut gbuffer, &quad, &material, &textures, &camera);
+        let left = gbuffer.get(12, 16);
+        assert_eq!(left.albedo, Color::bright_green());
+        assert_eq!(left.normal, Vec3f::from_parts(0.0, 0.0, 1.0));
+        assert!(left.uv.x < 0.5);
+        assert!(!gbuffer.get(20, 16).depth.is_finite());
+        // Without the cutoff the whole quad is drawn
+        material.alpha_cutoff = None;
+        Rasterizer::draw_gbuffer(&mut gbuffer, &quad, &material, &textures, &camera);
+        assert_eq!(gbuffer.get(20, 16).albedo, Color::bright_green());
+    }
+
+    #[test]
+    fn test_normal_map() {
+        let mut camera = Camera::new(Vec3f::from_parts(0.0, 0.0, 5.0), -Vec3f::UNIT_Z, Vec3f::UNIT_Y);
+        camera.set_viewport(32, 32);
+        let p = |x: f32, y: f32| Vec3f::from_parts(x, y, 0.0);
+        let t = |u: f32, v: f32| Vec2f::from_parts(u, v);
+        let quad = Mesh::with_uvs(
+            vec![
+                Triangle::new(p(-2.0, -2.0), p(2.0, -2.0), p(-2.0, 2.0)),
+                Triangle::new(p(2.0, 2.0), p(-2.0, 2.0), p(2.0, -2.0)),
+            ],
+            vec![[t(0.0, 1.0), t(1.0, 1.0), t(0.0, 0.0)], [t(1.0, 0.0), t(0.0, 0.0), t(1.0, 1.0)]],
+        );
+        // Leaning along u, which runs along X
+        let mut image = Canvas::new(1, 1);
+        image.fill(Color::new(1.0, 0.5, 1.0));
+        let mut scene = Scene::new();
+        scene.add_texture(Texture::new(image));
+        let mut material = Material::new();
+        material.normal_texture = Some(TextureSlot::new(0));
+        let mut gbuffer = AovBuffers::new(32, 32, &[Aov::Normal, Aov::Depth]);
+        Rasterizer::draw_gbuffer(&mut gbuffer, &quad, &material, &scene.textures, &camera);
+        let normal = gbuffer.get(12, 16).normal;
+        assert_approx_eq!(normal.x, 0.5f32.sqrt());
+        assert_approx_eq!(normal.z, 0.5f32.sqrt());
+        // The path tracer shades the same surface with the same normal
+        let mesh = scene.add_mesh(quad);
+        let material = scene.add_material(material);
+        scene.add_instance(mesh, material, Mat4f::identity());
+        let tlas = Tlas::new(&scene);
+        let ray = Ray::new(Vec3f::from_parts(-0.5, 0.0, 5.0), -Vec3f::UNIT_Z);
+        let hit = Hit::find(&scene, &tlas, &ray, RayKind::Camera, TriangleIntersector::default()).unwrap();
+        assert_eq!(hit.normal, Vec3f::UNIT_Z);
+        assert_approx_eq!(hit.shading_normal.dot(normal), 1.0);
+    }
+
+    #[test]
+    fn test_clip_planes() {
+        let mut camera = Camera::new(Vec3f::from_parts(0.0, 0.0, 5.0), -Vec3f::UNIT_Z, Vec3f::UNIT_Y);
+        camera.set_viewport(32, 32);
+        let p = |x: f32, y: f32| Vec3f::from_parts(x, y, 0.0);
+        let quad = Mesh::new(vec![
+            Triangle::new(p(-2.0, -2.0), p(2.0, -2.0), p(-2.0, 2.0)),
+            Triangle::new(p(2.0, 2.0), p(-2.0, 2.0), p(2.0, -2.0)),
+        ]);
+        camera.clip_planes.push(ClipPlanes::plane(Vec3f::ZERO, Vec3f::UNIT_X));
+        let mut gbuffer = AovBuffers::new(32, 32, &[Aov::Albedo, Aov::Normal, Aov::Depth]);
+        Rasterizer::draw_gbuffer(&mut gbuffer, &quad, &Material::new(), &[], &camera);
+        assert!(!gbuffer.get(12, 16).depth.is_finite());
+        assert!(gbuffer.get(20, 16).depth.is_finite());
+        // Both triangles lie wholly below this one
+        camera.clip_planes.push(ClipPlanes::plane(p(0.0, 3.0), Vec3f::UNIT_Y));
+        gbuffer.clear();
+        crate::profile::take_stats();
+        Rasterizer::draw_gbuffer(&mut gbuffer, &quad, &Material::new(), &[], &camera);
+        assert_eq!(crate::profile::take_stats().frustum_culled, 2);
+        assert!(!gbuffer.get(20, 16).depth.is_finite());
+    }
+
+    #[test]
+    fn test_near_clipping() {
+        // Floor running from far ahead to behind the camera
+        let camera = Camera::new(Vec3f::from_parts(0.0, 1.0, 0.0), -Vec3f::UNIT_Z, Vec3f::UNIT_Y);
+        let p = |x: f32, z: f32| Vec3f::from_parts(x, 0.0, z);
+        let floor = Mesh::new(vec![
+            Triangle::new(p(-50.0, -50.0), p(-50.0, 50.0), p(50.0, -50.0)),
+            Triangle::new(p(50.0, 50.0), p(50.0, -50.0), p(-50.0, 50.0)),
+        ]);
+        let mut gbuffer = AovBuffers::new(32, 24, &[Aov::Normal, Aov::Depth]);
+        crate::profile::take_stats();
+        Rasterizer::draw_gbuffer(&mut gbuffer, &floor, &Material::new(), &[], &camera);
+        // The diagonal of the floor passes through the camera, with all of the view on one side
+        let stats = crate::profile::take_stats();
+        assert_eq!((stats.triangles_drawn, stats.frustum_culled), (1, 1));
+        // The floor fills the bottom half up to the horizon, the nearest row seen where the
+        // bottom edge of the view meets it
+        for x in 0..32 {
+            assert!(!gbuffer.get(x, 11).depth.is_finite());
+            assert_eq!(gbuffer.get(x, 12).normal, Vec3f::UNIT_Y);
+            assert_eq!(gbuffer.get(x, 23).normal, Vec3f::UNIT_Y);
+        }
+        let nearest = 1.0 / (30.0f32).to_radians().sin();
+        assert_approx_eq!(gbuffer.get(16, 23).depth, nearest, 0.1);
+    }
+}
@@ -0,0 +1,222 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::Rasterizer;
+use crate::canvas::{Aov, AovBuffers, Canvas, Color};
+use crate::model::PointLight;
+use crate::shader::Camera;
+
+///
+/// Lights binned into screen tiles, so shading a pixel only evaluates the lights that
+/// can reach its tile.
+///
+/// A light is kept for a tile if its screen bounds overlap the tile and, when the depth
+/// AOV is available, its sphere of influence overlaps the range of depths seen in the
+/// tile. Lights without a range reach every tile.
+///
+pub struct LightGrid {
+    tile_size: usize,
+    tiles: (usize, usize),
+    lights: Vec<Vec<usize>>,
+}
+
+impl LightGrid {
+    /// Bin `lights` into `tile_size` square tiles covering `gbuffer` seen from `camera`
+    pub fn build(lights: &[PointLight], camera: &Camera, gbuffer: &AovBuffers, tile_size: usize) -> Self {
+        crate::profile_scope!("light_grid");
+        assert!(tile_size > 0);
+        let (width, height) = (gbuffer.width(), gbuffer.height());
+        let tiles = (width.div_ceil(tile_size), height.div_ceil(tile_size));
+        let depth_ranges = if gbuffer.is_enabled(Aov::Depth) {
+            Some(depth_ranges(gbuffer, tile_size, tiles))
+        } else {
+            None
+        };
+        let mut grid = Self {
+            tile_size,
+            tiles,
+            lights: vec![Vec::new(); tiles.0 * tiles.1],
+        };
+        for (index, light) in lights.iter().enumerate() {
+            let bounds = Rasterizer::light_bounds(light, camera, width, height);
+            if bounds.area() == 0 {
+                continue;
+            }
+            let distance = (light.position - camera.position).magnitude();
+            for ty in bounds.y0 / tile_size..bounds.y1.div_ceil(tile_size) {
+                for tx in bounds.x0 / tile_size..bounds.x1.div_ceil(tile_size) {
+                    let tile = ty * tiles.0 + tx;
+                    let reaches = match (light.range, &depth_ranges) {
+                        (Some(range), Some(depths)) => {
+                            let (near, far) = depths[tile];
+                            near <= distance + range && far >= distance - range
+                        }
+                        _ => true,
+                    };
+                    if reaches {
+                        grid.lights[tile].push(index);
+                    }
+                }
+            }
+        }
+        grid
+    }
+    /// Width and height in pixels of each tile
+    pub fn tile_size(&self) -> usize {
+        self.tile_size
+    }
+    /// Number of tiles across and down
+    pub fn tiles(&self) -> (usize, usize) {
+        self.tiles
+    }
+    /// Indices of the lights that can reach Pixel (x, y)
+    pub fn lights_at(&self, x: usize, y: usize) -> &[usize] {
+        let tile = (y / self.tile_size) * self.tiles.0 + x / self.tile_size;
+        &self.lights[tile]
+    }
+    /// Total number of light and tile pairs, a measure of the shading cost
+    pub fn len(&self) -> usize {
+        self.lights.iter().map(Vec::len).sum()
+    }
+    /// Check if no light reaches any tile
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Nearest and farthest finite depth of each tile, an empty range for tiles without any
+fn depth_ranges(gbuffer: &AovBuffers, tile_size: usize, tiles: (usize, usize)) -> Vec<(f32, f32)> {
+    let mut ranges = vec![(f32::INFINITY, f32::NEG_INFINITY); tiles.0 * tiles.1];
+    for y in 0..gbuffer.height() {
+        for x in 0..gbuffer.width() {
+            let depth = gbuffer.get(x, y).depth;
+            if depth.is_finite() {
+                let range = &mut ranges[(y / tile_size) * tiles.0 + x / tile_size];
+                range.0 = range.0.min(depth);
+                range.1 = range.1.max(depth);
+            }
+        }
+    }
+    ranges
+}
+
+impl Rasterizer {
+    /// Shade the G-buffer `gbuffer` into `target` with `lights`, evaluating only the
+    /// lights `grid` binned into each pixel's tile.
+    ///
+    /// Gives the same result as `accumulate_lights` on a black target, but the cost
+    /// grows with the lights near each pixel rather than with every light in the scene.
+    /// Rows are shaded in parallel on the global job pool.
+    pub fn shade_tiled(target: &mut Canvas, gbuffer: &AovBuffers, camera: &Camera, lights: &[PointLight], grid: &LightGrid) {
+        crate::profile_scope!("shade_tiled");
+        assert!(gbuffer.is_enabled(Aov::Albedo) && gbuffer.is_enabled(Aov::Normal) && gbuffer.is_enabled(Aov::Depth));
+        let (width, height) = (target.width(), target.height());
+        let rows = crate::jobs::global().map(height, |y| {
+            let shade = |x: usize| {
+                let sample = gbuffer.get(x, y);
+                if !sample.depth.is_finite() {
+                    return None;
+                }
+                let position = camera.position + camera.ray_direction(x, y, width, height) * sample.depth;
+                let irradiance: Color = grid
+                    .lights_at(x, y)
+                    .iter()
+                    .map(|&light| &lights[light])
+                    .filter(|light| light.links_to(sample.light_links))
+                    .map(|light| light.irradiance(position, sample.normal))
+                    .sum();
+                Some(sample.albedo * irradiance)
+            };
+            (0..width).map(shade).collect::<Vec<Option<Color>>>()
+        });
+        for (y, row) in rows.into_iter().enumerate() {
+            for (x, color) in row.into_iter().enumerate() {
+                if let Some(color) = color {
+                    target.set(x, y, color);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LightGrid;
+    use crate::canvas::{Aov, AovBuffers, AovSample, Canvas, Color};
+    use crate::math::Vec3f;
+    use crate::model::PointLight;
+    use crate::shader::Camera;
+    use crate::Rasterizer;
+
+    fn scene() -> (Camera, AovBuffers, Vec<PointLight>) {
+        let mut camera = Camera::new(
+            Vec3f::from_parts(0.0, 4.0, 0.0),
+            Vec3f::from_parts(0.0, -1.0, 0.0),
+            Vec3f::from_parts(0.0, 0.0, -1.0),
+        );
+        camera.set_viewport(64, 64);
+        let mut gbuffer = AovBuffers::new(64, 64, &[Aov::Albedo, Aov::Normal, Aov::Depth]);
+        for y in 0..64 {
+            for x in 0..64 {
+                let direction = camera.ray_direction(x, y, 64, 64);
+                let sample = AovSample {
+                    albedo: Color::white(),
+                    normal: Vec3f::from_parts(0.0, 1.0, 0.0),
+                    depth: 4.0 / -direction.y,
+                    ..AovSample::default()
+                };
+                gbuffer.set(x, y, &sample);
+            }
+        }
+        let mut lights = Vec::new();
+        for i in 0..16 {
+            let x = (i % 4) as f32 - 1.5;
+            let z = (i / 4) as f32 - 1.5;
+            let mut light = PointLight::new(Vec3f::from_parts(x, 0.2, z), 0.5);
+            light.range = Some(0.6);
+            lights.push(light);
+        }
+        // Far below the floor, on screen but hidden behind it
+        let mut hidden = PointLight::new(Vec3f::from_parts(0.0, -20.0, 0.0), 1.0);
+        hidden.range = Some(1.0);
+        lights.push(hidden);
+        (camera, gbuffer, lights)
+    }
+
+    #[test]
+    fn test_binning() {
+        let (camera, gbuffer, lights) = scene();
+        let grid = LightGrid::build(&lights, &camera, &gbuffer, 8);
+        assert_eq!(grid.tiles(), (8, 8));
+        assert!(grid.len() < 64 * lights.len() / 4);
+        assert!((0..64).all(|y| (0..64).all(|x| !grid.lights_at(x, y).contains(&16))));
+        // Unlimited lights reach everything
+        let everywhere = [PointLight::new(Vec3f::from_parts(0.0, 1.0, 0.0), 1.0)];
+        assert_eq!(LightGrid::build(&everywhere, &camera, &gbuffer, 16).len(), 16);
+    }
+
+    #[test]
+    fn test_matches_accumulation() {
+        let (camera, gbuffer, lights) = scene();
+        let grid = LightGrid::build(&lights, &camera, &gbuffer, 8);
+        let mut tiled = Canvas::new(64, 64);
+        Rasterizer::shade_tiled(&mut tiled, &gbuffer, &camera, &lights, &grid);
+        let mut accumulated = Canvas::new(64, 64);
+        Rasterizer::accumulate_lights(&mut accumulated, &gbuffer, &camera, &lights);
+        assert!(tiled.pixels().all(|(x, y, c)| accumulated.get(x, y) == c));
+        assert!(tiled.pixels().any(|(_, _, c)| c != Color::black()));
+    }
+}
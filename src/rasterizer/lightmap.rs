@@ -0,0 +1,112 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::clipping::scan_polygon;
+use super::velocity::cull_polygon;
+use super::Rasterizer;
+use crate::canvas::{AovBuffers, Canvas, Color};
+use crate::math::Vec3f;
+use crate::model::Mesh;
+use crate::profile::{self, PipelineStats};
+use crate::raytracer::Lightmap;
+use crate::shader::Camera;
+
+impl Rasterizer {
+    /// Draw `mesh` into `target` lit by its baked `lightmap`, colored `albedo`.
+    ///
+    /// The mesh is drawn where it stood when it was baked. Surfaces are depth tested
+    /// against and written to the depth AOV of `aovs`, which must match the size of
+    /// `target`. Surfaces behind the camera's clip plane or user clip planes are discarded.
+    /// Triangles are clipped to the view, and those wholly clipped, off screen or between
+    /// pixel centers are culled.
+    pub fn draw_lightmapped(
+        target: &mut Canvas,
+        aovs: &mut AovBuffers,
+        mesh: &Mesh,
+        lightmap: &Lightmap,
+        camera: &Camera,
+        albedo: Color,
+    ) {
+        let (width, height) = (target.width(), target.height());
+        let view_projection = camera.view_projection();
+        let project = |vertex: Vec3f| {
+            let clip = vertex.extend(1.0).transform_homogeneous(view_projection);
+            (clip, vertex - camera.position)
+        };
+        let mut stats = PipelineStats::default();
+        for (index, triangle) in mesh.triangles().iter().enumerate() {
+            if camera.clip_planes.clips_all(&[triangle.a, triangle.b, triangle.c]) {
+                stats.triangles_submitted += 1;
+                stats.frustum_culled += 1;
+                continue;
+            }
+            let [(ca, oa), (cb, ob), (cc, oc)] = [project(triangle.a), project(triangle.b), project(triangle.c)];
+            let polygon = Rasterizer::clip_triangle([ca, cb, cc]);
+            if cull_polygon(width, height, &polygon, &mut stats) {
+                continue;
+            }
+            scan_polygon(width, height, &polygon, |x, y, _, [_, u, v]| {
+                let offset = oa * (1.0 - u - v) + ob * u + oc * v;
+                let depth = offset.magnitude();
+                let mut sample = aovs.get(x, y);
+                if depth >= sample.depth {
+                    stats.depth_rejected += 1;
+                    return;
+                }
+                if camera.is_clipped(camera.position + offset) {
+                    return;
+                }
+                sample.depth = depth;
+                aovs.set(x, y, &sample);
+                target.set(x, y, albedo * lightmap.sample(index, u, v));
+                stats.pixels_shaded += 1;
+            });
+        }
+        profile::record_stats(&stats);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::{Aov, AovBuffers, Canvas, Color};
+    use crate::math::Vec3f;
+    use crate::model::{cube, PointLight};
+    use crate::shader::Camera;
+    use crate::{LightmapBaker, Rasterizer};
+
+    #[test]
+    fn test_draw_lightmapped() {
+        let mesh = cube(1.0);
+        let light = PointLight::new(Vec3f::from_parts(0.0, 0.0, 3.0), 9.0);
+        let mut baker = LightmapBaker::new(32);
+        baker.indirect_samples = 0;
+        let lightmap = baker.bake(&mesh, &[light]);
+        let mut camera = Camera::new(
+            Vec3f::from_parts(0.0, 0.0, 5.0),
+            Vec3f::from_parts(0.0, 0.0, -1.0),
+            Vec3f::from_parts(0.0, 1.0, 0.0),
+        );
+        camera.set_viewport(32, 32);
+        let mut target = Canvas::new(32, 32);
+        let mut aovs = AovBuffers::new(32, 32, &[Aov::Depth]);
+        Rasterizer::draw_lightmapped(&mut target, &mut aovs, &mesh, &lightmap, &camera, Color::bright_red());
+        // The lit north face shows in the middle, the background is untouched
+        let center = target.get(16, 16);
+        assert!(center.r.value() > 0.5 && center.g.value() == 0.0, "{:?}", center);
+        assert_eq!(target.get(0, 0), Color::black());
+        assert!(aovs.get(16, 16).depth < 5.0);
+    }
+}
@@ -14,34 +14,351 @@
 // limitations under the License.
 //
 
-use crate::image::{Color, Image};
+mod arena;
+mod clipping;
+mod command_buffer;
+mod debug_draw;
+mod deferred;
+mod fill;
+mod gbuffer;
+mod light_grid;
+mod lightmap;
+mod outline;
+mod paint;
+mod planar;
+mod reflections;
+mod text;
+mod velocity;
+mod water;
+mod wireframe;
+
+use crate::canvas::{ClipRect, PixelBuffer};
+use crate::math::Vec2f;
+use std::convert::TryFrom;
+
+pub use self::arena::{Arena, ArenaStats};
+pub use self::clipping::{ClipVertex, ClippedPolygon, MAX_CLIPPED_VERTICES};
+pub use self::command_buffer::{Command, CommandBuffer};
+pub use self::debug_draw::DebugDraw;
+pub use self::deferred::LightBounds;
+pub use self::light_grid::LightGrid;
+pub use self::paint::{Gradient, Paint};
+pub use self::planar::PlanarReflector;
+pub use self::reflections::ReflectionSettings;
 
 /// Trait to draw on a Buffer
 pub struct Rasterizer;
 
 impl Rasterizer {
-    /// Set all Pixels to color.
-    fn clear<C: Color>(image: &mut Image<C>, color: C) {}
+    /// Set all Pixels inside the buffer's clip rectangle to value.
+    pub fn clear<B: PixelBuffer>(buffer: &mut B, value: B::Pixel) {
+        let rect = buffer.clip_rect();
+        for y in rect.y0..rect.y1 {
+            buffer.fill_span(y, rect.x0, rect.x1, value);
+        }
+    }
 
     /// Draw point at (x, y)
-    fn draw_point<C: Color>(image: &mut Image<C>, x: usize, y: usize, color: T) {}
-
-    /// Draw line from (x1, y1) to (x2, y2)
-    fn draw_line<C: Color>(image: &mut Image<C>, x1: usize, y1: usize, x2: usize, y2: usize, color: C) {
-        let m_new = 2 * (y2 - y1) as isize;
-        let mut slope_error_new = m_new - (x2 - x1) as isize;
-        let mut y = y1;
-        for x in x1..x2 {
-            // Set Pixel to line color
-            image.set(x, y, color);
-            // Add slope to increment angle formed
-            slope_error_new += m_new;
-            // Slope Error Reached Limit, time to
-            // increment y and update slope error.
-            if slope_error_new >= 0 {
-                y += 1;
-                slope_error_new -= 2 * (x2 - x1) as isize;
+    pub fn draw_point<B: PixelBuffer>(buffer: &mut B, x: usize, y: usize, value: B::Pixel) {
+        if buffer.clip_rect().contains(x, y) {
+            buffer.set(x, y, value);
+        }
+    }
+
+    /// Fill the `width` by `height` rectangle with top left corner (x, y), clipped to the
+    /// buffer.
+    pub fn fill_rect<B: PixelBuffer>(buffer: &mut B, x: isize, y: isize, width: usize, height: usize, value: B::Pixel) {
+        let rect = buffer.clip_rect();
+        let clamp = |v: isize, min: usize, max: usize| v.max(min as isize).min(max as isize) as usize;
+        let x0 = clamp(x, rect.x0, rect.x1);
+        let y0 = clamp(y, rect.y0, rect.y1);
+        let x1 = clamp(x + width as isize, x0, rect.x1);
+        let y1 = clamp(y + height as isize, y0, rect.y1);
+        for py in y0..y1 {
+            buffer.fill_span(py, x0, x1, value);
+        }
+    }
+
+    /// Draw line from (x1, y1) to (x2, y2) including both ends, clipped to the buffer.
+    ///
+    /// Uses Bresenham's algorithm, so the line may run in any direction and lights
+    /// exactly one Pixel per step along its longer axis. The line is first cut to the
+    /// clip rectangle, so ends far outside it cost nothing; where it crosses the edge the
+    /// cut line may round to the neighbouring Pixel of the uncut one.
+    pub fn draw_line<B: PixelBuffer>(buffer: &mut B, x1: usize, y1: usize, x2: usize, y2: usize, value: B::Pixel) {
+        // Ends too far out for isize still lie beyond the right or bottom edge
+        let signed = |v: usize| isize::try_from(v).unwrap_or(isize::MAX);
+        let from = (signed(x1), signed(y1));
+        let to = (signed(x2), signed(y2));
+        let ((mut x, mut y), (x2, y2)) = match clip_line(&buffer.clip_rect(), from, to) {
+            Some(line) => line,
+            None => return,
+        };
+        let dx = (x2 - x).abs();
+        let dy = -(y2 - y).abs();
+        let step_x = if x < x2 { 1 } else { -1 };
+        let step_y = if y < y2 { 1 } else { -1 };
+        // Error of the next Pixel is kept doubled so it stays an integer
+        let mut error = dx + dy;
+        loop {
+            buffer.set(x as usize, y as usize, value);
+            if x == x2 && y == y2 {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
             }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Draw line between sub-pixel positions `from` and `to`, clipped to the buffer.
+    pub fn draw_line_f<B: PixelBuffer>(buffer: &mut B, from: Vec2f, to: Vec2f, value: B::Pixel) {
+        let rect = buffer.clip_rect();
+        let (left, top, right, bottom) = (rect.x0 as f32, rect.y0 as f32, rect.x1 as f32, rect.y1 as f32);
+        // Clip to the buffer first (Liang-Barsky) so far off screen ends cost nothing
+        let delta = to - from;
+        let (mut t0, mut t1) = (0.0f32, 1.0f32);
+        for &(p, q) in &[
+            (-delta.x, from.x - left),
+            (delta.x, right - from.x),
+            (-delta.y, from.y - top),
+            (delta.y, bottom - from.y),
+        ] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return;
+                }
+            } else {
+                let t = q / p;
+                if p < 0.0 {
+                    t0 = t0.max(t);
+                } else {
+                    t1 = t1.min(t);
+                }
+            }
+        }
+        if t0 > t1 || !t0.is_finite() || !t1.is_finite() {
+            return;
+        }
+        let start = from + delta * t0;
+        let span = delta * (t1 - t0);
+        let steps = span.x.abs().max(span.y.abs()).ceil().max(1.0) as usize;
+        for step in 0..=steps {
+            let point = start + span * (step as f32 / steps as f32);
+            let (x, y) = (point.x.floor(), point.y.floor());
+            if x >= left && y >= top && x < right && y < bottom {
+                buffer.set(x as usize, y as usize, value);
+            }
+        }
+    }
+}
+
+/// Cut the line from `from` to `to` to the Pixels of `rect` (Cohen-Sutherland), rounding
+/// the new ends to the nearest Pixel, or `None` if it misses `rect`
+fn clip_line(rect: &ClipRect, from: (isize, isize), to: (isize, isize)) -> Option<((isize, isize), (isize, isize))> {
+    const LEFT: u8 = 1;
+    const RIGHT: u8 = 2;
+    const TOP: u8 = 4;
+    const BOTTOM: u8 = 8;
+    if rect.is_empty() {
+        return None;
+    }
+    // Pixels are the integer points of the closed box, which keeps rounding inside it
+    let (left, top) = (rect.x0 as f64, rect.y0 as f64);
+    let (right, bottom) = ((rect.x1 - 1) as f64, (rect.y1 - 1) as f64);
+    let outcode = |(x, y): (f64, f64)| {
+        let mut code = 0;
+        if x < left {
+            code |= LEFT;
+        } else if x > right {
+            code |= RIGHT;
+        }
+        if y < top {
+            code |= TOP;
+        } else if y > bottom {
+            code |= BOTTOM;
+        }
+        code
+    };
+    let (mut a, mut b) = ((from.0 as f64, from.1 as f64), (to.0 as f64, to.1 as f64));
+    let (mut code_a, mut code_b) = (outcode(a), outcode(b));
+    loop {
+        if code_a | code_b == 0 {
+            let round = |(x, y): (f64, f64)| (x.round() as isize, y.round() as isize);
+            return Some((round(a), round(b)));
+        }
+        if code_a & code_b != 0 {
+            return None;
+        }
+        // Move an end that is outside onto the edge it lies beyond
+        let code = if code_a != 0 { code_a } else { code_b };
+        let ((x0, y0), (x1, y1)) = (a, b);
+        let point = if code & TOP != 0 {
+            (x0 + (x1 - x0) * (top - y0) / (y1 - y0), top)
+        } else if code & BOTTOM != 0 {
+            (x0 + (x1 - x0) * (bottom - y0) / (y1 - y0), bottom)
+        } else if code & LEFT != 0 {
+            (left, y0 + (y1 - y0) * (left - x0) / (x1 - x0))
+        } else {
+            (right, y0 + (y1 - y0) * (right - x0) / (x1 - x0))
+        };
+        if code == code_a {
+            a = point;
+            code_a = outcode(a);
+        } else {
+            b = point;
+            code_b = outcode(b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rasterizer, Vec2f};
+    use crate::canvas::{Canvas, ClipRect, Color, IdBuffer};
+
+    #[test]
+    fn test_draw_ids() {
+        // The same draw calls render colors and object ids for picking
+        let mut canvas = Canvas::new(8, 8);
+        let mut ids = IdBuffer::new(8, 8);
+        Rasterizer::clear(&mut canvas, Color::white());
+        Rasterizer::draw_line(&mut canvas, 0, 0, 7, 3, Color::bright_red());
+        Rasterizer::draw_line(&mut ids, 0, 0, 7, 3, 5);
+        Rasterizer::draw_point(&mut ids, 6, 7, 9);
+        for y in 0..8 {
+            for x in 0..8 {
+                let drawn = canvas.get(x, y) == Color::bright_red();
+                assert_eq!(drawn, ids.pick(x, y) == Some(5));
+            }
+        }
+        assert_eq!(ids.pick(0, 0), Some(5));
+        assert_eq!(ids.pick(6, 7), Some(9));
+        assert_eq!(ids.pick(7, 7), None);
+    }
+
+    #[test]
+    fn test_fill_rect() {
+        let mut canvas = Canvas::new(4, 4);
+        Rasterizer::fill_rect(&mut canvas, -1, 2, 3, 5, Color::white());
+        let lit: Vec<(usize, usize)> = canvas.pixels().filter(|p| p.2 == Color::white()).map(|p| (p.0, p.1)).collect();
+        assert_eq!(lit, vec![(0, 2), (1, 2), (0, 3), (1, 3)]);
+    }
+
+    #[test]
+    fn test_draw_line_clipped() {
+        let mut canvas = Canvas::new(4, 4);
+        Rasterizer::draw_line(&mut canvas, 2, 1, 9, 3, Color::white());
+        let lit: Vec<(usize, usize)> = canvas.pixels().filter(|p| p.2 == Color::white()).map(|p| (p.0, p.1)).collect();
+        assert_eq!(lit, vec![(2, 1), (3, 1)]);
+        let mut canvas = Canvas::new(4, 4);
+        Rasterizer::draw_line(&mut canvas, 1, 2, usize::MAX, 2, Color::white());
+        Rasterizer::draw_line(&mut canvas, 2, 0, 2, usize::MAX, Color::white());
+        let lit: Vec<(usize, usize)> = canvas.pixels().filter(|p| p.2 == Color::white()).map(|p| (p.0, p.1)).collect();
+        assert_eq!(lit, vec![(2, 0), (2, 1), (1, 2), (2, 2), (3, 2), (2, 3)]);
+    }
+
+    /// Pixels lit by `draw_line` from `from` to `to` on a 16 by 16 canvas
+    fn line_pixels(from: (usize, usize), to: (usize, usize)) -> Vec<(usize, usize)> {
+        let mut canvas = Canvas::new(16, 16);
+        Rasterizer::draw_line(&mut canvas, from.0, from.1, to.0, to.1, Color::white());
+        canvas.pixels().filter(|p| p.2 == Color::white()).map(|p| (p.0, p.1)).collect()
+    }
+
+    #[test]
+    fn test_draw_line_octants() {
+        let center = (8, 8);
+        for &(dx, dy) in &[(7isize, 3isize), (3, 7), (-3, 7), (-7, 3), (-7, -3), (-3, -7), (3, -7), (7, -3)] {
+            let to = ((8 + dx) as usize, (8 + dy) as usize);
+            let lit = line_pixels(center, to);
+            assert!(lit.contains(&center) && lit.contains(&to), "{:?} misses an end", to);
+            // One Pixel per step along the longer axis, each next to the last
+            assert_eq!(lit.len(), 8, "{:?} lit {:?}", to, lit);
+            if dx.abs() > dy.abs() {
+                let mut columns: Vec<usize> = lit.iter().map(|p| p.0).collect();
+                columns.sort();
+                columns.dedup();
+                assert_eq!(columns.len(), 8);
+            } else {
+                let rows: Vec<usize> = lit.iter().map(|p| p.1).collect();
+                assert!(rows.windows(2).all(|pair| pair[1] == pair[0] + 1));
+            }
+            // Following the exact line to within half a Pixel
+            for &(x, y) in &lit {
+                let (x, y) = (x as f32 - 8.0, y as f32 - 8.0);
+                let (dx, dy) = (dx as f32, dy as f32);
+                let distance = if dx.abs() > dy.abs() { y - x * dy / dx } else { x - y * dx / dy };
+                assert!(distance.abs() <= 0.5, "{:?} strays to ({}, {})", to, x, y);
+            }
+            // Swapping the ends draws the same Pixels
+            assert_eq!(line_pixels(to, center), lit);
+        }
+    }
+
+    #[test]
+    fn test_draw_line_straight() {
+        assert_eq!(line_pixels((3, 5), (6, 5)), vec![(3, 5), (4, 5), (5, 5), (6, 5)]);
+        assert_eq!(line_pixels((6, 5), (3, 5)), vec![(3, 5), (4, 5), (5, 5), (6, 5)]);
+        assert_eq!(line_pixels((2, 9), (2, 6)), vec![(2, 6), (2, 7), (2, 8), (2, 9)]);
+        assert_eq!(line_pixels((4, 4), (1, 1)), vec![(1, 1), (2, 2), (3, 3), (4, 4)]);
+        assert_eq!(line_pixels((7, 7), (7, 7)), vec![(7, 7)]);
+    }
+
+    #[test]
+    fn test_draw_line_f() {
+        let mut canvas = Canvas::new(8, 8);
+        // Steep, right to left and partly off screen
+        Rasterizer::draw_line_f(&mut canvas, Vec2f::from_parts(6.5, -4.0), Vec2f::from_parts(2.5, 12.0), Color::white());
+        for y in 0..8 {
+            let lit: Vec<usize> = (0..8).filter(|&x| canvas.get(x, y) == Color::white()).collect();
+            assert_eq!(lit.len(), 1, "row {} has {:?}", y, lit);
+        }
+        assert_eq!(canvas.get(5, 0), Color::white());
+        assert_eq!(canvas.get(3, 7), Color::white());
+        // Entirely outside
+        let mut canvas = Canvas::new(8, 8);
+        Rasterizer::draw_line_f(&mut canvas, Vec2f::from_parts(-5.0, -1.0), Vec2f::from_parts(20.0, -3.0), Color::white());
+        assert!(canvas.pixels().all(|(_, _, c)| c == Color::black()));
+    }
+
+    #[test]
+    fn test_draw_line_far_outside() {
+        // Cut to the canvas before stepping, so this does not walk a trillion Pixels
+        let mut canvas = Canvas::new(8, 8);
+        Rasterizer::draw_line(&mut canvas, 1 << 40, 4, 2, 4, Color::white());
+        let lit: Vec<(usize, usize)> = canvas.pixels().filter(|p| p.2 == Color::white()).map(|p| (p.0, p.1)).collect();
+        assert_eq!(lit, vec![(2, 4), (3, 4), (4, 4), (5, 4), (6, 4), (7, 4)]);
+        Rasterizer::draw_line(&mut canvas, 1 << 40, 1 << 40, 1 << 41, 1 << 41, Color::bright_red());
+        assert!(canvas.pixels().all(|(_, _, c)| c != Color::bright_red()));
+    }
+
+    #[test]
+    fn test_clip_rect() {
+        let mut canvas = Canvas::new(16, 16);
+        let rect = ClipRect::new(4, 4, 8, 6);
+        canvas.set_clip_rect(Some(rect));
+        Rasterizer::clear(&mut canvas, Color::new(0.5, 0.5, 0.5));
+        Rasterizer::draw_line(&mut canvas, 0, 0, 15, 15, Color::white());
+        Rasterizer::draw_line(&mut canvas, 15, 0, 0, 15, Color::white());
+        let (from, to) = (Vec2f::from_parts(-3.0, 6.5), Vec2f::from_parts(30.0, 6.5));
+        Rasterizer::draw_line_f(&mut canvas, from, to, Color::white());
+        Rasterizer::fill_rect(&mut canvas, -4, 8, 40, 40, Color::white());
+        Rasterizer::fill_circle(&mut canvas, Vec2f::from_parts(12.0, 4.0), 3.0, Color::white());
+        Rasterizer::draw_point(&mut canvas, 2, 2, Color::white());
+        Rasterizer::draw_text(&mut canvas, 0, 0, "CLIP", 2, Color::white());
+        for (x, y, color) in canvas.pixels() {
+            assert_eq!(color != Color::black(), rect.contains(x, y), "({}, {})", x, y);
         }
+        // Pixels on both diagonals, the horizontal line and the rectangle were drawn
+        let white = |x: usize, y: usize| canvas.get(x, y) == Color::white();
+        assert!(white(4, 4) && white(9, 9) && white(11, 4) && white(6, 9));
+        assert!((4..12).all(|x| white(x, 6) && white(x, 8) && white(x, 9)));
+        assert!(!white(5, 7));
     }
 }
\ No newline at end of file
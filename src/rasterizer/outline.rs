@@ -0,0 +1,96 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::Rasterizer;
+use crate::canvas::{IdBuffer, PixelBuffer};
+
+impl Rasterizer {
+    /// Draw an outline `thickness` pixels wide around the objects in `selected`.
+    ///
+    /// The outline is found by growing the selected pixels of `ids` outwards, so it hugs
+    /// the visible silhouette and is drawn on top of whatever surrounds the selection.
+    pub fn draw_selection_outline<B: PixelBuffer>(
+        buffer: &mut B,
+        ids: &IdBuffer,
+        selected: &[u32],
+        thickness: usize,
+        value: B::Pixel,
+    ) {
+        assert_eq!(buffer.width(), ids.width());
+        assert_eq!(buffer.height(), ids.height());
+        let is_selected = |x: usize, y: usize| {
+            let id = ids.get(x, y);
+            id != IdBuffer::BACKGROUND && selected.contains(&id)
+        };
+        let radius = thickness as isize;
+        let (width, height) = (ids.width() as isize, ids.height() as isize);
+        let rect = buffer.clip_rect();
+        for y in rect.y0 as isize..rect.y1 as isize {
+            for x in rect.x0 as isize..rect.x1 as isize {
+                if is_selected(x as usize, y as usize) {
+                    continue;
+                }
+                let near_selection = (-radius..=radius).any(|dy| {
+                    (-radius..=radius).any(|dx| {
+                        let (nx, ny) = (x + dx, y + dy);
+                        dx * dx + dy * dy <= radius * radius
+                            && nx >= 0
+                            && ny >= 0
+                            && nx < width
+                            && ny < height
+                            && is_selected(nx as usize, ny as usize)
+                    })
+                });
+                if near_selection {
+                    buffer.set(x as usize, y as usize, value);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::{Canvas, Color, IdBuffer};
+    use crate::Rasterizer;
+
+    #[test]
+    fn test_selection_outline() {
+        let mut ids = IdBuffer::new(9, 9);
+        for y in 3..6 {
+            for x in 3..6 {
+                ids.set(x, y, 1);
+            }
+        }
+        ids.set(0, 0, 2);
+        let mut canvas = Canvas::new(9, 9);
+        Rasterizer::draw_selection_outline(&mut canvas, &ids, &[1], 1, Color::bright_yellow());
+        let outlined = |x, y| canvas.get(x, y) == Color::bright_yellow();
+        // Selected pixels themselves are left alone
+        assert!(!outlined(4, 4));
+        assert!(!outlined(3, 3));
+        // Edge neighbours are outlined, diagonal corners are outside a radius of 1
+        assert!(outlined(2, 4));
+        assert!(outlined(6, 4));
+        assert!(outlined(4, 2));
+        assert!(outlined(4, 6));
+        assert!(!outlined(2, 2));
+        assert!(!outlined(7, 4));
+        // Unselected objects get no outline
+        assert!(!outlined(1, 0));
+        assert!(!outlined(0, 1));
+    }
+}
@@ -0,0 +1,147 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::canvas::{Canvas, Color};
+use crate::math::Vec2f;
+
+///
+/// Colors interpolated between stops placed from 0.0 to 1.0
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    stops: Vec<(f32, Color)>,
+}
+
+impl Gradient {
+    /// Create new Gradient running from `from` at 0.0 to `to` at 1.0
+    pub fn new(from: Color, to: Color) -> Self {
+        Self {
+            stops: vec![(0.0, from), (1.0, to)],
+        }
+    }
+    /// Add a color stop at `offset`, between 0.0 and 1.0
+    pub fn add_stop(mut self, offset: f32, color: Color) -> Self {
+        let offset = offset.clamp(0.0, 1.0);
+        let index = self.stops.iter().position(|&(o, _)| o > offset).unwrap_or(self.stops.len());
+        self.stops.insert(index, (offset, color));
+        self
+    }
+    /// Get the color at `t`, clamped to the first and last stops
+    pub fn sample(&self, t: f32) -> Color {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let after = self.stops.iter().position(|&(o, _)| o >= t).unwrap_or(self.stops.len() - 1);
+        if after == 0 {
+            return self.stops[0].1;
+        }
+        let (start, from) = self.stops[after - 1];
+        let (end, to) = self.stops[after];
+        let f = if end > start { (t - start) / (end - start) } else { 1.0 };
+        from * (1.0 - f) + to * f
+    }
+}
+
+///
+/// How a filled shape is colored, evaluated at the center of every covered pixel
+///
+#[derive(Clone)]
+pub enum Paint {
+    /// Single flat color
+    Solid(Color),
+    /// Gradient along the line from `start` to `end`, constant across it
+    Linear { start: Vec2f, end: Vec2f, gradient: Gradient },
+    /// Gradient from `center` outwards, reaching its end at `radius`
+    Radial { center: Vec2f, radius: f32, gradient: Gradient },
+    /// Canvas repeated across the plane with its top left corner at `origin`
+    Pattern { texture: Canvas, origin: Vec2f },
+}
+
+impl Paint {
+    /// Get the color of the pixel centered at `point`
+    pub fn color_at(&self, point: Vec2f) -> Color {
+        match self {
+            Paint::Solid(color) => *color,
+            Paint::Linear { start, end, gradient } => {
+                let axis = *end - *start;
+                let length = axis.dot(axis);
+                let t = if length > 0.0 { (point - *start).dot(axis) / length } else { 0.0 };
+                gradient.sample(t)
+            }
+            Paint::Radial { center, radius, gradient } => {
+                let t = if *radius > 0.0 { (point - *center).magnitude() / radius } else { 1.0 };
+                gradient.sample(t)
+            }
+            Paint::Pattern { texture, origin } => {
+                if texture.width() == 0 || texture.height() == 0 {
+                    return Color::black();
+                }
+                let local = point - *origin;
+                let x = (local.x.floor() as isize).rem_euclid(texture.width() as isize) as usize;
+                let y = (local.y.floor() as isize).rem_euclid(texture.height() as isize) as usize;
+                texture.get(x, y)
+            }
+        }
+    }
+}
+
+impl From<Color> for Paint {
+    fn from(color: Color) -> Self {
+        Paint::Solid(color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Gradient, Paint};
+    use crate::canvas::{Canvas, Color};
+    use crate::math::Vec2f;
+
+    #[test]
+    fn test_gradient() {
+        let gradient = Gradient::new(Color::black(), Color::white()).add_stop(0.5, Color::bright_red());
+        assert_eq!(gradient.sample(-1.0), Color::black());
+        assert_eq!(gradient.sample(0.25), Color::new(0.5, 0.0, 0.0));
+        assert_eq!(gradient.sample(0.5), Color::bright_red());
+        assert_eq!(gradient.sample(0.75), Color::new(1.0, 0.5, 0.5));
+        assert_eq!(gradient.sample(2.0), Color::white());
+    }
+
+    #[test]
+    fn test_paints() {
+        let gradient = Gradient::new(Color::black(), Color::white());
+        let linear = Paint::Linear {
+            start: Vec2f::from_parts(0.0, 0.0),
+            end: Vec2f::from_parts(0.0, 10.0),
+            gradient: gradient.clone(),
+        };
+        assert_eq!(linear.color_at(Vec2f::from_parts(100.0, 5.0)), Color::grey());
+        let radial = Paint::Radial {
+            center: Vec2f::from_parts(5.0, 5.0),
+            radius: 4.0,
+            gradient,
+        };
+        assert_eq!(radial.color_at(Vec2f::from_parts(5.0, 7.0)), Color::grey());
+        assert_eq!(radial.color_at(Vec2f::from_parts(20.0, 5.0)), Color::white());
+        let mut texture = Canvas::new(2, 2);
+        texture.set(1, 0, Color::white());
+        let pattern = Paint::Pattern {
+            texture,
+            origin: Vec2f::from_parts(0.0, 0.0),
+        };
+        assert_eq!(pattern.color_at(Vec2f::from_parts(3.5, 2.5)), Color::white());
+        assert_eq!(pattern.color_at(Vec2f::from_parts(-1.5, 0.5)), Color::black());
+        assert_eq!(pattern.color_at(Vec2f::from_parts(-0.5, 0.5)), Color::white());
+    }
+}
@@ -0,0 +1,231 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+use super::Rasterizer;
+use crate::canvas::{Aov, AovBuffers, Canvas, Color, Filter};
+use crate::math::{Vec3f, Vec4f};
+use crate::shader::Camera;
+
+///
+/// Flat mirror such as a floor or still water.
+///
+/// The scene is rendered a second time from `mirror_camera`, which sees it as reflected
+/// in the mirror and clips away everything behind it, then the result is laid over the
+/// mirror's pixels with `Rasterizer::accumulate_planar_reflection`. Unlike screen space
+/// reflections this also shows what is off screen or hidden in the main view.
+///
+/// ```
+/// use softrender::canvas::{Aov, AovBuffers, Canvas};
+/// use softrender::math::Vec3f;
+/// use softrender::model::{cube, Material};
+/// use softrender::shader::Camera;
+/// use softrender::{PlanarReflector, Rasterizer};
+///
+/// let camera = Camera::new(Vec3f::new(0.0, 2.0, 5.0), Vec3f::new(0.0, -0.4, -1.0), Vec3f::UNIT_Y);
+/// let floor = PlanarReflector::new(Vec3f::new(0.0, -0.5, 0.0), Vec3f::UNIT_Y);
+/// let mut reflection = AovBuffers::new(64, 48, &[Aov::Albedo, Aov::Depth]);
+/// Rasterizer::draw_gbuffer(&mut reflection, &cube(1.0), &Material::new(), &[], &floor.mirror_camera(&camera));
+/// let reflection: Canvas = reflection.to_canvas(Aov::Albedo).unwrap();
+/// ```
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PlanarReflector {
+    /// Any point on the mirror
+    pub point: Vec3f,
+    /// Unit normal on the reflecting side
+    pub normal: Vec3f,
+    /// Fraction of the light reaching the mirror that it reflects
+    pub reflectivity: f32,
+}
+
+impl PlanarReflector {
+    /// Create new fully reflective PlanarReflector through `point` facing `normal`
+    pub fn new(point: Vec3f, normal: Vec3f) -> Self {
+        Self {
+            point,
+            normal: normal.normalize(),
+            reflectivity: 1.0,
+        }
+    }
+    /// Plane of the mirror as `(a, b, c, d)`, positive on the reflecting side
+    pub fn plane(&self) -> Vec4f {
+        self.normal.extend(-self.normal.dot(self.point))
+    }
+    /// Signed distance of `point` from the mirror, positive on the reflecting side
+    pub fn distance(&self, point: Vec3f) -> f32 {
+        self.normal.dot(point - self.point)
+    }
+    /// Mirror image of `point`
+    pub fn mirror_point(&self, point: Vec3f) -> Vec3f {
+        point - self.normal * (2.0 * self.distance(point))
+    }
+    /// Mirror image of `direction`
+    pub fn mirror_direction(&self, direction: Vec3f) -> Vec3f {
+        direction - self.normal * (2.0 * self.normal.dot(direction))
+    }
+    /// Camera seeing what `camera` sees in the mirror, with its near plane on the mirror
+    /// so nothing behind the mirror gets in the way.
+    ///
+    /// The mirrored image is flipped, so read it back through `sample` or
+    /// `Rasterizer::accumulate_planar_reflection` rather than pixel by pixel.
+    pub fn mirror_camera(&self, camera: &Camera) -> Camera {
+        Camera {
+            position: self.mirror_point(camera.position),
+            front: self.mirror_direction(camera.front),
+            up: self.mirror_direction(camera.up),
+            clip_plane: Some(self.plane()),
+            ..*camera
+        }
+    }
+    /// Color of `reflection`, rendered with `mirror_camera(camera)`, reflected at `point` on
+    /// the mirror, `None` if the point is outside the reflection
+    pub fn sample(&self, reflection: &Canvas, camera: &Camera, point: Vec3f) -> Option<Color> {
+        let (width, height) = (reflection.width(), reflection.height());
+        let pixel = self.mirror_camera(camera).project(point.extend(1.0), width, height)?;
+        if pixel.x < 0.0 || pixel.y < 0.0 || pixel.x >= width as f32 || pixel.y >= height as f32 {
+            return None;
+        }
+        Some(reflection.sample(pixel.x, pixel.y, Filter::Bilinear))
+    }
+}
+
+impl Rasterizer {
+    /// Add the reflection in `reflector`, rendered into `reflection` from
+    /// `reflector.mirror_camera(camera)`, onto the pixels of `target` showing the mirror.
+    ///
+    /// The G-buffer needs the normal and depth AOVs, a pixel shows the mirror where its
+    /// surface lies on the mirror's plane facing the same way. Nothing is reflected when
+    /// the camera is behind the mirror. `reflection` may be any size.
+    pub fn accumulate_planar_reflection(
+        target: &mut Canvas,
+        gbuffer: &AovBuffers,
+        camera: &Camera,
+        reflector: &PlanarReflector,
+        reflection: &Canvas,
+    ) {
+        crate::profile_scope!("planar_reflection");
+        assert!(gbuffer.is_enabled(Aov::Normal) && gbuffer.is_enabled(Aov::Depth));
+        assert_eq!((target.width(), target.height()), (gbuffer.width(), gbuffer.height()));
+        if reflector.distance(camera.position) <= 0.0 {
+            return;
+        }
+        let (width, height) = (target.width(), target.height());
+        let mirror = reflector.mirror_camera(camera);
+        let (reflection_width, reflection_height) = (reflection.width() as f32, reflection.height() as f32);
+        for y in 0..height {
+            for x in 0..width {
+                let sample = gbuffer.get(x, y);
+                if !sample.depth.is_finite() || sample.normal.dot(reflector.normal).abs() < 0.99 {
+                    continue;
+                }
+                let position = camera.position + camera.ray_direction(x, y, width, height) * sample.depth;
+                // Allow for the precision of the stored depth
+                if reflector.distance(position).abs() > 1e-3 * sample.depth.max(1.0) {
+                    continue;
+                }
+                let pixel = match mirror.project(position.extend(1.0), reflection.width(), reflection.height()) {
+                    Some(pixel) => pixel,
+                    None => continue,
+                };
+                if pixel.x < 0.0 || pixel.y < 0.0 || pixel.x >= reflection_width || pixel.y >= reflection_height {
+                    continue;
+                }
+                let reflected = reflection.sample(pixel.x, pixel.y, Filter::Bilinear) * reflector.reflectivity;
+                target.set(x, y, target.get(x, y) + reflected);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PlanarReflector;
+    use crate::canvas::{Aov, AovBuffers, Canvas, Color};
+    use crate::math::Vec3f;
+    use crate::model::{cube, Material, Mesh, Triangle};
+    use crate::shader::Camera;
+    use crate::Rasterizer;
+
+    fn box_at(center: Vec3f, size: f32) -> Mesh {
+        let mesh = cube(size);
+        Mesh::new(mesh.triangles().iter().map(|t| Triangle::new(t.a + center, t.b + center, t.c + center)).collect())
+    }
+
+    fn material(color: Color) -> Material {
+        let mut material = Material::new();
+        material.surface = color;
+        material
+    }
+
+    #[test]
+    fn test_mirror() {
+        let floor = PlanarReflector::new(Vec3f::new(0.0, 1.0, 0.0), Vec3f::new(0.0, 2.0, 0.0));
+        assert_eq!(floor.plane(), Vec3f::UNIT_Y.extend(-1.0));
+        assert_eq!(floor.mirror_point(Vec3f::new(1.0, 3.0, 2.0)), Vec3f::new(1.0, -1.0, 2.0));
+        assert_eq!(floor.mirror_direction(Vec3f::new(1.0, -1.0, 0.0)), Vec3f::new(1.0, 1.0, 0.0));
+        let camera = Camera::new(Vec3f::new(0.0, 3.0, 5.0), Vec3f::new(0.0, -1.0, -1.0), Vec3f::UNIT_Y);
+        let mirror = floor.mirror_camera(&camera);
+        assert_eq!(mirror.position, Vec3f::new(0.0, -1.0, 5.0));
+        assert_eq!(mirror.front, Vec3f::new(0.0, 1.0, -1.0));
+        assert!(mirror.is_clipped(Vec3f::new(0.0, 0.5, 0.0)));
+        assert!(!mirror.is_clipped(Vec3f::new(0.0, 1.5, 0.0)));
+    }
+
+    #[test]
+    fn test_accumulate_planar_reflection() {
+        let mut camera = Camera::new(Vec3f::new(0.0, 1.0, 4.0), Vec3f::new(0.0, -1.5, -4.0), Vec3f::UNIT_Y);
+        camera.set_viewport(64, 64);
+        let mut floor = PlanarReflector::new(Vec3f::ZERO, Vec3f::UNIT_Y);
+        floor.reflectivity = 0.5;
+        let above = box_at(Vec3f::new(0.0, 1.0, 0.0), 1.0);
+        // Below the floor, between the floor and the mirrored camera
+        let below = box_at(Vec3f::new(0.0, -0.3, 2.5), 0.5);
+        let p = |x: f32, z: f32| Vec3f::new(x, 0.0, z);
+        let ground = Mesh::new(vec![
+            Triangle::new(p(-5.0, -5.0), p(-5.0, 3.0), p(5.0, 3.0)),
+            Triangle::new(p(5.0, 3.0), p(5.0, -5.0), p(-5.0, -5.0)),
+        ]);
+
+        let mut gbuffer = AovBuffers::new(64, 64, &[Aov::Albedo, Aov::Normal, Aov::Depth]);
+        Rasterizer::draw_gbuffer(&mut gbuffer, &ground, &material(Color::WHITE), &[], &camera);
+        Rasterizer::draw_gbuffer(&mut gbuffer, &above, &material(Color::RED), &[], &camera);
+        let render = |camera: &Camera| {
+            let mut buffers = AovBuffers::new(64, 64, &[Aov::Albedo, Aov::Depth]);
+            Rasterizer::draw_gbuffer(&mut buffers, &above, &material(Color::RED), &[], camera);
+            Rasterizer::draw_gbuffer(&mut buffers, &below, &material(Color::GREEN), &[], camera);
+            buffers.to_canvas(Aov::Albedo).unwrap()
+        };
+        let reflection = render(&floor.mirror_camera(&camera));
+        let mut target = Canvas::new(64, 64);
+        Rasterizer::accumulate_planar_reflection(&mut target, &gbuffer, &camera, &floor, &reflection);
+
+        // The box shows up mirrored on the floor, the box itself reflects nothing
+        // Where the line from the camera to the box's mirror image crosses the floor
+        let image = floor.mirror_point(Vec3f::new(0.0, 1.0, 0.0));
+        let crossing = camera.position + (image - camera.position) * 0.5;
+        let pixel = camera.project(crossing.extend(1.0), 64, 64).unwrap();
+        assert_eq!(target.get(pixel.x as usize, pixel.y as usize), Color::new(0.5, 0.0, 0.0));
+        let top = camera.project(Vec3f::new(0.0, 1.0, 0.0).extend(1.0), 64, 64).unwrap();
+        assert_eq!(target.get(top.x as usize, top.y as usize), Color::BLACK);
+        // Without the clip plane the box under the floor hides the reflection
+        let mut unclipped = floor.mirror_camera(&camera);
+        unclipped.clip_plane = None;
+        let sample = floor.sample(&render(&unclipped), &camera, crossing);
+        assert_eq!(sample, Some(Color::GREEN));
+        assert_eq!(floor.sample(&reflection, &camera, crossing), Some(Color::RED));
+    }
+}
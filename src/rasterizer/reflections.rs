@@ -0,0 +1,191 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+use super::Rasterizer;
+use crate::canvas::{Aov, AovBuffers, AovSample, Canvas, Color, Filter};
+use crate::math::Vec3f;
+use crate::shader::Camera;
+
+/// How far and how finely screen space reflection rays are marched
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ReflectionSettings {
+    /// Longest distance a reflection ray travels in world units
+    pub max_distance: f32,
+    /// Number of steps the ray is marched in
+    pub steps: usize,
+    /// How far behind the depth buffer a ray may be and still count as hitting it
+    pub thickness: f32,
+    /// Fraction of the screen at each edge over which hits fade into the environment
+    pub edge_fade: f32,
+}
+
+impl Default for ReflectionSettings {
+    fn default() -> Self {
+        Self {
+            max_distance: 20.0,
+            steps: 64,
+            thickness: 0.5,
+            edge_fade: 0.1,
+        }
+    }
+}
+
+impl Rasterizer {
+    /// Add screen space reflections of the lit image `lit` into `target`.
+    ///
+    /// The G-buffer needs the normal and depth AOVs. `reflectivity` gives how much each
+    /// pixel's surface reflects, typically looked up by its material id, and pixels
+    /// returning zero are skipped. Reflection rays are marched through the depth buffer and
+    /// pick up the lit color where they pass behind a surface. Rays leaving the screen or
+    /// running out of distance sample `environment` as an equirectangular map instead, or
+    /// reflect nothing without one.
+    pub fn accumulate_reflections<F: Fn(&AovSample) -> f32>(
+        target: &mut Canvas,
+        lit: &Canvas,
+        gbuffer: &AovBuffers,
+        camera: &Camera,
+        environment: Option<&Canvas>,
+        settings: &ReflectionSettings,
+        reflectivity: F,
+    ) {
+        crate::profile_scope!("reflections");
+        assert!(gbuffer.is_enabled(Aov::Normal) && gbuffer.is_enabled(Aov::Depth));
+        assert_eq!((target.width(), target.height()), (gbuffer.width(), gbuffer.height()));
+        assert_eq!((lit.width(), lit.height()), (gbuffer.width(), gbuffer.height()));
+        let (width, height) = (target.width(), target.height());
+        for y in 0..height {
+            for x in 0..width {
+                let sample = gbuffer.get(x, y);
+                let amount = reflectivity(&sample);
+                if !sample.depth.is_finite() || amount <= 0.0 {
+                    continue;
+                }
+                let view = camera.ray_direction(x, y, width, height);
+                let position = camera.position + view * sample.depth;
+                let direction = (view - sample.normal * (2.0 * view.dot(sample.normal))).normalize();
+                let missed = environment
+                    .map_or(Color::black(), |map| map.sample_direction(direction, Filter::Bilinear));
+                let reflected = match trace(gbuffer, camera, position, direction, settings) {
+                    Some((hit_x, hit_y, fade)) => lit.get(hit_x, hit_y) * fade + missed * (1.0 - fade),
+                    None => missed,
+                };
+                target.set(x, y, target.get(x, y) + reflected * amount);
+            }
+        }
+    }
+}
+
+/// March a ray from `origin` along `direction` through the depth buffer, returning the
+/// Pixel it hits and how far it is from the screen edges
+fn trace(
+    gbuffer: &AovBuffers,
+    camera: &Camera,
+    origin: Vec3f,
+    direction: Vec3f,
+    settings: &ReflectionSettings,
+) -> Option<(usize, usize, f32)> {
+    let (width, height) = (gbuffer.width(), gbuffer.height());
+    let step = settings.max_distance / settings.steps.max(1) as f32;
+    for index in 1..=settings.steps {
+        let point = origin + direction * (step * index as f32);
+        let screen = camera.project(point.extend(1.0), width, height)?;
+        if screen.x < 0.0 || screen.y < 0.0 || screen.x >= width as f32 || screen.y >= height as f32 {
+            return None;
+        }
+        let (x, y) = (screen.x as usize, screen.y as usize);
+        let depth = gbuffer.get(x, y).depth;
+        let behind = (point - camera.position).magnitude() - depth;
+        if behind >= 0.0 && behind <= settings.thickness.max(step) {
+            let (u, v) = (screen.x / width as f32, screen.y / height as f32);
+            let edge = u.min(v).min(1.0 - u).min(1.0 - v);
+            let fade = if settings.edge_fade > 0.0 { (edge / settings.edge_fade).min(1.0) } else { 1.0 };
+            return Some((x, y, fade));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReflectionSettings;
+    use crate::canvas::{Aov, AovBuffers, AovSample, Canvas, Color};
+    use crate::math::Vec3f;
+    use crate::shader::Camera;
+    use crate::Rasterizer;
+
+    /// Camera a unit above a reflective floor looking at a red wall 4 units away, with the
+    /// wall dropped when `wall` is false
+    fn room(wall: bool) -> (Camera, AovBuffers, Canvas) {
+        let mut camera = Camera::new(
+            Vec3f::from_parts(0.0, 1.0, 0.0),
+            Vec3f::from_parts(0.0, 0.0, -1.0),
+            Vec3f::from_parts(0.0, 1.0, 0.0),
+        );
+        camera.set_viewport(32, 32);
+        let mut gbuffer = AovBuffers::new(32, 32, &[Aov::Normal, Aov::Depth]);
+        let mut lit = Canvas::new(32, 32);
+        for y in 0..32 {
+            for x in 0..32 {
+                let direction = camera.ray_direction(x, y, 32, 32);
+                let floor = if direction.y < 0.0 { -1.0 / direction.y } else { f32::INFINITY };
+                let back = if wall { 4.0 / -direction.z } else { f32::INFINITY };
+                let mut sample = AovSample::default();
+                if back < floor {
+                    sample.normal = Vec3f::UNIT_Z;
+                    sample.depth = back;
+                    lit.set(x, y, Color::RED);
+                } else if floor.is_finite() {
+                    sample.normal = Vec3f::UNIT_Y;
+                    sample.depth = floor;
+                }
+                gbuffer.set(x, y, &sample);
+            }
+        }
+        (camera, gbuffer, lit)
+    }
+
+    #[test]
+    fn test_reflect_wall() {
+        let (camera, gbuffer, lit) = room(true);
+        let mut target = Canvas::new(32, 32);
+        let settings = ReflectionSettings::default();
+        Rasterizer::accumulate_reflections(&mut target, &lit, &gbuffer, &camera, None, &settings, |sample| {
+            if sample.normal.y > 0.5 {
+                0.5
+            } else {
+                0.0
+            }
+        });
+        // The floor just below the wall mirrors it, the wall itself reflects nothing
+        let floor = target.get(16, 24);
+        assert!(floor.r.value() > 0.0 && floor.r.value() <= 0.5);
+        assert_eq!(floor.g.value(), 0.0);
+        assert_eq!(target.get(16, 8), Color::black());
+    }
+
+    #[test]
+    fn test_environment_fallback() {
+        let (camera, gbuffer, lit) = room(false);
+        let mut sky = Canvas::new(16, 8);
+        sky.fill(Color::BLUE);
+        let mut target = Canvas::new(32, 32);
+        let settings = ReflectionSettings::default();
+        Rasterizer::accumulate_reflections(&mut target, &lit, &gbuffer, &camera, Some(&sky), &settings, |_| 1.0);
+        assert!((target.get(16, 28).b.value() - 1.0).abs() < 1e-4);
+        assert_eq!(target.get(16, 4), Color::black());
+    }
+}
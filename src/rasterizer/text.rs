@@ -0,0 +1,147 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::Rasterizer;
+use crate::canvas::PixelBuffer;
+
+/// Width in pixels of a glyph at scale 1
+const GLYPH_WIDTH: usize = 3;
+/// Height in pixels of a glyph at scale 1
+const GLYPH_HEIGHT: usize = 5;
+
+impl Rasterizer {
+    /// Draw `text` with the built in 3x5 pixel font, top left corner at (x, y).
+    ///
+    /// Each font pixel becomes a `scale` by `scale` block. Lowercase letters are drawn as
+    /// uppercase and characters without a glyph as `?`. Pixels outside the buffer are
+    /// skipped.
+    pub fn draw_text<B: PixelBuffer>(buffer: &mut B, x: isize, y: isize, text: &str, scale: usize, value: B::Pixel) {
+        let advance = ((GLYPH_WIDTH + 1) * scale) as isize;
+        for (index, c) in text.chars().enumerate() {
+            let rows = glyph(c);
+            let left = x + index as isize * advance;
+            for (row, bits) in rows.iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (0b100 >> column) != 0 {
+                        let px = left + (column * scale) as isize;
+                        let py = y + (row * scale) as isize;
+                        Rasterizer::fill_rect(buffer, px, py, scale, scale, value);
+                    }
+                }
+            }
+        }
+    }
+    /// Size in pixels of `text` drawn by `draw_text` at `scale`
+    pub fn text_size(text: &str, scale: usize) -> (usize, usize) {
+        let count = text.chars().count();
+        let width = if count == 0 { 0 } else { (count * (GLYPH_WIDTH + 1) - 1) * scale };
+        (width, GLYPH_HEIGHT * scale)
+    }
+}
+
+/// Rows of a glyph from top to bottom, bit 2 is the leftmost pixel
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        ';' => [0b000, 0b010, 0b000, 0b010, 0b100],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '(' => [0b010, 0b100, 0b100, 0b100, 0b010],
+        ')' => [0b010, 0b001, 0b001, 0b001, 0b010],
+        '[' => [0b110, 0b100, 0b100, 0b100, 0b110],
+        ']' => [0b011, 0b001, 0b001, 0b001, 0b011],
+        '<' => [0b001, 0b010, 0b100, 0b010, 0b001],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        '"' => [0b101, 0b101, 0b000, 0b000, 0b000],
+        '*' => [0b000, 0b101, 0b010, 0b101, 0b000],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        '|' => [0b010, 0b010, 0b010, 0b010, 0b010],
+        _ => [0b110, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::{Canvas, Color};
+    use crate::Rasterizer;
+
+    #[test]
+    fn test_text_size() {
+        assert_eq!(Rasterizer::text_size("", 1), (0, 5));
+        assert_eq!(Rasterizer::text_size("A", 1), (3, 5));
+        assert_eq!(Rasterizer::text_size("FPS", 2), (22, 10));
+    }
+
+    #[test]
+    fn test_draw_text() {
+        let mut canvas = Canvas::new(8, 6);
+        Rasterizer::draw_text(&mut canvas, 0, 0, "t1", 1, Color::white());
+        let lit = |x, y| canvas.get(x, y) == Color::white();
+        // T
+        assert!(lit(0, 0) && lit(1, 0) && lit(2, 0));
+        assert!(!lit(0, 1) && lit(1, 1) && !lit(2, 1));
+        // Gap column then 1
+        assert!((0..6).all(|y| !lit(3, y)));
+        assert!(lit(5, 0) && lit(4, 1) && lit(5, 4) && lit(6, 4));
+        // Off screen text is clipped rather than panicking
+        Rasterizer::draw_text(&mut canvas, -5, 4, "XYZ", 2, Color::white());
+    }
+}
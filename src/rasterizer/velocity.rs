@@ -0,0 +1,271 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::clipping::{scan_polygon, ClippedPolygon, MAX_CLIPPED_VERTICES};
+use super::Rasterizer;
+use crate::canvas::AovBuffers;
+use crate::math::{Mat4f, Vec2f, Vec3f, Vec4f};
+use crate::model::Mesh;
+use crate::profile::{self, PipelineStats};
+use crate::shader::Camera;
+
+/// Vertex ready for rasterization
+struct Projected {
+    /// Position in clip space
+    clip: Vec4f,
+    /// Position in the previous frame, in previous clip space
+    previous: Vec4f,
+    /// Offset from the camera in world space
+    offset: Vec3f,
+}
+
+impl Rasterizer {
+    /// Rasterize `mesh` into the depth and velocity AOVs of `aovs`.
+    ///
+    /// `model` and `camera` place the mesh in this frame, `previous_model` and
+    /// `previous_camera` in the previous one, and the difference in where each surface
+    /// lands on screen is stored as its velocity. Meshes with previous triangles start
+    /// from those, so deforming surfaces get their own motion. Surfaces are depth tested
+    /// against the depth AOV, so it should be enabled unless the mesh is drawn alone.
+    /// Triangles are clipped to the view, and those off screen or between pixel centers
+    /// are culled.
+    pub fn draw_velocity(
+        aovs: &mut AovBuffers,
+        mesh: &Mesh,
+        model: Mat4f,
+        camera: &Camera,
+        previous_model: Mat4f,
+        previous_camera: &Camera,
+    ) {
+        let (width, height) = (aovs.width(), aovs.height());
+        let view_projection = camera.view_projection();
+        let previous_view_projection = previous_camera.view_projection();
+        let project = |vertex: Vec3f, previous: Vec3f| {
+            let world = vertex * model;
+            Projected {
+                clip: world.extend(1.0).transform_homogeneous(view_projection),
+                previous: (previous * previous_model).extend(1.0).transform_homogeneous(previous_view_projection),
+                offset: world - camera.position,
+            }
+        };
+        let mut stats = PipelineStats::default();
+        for (index, triangle) in mesh.triangles().iter().enumerate() {
+            let previous = mesh.previous_triangles().map_or(triangle, |previous| &previous[index]);
+            let vertices = [
+                project(triangle.a, previous.a),
+                project(triangle.b, previous.b),
+                project(triangle.c, previous.c),
+            ];
+            let polygon = Rasterizer::clip_triangle([vertices[0].clip, vertices[1].clip, vertices[2].clip]);
+            if cull_polygon(width, height, &polygon, &mut stats) {
+                continue;
+            }
+            draw_velocity_triangle(aovs, &vertices, &polygon, &mut stats);
+        }
+        profile::record_stats(&stats);
+    }
+}
+
+fn draw_velocity_triangle(
+    aovs: &mut AovBuffers,
+    [a, b, c]: &[Projected; 3],
+    polygon: &ClippedPolygon,
+    stats: &mut PipelineStats,
+) {
+    let (width, height) = (aovs.width() as f32, aovs.height() as f32);
+    scan_polygon(aovs.width(), aovs.height(), polygon, |x, y, point, [pa, pb, pc]| {
+        let depth = (a.offset * pa + b.offset * pb + c.offset * pc).magnitude();
+        let mut sample = aovs.get(x, y);
+        if depth >= sample.depth {
+            stats.depth_rejected += 1;
+            return;
+        }
+        stats.pixels_shaded += 1;
+        let previous = a.previous * pa + b.previous * pb + c.previous * pc;
+        sample.depth = depth;
+        sample.velocity = if previous.w > 0.0 {
+            let previous_screen = Vec2f::from_parts(
+                (previous.x / previous.w + 1.0) / 2.0 * width,
+                (1.0 - previous.y / previous.w) / 2.0 * height,
+            );
+            point - previous_screen
+        } else {
+            Vec2f::from_parts(0.0, 0.0)
+        };
+        aovs.set(x, y, &sample);
+    });
+}
+
+/// Check if the triangle clipped to `polygon` can be skipped, as nothing of it was left in
+/// view, it lies wholly off one side of a `width` by `height` buffer or it covers no Pixel
+/// center, counting it in `stats` either way
+pub(super) fn cull_polygon(width: usize, height: usize, polygon: &ClippedPolygon, stats: &mut PipelineStats) -> bool {
+    stats.triangles_submitted += 1;
+    if polygon.is_empty() {
+        stats.frustum_culled += 1;
+        return true;
+    }
+    let mut screen = [Vec2f::from_parts(0.0, 0.0); MAX_CLIPPED_VERTICES];
+    let count = polygon.vertices().len();
+    for (slot, point) in screen.iter_mut().zip(polygon.screen(width, height)) {
+        *slot = point;
+    }
+    let screen = &screen[..count];
+    let (mut min_x, mut max_x) = (f32::INFINITY, f32::NEG_INFINITY);
+    let (mut min_y, mut max_y) = (f32::INFINITY, f32::NEG_INFINITY);
+    for point in screen {
+        min_x = min_x.min(point.x);
+        max_x = max_x.max(point.x);
+        min_y = min_y.min(point.y);
+        max_y = max_y.max(point.y);
+    }
+    if max_x < 0.0 || max_y < 0.0 || min_x > width as f32 || min_y > height as f32 {
+        stats.frustum_culled += 1;
+        return true;
+    }
+    // Pixel centers lie at half way points, so a box between two of them misses every one
+    let misses = |min: f32, max: f32| (max - 0.5).floor() < (min - 0.5).ceil();
+    let area: f32 = (0..count)
+        .map(|index| {
+            let (p, q) = (screen[index], screen[(index + 1) % count]);
+            p.x * q.y - q.x * p.y
+        })
+        .sum();
+    if area == 0.0 || misses(min_x, max_x) || misses(min_y, max_y) {
+        stats.small_culled += 1;
+        return true;
+    }
+    stats.triangles_drawn += 1;
+    false
+}
+
+/// Call `fragment` with the position, Pixel center and perspective correct barycentric
+/// weights of every Pixel of a `width` by `height` buffer covered by the triangle at
+/// `screen` in pixels with clip space `w`, in either winding
+pub(super) fn scan_triangle<F: FnMut(usize, usize, Vec2f, [f32; 3])>(
+    width: usize,
+    height: usize,
+    [a, b, c]: [Vec2f; 3],
+    [wa, wb, wc]: [f32; 3],
+    mut fragment: F,
+) {
+    let edge = |p: Vec2f, q: Vec2f, r: Vec2f| (q.x - p.x) * (r.y - p.y) - (q.y - p.y) * (r.x - p.x);
+    let area = edge(a, b, c);
+    if area == 0.0 {
+        return;
+    }
+    let min_x = a.x.min(b.x).min(c.x).max(0.0) as usize;
+    let min_y = a.y.min(b.y).min(c.y).max(0.0) as usize;
+    let max_x = a.x.max(b.x).max(c.x).ceil().min(width as f32) as usize;
+    let max_y = a.y.max(b.y).max(c.y).ceil().min(height as f32) as usize;
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let point = Vec2f::from_parts(x as f32 + 0.5, y as f32 + 0.5);
+            // Barycentric weights, the same sign as the area for either winding
+            let ba = edge(b, c, point) / area;
+            let bb = edge(c, a, point) / area;
+            let bc = edge(a, b, point) / area;
+            if ba < 0.0 || bb < 0.0 || bc < 0.0 {
+                continue;
+            }
+            // Perspective correct weights
+            let (pa, pb, pc) = (ba / wa, bb / wb, bc / wc);
+            let sum = pa + pb + pc;
+            fragment(x, y, point, [pa / sum, pb / sum, pc / sum]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::cull_polygon;
+    use crate::canvas::{Aov, AovBuffers};
+    use crate::math::{Mat4f, Vec3f, Vec4f};
+    use crate::model::{cube, Triangle};
+    use crate::profile::PipelineStats;
+    use crate::shader::Camera;
+    use crate::Rasterizer;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn camera(x: f32) -> Camera {
+        let mut camera = Camera::new(
+            Vec3f::from_parts(x, 0.0, 5.0),
+            Vec3f::from_parts(0.0, 0.0, -1.0),
+            Vec3f::from_parts(0.0, 1.0, 0.0),
+        );
+        camera.set_viewport(32, 32);
+        camera
+    }
+
+    #[test]
+    fn test_cull_polygon() {
+        // Triangles with corners in pixels of an 8 by 8 buffer, in clip space with w of 1
+        let p = |x: f32, y: f32| Vec4f::from_parts(x / 4.0 - 1.0, 1.0 - y / 4.0, 0.0, 1.0);
+        let mut stats = PipelineStats::default();
+        let mut cull = |corners| cull_polygon(8, 8, &Rasterizer::clip_triangle(corners), &mut stats);
+        assert!(!cull([p(1.0, 1.0), p(6.0, 1.0), p(1.0, 6.0)]));
+        // Behind the camera
+        assert!(cull([p(1.0, 1.0), p(6.0, 1.0), p(1.0, 6.0)].map(|corner| -corner)));
+        assert!(cull([p(9.0, 1.0), p(12.0, 1.0), p(9.0, 6.0)]));
+        // Between the centers of neighbouring pixels, and a sliver along a row of them
+        assert!(cull([p(2.6, 2.6), p(3.4, 2.6), p(2.6, 3.4)]));
+        assert!(cull([p(1.0, 2.5), p(6.0, 2.5), p(3.0, 2.5)]));
+        assert!(!cull([p(2.4, 2.4), p(2.8, 2.4), p(2.4, 2.8)]));
+        let expected = PipelineStats {
+            triangles_submitted: 6,
+            triangles_drawn: 2,
+            frustum_culled: 2,
+            small_culled: 2,
+            ..PipelineStats::default()
+        };
+        assert_eq!(stats, expected);
+    }
+
+    #[test]
+    fn test_static_and_moving() {
+        let mesh = cube(1.0);
+        let mut aovs = AovBuffers::new(32, 32, &[Aov::Depth, Aov::Velocity]);
+        let identity = Mat4f::identity();
+        Rasterizer::draw_velocity(&mut aovs, &mesh, identity, &camera(0.0), identity, &camera(0.0));
+        let center = aovs.get(16, 16);
+        assert!(center.depth.is_finite() && center.depth < 5.0);
+        assert_approx_eq!(center.velocity.x, 0.0);
+        assert!(!aovs.get(0, 0).depth.is_finite());
+        // Moving the camera left makes the cube move right on screen
+        let mut aovs = AovBuffers::new(32, 32, &[Aov::Depth, Aov::Velocity]);
+        Rasterizer::draw_velocity(&mut aovs, &mesh, identity, &camera(-0.1), identity, &camera(0.0));
+        let moved = aovs.get(16, 16).velocity;
+        assert!(moved.x > 0.0);
+        assert_approx_eq!(moved.y, 0.0, 1e-3);
+        // Moving the cube the same way cancels out
+        let mut aovs = AovBuffers::new(32, 32, &[Aov::Depth, Aov::Velocity]);
+        let shifted = Mat4f::translation(Vec3f::from_parts(-0.1, 0.0, 0.0));
+        Rasterizer::draw_velocity(&mut aovs, &mesh, shifted, &camera(-0.1), identity, &camera(0.0));
+        assert_approx_eq!(aovs.get(16, 16).velocity.x, 0.0, 1e-3);
+        // Deforming meshes move from their previous triangles
+        let mut deformed = cube(1.0);
+        let previous = mesh.triangles().iter().map(|t| {
+            let shift = |v: Vec3f| v + Vec3f::from_parts(0.0, 0.1, 0.0);
+            Triangle::new(shift(t.a), shift(t.b), shift(t.c))
+        });
+        deformed.set_previous_triangles(previous.collect());
+        let mut aovs = AovBuffers::new(32, 32, &[Aov::Depth, Aov::Velocity]);
+        Rasterizer::draw_velocity(&mut aovs, &deformed, identity, &camera(0.0), identity, &camera(0.0));
+        let moved = aovs.get(16, 16).velocity;
+        assert_approx_eq!(moved.x, 0.0, 1e-3);
+        assert!(moved.y > 0.0);
+    }
+}
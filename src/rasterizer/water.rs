@@ -0,0 +1,114 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+use super::Rasterizer;
+use crate::canvas::{Aov, AovBuffers, Canvas, Color};
+use crate::model::Water;
+use crate::shader::Camera;
+
+impl Rasterizer {
+    /// Shade `water` over `target`, which holds the scene rendered without it.
+    ///
+    /// `scene` needs the depth AOV of the scene and `surface` the depth AOV of the water
+    /// mesh drawn alone, both from `camera` and the size of `target`. Light coming up
+    /// through the water is absorbed by the distance between the surface and the scene
+    /// behind it, fading into the scatter color where nothing is behind it, and mixed with
+    /// the reflection rendered from `water.reflector().mirror_camera(camera)` by the
+    /// Fresnel term. Normals are taken from the waves at `time` seconds, so the surface
+    /// keeps moving between mesh rebuilds.
+    pub fn composite_water(
+        target: &mut Canvas,
+        scene: &AovBuffers,
+        surface: &AovBuffers,
+        camera: &Camera,
+        water: &Water,
+        time: f32,
+        reflection: &Canvas,
+    ) {
+        crate::profile_scope!("water");
+        assert!(scene.is_enabled(Aov::Depth) && surface.is_enabled(Aov::Depth));
+        assert_eq!((target.width(), target.height()), (scene.width(), scene.height()));
+        assert_eq!((target.width(), target.height()), (surface.width(), surface.height()));
+        let (width, height) = (target.width(), target.height());
+        let reflector = water.reflector();
+        for y in 0..height {
+            for x in 0..width {
+                let depth = surface.get(x, y).depth;
+                let behind = scene.get(x, y).depth;
+                if !depth.is_finite() || depth >= behind {
+                    continue;
+                }
+                let view = camera.ray_direction(x, y, width, height);
+                let position = camera.position + view * depth;
+                let normal = water.normal(position.x, position.z, time);
+                let transmittance = water.transmittance(behind - depth);
+                let below = target.get(x, y) * transmittance + water.scatter * (Color::WHITE - transmittance);
+                // Look the reflection up on the mirror, nudged along the slope of the waves
+                let mut mirror = position;
+                mirror.x += normal.x * water.distortion;
+                mirror.y = water.level;
+                mirror.z += normal.z * water.distortion;
+                let reflected = reflector.sample(reflection, camera, mirror).unwrap_or(below);
+                let fresnel = water.fresnel(-view.dot(normal));
+                target.set(x, y, below * (1.0 - fresnel) + reflected * fresnel);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::{Aov, AovBuffers, AovSample, Canvas, Color};
+    use crate::math::Vec3f;
+    use crate::model::Water;
+    use crate::shader::Camera;
+    use crate::Rasterizer;
+
+    #[test]
+    fn test_composite_water() {
+        // Looking straight down at calm water, shallow on the left and bottomless on the right
+        let mut camera = Camera::new(Vec3f::new(0.0, 5.0, 0.0), -Vec3f::UNIT_Y, -Vec3f::UNIT_Z);
+        camera.set_viewport(32, 32);
+        let mut water = Water::new(100.0, 1);
+        water.waves.clear();
+        let mut scene = AovBuffers::new(32, 32, &[Aov::Depth]);
+        let mut surface = AovBuffers::new(32, 32, &[Aov::Depth]);
+        for y in 0..32 {
+            for x in 0..32 {
+                let distance = 5.0 / -camera.ray_direction(x, y, 32, 32).y;
+                let mut sample = AovSample { depth: distance, ..AovSample::default() };
+                if y > 0 {
+                    surface.set(x, y, &sample);
+                }
+                sample.depth = if x < 16 { distance * 1.2 } else { f32::INFINITY };
+                scene.set(x, y, &sample);
+            }
+        }
+        let mut target = Canvas::new(32, 32);
+        target.fill(Color::WHITE);
+        let mut reflection = Canvas::new(32, 32);
+        reflection.fill(Color::WHITE);
+        Rasterizer::composite_water(&mut target, &scene, &surface, &camera, &water, 0.0, &reflection);
+        let (shallow, deep) = (target.get(8, 16), target.get(24, 16));
+        // Red is absorbed first, deep water is the scatter color plus a faint reflection
+        assert!(shallow.r.value() < shallow.b.value());
+        assert!(shallow.r.value() > deep.r.value());
+        assert!((deep.b.value() - (0.08 * 0.98 + 0.02)).abs() < 0.01);
+        // No water on the top row
+        assert_eq!(target.get(8, 0), Color::WHITE);
+    }
+}
@@ -0,0 +1,118 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+use super::Rasterizer;
+use crate::canvas::PixelBuffer;
+use crate::math::{Mat4f, Vec2f, Vec4f};
+use crate::model::Mesh;
+
+/// Smallest clip space `w` a line endpoint may have before it is cut at the camera.
+const NEAR_W: f32 = 1e-5;
+
+impl Rasterizer {
+    /// Draw the three edges of every triangle of `mesh` projected through the
+    /// model-view-projection matrix `mvp`.
+    ///
+    /// Edges are cut where they pass behind the camera and clipped to the buffer. Edges
+    /// shared by two triangles are drawn twice, which only matters with blending.
+    ///
+    /// ```
+    /// use softrender::canvas::{Canvas, Color};
+    /// use softrender::math::{Mat4f, Vec3f};
+    /// use softrender::model::cube;
+    /// use softrender::Rasterizer;
+    ///
+    /// let mut canvas = Canvas::new(64, 64);
+    /// let view = Mat4f::look_at_rh(Vec3f::new(2.0, 2.0, 3.0), Vec3f::ZERO, Vec3f::UNIT_Y);
+    /// let mvp = view * Mat4f::perspective_rh_gl(60.0, 1.0, 0.1, 100.0);
+    /// Rasterizer::draw_mesh_wireframe(&mut canvas, &cube(1.0), &mvp, Color::white());
+    /// assert!(canvas.pixels().any(|(_, _, color)| color == Color::white()));
+    /// ```
+    pub fn draw_mesh_wireframe<B: PixelBuffer>(buffer: &mut B, mesh: &Mesh, mvp: &Mat4f, value: B::Pixel) {
+        for triangle in mesh.triangles() {
+            let a = triangle.a.extend(1.0).transform_homogeneous(*mvp);
+            let b = triangle.b.extend(1.0).transform_homogeneous(*mvp);
+            let c = triangle.c.extend(1.0).transform_homogeneous(*mvp);
+            Rasterizer::draw_line_clip(buffer, a, b, value);
+            Rasterizer::draw_line_clip(buffer, b, c, value);
+            Rasterizer::draw_line_clip(buffer, c, a, value);
+        }
+    }
+
+    /// Draw line between the clip space positions `from` and `to`, cut where it passes
+    /// behind the camera and clipped to the buffer
+    pub(super) fn draw_line_clip<B: PixelBuffer>(buffer: &mut B, from: Vec4f, to: Vec4f, value: B::Pixel) {
+        let (mut a, mut b) = (from, to);
+        if a.w < NEAR_W && b.w < NEAR_W {
+            return;
+        }
+        if a.w < NEAR_W {
+            a = a + (b - a) * ((NEAR_W - a.w) / (b.w - a.w));
+        } else if b.w < NEAR_W {
+            b = b + (a - b) * ((NEAR_W - b.w) / (a.w - b.w));
+        }
+        let (width, height) = (buffer.width() as f32, buffer.height() as f32);
+        let to_screen = |clip: Vec4f| {
+            Vec2f::from_parts(
+                (clip.x / clip.w + 1.0) * 0.5 * width,
+                (1.0 - clip.y / clip.w) * 0.5 * height,
+            )
+        };
+        Rasterizer::draw_line_f(buffer, to_screen(a), to_screen(b), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::{Canvas, Color};
+    use crate::math::{Mat4f, Vec3f};
+    use crate::model::{Mesh, Triangle};
+    use crate::Rasterizer;
+
+    /// Square one unit across at depth `z`, seen by a camera at z = 1 with a 90 degree
+    /// view
+    fn square(z: f32) -> Mesh {
+        let corner = |x: f32, y: f32| Vec3f::new(x, y, z);
+        Mesh::new(vec![
+            Triangle::new(corner(-0.5, -0.5), corner(0.5, -0.5), corner(0.5, 0.5)),
+            Triangle::new(corner(0.5, 0.5), corner(-0.5, 0.5), corner(-0.5, -0.5)),
+        ])
+    }
+
+    fn mvp() -> Mat4f {
+        Mat4f::look_at_rh(Vec3f::new(0.0, 0.0, 1.0), Vec3f::ZERO, Vec3f::UNIT_Y)
+            * Mat4f::perspective_rh_gl(90.0, 1.0, 0.1, 100.0)
+    }
+
+    #[test]
+    fn test_wireframe() {
+        let mut canvas = Canvas::new(32, 32);
+        Rasterizer::draw_mesh_wireframe(&mut canvas, &square(0.0), &mvp(), Color::white());
+        let white = |x: usize, y: usize| canvas.get(x, y) == Color::white();
+        // The square covers the middle half of the view, with its diagonal drawn through
+        assert!(white(8, 16) && white(24, 16) && white(16, 8) && white(16, 24));
+        assert!(white(16, 16));
+        assert!(!white(12, 12) && !white(20, 20) && !white(4, 4) && !white(28, 28));
+    }
+
+    #[test]
+    fn test_wireframe_behind_camera() {
+        let mut canvas = Canvas::new(32, 32);
+        Rasterizer::draw_mesh_wireframe(&mut canvas, &square(2.0), &mvp(), Color::white());
+        assert!(canvas.pixels().all(|(_, _, color)| color == Color::black()));
+    }
+}
@@ -0,0 +1,409 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+use crate::canvas::{Canvas, Color};
+use crate::{Error, Result};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// File signature of a checkpoint
+const MAGIC: &[u8; 8] = b"SRCHKPT\0";
+/// Checkpoint format version, bumped whenever the layout changes
+const VERSION: u32 = 2;
+
+///
+/// Running sum of the samples taken for every Pixel of a progressive render.
+///
+/// Sums are kept in `f64` so that thousands of samples per Pixel do not lose the small
+/// contributions of late samples. The squares of the samples' luminance are summed too,
+/// giving the variance used to tell fireflies from bright details.
+///
+/// ```
+/// use softrender::canvas::Color;
+/// use softrender::Accumulator;
+///
+/// let mut accumulator = Accumulator::new(2, 2);
+/// accumulator.add_sample(1, 0, Color::new(1.0, 0.0, 0.0));
+/// accumulator.add_sample(1, 0, Color::new(0.0, 0.0, 1.0));
+/// assert_eq!(accumulator.samples(1, 0), 2);
+/// assert_eq!(accumulator.resolve().get(1, 0), Color::new(0.5, 0.0, 0.5));
+/// ```
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Accumulator {
+    dimensions: (usize, usize),
+    sums: Vec<[f64; 3]>,
+    /// Sums of the squared luminance of the samples
+    squares: Vec<f64>,
+    samples: Vec<u32>,
+    passes: u32,
+}
+
+impl Accumulator {
+    /// Create new empty Accumulator
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            dimensions: (width, height),
+            sums: vec![[0.0; 3]; width * height],
+            squares: vec![0.0; width * height],
+            samples: vec![0; width * height],
+            passes: 0,
+        }
+    }
+    /// Get Width of the Accumulator
+    pub fn width(&self) -> usize {
+        self.dimensions.0
+    }
+    /// Get Height of the Accumulator
+    pub fn height(&self) -> usize {
+        self.dimensions.1
+    }
+    /// Add a sample of Pixel (x, y)
+    pub fn add_sample(&mut self, x: usize, y: usize, sample: Color) {
+        let index = y * self.dimensions.0 + x;
+        let sum = &mut self.sums[index];
+        sum[0] += f64::from(sample.r.value());
+        sum[1] += f64::from(sample.g.value());
+        sum[2] += f64::from(sample.b.value());
+        self.squares[index] += f64::from(sample.luminance()).powi(2);
+        self.samples[index] += 1;
+    }
+    /// Add one sample for every Pixel of `canvas` and count a finished pass.
+    ///
+    /// # Panics
+    /// Panics if `canvas` differs in size from the Accumulator.
+    pub fn add_pass(&mut self, canvas: &Canvas) {
+        assert_eq!((canvas.width(), canvas.height()), self.dimensions, "pass differs in size");
+        for (x, y, color) in canvas.pixels() {
+            self.add_sample(x, y, color);
+        }
+        self.passes += 1;
+    }
+    /// Count a finished pass of samples added with `add_sample`
+    pub fn finish_pass(&mut self) {
+        self.passes += 1;
+    }
+    /// Number of finished passes, the pass a resumed render continues from
+    pub fn passes(&self) -> u32 {
+        self.passes
+    }
+    /// Number of samples taken of Pixel (x, y)
+    pub fn samples(&self, x: usize, y: usize) -> u32 {
+        self.samples[y * self.dimensions.0 + x]
+    }
+    /// Number of samples taken over all Pixels
+    pub fn total_samples(&self) -> u64 {
+        self.samples.iter().map(|&count| u64::from(count)).sum()
+    }
+    /// Mean of the samples of Pixel (x, y), black if it has none
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        let index = y * self.dimensions.0 + x;
+        match self.samples[index] {
+            0 => Color::black(),
+            count => {
+                let [r, g, b] = self.sums[index];
+                let count = f64::from(count);
+                Color::new((r / count) as f32, (g / count) as f32, (b / count) as f32)
+            }
+        }
+    }
+    /// Variance of the luminance of the samples of Pixel (x, y), 0 with fewer than two
+    pub fn variance(&self, x: usize, y: usize) -> f32 {
+        let index = y * self.dimensions.0 + x;
+        let count = self.samples[index];
+        if count < 2 {
+            return 0.0;
+        }
+        let count = f64::from(count);
+        let mean = f64::from(self.get(x, y).luminance());
+        ((self.squares[index] / count - mean * mean) * count / (count - 1.0)).max(0.0) as f32
+    }
+    /// Mean of the samples of every Pixel
+    pub fn resolve(&self) -> Canvas {
+        let mut canvas = Canvas::new(self.width(), self.height());
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                canvas.set(x, y, self.get(x, y));
+            }
+        }
+        canvas
+    }
+    /// Mean of the samples of every Pixel, with fireflies dimmed to their neighbourhood.
+    ///
+    /// A Pixel more than `ratio` times as bright as its brightest neighbour is an outlier
+    /// if its own samples vary enough that it could as well be that dark, which is what a
+    /// few very bright samples among ordinary ones look like. Outliers are scaled down to
+    /// the brightness of that neighbour. Bright details seen by every sample, such as a
+    /// light, vary little and are kept.
+    pub fn resolve_rejecting_outliers(&self, ratio: f32) -> Canvas {
+        let mut canvas = self.resolve();
+        let (width, height) = self.dimensions;
+        for y in 0..height {
+            for x in 0..width {
+                let color = canvas.get(x, y);
+                let luminance = color.luminance();
+                let mut brightest = 0.0f32;
+                for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                    for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                        if (nx, ny) != (x, y) {
+                            brightest = brightest.max(self.get(nx, ny).luminance());
+                        }
+                    }
+                }
+                if luminance <= brightest * ratio {
+                    continue;
+                }
+                // Three standard errors of the mean covers the gap for a likely outlier
+                let error = (self.variance(x, y) / self.samples(x, y) as f32).sqrt();
+                if luminance - brightest <= 3.0 * error {
+                    canvas.set(x, y, color * (brightest / luminance));
+                }
+            }
+        }
+        canvas
+    }
+    /// Forget every sample
+    pub fn clear(&mut self) {
+        self.sums.iter_mut().for_each(|sum| *sum = [0.0; 3]);
+        self.squares.iter_mut().for_each(|square| *square = 0.0);
+        self.samples.iter_mut().for_each(|count| *count = 0);
+        self.passes = 0;
+    }
+    /// Write the sums, sample counts and pass count in the checkpoint format
+    pub fn write_checkpoint<W: Write>(&self, output: &mut W) -> Result<()> {
+        output.write_all(MAGIC)?;
+        output.write_u32::<LittleEndian>(VERSION)?;
+        output.write_u32::<LittleEndian>(self.width() as u32)?;
+        output.write_u32::<LittleEndian>(self.height() as u32)?;
+        output.write_u32::<LittleEndian>(self.passes)?;
+        for ((sum, &square), &count) in self.sums.iter().zip(&self.squares).zip(&self.samples) {
+            for &channel in sum {
+                output.write_f64::<LittleEndian>(channel)?;
+            }
+            output.write_f64::<LittleEndian>(square)?;
+            output.write_u32::<LittleEndian>(count)?;
+        }
+        Ok(())
+    }
+    /// Read an Accumulator written by `write_checkpoint`.
+    ///
+    /// Checkpoints of version 1 have no squared sums, so their Pixels report no variance.
+    pub fn read_checkpoint<R: Read>(input: &mut R) -> Result<Self> {
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(Error::Decode("not a render checkpoint".to_string()));
+        }
+        let version = input.read_u32::<LittleEndian>()?;
+        if version != 1 && version != VERSION {
+            return Err(Error::Decode(format!("unsupported checkpoint version {}", version)));
+        }
+        let width = input.read_u32::<LittleEndian>()? as usize;
+        let height = input.read_u32::<LittleEndian>()? as usize;
+        let mut accumulator = Self::new(width, height);
+        accumulator.passes = input.read_u32::<LittleEndian>()?;
+        let pixels = accumulator.sums.iter_mut().zip(accumulator.squares.iter_mut());
+        for ((sum, square), count) in pixels.zip(accumulator.samples.iter_mut()) {
+            for channel in sum.iter_mut() {
+                *channel = input.read_f64::<LittleEndian>()?;
+            }
+            if version > 1 {
+                *square = input.read_f64::<LittleEndian>()?;
+            }
+            *count = input.read_u32::<LittleEndian>()?;
+        }
+        Ok(accumulator)
+    }
+    /// Save a checkpoint to `path`.
+    ///
+    /// The checkpoint is written next to `path` and renamed over it once complete, so a
+    /// crash while saving leaves the previous checkpoint intact.
+    pub fn save_checkpoint(&self, path: &Path) -> Result<()> {
+        let partial = partial_path(path);
+        {
+            let mut output = BufWriter::new(File::create(&partial)?);
+            self.write_checkpoint(&mut output)?;
+            output.flush()?;
+        }
+        fs::rename(&partial, path)?;
+        Ok(())
+    }
+    /// Load a checkpoint saved by `save_checkpoint`
+    pub fn load_checkpoint(path: &Path) -> Result<Self> {
+        Self::read_checkpoint(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(std::ffi::OsStr::to_os_string).unwrap_or_default();
+    name.push(".partial");
+    path.with_file_name(name)
+}
+
+///
+/// Saves an Accumulator to disk at most once per interval during a long render.
+///
+/// ```no_run
+/// use softrender::canvas::Canvas;
+/// use softrender::{Accumulator, Checkpointer};
+/// use std::time::Duration;
+///
+/// let mut checkpointer = Checkpointer::new("render.ckpt", Duration::from_secs(300));
+/// let mut accumulator = checkpointer.resume_or(|| Accumulator::new(640, 480)).unwrap();
+/// for _pass in accumulator.passes()..4096 {
+///     let pass = Canvas::new(640, 480); // trace one sample per Pixel...
+///     accumulator.add_pass(&pass);
+///     checkpointer.tick(&accumulator).unwrap();
+/// }
+/// checkpointer.save(&accumulator).unwrap();
+/// ```
+///
+#[derive(Clone, Debug)]
+pub struct Checkpointer {
+    path: PathBuf,
+    interval: Duration,
+    last: Instant,
+}
+
+impl Checkpointer {
+    /// Create new Checkpointer saving to `path` every `interval`
+    pub fn new<P: Into<PathBuf>>(path: P, interval: Duration) -> Self {
+        Self {
+            path: path.into(),
+            interval,
+            last: Instant::now(),
+        }
+    }
+    /// Path checkpoints are saved to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// Load the checkpoint if one exists, otherwise start over with `start`
+    pub fn resume_or<F: FnOnce() -> Accumulator>(&self, start: F) -> Result<Accumulator> {
+        if self.path.exists() {
+            let accumulator = Accumulator::load_checkpoint(&self.path)?;
+            tracing::info!("resuming render from pass {} of {}", accumulator.passes(), self.path.display());
+            Ok(accumulator)
+        } else {
+            Ok(start())
+        }
+    }
+    /// Save `accumulator` if the interval has passed since the last save, returns true if
+    /// it saved
+    pub fn tick(&mut self, accumulator: &Accumulator) -> Result<bool> {
+        if self.last.elapsed() < self.interval {
+            return Ok(false);
+        }
+        self.save(accumulator)?;
+        Ok(true)
+    }
+    /// Save `accumulator` now
+    pub fn save(&mut self, accumulator: &Accumulator) -> Result<()> {
+        accumulator.save_checkpoint(&self.path)?;
+        self.last = Instant::now();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Accumulator, Checkpointer};
+    use crate::canvas::{Canvas, Color};
+    use crate::Error;
+    use std::time::Duration;
+
+    #[test]
+    fn test_accumulate() {
+        let mut accumulator = Accumulator::new(3, 2);
+        let mut pass = Canvas::new(3, 2);
+        pass.fill(Color::new(0.5, 1.0, 2.0));
+        accumulator.add_pass(&pass);
+        pass.fill(Color::new(1.5, 0.0, 0.0));
+        accumulator.add_pass(&pass);
+        accumulator.add_sample(2, 1, Color::new(4.0, 1.0, 5.0));
+        assert_eq!(accumulator.passes(), 2);
+        assert_eq!(accumulator.samples(2, 1), 3);
+        assert_eq!(accumulator.total_samples(), 13);
+        assert_eq!(accumulator.get(0, 0), Color::new(1.0, 0.5, 1.0));
+        assert_eq!(accumulator.resolve().get(2, 1), Color::new(2.0, 2.0 / 3.0, 7.0 / 3.0));
+        accumulator.clear();
+        assert_eq!(accumulator.get(0, 0), Color::black());
+        assert_eq!(accumulator.passes(), 0);
+    }
+
+    #[test]
+    fn test_reject_outliers() {
+        let mut accumulator = Accumulator::new(4, 3);
+        for _ in 0..16 {
+            for y in 0..3 {
+                for x in 0..4 {
+                    accumulator.add_sample(x, y, Color::new(1.0, 1.0, 1.0));
+                }
+            }
+        }
+        // One lucky sample in the middle, a steady light in the corner
+        accumulator.add_sample(1, 1, Color::new(200.0, 100.0, 200.0));
+        for _ in 0..17 {
+            accumulator.add_sample(3, 2, Color::new(20.0, 20.0, 20.0));
+        }
+        assert_eq!(accumulator.variance(0, 0), 0.0);
+        assert!(accumulator.variance(1, 1) > 100.0);
+        let plain = accumulator.resolve();
+        let filtered = accumulator.resolve_rejecting_outliers(4.0);
+        assert!(plain.get(1, 1).luminance() > 5.0);
+        assert!((filtered.get(1, 1).luminance() - 1.0).abs() < 1e-3);
+        assert!(filtered.get(1, 1).r.value() > filtered.get(1, 1).g.value());
+        assert_eq!(filtered.get(3, 2), plain.get(3, 2));
+        assert_eq!(filtered.get(0, 0), plain.get(0, 0));
+    }
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let mut accumulator = Accumulator::new(4, 3);
+        accumulator.add_sample(3, 2, Color::new(0.1, 0.2, 0.3));
+        accumulator.add_sample(3, 2, Color::new(7.0, 0.0, 0.0));
+        accumulator.finish_pass();
+        let mut bytes = Vec::new();
+        accumulator.write_checkpoint(&mut bytes).unwrap();
+        assert_eq!(Accumulator::read_checkpoint(&mut bytes.as_slice()).unwrap(), accumulator);
+        bytes[0] = b'X';
+        assert!(matches!(Accumulator::read_checkpoint(&mut bytes.as_slice()), Err(Error::Decode(_))));
+        let truncated = &bytes[..bytes.len() - 1];
+        assert!(Accumulator::read_checkpoint(&mut &truncated[..]).is_err());
+    }
+
+    #[test]
+    fn test_checkpointer() {
+        let path = std::env::temp_dir().join(format!("softrender-checkpoint-{}.ckpt", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut checkpointer = Checkpointer::new(&path, Duration::from_secs(3600));
+        let mut accumulator = checkpointer.resume_or(|| Accumulator::new(2, 2)).unwrap();
+        accumulator.add_sample(0, 1, Color::white());
+        accumulator.finish_pass();
+        assert!(!checkpointer.tick(&accumulator).unwrap());
+        assert!(!path.exists());
+        let mut eager = Checkpointer::new(&path, Duration::from_secs(0));
+        assert!(eager.tick(&accumulator).unwrap());
+        let resumed = checkpointer.resume_or(|| Accumulator::new(2, 2)).unwrap();
+        assert_eq!(resumed, accumulator);
+        assert_eq!(resumed.passes(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
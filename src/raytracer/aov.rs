@@ -0,0 +1,75 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+use super::Raytracer;
+use crate::canvas::{AovBuffers, AovSample, Canvas, Color};
+
+impl Raytracer {
+    /// Set each Pixel (x, y) of `canvas` to the color `shade(x, y)` returns, and the same
+    /// Pixel of `aovs`, if given, to the surface sample returned with it.
+    ///
+    /// # Panics
+    /// Panics if `aovs` is not the size of `canvas`.
+    pub fn render_with_aovs<S>(canvas: &mut Canvas, mut aovs: Option<&mut AovBuffers>, shade: S)
+    where
+        S: Fn(usize, usize) -> (Color, AovSample),
+    {
+        let (width, height) = (canvas.width(), canvas.height());
+        if let Some(aovs) = &aovs {
+            assert!(aovs.width() == width && aovs.height() == height, "AOV buffers must match the canvas");
+        }
+        for y in 0..height {
+            for x in 0..width {
+                let (color, sample) = shade(x, y);
+                canvas.set(x, y, color);
+                if let Some(aovs) = aovs.as_deref_mut() {
+                    aovs.set(x, y, &sample);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Raytracer;
+    use crate::canvas::{Aov, AovBuffers, AovSample, Canvas, Color};
+
+    #[test]
+    fn test_render_with_aovs() {
+        // Left half sees an object at depth 2, the right half sees nothing
+        let shade = |x: usize, _: usize| {
+            if x < 2 {
+                let sample = AovSample { depth: 2.0, object_id: 1, ..AovSample::default() };
+                (Color::white(), sample)
+            } else {
+                (Color::black(), AovSample::default())
+            }
+        };
+        let mut canvas = Canvas::new(4, 2);
+        let mut aovs = AovBuffers::new(4, 2, &[Aov::Depth, Aov::ObjectId]);
+        Raytracer::render_with_aovs(&mut canvas, Some(&mut aovs), shade);
+        assert_eq!(canvas.get(1, 1), Color::white());
+        assert_eq!((aovs.get(1, 1).depth, aovs.get(1, 1).object_id), (2.0, 1));
+        assert!(aovs.get(3, 0).depth.is_infinite());
+        assert_eq!(aovs.get(3, 0).object_id, 0);
+        // Without buffers only the beauty pass is written
+        let mut canvas = Canvas::new(4, 2);
+        Raytracer::render_with_aovs(&mut canvas, None, shade);
+        assert_eq!(canvas.get(0, 0), Color::white());
+    }
+}
@@ -0,0 +1,469 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Bidirectional path tracing.
+//!
+//! A subpath is grown from the camera and another from a point on an area light, then
+//! every prefix of one is joined with every prefix of the other. Each way of building a
+//! path is weighted with the balance heuristic against all the other ways that could have
+//! built it, so paths are counted once however they were found.
+//!
+//! Subpaths of a single camera vertex are never joined to light subpaths, as that would
+//! splat light onto other pixels than the one being traced.
+
+use super::path::{Bsdf, Hit};
+use super::{PathTracer, PixelSampler, RenderSettings};
+use crate::canvas::Color;
+use crate::math::{cosine_hemisphere, cosine_hemisphere_pdf, uniform_sphere, OrthonormalBasis, Ray, Vec3f};
+use crate::model::{RayKind, Scene};
+use std::f32::consts::PI;
+
+/// What a path vertex lies on
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Kind {
+    Camera,
+    /// Area light at this index in `Scene::area_lights`
+    Light(usize),
+    Surface(Bsdf),
+}
+
+/// Point of a camera or light subpath
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Vertex {
+    kind: Kind,
+    position: Vec3f,
+    /// Unit normal on the side the subpath arrived from, or leaves a light from
+    normal: Vec3f,
+    /// Light, or importance on the camera side, carried from the start of the subpath to
+    /// this vertex, divided by the density of sampling it
+    throughput: Color,
+    /// Density of sampling this vertex walking away from the start of its subpath, per
+    /// unit area
+    pdf_forward: f32,
+    /// Density of sampling this vertex walking from the other end, per unit area
+    pdf_reverse: f32,
+    /// Whether the subpath went on through the glass lobe, which no other vertex can join
+    delta: bool,
+    /// Distance rays leaving this vertex start off it along `normal`
+    bias: f32,
+}
+
+impl Vertex {
+    fn new(kind: Kind, position: Vec3f, normal: Vec3f, throughput: Color) -> Vertex {
+        Vertex {
+            kind,
+            position,
+            normal,
+            throughput,
+            pdf_forward: 0.0,
+            pdf_reverse: 0.0,
+            delta: false,
+            bias: 0.0,
+        }
+    }
+    /// Density of sampling `next` from this vertex having arrived from `previous`, per
+    /// unit area at `next`
+    fn pdf(&self, previous: Option<&Vertex>, next: &Vertex) -> f32 {
+        let direction = (next.position - self.position).normalize();
+        let pdf = match (self.kind, previous) {
+            (Kind::Light(_), _) => cosine_hemisphere_pdf(self.normal.dot(direction)),
+            (Kind::Surface(bsdf), Some(previous)) => {
+                bsdf.pdf((previous.position - self.position).normalize(), direction)
+            }
+            _ => return 0.0,
+        };
+        next.area_density(self.position, pdf)
+    }
+    /// Convert the density `pdf` of the direction from `origin` to this vertex, per
+    /// steradian, to a density per unit area here
+    fn area_density(&self, origin: Vec3f, pdf: f32) -> f32 {
+        let offset = self.position - origin;
+        let distance_squared = offset.dot(offset);
+        if distance_squared <= 0.0 {
+            return 0.0;
+        }
+        let cosine = match self.kind {
+            Kind::Camera => 1.0,
+            _ => self.normal.dot(offset).abs() / distance_squared.sqrt(),
+        };
+        pdf * cosine / distance_squared
+    }
+}
+
+impl PathTracer {
+    /// Light from the area lights arriving along `ray`, whose closest surface is `first`,
+    /// joining a camera subpath with a light subpath in every way allowed by `max_depth`
+    pub(super) fn trace_bidirectional<S: PixelSampler>(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        first: Option<Hit>,
+        sampler: &mut S,
+    ) -> Color {
+        if scene.area_lights.is_empty() {
+            return Color::black();
+        }
+        let max_depth = self.settings.max_depth as usize;
+        let white = Color::new(1.0, 1.0, 1.0);
+        let mut camera_path = vec![Vertex::new(Kind::Camera, ray.origin, ray.direction.normalize(), white)];
+        self.walk(scene, *ray, first, white, 1.0, sampler, max_depth + 2, &mut camera_path);
+        let light_path = self.light_path(scene, sampler, max_depth, ray.time);
+        let mut radiance = Color::black();
+        for t in 2..=camera_path.len() {
+            // Paths bounce off `s + t - 2` surfaces
+            for s in 0..=light_path.len().min(max_depth + 2 - t) {
+                let light = self.connect(scene, &light_path, &camera_path, s, t, sampler);
+                radiance += self.settings.clamp_path((s + t - 2) as u32, light);
+            }
+        }
+        radiance
+    }
+    /// Subpath starting on a random point of a random area light, of at most `max_vertices`,
+    /// at `time` within the shutter
+    fn light_path<S: PixelSampler>(
+        &self,
+        scene: &Scene,
+        sampler: &mut S,
+        max_vertices: usize,
+        time: f32,
+    ) -> Vec<Vertex> {
+        let mut path = Vec::with_capacity(max_vertices);
+        if max_vertices == 0 {
+            return path;
+        }
+        let count = scene.area_lights.len();
+        let pick = ((sampler.next_1d() * count as f32) as usize).min(count - 1);
+        let light = &scene.area_lights[pick];
+        let (u1, u2) = sampler.next_2d();
+        let normal = uniform_sphere(u1, u2);
+        let position = light.position + normal * light.radius;
+        let pdf = origin_pdf(scene, pick);
+        let mut origin = Vertex::new(Kind::Light(pick), position, normal, light.radiance() * (1.0 / pdf));
+        origin.pdf_forward = pdf;
+        origin.bias = self.settings.ray_offset(position, 0.0);
+        path.push(origin);
+        // Lights glow evenly, so leave in a cosine weighted direction
+        let (u1, u2) = sampler.next_2d();
+        let local = cosine_hemisphere(u1, u2);
+        if local.z <= 0.0 {
+            return path;
+        }
+        let direction = OrthonormalBasis::from_normal(normal).to_world(local);
+        let ray = Ray::new(position + normal * origin.bias, direction).with_time(time);
+        let pdf = cosine_hemisphere_pdf(local.z);
+        let first = Hit::find(scene, self.tlas(), &ray, RayKind::Reflection, self.settings.triangle_intersector);
+        self.walk(scene, ray, first, origin.throughput * PI, pdf, sampler, max_vertices, &mut path);
+        path
+    }
+    /// Extend `path` along `ray`, whose closest surface is `first`, which was picked with
+    /// density `pdf` per steradian and carries `throughput`, bouncing until it holds
+    /// `max_vertices` or Russian roulette ends it
+    #[allow(clippy::too_many_arguments)]
+    fn walk<S: PixelSampler>(
+        &self,
+        scene: &Scene,
+        mut ray: Ray,
+        first: Option<Hit>,
+        mut throughput: Color,
+        mut pdf: f32,
+        sampler: &mut S,
+        max_vertices: usize,
+        path: &mut Vec<Vertex>,
+    ) {
+        // Subpaths are cut by their number of vertices, the settings only decide roulette
+        let roulette = RenderSettings {
+            max_depth: u32::MAX,
+            ..self.settings
+        };
+        let from_camera = path[0].kind == Kind::Camera;
+        // Only the first hit of a camera subpath is seen by the camera, and it is passed in
+        let (kind, intersector) = (RayKind::Reflection, self.settings.triangle_intersector);
+        let mut first = Some(first);
+        while path.len() < max_vertices {
+            let hit = first.take().unwrap_or_else(|| Hit::find(scene, self.tlas(), &ray, kind, intersector));
+            let max_t = hit.map_or(f32::INFINITY, |hit| hit.t);
+            let previous = path.len() - 1;
+            if let Some((light, t)) = self.hit_light(scene, &ray, max_t) {
+                // Camera subpaths end on the lights they find, light subpaths are absorbed
+                if from_camera {
+                    let position = ray.at(t);
+                    let normal = (position - scene.area_lights[light].position).normalize();
+                    let mut vertex = Vertex::new(Kind::Light(light), position, normal, throughput);
+                    vertex.pdf_forward = vertex.area_density(path[previous].position, pdf);
+                    path.push(vertex);
+                }
+                return;
+            }
+            let hit = match hit {
+                Some(hit) => hit,
+                None => return,
+            };
+            let bsdf = Bsdf::at(scene, &hit, None);
+            let mut vertex = Vertex::new(Kind::Surface(bsdf), hit.position, hit.normal, throughput);
+            vertex.pdf_forward = vertex.area_density(path[previous].position, pdf);
+            vertex.bias = self.settings.ray_offset(hit.position, hit.distance);
+            path.push(vertex);
+            if path.len() >= max_vertices {
+                return;
+            }
+            let outgoing = -ray.direction.normalize();
+            let choice = sampler.next_1d();
+            let sample = match bsdf.sample(outgoing, choice, sampler.next_2d()) {
+                Some(sample) => sample,
+                None => return,
+            };
+            throughput *= sample.weight;
+            // Neither end of a glass bounce can be sampled any other way
+            path[previous + 1].delta = sample.delta;
+            pdf = if sample.delta { 0.0 } else { sample.pdf };
+            let reverse = if sample.delta { 0.0 } else { bsdf.pdf(sample.direction, outgoing) };
+            path[previous].pdf_reverse = path[previous].area_density(hit.position, reverse);
+            let survival = roulette.survival_probability(previous as u32 + 1, throughput);
+            if sampler.next_1d() >= survival {
+                return;
+            }
+            throughput *= 1.0 / survival;
+            ray = Ray::new(hit.offset(sample.direction, &self.settings), sample.direction).with_time(hit.time);
+        }
+    }
+    /// Weighted light carried by the path made of the first `s` vertices of `light_path`
+    /// and the first `t` vertices of `camera_path`.
+    ///
+    /// With `s` of 1 a fresh point on a light is sampled instead, which finds the lights
+    /// a vertex can see far more often.
+    fn connect<S: PixelSampler>(
+        &self,
+        scene: &Scene,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        s: usize,
+        t: usize,
+        sampler: &mut S,
+    ) -> Color {
+        let end = &camera_path[t - 1];
+        // Light linking is decided by the first surface after the light
+        let linked = match s {
+            0 => linked(scene, end, &camera_path[t - 2]),
+            1 => true,
+            _ => linked(scene, &light_path[0], &light_path[1]),
+        };
+        if !linked {
+            return Color::black();
+        }
+        let mut sampled = None;
+        let contribution = match (s, end.kind) {
+            (0, Kind::Light(light)) => end.throughput * scene.area_lights[light].radiance(),
+            (0, _) | (_, Kind::Camera) | (_, Kind::Light(_)) => return Color::black(),
+            (1, Kind::Surface(bsdf)) => {
+                let outgoing = (camera_path[t - 2].position - end.position).normalize();
+                let origin = end.position + end.normal * end.bias;
+                let count = scene.area_lights.len();
+                let pick = ((sampler.next_1d() * count as f32) as usize).min(count - 1);
+                let light = &scene.area_lights[pick];
+                let (u1, u2) = sampler.next_2d();
+                let (direction, distance) = match light.sample(origin, u1, u2) {
+                    Some(sample) if light.links_to(bsdf.links) => sample,
+                    _ => return Color::black(),
+                };
+                let pdf = light.pdf(origin) / count as f32;
+                let cosine = direction.dot(end.normal);
+                if pdf <= 0.0 || cosine <= 0.0 || !self.visible(scene, origin, direction, distance, bsdf.time) {
+                    return Color::black();
+                }
+                let position = origin + direction * distance;
+                let normal = (position - light.position).normalize();
+                let mut vertex = Vertex::new(Kind::Light(pick), position, normal, light.radiance() * (1.0 / pdf));
+                // Weighted as if picked the way light subpaths start
+                vertex.pdf_forward = origin_pdf(scene, pick);
+                sampled = Some(vertex);
+                end.throughput * bsdf.eval(outgoing, direction) * light.radiance() * (cosine / pdf)
+            }
+            (_, Kind::Surface(bsdf)) => {
+                let start = &light_path[s - 1];
+                let light_bsdf = match start.kind {
+                    Kind::Surface(light_bsdf) => light_bsdf,
+                    _ => return Color::black(),
+                };
+                let offset = start.position - end.position;
+                let distance = offset.magnitude();
+                let direction = offset * (1.0 / distance);
+                let outgoing = (camera_path[t - 2].position - end.position).normalize();
+                let light_outgoing = (light_path[s - 2].position - start.position).normalize();
+                let cosines = direction.dot(end.normal).abs() * direction.dot(start.normal).abs();
+                let geometry = cosines / (distance * distance);
+                let reflected = bsdf.eval(outgoing, direction) * light_bsdf.eval(light_outgoing, -direction);
+                if geometry <= 0.0 || reflected == Color::black() {
+                    return Color::black();
+                }
+                let origin = end.position + end.normal * end.bias;
+                let target = start.position + start.normal * start.bias;
+                let span = target - origin;
+                if !self.visible(scene, origin, span.normalize(), span.magnitude(), bsdf.time) {
+                    return Color::black();
+                }
+                start.throughput * reflected * end.throughput * geometry
+            }
+        };
+        if contribution == Color::black() {
+            return contribution;
+        }
+        contribution * mis_weight(scene, light_path, camera_path, sampled, s, t)
+    }
+}
+
+/// Check if the area light at `light` shines on the surface at `surface`, anything else
+/// counts as linked
+fn linked(scene: &Scene, light: &Vertex, surface: &Vertex) -> bool {
+    match (light.kind, surface.kind) {
+        (Kind::Light(light), Kind::Surface(bsdf)) => scene.area_lights[light].links_to(bsdf.links),
+        _ => true,
+    }
+}
+
+/// Density of a light subpath starting at a point of area light `light`, per unit area
+fn origin_pdf(scene: &Scene, light: usize) -> f32 {
+    let radius = scene.area_lights[light].radius;
+    1.0 / (scene.area_lights.len() as f32 * 4.0 * PI * radius * radius)
+}
+
+/// Balance heuristic weight of the path joining the first `s` vertices of `light_path`,
+/// or `sampled` for `s` of 1, to the first `t` of `camera_path`
+fn mis_weight(
+    scene: &Scene,
+    light_path: &[Vertex],
+    camera_path: &[Vertex],
+    sampled: Option<Vertex>,
+    s: usize,
+    t: usize,
+) -> f32 {
+    if s + t == 2 {
+        return 1.0;
+    }
+    let mut light = light_path[..s].to_vec();
+    if let Some(sampled) = sampled {
+        light[0] = sampled;
+    }
+    let mut camera = camera_path[..t].to_vec();
+    // Densities of walking through the joint the other way
+    let end = camera[t - 1];
+    camera[t - 1].pdf_reverse = match (s, end.kind) {
+        (0, Kind::Light(index)) => origin_pdf(scene, index),
+        (0, _) => 0.0,
+        (1, _) => light[0].pdf(None, &end),
+        _ => light[s - 1].pdf(Some(&light[s - 2]), &end),
+    };
+    camera[t - 2].pdf_reverse = end.pdf(light.last(), &camera[t - 2]);
+    if s > 0 {
+        light[s - 1].pdf_reverse = end.pdf(Some(&camera[t - 2]), &light[s - 1]);
+    }
+    if s > 1 {
+        light[s - 2].pdf_reverse = light[s - 1].pdf(Some(&end), &light[s - 2]);
+    }
+    // The joined vertices were reached by a connection, not by their glass lobes
+    camera[t - 1].delta = false;
+    if s > 0 {
+        light[s - 1].delta = false;
+    }
+    // Sum the density of every other way of building the path relative to this one
+    let ratio = |vertex: &Vertex| {
+        let remap = |pdf: f32| if pdf == 0.0 { 1.0 } else { pdf };
+        remap(vertex.pdf_reverse) / remap(vertex.pdf_forward)
+    };
+    let mut sum = 0.0;
+    let mut running = 1.0;
+    // The camera subpath always keeps the camera and its first hit
+    for index in (2..t).rev() {
+        running *= ratio(&camera[index]);
+        if !camera[index].delta && !camera[index - 1].delta {
+            sum += running;
+        }
+    }
+    running = 1.0;
+    for index in (0..s).rev() {
+        running *= ratio(&light[index]);
+        if !light[index].delta && (index == 0 || !light[index - 1].delta) {
+            sum += running;
+        }
+    }
+    1.0 / (1.0 + sum)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::Color;
+    use crate::math::{Mat4f, Ray, Vec3f};
+    use crate::model::{Material, Mesh, Scene, SphereLight, Triangle};
+    use crate::raytracer::{Integrator, PathTracer, PixelSampler, RandomSampler};
+
+    /// Grey box open to the front with a small light near the top
+    fn room() -> Scene {
+        let p = |x: f32, y: f32, z: f32| Vec3f::new(x, y, z);
+        let quad = |a: Vec3f, b: Vec3f, c: Vec3f, d: Vec3f| vec![Triangle::new(a, b, c), Triangle::new(a, c, d)];
+        let mut triangles = quad(p(-1.0, 0.0, 1.0), p(1.0, 0.0, 1.0), p(1.0, 0.0, -1.0), p(-1.0, 0.0, -1.0));
+        triangles.extend(quad(p(-1.0, 2.0, -1.0), p(1.0, 2.0, -1.0), p(1.0, 2.0, 1.0), p(-1.0, 2.0, 1.0)));
+        triangles.extend(quad(p(-1.0, 0.0, -1.0), p(1.0, 0.0, -1.0), p(1.0, 2.0, -1.0), p(-1.0, 2.0, -1.0)));
+        triangles.extend(quad(p(-1.0, 0.0, 1.0), p(-1.0, 0.0, -1.0), p(-1.0, 2.0, -1.0), p(-1.0, 2.0, 1.0)));
+        triangles.extend(quad(p(1.0, 0.0, -1.0), p(1.0, 0.0, 1.0), p(1.0, 2.0, 1.0), p(1.0, 2.0, -1.0)));
+        let mut scene = Scene::new();
+        let mesh = scene.add_mesh(Mesh::new(triangles));
+        let mut material = Material::new();
+        material.surface = Color::new(0.7, 0.7, 0.7);
+        material.reflectivity = 0.3;
+        material.roughness = 0.4;
+        let material = scene.add_material(material);
+        scene.add_instance(mesh, material, Mat4f::identity());
+        scene.add_area_light(SphereLight::new(Vec3f::new(0.3, 1.7, -0.3), 0.1, 50.0));
+        scene
+    }
+
+    fn mean(scene: &Scene, integrator: Integrator, ray: &Ray, count: u32) -> f32 {
+        let mut tracer = PathTracer::new(scene);
+        tracer.integrator = integrator;
+        tracer.settings.max_depth = 4;
+        let mut sampler = RandomSampler::new(3);
+        let total: f32 = (0..count)
+            .map(|index| {
+                sampler.start(0, 0, index);
+                tracer.trace(scene, ray, &mut sampler).luminance()
+            })
+            .sum();
+        total / count as f32
+    }
+
+    #[test]
+    fn test_matches_path_tracing() {
+        let scene = room();
+        let rays = [
+            Ray::new(Vec3f::new(0.0, 1.0, 3.0), Vec3f::new(0.0, -0.4, -1.0)),
+            Ray::new(Vec3f::new(0.0, 1.0, 3.0), Vec3f::new(-0.3, 0.2, -1.0)),
+        ];
+        for ray in &rays {
+            let path = mean(&scene, Integrator::Path, ray, 20_000);
+            let bidirectional = mean(&scene, Integrator::Bidirectional, ray, 20_000);
+            assert!(path > 0.0);
+            assert!((bidirectional - path).abs() < 0.05 * path, "{} {}", bidirectional, path);
+        }
+    }
+
+    #[test]
+    fn test_sees_light() {
+        let scene = room();
+        let mut tracer = PathTracer::new(&scene);
+        tracer.integrator = Integrator::Bidirectional;
+        let ray = Ray::new(Vec3f::new(0.3, 1.7, 3.0), Vec3f::new(0.0, 0.0, -1.0));
+        assert_eq!(tracer.trace(&scene, &ray, &mut RandomSampler::new(0)), Color::new(50.0, 50.0, 50.0));
+    }
+}
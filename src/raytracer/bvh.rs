@@ -0,0 +1,510 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::math::{Aabb, Ray};
+use crate::model::{Mesh, Triangle};
+
+/// How a `Bvh` chooses where to split its nodes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SplitMethod {
+    /// Halve the primitives along the longest axis of their centers, fast to build
+    Median,
+    /// Pick the cheapest of `bins` planes per axis by the surface area heuristic, slower
+    /// to build but faster to trace
+    BinnedSah { bins: usize },
+}
+
+/// How a `Bvh` is built and what its `sah_cost` assumes
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BvhOptions {
+    pub split: SplitMethod,
+    /// Most primitives kept in a leaf before it is split
+    pub leaf_size: usize,
+    /// Cost of visiting a node
+    pub traversal_cost: f32,
+    /// Cost of intersecting a primitive
+    pub intersection_cost: f32,
+}
+
+impl Default for BvhOptions {
+    fn default() -> Self {
+        Self {
+            split: SplitMethod::BinnedSah { bins: 12 },
+            leaf_size: 4,
+            traversal_cost: 1.0,
+            intersection_cost: 1.0,
+        }
+    }
+}
+
+/// Shape of a built `Bvh`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BvhStats {
+    pub nodes: usize,
+    pub leaves: usize,
+    pub primitives: usize,
+    /// Most nodes from the root to a leaf, counting both
+    pub depth: usize,
+    pub max_leaf_size: usize,
+    /// Average primitives per leaf
+    pub average_leaf_size: f32,
+    pub sah_cost: f32,
+}
+
+/// Node of a `Bvh`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BvhNode {
+    pub bounds: Aabb,
+    /// Index of the first child for interior nodes, whose second child follows it, or of
+    /// the first entry of `Bvh::primitives` for leaves
+    pub start: usize,
+    /// Number of primitives of a leaf, zero for interior nodes
+    pub count: usize,
+}
+
+impl BvhNode {
+    /// Check if this node holds primitives rather than children
+    pub fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+///
+/// Bounding volume hierarchy over primitives given by their bounds.
+///
+/// Used on its own over the triangles of a mesh, or over the instances of a scene as
+/// the top level of a `Tlas`.
+///
+#[derive(Clone, Debug, Default)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    primitives: Vec<usize>,
+    options: BvhOptions,
+    built_cost: f32,
+}
+
+impl Bvh {
+    /// Build a Bvh over primitives with `bounds` using default `BvhOptions`
+    pub fn new(bounds: &[Aabb]) -> Bvh {
+        Bvh::with_options(bounds, BvhOptions::default())
+    }
+    /// Build a Bvh over primitives with `bounds`
+    pub fn with_options(bounds: &[Aabb], options: BvhOptions) -> Bvh {
+        crate::profile_scope!("bvh_build");
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * bounds.len()),
+            primitives: (0..bounds.len()).collect(),
+            options,
+            built_cost: 0.0,
+        };
+        if !bounds.is_empty() {
+            bvh.nodes.push(BvhNode {
+                bounds: Aabb::empty(),
+                start: 0,
+                count: bounds.len(),
+            });
+            bvh.split(0, bounds);
+        }
+        bvh.built_cost = bvh.sah_cost();
+        bvh
+    }
+    /// Build a Bvh over the triangles of `mesh`, bounding them at both their previous and
+    /// current positions if the mesh deforms
+    pub fn from_mesh(mesh: &Mesh) -> Bvh {
+        Bvh::new(&mesh_bounds(mesh))
+    }
+    /// Options this Bvh was built with
+    pub fn options(&self) -> BvhOptions {
+        self.options
+    }
+    /// Count the nodes and measure the depth and cost of this Bvh
+    pub fn stats(&self) -> BvhStats {
+        let mut depth = 0;
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push((0, 1));
+        }
+        while let Some((index, level)) = stack.pop() {
+            let node = self.nodes[index];
+            depth = depth.max(level);
+            if !node.is_leaf() {
+                stack.push((node.start, level + 1));
+                stack.push((node.start + 1, level + 1));
+            }
+        }
+        let leaves = self.nodes.iter().filter(|node| node.is_leaf());
+        let (count, largest) = leaves.fold((0, 0), |(count, largest), node| (count + 1, largest.max(node.count)));
+        BvhStats {
+            nodes: self.nodes.len(),
+            leaves: count,
+            primitives: self.primitives.len(),
+            depth,
+            max_leaf_size: largest,
+            average_leaf_size: if count > 0 { self.primitives.len() as f32 / count as f32 } else { 0.0 },
+            sah_cost: self.sah_cost(),
+        }
+    }
+    /// Nodes, the root first and children always after their parent
+    pub fn nodes(&self) -> &[BvhNode] {
+        &self.nodes
+    }
+    /// Primitive indices, in the order leaves refer to them
+    pub fn primitives(&self) -> &[usize] {
+        &self.primitives
+    }
+    /// Bounds of every primitive
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |root| root.bounds)
+    }
+    /// Recompute node bounds from new primitive `bounds`, keeping the tree as built.
+    ///
+    /// Cheap compared to a rebuild, but the tree gets slower to traverse the further the
+    /// primitives move from where they were when it was built.
+    pub fn refit_bounds(&mut self, bounds: &[Aabb]) {
+        // Children come after their parent, so walking backwards sees them first
+        for index in (0..self.nodes.len()).rev() {
+            let node = self.nodes[index];
+            self.nodes[index].bounds = if node.is_leaf() {
+                self.primitives[node.start..node.start + node.count]
+                    .iter()
+                    .fold(Aabb::empty(), |aabb, &primitive| aabb.union(&bounds[primitive]))
+            } else {
+                self.nodes[node.start].bounds.union(&self.nodes[node.start + 1].bounds)
+            };
+        }
+    }
+    /// Recompute node bounds from the moved `triangles` of the mesh this Bvh was built
+    /// from, such as after skinning or morphing.
+    ///
+    /// # Panics
+    /// Panics if there are fewer triangles than the Bvh was built over.
+    pub fn refit(&mut self, triangles: &[Triangle]) {
+        assert!(triangles.len() >= self.primitives.len(), "refit with fewer triangles than built");
+        self.refit_bounds(&triangle_bounds(triangles));
+    }
+    /// Expected cost of tracing a ray through this Bvh by the surface area heuristic,
+    /// using the costs of its `BvhOptions`. A ray hitting the root visits each node with
+    /// a probability of its surface area over that of the root.
+    pub fn sah_cost(&self) -> f32 {
+        let BvhOptions {
+            traversal_cost,
+            intersection_cost,
+            ..
+        } = self.options;
+        let root = self.bounds().surface_area();
+        if root <= 0.0 {
+            return self.primitives.len() as f32 * intersection_cost;
+        }
+        self.nodes
+            .iter()
+            .map(|node| {
+                let cost = if node.is_leaf() { node.count as f32 * intersection_cost } else { traversal_cost };
+                cost * node.bounds.surface_area() / root
+            })
+            .sum()
+    }
+    /// How much slower than when built this Bvh is expected to be after refitting, as
+    /// the ratio of its current to its original `sah_cost`
+    pub fn degradation(&self) -> f32 {
+        if self.built_cost > 0.0 {
+            self.sah_cost() / self.built_cost
+        } else {
+            1.0
+        }
+    }
+    /// Refit to moved `triangles`, rebuilding instead when that leaves the Bvh more than
+    /// `threshold` times as costly as a fresh build, such as 1.5. Returns true if it was
+    /// rebuilt.
+    pub fn update(&mut self, triangles: &[Triangle], threshold: f32) -> bool {
+        self.update_bounds(&triangle_bounds(triangles), threshold)
+    }
+    /// Refit to moved primitive `bounds` like `update`
+    pub fn update_bounds(&mut self, bounds: &[Aabb], threshold: f32) -> bool {
+        if bounds.len() != self.primitives.len() {
+            *self = Bvh::with_options(bounds, self.options);
+            return true;
+        }
+        self.refit_bounds(bounds);
+        if self.degradation() > threshold {
+            *self = Bvh::with_options(bounds, self.options);
+            return true;
+        }
+        false
+    }
+    /// Visit every primitive whose leaf `ray` enters before the closest hit so far.
+    ///
+    /// `hit` is called with a primitive and the distance of the closest hit so far, and
+    /// returns the distance of a closer hit if it finds one. Returns the distance of the
+    /// closest hit found, if any closer than `t_max`.
+    pub fn traverse<F: FnMut(usize, f32) -> Option<f32>>(&self, ray: &Ray, t_max: f32, mut hit: F) -> Option<f32> {
+        let mut closest = t_max;
+        let mut found = false;
+        let mut stack = Vec::with_capacity(64);
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.bounds.intersect(ray, closest).is_none() {
+                continue;
+            }
+            if node.is_leaf() {
+                for &primitive in &self.primitives[node.start..node.start + node.count] {
+                    if let Some(t) = hit(primitive, closest) {
+                        if t < closest {
+                            closest = t;
+                            found = true;
+                        }
+                    }
+                }
+            } else {
+                let near = self.nodes[node.start].bounds.intersect(ray, closest);
+                let far = self.nodes[node.start + 1].bounds.intersect(ray, closest);
+                // Visit the nearer child first so it can cull the other
+                match (near, far) {
+                    (Some(a), Some(b)) if b < a => stack.extend_from_slice(&[node.start, node.start + 1]),
+                    (Some(_), Some(_)) => stack.extend_from_slice(&[node.start + 1, node.start]),
+                    (Some(_), None) => stack.push(node.start),
+                    (None, Some(_)) => stack.push(node.start + 1),
+                    (None, None) => {}
+                }
+            }
+        }
+        if found {
+            Some(closest)
+        } else {
+            None
+        }
+    }
+    fn split(&mut self, index: usize, bounds: &[Aabb]) {
+        let BvhNode { start, count, .. } = self.nodes[index];
+        let primitives = &mut self.primitives[start..start + count];
+        self.nodes[index].bounds = primitives.iter().fold(Aabb::empty(), |aabb, &p| aabb.union(&bounds[p]));
+        if count <= self.options.leaf_size.max(1) {
+            return;
+        }
+        let centers = Aabb::from_points(primitives.iter().map(|&p| bounds[p].center()));
+        let size = centers.size();
+        let longest = if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        };
+        let sah = match self.options.split {
+            SplitMethod::BinnedSah { bins } => best_bin_split(primitives, bounds, &centers, bins.max(2)),
+            SplitMethod::Median => None,
+        };
+        // Centers sharing one spot give no plane to split at, so they are halved
+        let (axis, half) = sah.unwrap_or((longest, count / 2));
+        primitives.sort_by(|&a, &b| {
+            let (a, b) = (bounds[a].center()[axis], bounds[b].center()[axis]);
+            a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let child = self.nodes.len();
+        self.nodes.push(BvhNode {
+            bounds: Aabb::empty(),
+            start,
+            count: half,
+        });
+        self.nodes.push(BvhNode {
+            bounds: Aabb::empty(),
+            start: start + half,
+            count: count - half,
+        });
+        self.nodes[index] = BvhNode {
+            bounds: self.nodes[index].bounds,
+            start: child,
+            count: 0,
+        };
+        self.split(child, bounds);
+        self.split(child + 1, bounds);
+    }
+}
+
+/// Axis and number of primitives on the lower side of the plane between bins with the
+/// least surface area heuristic cost, if any plane has primitives on both sides
+fn best_bin_split(primitives: &[usize], bounds: &[Aabb], centers: &Aabb, bins: usize) -> Option<(usize, usize)> {
+    let mut best: Option<(f32, usize, usize)> = None;
+    for axis in 0..3 {
+        let extent = centers.max[axis] - centers.min[axis];
+        if extent <= 0.0 {
+            continue;
+        }
+        let bin_of = |p: usize| (((bounds[p].center()[axis] - centers.min[axis]) / extent * bins as f32) as usize).min(bins - 1);
+        let mut counts = vec![0usize; bins];
+        let mut boxes = vec![Aabb::empty(); bins];
+        for &p in primitives {
+            let bin = bin_of(p);
+            counts[bin] += 1;
+            boxes[bin] = boxes[bin].union(&bounds[p]);
+        }
+        // Sweep from the right to know the cost of each upper side
+        let mut right_area = vec![0.0; bins];
+        let mut right_box = Aabb::empty();
+        for bin in (1..bins).rev() {
+            right_box = right_box.union(&boxes[bin]);
+            right_area[bin] = right_box.surface_area();
+        }
+        let (mut left_box, mut left_count) = (Aabb::empty(), 0);
+        for bin in 0..bins - 1 {
+            left_box = left_box.union(&boxes[bin]);
+            left_count += counts[bin];
+            let right_count = primitives.len() - left_count;
+            if left_count == 0 || right_count == 0 {
+                continue;
+            }
+            let cost = left_box.surface_area() * left_count as f32 + right_area[bin + 1] * right_count as f32;
+            if best.is_none_or(|(lowest, _, _)| cost < lowest) {
+                best = Some((cost, axis, left_count));
+            }
+        }
+    }
+    best.map(|(_, axis, left)| (axis, left))
+}
+
+/// Bounds of each of `triangles`
+pub(crate) fn triangle_bounds(triangles: &[Triangle]) -> Vec<Aabb> {
+    triangles.iter().map(|t| Aabb::from_points(vec![t.a, t.b, t.c])).collect()
+}
+
+/// Bounds of each triangle of `mesh` over the whole shutter
+pub(crate) fn mesh_bounds(mesh: &Mesh) -> Vec<Aabb> {
+    let mut bounds = triangle_bounds(mesh.triangles());
+    if let Some(previous) = mesh.previous_triangles() {
+        for (bounds, previous) in bounds.iter_mut().zip(triangle_bounds(previous)) {
+            *bounds = bounds.union(&previous);
+        }
+    }
+    bounds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bvh, BvhOptions, SplitMethod};
+    use crate::math::{Ray, Vec3f};
+    use crate::model::{cube, Mesh, Triangle};
+
+    fn grid(size: usize) -> Mesh {
+        let mut triangles = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let (x, y) = (x as f32, y as f32);
+                let corner = |dx: f32, dy: f32| Vec3f::from_parts(x + dx, y + dy, (x * 0.3 + y * 0.7).sin());
+                triangles.push(Triangle::new(corner(0.0, 0.0), corner(1.0, 0.0), corner(1.0, 1.0)));
+                triangles.push(Triangle::new(corner(1.0, 1.0), corner(0.0, 1.0), corner(0.0, 0.0)));
+            }
+        }
+        Mesh::new(triangles)
+    }
+
+    #[test]
+    fn test_build() {
+        let mesh = grid(8);
+        let bvh = Bvh::from_mesh(&mesh);
+        assert_eq!(bvh.bounds(), mesh.bounds());
+        let mut seen = bvh.primitives().to_vec();
+        seen.sort_unstable();
+        assert_eq!(seen, (0..128).collect::<Vec<_>>());
+        for node in bvh.nodes().iter().filter(|node| !node.is_leaf()) {
+            let union = bvh.nodes()[node.start].bounds.union(&bvh.nodes()[node.start + 1].bounds);
+            assert_eq!(union, node.bounds);
+        }
+        let ray = Ray::new(Vec3f::default(), Vec3f::from_parts(1.0, 0.0, 0.0));
+        assert!(Bvh::new(&[]).traverse(&ray, 1.0, |_, _| None).is_none());
+    }
+
+    #[test]
+    fn test_traverse() {
+        let mesh = grid(8);
+        let bvh = Bvh::from_mesh(&mesh);
+        for index in 0..64 {
+            let origin = Vec3f::from_parts(0.1 + index as f32 * 0.123, 0.2 + index as f32 * 0.117, 5.0);
+            let ray = Ray::new(origin, Vec3f::from_parts(0.01, -0.02, -1.0));
+            let hits = mesh.triangles().iter().filter_map(|t| t.intersect(&ray)).map(|hit| hit.t);
+            let brute = hits.fold(None, |best: Option<f32>, t| Some(best.map_or(t, |best| best.min(t))));
+            let intersect = |primitive: usize, _| mesh.triangles()[primitive].intersect(&ray).map(|hit| hit.t);
+            let found = bvh.traverse(&ray, f32::INFINITY, intersect);
+            assert_eq!(found, brute);
+        }
+    }
+
+    #[test]
+    fn test_refit() {
+        let original = cube(2.0);
+        let mut bvh = Bvh::from_mesh(&original);
+        let offset = Vec3f::from_parts(3.0, 0.0, 0.0);
+        let moved = original.triangles().iter().map(|t| Triangle::new(t.a + offset, t.b + offset, t.c + offset));
+        let mesh = Mesh::new(moved.collect());
+        bvh.refit_bounds(&super::triangle_bounds(mesh.triangles()));
+        assert_eq!(bvh.bounds(), mesh.bounds());
+        let ray = Ray::new(Vec3f::from_parts(3.0, 0.0, 5.0), Vec3f::from_parts(0.0, 0.0, -1.0));
+        let intersect = |primitive: usize, _| mesh.triangles()[primitive].intersect(&ray).map(|hit| hit.t);
+        assert_eq!(bvh.traverse(&ray, 100.0, intersect), Some(4.0));
+    }
+
+    #[test]
+    fn test_options() {
+        let mesh = grid(16);
+        let bounds = super::triangle_bounds(mesh.triangles());
+        let median = Bvh::with_options(&bounds, BvhOptions { split: SplitMethod::Median, ..BvhOptions::default() });
+        let sah = Bvh::new(&bounds);
+        assert!(sah.sah_cost() <= median.sah_cost());
+        let stats = median.stats();
+        assert_eq!(stats.primitives, 512);
+        assert_eq!(stats.leaves, 128);
+        assert_eq!(stats.nodes, 255);
+        assert_eq!(stats.depth, 8);
+        assert_eq!(stats.max_leaf_size, 4);
+        assert_eq!(stats.average_leaf_size, 4.0);
+        let coarse = BvhOptions { leaf_size: 16, split: SplitMethod::Median, ..BvhOptions::default() };
+        assert_eq!(Bvh::with_options(&bounds, coarse).stats().leaves, 32);
+        // Pricier traversal makes the same tree costlier
+        let pricey = BvhOptions { traversal_cost: 4.0, split: SplitMethod::Median, ..BvhOptions::default() };
+        assert!(Bvh::with_options(&bounds, pricey).sah_cost() > median.sah_cost());
+        // Primitives at the same spot still split
+        let stacked = vec![bounds[0]; 9];
+        let stats = Bvh::new(&stacked).stats();
+        assert_eq!((stats.leaves, stats.max_leaf_size), (3, 4));
+    }
+
+    #[test]
+    fn test_update() {
+        let mesh = grid(8);
+        let mut bvh = Bvh::from_mesh(&mesh);
+        assert_eq!(bvh.degradation(), 1.0);
+        // A wave moves vertices a little, refitting is enough
+        let wave = |t: &Triangle| {
+            let lift = |v: Vec3f| Vec3f::from_parts(v.x, v.y, v.z + (v.x * 0.5).cos() * 0.2);
+            Triangle::new(lift(t.a), lift(t.b), lift(t.c))
+        };
+        let waved: Vec<Triangle> = mesh.triangles().iter().map(wave).collect();
+        assert!(!bvh.update(&waved, 1.5));
+        assert!(bvh.degradation() < 1.5);
+        let ray = Ray::new(Vec3f::from_parts(2.5, 3.5, 5.0), Vec3f::from_parts(0.0, 0.0, -1.0));
+        let expected = waved.iter().filter_map(|t| t.intersect(&ray)).map(|hit| hit.t).next();
+        assert_eq!(bvh.traverse(&ray, 100.0, |p, _| waved[p].intersect(&ray).map(|hit| hit.t)), expected);
+        // Mirroring the grid scatters neighbours of the tree apart, so it rebuilds
+        let mirror = |t: &Triangle| {
+            let flip = |v: Vec3f| if (v.x as usize).is_multiple_of(2) { v } else { Vec3f::from_parts(8.0 - v.x, v.y, v.z) };
+            Triangle::new(flip(t.a), flip(t.b), flip(t.c))
+        };
+        let scattered: Vec<Triangle> = mesh.triangles().iter().map(mirror).collect();
+        assert!(bvh.update(&scattered, 1.5));
+        assert_eq!(bvh.degradation(), 1.0);
+    }
+}
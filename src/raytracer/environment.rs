@@ -0,0 +1,236 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Importance sampling of environment maps.
+//!
+//! Directions are picked in proportion to the light each texel of an equirectangular
+//! environment contributes, so a small bright sun is found by a few samples instead of
+//! the rare ray that happens to hit it.
+
+use crate::canvas::{Canvas, Color};
+use crate::math::{equirect_direction, equirect_uv, Vec2f, Vec3f};
+use std::f32::consts::PI;
+
+/// Direction picked by an `EnvironmentSampler`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EnvironmentSample {
+    /// Unit vector towards the environment
+    pub direction: Vec3f,
+    /// Light arriving from `direction`
+    pub radiance: Color,
+    /// Probability density of picking `direction`, per steradian
+    pub pdf: f32,
+}
+
+///
+/// Distribution over the directions of an equirectangular environment map following
+/// its luminance
+///
+/// ```
+/// use softrender::canvas::{Canvas, Color};
+/// use softrender::EnvironmentSampler;
+///
+/// let mut sky = Canvas::new(32, 16);
+/// sky.fill(Color::new(0.1, 0.1, 0.2));
+/// sky.set(8, 4, Color::new(500.0, 500.0, 450.0));
+/// let sampler = EnvironmentSampler::new(&sky);
+/// let sample = sampler.sample(0.5, 0.5);
+/// assert_eq!(sample.radiance, sky.get(8, 4));
+/// ```
+///
+pub struct EnvironmentSampler {
+    environment: Canvas,
+    /// Cumulative distribution of picking each row, `height + 1` entries from 0 to 1
+    rows: Vec<f32>,
+    /// Cumulative distribution of picking each texel within its row, `width + 1` per row
+    columns: Vec<f32>,
+    /// Total of the texel weights
+    total: f32,
+}
+
+impl EnvironmentSampler {
+    /// Build the distribution of `environment`.
+    ///
+    /// Each texel is weighted by its luminance times the solid angle it covers. A black
+    /// environment falls back to picking directions uniformly.
+    pub fn new(environment: &Canvas) -> EnvironmentSampler {
+        let (width, height) = (environment.width(), environment.height());
+        let mut columns = Vec::with_capacity((width + 1) * height);
+        let mut row_weights = Vec::with_capacity(height);
+        for y in 0..height {
+            let solid_angle = (PI * (y as f32 + 0.5) / height as f32).sin();
+            let mut sum = 0.0;
+            columns.push(0.0);
+            for x in 0..width {
+                sum += environment.get(x, y).luminance().max(0.0) * solid_angle;
+                columns.push(sum);
+            }
+            normalize(&mut columns[y * (width + 1)..], width);
+            row_weights.push(sum);
+        }
+        let mut rows = Vec::with_capacity(height + 1);
+        let mut total = 0.0;
+        rows.push(0.0);
+        for weight in row_weights {
+            total += weight;
+            rows.push(total);
+        }
+        if total > 0.0 {
+            normalize(&mut rows, height);
+        } else {
+            // Black environment, pick rows by solid angle alone
+            let mut sum = 0.0;
+            for y in 0..height {
+                sum += (PI * (y as f32 + 0.5) / height as f32).sin();
+                rows[y + 1] = sum;
+            }
+            normalize(&mut rows, height);
+        }
+        EnvironmentSampler {
+            environment: environment.clone(),
+            rows,
+            columns,
+            total,
+        }
+    }
+    /// Environment being sampled
+    pub fn environment(&self) -> &Canvas {
+        &self.environment
+    }
+    /// Total luminance weight of the environment, 0 if it is black
+    pub fn total(&self) -> f32 {
+        self.total
+    }
+    /// Pick a direction from two uniform random numbers in [0, 1)
+    pub fn sample(&self, u1: f32, u2: f32) -> EnvironmentSample {
+        let (width, height) = (self.environment.width(), self.environment.height());
+        let y = search(&self.rows, u1);
+        let row = &self.columns[y * (width + 1)..(y + 1) * (width + 1)];
+        let x = search(row, u2);
+        // Place the direction within the texel by how far into its range the numbers fall
+        let within = |cdf: &[f32], index: usize, u: f32| {
+            let range = cdf[index + 1] - cdf[index];
+            if range > 0.0 { ((u - cdf[index]) / range).clamp(0.0, 1.0) } else { 0.5 }
+        };
+        let uv = Vec2f::from_parts(
+            (x as f32 + within(row, x, u2)) / width as f32,
+            (y as f32 + within(&self.rows, y, u1)) / height as f32,
+        );
+        let direction = equirect_direction(uv);
+        EnvironmentSample {
+            direction,
+            radiance: self.environment.get(x, y),
+            pdf: self.texel_pdf(x, y),
+        }
+    }
+    /// Probability density of `sample` returning the unit vector `direction`, per steradian
+    pub fn pdf(&self, direction: Vec3f) -> f32 {
+        let (x, y) = self.texel(direction);
+        self.texel_pdf(x, y)
+    }
+    /// Light arriving from the unit vector `direction`
+    pub fn radiance(&self, direction: Vec3f) -> Color {
+        let (x, y) = self.texel(direction);
+        self.environment.get(x, y)
+    }
+    /// Texel of the environment seen in the unit vector `direction`
+    fn texel(&self, direction: Vec3f) -> (usize, usize) {
+        let (width, height) = (self.environment.width(), self.environment.height());
+        let uv = equirect_uv(direction);
+        (
+            ((uv.x * width as f32) as usize).min(width - 1),
+            ((uv.y * height as f32) as usize).min(height - 1),
+        )
+    }
+    /// Density over the directions inside texel (x, y)
+    fn texel_pdf(&self, x: usize, y: usize) -> f32 {
+        let (width, height) = (self.environment.width(), self.environment.height());
+        let row = &self.columns[y * (width + 1)..];
+        let probability = (self.rows[y + 1] - self.rows[y]) * (row[x + 1] - row[x]);
+        let sin_theta = (PI * (y as f32 + 0.5) / height as f32).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        // Texel area on the unit sphere is (2 PI / width) * (PI / height) * sin(theta)
+        probability * (width * height) as f32 / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+/// Scale the first `count + 1` entries of a running sum to end at 1, or spread evenly if
+/// the sum is 0
+fn normalize(cdf: &mut [f32], count: usize) {
+    let total = cdf[count];
+    for (index, value) in cdf[..=count].iter_mut().enumerate() {
+        *value = if total > 0.0 { *value / total } else { index as f32 / count as f32 };
+    }
+}
+
+/// Index of the entry of `cdf` whose range holds `u`, skipping empty ranges
+fn search(cdf: &[f32], u: f32) -> usize {
+    let count = cdf.len() - 1;
+    let index = cdf[1..].partition_point(|&value| value <= u);
+    index.min(count - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EnvironmentSampler;
+    use crate::canvas::{Canvas, Color};
+    use assert_approx_eq::assert_approx_eq;
+    use std::f32::consts::PI;
+
+    fn sky() -> Canvas {
+        let mut sky = Canvas::new(32, 16);
+        sky.fill(Color::new(0.1, 0.1, 0.1));
+        sky.set(20, 5, Color::new(1000.0, 1000.0, 1000.0));
+        sky
+    }
+
+    #[test]
+    fn test_sample_sun() {
+        let sky = sky();
+        let sampler = EnvironmentSampler::new(&sky);
+        let mut hits = 0;
+        for i in 0..16 {
+            for j in 0..16 {
+                let sample = sampler.sample((i as f32 + 0.5) / 16.0, (j as f32 + 0.5) / 16.0);
+                if sample.radiance == sky.get(20, 5) {
+                    hits += 1;
+                    assert_approx_eq!(sampler.pdf(sample.direction), sample.pdf, sample.pdf * 1e-3);
+                }
+            }
+        }
+        // The sun outshines the rest of the sky many times over
+        assert!(hits > 200, "{}", hits);
+    }
+
+    #[test]
+    fn test_uniform() {
+        let mut sky = Canvas::new(32, 16);
+        sky.fill(Color::white());
+        let sampler = EnvironmentSampler::new(&sky);
+        // A uniform environment has the density of uniform sphere sampling everywhere
+        for &(u1, u2) in &[(0.1, 0.3), (0.5, 0.5), (0.9, 0.7)] {
+            assert_approx_eq!(sampler.sample(u1, u2).pdf, 1.0 / (4.0 * PI), 0.01);
+        }
+        // A black one still picks directions
+        let black = EnvironmentSampler::new(&Canvas::new(8, 4));
+        assert_eq!(black.total(), 0.0);
+        let sample = black.sample(0.3, 0.6);
+        assert!(sample.direction.magnitude() > 0.99);
+        assert_eq!(black.radiance(sample.direction), Color::black());
+    }
+}
@@ -0,0 +1,384 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Offline baking of mesh lighting into lightmap textures.
+//!
+//! Every triangle gets its own chart in a second UV set covering the lightmap, the
+//! baker traces direct light with shadow rays plus one diffuse bounce into each texel,
+//! and the rasterizer reads the result back with `Rasterizer::draw_lightmapped`.
+
+use super::{hash, Raytracer};
+use crate::canvas::{Canvas, Color, Filter, Texture};
+use crate::math::{cosine_hemisphere, OrthonormalBasis, Ray, Vec2f, Vec3f};
+use crate::model::{Material, Mesh, PointLight, TriangleIntersector};
+
+///
+/// Baked lighting for a mesh and the UV charts mapping its triangles into it
+///
+#[derive(Clone)]
+pub struct Lightmap {
+    texture: Canvas,
+    uvs: Vec<[Vec2f; 3]>,
+}
+
+impl Lightmap {
+    /// Give each of `triangles` its own chart in a `resolution` square lightmap.
+    ///
+    /// Triangles are laid out in a grid of square cells, each covering the lower left half
+    /// of its cell with a one texel border so bilinear filtering does not bleed between
+    /// charts. UVs run from 0 to 1 with (0, 0) at the top left of the texture.
+    pub fn chart(triangles: usize, resolution: usize) -> Vec<[Vec2f; 3]> {
+        let cells = (triangles as f32).sqrt().ceil().max(1.0) as usize;
+        let cell = resolution as f32 / cells as f32;
+        let padding = 1.0f32.min(cell / 4.0);
+        (0..triangles)
+            .map(|index| {
+                let x0 = (index % cells) as f32 * cell + padding;
+                let y0 = (index / cells) as f32 * cell + padding;
+                let x1 = x0 + cell - 2.0 * padding;
+                let y1 = y0 + cell - 2.0 * padding;
+                let uv = |x: f32, y: f32| Vec2f::from_parts(x / resolution as f32, y / resolution as f32);
+                [uv(x0, y0), uv(x1, y0), uv(x0, y1)]
+            })
+            .collect()
+    }
+    /// Lighting texture, multiply by the surface color to shade
+    pub fn texture(&self) -> &Canvas {
+        &self.texture
+    }
+    /// Lightmap UVs of the vertices of each triangle
+    pub fn uvs(&self) -> &[[Vec2f; 3]] {
+        &self.uvs
+    }
+    /// Lightmap UV of the point with barycentric weights `u` for `b` and `v` for `c` on
+    /// triangle `triangle`
+    pub fn uv(&self, triangle: usize, u: f32, v: f32) -> Vec2f {
+        let [a, b, c] = self.uvs[triangle];
+        a + (b - a) * u + (c - a) * v
+    }
+    /// Light at the point with barycentric weights `u` for `b` and `v` for `c` on
+    /// triangle `triangle`, bilinearly filtered
+    pub fn sample(&self, triangle: usize, u: f32, v: f32) -> Color {
+        let uv = self.uv(triangle, u, v);
+        let (width, height) = (self.texture.width() as f32, self.texture.height() as f32);
+        self.texture.sample(uv.x * width, uv.y * height, Filter::Bilinear)
+    }
+}
+
+///
+/// Settings for baking a `Lightmap` with the raytracer
+///
+/// ```
+/// use softrender::LightmapBaker;
+/// use softrender::math::Vec3f;
+/// use softrender::model::{cube, PointLight};
+///
+/// let mesh = cube(1.0);
+/// let light = PointLight::new(Vec3f::from_parts(0.0, 2.0, 0.0), 4.0);
+/// let mut baker = LightmapBaker::new(32);
+/// baker.indirect_samples = 4;
+/// let lightmap = baker.bake(&mesh, &[light]);
+/// assert_eq!(lightmap.uvs().len(), mesh.triangles().len());
+/// ```
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LightmapBaker {
+    /// Width and height of the lightmap in texels
+    pub resolution: usize,
+    /// Rays traced per texel for the diffuse bounce, 0 bakes direct light only
+    pub indirect_samples: usize,
+    /// Diffuse color of every surface for the bounce
+    pub albedo: Color,
+    /// Light arriving along rays escaping the mesh
+    pub sky: Color,
+    /// Distance rays start off the surface to avoid hitting it again
+    pub bias: f32,
+    /// Ray/triangle test for the shadow and bounce rays
+    pub triangle_intersector: TriangleIntersector,
+}
+
+impl LightmapBaker {
+    /// Create new LightmapBaker for a `resolution` square lightmap
+    pub fn new(resolution: usize) -> LightmapBaker {
+        LightmapBaker {
+            resolution,
+            indirect_samples: 16,
+            albedo: Color::new(0.5, 0.5, 0.5),
+            sky: Color::black(),
+            bias: 1e-3,
+            triangle_intersector: TriangleIntersector::default(),
+        }
+    }
+    /// Bake `lights` onto `mesh` using charts from `Lightmap::chart`
+    pub fn bake(&self, mesh: &Mesh, lights: &[PointLight]) -> Lightmap {
+        let uvs = Lightmap::chart(mesh.triangles().len(), self.resolution);
+        self.bake_with_uvs(mesh, uvs, lights)
+    }
+    /// Bake `lights` onto `mesh` using `uvs`, one chart per triangle, such as a second UV
+    /// set from an imported model.
+    ///
+    /// Triangles face the side their vertices wind counter clockwise around. Texels no
+    /// chart covers take the light of their neighbours so filtering does not pull in black.
+    ///
+    /// # Panics
+    /// Panics if `uvs` does not have an entry for every triangle.
+    pub fn bake_with_uvs(&self, mesh: &Mesh, uvs: Vec<[Vec2f; 3]>, lights: &[PointLight]) -> Lightmap {
+        self.bake_with_material(mesh, uvs, &Material::new(), &[], lights)
+    }
+    /// Bake like `bake_with_uvs`, with rays passing through the texels of `mesh` cut out
+    /// by `material`, whose textures are looked up in `textures`.
+    ///
+    /// # Panics
+    /// Panics if `uvs` does not have an entry for every triangle.
+    pub fn bake_with_material(
+        &self,
+        mesh: &Mesh,
+        uvs: Vec<[Vec2f; 3]>,
+        material: &Material,
+        textures: &[Texture],
+        lights: &[PointLight],
+    ) -> Lightmap {
+        crate::profile_scope!("lightmap_bake");
+        let scene = Scene {
+            mesh,
+            material,
+            textures,
+            lights,
+        };
+        let triangles = mesh.triangles();
+        assert_eq!(uvs.len(), triangles.len(), "lightmap needs one chart per triangle");
+        let size = self.resolution;
+        let mut texture = Canvas::new(size, size);
+        let mut covered = vec![false; size * size];
+        let edge = |p: Vec2f, q: Vec2f, r: Vec2f| (q.x - p.x) * (r.y - p.y) - (q.y - p.y) * (r.x - p.x);
+        for (index, (triangle, chart)) in triangles.iter().zip(&uvs).enumerate() {
+            let [a, b, c] = chart.map(|uv| uv * size as f32);
+            let area = edge(a, b, c);
+            if area == 0.0 {
+                continue;
+            }
+            let normal = triangle.normal();
+            let min_x = a.x.min(b.x).min(c.x).max(0.0) as usize;
+            let min_y = a.y.min(b.y).min(c.y).max(0.0) as usize;
+            let max_x = (a.x.max(b.x).max(c.x).ceil() as usize).min(size);
+            let max_y = (a.y.max(b.y).max(c.y).ceil() as usize).min(size);
+            for y in min_y..max_y {
+                for x in min_x..max_x {
+                    let point = Vec2f::from_parts(x as f32 + 0.5, y as f32 + 0.5);
+                    let u = edge(c, a, point) / area;
+                    let v = edge(a, b, point) / area;
+                    if u < 0.0 || v < 0.0 || u + v > 1.0 {
+                        continue;
+                    }
+                    let position = triangle.a + (triangle.b - triangle.a) * u + (triangle.c - triangle.a) * v;
+                    let seed = (index * size * size + y * size + x) as u32;
+                    texture.set(x, y, self.light(&scene, position, normal, seed));
+                    covered[y * size + x] = true;
+                }
+            }
+        }
+        dilate(&mut texture, &mut covered);
+        Lightmap { texture, uvs }
+    }
+    /// Direct light plus one diffuse bounce arriving at `position`
+    fn light(&self, scene: &Scene, position: Vec3f, normal: Vec3f, seed: u32) -> Color {
+        let direct = self.direct(scene, position, normal);
+        if self.indirect_samples == 0 {
+            return direct;
+        }
+        let basis = OrthonormalBasis::from_normal(normal);
+        let rotation = (hash(seed) as f32 / u32::MAX as f32, hash(seed ^ 0x9e37_79b9) as f32 / u32::MAX as f32);
+        let mut bounce = Color::black();
+        for sample in 0..self.indirect_samples {
+            // Hammersley points, rotated per texel so neighbours do not share a pattern
+            let r1 = (sample as f32 / self.indirect_samples as f32 + rotation.0).fract();
+            let r2 = (radical_inverse(sample as u32) + rotation.1).fract();
+            let direction = basis.to_world(cosine_hemisphere(r1, r2));
+            let ray = Ray::new(position + normal * self.bias, direction);
+            let intersector = self.triangle_intersector;
+            bounce += match Raytracer::intersect(scene.mesh, scene.material, scene.textures, &ray, intersector) {
+                Some((index, hit)) => {
+                    let hit_triangle = &scene.mesh.triangles()[index];
+                    let edges = (hit_triangle.b - hit_triangle.a, hit_triangle.c - hit_triangle.a);
+                    let hit_normal = Vec3f::cross(edges.0, edges.1).normalize();
+                    if hit_normal.dot(direction) >= 0.0 {
+                        // Back of a surface, which does not reflect
+                        Color::black()
+                    } else {
+                        let hit_position = ray.at(hit.t) + hit_normal * self.bias;
+                        self.direct(scene, hit_position, hit_normal) * self.albedo
+                    }
+                }
+                None => self.sky,
+            };
+        }
+        direct + bounce * (1.0 / self.indirect_samples as f32)
+    }
+    /// Light from the scene's lights reaching `position` unblocked by its mesh
+    fn direct(&self, scene: &Scene, position: Vec3f, normal: Vec3f) -> Color {
+        let origin = position + normal * self.bias;
+        let mut total = Color::black();
+        for light in scene.lights {
+            let irradiance = light.irradiance(position, normal);
+            if irradiance == Color::black() {
+                continue;
+            }
+            // Rays reach the light at t = 1
+            let ray = Ray::new(origin, light.position - origin);
+            let intersector = self.triangle_intersector;
+            if !Raytracer::occluded(scene.mesh, scene.material, scene.textures, &ray, 1.0, intersector) {
+                total += irradiance;
+            }
+        }
+        total
+    }
+}
+
+/// Geometry and lights being baked
+struct Scene<'a> {
+    mesh: &'a Mesh,
+    material: &'a Material,
+    textures: &'a [Texture],
+    lights: &'a [PointLight],
+}
+
+/// Van der Corput radical inverse in base 2
+fn radical_inverse(index: u32) -> f32 {
+    index.reverse_bits() as f32 / 4_294_967_296.0
+}
+
+/// Fill texels not covered by any chart with the average of their covered neighbours
+fn dilate(texture: &mut Canvas, covered: &mut [bool]) {
+    let (width, height) = (texture.width(), texture.height());
+    for _ in 0..2 {
+        let mut filled = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                if covered[y * width + x] {
+                    continue;
+                }
+                let mut sum = Color::black();
+                let mut count = 0;
+                for (nx, ny) in [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)] {
+                    if nx < width && ny < height && covered[ny * width + nx] {
+                        sum += texture.get(nx, ny);
+                        count += 1;
+                    }
+                }
+                if count > 0 {
+                    filled.push((x, y, sum * (1.0 / count as f32)));
+                }
+            }
+        }
+        for (x, y, color) in filled {
+            texture.set(x, y, color);
+            covered[y * width + x] = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Lightmap, LightmapBaker};
+    use crate::canvas::{Canvas, Color, Texture};
+    use crate::math::{Vec2f, Vec3f};
+    use crate::model::{Material, Mesh, PointLight, TextureSlot, Triangle};
+
+    /// Upward facing unit square at y = 0 split into two triangles
+    fn floor() -> Vec<Triangle> {
+        let p = |x: f32, z: f32| Vec3f::from_parts(x, 0.0, z);
+        vec![
+            Triangle::new(p(0.0, 0.0), p(0.0, 1.0), p(1.0, 0.0)),
+            Triangle::new(p(1.0, 1.0), p(1.0, 0.0), p(0.0, 1.0)),
+        ]
+    }
+
+    #[test]
+    fn test_chart() {
+        let uvs = Lightmap::chart(5, 30);
+        assert_eq!(uvs.len(), 5);
+        // Three by three grid of 10 texel cells with a texel of padding
+        let [a, b, c] = uvs[4];
+        assert_eq!((a.x * 30.0, a.y * 30.0), (11.0, 11.0));
+        assert_eq!((b.x * 30.0, b.y * 30.0), (19.0, 11.0));
+        assert_eq!((c.x * 30.0, c.y * 30.0), (11.0, 19.0));
+    }
+
+    #[test]
+    fn test_direct_and_shadow() {
+        let light = PointLight::new(Vec3f::from_parts(0.5, 1.0, 0.5), 1.0);
+        let mut baker = LightmapBaker::new(16);
+        baker.indirect_samples = 0;
+        let lightmap = baker.bake(&Mesh::new(floor()), &[light]);
+        // Straight below the light the floor receives the full intensity
+        let center = lightmap.sample(0, 0.49, 0.49);
+        assert!((center.r.value() - 1.0).abs() < 0.2, "{:?}", center);
+        // Nothing reaches the back of the floor
+        let below = PointLight::new(Vec3f::from_parts(0.5, -1.0, 0.5), 1.0);
+        let dark = baker.bake(&Mesh::new(floor()), &[below]);
+        assert_eq!(dark.sample(0, 0.3, 0.3), Color::black());
+        // A blocker between the light and the floor casts a shadow
+        let mut triangles = floor();
+        let q = |x: f32, z: f32| Vec3f::from_parts(x, 0.5, z);
+        triangles.push(Triangle::new(q(-1.0, -1.0), q(-1.0, 3.0), q(3.0, -1.0)));
+        let shadowed = baker.bake(&Mesh::new(triangles), &[light]);
+        assert_eq!(shadowed.sample(0, 0.3, 0.3), Color::black());
+    }
+
+    #[test]
+    fn test_indirect() {
+        // A light under a ceiling with a blocker keeping it off the floor, so the floor
+        // only sees the light bouncing off the ceiling
+        let mut triangles = floor();
+        let q = |x: f32, y: f32, z: f32| Vec3f::from_parts(x, y, z);
+        triangles.push(Triangle::new(q(-1.0, 1.0, -1.0), q(3.0, 1.0, -1.0), q(-1.0, 1.0, 3.0)));
+        triangles.push(Triangle::new(q(0.2, 0.45, 0.2), q(0.2, 0.45, 0.9), q(0.9, 0.45, 0.2)));
+        let mesh = Mesh::new(triangles);
+        let light = PointLight::new(Vec3f::from_parts(0.5, 0.5, 0.5), 1.0);
+        let mut baker = LightmapBaker::new(32);
+        baker.indirect_samples = 0;
+        let direct = baker.bake(&mesh, &[light]);
+        assert_eq!(direct.sample(0, 0.3, 0.3), Color::black());
+        assert!(direct.sample(2, 0.375, 0.375).r.value() > 0.0);
+        baker.indirect_samples = 32;
+        let bounced = baker.bake(&mesh, &[light]).sample(0, 0.3, 0.3);
+        assert!(bounced.r.value() > 0.0 && bounced.r.value() < direct.sample(2, 0.375, 0.375).r.value(), "{:?}", bounced);
+    }
+
+    #[test]
+    fn test_cutout_shadow() {
+        // A blocker whose texture is cut out everywhere casts no shadow
+        let mut triangles = floor();
+        let q = |x: f32, z: f32| Vec3f::from_parts(x, 0.5, z);
+        triangles.push(Triangle::new(q(-1.0, -1.0), q(-1.0, 3.0), q(3.0, -1.0)));
+        let t = |u: f32| Vec2f::from_parts(u, 0.5);
+        let mesh = Mesh::with_uvs(triangles, vec![[t(0.9), t(0.9), t(0.9)], [t(0.9), t(0.9), t(0.9)], [t(0.1), t(0.1), t(0.1)]]);
+        let mut mask = Canvas::new(2, 1);
+        mask.set(1, 0, Color::white());
+        let textures = [Texture::new(mask)];
+        let mut material = Material::new();
+        material.alpha_texture = Some(TextureSlot::new(0));
+        let light = PointLight::new(Vec3f::from_parts(0.5, 1.0, 0.5), 1.0);
+        let mut baker = LightmapBaker::new(16);
+        baker.indirect_samples = 0;
+        let uvs = Lightmap::chart(3, 16);
+        let opaque = baker.bake_with_material(&mesh, uvs.clone(), &material, &textures, &[light]);
+        assert_eq!(opaque.sample(0, 0.3, 0.3), Color::black());
+        material.alpha_cutoff = Some(0.5);
+        let cut_out = baker.bake_with_material(&mesh, uvs, &material, &textures, &[light]);
+        assert!(cut_out.sample(0, 0.3, 0.3).r.value() > 0.5);
+    }
+}
@@ -15,14 +15,121 @@
 //
 
 
-use super::image::{Color, Image};
+mod accumulator;
+mod aov;
+mod bdpt;
+mod bvh;
+mod environment;
+mod lightmap;
+mod path;
+mod sampler;
+mod settings;
+mod tiles;
+mod tlas;
+
+use crate::canvas::{Canvas, Color, Texture};
+use crate::math::{hash, Ray};
+use crate::model::{Material, Mesh, TriangleHit, TriangleIntersector};
+
+pub use self::accumulator::{Accumulator, Checkpointer};
+pub use self::bvh::{Bvh, BvhNode, BvhOptions, BvhStats, SplitMethod};
+pub use self::environment::{EnvironmentSample, EnvironmentSampler};
+pub use self::lightmap::{Lightmap, LightmapBaker};
+pub use self::path::{power_heuristic, Hit, Integrator, LightSampling, PathTracer};
+pub use self::sampler::{HaltonSampler, PixelSampler, RandomSampler, SobolSampler};
+pub use self::settings::RenderSettings;
+pub use self::tiles::{Tile, TileOrder};
+pub use self::tlas::{InstanceHit, Tlas};
 
 /// Trait to draw on a Buffer
 pub struct Raytracer {}
 
 impl Raytracer {
     /// Set all Pixels to color.
-    fn clear<C: Color>(image: &mut Image<C>, color: C) {
-        image.fill(color);
+    pub fn clear(canvas: &mut Canvas, color: Color) {
+        canvas.fill(color);
     }
-}
\ No newline at end of file
+
+    /// Render `canvas` tile by tile in the order chosen by `settings`, setting each Pixel
+    /// (x, y) to `shade(x, y)`.
+    ///
+    /// Tiles are shaded in parallel on the global job pool, sized with `set_thread_count`,
+    /// and written to `canvas` in order, calling `progress` after every tile with the
+    /// partly written canvas, for example to present it.
+    ///
+    /// ```
+    /// use softrender::canvas::{Canvas, Color};
+    /// use softrender::{Raytracer, RenderSettings, TileOrder};
+    ///
+    /// let mut canvas = Canvas::new(96, 96);
+    /// let settings = RenderSettings { tile_order: TileOrder::Spiral, ..RenderSettings::default() };
+    /// let mut first = None;
+    /// Raytracer::render_tiles(&mut canvas, &settings, |_, _| Color::white(), |tile, _| {
+    ///     first = first.or(Some(*tile));
+    /// });
+    /// assert_eq!((first.unwrap().x, first.unwrap().y), (32, 32));
+    /// assert_eq!(canvas.get(95, 95), Color::white());
+    /// ```
+    pub fn render_tiles<S, P>(canvas: &mut Canvas, settings: &RenderSettings, shade: S, mut progress: P)
+    where
+        S: Fn(usize, usize) -> Color + Sync,
+        P: FnMut(&Tile, &Canvas),
+    {
+        let tiles = settings.tiles(canvas.width(), canvas.height());
+        let pixels = crate::jobs::global().map(tiles.len(), |index| {
+            let tile = tiles[index];
+            let rows = (tile.y..tile.y + tile.height).map(|y| (tile.x..tile.x + tile.width).map(move |x| (x, y)));
+            rows.flatten().map(|(x, y)| shade(x, y)).collect::<Vec<Color>>()
+        });
+        for (tile, pixels) in tiles.iter().zip(pixels) {
+            for (index, color) in pixels.into_iter().enumerate() {
+                canvas.set(tile.x + index % tile.width, tile.y + index / tile.width, color);
+            }
+            progress(tile, canvas);
+        }
+    }
+
+    /// Closest triangle of `mesh` hit by `ray` using `intersector` and where it was hit.
+    ///
+    /// Texels cut out by `material` let the ray pass through.
+    pub fn intersect(
+        mesh: &Mesh,
+        material: &Material,
+        textures: &[Texture],
+        ray: &Ray,
+        intersector: TriangleIntersector,
+    ) -> Option<(usize, TriangleHit)> {
+        let mut closest: Option<(usize, TriangleHit)> = None;
+        for (index, triangle) in mesh.triangles().iter().enumerate() {
+            if let Some(hit) = triangle.intersect_with(ray, intersector) {
+                if closest.is_none_or(|(_, best)| hit.t < best.t) && !cut_out(mesh, material, textures, index, &hit) {
+                    closest = Some((index, hit));
+                }
+            }
+        }
+        closest
+    }
+
+    /// Check if anything in `mesh` blocks `ray` before `max_t`, for shadow rays.
+    ///
+    /// Texels cut out by `material` count as transparent.
+    pub fn occluded(
+        mesh: &Mesh,
+        material: &Material,
+        textures: &[Texture],
+        ray: &Ray,
+        max_t: f32,
+        intersector: TriangleIntersector,
+    ) -> bool {
+        mesh.triangles().iter().enumerate().any(|(index, triangle)| {
+            triangle
+                .intersect_with(ray, intersector)
+                .is_some_and(|hit| hit.t < max_t && !cut_out(mesh, material, textures, index, &hit))
+        })
+    }
+}
+
+/// Check if `hit` on triangle `index` of `mesh` falls on a texel `material` cuts out
+fn cut_out(mesh: &Mesh, material: &Material, textures: &[Texture], index: usize, hit: &TriangleHit) -> bool {
+    material.is_cut_out(textures, mesh.uv(index, hit.u, hit.v))
+}
@@ -0,0 +1,1006 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Accumulator, EnvironmentSampler, PixelSampler, RenderSettings, Tlas};
+use crate::canvas::{AovBuffers, AovSample, Color, Wavelengths, WAVELENGTH_COUNT};
+use crate::math::{
+    cosine_hemisphere, cosine_hemisphere_pdf, spherical_direction, OrthonormalBasis, Ray, Vec2f, Vec3f,
+};
+use crate::model::{BackfaceMode, Material, RayKind, Scene, Triangle, TriangleIntersector, ALL_LINKS};
+use crate::shader::Camera;
+use std::f32::consts::PI;
+
+/// How light arriving straight from the lights is found at every bounce
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LightSampling {
+    /// Only by bouncing in directions picked by the surface, noisy for small lights
+    Bsdf,
+    /// Only by picking points on the lights, noisy for sharp highlights of large lights
+    Light,
+    /// Both, weighted with the power heuristic so each covers where the other is noisy
+    #[default]
+    Mis,
+}
+
+/// Way paths are built
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Integrator {
+    /// Paths grow from the camera only
+    #[default]
+    Path,
+    /// Paths grow from both the camera and the area lights and are joined at every pair of
+    /// vertices, finding light that mostly arrives through reflections much sooner.
+    ///
+    /// Point lights and the environment are still gathered along the camera path.
+    Bidirectional,
+}
+
+/// Surface point a ray hit
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Hit {
+    /// Distance along the ray in multiples of its direction
+    pub t: f32,
+    /// Distance from the ray origin in scene units
+    pub distance: f32,
+    pub position: Vec3f,
+    /// Unit geometric normal, facing the side the ray came from unless the material shades
+    /// backfaces with `BackfaceMode::Interior`
+    pub normal: Vec3f,
+    /// Unit normal to shade with, `normal` bent by the bump and normal maps of the material
+    pub shading_normal: Vec3f,
+    /// Whether the ray hit the side the triangle's winding faces, the outside of a closed mesh
+    pub front: bool,
+    pub uv: Vec2f,
+    /// Index of the instance hit in `Scene::instances`
+    pub instance: usize,
+    /// Moment within the shutter of the ray that found it, and of rays leaving it
+    pub time: f32,
+}
+
+impl Hit {
+    /// Closest surface of `scene` visible to rays of kind `kind` along `ray`, using the
+    /// acceleration structure `tlas` and the ray/triangle test `intersector`
+    pub fn find(
+        scene: &Scene,
+        tlas: &Tlas,
+        ray: &Ray,
+        kind: RayKind,
+        intersector: TriangleIntersector,
+    ) -> Option<Hit> {
+        let hit = tlas.intersect_visible(scene, ray, kind, intersector)?;
+        let instance = &scene.instances[hit.instance];
+        let mesh = &scene.meshes[instance.mesh];
+        let triangle = mesh.triangle_at(hit.triangle, ray.time);
+        let transform = instance.transform_at(ray.time);
+        let [a, b, c] = [triangle.a, triangle.b, triangle.c].map(|vertex| vertex * transform);
+        let material = scene.materials.get(instance.material);
+        let normal = Triangle::new(a, b, c).normal();
+        let front = normal.dot(ray.direction) <= 0.0;
+        // Glass always needs the normal on the side light leaves through to refract
+        let interior = material.is_some_and(|material| {
+            material.backface == BackfaceMode::Interior && material.transparency <= 0.0
+        });
+        let normal = if front || interior { normal } else { -normal };
+        let uv = mesh.uv(hit.triangle, hit.hit.u, hit.hit.v);
+        // Tangent frame into world space like the vertices
+        let (dpdu, dpdv) = mesh.uv_derivatives(hit.triangle);
+        let (dpdu, dpdv) = ((triangle.a + dpdu) * transform - a, (triangle.a + dpdv) * transform - a);
+        let shading_normal = material.map_or(normal, |material| {
+            material.shading_normal(&scene.textures, uv, normal, dpdu, dpdv)
+        });
+        // Far along a ray `t` is only known roughly, the barycentrics place the point on
+        // the triangle itself
+        let (u, v) = (hit.hit.u, hit.hit.v);
+        Some(Hit {
+            t: hit.hit.t,
+            distance: hit.hit.t * ray.direction.magnitude(),
+            position: a * (1.0 - u - v) + b * u + c * v,
+            normal,
+            shading_normal,
+            front,
+            uv,
+            instance: hit.instance,
+            time: ray.time,
+        })
+    }
+    /// Point off the surface on the side `direction` leaves towards, by the offset of
+    /// `settings`, to start a ray that does not hit the surface again
+    pub(super) fn offset(&self, direction: Vec3f, settings: &RenderSettings) -> Vec3f {
+        let bias = settings.ray_offset(self.position, self.distance);
+        if direction.dot(self.normal) < 0.0 {
+            self.position - self.normal * bias
+        } else {
+            self.position + self.normal * bias
+        }
+    }
+}
+
+///
+/// Unidirectional path tracer.
+///
+/// Surfaces reflect with a Lambertian lobe plus a glossy lobe set by the material's
+/// `reflectivity` and `roughness`, and materials with `transparency` act as clear glass of
+/// index `ior`, treating triangles wound towards the ray as the outside. Light comes from
+/// the scene's point and area lights and from `environment`, surfaces themselves do not
+/// glow. Instances only show up to the rays their `visibility` allows. Point lights can
+/// not be hit by chance, so they are always sampled directly whatever `light_sampling`
+/// says. With `motion_blur` each camera ray samples a random moment of the shutter, and
+/// every ray along its path sees moving geometry at that moment.
+///
+/// ```
+/// use softrender::math::{Mat4f, Vec3f};
+/// use softrender::model::{cube, Material, Scene, SphereLight};
+/// use softrender::shader::Camera;
+/// use softrender::{Accumulator, PathTracer, RandomSampler};
+///
+/// let mut scene = Scene::new();
+/// let mesh = scene.add_mesh(cube(2.0));
+/// let material = scene.add_material(Material::new());
+/// scene.add_instance(mesh, material, Mat4f::identity());
+/// scene.add_area_light(SphereLight::new(Vec3f::new(0.0, 4.0, 4.0), 0.5, 100.0));
+/// let camera = Camera::new(Vec3f::new(0.0, 0.0, 5.0), Vec3f::new(0.0, 0.0, -1.0), Vec3f::UNIT_Y);
+/// let tracer = PathTracer::new(&scene);
+/// let mut accumulator = Accumulator::new(8, 6);
+/// let mut sampler = RandomSampler::new(0);
+/// for _ in 0..4 {
+///     tracer.render_pass(&scene, &camera, &mut accumulator, &mut sampler);
+/// }
+/// assert!(accumulator.get(4, 3).luminance() > 0.0);
+/// assert_eq!(accumulator.get(0, 0), softrender::canvas::Color::black());
+/// ```
+///
+pub struct PathTracer {
+    pub settings: RenderSettings,
+    pub integrator: Integrator,
+    /// How the `Integrator::Path` integrator finds direct light
+    pub light_sampling: LightSampling,
+    /// Light arriving from directions that hit nothing, black if `None`
+    pub environment: Option<EnvironmentSampler>,
+    /// Trace a few wavelengths per path instead of red, green and blue, so glass with
+    /// `dispersion` splits light into its colors. Always uses `Integrator::Path`.
+    pub spectral: bool,
+    /// Spread camera rays over the shutter, blurring instances with a `previous_transform`
+    /// and meshes with previous triangles along their motion. Without it the scene is
+    /// seen as it is now.
+    pub motion_blur: bool,
+    tlas: Tlas,
+}
+
+impl PathTracer {
+    /// Create new PathTracer for `scene` with default settings
+    pub fn new(scene: &Scene) -> PathTracer {
+        PathTracer {
+            settings: RenderSettings::default(),
+            integrator: Integrator::default(),
+            light_sampling: LightSampling::default(),
+            environment: None,
+            spectral: false,
+            motion_blur: false,
+            tlas: Tlas::new(scene),
+        }
+    }
+    /// Update the acceleration structure after instances of `scene` moved or were added
+    pub fn refit(&mut self, scene: &Scene) {
+        self.tlas.refit(scene);
+    }
+    /// Acceleration structure rays are traced through
+    pub fn tlas(&self) -> &Tlas {
+        &self.tlas
+    }
+    /// Trace one sample of every Pixel seen by `camera` into `accumulator`
+    pub fn render_pass<S: PixelSampler>(
+        &self,
+        scene: &Scene,
+        camera: &Camera,
+        accumulator: &mut Accumulator,
+        sampler: &mut S,
+    ) {
+        self.render_pass_with_aovs(scene, camera, accumulator, sampler, None);
+    }
+    /// Trace one sample of every Pixel seen by `camera` into `accumulator` like
+    /// `render_pass`, storing the surface each camera ray hits first in `aovs` if given.
+    ///
+    /// Writes the textured albedo, shading normal, distance from the camera, texture
+    /// coordinates and light links of the surface, with the instance and material indices
+    /// plus one as object and material ids. Pixels seeing nothing get the empty sample.
+    pub fn render_pass_with_aovs<S: PixelSampler>(
+        &self,
+        scene: &Scene,
+        camera: &Camera,
+        accumulator: &mut Accumulator,
+        sampler: &mut S,
+        mut aovs: Option<&mut AovBuffers>,
+    ) {
+        let (width, height) = (accumulator.width(), accumulator.height());
+        let index = accumulator.passes();
+        for y in 0..height {
+            for x in 0..width {
+                sampler.start(x, y, index);
+                let time = if self.motion_blur { sampler.next_1d() } else { 1.0 };
+                let ray = camera.ray(x, y, width, height).with_time(time);
+                let (radiance, hit) = self.trace_primary(scene, &ray, sampler);
+                if let Some(aovs) = aovs.as_deref_mut() {
+                    aovs.set(x, y, &self.aov_sample(scene, hit));
+                }
+                accumulator.add_sample(x, y, radiance);
+            }
+        }
+        accumulator.finish_pass();
+    }
+    /// AOVs of `hit`, the surface a camera ray hits first
+    fn aov_sample(&self, scene: &Scene, hit: Option<Hit>) -> AovSample {
+        let hit = match hit {
+            Some(hit) => hit,
+            None => return AovSample::default(),
+        };
+        let instance = &scene.instances[hit.instance];
+        let albedo = scene.materials.get(instance.material).map_or(Color::black(), |material| {
+            material.surface_at_point(&scene.textures, hit.uv, hit.position, hit.normal)
+        });
+        AovSample {
+            albedo,
+            normal: hit.shading_normal,
+            depth: hit.distance,
+            uv: hit.uv,
+            object_id: hit.instance as u32 + 1,
+            material_id: instance.material as u32 + 1,
+            light_links: instance.light_links,
+            ..AovSample::default()
+        }
+    }
+    /// Light arriving along `ray` from one random path
+    pub fn trace<S: PixelSampler>(&self, scene: &Scene, ray: &Ray, sampler: &mut S) -> Color {
+        self.trace_primary(scene, ray, sampler).0
+    }
+    /// Light arriving along `ray` from one random path, and the surface `ray` hits first
+    fn trace_primary<S: PixelSampler>(&self, scene: &Scene, ray: &Ray, sampler: &mut S) -> (Color, Option<Hit>) {
+        let hit = Hit::find(scene, &self.tlas, ray, RayKind::Camera, self.settings.triangle_intersector);
+        let radiance = match self.integrator {
+            _ if self.spectral => self.trace_spectral(scene, ray, hit, sampler),
+            Integrator::Path => self.trace_path(scene, ray, hit, sampler, true, None).0,
+            Integrator::Bidirectional => {
+                let mut radiance = self.trace_bidirectional(scene, ray, hit, sampler);
+                if !scene.lights.is_empty() || self.environment.is_some() {
+                    radiance += self.trace_path(scene, ray, hit, sampler, false, None).0;
+                }
+                radiance
+            }
+        };
+        (self.settings.clamp_sample(radiance), hit)
+    }
+    /// Light arriving along `ray`, whose closest surface is `first`, from one random path
+    /// carrying a few wavelengths
+    fn trace_spectral<S: PixelSampler>(&self, scene: &Scene, ray: &Ray, first: Option<Hit>, sampler: &mut S) -> Color {
+        let wavelengths = Wavelengths::sample(sampler.next_1d());
+        let (shared, dispersed) = self.trace_path(scene, ray, first, sampler, true, Some(wavelengths.hero()));
+        let mut values = wavelengths.lambda.map(|wavelength| shared.at_wavelength(wavelength));
+        // Light found after the path split only reached the hero wavelength, which stands
+        // in for all of them
+        values[0] += dispersed.at_wavelength(wavelengths.hero()) * WAVELENGTH_COUNT as f32;
+        wavelengths.to_color(values)
+    }
+    /// Light arriving along `ray`, whose closest surface is `first`, from a path grown from
+    /// the camera, leaving out the area lights unless `area_lights` is set.
+    ///
+    /// Light found after refracting through dispersive glass at the angle for `wavelength`
+    /// is returned apart from the rest, as it only holds for that wavelength.
+    fn trace_path<S: PixelSampler>(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        first: Option<Hit>,
+        sampler: &mut S,
+        area_lights: bool,
+        wavelength: Option<f32>,
+    ) -> (Color, Color) {
+        // Without area lights the remaining lights are always found both ways
+        let light_sampling = if area_lights { self.light_sampling } else { LightSampling::Mis };
+        let mut first = Some(first);
+        let mut ray = *ray;
+        let mut radiance = [Color::black(); 2];
+        let mut dispersed = false;
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        // Density of the bounce that picked `ray`, `None` for the camera ray
+        let mut bsdf_pdf: Option<f32> = None;
+        // Light linking groups of the surface `ray` left, the camera sees every light
+        let mut links = ALL_LINKS;
+        let mut depth = 0;
+        loop {
+            // Only the first hit is seen by the camera, and it is passed in
+            let (kind, intersector) = (RayKind::Reflection, self.settings.triangle_intersector);
+            let hit = first.take().unwrap_or_else(|| Hit::find(scene, &self.tlas, &ray, kind, intersector));
+            let max_t = hit.map_or(f32::INFINITY, |hit| hit.t);
+            if let Some((light, _)) = self.hit_light(scene, &ray, max_t) {
+                if area_lights && scene.area_lights[light].links_to(links) {
+                    let light_pdf = self.light_pdf(scene, Some(light), ray.origin, ray.direction, true);
+                    let weight = emission_weight(light_sampling, bsdf_pdf, light_pdf);
+                    let light = throughput * scene.area_lights[light].radiance() * weight;
+                    radiance[dispersed as usize] += self.settings.clamp_path(depth, light);
+                }
+                break;
+            }
+            let hit = match hit {
+                Some(hit) => hit,
+                None => {
+                    if let Some(environment) = &self.environment {
+                        let light_pdf = self.light_pdf(scene, None, ray.origin, ray.direction, area_lights);
+                        let weight = emission_weight(light_sampling, bsdf_pdf, light_pdf);
+                        let light = throughput * environment.radiance(ray.direction.normalize()) * weight;
+                        radiance[dispersed as usize] += self.settings.clamp_path(depth, light);
+                    }
+                    break;
+                }
+            };
+            let bsdf = Bsdf::at(scene, &hit, wavelength);
+            let outgoing = -ray.direction.normalize();
+            let origin = hit.offset(hit.normal, &self.settings);
+            let direct = throughput * self.point_lights(scene, &bsdf, outgoing, hit.position, origin);
+            radiance[dispersed as usize] += self.settings.clamp_path(depth + 1, direct);
+            if light_sampling != LightSampling::Bsdf {
+                let direct = self.sample_light(scene, &bsdf, outgoing, origin, sampler, area_lights);
+                radiance[dispersed as usize] += self.settings.clamp_path(depth + 1, throughput * direct);
+            }
+            let choice = sampler.next_1d();
+            let sample = match bsdf.sample(outgoing, choice, sampler.next_2d()) {
+                Some(sample) => sample,
+                None => break,
+            };
+            throughput *= sample.weight;
+            dispersed |= sample.delta && bsdf.dispersive && wavelength.is_some();
+            depth += 1;
+            let survival = self.settings.survival_probability(depth, throughput);
+            if survival <= 0.0 || sampler.next_1d() >= survival {
+                break;
+            }
+            throughput *= 1.0 / survival;
+            // Light sampling can not find light through glass, so it is left to the bounce
+            bsdf_pdf = if sample.delta { None } else { Some(sample.pdf) };
+            links = bsdf.links;
+            ray = Ray::new(hit.offset(sample.direction, &self.settings), sample.direction).with_time(hit.time);
+        }
+        (radiance[0], radiance[1])
+    }
+    /// Light from one randomly picked area light or the environment reaching `origin`,
+    /// reflected towards `outgoing`
+    fn sample_light<S: PixelSampler>(
+        &self,
+        scene: &Scene,
+        bsdf: &Bsdf,
+        outgoing: Vec3f,
+        origin: Vec3f,
+        sampler: &mut S,
+        area_lights: bool,
+    ) -> Color {
+        let count = self.light_count(scene, area_lights);
+        if count == 0 {
+            return Color::black();
+        }
+        let pick = ((sampler.next_1d() * count as f32) as usize).min(count - 1);
+        let (u1, u2) = sampler.next_2d();
+        let light = scene.area_lights.get(pick).filter(|_| area_lights);
+        let (light, direction, distance, radiance) = match light {
+            Some(light) if !light.links_to(bsdf.links) => return Color::black(),
+            Some(light) => match light.sample(origin, u1, u2) {
+                Some((direction, distance)) => (Some(pick), direction, distance, light.radiance()),
+                None => return Color::black(),
+            },
+            None => match &self.environment {
+                Some(environment) => {
+                    let sample = environment.sample(u1, u2);
+                    (None, sample.direction, f32::INFINITY, sample.radiance)
+                }
+                None => return Color::black(),
+            },
+        };
+        let cosine = direction.dot(bsdf.normal);
+        if cosine <= 0.0 || !self.visible(scene, origin, direction, distance, bsdf.time) {
+            return Color::black();
+        }
+        let light_pdf = self.light_pdf(scene, light, origin, direction, area_lights);
+        if light_pdf <= 0.0 {
+            return Color::black();
+        }
+        let weight = if self.light_sampling == LightSampling::Mis || !area_lights {
+            power_heuristic(light_pdf, bsdf.pdf(outgoing, direction))
+        } else {
+            1.0
+        };
+        bsdf.eval(outgoing, direction) * radiance * (cosine * weight / light_pdf)
+    }
+    /// Density of `sample_light` picking the unit `direction` from `origin` towards
+    /// area light `light`, or the environment if `None`, per steradian
+    fn light_pdf(
+        &self,
+        scene: &Scene,
+        light: Option<usize>,
+        origin: Vec3f,
+        direction: Vec3f,
+        area_lights: bool,
+    ) -> f32 {
+        let pick = 1.0 / self.light_count(scene, area_lights).max(1) as f32;
+        match (light, &self.environment) {
+            (Some(light), _) => pick * scene.area_lights[light].pdf(origin),
+            (None, Some(environment)) => pick * environment.pdf(direction.normalize()),
+            (None, None) => 0.0,
+        }
+    }
+    /// Number of lights `sample_light` picks from
+    fn light_count(&self, scene: &Scene, area_lights: bool) -> usize {
+        let environment = self.environment.as_ref().is_some_and(|environment| environment.total() > 0.0);
+        let area_lights = if area_lights { scene.area_lights.len() } else { 0 };
+        area_lights + environment as usize
+    }
+    /// Light from the point lights reaching `position`, reflected towards `outgoing`
+    fn point_lights(&self, scene: &Scene, bsdf: &Bsdf, outgoing: Vec3f, position: Vec3f, origin: Vec3f) -> Color {
+        let mut total = Color::black();
+        for light in scene.lights.iter().filter(|light| light.links_to(bsdf.links)) {
+            let irradiance = light.irradiance(position, bsdf.normal);
+            if irradiance == Color::black() {
+                continue;
+            }
+            let to_light = light.position - origin;
+            let distance = to_light.magnitude();
+            if self.visible(scene, origin, to_light * (1.0 / distance), distance, bsdf.time) {
+                total += bsdf.eval(outgoing, to_light * (1.0 / distance)) * irradiance;
+            }
+        }
+        total
+    }
+    /// Closest area light along `ray` before `max_t`, with the distance to it
+    pub(super) fn hit_light(&self, scene: &Scene, ray: &Ray, max_t: f32) -> Option<(usize, f32)> {
+        let hits = scene.area_lights.iter().enumerate();
+        let hits = hits.filter_map(|(index, light)| light.intersect(ray).map(|t| (index, t)));
+        hits.filter(|&(_, t)| t < max_t).min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+    }
+    /// Check if nothing blocks the unit `direction` from `origin` for `distance` at `time`
+    /// within the shutter
+    pub(super) fn visible(&self, scene: &Scene, origin: Vec3f, direction: Vec3f, distance: f32, time: f32) -> bool {
+        // Stop just short of the light so its own surface does not count
+        let max_t = if distance.is_finite() {
+            distance - self.settings.ray_offset(origin + direction * distance, distance)
+        } else {
+            distance
+        };
+        let ray = Ray::new(origin, direction).with_time(time);
+        let intersector = self.settings.triangle_intersector;
+        !self.tlas.occluded(scene, &ray, max_t, intersector) && self.hit_light(scene, &ray, max_t).is_none()
+    }
+}
+
+/// Weight of light found by a bounce of density `bsdf_pdf` that light sampling finds
+/// with density `light_pdf`
+fn emission_weight(light_sampling: LightSampling, bsdf_pdf: Option<f32>, light_pdf: f32) -> f32 {
+    match (bsdf_pdf, light_sampling) {
+        (None, _) | (_, LightSampling::Bsdf) => 1.0,
+        (Some(_), LightSampling::Light) => 0.0,
+        (Some(bsdf_pdf), LightSampling::Mis) => power_heuristic(bsdf_pdf, light_pdf),
+    }
+}
+
+/// Power heuristic weight, with exponent 2, of a sample taken with density `pdf` when
+/// another strategy would have taken it with density `other_pdf`
+pub fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b > 0.0 {
+        a / (a + b)
+    } else {
+        0.0
+    }
+}
+
+/// Fraction of light reflected by a boundary between media with `eta` the ratio of the index
+/// of refraction on the incident side to the other side, with the cosines of the incident
+/// and refracted angles
+fn fresnel(cos_incident: f32, cos_refracted: f32, eta: f32) -> f32 {
+    let s = (eta * cos_incident - cos_refracted) / (eta * cos_incident + cos_refracted);
+    let p = (cos_incident - eta * cos_refracted) / (cos_incident + eta * cos_refracted);
+    (s * s + p * p) / 2.0
+}
+
+/// Direction picked by `Bsdf::sample`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(super) struct BsdfSample {
+    /// Unit incoming direction
+    pub(super) direction: Vec3f,
+    /// Fraction of the light arriving from `direction` that leaves, with the cosine term,
+    /// divided by `pdf`
+    pub(super) weight: Color,
+    /// Density of picking `direction` per steradian, or the chance of picking it if `delta`
+    pub(super) pdf: f32,
+    /// Picked by the glass lobe, which only reflects and refracts in single directions and is
+    /// left out of `eval` and `pdf`
+    pub(super) delta: bool,
+}
+
+/// Lambertian plus normalized Phong reflection at a surface point, over clear glass.
+///
+/// Directions are unit vectors pointing away from the surface, light arrives from
+/// `incoming` and leaves towards `outgoing`. Both sides give the same reflection, so
+/// paths may be traced either way.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(super) struct Bsdf {
+    diffuse: Color,
+    specular: Color,
+    exponent: f32,
+    /// Probability of sampling the glossy lobe rather than the Lambertian one
+    specular_chance: f32,
+    /// Weight, and probability of sampling, the glass lobe
+    transmission: f32,
+    /// Color of light refracted through the glass
+    tint: Color,
+    /// Ratio of the index of refraction on the side of the normal to the one behind it
+    eta: f32,
+    /// Whether `eta` depends on the wavelength
+    pub(super) dispersive: bool,
+    /// Light linking groups of the surface, deciding which lights reach it
+    pub(super) links: u32,
+    /// Moment within the shutter the surface was hit, for rays leaving it
+    pub(super) time: f32,
+    /// Unit normal on the side light is reflected on
+    pub(super) normal: Vec3f,
+}
+
+impl Bsdf {
+    /// Reflection of the surface of `scene` at `hit`, for light of `wavelength` nanometers
+    /// or of all visible wavelengths if `None`
+    pub(super) fn at(scene: &Scene, hit: &Hit, wavelength: Option<f32>) -> Bsdf {
+        let material = scene.materials.get(scene.instances[hit.instance].material);
+        let material = material.copied().unwrap_or_else(Material::new);
+        let albedo = material.surface_at_point(&scene.textures, hit.uv, hit.position, hit.normal);
+        let mut bsdf = Bsdf::new(&material, albedo, hit.shading_normal);
+        bsdf.links = scene.instances[hit.instance].light_links;
+        bsdf.time = hit.time;
+        let ior = wavelength.map_or(material.ior, |wavelength| material.ior_at(wavelength));
+        bsdf.eta = if hit.front { 1.0 / ior } else { ior };
+        bsdf
+    }
+    fn new(material: &Material, albedo: Color, normal: Vec3f) -> Bsdf {
+        let transmission = material.transparency.clamp(0.0, 1.0);
+        let reflectivity = material.reflectivity.clamp(0.0, 1.0);
+        let diffuse = albedo * ((1.0 - reflectivity) * (1.0 - transmission));
+        let specular = Color::new(1.0, 1.0, 1.0) * (reflectivity * (1.0 - transmission));
+        let roughness = material.roughness.max(0.01);
+        let weights = (diffuse.luminance(), specular.luminance());
+        let total = weights.0 + weights.1;
+        Bsdf {
+            diffuse,
+            specular,
+            exponent: 2.0 / (roughness * roughness) - 2.0,
+            specular_chance: if total > 0.0 { weights.1 / total } else { 0.0 },
+            transmission,
+            tint: albedo,
+            eta: 1.0 / material.ior,
+            dispersive: material.dispersion.is_some() && transmission > 0.0,
+            links: ALL_LINKS,
+            time: 1.0,
+            normal,
+        }
+    }
+    /// Reflected fraction of light arriving from `incoming` leaving towards `outgoing`, per
+    /// steradian and before the cosine term
+    pub(super) fn eval(&self, outgoing: Vec3f, incoming: Vec3f) -> Color {
+        if incoming.dot(self.normal) <= 0.0 || outgoing.dot(self.normal) <= 0.0 {
+            return Color::black();
+        }
+        let lobe = self.mirror(outgoing).dot(incoming).max(0.0).powf(self.exponent);
+        self.diffuse * (1.0 / PI) + self.specular * ((self.exponent + 2.0) / (2.0 * PI) * lobe)
+    }
+    /// Density of `sample` picking `incoming` for `outgoing`, per steradian
+    pub(super) fn pdf(&self, outgoing: Vec3f, incoming: Vec3f) -> f32 {
+        let cosine = incoming.dot(self.normal);
+        if cosine <= 0.0 || outgoing.dot(self.normal) <= 0.0 {
+            return 0.0;
+        }
+        let lobe = self.mirror(outgoing).dot(incoming).max(0.0).powf(self.exponent);
+        let specular = (self.exponent + 1.0) / (2.0 * PI) * lobe;
+        let pdf = self.specular_chance * specular + (1.0 - self.specular_chance) * cosine_hemisphere_pdf(cosine);
+        pdf * (1.0 - self.transmission)
+    }
+    /// Pick an incoming direction for `outgoing`, choosing the lobe with `choice` and the
+    /// direction within it with `(u1, u2)`, `None` if it falls below the surface
+    pub(super) fn sample(&self, outgoing: Vec3f, choice: f32, (u1, u2): (f32, f32)) -> Option<BsdfSample> {
+        if choice < self.transmission {
+            return Some(self.sample_glass(outgoing, choice / self.transmission));
+        }
+        let choice = (choice - self.transmission) / (1.0 - self.transmission);
+        let direction = if choice < self.specular_chance {
+            let local = spherical_direction(u1.powf(1.0 / (self.exponent + 1.0)), 2.0 * PI * u2);
+            OrthonormalBasis::from_normal(self.mirror(outgoing)).to_world(local)
+        } else {
+            OrthonormalBasis::from_normal(self.normal).to_world(cosine_hemisphere(u1, u2))
+        };
+        let cosine = direction.dot(self.normal);
+        let pdf = self.pdf(outgoing, direction);
+        if cosine <= 0.0 || pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            direction,
+            weight: self.eval(outgoing, direction) * (cosine / pdf),
+            pdf,
+            delta: false,
+        })
+    }
+    /// Reflect or refract `outgoing` through the glass, picking between them by the
+    /// Fresnel term with `choice`
+    fn sample_glass(&self, outgoing: Vec3f, choice: f32) -> BsdfSample {
+        let cos_incident = outgoing.dot(self.normal);
+        let sin_squared = self.eta * self.eta * (1.0 - cos_incident * cos_incident).max(0.0);
+        // Past the critical angle everything is reflected
+        let (reflected, cos_refracted) = if sin_squared >= 1.0 {
+            (1.0, 0.0)
+        } else {
+            let cos_refracted = (1.0 - sin_squared).sqrt();
+            (fresnel(cos_incident, cos_refracted, self.eta), cos_refracted)
+        };
+        if choice < reflected {
+            BsdfSample {
+                direction: self.mirror(outgoing),
+                weight: Color::new(1.0, 1.0, 1.0),
+                pdf: self.transmission * reflected,
+                delta: true,
+            }
+        } else {
+            let direction = self.normal * (self.eta * cos_incident - cos_refracted) - outgoing * self.eta;
+            BsdfSample {
+                direction: direction.normalize(),
+                weight: self.tint,
+                pdf: self.transmission * (1.0 - reflected),
+                delta: true,
+            }
+        }
+    }
+    /// Mirror image of `direction` about the normal
+    fn mirror(&self, direction: Vec3f) -> Vec3f {
+        self.normal * (2.0 * self.normal.dot(direction)) - direction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{power_heuristic, Bsdf, Hit, LightSampling, PathTracer};
+    use crate::canvas::{Aov, AovBuffers, Canvas, Color, Texture};
+    use crate::math::{Mat4f, Ray, Vec2f, Vec3f, Vec4f};
+    use crate::model::{
+        cube, BackfaceMode, Material, Mesh, PointLight, RayKind, Scene, SphereLight, TextureSlot, Triangle,
+        TriangleIntersector, Visibility,
+    };
+    use crate::raytracer::{Accumulator, PixelSampler, RandomSampler};
+    use crate::shader::Camera;
+    use assert_approx_eq::assert_approx_eq;
+
+    const MT: TriangleIntersector = TriangleIntersector::MollerTrumbore;
+
+    /// Glossy floor lit by a small light in its mirror direction seen from `camera`
+    fn scene() -> Scene {
+        let corners = [(-10.0, -10.0), (10.0, -10.0), (10.0, 10.0), (-10.0, 10.0)];
+        let [a, b, c, d] = corners.map(|(x, z)| Vec3f::new(x, 0.0, z));
+        let mut material = Material::new();
+        material.surface = Color::new(0.5, 0.5, 0.5);
+        material.reflectivity = 0.5;
+        material.roughness = 0.3;
+        Scene::builder()
+            .mesh(Mesh::new(vec![Triangle::new(a, c, b), Triangle::new(a, d, c)]))
+            .material(material)
+            .area_light(SphereLight::new(Vec3f::new(0.0, 1.2, -2.4), 0.2, 100.0))
+            .build()
+    }
+
+    fn camera() -> Ray {
+        Ray::new(Vec3f::new(0.0, 1.0, 2.0), Vec3f::new(0.0, -1.0, -2.0).normalize())
+    }
+
+    /// Mean and variance of `count` samples of the camera ray
+    fn estimate(scene: &Scene, light_sampling: LightSampling, count: u32) -> (f32, f32) {
+        let mut tracer = PathTracer::new(scene);
+        tracer.light_sampling = light_sampling;
+        let mut sampler = RandomSampler::new(1);
+        let samples: Vec<f32> = (0..count)
+            .map(|index| {
+                sampler.start(0, 0, index);
+                tracer.trace(scene, &camera(), &mut sampler).luminance()
+            })
+            .collect();
+        let mean = samples.iter().sum::<f32>() / count as f32;
+        let variance = samples.iter().map(|sample| (sample - mean) * (sample - mean)).sum::<f32>() / count as f32;
+        (mean, variance)
+    }
+
+    #[test]
+    fn test_power_heuristic() {
+        assert_approx_eq!(power_heuristic(1.0, 1.0), 0.5);
+        assert_approx_eq!(power_heuristic(3.0, 1.0), 0.9);
+        assert_eq!(power_heuristic(2.0, 0.0), 1.0);
+        assert_eq!(power_heuristic(0.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_strategies_agree() {
+        let scene = scene();
+        let (mis, mis_variance) = estimate(&scene, LightSampling::Mis, 20_000);
+        let (light, light_variance) = estimate(&scene, LightSampling::Light, 20_000);
+        let (bsdf, bsdf_variance) = estimate(&scene, LightSampling::Bsdf, 20_000);
+        assert!(mis > 0.0);
+        assert!((light - mis).abs() < 0.05 * mis, "{} {}", light, mis);
+        assert!((bsdf - mis).abs() < 0.1 * mis, "{} {}", bsdf, mis);
+        // The light is small, so finding it by bouncing is far noisier
+        assert!(mis_variance < bsdf_variance * 0.5, "{} {}", mis_variance, bsdf_variance);
+        assert!(mis_variance < light_variance * 1.5, "{} {}", mis_variance, light_variance);
+    }
+
+    #[test]
+    fn test_direct_view() {
+        let scene = scene();
+        let tracer = PathTracer::new(&scene);
+        let mut sampler = RandomSampler::new(0);
+        let ray = Ray::new(Vec3f::new(0.0, 1.2, 0.0), Vec3f::new(0.0, 0.0, -1.0));
+        assert_eq!(tracer.trace(&scene, &ray, &mut sampler), Color::new(100.0, 100.0, 100.0));
+        let up = Ray::new(Vec3f::new(0.0, 1.0, 0.0), Vec3f::UNIT_Y);
+        assert_eq!(tracer.trace(&scene, &up, &mut sampler), Color::black());
+    }
+
+    #[test]
+    fn test_triangle_intersector() {
+        // Shadow rays aimed at the diagonal two triangles of a quad share slip between them
+        // with Möller–Trumbore but never with the watertight test
+        let corners = [(-1.3, -0.7), (1.7, -0.9), (0.9, 1.1), (-1.1, 1.3)];
+        let [a, b, c, d] = corners.map(|(x, z)| Vec3f::new(x, 0.1, z));
+        let mut scene = Scene::new();
+        let mesh = scene.add_mesh(Mesh::new(vec![Triangle::new(a, c, b), Triangle::new(a, d, c)]));
+        let material = scene.add_material(Material::new());
+        scene.add_instance(mesh, material, Mat4f::identity());
+        let mut tracer = PathTracer::new(&scene);
+        let leaks = |tracer: &PathTracer| {
+            (1..1000)
+                .filter(|&index| {
+                    let edge = a + (c - a) * (index as f32 / 1000.0);
+                    let below = edge + Vec3f::new(0.3, -1.0, 0.2);
+                    tracer.visible(&scene, below, (edge - below).normalize(), 2.0, 0.0)
+                })
+                .count()
+        };
+        assert!(leaks(&tracer) > 0);
+        tracer.settings.triangle_intersector = TriangleIntersector::Watertight;
+        assert_eq!(leaks(&tracer), 0);
+    }
+
+    #[test]
+    fn test_aovs() {
+        // Looking along the floor, the bottom row sees it and the top row the empty sky
+        let mut scene = scene();
+        scene.instances[0].light_links = 2;
+        let camera = Camera::new(Vec3f::new(0.0, 1.0, 5.0), -Vec3f::UNIT_Z, Vec3f::UNIT_Y);
+        let tracer = PathTracer::new(&scene);
+        let mut accumulator = Accumulator::new(8, 6);
+        let enabled = [Aov::Albedo, Aov::Normal, Aov::Depth, Aov::ObjectId, Aov::MaterialId, Aov::LightLinks];
+        let mut aovs = AovBuffers::new(8, 6, &enabled);
+        let mut sampler = RandomSampler::new(0);
+        tracer.render_pass_with_aovs(&scene, &camera, &mut accumulator, &mut sampler, Some(&mut aovs));
+        assert_eq!(accumulator.passes(), 1);
+        let floor = aovs.get(4, 5);
+        assert_eq!(floor.albedo, Color::new(0.5, 0.5, 0.5));
+        assert_eq!(floor.normal, Vec3f::UNIT_Y);
+        let ray = camera.ray(4, 5, 8, 6);
+        assert_approx_eq!(floor.depth, ray.direction.normalize().y.recip().abs(), 1e-4);
+        assert_eq!((floor.object_id, floor.material_id, floor.light_links), (1, 1, 2));
+        let sky = aovs.get(4, 0);
+        assert!(sky.depth.is_infinite());
+        assert_eq!((sky.object_id, sky.material_id), (0, 0));
+    }
+
+    #[test]
+    fn test_light_linking() {
+        // Unlinked from the floor the light goes dark in its reflection but stays visible
+        let mut scene = scene();
+        scene.area_lights[0].links = 2;
+        assert_eq!(estimate(&scene, LightSampling::Mis, 100), (0.0, 0.0));
+        let tracer = PathTracer::new(&scene);
+        let mut sampler = RandomSampler::new(0);
+        let ray = Ray::new(Vec3f::new(0.0, 1.2, 0.0), Vec3f::new(0.0, 0.0, -1.0));
+        assert_eq!(tracer.trace(&scene, &ray, &mut sampler), Color::new(100.0, 100.0, 100.0));
+        scene.instances[0].light_links = 3;
+        assert!(estimate(&scene, LightSampling::Mis, 100).0 > 0.0);
+    }
+
+    #[test]
+    fn test_visibility() {
+        // Hidden from the camera the floor neither shows nor reflects the light
+        let mut scene = scene();
+        scene.instances[0].visibility.camera = false;
+        assert_eq!(estimate(&scene, LightSampling::Mis, 100), (0.0, 0.0));
+        scene.instances[0].visibility = Visibility::ALL;
+        assert!(estimate(&scene, LightSampling::Mis, 100).0 > 0.0);
+    }
+
+    #[test]
+    fn test_ray_bias_scales() {
+        // A floor far from the origin, lit from above and seen from across the scene
+        let mut scene = scene();
+        scene.area_lights.clear();
+        scene.instances[0].transform = Mat4f::translation(Vec3f::new(0.0, -1e5, -1e5));
+        scene.materials[0].reflectivity = 0.0;
+        scene.add_light(PointLight::new(Vec3f::new(0.0, -99000.0, -1e5), 1e6));
+        let count = |tracer: &PathTracer| {
+            let mut sampler = RandomSampler::new(0);
+            (0..200)
+                .filter(|&index| {
+                    let target = Vec3f::new(index as f32 * 0.09 - 9.0, -1e5, -1e5 + (index % 7) as f32);
+                    sampler.start(0, 0, index);
+                    tracer.trace(&scene, &Ray::new(Vec3f::ZERO, target.normalize()), &mut sampler) == Color::black()
+                })
+                .count()
+        };
+        let mut tracer = PathTracer::new(&scene);
+        tracer.settings.max_depth = 1;
+        assert_eq!(count(&tracer), 0);
+        // A fixed bias suited to a unit sized scene lets the floor shadow itself
+        tracer.settings.ray_bias = 1e-3;
+        tracer.settings.ray_bias_scale = 0.0;
+        assert!(count(&tracer) > 0);
+    }
+
+    #[test]
+    fn test_backface_modes() {
+        // Looking up at the floor from below sees its back
+        let mut scene = scene();
+        let up = Ray::new(Vec3f::new(0.0, -1.0, 0.5), Vec3f::UNIT_Y);
+        let hit = |scene: &Scene| Hit::find(scene, &PathTracer::new(scene).tlas, &up, RayKind::Camera, MT);
+        let flipped = hit(&scene).unwrap();
+        assert!(!flipped.front);
+        assert_eq!(flipped.normal, -Vec3f::UNIT_Y);
+        scene.materials[0].backface = BackfaceMode::Interior;
+        let interior = hit(&scene).unwrap();
+        assert!(!interior.front);
+        assert_eq!(interior.normal, Vec3f::UNIT_Y);
+        // Which reflects none of the light below it
+        scene.area_lights[0].position = Vec3f::new(0.0, -1.0, -1.0);
+        let tracer = PathTracer::new(&scene);
+        let mut sampler = RandomSampler::new(0);
+        for index in 0..100 {
+            sampler.start(0, 0, index);
+            assert_eq!(tracer.trace(&scene, &up, &mut sampler), Color::black());
+        }
+        scene.materials[0].backface = BackfaceMode::Skip;
+        assert!(hit(&scene).is_none());
+    }
+
+    #[test]
+    fn test_normal_map() {
+        // Shading bends with the map while rays still leave the flat floor
+        let mut scene = scene();
+        let mut image = Canvas::new(1, 1);
+        image.fill(Color::new(1.0, 0.5, 1.0));
+        let texture = scene.add_texture(Texture::new(image));
+        scene.materials[0].normal_texture = Some(TextureSlot::new(texture));
+        let tracer = PathTracer::new(&scene);
+        let down = Ray::new(Vec3f::new(0.0, 1.0, 0.0), -Vec3f::UNIT_Y);
+        let hit = Hit::find(&scene, tracer.tlas(), &down, RayKind::Camera, MT).unwrap();
+        assert_eq!(hit.normal, Vec3f::UNIT_Y);
+        assert_approx_eq!(hit.shading_normal.dot(Vec3f::UNIT_Y), 0.5f32.sqrt());
+        assert_eq!(Bsdf::at(&scene, &hit, None).normal, hit.shading_normal);
+    }
+
+    #[test]
+    fn test_motion_blur() {
+        // The floor was a unit lower at the start of the shutter
+        let mut scene = scene();
+        scene.instances[0].previous_transform = Some(Mat4f::translation(Vec3f::new(0.0, -1.0, 0.0)));
+        let tracer = PathTracer::new(&scene);
+        let down = Ray::new(Vec3f::new(0.0, 1.0, 0.0), -Vec3f::UNIT_Y);
+        let t = |time| Hit::find(&scene, tracer.tlas(), &down.with_time(time), RayKind::Camera, MT).unwrap().t;
+        assert_eq!(t(1.0), 1.0);
+        assert_eq!(t(0.0), 2.0);
+        assert_approx_eq!(t(0.5), 1.5);
+        // A cube sliding in from the left leaves a trail only when blurred
+        let mut scene = Scene::new();
+        let mesh = scene.add_mesh(cube(2.0));
+        let material = scene.add_material(Material::new());
+        let instance = scene.add_instance(mesh, material, Mat4f::identity());
+        scene.instances[instance].previous_transform = Some(Mat4f::translation(Vec3f::new(-3.0, 0.0, 0.0)));
+        scene.add_area_light(SphereLight::new(Vec3f::new(0.0, 4.0, 4.0), 0.5, 100.0));
+        let camera = Camera::new(Vec3f::new(0.0, 0.0, 5.0), Vec3f::new(0.0, 0.0, -1.0), Vec3f::UNIT_Y);
+        let trail = camera.project(Vec4f::from_parts(-1.5, 0.0, 1.0, 1.0), 32, 24).unwrap();
+        let (x, y) = (trail.x as usize, trail.y as usize);
+        let mut tracer = PathTracer::new(&scene);
+        for &motion_blur in &[false, true] {
+            tracer.motion_blur = motion_blur;
+            let mut accumulator = Accumulator::new(32, 24);
+            let mut sampler = RandomSampler::new(0);
+            for _ in 0..16 {
+                tracer.render_pass(&scene, &camera, &mut accumulator, &mut sampler);
+            }
+            assert_eq!(accumulator.get(x, y).luminance() > 0.0, motion_blur);
+            assert!(accumulator.get(16, 12).luminance() > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_spectral_matches_rgb() {
+        let scene = scene();
+        let mut tracer = PathTracer::new(&scene);
+        tracer.spectral = true;
+        let mut sampler = RandomSampler::new(2);
+        let count = 20_000;
+        let mut total = Color::black();
+        for index in 0..count {
+            sampler.start(0, 0, index);
+            total += tracer.trace(&scene, &camera(), &mut sampler) * (1.0 / count as f32);
+        }
+        let (rgb, _) = estimate(&scene, LightSampling::Mis, 20_000);
+        for channel in [total.r.value(), total.g.value(), total.b.value()] {
+            assert!((channel - rgb).abs() < 0.05 * rgb, "{} {}", channel, rgb);
+        }
+    }
+
+    #[test]
+    fn test_glass() {
+        // Light seen through a glass cube loses about 4% at each face
+        let mut scene = Scene::new();
+        let mesh = scene.add_mesh(cube(1.0));
+        let mut material = Material::new();
+        material.transparency = 1.0;
+        let material = scene.add_material(material);
+        scene.add_instance(mesh, material, Mat4f::identity());
+        scene.add_area_light(SphereLight::new(Vec3f::new(0.0, 0.0, -3.0), 0.5, 100.0));
+        let tracer = PathTracer::new(&scene);
+        let mut sampler = RandomSampler::new(4);
+        let ray = Ray::new(Vec3f::new(0.0, 0.0, 3.0), Vec3f::new(0.0, 0.0, -1.0));
+        let count = 4000;
+        let total: f32 = (0..count)
+            .map(|index| {
+                sampler.start(0, 0, index);
+                tracer.trace(&scene, &ray, &mut sampler).luminance()
+            })
+            .sum();
+        assert_approx_eq!(total / count as f32, 92.3, 1.5);
+    }
+
+    #[test]
+    fn test_dispersion() {
+        let mut scene = Scene::new();
+        let mut material = Material::new();
+        material.transparency = 1.0;
+        material.dispersion = Some(20.0);
+        let mesh = scene.add_mesh(cube(1.0));
+        let material = scene.add_material(material);
+        scene.add_instance(mesh, material, Mat4f::identity());
+        let hit = Hit {
+            t: 1.0,
+            distance: 1.0,
+            position: Vec3f::ZERO,
+            normal: Vec3f::UNIT_Y,
+            shading_normal: Vec3f::UNIT_Y,
+            front: true,
+            uv: Vec2f::from_parts(0.0, 0.0),
+            instance: 0,
+            time: 1.0,
+        };
+        let outgoing = Vec3f::new(1.0, 1.0, 0.0).normalize();
+        let refract = |wavelength| {
+            let bsdf = Bsdf::at(&scene, &hit, wavelength);
+            let sample = bsdf.sample(outgoing, 0.99, (0.5, 0.5)).unwrap();
+            assert!(sample.delta);
+            sample.direction
+        };
+        // Snell's law at 45 degrees
+        let direction = refract(None);
+        assert_approx_eq!(direction.x, -outgoing.x / 1.5);
+        assert!(direction.y < 0.0);
+        // Blue bends further towards the normal than red
+        assert!(refract(Some(420.0)).x.abs() < refract(Some(680.0)).x.abs());
+        assert!(Bsdf::at(&scene, &hit, None).dispersive);
+    }
+}
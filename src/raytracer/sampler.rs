@@ -0,0 +1,209 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::hash;
+use crate::math::{owen_scramble, scrambled_halton, scrambled_sobol};
+
+///
+/// Source of the random numbers a path tracer draws for each sample of a Pixel.
+///
+/// Every sample starts its own sequence with `start`, so samples can be taken in any order
+/// and on any thread and still give the same image.
+///
+pub trait PixelSampler {
+    /// Start the numbers for sample `index` of Pixel (x, y)
+    fn start(&mut self, x: usize, y: usize, index: u32);
+    /// Next number in [0, 1)
+    fn next_1d(&mut self) -> f32;
+    /// Next pair of numbers in [0, 1), for picking directions and points on lights
+    fn next_2d(&mut self) -> (f32, f32) {
+        (self.next_1d(), self.next_1d())
+    }
+}
+
+///
+/// Independent pseudo random numbers, the baseline other samplers are compared against
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RandomSampler {
+    seed: u32,
+    state: u32,
+}
+
+impl RandomSampler {
+    /// Create new RandomSampler, different seeds give different noise
+    pub fn new(seed: u32) -> RandomSampler {
+        RandomSampler { seed, state: hash(seed) }
+    }
+}
+
+impl PixelSampler for RandomSampler {
+    fn start(&mut self, x: usize, y: usize, index: u32) {
+        self.state = hash(self.seed ^ hash(x as u32 ^ hash(y as u32 ^ hash(index))));
+    }
+    fn next_1d(&mut self) -> f32 {
+        self.state = hash(self.state.wrapping_add(0x9e37_79b9));
+        // Top 24 bits, which a f32 holds exactly, so the result stays below 1
+        (self.state >> 8) as f32 / 16_777_216.0
+    }
+}
+
+/// Prime bases of the Halton dimensions
+const PRIMES: [u32; 16] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53];
+
+///
+/// Scrambled Halton sequence, each dimension using the next prime base.
+///
+/// The samples of a Pixel spread evenly over every dimension, so images converge visibly
+/// faster than with `RandomSampler`. Each Pixel scrambles the digits differently to keep
+/// neighbours from sharing a pattern. Past 16 dimensions the bases start over with new
+/// scrambles.
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct HaltonSampler {
+    seed: u32,
+    pixel: u32,
+    index: u32,
+    dimension: u32,
+}
+
+impl HaltonSampler {
+    /// Create new HaltonSampler, different seeds give different noise
+    pub fn new(seed: u32) -> HaltonSampler {
+        HaltonSampler {
+            seed,
+            ..HaltonSampler::default()
+        }
+    }
+}
+
+impl PixelSampler for HaltonSampler {
+    fn start(&mut self, x: usize, y: usize, index: u32) {
+        self.pixel = hash(self.seed ^ hash(x as u32 ^ hash(y as u32)));
+        self.index = index;
+        self.dimension = 0;
+    }
+    fn next_1d(&mut self) -> f32 {
+        let base = PRIMES[self.dimension as usize % PRIMES.len()];
+        let value = scrambled_halton(self.index, base, hash(self.pixel ^ hash(self.dimension)));
+        self.dimension += 1;
+        value
+    }
+}
+
+///
+/// Owen scrambled Sobol sequence.
+///
+/// Every pair of numbers comes from the first two Sobol dimensions, which spread a power of
+/// two samples evenly over the unit square, so render a power of two passes for the best
+/// result. Each pair scrambles the points and shuffles their order by its own seed, keeping
+/// the pairs independent of each other.
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SobolSampler {
+    seed: u32,
+    pixel: u32,
+    index: u32,
+    dimension: u32,
+}
+
+impl SobolSampler {
+    /// Create new SobolSampler, different seeds give different noise
+    pub fn new(seed: u32) -> SobolSampler {
+        SobolSampler {
+            seed,
+            ..SobolSampler::default()
+        }
+    }
+    /// Seed of the current dimension, with its shuffled sample index
+    fn advance(&mut self) -> (u32, u32) {
+        let seed = hash(self.pixel ^ hash(self.dimension));
+        self.dimension += 1;
+        (seed, owen_scramble(self.index, seed))
+    }
+}
+
+impl PixelSampler for SobolSampler {
+    fn start(&mut self, x: usize, y: usize, index: u32) {
+        self.pixel = hash(self.seed ^ hash(x as u32 ^ hash(y as u32)));
+        self.index = index;
+        self.dimension = 0;
+    }
+    fn next_1d(&mut self) -> f32 {
+        let (seed, index) = self.advance();
+        scrambled_sobol(index, 0, hash(seed))
+    }
+    fn next_2d(&mut self) -> (f32, f32) {
+        let (seed, index) = self.advance();
+        (scrambled_sobol(index, 0, hash(seed)), scrambled_sobol(index, 1, hash(seed ^ 1)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HaltonSampler, PixelSampler, RandomSampler, SobolSampler};
+
+    #[test]
+    fn test_random() {
+        let mut sampler = RandomSampler::new(7);
+        sampler.start(3, 4, 0);
+        let first: Vec<f32> = (0..4).map(|_| sampler.next_1d()).collect();
+        sampler.start(3, 4, 1);
+        assert_ne!(sampler.next_1d(), first[0]);
+        sampler.start(3, 4, 0);
+        assert_eq!(sampler.next_2d(), (first[0], first[1]));
+        let mean = (0..10_000).map(|_| sampler.next_1d()).sum::<f32>() / 10_000.0;
+        assert!((mean - 0.5).abs() < 0.01);
+        assert!((0..1000).all(|_| (0.0..1.0).contains(&sampler.next_1d())));
+    }
+
+    /// Root mean square error over many Pixels of estimating the area of a quarter disk
+    /// after a 1D draw with `samples` samples
+    fn error<S: PixelSampler>(sampler: &mut S, samples: u32) -> f32 {
+        let pixels = 256;
+        let mut total = 0.0;
+        for x in 0..pixels {
+            let mut inside = 0;
+            for index in 0..samples {
+                sampler.start(x, 0, index);
+                sampler.next_1d();
+                let (u, v) = sampler.next_2d();
+                if u * u + v * v < 1.0 {
+                    inside += 1;
+                }
+            }
+            let estimate = inside as f32 / samples as f32;
+            total += (estimate - std::f32::consts::FRAC_PI_4).powi(2);
+        }
+        (total / pixels as f32).sqrt()
+    }
+
+    #[test]
+    fn test_low_discrepancy() {
+        let random = error(&mut RandomSampler::new(1), 64);
+        let halton = error(&mut HaltonSampler::new(1), 64);
+        let sobol = error(&mut SobolSampler::new(1), 64);
+        assert!(halton < random * 0.5, "{} {}", halton, random);
+        assert!(sobol < random * 0.5, "{} {}", sobol, random);
+        let mut sampler = SobolSampler::new(2);
+        sampler.start(1, 2, 5);
+        let first = (sampler.next_1d(), sampler.next_2d());
+        sampler.start(1, 2, 5);
+        assert_eq!((sampler.next_1d(), sampler.next_2d()), first);
+        sampler.start(1, 2, 6);
+        assert!((0..100).all(|_| (0.0..1.0).contains(&sampler.next_1d())));
+    }
+}
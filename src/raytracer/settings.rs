@@ -0,0 +1,205 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::{Tile, TileOrder};
+use crate::canvas::Color;
+use crate::math::Vec3f;
+use crate::model::TriangleIntersector;
+
+///
+/// Per render quality controls for the Raytracer
+///
+/// Paths are always cut at `max_depth` bounces. From `russian_roulette_depth` onwards
+/// they are terminated at random with a probability based on how much light they can
+/// still carry, and survivors are weighted up to keep the estimate unbiased.
+///
+/// Rays leaving a surface start a little off it along its geometric normal so rounding
+/// does not let them hit it again. Rounding grows with the size of the numbers involved,
+/// so the offset is `ray_bias` plus `ray_bias_scale` times the larger of the distance the
+/// ray that found the point travelled and the point's largest coordinate. Raise them if
+/// surfaces shadow themselves in speckles (acne), lower them if light leaks through thin
+/// walls or into corners.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RenderSettings {
+    /// Maximum number of bounces before a path is terminated
+    pub max_depth: u32,
+    /// Bounce at which Russian roulette starts, paths shorter than this always continue
+    pub russian_roulette_depth: u32,
+    /// Largest channel value a single sample may contribute, `None` disables clamping.
+    ///
+    /// Clamping removes fireflies at the cost of some energy loss (bias).
+    pub firefly_clamp: Option<f32>,
+    /// Largest channel value light that bounced off more than one surface may contribute
+    /// to a sample, `None` disables clamping.
+    ///
+    /// Most fireflies come from rare paths finding a bright light after several bounces,
+    /// so this removes them while leaving directly lit highlights alone.
+    pub indirect_clamp: Option<f32>,
+    /// Ray/Triangle intersection algorithm
+    pub triangle_intersector: TriangleIntersector,
+    /// Width and height of the tiles the image is rendered in
+    pub tile_size: usize,
+    /// Order tiles are rendered in, so previews can show the interesting region first
+    pub tile_order: TileOrder,
+    /// Smallest distance rays leaving a surface start off it, in scene units
+    pub ray_bias: f32,
+    /// Distance rays leaving a surface start off it per unit of the scale of the point
+    pub ray_bias_scale: f32,
+}
+
+impl RenderSettings {
+    /// Create default RenderSettings
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Probability of a path continuing after `depth` bounces with the given throughput.
+    ///
+    /// Divide the throughput of a surviving path by this probability.
+    pub fn survival_probability(&self, depth: u32, throughput: Color) -> f32 {
+        if depth >= self.max_depth {
+            0.0
+        } else if depth < self.russian_roulette_depth {
+            1.0
+        } else {
+            let max = throughput.r.value().max(throughput.g.value()).max(throughput.b.value());
+            max.clamp(0.05, 1.0)
+        }
+    }
+    /// Clamp a sample to `firefly_clamp`, scaling all channels to keep its hue.
+    pub fn clamp_sample(&self, sample: Color) -> Color {
+        match self.firefly_clamp {
+            Some(limit) => clamp_channels(sample, limit),
+            None => sample,
+        }
+    }
+    /// Clamp light that reached the camera off `surfaces` surfaces to `indirect_clamp` if
+    /// it bounced off more than one, scaling all channels to keep its hue.
+    pub fn clamp_path(&self, surfaces: u32, light: Color) -> Color {
+        match self.indirect_clamp {
+            Some(limit) if surfaces > 1 => clamp_channels(light, limit),
+            _ => light,
+        }
+    }
+    /// Distance to start rays off the surface at `position`, found by a ray that travelled
+    /// `distance`
+    pub fn ray_offset(&self, position: Vec3f, distance: f32) -> f32 {
+        let scale = position.x.abs().max(position.y.abs()).max(position.z.abs()).max(distance);
+        self.ray_bias + self.ray_bias_scale * scale
+    }
+    /// Tiles of a `width` by `height` image in the order they are rendered
+    pub fn tiles(&self, width: usize, height: usize) -> Vec<Tile> {
+        self.tile_order.tiles(width, height, self.tile_size)
+    }
+}
+
+/// Scale `color` down so that no channel exceeds `limit`
+fn clamp_channels(color: Color, limit: f32) -> Color {
+    let max = color.r.value().max(color.g.value()).max(color.b.value());
+    if max > limit {
+        color * (limit / max)
+    } else {
+        color
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            max_depth: 8,
+            russian_roulette_depth: 3,
+            firefly_clamp: None,
+            indirect_clamp: None,
+            triangle_intersector: TriangleIntersector::default(),
+            tile_size: 32,
+            tile_order: TileOrder::default(),
+            ray_bias: 1e-4,
+            ray_bias_scale: 1e-4,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Color, RenderSettings, TileOrder, Vec3f};
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_survival_probability() {
+        let settings = RenderSettings {
+            max_depth: 5,
+            russian_roulette_depth: 2,
+            ..RenderSettings::default()
+        };
+        let throughput = Color::new(0.5, 0.25, 0.1);
+        assert_eq!(settings.survival_probability(0, throughput), 1.0);
+        assert_eq!(settings.survival_probability(1, throughput), 1.0);
+        assert_approx_eq!(settings.survival_probability(2, throughput), 0.5);
+        assert_approx_eq!(settings.survival_probability(4, Color::new(0.0, 0.01, 0.0)), 0.05);
+        assert_approx_eq!(settings.survival_probability(4, Color::new(3.0, 0.0, 0.0)), 1.0);
+        assert_eq!(settings.survival_probability(5, throughput), 0.0);
+    }
+
+    #[test]
+    fn test_clamp_sample() {
+        let mut settings = RenderSettings::new();
+        let sample = Color::new(40.0, 20.0, 1.0);
+        assert_approx_eq!(settings.clamp_sample(sample).r.value(), 40.0);
+        settings.firefly_clamp = Some(10.0);
+        let clamped = settings.clamp_sample(sample);
+        assert_approx_eq!(clamped.r.value(), 10.0);
+        assert_approx_eq!(clamped.g.value(), 5.0);
+        assert_approx_eq!(clamped.b.value(), 0.25);
+        let dim = Color::new(0.5, 0.5, 0.5);
+        assert_approx_eq!(settings.clamp_sample(dim).g.value(), 0.5);
+    }
+
+    #[test]
+    fn test_clamp_path() {
+        let mut settings = RenderSettings::new();
+        let light = Color::new(40.0, 20.0, 1.0);
+        assert_eq!(settings.clamp_path(3, light), light);
+        settings.indirect_clamp = Some(10.0);
+        assert_eq!(settings.clamp_path(0, light), light);
+        assert_eq!(settings.clamp_path(1, light), light);
+        let clamped = settings.clamp_path(2, light);
+        assert_approx_eq!(clamped.r.value(), 10.0);
+        assert_approx_eq!(clamped.g.value(), 5.0);
+    }
+
+    #[test]
+    fn test_ray_offset() {
+        let settings = RenderSettings {
+            ray_bias: 1e-3,
+            ray_bias_scale: 1e-4,
+            ..RenderSettings::default()
+        };
+        assert_approx_eq!(settings.ray_offset(Vec3f::ZERO, 0.0), 1e-3);
+        assert_approx_eq!(settings.ray_offset(Vec3f::new(1.0, -2.0, 0.5), 5.0), 1.5e-3);
+        assert_approx_eq!(settings.ray_offset(Vec3f::new(0.0, -1e4, 0.0), 5.0), 1.001);
+    }
+
+    #[test]
+    fn test_tiles() {
+        let mut settings = RenderSettings::new();
+        assert_eq!(settings.tiles(64, 64).len(), 4);
+        settings.tile_size = 16;
+        settings.tile_order = TileOrder::Spiral;
+        let tiles = settings.tiles(48, 48);
+        assert_eq!(tiles.len(), 9);
+        assert_eq!((tiles[0].x, tiles[0].y), (16, 16));
+    }
+}
@@ -0,0 +1,161 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+use super::hash;
+
+/// Rectangle of Pixels rendered as one unit of work
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Order tiles of an image are rendered in
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum TileOrder {
+    /// Row by row from the top left
+    #[default]
+    Scanline,
+    /// Along a Hilbert curve, keeping consecutive tiles next to each other
+    Hilbert,
+    /// Ring by ring outwards from the center, where the subject usually is
+    Spiral,
+    /// Shuffled by `seed`, giving a quick impression of the whole image
+    Random { seed: u32 },
+}
+
+impl TileOrder {
+    /// Split a `width` by `height` image into tiles of `size` by `size` Pixels, clipped at
+    /// the right and bottom edges, in this order
+    pub fn tiles(self, width: usize, height: usize, size: usize) -> Vec<Tile> {
+        let size = size.max(1);
+        let (columns, rows) = (width.div_ceil(size), height.div_ceil(size));
+        let tile = |column: usize, row: usize| {
+            let (x, y) = (column * size, row * size);
+            Tile {
+                x,
+                y,
+                width: size.min(width - x),
+                height: size.min(height - y),
+            }
+        };
+        let mut grid: Vec<(usize, usize)> = (0..rows).flat_map(|row| (0..columns).map(move |column| (column, row))).collect();
+        match self {
+            TileOrder::Scanline => (),
+            TileOrder::Hilbert => {
+                let side = columns.max(rows).next_power_of_two();
+                grid.sort_by_key(|&(column, row)| hilbert_index(side, column, row));
+            }
+            TileOrder::Spiral => {
+                // Twice the offset from the center keeps the grid center on whole numbers
+                let center = (columns as isize - 1, rows as isize - 1);
+                grid.sort_by_key(|&(column, row)| {
+                    let (dx, dy) = (2 * column as isize - center.0, 2 * row as isize - center.1);
+                    let ring = dx.abs().max(dy.abs());
+                    // Walk each ring clockwise starting from the top
+                    let angle = (dx as f32).atan2(-dy as f32).rem_euclid(std::f32::consts::PI * 2.0);
+                    (ring, (angle * 1000.0) as u32)
+                });
+            }
+            TileOrder::Random { seed } => {
+                let seed = hash(seed);
+                grid.sort_by_key(|&(column, row)| hash((row * columns + column) as u32 ^ seed));
+            }
+        }
+        grid.into_iter().map(|(column, row)| tile(column, row)).collect()
+    }
+}
+
+/// Distance along a Hilbert curve filling a `side` by `side` grid of (x, y)
+fn hilbert_index(side: usize, mut x: usize, mut y: usize) -> usize {
+    let mut index = 0;
+    let mut s = side / 2;
+    while s > 0 {
+        let (rx, ry) = (usize::from(x & s > 0), usize::from(y & s > 0));
+        index += s * s * ((3 * rx) ^ ry);
+        // Rotate the quadrant so the curve continues where the last one ended
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Tile, TileOrder};
+
+    fn covers(tiles: &[Tile], width: usize, height: usize) -> bool {
+        let mut covered = vec![0; width * height];
+        for tile in tiles {
+            for y in tile.y..tile.y + tile.height {
+                for x in tile.x..tile.x + tile.width {
+                    covered[y * width + x] += 1;
+                }
+            }
+        }
+        covered.iter().all(|&count| count == 1)
+    }
+
+    #[test]
+    fn test_scanline() {
+        let tiles = TileOrder::Scanline.tiles(70, 40, 32);
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(tiles[1], Tile { x: 32, y: 0, width: 32, height: 32 });
+        assert_eq!(tiles[5], Tile { x: 64, y: 32, width: 6, height: 8 });
+        assert!(covers(&tiles, 70, 40));
+    }
+
+    #[test]
+    fn test_hilbert() {
+        let tiles = TileOrder::Hilbert.tiles(64, 64, 16);
+        assert!(covers(&tiles, 64, 64));
+        assert_eq!((tiles[0].x, tiles[0].y), (0, 0));
+        // Every step moves to a neighbouring tile
+        for pair in tiles.windows(2) {
+            let distance = (pair[0].x as isize - pair[1].x as isize).abs() + (pair[0].y as isize - pair[1].y as isize).abs();
+            assert_eq!(distance, 16);
+        }
+        assert!(covers(&TileOrder::Hilbert.tiles(50, 20, 16), 50, 20));
+    }
+
+    #[test]
+    fn test_spiral() {
+        let tiles = TileOrder::Spiral.tiles(80, 80, 16);
+        assert!(covers(&tiles, 80, 80));
+        assert_eq!((tiles[0].x, tiles[0].y), (32, 32));
+        // The first ring surrounds the center before anything further out
+        assert!(tiles[1..9].iter().all(|t| t.x >= 16 && t.x <= 48 && t.y >= 16 && t.y <= 48));
+        assert_eq!((tiles[1].x, tiles[1].y), (32, 16));
+    }
+
+    #[test]
+    fn test_random() {
+        let tiles = TileOrder::Random { seed: 7 }.tiles(64, 64, 8);
+        assert!(covers(&tiles, 64, 64));
+        assert_eq!(tiles, TileOrder::Random { seed: 7 }.tiles(64, 64, 8));
+        assert_ne!(tiles, TileOrder::Random { seed: 8 }.tiles(64, 64, 8));
+        assert_ne!(tiles, TileOrder::Scanline.tiles(64, 64, 8));
+    }
+}
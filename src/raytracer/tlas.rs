@@ -0,0 +1,374 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::bvh::{mesh_bounds, Bvh};
+use super::cut_out;
+use crate::math::{Aabb, Mat4f, Ray};
+use crate::model::{BackfaceMode, Instance, RayKind, Scene, TriangleHit, TriangleIntersector};
+
+/// Ray hit on an instance of a `Scene`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct InstanceHit {
+    /// Index in `Scene::instances`
+    pub instance: usize,
+    /// Index of the triangle in the mesh of the instance
+    pub triangle: usize,
+    pub hit: TriangleHit,
+}
+
+///
+/// Two level acceleration structure over the instances of a `Scene`.
+///
+/// Each mesh gets a bottom level `Bvh` over its triangles in model space, shared by every
+/// instance of it, and a top level `Bvh` is built over the world space bounds of the
+/// instances. Moving instances only needs `refit` of the top level.
+///
+/// Instances with a `previous_transform` and meshes with previous triangles are bounded
+/// over the whole shutter and hit where they are at the `time` of each ray, blurring
+/// them as they move.
+///
+/// ```
+/// use softrender::math::{Mat4f, Ray, Vec3f};
+/// use softrender::model::{cube, Material, Scene, TriangleIntersector};
+/// use softrender::Tlas;
+///
+/// let mut scene = Scene::new();
+/// let mesh = scene.add_mesh(cube(2.0));
+/// let material = scene.add_material(Material::new());
+/// let instance = scene.add_instance(mesh, material, Mat4f::identity());
+/// let mut tlas = Tlas::new(&scene);
+/// scene.instances[instance].transform = Mat4f::translation(Vec3f::from_parts(4.0, 0.0, 0.0));
+/// tlas.refit(&scene);
+/// let ray = Ray::new(Vec3f::from_parts(4.0, 0.0, 5.0), Vec3f::from_parts(0.0, 0.0, -1.0));
+/// assert_eq!(tlas.intersect(&scene, &ray, TriangleIntersector::default()).unwrap().hit.t, 4.0);
+/// ```
+///
+#[derive(Clone, Debug, Default)]
+pub struct Tlas {
+    top: Bvh,
+    blas: Vec<Bvh>,
+    inverses: Vec<Option<Mat4f>>,
+}
+
+impl Tlas {
+    /// Build bottom level structures for every mesh of `scene` and the top level over its
+    /// instances
+    pub fn new(scene: &Scene) -> Tlas {
+        let mut tlas = Tlas {
+            top: Bvh::default(),
+            blas: scene.meshes.iter().map(Bvh::from_mesh).collect(),
+            inverses: Vec::new(),
+        };
+        tlas.rebuild(scene);
+        tlas
+    }
+    /// Top level Bvh, whose primitives are instance indices
+    pub fn top(&self) -> &Bvh {
+        &self.top
+    }
+    /// Bottom level Bvh of mesh `mesh`
+    pub fn blas(&self, mesh: usize) -> Option<&Bvh> {
+        self.blas.get(mesh)
+    }
+    /// World space bounds of the whole scene
+    pub fn bounds(&self) -> Aabb {
+        self.top.bounds()
+    }
+    /// Rebuild the bottom level Bvh of mesh `mesh` after its triangles changed
+    pub fn rebuild_blas(&mut self, scene: &Scene, mesh: usize) {
+        self.blas[mesh] = Bvh::from_mesh(&scene.meshes[mesh]);
+        self.refit(scene);
+    }
+    /// Refit the bottom level Bvh of mesh `mesh` after its vertices moved, such as with
+    /// skinned or morphed meshes, rebuilding it if refitting made it more than `threshold`
+    /// times as costly to trace. Returns true if it was rebuilt.
+    pub fn update_blas(&mut self, scene: &Scene, mesh: usize, threshold: f32) -> bool {
+        let rebuilt = self.blas[mesh].update_bounds(&mesh_bounds(&scene.meshes[mesh]), threshold);
+        self.refit(scene);
+        rebuilt
+    }
+    /// Rebuild the top level, needed when instances are added or removed
+    pub fn rebuild(&mut self, scene: &Scene) {
+        self.top = Bvh::new(&self.instance_bounds(scene));
+        self.update_inverses(scene);
+    }
+    /// Update the top level for instances that moved, keeping its tree
+    pub fn refit(&mut self, scene: &Scene) {
+        crate::profile_scope!("tlas_refit");
+        if scene.instances.len() != self.inverses.len() {
+            return self.rebuild(scene);
+        }
+        self.top.refit_bounds(&self.instance_bounds(scene));
+        self.update_inverses(scene);
+    }
+    /// Closest instance triangle hit by `ray` using `intersector`, honouring material
+    /// cutouts, skipped backfaces and the scene's clip planes and seeing every instance
+    /// whatever its visibility
+    pub fn intersect(&self, scene: &Scene, ray: &Ray, intersector: TriangleIntersector) -> Option<InstanceHit> {
+        self.closest(scene, ray, None, intersector)
+    }
+    /// Closest triangle of the instances visible to rays of kind `kind` hit by `ray`
+    pub fn intersect_visible(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        kind: RayKind,
+        intersector: TriangleIntersector,
+    ) -> Option<InstanceHit> {
+        self.closest(scene, ray, Some(kind), intersector)
+    }
+    /// Check if any instance casting shadows blocks `ray` before `max_t`, for shadow rays
+    pub fn occluded(&self, scene: &Scene, ray: &Ray, max_t: f32, intersector: TriangleIntersector) -> bool {
+        let mut occluded = false;
+        self.top.traverse(ray, max_t, |instance, t_max| {
+            // Any hit will do, reporting it at zero culls the rest of the traversal
+            let kind = Some(RayKind::Shadow);
+            if !occluded && self.intersect_instance(scene, instance, ray, t_max, kind, intersector).is_some() {
+                occluded = true;
+            }
+            if occluded {
+                Some(0.0)
+            } else {
+                None
+            }
+        });
+        occluded
+    }
+    fn closest(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        kind: Option<RayKind>,
+        intersector: TriangleIntersector,
+    ) -> Option<InstanceHit> {
+        let mut closest = None;
+        self.top.traverse(ray, f32::INFINITY, |instance, t_max| {
+            let hit = self.intersect_instance(scene, instance, ray, t_max, kind, intersector)?;
+            closest = Some(hit);
+            Some(hit.hit.t)
+        });
+        closest
+    }
+    /// Closest hit on instance `instance`, skipped if it is not visible to rays of kind `kind`
+    fn intersect_instance(
+        &self,
+        scene: &Scene,
+        instance: usize,
+        ray: &Ray,
+        t_max: f32,
+        kind: Option<RayKind>,
+        intersector: TriangleIntersector,
+    ) -> Option<InstanceHit> {
+        let placement = &scene.instances[instance];
+        if kind.is_some_and(|kind| !placement.visibility.visible_to(kind)) {
+            return None;
+        }
+        let inverse = match placement.previous_transform {
+            Some(_) => placement.transform_at(ray.time).invert_affine()?,
+            None => self.inverses[instance]?,
+        };
+        let (mesh, bvh) = (scene.meshes.get(placement.mesh)?, self.blas.get(placement.mesh)?);
+        let material = scene.materials.get(placement.material);
+        let skip_backfaces = material.is_some_and(|material| material.backface == BackfaceMode::Skip);
+        // An affine transform keeps distances in multiples of the direction the same
+        let origin = ray.origin * inverse;
+        let local = Ray::new(origin, (ray.origin + ray.direction) * inverse - origin).with_time(ray.time);
+        let mut closest = None;
+        bvh.traverse(&local, t_max, |triangle, closest_t| {
+            let shape = mesh.triangle_at(triangle, ray.time);
+            let hit = shape.intersect_with(&local, intersector)?;
+            if hit.t >= closest_t || (skip_backfaces && shape.is_backface(&local)) {
+                return None;
+            }
+            let visible = material.is_none_or(|material| !cut_out(mesh, material, &scene.textures, triangle, &hit));
+            if !visible || scene.clip_planes.is_clipped(ray.at(hit.t)) {
+                return None;
+            }
+            closest = Some(InstanceHit { instance, triangle, hit });
+            Some(hit.t)
+        });
+        closest
+    }
+    fn instance_bounds(&self, scene: &Scene) -> Vec<Aabb> {
+        let blas_bounds = |mesh: usize| self.blas.get(mesh).map_or(Aabb::empty(), Bvh::bounds);
+        let instance_bounds = |instance: &Instance| {
+            let bounds = blas_bounds(instance.mesh);
+            let current = bounds.transform(instance.transform);
+            // Blended transforms keep the corners between where they are at either end
+            instance.previous_transform.map_or(current, |previous| current.union(&bounds.transform(previous)))
+        };
+        scene.instances.iter().map(instance_bounds).collect()
+    }
+    fn update_inverses(&mut self, scene: &Scene) {
+        self.inverses = scene.instances.iter().map(|instance| instance.transform.invert_affine()).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tlas;
+    use crate::math::{ClipPlanes, Mat4f, Ray, Vec3f};
+    use crate::model::{cube, BackfaceMode, Material, RayKind, Scene, TriangleIntersector, Visibility};
+
+    const MT: TriangleIntersector = TriangleIntersector::MollerTrumbore;
+
+    fn down(x: f32) -> Ray {
+        Ray::new(Vec3f::from_parts(x, 0.0, 5.0), Vec3f::from_parts(0.0, 0.0, -1.0))
+    }
+
+    #[test]
+    fn test_instances() {
+        let mut scene = Scene::new();
+        let mesh = scene.add_mesh(cube(2.0));
+        let material = scene.add_material(Material::new());
+        scene.add_instance(mesh, material, Mat4f::identity());
+        let moving = scene.add_instance(mesh, material, Mat4f::translation(Vec3f::from_parts(4.0, 0.0, 1.0)));
+        let mut tlas = Tlas::new(&scene);
+        assert_eq!(tlas.bounds().max, Vec3f::from_parts(5.0, 1.0, 2.0));
+        let hit = tlas.intersect(&scene, &down(4.0), MT).unwrap();
+        assert_eq!((hit.instance, hit.hit.t), (moving, 3.0));
+        assert_eq!(tlas.intersect(&scene, &down(0.0), MT).unwrap().instance, 0);
+        assert!(tlas.intersect(&scene, &down(2.5), MT).is_none());
+        scene.instances[moving].transform = Mat4f::translation(Vec3f::from_parts(2.5, 0.0, 0.0));
+        tlas.refit(&scene);
+        assert_eq!(tlas.intersect(&scene, &down(2.5), MT).unwrap().hit.t, 4.0);
+        assert!(tlas.intersect(&scene, &down(4.0), MT).is_none());
+        assert!(tlas.occluded(&scene, &down(2.5), 10.0, MT));
+        assert!(!tlas.occluded(&scene, &down(2.5), 3.0, MT));
+        // Adding an instance rebuilds the top level
+        scene.add_instance(mesh, material, Mat4f::translation(Vec3f::from_parts(-4.0, 0.0, 0.0)));
+        tlas.refit(&scene);
+        assert_eq!(tlas.intersect(&scene, &down(-4.0), MT).unwrap().instance, 2);
+    }
+
+    #[test]
+    fn test_deforming_mesh() {
+        let mut scene = Scene::new();
+        let mesh = scene.add_mesh(cube(2.0));
+        let material = scene.add_material(Material::new());
+        scene.add_instance(mesh, material, Mat4f::identity());
+        let mut tlas = Tlas::new(&scene);
+        scene.meshes[mesh] = cube(4.0);
+        assert!(!tlas.update_blas(&scene, mesh, f32::INFINITY));
+        assert_eq!(tlas.bounds().max, Vec3f::from_parts(2.0, 2.0, 2.0));
+        assert_eq!(tlas.intersect(&scene, &down(1.5), MT).unwrap().hit.t, 3.0);
+    }
+
+    #[test]
+    fn test_motion() {
+        let mut scene = Scene::new();
+        let mut mesh = cube(2.0);
+        mesh.set_previous_triangles(cube(4.0).triangles().to_vec());
+        let mesh = scene.add_mesh(mesh);
+        let material = scene.add_material(Material::new());
+        let moving = scene.add_instance(mesh, material, Mat4f::translation(Vec3f::from_parts(4.0, 0.0, 0.0)));
+        scene.instances[moving].previous_transform = Some(Mat4f::identity());
+        let tlas = Tlas::new(&scene);
+        // Bounds cover the instance and the mesh over the whole shutter
+        assert_eq!(tlas.bounds().min, Vec3f::from_parts(-2.0, -2.0, -2.0));
+        assert_eq!(tlas.bounds().max, Vec3f::from_parts(6.0, 2.0, 2.0));
+        let at = |x: f32, time: f32| down(x).with_time(time);
+        assert!(tlas.intersect(&scene, &at(0.0, 1.0), MT).is_none());
+        assert_eq!(tlas.intersect(&scene, &at(0.0, 0.0), MT).unwrap().hit.t, 3.0);
+        assert_eq!(tlas.intersect(&scene, &at(4.0, 1.0), MT).unwrap().hit.t, 4.0);
+        // Half way the instance is at x = 2 and the mesh 3 units wide
+        assert_eq!(tlas.intersect(&scene, &at(2.0, 0.5), MT).unwrap().hit.t, 3.5);
+        assert!(tlas.occluded(&scene, &at(0.5, 0.5), 10.0, MT));
+        assert!(!tlas.occluded(&scene, &at(0.0, 0.5), 10.0, MT));
+    }
+
+    #[test]
+    fn test_cutout() {
+        let mut scene = Scene::new();
+        let mesh = scene.add_mesh(cube(2.0));
+        let mut material = Material::new();
+        material.alpha = 0.0;
+        material.alpha_cutoff = Some(0.5);
+        let material = scene.add_material(material);
+        scene.add_instance(mesh, material, Mat4f::identity());
+        let tlas = Tlas::new(&scene);
+        assert!(tlas.intersect(&scene, &down(0.0), MT).is_none());
+        assert!(!tlas.occluded(&scene, &down(0.0), 10.0, MT));
+    }
+
+    #[test]
+    fn test_skip_backfaces() {
+        let mut scene = Scene::new();
+        let mesh = scene.add_mesh(cube(2.0));
+        let mut material = Material::new();
+        material.backface = BackfaceMode::Skip;
+        let material = scene.add_material(material);
+        scene.add_instance(mesh, material, Mat4f::identity());
+        let tlas = Tlas::new(&scene);
+        // From outside only the front face is hit, from inside nothing
+        assert_eq!(tlas.intersect(&scene, &down(0.0), MT).unwrap().hit.t, 4.0);
+        let inside = Ray::new(Vec3f::ZERO, Vec3f::UNIT_Z);
+        assert!(tlas.intersect(&scene, &inside, MT).is_none());
+        assert!(!tlas.occluded(&scene, &inside, 10.0, MT));
+        scene.materials[material].backface = BackfaceMode::Flip;
+        assert_eq!(tlas.intersect(&scene, &inside, MT).unwrap().hit.t, 1.0);
+    }
+
+    #[test]
+    fn test_visibility() {
+        let mut scene = Scene::new();
+        let mesh = scene.add_mesh(cube(2.0));
+        let material = scene.add_material(Material::new());
+        let proxy = scene.add_instance(mesh, material, Mat4f::identity());
+        scene.instances[proxy].visibility = Visibility::SHADOW_ONLY;
+        let tlas = Tlas::new(&scene);
+        assert!(tlas.intersect(&scene, &down(0.0), MT).is_some());
+        assert!(tlas.intersect_visible(&scene, &down(0.0), RayKind::Camera, MT).is_none());
+        assert!(tlas.intersect_visible(&scene, &down(0.0), RayKind::Reflection, MT).is_none());
+        assert!(tlas.occluded(&scene, &down(0.0), 10.0, MT));
+        scene.instances[proxy].visibility = Visibility {
+            shadow: false,
+            ..Visibility::ALL
+        };
+        assert!(tlas.intersect_visible(&scene, &down(0.0), RayKind::Camera, MT).is_some());
+        assert!(!tlas.occluded(&scene, &down(0.0), 10.0, MT));
+    }
+
+    #[test]
+    fn test_clip_planes() {
+        let mut scene = Scene::new();
+        let mesh = scene.add_mesh(cube(2.0));
+        let material = scene.add_material(Material::new());
+        scene.add_instance(mesh, material, Mat4f::identity());
+        let tlas = Tlas::new(&scene);
+        // Cutting the front half away shows the inside of the back face
+        scene.clip_planes.push(ClipPlanes::plane(Vec3f::ZERO, -Vec3f::UNIT_Z));
+        assert_eq!(tlas.intersect(&scene, &down(0.0), MT).unwrap().hit.t, 6.0);
+        assert!(tlas.occluded(&scene, &down(0.0), 10.0, MT));
+        assert!(!tlas.occluded(&scene, &down(0.0), 5.0, MT));
+        scene.clip_planes.push(ClipPlanes::plane(Vec3f::ZERO, Vec3f::UNIT_Z));
+        assert!(tlas.intersect(&scene, &down(0.0), MT).is_none());
+    }
+
+    #[test]
+    fn test_inside() {
+        let mut scene = Scene::new();
+        let mesh = scene.add_mesh(cube(2.0));
+        let material = scene.add_material(Material::new());
+        scene.add_instance(mesh, material, Mat4f::identity());
+        let tlas = Tlas::new(&scene);
+        // Starting inside the bounds, faces beyond the limit must not count
+        let ray = Ray::new(Vec3f::from_parts(0.0, 0.0, 0.5), Vec3f::from_parts(0.0, 0.0, 1.0));
+        assert!(!tlas.occluded(&scene, &ray, 0.25, MT));
+        assert!(tlas.occluded(&scene, &ray, 1.0, MT));
+        assert_eq!(tlas.intersect(&scene, &ray, MT).unwrap().hit.t, 0.5);
+    }
+}
@@ -0,0 +1,447 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Lightweight render graph for multi pass rendering.
+//!
+//! Passes declare the buffers they read and write, the graph works out an execution
+//! order from those dependencies and allocates each buffer just before its first use.
+//!
+//! ```
+//! use softrender::canvas::{Canvas, Color};
+//! use softrender::render_graph::RenderGraph;
+//!
+//! let mut graph = RenderGraph::new(4, 4);
+//! let color = graph.create("color", Canvas::new);
+//! let output = graph.create("output", Canvas::new);
+//! // Declared out of order, the graph runs "main" before "post"
+//! graph.add_pass("post").read(color).write(output).execute(move |ctx| {
+//!     let value = ctx.get(color).get(0, 0);
+//!     ctx.get_mut(output).set(0, 0, value * 0.5);
+//! });
+//! graph.add_pass("main").write(color).execute(move |ctx| {
+//!     ctx.get_mut(color).set(0, 0, Color::white());
+//! });
+//! graph.execute().unwrap();
+//! assert_eq!(graph.get(output).unwrap().get(0, 0), Color::new(0.5, 0.5, 0.5));
+//! ```
+//!
+//! Buffers created with `create_target` carry a `Format`, and passes reading them with
+//! `read_as` are checked against it before anything runs, so a pass expecting depth
+//! cannot silently read a color buffer.
+
+use crate::canvas::Format;
+use crate::Result;
+use std::any::Any;
+use std::error::Error;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Typed reference to a buffer owned by a `RenderGraph`
+pub struct Handle<T> {
+    index: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Handle({})", self.index)
+    }
+}
+
+/// Problem found while scheduling a `RenderGraph`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GraphError {
+    /// The named passes depend on each other in a loop
+    Cycle(Vec<String>),
+    /// A pass reads a buffer expecting a different Format than it was created with
+    Format {
+        pass: String,
+        resource: String,
+        expected: Format,
+        found: Format,
+    },
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphError::Cycle(passes) => write!(f, "render passes form a cycle: {}", passes.join(", ")),
+            GraphError::Format {
+                pass,
+                resource,
+                expected,
+                found,
+            } => write!(f, "pass {} reads {} as {} but it is {}", pass, resource, expected, found),
+        }
+    }
+}
+
+impl Error for GraphError {}
+
+type Execute = Box<dyn FnMut(&mut PassContext)>;
+
+struct Resource {
+    name: String,
+    format: Option<Format>,
+    allocate: Box<dyn Fn(usize, usize) -> Box<dyn Any>>,
+    buffer: Option<Box<dyn Any>>,
+}
+
+struct Pass {
+    name: String,
+    reads: Vec<usize>,
+    writes: Vec<usize>,
+    expects: Vec<(usize, Format)>,
+    execute: Option<Execute>,
+}
+
+///
+/// Set of passes and the buffers flowing between them
+///
+pub struct RenderGraph {
+    dimensions: (usize, usize),
+    resources: Vec<Resource>,
+    passes: Vec<Pass>,
+}
+
+impl RenderGraph {
+    /// Create an empty RenderGraph whose buffers are `width` by `height`
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            dimensions: (width, height),
+            resources: Vec::new(),
+            passes: Vec::new(),
+        }
+    }
+    /// Get Width of the buffers
+    pub fn width(&self) -> usize {
+        self.dimensions.0
+    }
+    /// Get Height of the buffers
+    pub fn height(&self) -> usize {
+        self.dimensions.1
+    }
+    /// Declare a buffer, `allocate` is called with the graph size when it is first needed
+    pub fn create<T: Any, F: Fn(usize, usize) -> T + 'static>(&mut self, name: &str, allocate: F) -> Handle<T> {
+        self.add_resource(name, None, allocate)
+    }
+    /// Declare a buffer holding `format` data, checked against passes reading it with
+    /// `PassBuilder::read_as`
+    pub fn create_target<T, F>(&mut self, name: &str, format: Format, allocate: F) -> Handle<T>
+    where
+        T: Any,
+        F: Fn(usize, usize) -> T + 'static,
+    {
+        self.add_resource(name, Some(format), allocate)
+    }
+    fn add_resource<T, F>(&mut self, name: &str, format: Option<Format>, allocate: F) -> Handle<T>
+    where
+        T: Any,
+        F: Fn(usize, usize) -> T + 'static,
+    {
+        self.resources.push(Resource {
+            name: name.to_string(),
+            format,
+            allocate: Box::new(move |width, height| Box::new(allocate(width, height))),
+            buffer: None,
+        });
+        Handle {
+            index: self.resources.len() - 1,
+            marker: PhantomData,
+        }
+    }
+    /// Declare a pass, configured through the returned `PassBuilder`
+    pub fn add_pass(&mut self, name: &str) -> PassBuilder<'_> {
+        self.passes.push(Pass {
+            name: name.to_string(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+            expects: Vec::new(),
+            execute: None,
+        });
+        let pass = self.passes.len() - 1;
+        PassBuilder { graph: self, pass }
+    }
+    /// Resize the graph, dropping every allocated buffer
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.dimensions = (width, height);
+        for resource in &mut self.resources {
+            resource.buffer = None;
+        }
+    }
+    /// Get a buffer, `None` if no pass has used it yet
+    pub fn get<T: Any>(&self, handle: Handle<T>) -> Option<&T> {
+        self.resources[handle.index]
+            .buffer
+            .as_ref()
+            .and_then(|buffer| buffer.downcast_ref())
+    }
+    /// Get the name of a buffer
+    pub fn name<T>(&self, handle: Handle<T>) -> &str {
+        &self.resources[handle.index].name
+    }
+    /// Get the Format of a buffer, `None` if it was declared with `create`
+    pub fn format<T>(&self, handle: Handle<T>) -> Option<Format> {
+        self.resources[handle.index].format
+    }
+    /// Work out the order passes will run in.
+    ///
+    /// A pass runs after every pass writing a buffer it reads; passes writing the same
+    /// buffer keep the order they were added in.
+    pub fn schedule(&self) -> Result<Vec<&str>> {
+        Ok(self.order()?.into_iter().map(|pass| self.passes[pass].name.as_str()).collect())
+    }
+    /// Run every pass in dependency order, allocating buffers as they are first needed
+    pub fn execute(&mut self) -> Result<()> {
+        let order = self.order()?;
+        let (width, height) = self.dimensions;
+        for pass in order {
+            let Pass { reads, writes, execute, .. } = &mut self.passes[pass];
+            for &index in reads.iter().chain(writes.iter()) {
+                let resource = &mut self.resources[index];
+                if resource.buffer.is_none() {
+                    resource.buffer = Some((resource.allocate)(width, height));
+                }
+            }
+            if let Some(execute) = execute {
+                let mut context = PassContext {
+                    resources: &mut self.resources,
+                    reads,
+                    writes,
+                };
+                execute(&mut context);
+            }
+        }
+        Ok(())
+    }
+    /// Check every `read_as` against the Format its buffer was created with
+    fn validate(&self) -> Result<()> {
+        for pass in &self.passes {
+            for &(index, expected) in &pass.expects {
+                let resource = &self.resources[index];
+                if let Some(found) = resource.format.filter(|&found| found != expected) {
+                    return Err(GraphError::Format {
+                        pass: pass.name.clone(),
+                        resource: resource.name.clone(),
+                        expected,
+                        found,
+                    }
+                    .into());
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Topologically sort passes, preferring declaration order between independent passes
+    fn order(&self) -> Result<Vec<usize>> {
+        self.validate()?;
+        let count = self.passes.len();
+        let mut depends_on = vec![Vec::new(); count];
+        for (pass, current) in self.passes.iter().enumerate() {
+            for (other, earlier) in self.passes.iter().enumerate() {
+                if other == pass {
+                    continue;
+                }
+                let reads_output = current.reads.iter().any(|r| earlier.writes.contains(r));
+                let same_output = other < pass && current.writes.iter().any(|w| earlier.writes.contains(w));
+                if reads_output || same_output {
+                    depends_on[pass].push(other);
+                }
+            }
+        }
+        let mut done = vec![false; count];
+        let mut order = Vec::with_capacity(count);
+        while order.len() < count {
+            let ready = (0..count).find(|&pass| !done[pass] && depends_on[pass].iter().all(|&d| done[d]));
+            match ready {
+                Some(pass) => {
+                    done[pass] = true;
+                    order.push(pass);
+                }
+                None => {
+                    let stuck = (0..count).filter(|&pass| !done[pass]);
+                    let passes = stuck.map(|pass| self.passes[pass].name.clone()).collect();
+                    return Err(GraphError::Cycle(passes).into());
+                }
+            }
+        }
+        Ok(order)
+    }
+}
+
+/// Declares the inputs, outputs and body of a pass
+pub struct PassBuilder<'a> {
+    graph: &'a mut RenderGraph,
+    pass: usize,
+}
+
+impl<'a> PassBuilder<'a> {
+    /// Declare a buffer this pass reads
+    pub fn read<T>(self, handle: Handle<T>) -> Self {
+        self.graph.passes[self.pass].reads.push(handle.index);
+        self
+    }
+    /// Declare a buffer this pass reads as `format` data, such as a depth buffer sampled
+    /// by a later pass. Scheduling fails with a `GraphError::Format` if the buffer was
+    /// created with a different Format
+    pub fn read_as<T>(self, handle: Handle<T>, format: Format) -> Self {
+        self.graph.passes[self.pass].expects.push((handle.index, format));
+        self.read(handle)
+    }
+    /// Declare a buffer this pass writes, which it may also read
+    pub fn write<T>(self, handle: Handle<T>) -> Self {
+        self.graph.passes[self.pass].writes.push(handle.index);
+        self
+    }
+    /// Set the body of this pass
+    pub fn execute<F: FnMut(&mut PassContext) + 'static>(self, execute: F) {
+        self.graph.passes[self.pass].execute = Some(Box::new(execute));
+    }
+}
+
+/// Access to the buffers a pass declared while it runs
+pub struct PassContext<'a> {
+    resources: &'a mut [Resource],
+    reads: &'a [usize],
+    writes: &'a [usize],
+}
+
+impl<'a> PassContext<'a> {
+    /// Get a buffer declared with `read` or `write`.
+    ///
+    /// # Panics
+    /// Panics if the pass did not declare the buffer.
+    pub fn get<T: Any>(&self, handle: Handle<T>) -> &T {
+        let declared = self.reads.contains(&handle.index) || self.writes.contains(&handle.index);
+        assert!(declared, "pass did not declare {}", self.resources[handle.index].name);
+        self.resources[handle.index].buffer.as_ref().unwrap().downcast_ref().unwrap()
+    }
+    /// Get a buffer declared with `write` for modification.
+    ///
+    /// # Panics
+    /// Panics if the pass did not declare the buffer as an output.
+    pub fn get_mut<T: Any>(&mut self, handle: Handle<T>) -> &mut T {
+        let declared = self.writes.contains(&handle.index);
+        assert!(declared, "pass did not declare {} as an output", self.resources[handle.index].name);
+        self.resources[handle.index].buffer.as_mut().unwrap().downcast_mut().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GraphError, RenderGraph};
+    use crate::canvas::{Canvas, Color, Format, IdBuffer};
+    use crate::Error;
+
+    #[test]
+    fn test_schedule() {
+        let mut graph = RenderGraph::new(2, 2);
+        let shadow = graph.create("shadow", Canvas::new);
+        let color = graph.create("color", Canvas::new);
+        let ids = graph.create("ids", IdBuffer::new);
+        let output = graph.create("output", Canvas::new);
+        graph.add_pass("post").read(color).write(output);
+        graph.add_pass("main").read(shadow).write(color).write(ids);
+        graph.add_pass("overlay").read(ids).write(output);
+        graph.add_pass("shadow").write(shadow);
+        assert_eq!(graph.schedule().unwrap(), vec!["shadow", "main", "post", "overlay"]);
+    }
+
+    #[test]
+    fn test_cycle() {
+        let mut graph = RenderGraph::new(2, 2);
+        let a = graph.create("a", Canvas::new);
+        let b = graph.create("b", Canvas::new);
+        graph.add_pass("first").read(a).write(b);
+        graph.add_pass("second").read(b).write(a);
+        graph.add_pass("free");
+        match graph.schedule() {
+            Err(Error::Graph(GraphError::Cycle(passes))) => assert_eq!(passes, vec!["first", "second"]),
+            other => panic!("expected a cycle, got {:?}", other),
+        }
+        assert!(graph.execute().is_err());
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut graph = RenderGraph::new(3, 2);
+        let color = graph.create("color", Canvas::new);
+        let ids = graph.create("ids", IdBuffer::new);
+        let unused = graph.create("unused", Canvas::new);
+        graph.add_pass("main").write(color).write(ids).execute(move |ctx| {
+            ctx.get_mut(color).set(2, 1, Color::white());
+            ctx.get_mut(ids).set(2, 1, 4);
+        });
+        graph.add_pass("tint").read(ids).write(color).execute(move |ctx| {
+            if ctx.get(ids).pick(2, 1).is_some() {
+                let tinted = ctx.get(color).get(2, 1) * Color::bright_red();
+                ctx.get_mut(color).set(2, 1, tinted);
+            }
+        });
+        graph.execute().unwrap();
+        assert_eq!(graph.get(color).unwrap().get(2, 1), Color::bright_red());
+        assert_eq!(graph.get(ids).unwrap().width(), 3);
+        assert!(graph.get(unused).is_none());
+        graph.resize(5, 5);
+        assert!(graph.get(color).is_none());
+        graph.execute().unwrap();
+        assert_eq!(graph.get(color).unwrap().width(), 5);
+    }
+
+    #[test]
+    fn test_formats() {
+        let mut graph = RenderGraph::new(2, 2);
+        let depth = graph.create_target("depth", Format::Depth32F, Canvas::new);
+        let color = graph.create_target("color", Format::Rgb32F, Canvas::new);
+        let ids = graph.create("ids", IdBuffer::new);
+        assert_eq!(graph.format(depth), Some(Format::Depth32F));
+        assert_eq!(graph.format(ids), None);
+        graph.add_pass("main").write(depth).write(color).write(ids);
+        graph.add_pass("fog").read_as(depth, Format::Depth32F).read_as(ids, Format::Id32).write(color);
+        assert_eq!(graph.schedule().unwrap(), vec!["main", "fog"]);
+        // Reading the color buffer where depth is expected is caught before running
+        graph.add_pass("ssao").read_as(color, Format::Depth32F).write(color);
+        let error = GraphError::Format {
+            pass: "ssao".to_string(),
+            resource: "color".to_string(),
+            expected: Format::Depth32F,
+            found: Format::Rgb32F,
+        };
+        assert!(matches!(graph.execute(), Err(Error::Graph(found)) if found == error));
+        assert_eq!(error.to_string(), "pass ssao reads color as depth32f but it is rgb32f");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_undeclared() {
+        let mut graph = RenderGraph::new(2, 2);
+        let color = graph.create("color", Canvas::new);
+        let output = graph.create("output", Canvas::new);
+        graph.add_pass("main").write(color).execute(|_| ());
+        graph.add_pass("post").write(output).execute(move |ctx| {
+            ctx.get(color);
+        });
+        graph.execute().unwrap();
+    }
+}
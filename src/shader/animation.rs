@@ -0,0 +1,247 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+
+//! Camera animation presets for rendering showcase sequences offline.
+//!
+//! A `CameraAnimation` gives the camera at any point of the animation, `frames` samples it
+//! evenly and `presenter::render_sequence` renders and saves each frame.
+//!
+//! ```
+//! use softrender::math::{Aabb, Vec3f};
+//! use softrender::shader::{CameraAnimation, Turntable};
+//!
+//! let bounds = Aabb::from_points(vec![Vec3f::new(-1.0, 0.0, -1.0), Vec3f::new(1.0, 2.0, 1.0)]);
+//! let turntable = Turntable::around(&bounds);
+//! let cameras = turntable.frames(36);
+//! assert_eq!(cameras.len(), 36);
+//! ```
+
+use super::Camera;
+use crate::math::{Aabb, Vec3f};
+
+///
+/// Camera moving over time, from `t` of 0.0 at the start to 1.0 at the end
+///
+pub trait CameraAnimation {
+    /// Camera at time `t`
+    fn camera_at(&self, t: f32) -> Camera;
+    /// `count` cameras evenly spaced over the animation.
+    ///
+    /// The last frame stops one step short of the end, so looping animations such as a
+    /// turntable do not show the first frame twice.
+    fn frames(&self, count: usize) -> Vec<Camera> {
+        (0..count).map(|frame| self.camera_at(frame as f32 / count as f32)).collect()
+    }
+}
+
+///
+/// Camera circling a point at a fixed distance and elevation
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Turntable {
+    /// Point the camera circles and looks at
+    pub center: Vec3f,
+    /// Distance of the camera from `center`
+    pub distance: f32,
+    /// Angle above the horizontal plane in degrees
+    pub elevation: f32,
+    /// Angle around the vertical axis the animation starts at in degrees, 0.0 looking
+    /// along negative Z
+    pub start: f32,
+    /// Number of full circles over the animation, negative turns clockwise seen from above
+    pub revolutions: f32,
+    /// Camera whose field of view, aspect ratio and clip planes are used
+    pub lens: Camera,
+}
+
+impl Turntable {
+    /// Create new Turntable circling `bounds` once, far enough away that the whole box
+    /// stays in view
+    pub fn around(bounds: &Aabb) -> Self {
+        let mut lens = Camera::new(Vec3f::ZERO, -Vec3f::UNIT_Z, Vec3f::UNIT_Y);
+        let radius = (bounds.size().magnitude() / 2.0).max(1e-3);
+        // Fit the bounding sphere into the narrower of the two fields of view
+        let half_fov = (lens.fov.to_radians() / 2.0).tan();
+        let half_fov = half_fov.atan().min((half_fov * lens.aspect_ratio).atan());
+        let distance = radius / half_fov.sin() * 1.1;
+        lens.near = (distance - radius).max(distance * 1e-3);
+        lens.far = distance + radius * 2.0;
+        Self {
+            center: bounds.center(),
+            distance,
+            elevation: 20.0,
+            start: 0.0,
+            revolutions: 1.0,
+            lens,
+        }
+    }
+}
+
+impl CameraAnimation for Turntable {
+    fn camera_at(&self, t: f32) -> Camera {
+        let angle = (self.start + 360.0 * self.revolutions * t).to_radians();
+        let elevation = self.elevation.to_radians();
+        let offset = Vec3f::new(
+            -angle.sin() * elevation.cos(),
+            elevation.sin(),
+            angle.cos() * elevation.cos(),
+        );
+        Camera {
+            position: self.center + offset * self.distance,
+            front: -offset,
+            up: Vec3f::UNIT_Y,
+            ..self.lens
+        }
+    }
+}
+
+///
+/// Camera following a Catmull-Rom spline through waypoints.
+///
+/// The camera looks along the path, or at `target` when it is set. Each segment between
+/// waypoints takes the same time.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Flythrough {
+    waypoints: Vec<Vec3f>,
+    /// Point the camera keeps looking at, `None` to look along the path
+    pub target: Option<Vec3f>,
+    /// Camera whose field of view, aspect ratio and clip planes are used
+    pub lens: Camera,
+}
+
+impl Flythrough {
+    /// Create new Flythrough visiting `waypoints` in order.
+    ///
+    /// # Panics
+    /// Panics if there are fewer than two waypoints.
+    pub fn new(waypoints: Vec<Vec3f>) -> Self {
+        assert!(waypoints.len() >= 2, "a flythrough needs at least two waypoints");
+        Self {
+            waypoints,
+            target: None,
+            lens: Camera::new(Vec3f::ZERO, -Vec3f::UNIT_Z, Vec3f::UNIT_Y),
+        }
+    }
+    /// Points the path passes through
+    pub fn waypoints(&self) -> &[Vec3f] {
+        &self.waypoints
+    }
+    /// Position on the path at time `t`
+    pub fn position(&self, t: f32) -> Vec3f {
+        let (segment, s) = self.segment(t);
+        let [p0, p1, p2, p3] = self.controls(segment);
+        let (s2, s3) = (s * s, s * s * s);
+        (p1 * 2.0 + (p2 - p0) * s + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * s2 + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * s3)
+            * 0.5
+    }
+    /// Direction of travel at time `t`, not normalized
+    pub fn tangent(&self, t: f32) -> Vec3f {
+        let (segment, s) = self.segment(t);
+        let [p0, p1, p2, p3] = self.controls(segment);
+        ((p2 - p0) + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * (2.0 * s) + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * (3.0 * s * s))
+            * 0.5
+    }
+    /// Segment index and the time within it
+    fn segment(&self, t: f32) -> (usize, f32) {
+        let segments = self.waypoints.len() - 1;
+        let scaled = t.clamp(0.0, 1.0) * segments as f32;
+        let segment = (scaled as usize).min(segments - 1);
+        (segment, scaled - segment as f32)
+    }
+    /// Control points of `segment`, repeating the end points at either end of the path
+    fn controls(&self, segment: usize) -> [Vec3f; 4] {
+        let last = self.waypoints.len() - 1;
+        [
+            self.waypoints[segment.saturating_sub(1)],
+            self.waypoints[segment],
+            self.waypoints[segment + 1],
+            self.waypoints[(segment + 2).min(last)],
+        ]
+    }
+}
+
+impl CameraAnimation for Flythrough {
+    fn camera_at(&self, t: f32) -> Camera {
+        let position = self.position(t);
+        let front = match self.target {
+            Some(target) => target - position,
+            None => self.tangent(t),
+        };
+        Camera {
+            position,
+            front,
+            up: Vec3f::UNIT_Y,
+            ..self.lens
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CameraAnimation, Flythrough, Turntable};
+    use crate::math::{Aabb, Vec3f, Vec4f};
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_turntable() {
+        let bounds = Aabb::from_points(vec![Vec3f::new(1.0, 1.0, 1.0), Vec3f::new(3.0, 3.0, 3.0)]);
+        let mut turntable = Turntable::around(&bounds);
+        turntable.elevation = 0.0;
+        let cameras = turntable.frames(4);
+        assert_eq!(cameras.len(), 4);
+        let center = Vec3f::new(2.0, 2.0, 2.0);
+        for camera in &cameras {
+            assert_approx_eq!((camera.position - center).magnitude(), turntable.distance, 1e-4);
+            // Every corner of the box stays in view
+            for &x in &[1.0, 3.0] {
+                for &z in &[1.0, 3.0] {
+                    let pixel = camera.project(Vec4f::from_parts(x, 3.0, z, 1.0), 64, 48).unwrap();
+                    assert!(pixel.x >= 0.0 && pixel.x <= 64.0 && pixel.y >= 0.0 && pixel.y <= 48.0);
+                }
+            }
+        }
+        assert_approx_eq!(cameras[0].position.z, 2.0 + turntable.distance, 1e-4);
+        assert_approx_eq!(cameras[1].position.x, 2.0 - turntable.distance, 1e-4);
+        assert_approx_eq!(cameras[2].position.z, 2.0 - turntable.distance, 1e-4);
+        // The last frame is one step before the start, so the loop is seamless
+        assert!((turntable.camera_at(1.0).position - cameras[0].position).magnitude() < 1e-3);
+    }
+
+    #[test]
+    fn test_flythrough() {
+        let waypoints = vec![Vec3f::new(0.0, 0.0, 0.0), Vec3f::new(4.0, 0.0, 0.0), Vec3f::new(4.0, 0.0, -4.0)];
+        let mut flythrough = Flythrough::new(waypoints);
+        assert_eq!(flythrough.position(0.0), Vec3f::new(0.0, 0.0, 0.0));
+        assert_eq!(flythrough.position(0.5), Vec3f::new(4.0, 0.0, 0.0));
+        assert_eq!(flythrough.position(1.0), Vec3f::new(4.0, 0.0, -4.0));
+        let start = flythrough.camera_at(0.0);
+        assert!(start.front.x > 0.0 && start.front.z.abs() < 1e-4);
+        let end = flythrough.camera_at(1.0);
+        assert!(end.front.z < 0.0);
+        flythrough.target = Some(Vec3f::new(0.0, 0.0, -4.0));
+        let camera = flythrough.camera_at(0.5);
+        assert!((camera.front.normalize() - Vec3f::new(-1.0, 0.0, -1.0).normalize()).magnitude() < 1e-5);
+        assert_eq!(flythrough.frames(10).len(), 10);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_flythrough_waypoints() {
+        Flythrough::new(vec![Vec3f::ZERO]);
+    }
+}
@@ -1,30 +1,310 @@
 //
 // Copyright 2017 Hans W. Uhlig.
 //
-// Licensed under the Apache License, Version 2.0 (the "License");
-// you may not use this file except in compliance with the License.
-// You may obtain a copy of the License at
-//
-//      http://www.apache.org/licenses/LICENSE-2.0
-//
-// Unless required by applicable law or agreed to in writing, software
-// distributed under the License is distributed on an "AS IS" BASIS,
-// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
-// See the License for the specific language governing permissions and
-// limitations under the License.
-//
-use crate::math::Mat4f;
-
+use crate::canvas::{Color, PixelBuffer};
+use crate::math::{ClipPlanes, Mat4f, Ray, Vec2f, Vec3f, Vec4f};
 
+///
+/// Perspective camera
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Camera {
-    projection: Mat4f,
-    position: Vec3f,
-    front: Vec3f,
-    up: Vec3f,
+    pub position: Vec3f,
+    pub front: Vec3f,
+    pub up: Vec3f,
+    /// Vertical field of view in degrees
+    pub fov: f32,
+    /// Height of the view in world units to project orthographically, for isometric and
+    /// other parallel views, `None` to project with perspective through `fov`. Deferred
+    /// lighting rebuilds positions from distances to `position`, so needs perspective.
+    pub orthographic: Option<f32>,
+    /// Shift of the image in fractions of its width and height, moving what is seen
+    /// without turning the camera. Tilt-shift lenses use it to frame tall buildings with
+    /// the camera level, keeping vertical lines parallel.
+    pub lens_shift: Vec2f,
+    /// Width divided by height of the image
+    pub aspect_ratio: f32,
+    pub near: f32,
+    pub far: f32,
+    /// Factor scene luminance in cd/m² is multiplied by to reach display range, see
+    /// `set_ev100` and `set_exposure_settings`
+    pub exposure: f32,
+    /// World space plane `(a, b, c, d)` replacing the near plane, so only points with
+    /// `a * x + b * y + c * z + d >= 0` are drawn. Used by mirrored reflection cameras to
+    /// cut away everything behind the mirror.
+    pub clip_plane: Option<Vec4f>,
+    /// User clip planes in world space, whose clipped side the Rasterizer discards like
+    /// that of `clip_plane`. The Raytracer takes its planes from `Scene::clip_planes`.
+    pub clip_planes: ClipPlanes,
 }
 
 impl Camera {
-    pub fn new() -> Camera {
+    /// Create new Camera at `position` looking along `front`
+    pub fn new(position: Vec3f, front: Vec3f, up: Vec3f) -> Camera {
+        Camera {
+            position,
+            front,
+            up,
+            fov: 60.0,
+            orthographic: None,
+            lens_shift: Vec2f::from_parts(0.0, 0.0),
+            aspect_ratio: 4.0 / 3.0,
+            near: 0.1,
+            far: 100.0,
+            exposure: 1.0,
+            clip_plane: None,
+            clip_planes: ClipPlanes::new(),
+        }
+    }
+    /// Match the aspect ratio to an image of `width` by `height` pixels
+    pub fn set_viewport(&mut self, width: usize, height: usize) {
+        if width > 0 && height > 0 {
+            self.aspect_ratio = width as f32 / height as f32;
+        }
+    }
+    /// Exposure value at ISO 100 matching the current exposure
+    pub fn ev100(&self) -> f32 {
+        (1.0 / (1.2 * self.exposure)).log2()
+    }
+    /// Expose for exposure value `ev100` at ISO 100, so that the brightest luminance
+    /// before the sensor saturates maps to 1.0
+    pub fn set_ev100(&mut self, ev100: f32) {
+        // Saturation based sensitivity, with 1.2 = 78 / (100 * 0.65) for a typical lens
+        self.exposure = 1.0 / (1.2 * ev100.exp2());
+    }
+    /// Expose like a physical camera with f-number `aperture`, `shutter` time in seconds
+    /// and `iso` sensitivity
+    pub fn set_exposure_settings(&mut self, aperture: f32, shutter: f32, iso: f32) {
+        self.set_ev100((aperture * aperture / shutter * 100.0 / iso).log2());
+    }
+    /// Scale a rendered color by the exposure
+    pub fn expose_color(&self, color: Color) -> Color {
+        color * self.exposure
+    }
+    /// Scale every Pixel of a rendered `buffer` by the exposure
+    pub fn expose<B: PixelBuffer<Pixel = Color>>(&self, buffer: &mut B) {
+        for y in 0..buffer.height() {
+            for x in 0..buffer.width() {
+                let color = buffer.get(x, y) * self.exposure;
+                buffer.set(x, y, color);
+            }
+        }
+    }
+    /// World to view space transform
+    pub fn view(&self) -> Mat4f {
+        Mat4f::look_to_rh(self.position, self.front, self.up)
+    }
+    /// View to clip space transform.
+    ///
+    /// With a `clip_plane` the near plane is tilted onto it, keeping the depth range but
+    /// clipping everything on its negative side.
+    pub fn projection(&self) -> Mat4f {
+        let mut projection = match self.orthographic {
+            Some(height) => Mat4f::orthographic_rh_gl(height, self.aspect_ratio, self.near, self.far),
+            None => Mat4f::perspective_rh_gl(self.fov, self.aspect_ratio, self.near, self.far),
+        };
+        if self.lens_shift != Vec2f::from_parts(0.0, 0.0) {
+            // Offset x and y in proportion to w, which is -z for perspective and 1 otherwise
+            let shift = self.lens_shift * 2.0;
+            let row = if self.orthographic.is_some() { 3 } else { 2 };
+            let sign = if self.orthographic.is_some() { -1.0 } else { 1.0 };
+            for (col, offset) in [(0, shift.x), (1, shift.y)] {
+                let mut column = projection.col(col);
+                column[row] += sign * offset;
+                projection.set_col(col, column);
+            }
+        }
+        if let Some(plane) = self.clip_plane {
+            // Lengyel's oblique near plane, the plane is moved into view space first
+            let inverse_view = self.view().invert_rigid();
+            let plane = Vec4f::from_parts(
+                inverse_view.row(0).dot(plane),
+                inverse_view.row(1).dot(plane),
+                inverse_view.row(2).dot(plane),
+                inverse_view.row(3).dot(plane),
+            );
+            if let Some(inverse) = projection.invert() {
+                let corner = Vec4f::from_parts(plane.x.signum(), plane.y.signum(), 1.0, 1.0);
+                let far = corner.transform_homogeneous(inverse);
+                let scaled = plane * (2.0 / plane.dot(far));
+                projection.set_col(2, scaled - projection.col(3));
+            }
+        }
+        projection
+    }
+    /// Check if `point` lies on the far side of the `clip_plane` or any of the `clip_planes`
+    pub fn is_clipped(&self, point: Vec3f) -> bool {
+        self.clip_plane.is_some_and(|plane| plane.dot(point.extend(1.0)) < 0.0) || self.clip_planes.is_clipped(point)
+    }
+    /// World to clip space transform
+    pub fn view_projection(&self) -> Mat4f {
+        self.view() * self.projection()
+    }
+    /// Unit direction of the ray through the center of Pixel (x, y) of a `width` by
+    /// `height` image.
+    ///
+    /// Orthographic cameras send every ray along `front`, from points spread over the
+    /// view, so use `ray` to trace them.
+    pub fn ray_direction(&self, x: usize, y: usize, width: usize, height: usize) -> Vec3f {
+        self.ray(x, y, width, height).direction
+    }
+    /// Ray through the center of Pixel (x, y) of a `width` by `height` image, with a unit
+    /// direction
+    pub fn ray(&self, x: usize, y: usize, width: usize, height: usize) -> Ray {
+        let forward = self.front.normalize();
+        let right = Vec3f::cross(forward, self.up).normalize();
+        let up = Vec3f::cross(right, forward);
+        // Position on the image plane from -1 to 1 before scaling to the view
+        let u = (x as f32 + 0.5) / width as f32 * 2.0 - 1.0 + self.lens_shift.x * 2.0;
+        let v = 1.0 - (y as f32 + 0.5) / height as f32 * 2.0 + self.lens_shift.y * 2.0;
+        match self.orthographic {
+            Some(height) => {
+                let offset = right * (u * height / 2.0 * self.aspect_ratio) + up * (v * height / 2.0);
+                Ray::new(self.position + offset, forward)
+            }
+            None => {
+                let tan_half = (self.fov.to_radians() / 2.0).tan();
+                let direction = forward + right * (u * tan_half * self.aspect_ratio) + up * (v * tan_half);
+                Ray::new(self.position, direction.normalize())
+            }
+        }
+    }
+    /// Position in a `width` by `height` image that `point` is seen at, `None` if it is
+    /// behind the camera.
+    ///
+    /// Directions, with `w` of 0.0, project to where infinitely distant points are seen.
+    pub fn project(&self, point: Vec4f, width: usize, height: usize) -> Option<Vec2f> {
+        let clip = point.transform_homogeneous(self.view_projection());
+        if clip.w <= 0.0 {
+            return None;
+        }
+        let (x, y) = (clip.x / clip.w, clip.y / clip.w);
+        Some(Vec2f::from_parts((x + 1.0) / 2.0 * width as f32, (1.0 - y) / 2.0 * height as f32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Camera;
+    use crate::canvas::{Canvas, Color};
+    use crate::math::{Vec2f, Vec3f, Vec4f};
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_set_viewport() {
+        let mut camera = Camera::new(
+            Vec3f::from_parts(0.0, 0.0, 0.0),
+            Vec3f::from_parts(0.0, 0.0, -1.0),
+            Vec3f::from_parts(0.0, 1.0, 0.0),
+        );
+        camera.set_viewport(1280, 720);
+        assert_eq!(camera.aspect_ratio, 16.0 / 9.0);
+        // Degenerate sizes, such as a minimized window, keep the last aspect ratio
+        camera.set_viewport(0, 0);
+        assert_eq!(camera.aspect_ratio, 16.0 / 9.0);
+        // A point on the right edge of the view lands on the right edge of clip space
+        let half_width = (30.0f32).to_radians().tan() * 16.0 / 9.0;
+        let clip = Vec3f::from_parts(half_width, 0.0, -1.0) * camera.view_projection();
+        assert!((clip.x - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_ray_project() {
+        let mut camera = Camera::new(
+            Vec3f::from_parts(1.0, 2.0, 3.0),
+            Vec3f::from_parts(1.0, 0.0, -1.0),
+            Vec3f::from_parts(0.0, 1.0, 0.0),
+        );
+        camera.set_viewport(64, 32);
+        let direction = camera.ray_direction(10, 20, 64, 32);
+        let point = camera.position + direction * 5.0;
+        let pixel = camera.project(point.extend(1.0), 64, 32).unwrap();
+        assert!((pixel.x - 10.5).abs() < 1e-3 && (pixel.y - 20.5).abs() < 1e-3);
+        let pixel = camera.project(direction.extend(0.0), 64, 32).unwrap();
+        assert!((pixel.x - 10.5).abs() < 1e-3 && (pixel.y - 20.5).abs() < 1e-3);
+        let behind = camera.position - direction;
+        assert!(camera.project(behind.extend(1.0), 64, 32).is_none());
+    }
 
+    #[test]
+    fn test_orthographic() {
+        let mut camera = Camera::new(Vec3f::from_parts(1.0, 0.0, 5.0), -Vec3f::UNIT_Z, Vec3f::UNIT_Y);
+        camera.orthographic = Some(4.0);
+        camera.set_viewport(64, 32);
+        // The view is 8 by 4 units and every ray runs straight ahead
+        let ray = camera.ray(0, 0, 64, 32);
+        assert_eq!(ray.direction, -Vec3f::UNIT_Z);
+        assert_approx_eq!(ray.origin.x, 1.0 - 4.0 + 0.0625, 1e-5);
+        assert_approx_eq!(ray.origin.y, 2.0 - 0.0625, 1e-5);
+        let pixel = camera.project(ray.at(3.0).extend(1.0), 64, 32).unwrap();
+        assert!((pixel - Vec2f::from_parts(0.5, 0.5)).magnitude() < 1e-3);
+        // Distance does not change the size of what is seen
+        let near = camera.project(Vec3f::from_parts(2.0, 1.0, 0.0).extend(1.0), 64, 32).unwrap();
+        let far = camera.project(Vec3f::from_parts(2.0, 1.0, -50.0).extend(1.0), 64, 32).unwrap();
+        assert!((near - far).magnitude() < 1e-3);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_lens_shift() {
+        let mut camera = Camera::new(Vec3f::ZERO, -Vec3f::UNIT_Z, Vec3f::UNIT_Y);
+        camera.set_viewport(64, 64);
+        camera.lens_shift = Vec2f::from_parts(0.0, 0.25);
+        // The camera stays level but sees higher up, with vertical lines kept upright
+        let ray = camera.ray(32, 32, 64, 64);
+        assert!(ray.direction.y > 0.0);
+        let pixel = camera.project(ray.at(10.0).extend(1.0), 64, 64).unwrap();
+        assert!((pixel - Vec2f::from_parts(32.5, 32.5)).magnitude() < 1e-3);
+        let bottom = camera.project(Vec3f::from_parts(1.0, 0.0, -5.0).extend(1.0), 64, 64).unwrap();
+        let top = camera.project(Vec3f::from_parts(1.0, 3.0, -5.0).extend(1.0), 64, 64).unwrap();
+        assert_approx_eq!(bottom.x, top.x, 1e-3);
+        camera.orthographic = Some(2.0);
+        let ray = camera.ray(32, 32, 64, 64);
+        assert_approx_eq!(ray.origin.y, 0.5 - 1.0 / 64.0, 1e-5);
+        let pixel = camera.project(ray.at(10.0).extend(1.0), 64, 64).unwrap();
+        assert!((pixel - Vec2f::from_parts(32.5, 32.5)).magnitude() < 1e-3);
+    }
+
+    #[test]
+    fn test_clip_plane() {
+        let mut camera = Camera::new(Vec3f::from_parts(0.0, -1.0, 4.0), -Vec3f::UNIT_Z, Vec3f::UNIT_Y);
+        let plane = Vec4f::from_parts(0.0, 1.0, 0.0, 0.0);
+        let depth = |camera: &Camera, point: Vec3f| {
+            let clip = point.extend(1.0).transform_homogeneous(camera.view_projection());
+            clip.z / clip.w
+        };
+        let point = Vec3f::from_parts(0.5, 0.0, 0.0);
+        assert!(depth(&camera, point) > -1.0);
+        assert!(!camera.is_clipped(point));
+        camera.clip_plane = Some(plane);
+        // Points on the plane land on the near plane, those below it in front of it
+        assert_approx_eq!(depth(&camera, point), -1.0, 1e-3);
+        assert!(depth(&camera, Vec3f::from_parts(0.0, -0.5, 0.0)) < -1.0);
+        assert!(depth(&camera, Vec3f::from_parts(0.0, 0.5, 0.0)) > -1.0);
+        assert!(camera.is_clipped(Vec3f::from_parts(0.0, -0.5, 0.0)));
+        // Only depth changes
+        let pixel = camera.project(Vec3f::from_parts(0.3, 0.2, 1.0).extend(1.0), 64, 48).unwrap();
+        camera.clip_plane = None;
+        let unclipped = camera.project(Vec3f::from_parts(0.3, 0.2, 1.0).extend(1.0), 64, 48).unwrap();
+        assert!((pixel - unclipped).magnitude() < 1e-3);
+    }
+
+    #[test]
+    fn test_exposure() {
+        let mut camera = Camera::new(Vec3f::ZERO, -Vec3f::UNIT_Z, Vec3f::UNIT_Y);
+        assert_eq!(camera.exposure, 1.0);
+        // Sunny 16: f/16 at 1/100s and ISO 100 is about EV 15
+        camera.set_exposure_settings(16.0, 1.0 / 100.0, 100.0);
+        assert_approx_eq!(camera.ev100(), (256.0f32 * 100.0).log2(), 1e-4);
+        assert_approx_eq!(camera.exposure, 1.0 / (1.2 * 25600.0), 1e-9);
+        // Doubling the sensitivity doubles the exposure
+        let exposure = camera.exposure;
+        camera.set_exposure_settings(16.0, 1.0 / 100.0, 200.0);
+        assert_approx_eq!(camera.exposure, exposure * 2.0, 1e-9);
+        camera.set_ev100(0.0);
+        let mut canvas = Canvas::new(2, 1);
+        canvas.fill(Color::new(1.2, 0.6, 0.0));
+        camera.expose(&mut canvas);
+        assert_eq!(canvas.get(1, 0), Color::new(1.0, 0.5, 0.0));
+        assert_eq!(camera.expose_color(Color::new(2.4, 0.0, 0.0)), Color::new(2.0, 0.0, 0.0));
+    }
+}
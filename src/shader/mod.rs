@@ -1,20 +1,13 @@
 //
 // Copyright 2017 Hans W. Uhlig.
 //
-// Licensed under the Apache License, Version 2.0 (the "License");
-// you may not use this file except in compliance with the License.
-// You may obtain a copy of the License at
-//
-//      http://www.apache.org/licenses/LICENSE-2.0
-//
-// Unless required by applicable law or agreed to in writing, software
-// distributed under the License is distributed on an "AS IS" BASIS,
-// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
-// See the License for the specific language governing permissions and
-// limitations under the License.
-//
-
 
+mod animation;
+mod camera;
 mod fragment;
 mod pipeline;
-mod vertex;
\ No newline at end of file
+mod vertex;
+
+pub use self::animation::{CameraAnimation, Flythrough, Turntable};
+pub use self::camera::Camera;
+pub use self::vertex::{IdentityVertexShader, StandardVertexShader, VertexShader};
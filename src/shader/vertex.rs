@@ -1,31 +1,33 @@
 //
 // Copyright 2017 Hans W. Uhlig.
 //
-// Licensed under the Apache License, Version 2.0 (the "License");
-// you may not use this file except in compliance with the License.
-// You may obtain a copy of the License at
-//
-//      http://www.apache.org/licenses/LICENSE-2.0
-//
-// Unless required by applicable law or agreed to in writing, software
-// distributed under the License is distributed on an "AS IS" BASIS,
-// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
-// See the License for the specific language governing permissions and
-// limitations under the License.
-//
+use crate::math::{Mat4f, Vec3f};
 
-trait VertexShader {
-    fn compute(input: Vec3f) -> Vec3f;
+/// Transforms vertex positions into clip space
+pub trait VertexShader {
+    fn compute(&self, input: Vec3f) -> Vec3f;
 }
 
 pub struct IdentityVertexShader;
 
 impl VertexShader for IdentityVertexShader {
-    fn compute(input: Vec3f) -> Vec3f { input }
+    fn compute(&self, input: Vec3f) -> Vec3f { input }
 }
 
 pub struct StandardVertexShader {
     projection: Mat4f,
     world: Mat4f,
     model: Mat4f
-}
\ No newline at end of file
+}
+
+impl StandardVertexShader {
+    pub fn new(model: Mat4f, world: Mat4f, projection: Mat4f) -> StandardVertexShader {
+        StandardVertexShader { projection, world, model }
+    }
+}
+
+impl VertexShader for StandardVertexShader {
+    fn compute(&self, input: Vec3f) -> Vec3f {
+        input * self.model * self.world * self.projection
+    }
+}
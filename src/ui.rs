@@ -0,0 +1,428 @@
+//
+// Copyright 2020 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Minimal immediate mode debug UI drawn with the crate's own rasterizer.
+//!
+//! Widgets are declared every frame and drawn straight into the frame buffer, returning
+//! whether the user changed them. Nothing is retained between frames except which widget
+//! is being dragged and the frame time history.
+//!
+//! ```
+//! use softrender::canvas::Canvas;
+//! use softrender::ui::{Ui, UiInput};
+//!
+//! let mut canvas = Canvas::new(320, 240);
+//! let mut ui = Ui::new();
+//! let mut exposure = 1.0;
+//! let mut wireframe = false;
+//! let mut frame = ui.frame(&mut canvas, UiInput::default());
+//! frame.label("Settings");
+//! frame.slider("Exposure", &mut exposure, 0.0, 4.0);
+//! frame.checkbox("Wireframe", &mut wireframe);
+//! ```
+
+use crate::canvas::{Color, PixelBuffer};
+use crate::input::{InputState, Key};
+use crate::profile::PipelineStats;
+use crate::Rasterizer;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+
+/// Number of frame times kept for the FPS graph
+const HISTORY: usize = 120;
+
+/// Pointer state fed to the UI each frame
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct UiInput {
+    pub mouse_x: f32,
+    pub mouse_y: f32,
+    pub mouse_down: bool,
+}
+
+///
+/// Immediate mode UI state kept between frames
+///
+pub struct Ui {
+    /// Font scale, each font pixel is drawn as a `scale` by `scale` block
+    pub scale: usize,
+    /// Width in pixels of sliders and the FPS graph
+    pub width: usize,
+    /// Top left corner of the first widget
+    pub origin: (isize, isize),
+    pub text_color: Color,
+    pub background_color: Color,
+    pub accent_color: Color,
+    was_down: bool,
+    active: Option<u64>,
+    frame_times: VecDeque<f32>,
+}
+
+impl Ui {
+    /// Create new Ui in the top left corner
+    pub fn new() -> Self {
+        Self {
+            scale: 2,
+            width: 160,
+            origin: (8, 8),
+            text_color: Color::white(),
+            background_color: Color::new(0.2, 0.2, 0.2),
+            accent_color: Color::new(0.3, 0.5, 0.9),
+            was_down: false,
+            active: None,
+            frame_times: VecDeque::with_capacity(HISTORY),
+        }
+    }
+    /// Record how long the last frame took in seconds, for `UiFrame::fps_graph`
+    pub fn record_frame_time(&mut self, seconds: f32) {
+        if self.frame_times.len() == HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(seconds);
+    }
+    /// Average frames per second over the recorded history
+    pub fn fps(&self) -> f32 {
+        let total: f32 = self.frame_times.iter().sum();
+        if total > 0.0 {
+            self.frame_times.len() as f32 / total
+        } else {
+            0.0
+        }
+    }
+    /// Start laying out widgets for this frame into `buffer`
+    pub fn frame<'a, B: PixelBuffer<Pixel = Color>>(&'a mut self, buffer: &'a mut B, input: UiInput) -> UiFrame<'a, B> {
+        let pressed = input.mouse_down && !self.was_down;
+        self.was_down = input.mouse_down;
+        if !input.mouse_down {
+            self.active = None;
+        }
+        let cursor = self.origin;
+        UiFrame {
+            ui: self,
+            buffer,
+            input,
+            pressed,
+            cursor,
+        }
+    }
+}
+
+impl Default for Ui {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Widgets of a single frame, laid out top to bottom
+pub struct UiFrame<'a, B: PixelBuffer<Pixel = Color>> {
+    ui: &'a mut Ui,
+    buffer: &'a mut B,
+    input: UiInput,
+    pressed: bool,
+    cursor: (isize, isize),
+}
+
+impl<'a, B: PixelBuffer<Pixel = Color>> UiFrame<'a, B> {
+    /// Draw a line of text
+    pub fn label(&mut self, text: &str) {
+        let (x, y) = self.cursor;
+        Rasterizer::draw_text(self.buffer, x, y, text, self.ui.scale, self.ui.text_color);
+        self.next_row();
+    }
+    /// Draw a checkbox, returns true if it was toggled this frame
+    pub fn checkbox(&mut self, label: &str, value: &mut bool) -> bool {
+        let (x, y) = self.cursor;
+        let scale = self.ui.scale;
+        let size = self.row_height() - 2 * scale;
+        let (text_width, _) = Rasterizer::text_size(label, scale);
+        let clicked = self.pressed && self.hovered(x, y, size + 2 * scale + text_width, size);
+        if clicked {
+            *value = !*value;
+        }
+        Rasterizer::fill_rect(self.buffer, x, y, size, size, self.ui.text_color);
+        Rasterizer::fill_rect(
+            self.buffer,
+            x + scale as isize,
+            y + scale as isize,
+            size - 2 * scale,
+            size - 2 * scale,
+            if *value { self.ui.accent_color } else { self.ui.background_color },
+        );
+        let text_x = x + (size + 2 * scale) as isize;
+        Rasterizer::draw_text(self.buffer, text_x, y + scale as isize, label, scale, self.ui.text_color);
+        self.next_row();
+        clicked
+    }
+    /// Draw a slider for `value` between `min` and `max`, returns true if it was dragged
+    /// this frame
+    pub fn slider(&mut self, label: &str, value: &mut f32, min: f32, max: f32) -> bool {
+        let id = widget_id(label);
+        let (x, y) = self.cursor;
+        let scale = self.ui.scale;
+        let width = self.ui.width;
+        let height = self.row_height() - 2 * scale;
+        if self.pressed && self.hovered(x, y, width, height) {
+            self.ui.active = Some(id);
+        }
+        let mut changed = false;
+        if self.ui.active == Some(id) && max > min {
+            let t = ((self.input.mouse_x - x as f32) / width as f32).clamp(0.0, 1.0);
+            let dragged = min + t * (max - min);
+            changed = dragged != *value;
+            *value = dragged;
+        }
+        let t = if max > min { ((*value - min) / (max - min)).clamp(0.0, 1.0) } else { 0.0 };
+        Rasterizer::fill_rect(self.buffer, x, y, width, height, self.ui.background_color);
+        Rasterizer::fill_rect(self.buffer, x, y, (t * width as f32) as usize, height, self.ui.accent_color);
+        let text = format!("{}: {:.2}", label, value);
+        Rasterizer::draw_text(self.buffer, x + scale as isize, y + scale as isize, &text, scale, self.ui.text_color);
+        self.next_row();
+        changed
+    }
+    /// Draw the recorded frame times as a bar graph with the average FPS
+    pub fn fps_graph(&mut self) {
+        let fps = self.ui.fps();
+        self.label(&format!("FPS {:.1}", fps));
+        let (x, y) = self.cursor;
+        let width = self.ui.width;
+        let height = self.row_height() * 2;
+        Rasterizer::fill_rect(self.buffer, x, y, width, height, self.ui.background_color);
+        // Scale so the slowest recorded frame fills the graph
+        let slowest = self.ui.frame_times.iter().cloned().fold(0.0f32, f32::max);
+        if slowest > 0.0 {
+            let bar = (width / HISTORY).max(1);
+            let times: Vec<f32> = self.ui.frame_times.iter().cloned().collect();
+            for (index, time) in times.iter().enumerate() {
+                let bar_height = ((time / slowest) * height as f32) as usize;
+                let bar_x = x + (index * bar) as isize;
+                let bar_y = y + (height - bar_height) as isize;
+                Rasterizer::fill_rect(self.buffer, bar_x, bar_y, bar, bar_height, self.ui.accent_color);
+            }
+        }
+        self.cursor.1 += (height + self.ui.scale * 2) as isize;
+    }
+    fn row_height(&self) -> usize {
+        9 * self.ui.scale
+    }
+    fn next_row(&mut self) {
+        self.cursor.1 += self.row_height() as isize;
+    }
+    fn hovered(&self, x: isize, y: isize, width: usize, height: usize) -> bool {
+        let (mx, my) = (self.input.mouse_x, self.input.mouse_y);
+        mx >= x as f32 && my >= y as f32 && mx < (x + width as isize) as f32 && my < (y + height as isize) as f32
+    }
+}
+
+///
+/// Corner overlay of live pipeline statistics, shown and hidden with a key.
+///
+/// ```
+/// use softrender::canvas::Canvas;
+/// use softrender::input::InputState;
+/// use softrender::profile;
+/// use softrender::ui::StatsOverlay;
+///
+/// let mut canvas = Canvas::new(320, 240);
+/// let mut overlay = StatsOverlay::new();
+/// let input = InputState::new();
+/// // Each frame, after drawing the scene
+/// overlay.handle(&input);
+/// overlay.draw(&mut canvas, 60.0, &profile::take_stats());
+/// ```
+///
+pub struct StatsOverlay {
+    /// Draw the overlay
+    pub visible: bool,
+    /// Key toggling `visible`
+    pub toggle: Key,
+    /// Font scale, each font pixel is drawn as a `scale` by `scale` block
+    pub scale: usize,
+    /// Top left corner of the overlay
+    pub origin: (isize, isize),
+    pub text_color: Color,
+    pub background_color: Color,
+}
+
+impl StatsOverlay {
+    /// Create new hidden StatsOverlay toggled with F3
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            toggle: Key::F3,
+            scale: 1,
+            origin: (4, 4),
+            text_color: Color::new(0.9, 0.9, 0.2),
+            background_color: Color::black(),
+        }
+    }
+    /// Toggle the overlay if the toggle key was pressed this frame
+    pub fn handle(&mut self, input: &InputState) {
+        if input.was_key_pressed(self.toggle) {
+            self.visible = !self.visible;
+        }
+    }
+    /// Lines of text shown for a frame rate of `fps` and the work in `stats`
+    pub fn lines(fps: f32, stats: &PipelineStats) -> Vec<String> {
+        vec![
+            format!("FPS {:.1}", fps),
+            format!("TRIS {} OF {}", stats.triangles_drawn, stats.triangles_submitted),
+            format!("CULLED {} FRUSTUM {} SMALL", stats.frustum_culled, stats.small_culled),
+            format!("PIXELS {}", stats.pixels_shaded),
+            format!("DEPTH REJECTED {}", stats.depth_rejected),
+            format!("DRAWS {} IN {} BATCHES", stats.draw_calls, stats.batches),
+        ]
+    }
+    /// Draw the overlay into `buffer` if it is visible
+    pub fn draw<B: PixelBuffer<Pixel = Color>>(&self, buffer: &mut B, fps: f32, stats: &PipelineStats) {
+        if !self.visible {
+            return;
+        }
+        let lines = StatsOverlay::lines(fps, stats);
+        let line_height = 9 * self.scale;
+        let width = lines.iter().map(|line| Rasterizer::text_size(line, self.scale).0).max().unwrap_or(0);
+        let padding = 2 * self.scale;
+        let (x, y) = self.origin;
+        let height = lines.len() * line_height;
+        Rasterizer::fill_rect(buffer, x, y, width + 2 * padding, height + 2 * padding, self.background_color);
+        for (index, line) in lines.iter().enumerate() {
+            let line_y = y + (padding + index * line_height) as isize;
+            Rasterizer::draw_text(buffer, x + padding as isize, line_y, line, self.scale, self.text_color);
+        }
+    }
+}
+
+impl Default for StatsOverlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn widget_id(label: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    label.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StatsOverlay, Ui, UiInput};
+    use crate::canvas::{Canvas, Color};
+    use crate::input::{Event, InputState, Key};
+    use crate::profile::PipelineStats;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn mouse(x: f32, y: f32, down: bool) -> UiInput {
+        UiInput {
+            mouse_x: x,
+            mouse_y: y,
+            mouse_down: down,
+        }
+    }
+
+    #[test]
+    fn test_checkbox() {
+        let mut canvas = Canvas::new(200, 100);
+        let mut ui = Ui::new();
+        let mut checked = false;
+        // Row one is the checkbox at (8, 8)
+        assert!(ui.frame(&mut canvas, mouse(12.0, 12.0, true)).checkbox("Wire", &mut checked));
+        assert!(checked);
+        // Holding the button does not toggle again
+        assert!(!ui.frame(&mut canvas, mouse(12.0, 12.0, true)).checkbox("Wire", &mut checked));
+        assert!(checked);
+        ui.frame(&mut canvas, mouse(12.0, 12.0, false)).checkbox("Wire", &mut checked);
+        assert!(ui.frame(&mut canvas, mouse(12.0, 12.0, true)).checkbox("Wire", &mut checked));
+        assert!(!checked);
+        // Clicking elsewhere does nothing
+        ui.frame(&mut canvas, mouse(150.0, 80.0, false));
+        assert!(!ui.frame(&mut canvas, mouse(150.0, 80.0, true)).checkbox("Wire", &mut checked));
+    }
+
+    #[test]
+    fn test_slider() {
+        let mut canvas = Canvas::new(200, 100);
+        let mut ui = Ui::new();
+        let mut value = 0.0;
+        {
+            let mut frame = ui.frame(&mut canvas, mouse(48.0, 30.0, true));
+            frame.label("Settings");
+            assert!(frame.slider("Value", &mut value, 0.0, 4.0));
+        }
+        assert_approx_eq!(value, 1.0);
+        // Dragging keeps working outside the slider until the button is released
+        ui.frame(&mut canvas, mouse(500.0, 90.0, true)).slider("Value", &mut value, 0.0, 4.0);
+        assert_approx_eq!(value, 4.0);
+        ui.frame(&mut canvas, mouse(8.0, 90.0, false)).slider("Value", &mut value, 0.0, 4.0);
+        assert_approx_eq!(value, 4.0);
+        // The filled part of the track is drawn in the accent color
+        let mut canvas = Canvas::new(200, 100);
+        value = 2.0;
+        ui.frame(&mut canvas, UiInput::default()).slider("", &mut value, 0.0, 4.0);
+        assert_eq!(canvas.get(80, 20), ui.accent_color);
+        assert_eq!(canvas.get(120, 20), ui.background_color);
+    }
+
+    #[test]
+    fn test_stats_overlay() {
+        let stats = PipelineStats {
+            triangles_submitted: 12,
+            triangles_drawn: 5,
+            frustum_culled: 6,
+            small_culled: 1,
+            pixels_shaded: 300,
+            depth_rejected: 42,
+            draw_calls: 8,
+            batches: 2,
+            state_changes: 3,
+        };
+        let lines = StatsOverlay::lines(59.94, &stats);
+        assert_eq!(lines[0], "FPS 59.9");
+        assert_eq!(lines[1], "TRIS 5 OF 12");
+        assert_eq!(lines[2], "CULLED 6 FRUSTUM 1 SMALL");
+        assert_eq!(lines[5], "DRAWS 8 IN 2 BATCHES");
+        assert_eq!(stats.draws_per_batch(), 4.0);
+        let mut overlay = StatsOverlay::new();
+        let mut canvas = Canvas::new(200, 100);
+        overlay.draw(&mut canvas, 60.0, &stats);
+        assert!(canvas.pixels().all(|(_, _, c)| c == Color::black()));
+        let mut input = InputState::new();
+        input.handle(&Event::KeyDown(Key::F3));
+        overlay.handle(&input);
+        assert!(overlay.visible);
+        overlay.background_color = Color::bright_blue();
+        overlay.draw(&mut canvas, 60.0, &stats);
+        assert_eq!(canvas.get(4, 4), Color::bright_blue());
+        assert!(canvas.pixels().any(|(_, _, c)| c == overlay.text_color));
+        // Held keys do not toggle again
+        input.begin_frame();
+        overlay.handle(&input);
+        assert!(overlay.visible);
+    }
+
+    #[test]
+    fn test_fps_graph() {
+        let mut canvas = Canvas::new(200, 100);
+        let mut ui = Ui::new();
+        assert_eq!(ui.fps(), 0.0);
+        for _ in 0..200 {
+            ui.record_frame_time(0.02);
+        }
+        assert_approx_eq!(ui.fps(), 50.0, 1e-3);
+        ui.frame(&mut canvas, UiInput::default()).fps_graph();
+        assert_eq!(canvas.get(8, 30), ui.accent_color);
+        assert!(canvas.pixels().any(|(_, _, c)| c == Color::white()));
+    }
+}